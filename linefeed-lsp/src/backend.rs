@@ -1,15 +1,23 @@
 use std::collections::HashMap;
 
+use linefeed::config::Config;
 use tokio::sync::Mutex;
 use tower_lsp::Client;
 
+use crate::document::Document;
+use crate::project::Project;
+
 /// The LSP backend managing server state
 #[derive(Debug)]
 pub struct Backend {
     /// LSP client for communication
     pub client: Client,
-    /// Cache of file contents, keyed by URI
-    pub sources: Mutex<HashMap<String, String>>,
+    /// Contents of the open files, keyed by URI
+    pub sources: Mutex<HashMap<String, Document>>,
+    /// Configuration applying to each open file, keyed by URI
+    pub configs: Mutex<HashMap<String, Config>>,
+    /// Index of the files in the workspace folders, for resolving names across imports
+    pub project: Mutex<Project>,
 }
 
 impl Backend {
@@ -18,6 +26,8 @@ impl Backend {
         Self {
             client,
             sources: Mutex::new(HashMap::new()),
+            configs: Mutex::new(HashMap::new()),
+            project: Mutex::new(Project::default()),
         }
     }
 }
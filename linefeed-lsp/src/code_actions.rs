@@ -0,0 +1,661 @@
+use std::collections::{HashMap, HashSet};
+
+use linefeed::chumsky::Parser as _;
+use linefeed::compiler::analysis;
+use linefeed::docs::{self, BuiltinKind};
+use linefeed::grammar::ast::{AstValue, BinaryOp, Expr, Pattern, Span, Spanned};
+use linefeed::grammar::lexer::Token;
+use linefeed::grammar::snippets::for_each_child;
+use tower_lsp::lsp_types::*;
+
+use crate::semantic_tokens::{position_to_byte_offset, span_to_range};
+
+/// Refactorings of the selected code: extracting an expression into a variable assigned right
+/// above its statement, and extracting an expression or statements into a function, taking the
+/// variables they read as parameters.
+pub fn extract_actions(source: &str, uri: &Url, range: Range) -> Vec<CodeActionOrCommand> {
+    let Some(tokens) = linefeed::grammar::lexer::lexer()
+        .parse(source)
+        .into_output()
+    else {
+        return vec![];
+    };
+
+    let Ok(mut ast) = linefeed::parse_tokens(source, &tokens) else {
+        return vec![];
+    };
+
+    let selection = trim_selection(
+        source,
+        position_to_byte_offset(source, range.start),
+        position_to_byte_offset(source, range.end),
+    );
+    if selection.start >= selection.end {
+        return vec![];
+    }
+
+    let root = Context {
+        statement: ast.span(),
+        top_level: ast.span(),
+        at_top: true,
+        movable: true,
+    };
+    let Some(mut selected) = find_selection(&mut ast, selection, root) else {
+        return vec![];
+    };
+
+    if escapes(&mut selected.expr, false) {
+        return vec![];
+    }
+
+    let mut taken: HashSet<&str> = tokens
+        .iter()
+        .filter_map(|Spanned(token, _)| match token {
+            Token::Ident(name) => Some(*name),
+            _ => None,
+        })
+        .collect();
+    let builtins = docs::builtins();
+    taken.extend(builtins.iter().map(|builtin| builtin.name));
+
+    let mut actions = Vec::new();
+
+    if let Some(edit) = extract_variable(source, &selected, &taken) {
+        actions.push(action(
+            "Extract into variable",
+            CodeActionKind::REFACTOR_EXTRACT,
+            uri,
+            edit,
+        ));
+    }
+
+    let mut program_names = Names::default();
+    program_names.visit(&mut ast);
+    let is_builtin = |name: &str| {
+        builtins
+            .iter()
+            .any(|builtin| builtin.name == name && builtin.kind != BuiltinKind::Method)
+    };
+
+    let mut selected_names = Names::default();
+    selected_names.visit(&mut selected.expr);
+    let assigned_used_elsewhere = program_names.reads.iter().any(|(name, span)| {
+        selected_names.assigned.contains(name)
+            && (span.end <= selected.expr.span().start || span.start >= selected.expr.span().end)
+    });
+
+    if !assigned_used_elsewhere {
+        let params = analysis::free_variables(&mut selected.expr)
+            .into_iter()
+            .filter(|name| {
+                !is_builtin(name)
+                    || program_names.assigned.contains(name.as_str())
+                    || program_names.args.contains(name.as_str())
+            })
+            .collect::<Vec<_>>();
+        let edit = extract_function(source, &selected, &params, &taken);
+        actions.push(action(
+            "Extract into function",
+            CodeActionKind::REFACTOR_EXTRACT,
+            uri,
+            edit,
+        ));
+    }
+
+    actions
+}
+
+/// Rewrites of the statements at `range`: a `for` loop that only appends to a list assigned empty
+/// right before it becomes a list comprehension, keeping an `if` around the append as its filter.
+pub fn rewrite_actions(source: &str, uri: &Url, range: Range) -> Vec<CodeActionOrCommand> {
+    let Some(tokens) = linefeed::grammar::lexer::lexer()
+        .parse(source)
+        .into_output()
+    else {
+        return vec![];
+    };
+
+    let Ok(mut ast) = linefeed::parse_tokens(source, &tokens) else {
+        return vec![];
+    };
+
+    let offset = position_to_byte_offset(source, range.start);
+    let Some((span, comprehension)) = find_appending_loop(source, &mut ast, offset) else {
+        return vec![];
+    };
+
+    // A comprehension takes fewer kinds of expressions than a loop, e.g. no blocks, so the rewrite
+    // is only offered if it still parses
+    let rewritten = format!(
+        "{}{comprehension}{}",
+        &source[..span.start],
+        &source[span.end..]
+    );
+    let parses = linefeed::grammar::lexer::lexer()
+        .parse(rewritten.as_str())
+        .into_output()
+        .is_some_and(|tokens| linefeed::parse_tokens(&rewritten, &tokens).is_ok());
+    if !parses {
+        return vec![];
+    }
+
+    let edit = TextEdit {
+        range: span_to_range(source, span),
+        new_text: comprehension,
+    };
+    vec![action(
+        "Convert to list comprehension",
+        CodeActionKind::REFACTOR_REWRITE,
+        uri,
+        edit,
+    )]
+}
+
+// Finds `xs = []; for x in items { xs.append(value) }` around `offset`, and the comprehension
+// replacing it
+fn find_appending_loop(
+    source: &str,
+    expr: &mut Spanned<Expr>,
+    offset: usize,
+) -> Option<(Span, String)> {
+    if let Expr::Sequence(_) = &expr.0 {
+        let statements = flatten_sequence(expr);
+        let found = statements.windows(2).find_map(|pair| {
+            let span = Span::new(pair[0].span().start, pair[1].span().end);
+            if offset < span.start || offset > span.end {
+                return None;
+            }
+            let comprehension = appending_loop(source, pair[0], pair[1])?;
+            Some((span, comprehension))
+        });
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    let mut found = None;
+    for_each_child(expr, |child| {
+        let contains = child.span().start <= offset && offset <= child.span().end;
+        if found.is_none() && contains {
+            found = find_appending_loop(source, child, offset);
+        }
+    });
+    found
+}
+
+fn appending_loop(
+    source: &str,
+    assign: &Spanned<Expr>,
+    for_loop: &Spanned<Expr>,
+) -> Option<String> {
+    let (Expr::Assign(pattern, empty), Expr::For(loop_var, iterable, body)) =
+        (&assign.0, &for_loop.0)
+    else {
+        return None;
+    };
+    let (Pattern::Ident(list), Expr::List(items)) = (&pattern.0, &empty.0) else {
+        return None;
+    };
+    if !items.is_empty() {
+        return None;
+    }
+
+    let (cond, append) = match &only_statement(body).0 {
+        // The `else` left out of an `if` is parsed as `null`
+        Expr::If(cond, then, otherwise)
+            if matches!(otherwise.0, Expr::Value(AstValue::Null))
+                && disjoint(cond.span(), then.span()) =>
+        {
+            (Some(cond.as_ref()), only_statement(then))
+        }
+        _ => (None, only_statement(body)),
+    };
+    let Expr::MethodCall(receiver, "append", args) = &append.0 else {
+        return None;
+    };
+    let (Expr::Local(target), [value]) = (&receiver.0, args.as_slice()) else {
+        return None;
+    };
+    if target != list {
+        return None;
+    }
+
+    // The list is only assigned once the comprehension is done, so it can't be read while building
+    let mut parts = vec![value.clone(), iterable.as_ref().clone()];
+    parts.extend(cond.cloned());
+    for part in &mut parts {
+        let mut names = Names::default();
+        names.visit(part);
+        if names.reads.iter().any(|(name, _)| name == list) || escapes(part, false) {
+            return None;
+        }
+    }
+
+    let text = |expr: &Spanned<Expr>| &source[expr.span().into_range()];
+    let filter = cond
+        .map(|cond| format!(" if {}", text(cond)))
+        .unwrap_or_default();
+    Some(format!(
+        "{list} = [{} for {} in {}{filter}]",
+        text(value),
+        &source[loop_var.span().into_range()],
+        text(iterable),
+    ))
+}
+
+// The statement a block consists of, looking through the sequence a trailing `;` makes
+fn only_statement<'a, 'src>(expr: &'a Spanned<Expr<'src>>) -> &'a Spanned<Expr<'src>> {
+    match &expr.0 {
+        Expr::Block(inner) => only_statement(inner),
+        Expr::Sequence(items) if items.len() == 1 => only_statement(&items[0]),
+        _ => expr,
+    }
+}
+
+// Whether two expressions are written apart, which the condition and body of `x if cond` and
+// `if cond { x }` are, but not those of `x unless cond`
+fn disjoint(a: Span, b: Span) -> bool {
+    a.end <= b.start || b.end <= a.start
+}
+
+fn action(title: &str, kind: CodeActionKind, uri: &Url, edit: TextEdit) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.to_string(),
+        kind: Some(kind),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Assigns the selected expression to a variable in a new statement above the one it is in. Only
+/// offered when the expression can be evaluated there with the same result.
+fn extract_variable(source: &str, selected: &Selected, taken: &HashSet<&str>) -> Option<TextEdit> {
+    let span = selected.expr.span();
+    let is_value = !matches!(
+        selected.expr.0,
+        Expr::Local(_) | Expr::Assign(..) | Expr::Sequence(_) | Expr::ParseError
+    );
+    if !selected.movable || !is_value || span == selected.statement {
+        return None;
+    }
+
+    let name = unique_name("value", taken);
+    let statement = selected.statement;
+    let separator = match line_indent(source, statement.start) {
+        Some(indent) => format!("\n{indent}"),
+        None => " ".to_string(),
+    };
+
+    Some(TextEdit {
+        range: span_to_range(source, Span::new(statement.start, span.end)),
+        new_text: format!(
+            "{name} = {};{separator}{}{name}",
+            &source[span.into_range()],
+            &source[statement.start..span.start],
+        ),
+    })
+}
+
+/// Moves the selection into a new function defined above the top-level statement it is in,
+/// replacing it with a call.
+fn extract_function(
+    source: &str,
+    selected: &Selected,
+    params: &[String],
+    taken: &HashSet<&str>,
+) -> TextEdit {
+    let span = selected.expr.span();
+    let name = unique_name("extracted", taken);
+    let params = params.join(", ");
+
+    let top_level = selected.top_level;
+    let indent = line_indent(source, top_level.start);
+    let outer_indent = indent.unwrap_or("");
+    let selection_line = &source[line_start(source, span.start)..];
+    let selection_indent =
+        &selection_line[..selection_line.len() - selection_line.trim_start().len()];
+
+    let body = source[span.into_range()]
+        .lines()
+        .map(|line| {
+            let line = line.strip_prefix(selection_indent).unwrap_or(line);
+            format!("{outer_indent}    {line}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let separator = match indent {
+        Some(indent) => format!("\n\n{indent}"),
+        None => "\n".to_string(),
+    };
+
+    TextEdit {
+        range: span_to_range(source, Span::new(top_level.start, span.end)),
+        new_text: format!(
+            "fn {name}({params}) {{\n{body}\n{outer_indent}}};{separator}{}{name}({params})",
+            &source[top_level.start..span.start],
+        ),
+    }
+}
+
+struct Selected<'src> {
+    /// The selected expression, or a sequence of the selected statements
+    expr: Spanned<Expr<'src>>,
+    /// The innermost statement containing the selection
+    statement: Span,
+    /// The top-level statement containing the selection
+    top_level: Span,
+    /// Whether evaluating the selection right before `statement` gives the same result, which it
+    /// doesn't if it's inside a loop or function body, or only evaluated on some condition
+    movable: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Context {
+    statement: Span,
+    top_level: Span,
+    at_top: bool,
+    movable: bool,
+}
+
+// Finds the outermost expression spanning exactly the selection, or the statements it covers
+fn find_selection<'src>(
+    expr: &mut Spanned<Expr<'src>>,
+    selection: Span,
+    cx: Context,
+) -> Option<Selected<'src>> {
+    let selected = |expr: Spanned<Expr<'src>>| Selected {
+        expr,
+        statement: cx.statement,
+        top_level: cx.top_level,
+        movable: cx.movable,
+    };
+
+    if expr.span() == selection {
+        return Some(selected(expr.clone()));
+    }
+
+    if let Expr::Sequence(_) = &expr.0 {
+        let statements = flatten_sequence(expr);
+        let first = statements
+            .iter()
+            .position(|stmt| stmt.span().start == selection.start);
+        let last = statements
+            .iter()
+            .position(|stmt| stmt.span().end == selection.end);
+        if let (Some(first), Some(last)) = (first, last)
+            && first < last
+        {
+            let run = statements[first..=last]
+                .iter()
+                .map(|&stmt| stmt.clone())
+                .collect();
+            return Some(selected(Spanned(Expr::Sequence(run), selection)));
+        }
+    }
+
+    let statement = |child: &Spanned<Expr>| Context {
+        statement: child.span(),
+        top_level: if cx.at_top {
+            child.span()
+        } else {
+            cx.top_level
+        },
+        movable: true,
+        ..cx
+    };
+    let nested = Context {
+        at_top: false,
+        ..cx
+    };
+    let conditional = Context {
+        movable: false,
+        ..nested
+    };
+
+    let mut found = None;
+    let mut visit = |child: &mut Spanned<Expr<'src>>, cx: Context| {
+        let contains = child.span().start <= selection.start && selection.end <= child.span().end;
+        if found.is_none() && contains {
+            found = find_selection(child, selection, cx);
+        }
+    };
+
+    match &mut expr.0 {
+        Expr::Sequence(items) => items.iter_mut().for_each(|item| {
+            let cx = statement(item);
+            visit(item, cx)
+        }),
+        Expr::Block(inner) => {
+            let cx = Context {
+                at_top: false,
+                ..statement(inner)
+            };
+            visit(inner, cx)
+        }
+        Expr::If(cond, then, otherwise) => {
+            visit(cond, nested);
+            visit(then, conditional);
+            visit(otherwise, conditional);
+        }
+        Expr::While(cond, body) => {
+            visit(cond, conditional);
+            visit(body, conditional);
+        }
+        Expr::For(_, iterable, body) | Expr::ListComprehension(body, _, iterable) => {
+            visit(iterable, nested);
+            visit(body, conditional);
+        }
+        Expr::Binary(lhs, BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce, rhs) => {
+            visit(lhs, nested);
+            visit(rhs, conditional);
+        }
+        Expr::Match(target, arms) => {
+            visit(target, nested);
+            for (pattern, body) in arms {
+                visit(pattern, conditional);
+                visit(body, conditional);
+            }
+        }
+        Expr::Value(AstValue::Func(_)) | Expr::NullSafe(_) => {
+            for_each_child(expr, |child| visit(child, conditional))
+        }
+        _ => for_each_child(expr, |child| visit(child, nested)),
+    }
+
+    found
+}
+
+// Statements chained by semicolons are parsed as nested sequences, `a; b; c` as `a; (b; c)`
+fn flatten_sequence<'a, 'src>(expr: &'a Spanned<Expr<'src>>) -> Vec<&'a Spanned<Expr<'src>>> {
+    match &expr.0 {
+        Expr::Sequence(items) => items.iter().flat_map(flatten_sequence).collect(),
+        _ => vec![expr],
+    }
+}
+
+// Whether the code leaves by `return`, or `break` or `continue` out of a loop around it, which it
+// can't from inside a function
+fn escapes(expr: &mut Spanned<Expr>, in_loop: bool) -> bool {
+    match &mut expr.0 {
+        Expr::Return(_) => true,
+        Expr::Break | Expr::Continue => !in_loop,
+        Expr::Value(AstValue::Func(_)) => false,
+        Expr::While(cond, body) => escapes(cond, in_loop) || escapes(body, true),
+        Expr::For(_, iterable, body) | Expr::ListComprehension(body, _, iterable) => {
+            escapes(iterable, in_loop) || escapes(body, true)
+        }
+        _ => {
+            let mut escaped = false;
+            for_each_child(expr, |child| escaped |= escapes(child, in_loop));
+            escaped
+        }
+    }
+}
+
+/// The variables read and assigned anywhere in an expression.
+#[derive(Default)]
+struct Names<'src> {
+    reads: Vec<(&'src str, Span)>,
+    assigned: HashSet<&'src str>,
+    /// The arguments of the functions defined in the expression, which are only assigned inside
+    /// those functions
+    args: HashSet<&'src str>,
+}
+
+impl<'src> Names<'src> {
+    fn visit(&mut self, expr: &mut Spanned<Expr<'src>>) {
+        match &expr.0 {
+            Expr::Local(name) => self.reads.push((name, expr.span())),
+            Expr::Assign(pattern, _)
+            | Expr::For(pattern, ..)
+            | Expr::ListComprehension(_, pattern, _) => self.assign(pattern),
+            Expr::Value(AstValue::Func(func)) => self.args.extend(&func.args),
+            Expr::Enum(name, _) => {
+                self.assigned.insert(name);
+            }
+            _ => {}
+        }
+
+        for_each_child(expr, |child| self.visit(child));
+    }
+
+    fn assign(&mut self, pattern: &Spanned<Pattern<'src>>) {
+        match &pattern.0 {
+            Pattern::Ident(name) => {
+                self.assigned.insert(name);
+            }
+            Pattern::Sequence(patterns) => patterns.iter().for_each(|p| self.assign(p)),
+            Pattern::Index(..) | Pattern::Value(_) => {}
+        }
+    }
+}
+
+// Shrinks the selection past surrounding whitespace and a trailing semicolon
+fn trim_selection(source: &str, start: usize, end: usize) -> Span {
+    let (start, end) = (start.min(source.len()), end.min(source.len()));
+    let selected = &source[start..end.max(start)];
+    let trimmed = selected.trim_start();
+    let start = start + (selected.len() - trimmed.len());
+    let trimmed = trimmed.trim_end().trim_end_matches(';').trim_end();
+    Span::new(start, start + trimmed.len())
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1)
+}
+
+// The indentation before `offset`, if nothing but whitespace comes before it on its line
+fn line_indent(source: &str, offset: usize) -> Option<&str> {
+    let before = &source[line_start(source, offset)..offset];
+    before.trim_start().is_empty().then_some(before)
+}
+
+fn unique_name(base: &str, taken: &HashSet<&str>) -> String {
+    std::iter::once(base.to_string())
+        .chain((2..).map(|i| format!("{base}_{i}")))
+        .find(|name| !taken.contains(name.as_str()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{apply_action, range_of, uri};
+
+    fn extract(source: &str, selection: &str) -> Vec<(String, String)> {
+        extract_actions(source, &uri(), range_of(source, selection))
+            .iter()
+            .map(|action| apply_action(source, action))
+            .collect()
+    }
+
+    fn rewrite(source: &str, at: &str) -> Vec<(String, String)> {
+        rewrite_actions(source, &uri(), range_of(source, at))
+            .iter()
+            .map(|action| apply_action(source, action))
+            .collect()
+    }
+
+    #[test]
+    fn expressions_are_extracted_into_variables_and_functions() {
+        let source = "xs = [1, 2];\nprint(len(xs) + 1)";
+
+        assert_eq!(
+            extract(source, "len(xs)"),
+            [
+                (
+                    "Extract into variable".to_string(),
+                    "xs = [1, 2];\nvalue = len(xs);\nprint(value + 1)".to_string(),
+                ),
+                (
+                    "Extract into function".to_string(),
+                    "xs = [1, 2];\nfn extracted(xs) {\n    len(xs)\n};\n\nprint(extracted(xs) + 1)"
+                        .to_string(),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn extracted_names_do_not_clash() {
+        let source = "value = 2;\nprint(value * 3)";
+
+        let (_, edited) = &extract(source, "value * 3")[0];
+        assert_eq!(edited, "value = 2;\nvalue_2 = value * 3;\nprint(value_2)");
+    }
+
+    #[test]
+    fn conditional_expressions_are_only_extracted_into_functions() {
+        let source = "x = 1;\nif x > 0 { print(x * 2) }";
+
+        let titles = extract(source, "x * 2")
+            .into_iter()
+            .map(|(title, _)| title)
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["Extract into function"]);
+    }
+
+    #[test]
+    fn code_leaving_a_loop_is_not_extracted() {
+        let source = "for x in 0..3 {\n    break if x > 1;\n    print(x)\n}";
+
+        assert!(extract(source, "break if x > 1").is_empty());
+    }
+
+    #[test]
+    fn appending_loops_are_rewritten_to_comprehensions() {
+        let source =
+            "squares = [];\nfor x in 0..5 {\n    squares.append(x * x);\n};\nprint(squares)";
+
+        assert_eq!(
+            rewrite(source, "for"),
+            [(
+                "Convert to list comprehension".to_string(),
+                "squares = [x * x for x in 0..5];\nprint(squares)".to_string(),
+            )]
+        );
+    }
+
+    #[test]
+    fn conditions_around_the_append_become_filters() {
+        let source = "evens = [];\nfor x in 0..10 {\n    if x % 2 == 0 { evens.append(x) }\n}";
+
+        let (_, edited) = &rewrite(source, "for")[0];
+        assert_eq!(edited, "evens = [x for x in 0..10 if x % 2 == 0]");
+    }
+
+    #[test]
+    fn loops_doing_more_than_appending_are_not_rewritten() {
+        let source = "ys = [];\nfor x in 0..3 {\n    print(x);\n    ys.append(x)\n}";
+        let reads_list = "ys = [];\nfor x in 0..3 {\n    ys.append(len(ys))\n}";
+
+        assert!(rewrite(source, "for").is_empty());
+        assert!(rewrite(reads_list, "for").is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use linefeed::docs::{self, BuiltinKind};
+use tower_lsp::lsp_types::*;
+
+use crate::project::Variable;
+use crate::semantic_tokens::position_to_byte_offset;
+
+/// Completions at `position`: only methods right after a `.`, and elsewhere the variables in scope
+/// followed by the builtin functions and snippets. Builtins are documented from the standard
+/// library reference, and calls are inserted as snippets with a placeholder for each argument.
+pub fn completions(
+    source: &str,
+    position: Position,
+    variables: Vec<Variable>,
+) -> Vec<CompletionItem> {
+    let after_dot = char_before(source, position) == Some('.');
+
+    let variables = if after_dot { vec![] } else { variables };
+
+    let builtins = docs::builtins()
+        .into_iter()
+        .filter(|builtin| (builtin.kind == BuiltinKind::Method) == after_dot)
+        .map(|builtin| {
+            let (min, max) = (*builtin.num_args.start(), *builtin.num_args.end());
+            // Optional arguments get a single empty placeholder, to be typed out or left empty
+            let args = match (min, max) {
+                (0, 0) => vec![],
+                (0, _) => vec![String::new()],
+                (min, _) => (1..=min).map(|i| format!("arg{i}")).collect(),
+            };
+
+            CompletionItem {
+                label: builtin.name.to_string(),
+                kind: Some(match builtin.kind {
+                    BuiltinKind::Method => CompletionItemKind::METHOD,
+                    BuiltinKind::Function | BuiltinKind::Snippet => CompletionItemKind::FUNCTION,
+                }),
+                detail: Some(builtin.signature()),
+                documentation: Some(Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!("{}\n\n{}", builtin.doc, builtin.arity()),
+                })),
+                insert_text: Some(call_snippet(builtin.name, &args)),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                // Sorted after the variables
+                sort_text: Some(format!("1{}", builtin.name)),
+                ..Default::default()
+            }
+        });
+
+    variables
+        .into_iter()
+        .map(variable_completion)
+        .chain(builtins)
+        .collect()
+}
+
+fn variable_completion(variable: Variable) -> CompletionItem {
+    let Some(args) = variable.args else {
+        return CompletionItem {
+            label: variable.name.clone(),
+            kind: Some(CompletionItemKind::VARIABLE),
+            sort_text: Some(format!("0{}", variable.name)),
+            ..Default::default()
+        };
+    };
+
+    CompletionItem {
+        label: variable.name.clone(),
+        kind: Some(CompletionItemKind::FUNCTION),
+        detail: Some(format!("{}({})", variable.name, args.join(", "))),
+        insert_text: Some(call_snippet(&variable.name, &args)),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        sort_text: Some(format!("0{}", variable.name)),
+        ..Default::default()
+    }
+}
+
+// E.g. `f(${1:a}, ${2:b})$0`, where the editor lets the arguments be filled in one at a time
+fn call_snippet(name: &str, args: &[String]) -> String {
+    let placeholders = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| format!("${{{}:{arg}}}", i + 1))
+        .collect::<Vec<_>>();
+    format!("{name}({})$0", placeholders.join(", "))
+}
+
+fn char_before(source: &str, position: Position) -> Option<char> {
+    if position.character == 0 {
+        return None;
+    }
+    source[..position_to_byte_offset(source, position)]
+        .chars()
+        .next_back()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    fn variable(name: &str, args: Option<&[&str]>) -> Variable {
+        Variable {
+            name: name.to_string(),
+            args: args.map(|args| args.iter().map(|arg| arg.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn variables_come_before_builtins() {
+        let source = "x = 1;\n";
+        let variables = vec![variable("x", None), variable("add", Some(&["a", "b"]))];
+
+        let items = completions(source, Position::new(1, 0), variables);
+        assert_eq!(labels(&items)[..2], ["x", "add"]);
+        assert!(labels(&items).contains(&"print"));
+        assert!(!labels(&items).contains(&"append"));
+        assert!(items[0].sort_text < items[2].sort_text);
+
+        assert_eq!(items[0].insert_text, None);
+        assert_eq!(
+            items[1].insert_text.as_deref(),
+            Some("add(${1:a}, ${2:b})$0")
+        );
+        assert_eq!(items[1].detail.as_deref(), Some("add(a, b)"));
+    }
+
+    #[test]
+    fn only_methods_follow_a_dot() {
+        // The emoji takes two UTF-16 code units, so the dot ends at column 8
+        let source = "s = \"😀\".";
+        let variables = vec![variable("s", None)];
+
+        let items = completions(source, Position::new(0, 8), variables);
+        assert!(labels(&items).contains(&"append"));
+        assert!(!labels(&items).contains(&"s"));
+        assert!(!labels(&items).contains(&"print"));
+        assert!(
+            items
+                .iter()
+                .all(|item| item.kind == Some(CompletionItemKind::METHOD))
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, TextDocumentContentChangeEvent};
+
+/// An open document, kept in a rope so the edits of incremental sync are cheap to apply.
+#[derive(Debug)]
+pub struct Document {
+    text: Rope,
+    /// The version of the last change, which the client increases with every change, so analysis
+    /// that was waiting for typing to pause can tell whether it is still needed
+    pub version: i32,
+}
+
+impl Document {
+    pub fn new(text: &str, version: i32) -> Self {
+        Self {
+            text: Rope::from_str(text),
+            version,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.to_string()
+    }
+
+    /// Applies a change sent by the client. Changes without a range replace the whole document.
+    pub fn apply(&mut self, change: TextDocumentContentChangeEvent) {
+        let Some(range) = change.range else {
+            self.text = Rope::from_str(&change.text);
+            return;
+        };
+
+        let start = self.char_index(range.start);
+        let end = self.char_index(range.end).max(start);
+        self.text.remove(start..end);
+        self.text.insert(start, &change.text);
+    }
+
+    // Positions count UTF-16 code units, like `position_to_byte_offset`. Positions past the end of
+    // a line or of the document are clamped to it
+    fn char_index(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        if line >= self.text.len_lines() {
+            return self.text.len_chars();
+        }
+
+        let line_text = self.text.line(line);
+        let line_break = line_text
+            .chars()
+            .rev()
+            .take_while(|c| matches!(c, '\n' | '\r'))
+            .count();
+        let line_start = self.text.line_to_char(line);
+        let line_end = line_start + line_text.len_chars() - line_break;
+
+        let start = self.text.char_to_utf16_cu(line_start);
+        let end = self.text.char_to_utf16_cu(line_end);
+        self.text
+            .utf16_cu_to_char((start + position.character as usize).min(end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Range;
+
+    use super::*;
+
+    fn edit(text: &str, (start, end): ((u32, u32), (u32, u32)), new_text: &str) -> String {
+        let mut document = Document::new(text, 0);
+        document.apply(TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: new_text.to_string(),
+        });
+        document.text()
+    }
+
+    #[test]
+    fn changes_without_a_range_replace_the_document() {
+        let mut document = Document::new("x = 1;", 0);
+        document.apply(TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "y = 2;".to_string(),
+        });
+        assert_eq!(document.text(), "y = 2;");
+    }
+
+    #[test]
+    fn changes_replace_their_range() {
+        assert_eq!(
+            edit("x = 1;\ny = 2;", ((1, 4), (1, 5)), "42"),
+            "x = 1;\ny = 42;"
+        );
+        assert_eq!(
+            edit("x = 1;\ny = 2;", ((0, 6), (1, 0)), " "),
+            "x = 1; y = 2;"
+        );
+        assert_eq!(edit("x = 1;", ((0, 0), (0, 0)), "# hi\n"), "# hi\nx = 1;");
+    }
+
+    #[test]
+    fn columns_count_utf16_code_units() {
+        // The emoji takes two code units and `é` one, so `1` starts at column 11
+        assert_eq!(
+            edit("s = \"😀é\"; 1", ((0, 11), (0, 12)), "2"),
+            "s = \"😀é\"; 2"
+        );
+        assert_eq!(edit("😀 = 1;", ((0, 0), (0, 2)), "x"), "x = 1;");
+    }
+
+    #[test]
+    fn positions_past_the_end_are_clamped() {
+        assert_eq!(
+            edit("x = 1;\r\ny;", ((0, 99), (0, 99)), " # end"),
+            "x = 1; # end\r\ny;"
+        );
+        assert_eq!(edit("x = 1;", ((5, 0), (5, 0)), "\ny;"), "x = 1;\ny;");
+    }
+}
@@ -0,0 +1,45 @@
+use linefeed::grammar::ast::Span;
+use tower_lsp::lsp_types::*;
+
+use crate::semantic_tokens::span_to_range;
+
+/// The edits formatting a document, which replace all of it unless it is already formatted.
+/// Documents that don't parse are left as they are until they do, which gives `None`.
+pub fn format_document(source: &str) -> Option<Vec<TextEdit>> {
+    let formatted = linefeed::formatter::format(source)?;
+    if formatted == source {
+        return Some(vec![]);
+    }
+
+    Some(vec![TextEdit {
+        range: span_to_range(source, Span::new(0, source.len())),
+        new_text: formatted,
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::apply_edits;
+
+    #[test]
+    fn documents_are_replaced_with_their_formatted_text() {
+        let source = "total=0;\nfor i in 0 .. 3 {\ntotal+=i;\n};\nprint( total );\n";
+
+        let edits = format_document(source).unwrap();
+        assert_eq!(
+            apply_edits(source, &edits),
+            "total = 0;\nfor i in 0..3 {\n  total += i;\n};\nprint(total);\n"
+        );
+    }
+
+    #[test]
+    fn formatted_documents_are_not_edited() {
+        assert_eq!(format_document("x = [1, 2];\n"), Some(vec![]));
+    }
+
+    #[test]
+    fn documents_that_do_not_parse_are_left_alone() {
+        assert_eq!(format_document("x = (1;\n"), None);
+    }
+}
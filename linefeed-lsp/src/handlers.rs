@@ -1,14 +1,44 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use linefeed::chumsky::Parser as _;
+use linefeed::config::Config;
+use linefeed::docs::{self, BuiltinKind};
+use linefeed::grammar::ast::Spanned;
+use linefeed::grammar::lexer::Token;
 use tower_lsp::LanguageServer;
-use tower_lsp::jsonrpc::Result;
+use tower_lsp::jsonrpc::{Error, Result};
 use tower_lsp::lsp_types::*;
 
 use crate::backend::Backend;
 use crate::capabilities;
-use crate::semantic_tokens;
+use crate::code_actions;
+use crate::completion;
+use crate::document::Document;
+use crate::formatting;
+use crate::lints;
+use crate::project::{self, Project};
+use crate::quick_fixes;
+use crate::semantic_tokens::{self, position_to_byte_offset, span_to_range};
+
+/// How long typing has to pause before a changed document is analyzed again.
+const ANALYSIS_DELAY: Duration = Duration::from_millis(150);
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        #[allow(deprecated)]
+        let roots = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) => folders.into_iter().map(|folder| folder.uri).collect(),
+            (None, root) => root.into_iter().collect::<Vec<_>>(),
+        };
+
+        let mut project = self.project.lock().await;
+        for root in roots.iter().filter_map(|uri| uri.to_file_path().ok()) {
+            project.index_dir(&root);
+        }
+
         Ok(InitializeResult {
             server_info: None,
             capabilities: capabilities::build_server_capabilities(),
@@ -19,6 +49,24 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "server initialized!")
             .await;
+
+        // Files changed outside the editor, e.g. by switching branches, have to be indexed again
+        let watchers = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.lf".to_string()),
+                kind: None,
+            }],
+        };
+        let registration = Registration {
+            id: "watch-lf-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(watchers).ok(),
+        };
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(MessageType::WARNING, format!("Can't watch files: {err}"))
+                .await;
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -26,15 +74,253 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.on_change(params.text_document.uri, params.text_document.text)
-            .await;
+        let document = params.text_document;
+        self.sources.lock().await.insert(
+            document.uri.to_string(),
+            Document::new(&document.text, document.version),
+        );
+        self.on_change(document.uri, document.text).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        // FULL sync mode means we get the entire document
-        if let Some(change) = params.content_changes.into_iter().next() {
-            self.on_change(params.text_document.uri, change.text).await;
+        // INCREMENTAL sync mode means we only get the edited ranges
+        let uri = params.text_document.uri;
+        let version = params.text_document.version;
+        {
+            let mut sources = self.sources.lock().await;
+            let Some(document) = sources.get_mut(&uri.to_string()) else {
+                return;
+            };
+            for change in params.content_changes {
+                document.apply(change);
+            }
+            document.version = version;
+        }
+
+        // Analysis goes through the whole file, so it waits for typing to pause. A later change
+        // analyzes the document instead
+        tokio::time::sleep(ANALYSIS_DELAY).await;
+        let text = match self.sources.lock().await.get(&uri.to_string()) {
+            Some(document) if document.version == version => document.text(),
+            _ => return,
+        };
+        self.on_change(uri, text).await;
+    }
+
+    async fn did_save(&self, _: DidSaveTextDocumentParams) {
+        // Open documents importing the saved file compile it from disk, so their diagnostics may
+        // have changed along with it
+        let documents = self
+            .sources
+            .lock()
+            .await
+            .iter()
+            .map(|(uri, document)| (uri.clone(), document.text()))
+            .collect::<Vec<_>>();
+
+        for (uri, text) in documents {
+            if let Ok(uri) = Url::parse(&uri) {
+                self.on_change(uri, text).await;
+            }
+        }
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let open = self.sources.lock().await;
+        let mut project = self.project.lock().await;
+
+        for change in params.changes {
+            // The contents of open files come from the editor instead
+            let Ok(path) = change.uri.to_file_path() else {
+                continue;
+            };
+            if open.contains_key(&change.uri.to_string()) {
+                continue;
+            }
+
+            match std::fs::read_to_string(&path) {
+                Ok(source) if change.typ != FileChangeType::DELETED => {
+                    project.update(&path, source)
+                }
+                _ => project.remove(&path),
+            }
+        }
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let position = params.text_document_position_params;
+        let project = self.project.lock().await;
+
+        let Some(location) = self.locate(&project, &position).and_then(|(path, offset)| {
+            let definition = project.definition(&path, offset)?;
+            to_lsp_location(&project, definition)
+        }) else {
+            return Ok(None);
+        };
+
+        Ok(Some(GotoDefinitionResponse::Scalar(location)))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let position = params.text_document_position;
+        let project = self.project.lock().await;
+
+        let Some((path, offset)) = self.locate(&project, &position) else {
+            return Ok(None);
+        };
+
+        let mut references = project.references(&path, offset);
+        if !params.context.include_declaration
+            && let Some(definition) = project.definition(&path, offset)
+        {
+            references.retain(|reference| *reference != definition);
+        }
+
+        Ok(Some(
+            references
+                .into_iter()
+                .filter_map(|reference| to_lsp_location(&project, reference))
+                .collect(),
+        ))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let project = self.project.lock().await;
+        let Some((path, offset)) = self.locate(&project, &params) else {
+            return Ok(None);
+        };
+        check_not_builtin(&project, &path, offset)?;
+
+        // Only names with a definition in the project can be renamed
+        let Some(span) = project.name_at(&path, offset) else {
+            return Ok(None);
+        };
+        if project.definition(&path, offset).is_none() {
+            return Ok(None);
         }
+
+        let range = span_to_range(project.source(&path).unwrap_or_default(), span);
+        Ok(Some(PrepareRenameResponse::Range(range)))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = params.text_document_position;
+        let new_name = params.new_name;
+
+        let is_ident = matches!(
+            linefeed::grammar::lexer::lexer()
+                .parse(new_name.as_str())
+                .into_output()
+                .as_deref(),
+            Some([Spanned(Token::Ident(_), _)])
+        );
+        if !is_ident {
+            return Err(Error::invalid_params(format!(
+                "'{new_name}' is not a valid name"
+            )));
+        }
+        // Calls by the new name would go to the builtin instead of the renamed function
+        if docs::builtins()
+            .iter()
+            .any(|builtin| builtin.kind != BuiltinKind::Method && builtin.name == new_name)
+        {
+            return Err(Error::invalid_params(format!(
+                "'{new_name}' is the name of a builtin function"
+            )));
+        }
+
+        let project = self.project.lock().await;
+        let Some((path, offset)) = self.locate(&project, &position) else {
+            return Ok(None);
+        };
+        check_not_builtin(&project, &path, offset)?;
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for reference in project.references(&path, offset) {
+            if let Some(location) = to_lsp_location(&project, reference) {
+                changes.entry(location.uri).or_default().push(TextEdit {
+                    range: location.range,
+                    new_text: new_name.clone(),
+                });
+            }
+        }
+
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+        }))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let position = params.text_document_position;
+        let uri = position.text_document.uri.to_string();
+
+        let Some(src) = self.sources.lock().await.get(&uri).map(Document::text) else {
+            return Ok(None);
+        };
+
+        let variables = {
+            let project = self.project.lock().await;
+            self.locate(&project, &position)
+                .map(|(path, offset)| project.variables_at(&path, offset))
+                .unwrap_or_default()
+        };
+
+        let items = completion::completions(&src, position.position, variables);
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+
+        let Some(src) = self
+            .sources
+            .lock()
+            .await
+            .get(&uri.to_string())
+            .map(Document::text)
+        else {
+            return Ok(None);
+        };
+
+        // Fixes for the diagnostics come first, as they are what the editor shows at an error
+        let mut actions = {
+            let project = self.project.lock().await;
+            let variables_at = |position| {
+                let position = TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier::new(uri.clone()),
+                    position,
+                };
+                self.locate(&project, &position)
+                    .map(|(path, offset)| project.variables_at(&path, offset))
+                    .unwrap_or_default()
+            };
+            quick_fixes::quick_fixes(&src, &uri, &params.context.diagnostics, variables_at)
+        };
+
+        actions.extend(code_actions::rewrite_actions(&src, &uri, params.range));
+        actions.extend(code_actions::extract_actions(&src, &uri, params.range));
+        Ok(Some(actions))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.to_string();
+
+        let Some(src) = self.sources.lock().await.get(&uri).map(Document::text) else {
+            return Ok(None);
+        };
+
+        Ok(formatting::format_document(&src))
     }
 
     async fn semantic_tokens_full(
@@ -48,7 +334,7 @@ impl LanguageServer for Backend {
             .await;
 
         // Get source from cache
-        let src = match self.sources.lock().await.get(&uri).cloned() {
+        let src = match self.sources.lock().await.get(&uri).map(Document::text) {
             Some(src) => src,
             None => {
                 // File not found in cache, return empty tokens
@@ -70,17 +356,65 @@ impl LanguageServer for Backend {
 }
 
 impl Backend {
-    /// Process document changes: cache source, parse, compile, and publish diagnostics
+    /// The path of a document and the byte offset of a position in it.
+    fn locate(
+        &self,
+        project: &Project,
+        position: &TextDocumentPositionParams,
+    ) -> Option<(std::path::PathBuf, usize)> {
+        let path = position.text_document.uri.to_file_path().ok()?;
+        let offset = position_to_byte_offset(project.source(&path)?, position.position);
+        Some((path, offset))
+    }
+
+    /// Process document changes: index, parse, compile, and publish diagnostics
     async fn on_change(&self, uri: Url, text: String) {
         let uri_string = uri.to_string();
+        let path = uri.to_file_path().ok();
+
+        // Pick up the same linefeed.toml and user config as the CLI would for this file
+        let config = match path.as_deref().and_then(Path::parent) {
+            Some(dir) => match Config::load(dir) {
+                Ok(config) => config,
+                Err(err) => {
+                    self.client
+                        .show_message(MessageType::ERROR, err.to_string())
+                        .await;
+                    Config::default()
+                }
+            },
+            None => Config::default(),
+        };
 
-        // Cache the source
-        self.sources.lock().await.insert(uri_string, text.clone());
+        if let Some(path) = &path {
+            self.project.lock().await.update(path, text.clone());
+        }
 
         // Validate syntax and compilation, publish diagnostics
-        let (_symbol_table, diagnostics) = semantic_tokens::safe_parse_and_compile(&text);
+        let (_symbol_table, mut diagnostics) =
+            semantic_tokens::safe_parse_and_compile(&text, path.as_deref());
+        diagnostics.extend(lints::lint_diagnostics(&text, &config));
+        self.configs.lock().await.insert(uri_string, config);
+
         self.client
             .publish_diagnostics(uri, diagnostics, None)
             .await;
     }
 }
+
+fn check_not_builtin(project: &Project, path: &Path, offset: usize) -> Result<()> {
+    match project.builtin(path, offset) {
+        Some(name) => Err(Error::invalid_params(format!(
+            "'{name}' is a builtin and can't be renamed"
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn to_lsp_location(project: &Project, location: project::Location) -> Option<Location> {
+    let source = project.source(&location.path).unwrap_or_default();
+    Some(Location {
+        uri: Url::from_file_path(&location.path).ok()?,
+        range: span_to_range(source, location.span),
+    })
+}
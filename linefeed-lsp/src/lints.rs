@@ -0,0 +1,49 @@
+use linefeed::chumsky::Parser;
+use linefeed::config::Config;
+use linefeed::lint::{self, LintLevel};
+use linefeed::type_check;
+use tower_lsp::lsp_types::*;
+
+use crate::semantic_tokens::span_to_range;
+
+/// Runs the linter and the type checker over a document. Documents that don't parse produce no
+/// lint diagnostics, since the parse errors are reported already.
+pub fn lint_diagnostics(source: &str, config: &Config) -> Vec<Diagnostic> {
+    let Some(tokens) = linefeed::grammar::lexer::lexer()
+        .parse(source)
+        .into_output()
+    else {
+        return vec![];
+    };
+
+    let Ok(ast) = linefeed::parse_tokens(source, &tokens) else {
+        return vec![];
+    };
+
+    let type_errors = type_check::check_types(&ast)
+        .into_iter()
+        .map(|error| Diagnostic {
+            range: span_to_range(source, error.span),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String("type_error".to_string())),
+            message: error.msg,
+            source: Some("linefeed".to_string()),
+            ..Default::default()
+        });
+
+    lint::lint(source, &ast, config)
+        .into_iter()
+        .map(|diagnostic| Diagnostic {
+            range: span_to_range(source, diagnostic.span),
+            severity: Some(match diagnostic.level {
+                LintLevel::Deny => DiagnosticSeverity::ERROR,
+                _ => DiagnosticSeverity::WARNING,
+            }),
+            code: Some(NumberOrString::String(diagnostic.lint.name().to_string())),
+            message: diagnostic.msg,
+            source: Some("linefeed".to_string()),
+            ..Default::default()
+        })
+        .chain(type_errors)
+        .collect()
+}
@@ -1,7 +1,16 @@
 mod backend;
 mod capabilities;
+mod code_actions;
+mod completion;
+mod document;
+mod formatting;
 mod handlers;
+mod lints;
+mod project;
+mod quick_fixes;
 mod semantic_tokens;
+#[cfg(test)]
+mod test_utils;
 
 use backend::Backend;
 use tower_lsp::{LspService, Server};
@@ -0,0 +1,644 @@
+//! An index of the Linefeed files in the workspace, which resolves names across imports for
+//! go-to-definition, references, rename and completion.
+//!
+//! Each file is indexed on its own, recording for every name in it either where in the file it is
+//! defined, or that it must come from one of the file's imports. Imports are only followed when a
+//! request is made, so a changed file is the only one that needs to be indexed again.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use linefeed::chumsky::Parser as _;
+use linefeed::compiler::analysis;
+use linefeed::compiler::stdlib_fn::StdlibFn;
+use linefeed::grammar::ast::{AstValue, Expr, Pattern, Span, Spanned};
+use linefeed::grammar::lexer::Token;
+use linefeed::grammar::snippets::for_each_child;
+
+/// A span of a file in the project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub span: Span,
+}
+
+/// A variable that can be used at some position, with the arguments it takes if it is a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    pub args: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Project {
+    files: HashMap<PathBuf, FileIndex>,
+}
+
+#[derive(Debug, Default)]
+struct FileIndex {
+    source: String,
+    parsed: bool,
+    /// Every name in the file, definitions included, with what it refers to
+    names: Vec<(Span, Def)>,
+    /// The files imported anywhere in the file, by the span of their `import` statement
+    imports: Vec<(Span, PathBuf)>,
+    /// Where each variable assigned at the top level of the file is defined
+    top_level: HashMap<String, Span>,
+    /// The names of the standard library functions and methods called in the file
+    builtins: Vec<(Span, String)>,
+    /// The body of each function in the file, with the names of its own variables
+    function_scopes: Vec<(Span, Vec<(String, Span)>)>,
+    /// The arguments of the functions assigned to names, by the span of the name
+    function_args: HashMap<Span, Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+enum Def {
+    /// Defined by the name with this span in the same file
+    Here(Span),
+    /// Not assigned in the file, so it is either imported or a builtin
+    Imported(String),
+}
+
+impl Project {
+    /// Indexes every `.lf` file in `dir` and its subdirectories, except hidden ones and `target`.
+    pub fn index_dir(&mut self, dir: &Path) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            if path.is_dir() {
+                if !name.to_string_lossy().starts_with('.') && name != "target" {
+                    self.index_dir(&path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "lf")
+                && let Ok(source) = fs::read_to_string(&path)
+            {
+                self.update(&path, source);
+            }
+        }
+    }
+
+    /// Indexes the file at `path` again, now that its contents are `source`.
+    pub fn update(&mut self, path: &Path, source: String) {
+        let path = normalize(path);
+        let mut index = FileIndex::new(&path, source);
+
+        // A file being edited often doesn't parse until the edit is done, and completion still
+        // needs the variables it had. Names are only resolved in files that parse
+        if !index.parsed
+            && let Some(previous) = self.files.remove(&path)
+        {
+            index.imports = previous.imports;
+            index.top_level = previous.top_level;
+            index.function_scopes = previous.function_scopes;
+            index.function_args = previous.function_args;
+        }
+
+        self.files.insert(path, index);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.files.remove(&normalize(path));
+    }
+
+    pub fn source(&self, path: &Path) -> Option<&str> {
+        Some(&self.files.get(&normalize(path))?.source)
+    }
+
+    /// Where the name at `offset` in the file is defined. On an `import`, this is the start of the
+    /// imported file.
+    pub fn definition(&self, path: &Path, offset: usize) -> Option<Location> {
+        let path = normalize(path);
+        let file = self.files.get(&path)?;
+
+        if let Some((_, imported)) = file
+            .imports
+            .iter()
+            .find(|(span, _)| contains(*span, offset))
+        {
+            return Some(Location {
+                path: imported.clone(),
+                span: Span::new(0, 0),
+            });
+        }
+
+        let (_, def) = file
+            .names
+            .iter()
+            .find(|(span, _)| contains(*span, offset))?;
+        self.resolve(&path, def)
+    }
+
+    /// The span of the name at `offset`, if there is one.
+    pub fn name_at(&self, path: &Path, offset: usize) -> Option<Span> {
+        let file = self.files.get(&normalize(path))?;
+        file.names
+            .iter()
+            .find(|(span, _)| contains(*span, offset))
+            .map(|(span, _)| *span)
+    }
+
+    /// The standard library function or method called by the name at `offset`, if any.
+    pub fn builtin(&self, path: &Path, offset: usize) -> Option<&str> {
+        let file = self.files.get(&normalize(path))?;
+        file.builtins
+            .iter()
+            .find(|(span, _)| contains(*span, offset))
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// The variables that can be used at `offset` in the file: those of the functions around it,
+    /// of the top level of the file, and of the files it imports.
+    pub fn variables_at(&self, path: &Path, offset: usize) -> Vec<Variable> {
+        let path = normalize(path);
+        let Some(file) = self.files.get(&path) else {
+            return vec![];
+        };
+
+        // Inner scopes shadow outer ones, so they are looked at first
+        let mut scopes = file
+            .function_scopes
+            .iter()
+            .filter(|(span, _)| contains(*span, offset))
+            .collect::<Vec<_>>();
+        scopes.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+
+        let mut variables = HashMap::new();
+        for (_, names) in scopes {
+            for (name, span) in names {
+                variables
+                    .entry(name.clone())
+                    .or_insert_with(|| file.function_args.get(span).cloned());
+            }
+        }
+        self.add_top_level(&path, &mut variables, &mut HashSet::new());
+
+        let mut variables = variables
+            .into_iter()
+            .map(|(name, args)| Variable { name, args })
+            .collect::<Vec<_>>();
+        variables.sort_by(|a, b| a.name.cmp(&b.name));
+        variables
+    }
+
+    // Like `find_top_level`, a later import overwrites the variables of an earlier one
+    fn add_top_level(
+        &self,
+        path: &Path,
+        variables: &mut HashMap<String, Option<Vec<String>>>,
+        visited: &mut HashSet<PathBuf>,
+    ) {
+        if !visited.insert(path.to_path_buf()) {
+            return;
+        }
+        let Some(file) = self.files.get(path) else {
+            return;
+        };
+
+        for (name, span) in &file.top_level {
+            variables
+                .entry(name.clone())
+                .or_insert_with(|| file.function_args.get(span).cloned());
+        }
+        for (_, imported) in file.imports.iter().rev() {
+            self.add_top_level(imported, variables, visited);
+        }
+    }
+
+    /// The names anywhere in the project that refer to the same definition as the name at
+    /// `offset`, including the definition itself.
+    pub fn references(&self, path: &Path, offset: usize) -> Vec<Location> {
+        let Some(target) = self.definition(path, offset) else {
+            return vec![];
+        };
+
+        let mut found = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| {
+                file.names
+                    .iter()
+                    .filter(|(_, def)| self.resolve(path, def).as_ref() == Some(&target))
+                    .map(|(span, _)| Location {
+                        path: path.clone(),
+                        span: *span,
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        found.sort_by_key(|location| (location.path.clone(), location.span.start));
+        found
+    }
+
+    fn resolve(&self, path: &Path, def: &Def) -> Option<Location> {
+        match def {
+            Def::Here(span) => Some(Location {
+                path: path.to_path_buf(),
+                span: *span,
+            }),
+            Def::Imported(name) => self.find_top_level(path, name, &mut HashSet::new()),
+        }
+    }
+
+    // Imported files pass on the variables of their own imports, so the definition may be several
+    // imports away. Circular imports are an error when compiling, and are cut short here
+    fn find_top_level(
+        &self,
+        path: &Path,
+        name: &str,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Option<Location> {
+        if !visited.insert(path.to_path_buf()) {
+            return None;
+        }
+
+        let file = self.files.get(path)?;
+        if let Some(&span) = file.top_level.get(name) {
+            return Some(Location {
+                path: path.to_path_buf(),
+                span,
+            });
+        }
+
+        // A later import overwrites the variables of an earlier one
+        file.imports
+            .iter()
+            .rev()
+            .find_map(|(_, imported)| self.find_top_level(imported, name, visited))
+    }
+}
+
+impl FileIndex {
+    fn new(path: &Path, source: String) -> Self {
+        let Some(resolver) = index_names(path, &source) else {
+            return Self {
+                source,
+                ..Self::default()
+            };
+        };
+
+        Self {
+            parsed: true,
+            names: resolver.names,
+            imports: resolver.imports,
+            top_level: resolver.top_level.unwrap_or_default(),
+            builtins: resolver.builtins,
+            function_scopes: resolver.function_scopes,
+            function_args: resolver.function_args,
+            source,
+        }
+    }
+}
+
+// Files that don't parse have no names until they do again
+fn index_names<'a>(path: &'a Path, source: &'a str) -> Option<Resolver<'a>> {
+    let tokens = linefeed::grammar::lexer::lexer()
+        .parse(source)
+        .into_output()?;
+    let mut ast = linefeed::parse_tokens(source, &tokens).ok()?;
+
+    let mut resolver = Resolver {
+        source,
+        tokens: tokens
+            .iter()
+            .filter_map(|Spanned(token, span)| match token {
+                Token::Ident(name) => Some((*name, *span)),
+                _ => None,
+            })
+            .collect(),
+        parens: tokens
+            .iter()
+            .filter_map(|Spanned(token, span)| match token {
+                Token::Ctrl(c @ ('(' | ')' | '[' | ']' | '{' | '}' | ',')) => Some((*c, *span)),
+                _ => None,
+            })
+            .collect(),
+        dir: path.parent().unwrap_or(Path::new(".")),
+        scopes: Vec::new(),
+        names: Vec::new(),
+        imports: Vec::new(),
+        top_level: None,
+        builtins: Vec::new(),
+        functions: analysis::find_function_definitions(&ast),
+        function_scopes: Vec::new(),
+        function_args: HashMap::new(),
+    };
+
+    resolver.enter_scope(&ast, Vec::new());
+    resolver.top_level = resolver.scopes.first().cloned();
+    resolver.visit(&mut ast);
+    Some(resolver)
+}
+
+/// Resolves the names of a file like the compiler allocates variables: a function has its own
+/// variables for its arguments and for what it assigns that isn't a variable of a scope around it.
+struct Resolver<'a> {
+    source: &'a str,
+    tokens: Vec<(&'a str, Span)>,
+    // The brackets and commas, for finding function arguments, which have no spans of their own
+    parens: Vec<(char, Span)>,
+    dir: &'a Path,
+    // The variables of each scope being visited, innermost last
+    scopes: Vec<HashMap<String, Span>>,
+    names: Vec<(Span, Def)>,
+    imports: Vec<(Span, PathBuf)>,
+    top_level: Option<HashMap<String, Span>>,
+    builtins: Vec<(Span, String)>,
+    // The names the file defines functions with, which shadow builtins
+    functions: HashSet<String>,
+    function_scopes: Vec<(Span, Vec<(String, Span)>)>,
+    function_args: HashMap<Span, Vec<String>>,
+}
+
+impl Resolver<'_> {
+    fn enter_scope(&mut self, body: &Spanned<Expr>, args: Vec<(String, Span)>) {
+        let mut scope = args.into_iter().collect::<HashMap<_, _>>();
+
+        for Spanned(name, span) in analysis::find_all_assignments(body) {
+            // Compiler generated variables aren't valid identifiers
+            if name.starts_with('!') || scope.contains_key(&name) || self.lookup(&name).is_some() {
+                continue;
+            }
+
+            if let Some(span) = self.name_span(span, &name) {
+                scope.insert(name, span);
+            }
+        }
+
+        self.scopes.push(scope);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Span> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    fn add_name(&mut self, name: &str, span: Span) {
+        // Code expanded from snippets has the span of the whole snippet call
+        if self.source.get(span.into_range()) != Some(name) {
+            return;
+        }
+
+        let def = match self.lookup(name) {
+            Some(def) => Def::Here(def),
+            None => Def::Imported(name.to_string()),
+        };
+        self.names.push((span, def));
+    }
+
+    fn visit(&mut self, expr: &mut Spanned<Expr>) {
+        let span = expr.span();
+        let mut is_func = false;
+
+        match &expr.0 {
+            // Calling a standard library function by name calls it even where a variable has the
+            // same name, so that name doesn't refer to the variable, unless the variable is a
+            // function the file defines
+            Expr::Local(name) if !self.builtins.iter().any(|(builtin, _)| *builtin == span) => {
+                self.add_name(name, span)
+            }
+            Expr::Call(func, _) => {
+                if let Expr::Local(name) = &func.0
+                    && StdlibFn::from_name(name).is_some()
+                    && !(self.functions.contains(*name) && self.lookup(name).is_some())
+                {
+                    self.builtins.push((func.span(), name.to_string()));
+                }
+            }
+            Expr::MethodCall(receiver, method, _) => {
+                let after_receiver = Span::new(receiver.span().end, span.end);
+                if let Some(method_span) = self.name_span(after_receiver, method) {
+                    self.builtins.push((method_span, method.to_string()));
+                }
+            }
+            Expr::Assign(pattern, value) => {
+                if let (Pattern::Ident(name), Expr::Value(AstValue::Func(func))) =
+                    (&pattern.0, &value.0)
+                    && let Some(name_span) = self.name_span(pattern.span(), name)
+                {
+                    let args = func.args.iter().map(|arg| arg.to_string()).collect();
+                    self.function_args.insert(name_span, args);
+                }
+                self.visit_pattern(pattern);
+            }
+            Expr::For(pattern, ..) | Expr::ListComprehension(_, pattern, _) => {
+                self.visit_pattern(pattern)
+            }
+            Expr::Enum(name, _) | Expr::Variant(name, _) => {
+                if let Some(name_span) = self.name_span(span, name) {
+                    self.add_name(name, name_span);
+                }
+            }
+            Expr::Import(path) => self.imports.push((span, normalize(&self.dir.join(path)))),
+            Expr::Value(AstValue::Func(func)) => {
+                let args = self.arg_spans(span, &func.args);
+                self.enter_scope(&func.body, args.clone());
+                let names = self.scopes.last().cloned().unwrap_or_default();
+                self.function_scopes
+                    .push((span, names.into_iter().collect()));
+                for (name, arg_span) in args {
+                    self.add_name(&name, arg_span);
+                }
+                is_func = true;
+            }
+            _ => {}
+        }
+
+        for_each_child(expr, |child| self.visit(child));
+
+        if is_func {
+            self.scopes.pop();
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &Spanned<Pattern>) {
+        match &pattern.0 {
+            Pattern::Ident(name) => {
+                if let Some(span) = self.name_span(pattern.span(), name) {
+                    self.add_name(name, span);
+                }
+            }
+            Pattern::Sequence(patterns) => patterns.iter().for_each(|p| self.visit_pattern(p)),
+            // The indexed variable is an expression, visited as a child of the assignment
+            Pattern::Index(..) | Pattern::Value(_) => {}
+        }
+    }
+
+    // The span of the first `name` in `within`, which for an assignment of a named function is the
+    // whole function
+    fn name_span(&self, within: Span, name: &str) -> Option<Span> {
+        self.tokens
+            .iter()
+            .find(|(token, span)| {
+                *token == name && span.start >= within.start && span.end <= within.end
+            })
+            .map(|(_, span)| *span)
+    }
+
+    // Arguments are the identifiers right after the opening parenthesis of the function and after
+    // each comma between its brackets
+    fn arg_spans(&self, func: Span, args: &[&str]) -> Vec<(String, Span)> {
+        let parens = self
+            .parens
+            .iter()
+            .filter(|(_, span)| span.start >= func.start && span.end <= func.end)
+            .skip_while(|(c, _)| *c != '(');
+
+        let mut starts = Vec::new();
+        let mut depth = 0;
+        for (c, span) in parens {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+            match (c, depth) {
+                (_, 0) => break,
+                ('(' | ',', 1) => starts.push(span.end),
+                _ => {}
+            }
+        }
+
+        args.iter()
+            .zip(starts)
+            .filter_map(|(arg, start)| {
+                let (_, span) = self
+                    .tokens
+                    .iter()
+                    .find(|(token, span)| token == arg && span.start >= start)?;
+                Some((arg.to_string(), *span))
+            })
+            .collect()
+    }
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+// Paths from the client and from imports can point to the same file in different ways
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &str = "import \"lib.lf\";\nprint(double(2));\n";
+    const LIB: &str = "fn double(x) x * 2;\n";
+
+    fn project() -> Project {
+        let mut project = Project::default();
+        project.update(Path::new("/project/main.lf"), MAIN.to_string());
+        project.update(Path::new("/project/lib.lf"), LIB.to_string());
+        project
+    }
+
+    fn location(path: &str, source: &str, name: &str) -> Location {
+        let start = source.find(name).unwrap();
+        Location {
+            path: PathBuf::from(path),
+            span: Span::new(start, start + name.len()),
+        }
+    }
+
+    fn names(variables: &[Variable]) -> Vec<&str> {
+        variables.iter().map(|v| v.name.as_str()).collect()
+    }
+
+    #[test]
+    fn definitions_are_found_through_imports() {
+        let project = project();
+        let offset = MAIN.find("double").unwrap();
+
+        assert_eq!(
+            project.definition(Path::new("/project/main.lf"), offset),
+            Some(location("/project/lib.lf", LIB, "double"))
+        );
+        assert_eq!(
+            project.definition(Path::new("/project/main.lf"), 0),
+            Some(Location {
+                path: PathBuf::from("/project/lib.lf"),
+                span: Span::new(0, 0),
+            })
+        );
+    }
+
+    #[test]
+    fn references_are_found_in_every_file() {
+        let project = project();
+        let offset = LIB.find("double").unwrap();
+
+        assert_eq!(
+            project.references(Path::new("/project/lib.lf"), offset),
+            [
+                location("/project/lib.lf", LIB, "double"),
+                location("/project/main.lf", MAIN, "double"),
+            ]
+        );
+    }
+
+    #[test]
+    fn builtins_are_not_names_of_the_project() {
+        let project = project();
+        let offset = MAIN.find("print").unwrap();
+
+        assert_eq!(
+            project.builtin(Path::new("/project/main.lf"), offset),
+            Some("print")
+        );
+        assert_eq!(
+            project.definition(Path::new("/project/main.lf"), offset),
+            None
+        );
+    }
+
+    #[test]
+    fn functions_of_the_project_shadow_builtins() {
+        let source = "fn filter(xs, f) xs;\nfilter([1], fn (x) x);\n";
+        let mut project = Project::default();
+        project.update(Path::new("/project/main.lf"), source.to_string());
+        let offset = source.rfind("filter").unwrap();
+
+        assert_eq!(project.builtin(Path::new("/project/main.lf"), offset), None);
+        assert_eq!(
+            project.definition(Path::new("/project/main.lf"), offset),
+            Some(location("/project/main.lf", source, "filter"))
+        );
+    }
+
+    #[test]
+    fn variables_in_functions_include_their_own_and_those_around_them() {
+        let source = "total = 0;\nfn add(a, b) {\n  c = a + b;\n  c\n};\n";
+        let mut project = Project::default();
+        project.update(Path::new("/project/main.lf"), source.to_string());
+
+        let inside =
+            project.variables_at(Path::new("/project/main.lf"), source.find("c\n").unwrap());
+        assert_eq!(names(&inside), ["a", "add", "b", "c", "total"]);
+        assert_eq!(inside[1].args, Some(vec!["a".to_string(), "b".to_string()]));
+
+        let outside = project.variables_at(Path::new("/project/main.lf"), 0);
+        assert_eq!(names(&outside), ["add", "total"]);
+    }
+
+    #[test]
+    fn files_that_stop_parsing_keep_their_variables() {
+        let mut project = project();
+        project.update(
+            Path::new("/project/main.lf"),
+            "import \"lib.lf\";\ny = (".to_string(),
+        );
+
+        let variables = project.variables_at(Path::new("/project/main.lf"), 0);
+        assert_eq!(names(&variables), ["double"]);
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use linefeed::chumsky::Parser as _;
+use linefeed::docs::{self, BuiltinKind};
+use linefeed::grammar::ast::{Span, Spanned};
+use linefeed::grammar::lexer::Token;
+use tower_lsp::lsp_types::*;
+
+use crate::project::Variable;
+use crate::semantic_tokens::{position_to_byte_offset, span_to_range};
+
+/// Fixes for the diagnostics of a document: replacing an unknown variable or method with a
+/// similarly named one, and inserting a `;` that is missing between two statements. The variables
+/// that can be used at a position come from `variables_at`.
+pub fn quick_fixes(
+    source: &str,
+    uri: &Url,
+    diagnostics: &[Diagnostic],
+    variables_at: impl Fn(Position) -> Vec<Variable>,
+) -> Vec<CodeActionOrCommand> {
+    let Some(tokens) = linefeed::grammar::lexer::lexer()
+        .parse(source)
+        .into_output()
+    else {
+        return vec![];
+    };
+
+    let mut actions = Vec::new();
+    for diagnostic in diagnostics {
+        if let Some(name) = quoted(&diagnostic.message, "No such variable '", "' in scope") {
+            let mut candidates = variables_at(diagnostic.range.start)
+                .into_iter()
+                .map(|variable| variable.name)
+                .collect::<Vec<_>>();
+            candidates.extend(builtin_names(false));
+            actions.extend(did_you_mean(
+                uri,
+                diagnostic,
+                diagnostic.range,
+                name,
+                &candidates,
+            ));
+        } else if let Some(name) = quoted(&diagnostic.message, "Method \"", "\" is unknown") {
+            if let Some(span) = method_span(source, &tokens, diagnostic.range, name) {
+                let range = span_to_range(source, span);
+                actions.extend(did_you_mean(
+                    uri,
+                    diagnostic,
+                    range,
+                    name,
+                    &builtin_names(true),
+                ));
+            }
+        } else if let Some(edit) = insert_semicolon(source, &tokens, diagnostic.range.start) {
+            actions.push(quick_fix("Insert missing `;`", uri, diagnostic, edit, true));
+        }
+    }
+
+    actions
+}
+
+// Replaces `name` at `range` with each of the names similar to it, the most similar preferred
+fn did_you_mean(
+    uri: &Url,
+    diagnostic: &Diagnostic,
+    range: Range,
+    name: &str,
+    candidates: &[String],
+) -> Vec<CodeActionOrCommand> {
+    similar_names(name, candidates)
+        .into_iter()
+        .enumerate()
+        .map(|(i, similar)| {
+            let edit = TextEdit {
+                range,
+                new_text: similar.to_string(),
+            };
+            let title = format!("Did you mean `{similar}`?");
+            quick_fix(&title, uri, diagnostic, edit, i == 0)
+        })
+        .collect()
+}
+
+fn quick_fix(
+    title: &str,
+    uri: &Url,
+    diagnostic: &Diagnostic,
+    edit: TextEdit,
+    is_preferred: bool,
+) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        is_preferred: Some(is_preferred),
+        ..Default::default()
+    })
+}
+
+// The name in a compile error message, e.g. `x` in "No such variable 'x' in scope"
+fn quoted<'a>(message: &'a str, before: &str, after: &str) -> Option<&'a str> {
+    message.strip_prefix(before)?.strip_suffix(after)
+}
+
+// Builtin methods, or the functions and snippets that are called by name
+fn builtin_names(methods: bool) -> Vec<String> {
+    docs::builtins()
+        .into_iter()
+        .filter(|builtin| (builtin.kind == BuiltinKind::Method) == methods)
+        .map(|builtin| builtin.name.to_string())
+        .collect()
+}
+
+// The name of the method called by the expression with the error, which comes right after a `.`.
+// The receiver is compiled first, so an unknown method in it would have been reported instead
+fn method_span(source: &str, tokens: &[Spanned<Token>], range: Range, name: &str) -> Option<Span> {
+    let start = position_to_byte_offset(source, range.start);
+    let end = position_to_byte_offset(source, range.end);
+
+    tokens.windows(2).find_map(|pair| match pair {
+        [
+            Spanned(Token::Ctrl('.'), _),
+            Spanned(Token::Ident(ident), span),
+        ] if *ident == name && span.start >= start && span.end <= end => Some(*span),
+        _ => None,
+    })
+}
+
+// Up to three names within a few edits of `name`, the closest first
+fn similar_names<'a>(name: &str, candidates: &'a [String]) -> Vec<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    let mut similar = candidates
+        .iter()
+        .filter(|candidate| candidate.as_str() != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate.as_str()))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    similar.sort();
+    similar.dedup();
+    similar.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+// The Levenshtein distance, counting characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(a != *b);
+            row.push(substitute.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+
+    prev[b.len()]
+}
+
+/// Inserts a `;` after the token before a parse error at `position`, if the program then parses
+/// past that point, i.e. the statement before it was missing its `;`.
+fn insert_semicolon(
+    source: &str,
+    tokens: &[Spanned<Token>],
+    position: Position,
+) -> Option<TextEdit> {
+    let offset = position_to_byte_offset(source, position);
+    if first_parse_error(source, tokens) != Some(offset) {
+        return None;
+    }
+
+    let Spanned(previous, span) = tokens
+        .iter()
+        .rev()
+        .find(|Spanned(_, span)| span.end <= offset)?;
+    if matches!(previous, Token::Ctrl(';' | '{' | '(' | '[' | ',')) {
+        return None;
+    }
+
+    let fixed = format!("{};{}", &source[..span.end], &source[span.end..]);
+    let fixed_tokens = linefeed::grammar::lexer::lexer()
+        .parse(fixed.as_str())
+        .into_output()?;
+    if first_parse_error(&fixed, &fixed_tokens).is_some_and(|error| error <= offset + 1) {
+        return None;
+    }
+
+    Some(TextEdit {
+        range: span_to_range(source, Span::new(span.end, span.end)),
+        new_text: ";".to_string(),
+    })
+}
+
+fn first_parse_error<'src>(
+    source: &'src str,
+    tokens: &'src [Spanned<Token<'src>>],
+) -> Option<usize> {
+    let errors = linefeed::parse_tokens(source, tokens).err()?;
+    errors.iter().map(|error| error.span().start).min()
+}
+
+#[cfg(test)]
+mod tests {
+    use linefeed::chumsky::Parser as _;
+
+    use super::*;
+    use crate::semantic_tokens::rich_error_to_diagnostic;
+    use crate::test_utils::{apply_action, range_of, uri};
+
+    fn fixes(source: &str, diagnostic: Diagnostic, variables: &[&str]) -> Vec<(String, String)> {
+        let variables_at = |_| {
+            variables
+                .iter()
+                .map(|name| Variable {
+                    name: name.to_string(),
+                    args: None,
+                })
+                .collect()
+        };
+        quick_fixes(source, &uri(), &[diagnostic], variables_at)
+            .iter()
+            .map(|action| apply_action(source, action))
+            .collect()
+    }
+
+    fn parse_error(source: &str) -> Diagnostic {
+        let tokens = linefeed::grammar::lexer::lexer().parse(source).unwrap();
+        let errors = linefeed::parse_tokens(source, &tokens).unwrap_err();
+        rich_error_to_diagnostic(source, errors.into_iter().next().unwrap())
+    }
+
+    #[test]
+    fn unknown_variables_are_replaced_with_similar_ones() {
+        let source = "counter_total = 1;\nprint(counter_totl)";
+        let diagnostic = Diagnostic::new_simple(
+            range_of(source, "counter_totl"),
+            "No such variable 'counter_totl' in scope".to_string(),
+        );
+
+        let fixes = fixes(source, diagnostic, &["counter_total"]);
+        assert_eq!(
+            fixes[0],
+            (
+                "Did you mean `counter_total`?".to_string(),
+                "counter_total = 1;\nprint(counter_total)".to_string(),
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_methods_are_replaced_with_similar_ones() {
+        let source = "xs = [];\nxs.apend(1)";
+        let diagnostic = Diagnostic::new_simple(
+            range_of(source, "xs.apend(1)"),
+            "Method \"apend\" is unknown".to_string(),
+        );
+
+        let fixes = fixes(source, diagnostic, &["xs"]);
+        assert_eq!(
+            fixes[0],
+            (
+                "Did you mean `append`?".to_string(),
+                "xs = [];\nxs.append(1)".to_string(),
+            )
+        );
+    }
+
+    #[test]
+    fn names_without_similar_ones_get_no_fixes() {
+        let source = "print(zzzzzzzz)";
+        let diagnostic = Diagnostic::new_simple(
+            range_of(source, "zzzzzzzz"),
+            "No such variable 'zzzzzzzz' in scope".to_string(),
+        );
+
+        assert!(fixes(source, diagnostic, &["total"]).is_empty());
+    }
+
+    #[test]
+    fn missing_semicolons_are_inserted() {
+        let source = "x = 1\nprint(x);";
+
+        assert_eq!(
+            fixes(source, parse_error(source), &[]),
+            [(
+                "Insert missing `;`".to_string(),
+                "x = 1;\nprint(x);".to_string(),
+            )]
+        );
+    }
+}
@@ -1,8 +1,11 @@
 use std::collections::HashMap;
+use std::fs;
 use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::{Path, PathBuf};
 
 use linefeed::chumsky::Parser as _;
-use linefeed::compiler::{CompileError, Compiler};
+use linefeed::compiler::module::Module;
+use linefeed::compiler::{CompileError, Compiler, analysis};
 use linefeed::grammar::ast::{AstValue, Expr, Func, Pattern, Span, Spanned};
 use linefeed::grammar::lexer::Token;
 use tower_lsp::lsp_types::*;
@@ -27,12 +30,13 @@ impl IdentifierInfo {
     }
 }
 
-/// Convert byte offset to (line, column) position
+/// Convert byte offset to (line, column) position, where columns count UTF-16 code units like
+/// LSP positions do
 pub fn byte_offset_to_position(source: &str, offset: usize) -> (u32, u32) {
     let mut line = 0;
     let mut col = 0;
 
-    for (i, ch) in source.chars().enumerate() {
+    for (i, ch) in source.char_indices() {
         if i >= offset {
             break;
         }
@@ -40,13 +44,32 @@ pub fn byte_offset_to_position(source: &str, offset: usize) -> (u32, u32) {
             line += 1;
             col = 0;
         } else {
-            col += 1;
+            col += ch.len_utf16() as u32;
         }
     }
 
     (line, col)
 }
 
+/// Convert an LSP (line, column) position to a byte offset, clamped to the end of its line
+pub fn position_to_byte_offset(source: &str, position: Position) -> usize {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum();
+    let line = source[line_start..].split('\n').next().unwrap_or("");
+
+    let mut units = 0;
+    for (offset, ch) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + offset;
+        }
+        units += ch.len_utf16();
+    }
+    line_start + line.len()
+}
+
 /// Convert a byte span to an LSP Range
 pub fn span_to_range(source: &str, span: Span) -> Range {
     let (start_line, start_col) = byte_offset_to_position(source, span.start);
@@ -100,7 +123,20 @@ pub fn rich_error_to_diagnostic(
 }
 
 /// Convert CompileError to LSP Diagnostic
-pub fn compile_error_to_diagnostic(source: &str, error: CompileError) -> Diagnostic {
+pub fn compile_error_to_diagnostic(
+    source: &str,
+    ast: &Spanned<Expr>,
+    compiler: &Compiler,
+    base_dir: Option<&Path>,
+    error: CompileError,
+) -> Diagnostic {
+    if let Some((module, span)) = error
+        .span()
+        .and_then(|span| compiler.modules().locate(span))
+    {
+        return imported_error_to_diagnostic(source, ast, base_dir, module, span, error.msg());
+    }
+
     let (range, message) = match error {
         CompileError::Spanned { span, msg } => {
             let range = span_to_range(source, span);
@@ -131,31 +167,6 @@ pub fn compile_error_to_diagnostic(source: &str, error: CompileError) -> Diagnos
     }
 }
 
-/// Extract comments from source code
-/// Returns a list of (byte_offset, length) pairs for each comment
-fn extract_comments(source: &str) -> Vec<(usize, usize)> {
-    let mut comments = Vec::new();
-    let bytes = source.as_bytes();
-    let mut i = 0;
-
-    while i < bytes.len() {
-        // Look for '#' character
-        if bytes[i] == b'#' {
-            let start = i;
-            // Continue until newline or end of string
-            while i < bytes.len() && bytes[i] != b'\n' {
-                i += 1;
-            }
-            let length = i - start;
-            comments.push((start, length));
-        } else {
-            i += 1;
-        }
-    }
-
-    comments
-}
-
 /// Map lexer token to LSP semantic token type index
 pub fn token_to_semantic_type(token: &Token) -> Option<u32> {
     match token {
@@ -187,6 +198,7 @@ pub fn token_to_semantic_type(token: &Token) -> Option<u32> {
 
         // Regex
         Token::Regex(_) => Some(TOKEN_TYPE_REGEXP),
+        Token::Symbol(_) => Some(TOKEN_TYPE_ENUM_MEMBER),
 
         // Operators
         Token::Op(_) | Token::RangeExclusive | Token::RangeInclusive => Some(TOKEN_TYPE_OPERATOR),
@@ -196,6 +208,9 @@ pub fn token_to_semantic_type(token: &Token) -> Option<u32> {
 
         // Control characters - skip (punctuation)
         Token::Ctrl(_) => None,
+
+        // Comments
+        Token::Comment(_) => Some(TOKEN_TYPE_COMMENT),
     }
 }
 
@@ -348,7 +363,12 @@ fn visit_expr(expr: &Spanned<Expr>, symbols: &mut HashMap<Span, IdentifierInfo>)
             visit_expr(else_expr, symbols);
         }
 
-        Expr::Block(body) | Expr::Return(body) => {
+        Expr::Block(body)
+        | Expr::Return(body)
+        | Expr::Test(_, body)
+        | Expr::Spread(body)
+        | Expr::Defer(body)
+        | Expr::NullSafe(body) => {
             visit_expr(body, symbols);
         }
 
@@ -363,7 +383,7 @@ fn visit_expr(expr: &Spanned<Expr>, symbols: &mut HashMap<Span, IdentifierInfo>)
             visit_expr(body, symbols);
         }
 
-        Expr::Break | Expr::Continue => {}
+        Expr::Break | Expr::Continue | Expr::Import(_) | Expr::Enum(..) | Expr::Variant(..) => {}
 
         Expr::ListComprehension(expr, pattern, iter) => {
             // The pattern variables are declarations
@@ -395,9 +415,12 @@ fn visit_expr(expr: &Spanned<Expr>, symbols: &mut HashMap<Span, IdentifierInfo>)
 
 /// Visit a function and mark its parameters
 fn visit_func(func: &Func, symbols: &mut HashMap<Span, IdentifierInfo>) {
-    // Note: func.args is Vec<&str> without span information
-    // We can't directly mark parameters here without spans
-    // This will be handled during token generation by matching identifiers in the function signature
+    // Parameters are marked during token generation by matching identifiers in the function
+    // signature
+
+    for default in &func.defaults {
+        visit_expr(default, symbols);
+    }
 
     // Visit the function body
     visit_expr(&func.body, symbols);
@@ -426,15 +449,25 @@ struct TokenInfo {
 
 /// Safely parse and compile source code with panic protection
 /// Returns (symbol_table, diagnostics)
-/// Diagnostics include both parse errors and compilation errors
-pub fn safe_parse_and_compile(source: &str) -> (HashMap<Span, IdentifierInfo>, Vec<Diagnostic>) {
-    // Lex tokens
+/// Diagnostics include lexer, parse and compilation errors. Imports are resolved relative to the
+/// directory of `path`, like the CLI does, or the working directory without one
+pub fn safe_parse_and_compile(
+    source: &str,
+    path: Option<&Path>,
+) -> (HashMap<Span, IdentifierInfo>, Vec<Diagnostic>) {
+    // Lex tokens. Like the CLI, a program with lexer errors isn't parsed
     let tokens = match linefeed::grammar::lexer::lexer()
         .parse(source)
         .into_output_errors()
     {
-        (Some(tokens), _) => tokens,
-        (None, _) => return (HashMap::new(), vec![]),
+        (Some(tokens), errors) if errors.is_empty() => tokens,
+        (_, errors) => {
+            let diagnostics = errors
+                .into_iter()
+                .map(|err| rich_error_to_diagnostic(source, err.map_token(|c| c.to_string())))
+                .collect();
+            return (HashMap::new(), diagnostics);
+        }
     };
 
     // Parse with panic protection
@@ -476,17 +509,21 @@ pub fn safe_parse_and_compile(source: &str) -> (HashMap<Span, IdentifierInfo>, V
 
     // If we have a valid AST, try to compile it
     let compile_diagnostics = if let Some(ast) = ast {
-        match catch_unwind(AssertUnwindSafe(|| {
-            let mut compiler = Compiler::default();
-            compiler.compile(&ast)
-        })) {
+        let base_dir = path.and_then(Path::parent);
+        let mut compiler = match base_dir {
+            Some(dir) => Compiler::default().with_base_dir(dir),
+            None => Compiler::default(),
+        };
+        match catch_unwind(AssertUnwindSafe(|| compiler.compile(&ast))) {
             Ok(Ok(_program)) => {
                 // Successful compilation
                 vec![]
             }
             Ok(Err(err)) => {
                 // Compilation error - convert to diagnostic
-                vec![compile_error_to_diagnostic(source, err)]
+                vec![compile_error_to_diagnostic(
+                    source, &ast, &compiler, base_dir, err,
+                )]
             }
             Err(_) => {
                 // Compiler panic - create error diagnostic
@@ -515,10 +552,48 @@ pub fn safe_parse_and_compile(source: &str) -> (HashMap<Span, IdentifierInfo>, V
     (symbol_table, compile_diagnostics)
 }
 
+/// Errors in imported files are reported on the import of the file, or at the start of the
+/// document if the file is imported by one of its imports, pointing to where in the file they are
+fn imported_error_to_diagnostic(
+    source: &str,
+    ast: &Spanned<Expr>,
+    base_dir: Option<&Path>,
+    module: &Module,
+    span: Span,
+    msg: &str,
+) -> Diagnostic {
+    let import = analysis::find_scope_imports(ast)
+        .into_iter()
+        .find(|Spanned(import, _)| {
+            let path = base_dir.map_or(PathBuf::from(import), |dir| dir.join(import));
+            fs::canonicalize(&path).unwrap_or(path) == module.path
+        });
+    let range = match import {
+        Some(Spanned(_, span)) => span_to_range(source, span),
+        None => Range::default(),
+    };
+
+    let related_information = Url::from_file_path(&module.path).ok().map(|uri| {
+        vec![DiagnosticRelatedInformation {
+            location: Location::new(uri, span_to_range(&module.src, span)),
+            message: msg.to_string(),
+        }]
+    });
+
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        message: format!("In {}: {msg}", module.path.display()),
+        source: Some("linefeed".to_string()),
+        related_information,
+        ..Default::default()
+    }
+}
+
 /// Generate semantic tokens from source code
 pub fn generate_semantic_tokens(source: &str) -> Option<Vec<SemanticToken>> {
-    // Parse source with lexer
-    let tokens = match linefeed::grammar::lexer::lexer()
+    // Parse source with lexer, keeping the comments to highlight them
+    let tokens = match linefeed::grammar::lexer::lexer_with_comments()
         .parse(source)
         .into_output_errors()
     {
@@ -528,7 +603,7 @@ pub fn generate_semantic_tokens(source: &str) -> Option<Vec<SemanticToken>> {
     };
 
     // Try to parse AST for enhanced semantic analysis (with panic protection)
-    let (symbol_table, _diagnostics) = safe_parse_and_compile(source);
+    let (symbol_table, _diagnostics) = safe_parse_and_compile(source, None);
 
     // Collect all tokens (without delta encoding yet)
     let mut all_tokens: Vec<TokenInfo> = vec![];
@@ -579,7 +654,7 @@ pub fn generate_semantic_tokens(source: &str) -> Option<Vec<SemanticToken>> {
         let start = span.start;
         let end = span.end;
         let (line, col) = byte_offset_to_position(source, start);
-        let length = (end - start) as u32;
+        let length = source[start..end].encode_utf16().count() as u32;
 
         all_tokens.push(TokenInfo {
             line,
@@ -590,19 +665,6 @@ pub fn generate_semantic_tokens(source: &str) -> Option<Vec<SemanticToken>> {
         });
     }
 
-    // Extract and add comments
-    let comments = extract_comments(source);
-    for (start, length) in comments {
-        let (line, col) = byte_offset_to_position(source, start);
-        all_tokens.push(TokenInfo {
-            line,
-            col,
-            length: length as u32,
-            token_type: TOKEN_TYPE_COMMENT,
-            modifiers: 0,
-        });
-    }
-
     // Sort tokens by (line, col) for proper delta encoding
     all_tokens.sort_by(|a, b| match a.line.cmp(&b.line) {
         std::cmp::Ordering::Equal => a.col.cmp(&b.col),
@@ -0,0 +1,52 @@
+//! Helpers for testing the edits the server sends, by applying them to the source they were made
+//! for.
+
+use linefeed::grammar::ast::Span;
+use tower_lsp::lsp_types::*;
+
+use crate::semantic_tokens::{position_to_byte_offset, span_to_range};
+
+pub fn uri() -> Url {
+    Url::parse("file:///test.lf").unwrap()
+}
+
+/// The range of the first occurrence of `needle` in `source`.
+pub fn range_of(source: &str, needle: &str) -> Range {
+    let start = source
+        .find(needle)
+        .unwrap_or_else(|| panic!("{needle:?} is not in {source:?}"));
+    span_to_range(source, Span::new(start, start + needle.len()))
+}
+
+/// `source` with the edits applied, which mustn't overlap.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let mut edits = edits
+        .iter()
+        .map(|edit| {
+            let start = position_to_byte_offset(source, edit.range.start);
+            let end = position_to_byte_offset(source, edit.range.end);
+            (start, end, edit.new_text.as_str())
+        })
+        .collect::<Vec<_>>();
+    // Later edits are applied first, so the offsets of earlier ones stay the same
+    edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+
+    let mut edited = source.to_string();
+    for (start, end, new_text) in edits {
+        edited.replace_range(start..end, new_text);
+    }
+    edited
+}
+
+/// The title of a code action, and `source` with its edit to the test document applied.
+pub fn apply_action(source: &str, action: &CodeActionOrCommand) -> (String, String) {
+    let CodeActionOrCommand::CodeAction(action) = action else {
+        panic!("{action:?} is a command");
+    };
+    let changes = action.edit.as_ref().and_then(|edit| edit.changes.as_ref());
+    let edits = changes
+        .and_then(|changes| changes.get(&uri()))
+        .unwrap_or_else(|| panic!("{action:?} doesn't edit the test document"));
+
+    (action.title.clone(), apply_edits(source, edits))
+}
@@ -0,0 +1,78 @@
+//! Counting the heap allocations made while a program runs, for the run report, and the bytes in
+//! use, for memory limits.
+//!
+//! Counting only happens when the binary installs [`CountingAllocator`] as its global allocator,
+//! so the library doesn't impose it on anyone embedding the VM. Everything is counted per thread,
+//! so a run only sees its own allocations even when others run at the same time, as in batch
+//! mode.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    // Memory freed by another thread than the one allocating it is subtracted from the thread that
+    // frees it, which can make this negative
+    static ALLOCATED_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting every allocation and reallocation made through it, and the bytes
+/// currently allocated.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        track_alloc(System.alloc(layout), layout.size())
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        track_alloc(System.alloc_zeroed(layout), layout.size())
+    }
+
+    // Growing a collection reallocates it, which is exactly what reserving capacity avoids
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            add_bytes(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        add_bytes(-(layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+}
+
+fn track_alloc(ptr: *mut u8, size: usize) -> *mut u8 {
+    if !ptr.is_null() {
+        add_bytes(size as isize);
+    }
+    ptr
+}
+
+// The counters can't be reached while the thread is being torn down, when nothing is left to count
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+fn add_bytes(bytes: isize) {
+    let _ = ALLOCATED_BYTES.try_with(|count| count.set(count.get() + bytes));
+}
+
+/// The number of allocations made so far by the current thread, which stays at zero unless
+/// [`CountingAllocator`] is the global allocator.
+pub fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// The number of bytes currently allocated by the current thread, which stays at zero unless
+/// [`CountingAllocator`] is the global allocator.
+pub fn allocated_bytes() -> isize {
+    ALLOCATED_BYTES.with(Cell::get)
+}
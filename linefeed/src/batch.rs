@@ -0,0 +1,160 @@
+//! Running one program on many inputs at once.
+//!
+//! The program is parsed and compiled once, and the compiled instructions are shared by worker
+//! threads that each run it on one input at a time. Runtime values aren't thread-safe, so every
+//! run lowers its own copy of the instructions to bytecode in a fresh VM. Regexes are compiled
+//! only once though, as the compiled regexes are shared by all runs.
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use chumsky::Parser as _;
+
+use crate::{
+    compiler::{module::Modules, Compiler, Instruction, Program},
+    config::Config,
+    grammar::{ast::Span, lexer},
+    parse_tokens, pretty_print_error_at, pretty_print_errors,
+    vm::{BytecodeInterpreter, RuntimeError},
+    EXIT_FAILURE, EXIT_SUCCESS,
+};
+
+/// What a single run of a batch printed, and its exit status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchOutput {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+struct CompiledProgram<'src> {
+    src: &'src str,
+    program: Program<Instruction>,
+    modules: Modules,
+}
+
+/// Runs `src` once for each of `inputs`, which are given to the program as its standard input,
+/// and returns the outputs in the same order. The runs happen concurrently on up to `threads`
+/// threads, or one per CPU if it is `None`.
+///
+/// If the program fails to parse or compile, nothing is run and the error report is returned.
+pub fn run_batch<S: AsRef<[u8]> + Sync>(
+    src: &str,
+    config: &Config,
+    inputs: &[S],
+    threads: Option<usize>,
+) -> Result<Vec<BatchOutput>, Vec<u8>> {
+    let compiled = compile(src, config)?;
+
+    let threads = threads
+        .or_else(|| thread::available_parallelism().map(|n| n.get()).ok())
+        .unwrap_or(1)
+        .clamp(1, inputs.len().max(1));
+
+    // Workers take the next input until all have been run, so slow inputs don't hold up others
+    let next_input = AtomicUsize::new(0);
+    let mut outputs = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut outputs = Vec::new();
+                    loop {
+                        let index = next_input.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(index) else {
+                            break outputs;
+                        };
+                        outputs.push((index, compiled.run(config, input.as_ref())));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("batch worker panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    outputs.sort_by_key(|(index, _)| *index);
+    Ok(outputs.into_iter().map(|(_, output)| output).collect())
+}
+
+fn compile<'src>(src: &'src str, config: &Config) -> Result<CompiledProgram<'src>, Vec<u8>> {
+    let mut report = Vec::new();
+
+    let tokens = match lexer::lexer().parse(src).into_output_errors() {
+        (Some(tokens), e) if e.is_empty() => tokens,
+        (_, e) => {
+            pretty_print_errors(&mut report, src, e);
+            return Err(report);
+        }
+    };
+    let ast = match parse_tokens(src, &tokens) {
+        Ok(ast) => ast,
+        Err(errs) => {
+            pretty_print_errors(&mut report, src, errs);
+            return Err(report);
+        }
+    };
+
+    // Lowering can fail too, e.g. on invalid regex literals, which is checked once up front. The
+    // regexes compiled while doing so are kept, so the runs reuse them rather than compiling them
+    let mut compiler = Compiler::default()
+        .with_opt_level(config.opt_level)
+        .with_sandbox(config.sandbox);
+    let result = compiler.compile_ir(&ast).and_then(|mut program| {
+        program.regex_pool = program.clone().into_bytecode()?.regex_pool;
+        Ok(program)
+    });
+
+    match result {
+        Ok(program) => Ok(CompiledProgram {
+            src,
+            program,
+            modules: compiler.modules().clone(),
+        }),
+        Err(err) => {
+            let span = err.span().unwrap_or(Span::new(0, 0));
+            pretty_print_error_at(&mut report, src, compiler.modules(), span, err.msg());
+            Err(report)
+        }
+    }
+}
+
+impl CompiledProgram<'_> {
+    fn run(&self, config: &Config, input: &[u8]) -> BatchOutput {
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+
+        let bytecode = self
+            .program
+            .clone()
+            .into_bytecode()
+            .expect("a compiled program can be lowered to bytecode");
+        let result = BytecodeInterpreter::new(bytecode)
+            .with_config(config)
+            .with_handles(input, &mut stdout, &mut stderr)
+            .run();
+
+        let status = match result {
+            Ok(()) => EXIT_SUCCESS,
+            Err((_, RuntimeError::Exit(code))) => code,
+            Err((span, err)) => {
+                pretty_print_error_at(&mut stderr, self.src, &self.modules, span, err);
+                EXIT_FAILURE
+            }
+        };
+
+        BatchOutput {
+            status,
+            stdout,
+            stderr,
+        }
+    }
+}
+
+const _: () = {
+    const fn assert_sync<T: Sync>() {}
+    assert_sync::<CompiledProgram>();
+};
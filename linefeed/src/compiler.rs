@@ -1,24 +1,39 @@
 // TODO: Make all arguments generic/polymorphic, generate code for all possible types. Type inference.
 
-use std::{collections::HashMap, ops::RangeInclusive};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+    rc::Rc,
+};
 
 use crate::{
     compiler::{
-        ir_value::IrValue,
+        analysis::MatchPattern,
+        ir_value::{IrFunction, IrNumber, IrValue},
         method::Method,
         scoped_map::{ScopedMap, VarType},
         stdlib_fn::StdlibFn,
     },
-    grammar::ast::{AstValue, BinaryOp, Expr, Pattern, Span, Spanned, UnaryOp},
+    config::Sandbox,
+    grammar::ast::{AstValue, BinaryOp, Expr, Func, Pattern, Span, Spanned, UnaryOp},
+    lint::{self, Lint},
     vm::{
         bytecode::Bytecode,
-        runtime_value::{function::RuntimeFunction, number::RuntimeNumber},
+        runtime_value::{
+            function::Capture,
+            regex::{RegexModifiers, RegexPool},
+            variant::RuntimeVariant,
+        },
     },
 };
 
 pub mod analysis;
+pub mod cfg;
+pub mod inline;
 pub mod ir_value;
+pub mod jumps;
 pub mod method;
+pub mod module;
 pub mod register_manager;
 pub mod scoped_map;
 pub mod stdlib_fn;
@@ -32,6 +47,12 @@ pub enum Instruction {
     StoreLocal(usize),
     LoadGlobal(usize),
     StoreGlobal(usize),
+    LoadUpvalue(usize),
+    StoreUpvalue(usize),
+    /// Like `StoreLocal` followed by `Pop`, which is what assignments used as statements become.
+    PopToLocal(usize),
+    /// Like `StoreGlobal` followed by `Pop`.
+    PopToGlobal(usize),
 
     // Values
     Value(IrValue),
@@ -74,6 +95,7 @@ pub enum Instruction {
 
     // Unary operations
     Not,
+    Neg,
     BitwiseNot,
 
     // Control flow
@@ -88,17 +110,36 @@ pub enum Instruction {
     GetBasePtr,
     Call(usize),
     Return,
+    MakeClosure(Vec<Capture>),
+    /// Pops a function to call when the current function returns, and pushes null.
+    Defer,
+    /// Starts the body of a function inlined in place of a call with the given number of
+    /// arguments. The base pointer is moved to the arguments as for a call, and the old one is
+    /// kept in the slot below them, where a call would have the function.
+    EnterInlined(usize),
+    /// Ends the body of an inlined function like `Return`, except that execution continues with
+    /// the next instruction.
+    ExitInlined,
 
     // Standard library functions and built-ins
     StdlibCall(StdlibFn, usize),
     MethodCall(Method, usize),
     IsIn,
     Index,
+    /// Like `Index`, but pushes null if the key or index is missing.
+    IndexOrNull,
     SetIndex,
     NextIter,
     NextIterOrJump(Label),
     ToIter,
+    /// Pops an iterator and pushes an empty list with room for the values it has left.
+    ListWithSizeHint,
     CreateTuple(usize),
+    IsStr,
+    IsUninit,
+    IsNull,
+    IsTupleOfLen(usize),
+    IsListOfLen(usize),
 }
 
 use chumsky::span::Span as _;
@@ -106,37 +147,143 @@ use Instruction::*;
 
 type LoopId = Span;
 
-#[derive(Debug, Default)]
-pub struct Program<T> {
+#[derive(Debug, Default, Clone)]
+pub struct Program<T: Code> {
     pub instructions: Vec<T>,
     pub source_map: Vec<Span>,
+    pub regex_pool: RegexPool,
+    /// The values the instructions refer to by index.
+    pub constants: T::Constants,
+}
+
+/// What a [`Program`] is made of. Instructions hold their values themselves, and lowering them to
+/// bytecode moves the values into the program's constants.
+pub trait Code: Sized {
+    type Constants: std::fmt::Debug + Default + Clone;
+
+    /// Appends the constants of another program, whose `instructions` are about to be appended
+    /// too, so they must be changed to refer to the constants in their new place.
+    fn append_constants(
+        constants: &mut Self::Constants,
+        instructions: &mut [Self],
+        other: Self::Constants,
+    );
 }
 
-#[derive(Default)]
+// Left empty, as the program is built up from many small programs while compiling, and taking up
+// no space keeps the compiler's stack frames small
+impl Code for Instruction {
+    type Constants = ();
+
+    fn append_constants(_: &mut (), _: &mut [Self], _: ()) {}
+}
+
+const _: () = {
+    // Compiled programs are shared between the threads of a batch run, see `crate::batch`
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Program<Instruction>>();
+};
+
+#[derive(Default, Clone)]
 pub struct Compiler {
     vars: ScopedMap<String, usize>,
     registers: register_manager::RegisterManager,
     label_count: usize,
     loop_labels: HashMap<LoopId, (Label, Label)>,
     loop_stack: Vec<LoopId>,
+    // Variables captured by each function currently being compiled, innermost last
+    captures: Vec<Vec<(String, Capture)>>,
+    modules: module::Modules,
+    // The variants of the enums declared so far, by the name of the enum
+    enums: HashMap<String, Vec<String>>,
+    // The names the compiled programs define functions with, which shadow builtins
+    user_functions: HashSet<String>,
+    opt_level: u8,
+    sandbox: Sandbox,
+    warnings: Vec<CompileWarning>,
 }
 
 impl Compiler {
+    /// Makes imports in the compiled program resolve relative to `dir`, which should be the
+    /// directory of the program's file.
+    pub fn with_base_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.modules = module::Modules::new(Some(dir.into()));
+        self
+    }
+
+    /// Sets how aggressively compiled programs are optimised, see [`crate::config::Config`].
+    /// Programs compiled at level 1 or above assume that their globals are only assigned by
+    /// themselves, so the REPL, where later inputs can reassign them, keeps the default of 0.
+    pub fn with_opt_level(mut self, opt_level: u8) -> Self {
+        self.opt_level = opt_level;
+        self
+    }
+
+    /// Makes imports fail to compile when `sandbox` disallows file system access.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Takes the warnings about the programs compiled since the last call. Imported files aren't
+    /// checked, as they are programs of their own.
+    pub fn take_warnings(&mut self) -> Vec<CompileWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// The files imported by the programs compiled so far, for reporting errors in them.
+    pub fn modules(&self) -> &module::Modules {
+        &self.modules
+    }
+
     pub fn compile(&mut self, expr: &Spanned<Expr>) -> Result<Program<Bytecode>, CompileError> {
-        let program = self
-            .compile_allocation_for_all_vars_in_scope(expr)
+        self.compile_at(expr, 0)
+    }
+
+    /// Compiles a program to be placed after `start_pc` instructions of earlier programs in the
+    /// same VM, which is how the REPL runs each input. Variables assigned by earlier programs
+    /// compiled with this compiler are still available.
+    pub fn compile_at(
+        &mut self,
+        expr: &Spanned<Expr>,
+        start_pc: usize,
+    ) -> Result<Program<Bytecode>, CompileError> {
+        self.compile_ir(expr)?.into_bytecode_at(start_pc)
+    }
+
+    /// Compiles a program without lowering it to bytecode. Unlike bytecode, the instructions
+    /// don't contain runtime values, so they can be shared between threads, with each VM
+    /// lowering its own copy.
+    pub fn compile_ir(
+        &mut self,
+        expr: &Spanned<Expr>,
+    ) -> Result<Program<Instruction>, CompileError> {
+        self.user_functions
+            .extend(analysis::find_function_definitions(expr));
+        self.warnings.extend(lint::compiler_warnings(expr));
+
+        let mut program = self
+            .compile_allocation_for_all_vars_in_scope(expr)?
             .then_program(self.compile_expr(expr)?)
             .then_instruction(Stop, expr.span().to_end());
 
+        if self.opt_level >= 1 {
+            inline::inline_functions(&mut program, || self.new_label());
+            jumps::thread_jumps(&mut program);
+        }
+
         assert_eq!(program.instructions.len(), program.source_map.len());
 
         // TODO: Optimise the instuctions emitted by the above
         //  - [ ] Remove unnecessary additions
         //  - [ ] Don't do lookups on constants, just insert them
 
-        let bytecode_program = program.into_bytecode()?;
+        Ok(program)
+    }
 
-        Ok(bytecode_program)
+    /// The number of global variables allocated by the programs compiled so far.
+    pub fn num_globals(&self) -> usize {
+        self.vars.cur_scope_len()
     }
 
     fn compile_expr(&mut self, expr: &Spanned<Expr>) -> Result<Program<Instruction>, CompileError> {
@@ -156,13 +303,12 @@ impl Compiler {
                 .compile_expr(val)?
                 .then_program(self.compile_pattern_assignment(expr, pattern)?),
 
+            // Functions can use the variables of the functions enclosing them, which are
+            // captured as upvalues when the function value is created. See
+            // https://craftinginterpreters.com/closures.html
             Expr::Value(AstValue::Func(func)) => {
-                // TODO: Implement
-                //   - [x] Static function calls (depends only on the function arguments)
-                //   - [ ] Closures (depends on outer variables)
-                // See https://craftinginterpreters.com/closures.html
-
                 self.vars.start_scope();
+                self.captures.push(Vec::new());
 
                 for (offset, arg) in func.args.iter().enumerate() {
                     self.vars.set_local(arg.to_string(), offset);
@@ -171,9 +317,10 @@ impl Compiler {
                 let func_label = self.new_label();
                 let post_func_label = self.new_label();
 
-                let val = IrValue::Function(RuntimeFunction {
+                let val = IrValue::Function(IrFunction {
                     location: func_label,
                     arity: func.args.len(),
+                    min_arity: func.args.len() - func.defaults.len(),
                     is_memoized: func.is_memoized,
                 });
 
@@ -186,7 +333,8 @@ impl Compiler {
                         ],
                         expr.span(),
                     )
-                    .then_program(self.compile_allocation_for_all_vars_in_scope(&func.body))
+                    .then_program(self.compile_allocation_for_all_vars_in_scope(&func.body)?)
+                    .then_program(self.compile_default_args(func)?)
                     .then_program(self.compile_expr(&func.body)?)
                     .then_instructions(
                         vec![Return, Instruction::Label(post_func_label)],
@@ -195,12 +343,23 @@ impl Compiler {
 
                 self.vars.pop_scope();
 
-                program
+                let captures = self.captures.pop().expect("captures for function scope");
+                if captures.is_empty() {
+                    program
+                } else {
+                    let captures = captures.into_iter().map(|(_, capture)| capture).collect();
+                    program.then_instruction(MakeClosure(captures), expr.span())
+                }
             }
 
             Expr::Call(func, args) => {
                 if let Expr::Local(name) = &func.0 {
-                    if let Some(stdlib_fn) = StdlibFn::from_name(name) {
+                    // A function the program defines itself is called instead of the builtin with
+                    // its name. Other variables don't shadow builtins, so `sum = sum(nums)` works.
+                    let is_user_function = self.user_functions.contains(*name)
+                        && self.vars.get(&name.to_string()).is_some();
+                    if let Some(stdlib_fn) = StdlibFn::from_name(name).filter(|_| !is_user_function)
+                    {
                         return self.compile_stdlib_call(stdlib_fn, args, expr);
                     }
                 }
@@ -263,12 +422,15 @@ impl Compiler {
 
             Expr::Block(sub_expr) => self.compile_expr(sub_expr)?,
 
+            // Test blocks are skipped in regular runs; the test runner extracts and runs them
+            Expr::Test(..) => Program::from_instruction(Value(IrValue::Null), expr.span()),
+
             Expr::Unary(op, sub_expr) => {
                 let program = self.compile_expr(sub_expr)?;
 
                 let to_add = match op {
                     UnaryOp::Not => vec![Not],
-                    UnaryOp::Neg => vec![Value(IrValue::Num(RuntimeNumber::from(-1))), Mul],
+                    UnaryOp::Neg => vec![Neg],
                     UnaryOp::BitwiseNot => vec![BitwiseNot],
                 };
 
@@ -306,6 +468,25 @@ impl Compiler {
                     .then_instruction(Instruction::Label(label_end), expr.span())
             }
 
+            Expr::Binary(lhs, BinaryOp::Coalesce, rhs) => {
+                let label_end = self.new_label();
+
+                // A missing key or index is null here instead of an error, so `m[k] ?? default`
+                // doesn't have to build one only to throw it away
+                let lhs_program = match &lhs.0 {
+                    Expr::Index(value, index) => self
+                        .compile_expr(value)?
+                        .then_program(self.compile_expr(index)?)
+                        .then_instruction(IndexOrNull, index.span()),
+                    _ => self.compile_expr(lhs)?,
+                };
+
+                lhs_program
+                    .then_instructions(vec![Dup, IsNull, IfFalse(label_end), Pop], expr.span())
+                    .then_program(self.compile_expr(rhs)?)
+                    .then_instruction(Instruction::Label(label_end), expr.span())
+            }
+
             Expr::Binary(lhs, op, rhs) => {
                 let lhs_program = self.compile_expr(lhs)?;
                 let rhs_program = self.compile_expr(rhs)?;
@@ -327,6 +508,7 @@ impl Compiler {
                     BinaryOp::Range => Range,
                     BinaryOp::Xor => Xor,
                     BinaryOp::In => IsIn,
+                    BinaryOp::Coalesce => unreachable!("handled above"),
                     BinaryOp::BitwiseAnd => BitwiseAnd,
                     BinaryOp::BitwiseOr => BitwiseOr,
                     BinaryOp::BitwiseXor => BitwiseXor,
@@ -524,6 +706,45 @@ impl Compiler {
                 program
             }
 
+            Expr::Import(path) => self.compile_import(expr, path)?,
+
+            Expr::Enum(name, variants) => {
+                if let Some(dup) = variants
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, variant)| variants[..i].contains(variant).then_some(variant))
+                {
+                    return Err(CompileError::Spanned {
+                        span: expr.span(),
+                        msg: format!("Variant '{dup}' is declared more than once in enum '{name}'"),
+                    });
+                }
+
+                self.enums.insert(
+                    name.to_string(),
+                    variants.iter().map(|variant| variant.to_string()).collect(),
+                );
+
+                let values = variants
+                    .iter()
+                    .enumerate()
+                    .map(|(i, variant)| IrValue::Variant(RuntimeVariant::new(name, variant, i)))
+                    .collect();
+
+                self.compile_var_assign(
+                    expr,
+                    name,
+                    Program::from_instruction(Value(IrValue::List(values)), expr.span()),
+                )?
+            }
+
+            Expr::Variant(enum_name, name) => Program::from_instruction(
+                Value(IrValue::Variant(
+                    self.resolve_variant(expr, enum_name, name)?,
+                )),
+                expr.span(),
+            ),
+
             Expr::Break => self.compile_loop_jump("break", expr, |(_, end_label)| end_label)?,
             Expr::Continue => {
                 self.compile_loop_jump("continue", expr, |(cond_label, _)| cond_label)?
@@ -561,7 +782,8 @@ impl Compiler {
 
                 let program = register_loop
                     .then_program(register_iterable)
-                    .then_instruction(Value(IrValue::List(Vec::new())), expr.span())
+                    .then_program(self.compile_var_load(expr, &loop_vars.iterator_var)?)
+                    .then_instruction(ListWithSizeHint, expr.span())
                     .then_instruction(Instruction::Label(iter_label), expr.span())
                     .then_program(self.compile_var_load(expr, &loop_vars.iterator_var)?)
                     .then_instruction(NextIterOrJump(end_label), expr.span())
@@ -585,81 +807,60 @@ impl Compiler {
 
             Expr::MethodCall(target, method_name, args) => {
                 let target_program = self.compile_expr(target)?;
+                self.compile_method_call(expr, target_program, method_name, args)?
+            }
 
-                let method =
-                    Method::from_name(method_name).ok_or_else(|| CompileError::Spanned {
-                        span: expr.span(),
-                        msg: format!("Method {method_name:?} is unknown"),
-                    })?;
-
-                if let Err(msg) = validate_num_args(method.num_args(), args.len()) {
-                    return Err(CompileError::Spanned {
-                        span: expr.span(),
-                        msg: format!("Method {} {msg}", method.name()),
-                    });
-                }
+            Expr::NullSafe(inner) => {
+                let label_end = self.new_label();
 
-                let program = args
-                    .iter()
-                    .map(|arg| self.compile_expr(arg))
-                    .collect::<Result<Vec<_>, _>>()?
-                    .into_iter()
-                    .fold(target_program, Program::then_program);
+                // A null receiver is left on the stack as the result, skipping the rest
+                let (target, rest) = match &inner.0 {
+                    Expr::MethodCall(target, method_name, args) => (
+                        self.compile_expr(target)?,
+                        self.compile_method_call(inner, Program::new(), method_name, args)?,
+                    ),
+                    Expr::Index(value, index) => (
+                        self.compile_expr(value)?,
+                        self.compile_expr(index)?
+                            .then_instruction(IndexOrNull, index.span()),
+                    ),
+                    _ => unreachable!("only method calls and indexes are null-safe"),
+                };
 
-                program.then_instruction(MethodCall(method, args.len()), expr.span())
+                target
+                    .then_instructions(vec![Dup, IsNull, IfTrue(label_end)], expr.span())
+                    .then_program(rest)
+                    .then_instruction(Instruction::Label(label_end), expr.span())
             }
 
             Expr::Match(val, arms) => {
+                self.check_variants_covered(expr, arms)?;
+
                 let mut program = self.compile_expr(val)?;
 
                 let labels = arms.iter().map(|_| self.new_label()).collect::<Vec<_>>();
                 let (label_last, label_end) = (self.new_label(), self.new_label());
 
                 for (i, (pattern, body)) in arms.iter().enumerate() {
-                    let constant_opt = analysis::eval_simple_constant(pattern).map_err(|msg| {
-                        CompileError::Spanned {
-                            span: expr.span(),
-                            msg,
-                        }
-                    })?;
-
                     let cur_label = labels[i];
                     let next_label = labels.get(i + 1).copied().unwrap_or(label_last);
 
-                    // TODO: Implement more advanced constant types (e.g. lists, tuples, sets)
-                    // TODO: Implement pattern matching (incl. binding values like (x, y) => x + y)
-                    if let Some(constant) = constant_opt {
-                        let arm_program = Program::from_instructions(
-                            vec![
-                                Instruction::Label(cur_label),
-                                Dup,
-                                Value(constant),
-                                Eq,
-                                IfFalse(next_label),
-                            ],
-                            pattern.span(),
-                        )
-                        .then_program(self.compile_expr(body)?)
-                        .then_instruction(Goto(label_end), expr.span());
-
-                        program.extend(arm_program);
-                    } else if let Expr::Local(name) = &pattern.0 {
-                        // Catch-all identifier pattern - matches everything, binds value
-                        let arm_program = Program::from_instruction(
-                            Instruction::Label(cur_label),
-                            pattern.span(),
-                        )
-                        .then_program(self.compile_var_store(name, pattern)?)
-                        .then_program(self.compile_expr(body)?)
-                        .then_instruction(Goto(label_end), expr.span());
-
-                        program.extend(arm_program);
-                    } else {
-                        return Err(CompileError::Spanned {
-                            span: expr.span(),
-                            msg: "Pattern matching not implemented yet".to_string(),
-                        });
-                    }
+                    // Failing inside a structure pattern jumps to code placed after the arm,
+                    // which pops the parts of the value being matched before trying the next arm
+                    let mut cleanup = Program::new();
+                    let arm_program =
+                        Program::from_instruction(Instruction::Label(cur_label), pattern.span())
+                            .then_program(self.compile_match_pattern(
+                                pattern,
+                                next_label,
+                                expr.span(),
+                                &mut cleanup,
+                            )?)
+                            .then_program(self.compile_expr(body)?)
+                            .then_instruction(Goto(label_end), expr.span())
+                            .then_program(cleanup);
+
+                    program.extend(arm_program);
                 }
 
                 program.add_instructions(
@@ -667,6 +868,9 @@ impl Compiler {
                         Instruction::Label(label_last),
                         RuntimeError("No arm matched the value".to_string()),
                         Instruction::Label(label_end),
+                        // Every arm leaves its value on top of the matched value, which is no
+                        // longer needed
+                        SwapPop,
                     ],
                     expr.span(),
                 );
@@ -674,6 +878,17 @@ impl Compiler {
                 program
             }
 
+            Expr::Defer(func) => self
+                .compile_expr(func)?
+                .then_instruction(Defer, expr.span()),
+
+            Expr::Spread(_) => {
+                return Err(CompileError::Spanned {
+                    span: expr.span(),
+                    msg: "Spread arguments can only be passed to all, any, max and min".to_string(),
+                })
+            }
+
             Expr::ParseError => {
                 return Err(CompileError::Spanned {
                     msg: "Parse error".to_string(),
@@ -693,12 +908,218 @@ impl Compiler {
         Ok(instructions)
     }
 
+    /// Compiles the check of whether the matched value, which is on top of the stack, matches
+    /// `pattern`, jumping to `next_label` if it doesn't and assigning the pattern's bindings if it
+    /// does. The matched value is left on the stack either way.
+    ///
+    /// Code that must only run when a nested pattern fails is added to `cleanup`.
+    fn compile_match_pattern(
+        &mut self,
+        pattern: &Spanned<Expr>,
+        next_label: Label,
+        match_span: Span,
+        cleanup: &mut Program<Instruction>,
+    ) -> Result<Program<Instruction>, CompileError> {
+        let span = pattern.span();
+
+        let constant_opt =
+            analysis::eval_simple_constant(pattern).map_err(|msg| CompileError::Spanned {
+                span: match_span,
+                msg,
+            })?;
+
+        let program = match (MatchPattern::from_expr(pattern), constant_opt, &pattern.0) {
+            // Catch-all identifier pattern - matches everything, binds value
+            (Some(MatchPattern::Binding(name)), _, _) => self.compile_var_store(name, pattern)?,
+
+            (Some(MatchPattern::Regex(regex, modifiers)), _, _) => self.compile_regex_match(
+                pattern,
+                next_label,
+                IrValue::Regex(regex.to_string(), modifiers.clone()),
+                analysis::named_groups(regex)
+                    .into_iter()
+                    .map(|name| (IrValue::Str(name.clone()), name))
+                    .collect(),
+            )?,
+
+            // Prefixes are matched by a regex capturing the rest of the string, as slicing can't
+            // produce an empty string
+            (Some(MatchPattern::Prefix(prefix, rest)), _, _) => self.compile_regex_match(
+                pattern,
+                next_label,
+                IrValue::Regex(
+                    format!("(?s)^{}(.*)", regex::escape(prefix)),
+                    RegexModifiers::default(),
+                ),
+                vec![(IrValue::Num(IrNumber::Int(0)), rest.to_string())],
+            )?,
+
+            // Structure patterns are matched item by item even if they are constant, since they
+            // may contain regexes, and pairs of integers are 2D vectors rather than tuples
+            (None, _, Expr::Tuple(items) | Expr::List(items)) => {
+                let shape_check = match &pattern.0 {
+                    Expr::Tuple(_) => IsTupleOfLen(items.len()),
+                    _ => IsListOfLen(items.len()),
+                };
+                let mut program =
+                    Program::from_instructions(vec![Dup, shape_check, IfFalse(next_label)], span);
+
+                for (i, item) in items.iter().enumerate() {
+                    let item_failed = self.new_label();
+                    let index = Value(IrValue::Num(IrNumber::Int(i as isize)));
+
+                    program.add_instructions(vec![Dup, index, Index], item.span());
+                    program.extend(self.compile_match_pattern(
+                        item,
+                        item_failed,
+                        match_span,
+                        cleanup,
+                    )?);
+                    program.add_instruction(Pop, item.span());
+
+                    // Bindings match anything, so they never need to be cleaned up after
+                    if !matches!(
+                        MatchPattern::from_expr(item),
+                        Some(MatchPattern::Binding(_))
+                    ) {
+                        cleanup.add_instructions(
+                            vec![Instruction::Label(item_failed), Pop, Goto(next_label)],
+                            item.span(),
+                        );
+                    }
+                }
+
+                program
+            }
+
+            (None, _, Expr::Variant(enum_name, name)) => {
+                let variant = self.resolve_variant(pattern, enum_name, name)?;
+                Program::from_instructions(
+                    vec![
+                        Dup,
+                        Value(IrValue::Variant(variant)),
+                        Eq,
+                        IfFalse(next_label),
+                    ],
+                    span,
+                )
+            }
+
+            // TODO: Implement more advanced constant types (e.g. sets)
+            (None, Some(constant), _) => Program::from_instructions(
+                vec![Dup, Value(constant), Eq, IfFalse(next_label)],
+                span,
+            ),
+
+            _ => {
+                return Err(CompileError::Spanned {
+                    span,
+                    msg: "Unsupported pattern in match arm".to_string(),
+                })
+            }
+        };
+
+        Ok(program)
+    }
+
+    /// Fails if every arm of a `match` is a variant of the same enum, but some of the enum's
+    /// variants have no arm. A catch-all arm like `_` turns the check off.
+    fn check_variants_covered(
+        &self,
+        expr: &Spanned<Expr>,
+        arms: &[(Spanned<Expr>, Spanned<Expr>)],
+    ) -> Result<(), CompileError> {
+        let Some(covered) = arms
+            .iter()
+            .map(|(pattern, _)| match &pattern.0 {
+                Expr::Variant(enum_name, name) => Some((*enum_name, *name)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(());
+        };
+
+        let Some(&(enum_name, _)) = covered.first() else {
+            return Ok(());
+        };
+
+        // Unknown enums and variants are reported when the arms are compiled
+        let Some(variants) = self.enums.get(enum_name) else {
+            return Ok(());
+        };
+
+        if covered.iter().any(|&(other, _)| other != enum_name) {
+            return Ok(());
+        }
+
+        let missing = variants
+            .iter()
+            .filter(|variant| !covered.iter().any(|(_, name)| name == variant))
+            .map(|variant| format!("{enum_name}.{variant}"))
+            .collect::<Vec<_>>();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(CompileError::Spanned {
+                span: expr.span(),
+                msg: format!(
+                    "Match on enum '{enum_name}' has no arm for {}",
+                    missing.join(", ")
+                ),
+            })
+        }
+    }
+
+    /// Matches the value on top of the stack against `regex`, assigning each of `groups`, given
+    /// by their position in the tuple `find` returns, to its variable.
+    fn compile_regex_match(
+        &mut self,
+        pattern: &Spanned<Expr>,
+        next_label: Label,
+        regex: IrValue,
+        groups: Vec<(IrValue, String)>,
+    ) -> Result<Program<Instruction>, CompileError> {
+        let span = pattern.span();
+        // Only strings are matched, so other values fall through to the next arm instead of
+        // failing in `is_match`
+        let mut program = Program::from_instructions(
+            vec![
+                Dup,
+                IsStr,
+                IfFalse(next_label),
+                Dup,
+                Value(regex.clone()),
+                MethodCall(Method::IsMatch, 1),
+                IfFalse(next_label),
+            ],
+            span,
+        );
+
+        if groups.is_empty() {
+            return Ok(program);
+        }
+
+        program.add_instructions(vec![Dup, Value(regex), MethodCall(Method::Find, 1)], span);
+        // Each group is stored by its key in what `find` returns, i.e. the group's name in the map
+        // for named groups
+        for (key, name) in groups {
+            program.add_instructions(vec![Dup, Value(key), Index], span);
+            program.extend(self.compile_var_store(&name, pattern)?);
+            program.add_instruction(Pop, span);
+        }
+        program.add_instruction(Pop, span);
+
+        Ok(program)
+    }
+
     fn compile_var_store(
         &mut self,
         name: &str,
         expr: &Spanned<Expr>,
     ) -> Result<Program<Instruction>, CompileError> {
-        let var = self.vars.get(&name.to_string()).ok_or_else(|| {
+        let var = self.resolve_var(name).ok_or_else(|| {
             CompileError::Spanned {
                 msg: format!(
                     "Internal compiler bug: allocation for variable {name:?} should have been done before assignment"
@@ -708,34 +1129,98 @@ impl Compiler {
         })?;
 
         let instruction = match var {
-            VarType::Local(offset) => StoreLocal(*offset),
-            VarType::Global(addr) => StoreGlobal(*addr),
+            VarType::Local(offset) => StoreLocal(offset),
+            VarType::Global(addr) => StoreGlobal(addr),
+            VarType::Upvalue(index) => StoreUpvalue(index),
         };
 
         Ok(Program::from_instruction(instruction, expr.span()))
     }
 
+    fn resolve_variant(
+        &self,
+        expr: &Spanned<Expr>,
+        enum_name: &str,
+        name: &str,
+    ) -> Result<RuntimeVariant, CompileError> {
+        let Some(variants) = self.enums.get(enum_name) else {
+            let msg = if self.vars.get(&enum_name.to_string()).is_some() {
+                format!("'{enum_name}' is not an enum, did you mean to call `.{name}()`?")
+            } else {
+                format!("No such enum '{enum_name}', enums must be declared before they are used")
+            };
+            return Err(CompileError::Spanned {
+                msg,
+                span: expr.span(),
+            });
+        };
+
+        let index = variants
+            .iter()
+            .position(|variant| variant == name)
+            .ok_or_else(|| CompileError::Spanned {
+                msg: format!("Enum '{enum_name}' has no variant '{name}'"),
+                span: expr.span(),
+            })?;
+
+        Ok(RuntimeVariant::new(enum_name, name, index))
+    }
+
     fn compile_var_load(
         &mut self,
         expr: &Spanned<Expr>,
         name: &str,
     ) -> Result<Program<Instruction>, CompileError> {
         let var = self
-            .vars
-            .get(&name.to_string())
+            .resolve_var(name)
             .ok_or_else(|| CompileError::Spanned {
                 msg: format!("No such variable '{name}' in scope"),
                 span: expr.span(),
             })?;
 
         let instruction = match var {
-            VarType::Local(offset) => LoadLocal(*offset),
-            VarType::Global(addr) => LoadGlobal(*addr),
+            VarType::Local(offset) => LoadLocal(offset),
+            VarType::Global(addr) => LoadGlobal(addr),
+            VarType::Upvalue(index) => LoadUpvalue(index),
         };
 
         Ok(Program::from_instruction(instruction, expr.span()))
     }
 
+    // Finds where a variable is stored. Variables of enclosing functions are resolved to the index
+    // of the upvalue capturing them.
+    fn resolve_var(&mut self, name: &str) -> Option<VarType<usize>> {
+        let var = match self.vars.get(&name.to_string())? {
+            VarType::Local(offset) => VarType::Local(*offset),
+            VarType::Global(addr) => VarType::Global(*addr),
+            VarType::Upvalue(_) => VarType::Upvalue(self.resolve_upvalue(name, self.vars.depth())),
+        };
+
+        Some(var)
+    }
+
+    // Captures a variable from the function enclosing the one at scope `depth`, returning its index
+    // among the upvalues of the function at `depth`. If the variable belongs to a function further
+    // out, every function in between captures it too, so it can be handed down when each of the
+    // closures is created.
+    fn resolve_upvalue(&mut self, name: &str, depth: usize) -> usize {
+        let enclosing = depth - 1;
+        let capture = match self.vars.get_in_scope(enclosing, &name.to_string()) {
+            Some(offset) => Capture::Local(*offset),
+            None => Capture::Upvalue(self.resolve_upvalue(name, enclosing)),
+        };
+
+        // Scope 0 is the global scope, which isn't a function
+        let captures = &mut self.captures[depth - 1];
+        match captures.iter().position(|(captured, _)| captured == name) {
+            Some(index) => index,
+            None => {
+                captures.push((name.to_string(), capture));
+                captures.len() - 1
+            }
+        }
+    }
+
     fn compile_var_assign(
         &mut self,
         expr: &Spanned<Expr>,
@@ -745,13 +1230,47 @@ impl Compiler {
         Ok(value_program.then_program(self.compile_var_store(name, expr)?))
     }
 
+    // Arguments left out of a call are uninitialised when the function starts, and are assigned
+    // their default values in order, so a default can refer to the arguments before it
+    fn compile_default_args(&mut self, func: &Func) -> Result<Program<Instruction>, CompileError> {
+        let first_default = func.args.len() - func.defaults.len();
+        let mut program = Program::new();
+
+        for default in &func.defaults {
+            program = program.then_program(self.compile_allocation_for_all_vars_in_scope(default)?);
+        }
+
+        for (arg, default) in func.args[first_default..].iter().zip(&func.defaults) {
+            let span = default.span();
+            let given_label = self.new_label();
+
+            let value = self.compile_expr(default)?;
+            program = program
+                .then_program(self.compile_var_load(default, arg)?)
+                .then_instructions(vec![IsUninit, IfFalse(given_label)], span)
+                .then_program(self.compile_var_assign(default, arg, value)?)
+                .then_instructions(vec![Pop, Instruction::Label(given_label)], span);
+        }
+
+        Ok(program)
+    }
+
     fn compile_allocation_for_all_vars_in_scope(
         &mut self,
         expr: &Spanned<Expr>,
-    ) -> Program<Instruction> {
-        analysis::find_all_assignments(expr).into_iter().fold(
-            Program::new(),
-            |program, assignment| {
+    ) -> Result<Program<Instruction>, CompileError> {
+        let mut assignments = analysis::find_all_assignments(expr);
+
+        // Imports assign the variables of the imported file
+        for Spanned(path, span) in analysis::find_scope_imports(expr) {
+            let index = self.load_module(&path, span)?;
+            let exports = &self.modules.get(index).exports;
+            assignments.extend(exports.iter().map(|name| Spanned(name.clone(), span)));
+        }
+
+        Ok(assignments
+            .into_iter()
+            .fold(Program::new(), |program, assignment| {
                 if self.vars.get(&assignment).is_some() {
                     return program;
                 }
@@ -759,8 +1278,120 @@ impl Compiler {
                 self.vars
                     .set_local(assignment.to_string(), self.vars.cur_scope_len());
                 program.then_instruction(Value(IrValue::Uninit), assignment.span())
-            },
-        )
+            }))
+    }
+
+    fn load_module(&mut self, path: &str, span: Span) -> Result<usize, CompileError> {
+        if !self.sandbox.fs {
+            return Err(CompileError::Spanned {
+                span,
+                msg: format!(
+                    "Cannot import {path:?}, as the sandbox config disallows file system access"
+                ),
+            });
+        }
+
+        self.modules.load(path, span)
+    }
+
+    // Compiles the arguments and call of a method, on a target compiled into `target_program`
+    fn compile_method_call(
+        &mut self,
+        expr: &Spanned<Expr>,
+        target_program: Program<Instruction>,
+        method_name: &str,
+        args: &[Spanned<Expr>],
+    ) -> Result<Program<Instruction>, CompileError> {
+        let method = Method::from_name(method_name).ok_or_else(|| CompileError::Spanned {
+            span: expr.span(),
+            msg: format!("Method {method_name:?} is unknown"),
+        })?;
+
+        if let Err(msg) = validate_num_args(method.num_args(), args.len()) {
+            return Err(CompileError::Spanned {
+                span: expr.span(),
+                msg: format!("Method {} {msg}", method.name()),
+            });
+        }
+
+        let program = args
+            .iter()
+            .map(|arg| self.compile_expr(arg))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .fold(target_program, Program::then_program);
+
+        Ok(program.then_instruction(Instruction::MethodCall(method, args.len()), expr.span()))
+    }
+
+    // The imported file is compiled as a function returning its top-level variables, which is
+    // called right away. Only the compiler state shared by the whole program is kept while
+    // compiling it, so the file can't use the variables or loops of the importing code.
+    fn compile_import(
+        &mut self,
+        expr: &Spanned<Expr>,
+        path: &str,
+    ) -> Result<Program<Instruction>, CompileError> {
+        let span = expr.span();
+        let index = self.load_module(path, span)?;
+        let module = self.modules.get(index).clone();
+
+        if let Some(name) = module
+            .exports
+            .iter()
+            .find(|name| self.vars.get(*name).is_none())
+        {
+            return Err(CompileError::Spanned {
+                span,
+                msg: format!(
+                    "Cannot import '{path}' here, since its variable '{name}' is not allocated. Imports must be statements at the top level of a file or function"
+                ),
+            });
+        }
+
+        let func = module.with_ast(|ast| {
+            self.user_functions
+                .extend(analysis::find_function_definitions(&ast));
+            let module_span = ast.span();
+            let exports = module
+                .exports
+                .iter()
+                .map(|name| Spanned(Expr::Local(name), module_span))
+                .collect();
+            let body = Spanned(
+                Expr::Sequence(vec![ast, Spanned(Expr::Tuple(exports), module_span)]),
+                module_span,
+            );
+            let func = Spanned(
+                Expr::Value(AstValue::Func(Func {
+                    args: Vec::new(),
+                    arg_spans: Vec::new(),
+                    defaults: Vec::new(),
+                    body: Rc::new(body),
+                    is_memoized: false,
+                })),
+                span,
+            );
+
+            let vars = std::mem::take(&mut self.vars);
+            let captures = std::mem::take(&mut self.captures);
+            let loop_stack = std::mem::take(&mut self.loop_stack);
+            let func = self.compile_expr(&func);
+            self.vars = vars;
+            self.captures = captures;
+            self.loop_stack = loop_stack;
+            func
+        })??;
+
+        let targets = module
+            .exports
+            .iter()
+            .map(|name| Spanned(Pattern::Ident(name), span))
+            .collect();
+
+        Ok(func.then_instruction(Call(0), span).then_program(
+            self.compile_pattern_assignment(expr, &Spanned(Pattern::Sequence(targets), span))?,
+        ))
     }
 
     // Assumes that the value is currently on top of the stack.
@@ -779,7 +1410,7 @@ impl Compiler {
                 .into_iter()
                 .enumerate()
                 .fold(Program::new(), |program, (i, p)| {
-                    let index = Value(IrValue::Num(RuntimeNumber::from(i as isize)));
+                    let index = Value(IrValue::Num(IrNumber::Int(i as isize)));
                     program
                         .then_instructions(vec![Dup, index, Index], expr.span())
                         .then_program(p)
@@ -895,6 +1526,14 @@ impl Compiler {
         args: &[Spanned<Expr>],
         expr: &Spanned<Expr>,
     ) -> Result<Program<Instruction>, CompileError> {
+        if stdlib_fn.reduces_variadic_args() {
+            if args.iter().any(|arg| matches!(arg.0, Expr::Spread(_))) {
+                return self.compile_spread_call(stdlib_fn, args, expr);
+            }
+
+            check_unambiguous_variadic_args(&stdlib_fn, args)?;
+        }
+
         if let Err(msg) = validate_num_args(stdlib_fn.num_args(), args.len()) {
             return Err(CompileError::Spanned {
                 span: expr.span(),
@@ -911,6 +1550,61 @@ impl Compiler {
 
         Ok(program.then_instruction(StdlibCall(stdlib_fn, args.len()), expr.span()))
     }
+
+    /// Collects all arguments into a single list, which the function then reduces over.
+    fn compile_spread_call(
+        &mut self,
+        stdlib_fn: StdlibFn,
+        args: &[Spanned<Expr>],
+        expr: &Spanned<Expr>,
+    ) -> Result<Program<Instruction>, CompileError> {
+        let program = args.iter().try_fold(
+            Program::from_instruction(Value(IrValue::new_list()), expr.span()),
+            |acc, arg| {
+                Ok(match &arg.0 {
+                    Expr::Spread(iterable) => acc
+                        .then_program(self.compile_expr(iterable)?)
+                        .then_instruction(StdlibCall(StdlibFn::ToList, 1), arg.span())
+                        .then_instruction(Add, arg.span()),
+                    _ => acc
+                        .then_program(self.compile_expr(arg)?)
+                        .then_instruction(MethodCall(Method::Append, 1), arg.span()),
+                })
+            },
+        )?;
+
+        Ok(program.then_instruction(StdlibCall(stdlib_fn, 1), expr.span()))
+    }
+}
+
+// With several arguments, variadic functions treat each argument as a single value, so an
+// iterable literal mixed in with other values (like `max(0, [1, 2])`) is almost certainly meant to
+// be spread.
+fn check_unambiguous_variadic_args(
+    stdlib_fn: &StdlibFn,
+    args: &[Spanned<Expr>],
+) -> Result<(), CompileError> {
+    let is_iterable_literal = |arg: &Spanned<Expr>| {
+        matches!(
+            arg.0,
+            Expr::List(_) | Expr::ListComprehension(..) | Expr::Binary(_, BinaryOp::Range, _)
+        )
+    };
+
+    if args.len() < 2 || args.iter().all(is_iterable_literal) {
+        return Ok(());
+    }
+
+    match args.iter().find(|arg| is_iterable_literal(arg)) {
+        Some(arg) => Err(CompileError::Spanned {
+            span: arg.span(),
+            msg: format!(
+                "Ambiguous call to {}: this iterable is treated as a single value alongside the other arguments. Use `*` to spread its items into the arguments",
+                stdlib_fn.name()
+            ),
+        }),
+        None => Ok(()),
+    }
 }
 
 pub struct LoopVars {
@@ -964,12 +1658,14 @@ fn repeat_span(span: Span, count: usize) -> Vec<Span> {
 
 impl<T> Program<T>
 where
-    T: std::fmt::Debug,
+    T: Code + std::fmt::Debug,
 {
     pub fn new() -> Self {
         Program {
             instructions: Vec::new(),
             source_map: Vec::new(),
+            regex_pool: RegexPool::default(),
+            constants: Default::default(),
         }
     }
 
@@ -992,6 +1688,8 @@ where
         Program {
             source_map: vec![span],
             instructions: vec![instr],
+            regex_pool: RegexPool::default(),
+            constants: Default::default(),
         }
     }
 
@@ -999,6 +1697,8 @@ where
         Program {
             source_map: repeat_span(span, instrs.len()),
             instructions: instrs,
+            regex_pool: RegexPool::default(),
+            constants: Default::default(),
         }
     }
 
@@ -1022,10 +1722,16 @@ where
         self
     }
 
-    pub fn extend(&mut self, other: Self) {
+    pub fn extend(&mut self, mut other: Self) {
         assert_eq!(self.instructions.len(), self.source_map.len());
+        T::append_constants(
+            &mut self.constants,
+            &mut other.instructions,
+            other.constants,
+        );
         self.instructions.extend(other.instructions);
         self.source_map.extend(other.source_map);
+        self.regex_pool.extend(other.regex_pool);
     }
 
     pub fn then_program(mut self, other: Self) -> Self {
@@ -1067,5 +1773,14 @@ impl CompileError {
     }
 }
 
+/// Code that compiles but is most likely a mistake, found by one of the lints the compiler runs
+/// (see [`crate::lint::compiler_diagnostics`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileWarning {
+    pub lint: Lint,
+    pub span: Span,
+    pub msg: String,
+}
+
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct Label(pub usize);
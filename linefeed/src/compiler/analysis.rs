@@ -1,10 +1,87 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, rc::Rc};
 
 use crate::{
     compiler::{ir_value::IrValue, make_loop_vars},
-    grammar::ast::{Expr, Pattern, Spanned, UnaryOp},
+    grammar::{
+        ast::{AstValue, BinaryOp, Expr, Pattern, Spanned, UnaryOp},
+        snippets::for_each_child,
+    },
+    vm::runtime_value::regex::RegexModifiers,
 };
 
+/// The `match` arm patterns that do more than compare with a constant.
+#[derive(Debug, Clone, Copy)]
+pub enum MatchPattern<'a> {
+    /// `name` matches anything, binding it to `name`.
+    Binding(&'a str),
+    /// `r/regex/` matches strings the regex matches, binding its named groups.
+    Regex(&'a str, &'a RegexModifiers),
+    /// `"prefix" + rest` matches strings starting with `prefix`, binding what follows to `rest`.
+    Prefix(&'a str, &'a str),
+}
+
+impl<'a> MatchPattern<'a> {
+    pub fn from_expr(expr: &'a Spanned<Expr>) -> Option<Self> {
+        match &expr.0 {
+            Expr::Local(name) => Some(Self::Binding(name)),
+            Expr::Value(AstValue::Regex(regex, modifiers)) => Some(Self::Regex(regex, modifiers)),
+            Expr::Binary(lhs, BinaryOp::Add, rhs) => match (&lhs.0, &rhs.0) {
+                (Expr::Value(AstValue::Str(prefix)), Expr::Local(rest)) => {
+                    Some(Self::Prefix(prefix, rest))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The variables the pattern assigns when it matches.
+    pub fn bindings(&self) -> Vec<String> {
+        match self {
+            Self::Binding(name) | Self::Prefix(_, name) => vec![name.to_string()],
+            Self::Regex(regex, _) => named_groups(regex),
+        }
+    }
+}
+
+/// The variables a `match` arm pattern assigns when it matches, including those of the patterns
+/// nested in tuple and list patterns.
+pub fn match_pattern_bindings(pattern: &Spanned<Expr>) -> Vec<String> {
+    match (MatchPattern::from_expr(pattern), &pattern.0) {
+        (Some(pattern), _) => pattern.bindings(),
+        (None, Expr::Tuple(items) | Expr::List(items)) => {
+            items.iter().flat_map(match_pattern_bindings).collect()
+        }
+        (None, _) => vec![],
+    }
+}
+
+/// The names of the named groups of a regex, which are the keys of the map `find` returns for a
+/// match.
+pub fn named_groups(regex: &str) -> Vec<String> {
+    // Invalid regexes are reported when the regex itself is compiled
+    let Ok(regex) = regex::Regex::new(regex) else {
+        return vec![];
+    };
+
+    regex
+        .capture_names()
+        .flatten()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Finds the paths of the imports whose variables belong to the scope of `expr`, i.e. the imports
+/// that are statements of the scope itself.
+pub fn find_scope_imports(expr: &Spanned<Expr>) -> Vec<Spanned<String>> {
+    match &expr.0 {
+        Expr::Import(path) => vec![Spanned(path.clone(), expr.span())],
+        Expr::Sequence(items) => items.iter().flat_map(find_scope_imports).collect(),
+        Expr::Block(inner) => find_scope_imports(inner),
+        _ => vec![],
+    }
+}
+
 pub fn find_all_assignments(expr: &Spanned<Expr>) -> Vec<Spanned<String>> {
     fn find_all_assignments_inner<'src>(expr: &Spanned<Expr<'src>>) -> Vec<Spanned<String>> {
         fn resolve_assignment_target<'src>(
@@ -32,10 +109,20 @@ pub fn find_all_assignments(expr: &Spanned<Expr>) -> Vec<Spanned<String>> {
                 res
             }
 
-            Expr::Break | Expr::Continue | Expr::Value(_) | Expr::ParseError | Expr::Local(_) => {
+            // The variables assigned by imports are found by `find_scope_imports`, since finding
+            // them requires loading the imported file
+            Expr::Break
+            | Expr::Continue
+            | Expr::Value(_)
+            | Expr::ParseError
+            | Expr::Local(_)
+            | Expr::Import(_)
+            | Expr::Variant(..) => {
                 vec![]
             }
 
+            Expr::Enum(name, _) => vec![Spanned(name.to_string(), expr.span())],
+
             Expr::List(items) | Expr::Tuple(items) => {
                 items.iter().flat_map(find_all_assignments_inner).collect()
             }
@@ -97,7 +184,9 @@ pub fn find_all_assignments(expr: &Spanned<Expr>) -> Vec<Spanned<String>> {
                 res
             }
 
-            Expr::Unary(_, sub_expr) => find_all_assignments_inner(sub_expr),
+            Expr::Unary(_, sub_expr) | Expr::NullSafe(sub_expr) => {
+                find_all_assignments_inner(sub_expr)
+            }
 
             Expr::Binary(lhs, _, rhs) => {
                 let mut res = find_all_assignments_inner(lhs);
@@ -108,9 +197,11 @@ pub fn find_all_assignments(expr: &Spanned<Expr>) -> Vec<Spanned<String>> {
             Expr::Match(expr, arms) => {
                 let mut res = find_all_assignments_inner(expr);
                 for (cond, body) in arms {
-                    if let Expr::Local(name) = &cond.0 {
-                        res.push(Spanned(name.to_string(), cond.span()));
-                    }
+                    res.extend(
+                        match_pattern_bindings(cond)
+                            .into_iter()
+                            .map(|name| Spanned(name, cond.span())),
+                    );
                     res.extend(find_all_assignments_inner(cond));
                     res.extend(find_all_assignments_inner(body));
                 }
@@ -131,6 +222,12 @@ pub fn find_all_assignments(expr: &Spanned<Expr>) -> Vec<Spanned<String>> {
             Expr::Block(sub_expr) => find_all_assignments_inner(sub_expr),
 
             Expr::Return(val) => find_all_assignments_inner(val),
+
+            Expr::Test(_, body) => find_all_assignments_inner(body),
+
+            Expr::Spread(iterable) => find_all_assignments_inner(iterable),
+
+            Expr::Defer(func) => find_all_assignments_inner(func),
         }
     }
 
@@ -142,6 +239,119 @@ pub fn find_all_assignments(expr: &Spanned<Expr>) -> Vec<Spanned<String>> {
         .collect()
 }
 
+/// The names `expr` defines functions with in `fn name(...)` statements, including those defined
+/// in the bodies of its functions.
+pub fn find_function_definitions(expr: &Spanned<Expr>) -> HashSet<String> {
+    fn find_function_definitions_inner(expr: &Spanned<Expr>, names: &mut HashSet<String>) {
+        match &expr.0 {
+            Expr::Sequence(exprs) => exprs
+                .iter()
+                .for_each(|expr| find_function_definitions_inner(expr, names)),
+
+            Expr::Block(sub_expr) => find_function_definitions_inner(sub_expr, names),
+
+            Expr::Assign(target, val) => {
+                if let (Pattern::Ident(name), Expr::Value(AstValue::Func(func))) =
+                    (&target.0, &val.0)
+                {
+                    names.insert(name.to_string());
+                    find_function_definitions_inner(&func.body, names);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    let mut names = HashSet::new();
+    find_function_definitions_inner(expr, &mut names);
+    names
+}
+
+/// The variables `expr` reads before it has assigned them itself, in the order they are first read.
+/// Reads inside functions defined in `expr` count too, unless they read the function's own
+/// arguments or variables.
+pub fn free_variables(expr: &mut Spanned<Expr>) -> Vec<String> {
+    let mut free = FreeVariables::default();
+    free.visit(expr);
+    free.free
+}
+
+#[derive(Default)]
+struct FreeVariables {
+    assigned: HashSet<String>,
+    free: Vec<String>,
+}
+
+impl FreeVariables {
+    fn visit(&mut self, expr: &mut Spanned<Expr>) {
+        match &mut expr.0 {
+            Expr::Local(name) => {
+                if !self.assigned.contains(*name) && !self.free.iter().any(|free| free == name) {
+                    self.free.push(name.to_string());
+                }
+            }
+
+            Expr::Assign(pattern, val) => {
+                // A function can call itself by the name it is assigned to
+                let is_func = matches!(val.0, Expr::Value(AstValue::Func(_)));
+                if is_func {
+                    self.assign(pattern);
+                }
+                self.visit(val);
+                if let Pattern::Index(target, index) = &mut pattern.0 {
+                    self.visit(target);
+                    self.visit(index);
+                }
+                self.assign(pattern);
+            }
+
+            Expr::For(pattern, iterable, body)
+            | Expr::ListComprehension(body, pattern, iterable) => {
+                self.visit(iterable);
+                self.assign(pattern);
+                self.visit(body);
+            }
+
+            Expr::Match(target, arms) => {
+                self.visit(target);
+                for (pattern, body) in arms {
+                    self.assigned.extend(match_pattern_bindings(pattern));
+                    self.visit(body);
+                }
+            }
+
+            Expr::Value(AstValue::Func(func)) => {
+                func.defaults
+                    .iter_mut()
+                    .for_each(|default| self.visit(default));
+
+                let outer = self.assigned.clone();
+                self.assigned
+                    .extend(func.args.iter().map(|arg| arg.to_string()));
+                self.visit(Rc::make_mut(&mut func.body));
+                self.assigned = outer;
+            }
+
+            Expr::Enum(name, _) => {
+                self.assigned.insert(name.to_string());
+            }
+
+            _ => for_each_child(expr, |child| self.visit(child)),
+        }
+    }
+
+    fn assign(&mut self, pattern: &Spanned<Pattern>) {
+        match &pattern.0 {
+            Pattern::Ident(name) => {
+                self.assigned.insert(name.to_string());
+            }
+            Pattern::Sequence(patterns) => patterns.iter().for_each(|p| self.assign(p)),
+            Pattern::Index(..) | Pattern::Value(_) => {}
+        }
+    }
+}
+
 pub fn eval_simple_constant(expr: &Spanned<Expr>) -> Result<Option<IrValue>, String> {
     let res = match &expr.0 {
         Expr::Value(ast_val) => IrValue::try_from(ast_val).ok(),
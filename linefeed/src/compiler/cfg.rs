@@ -0,0 +1,239 @@
+//! Control-flow graphs of compiled programs, split into one graph per function.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+};
+
+use crate::{
+    compiler::Program,
+    vm::{bytecode::Bytecode, runtime_value::RuntimeValue},
+};
+
+/// A straight-line run of instructions that is only entered at its first instruction and only
+/// left after its last one.
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Execution falls through to the next instruction or jumps unconditionally.
+    Always,
+    /// Taken when the condition is true.
+    True,
+    /// Taken when the condition is false.
+    False,
+    /// Taken when an iterator has another element.
+    Next,
+    /// Taken when an iterator is exhausted.
+    Done,
+}
+
+/// The blocks of the top-level program or of a single function, by their start pc.
+#[derive(Debug)]
+pub struct FunctionGraph {
+    pub entry: usize,
+    pub blocks: BTreeMap<usize, BasicBlock>,
+}
+
+impl FunctionGraph {
+    pub fn name(&self) -> String {
+        if self.entry == 0 {
+            "main".to_string()
+        } else {
+            format!("fn@{}", self.entry)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    pub functions: Vec<FunctionGraph>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(program: &Program<Bytecode>) -> Self {
+        let instructions = &program.instructions;
+
+        // Functions are only created from constants, so every function body starts at the
+        // location of a function constant
+        let entries = std::iter::once(0)
+            .chain(program.constants.iter().filter_map(|val| match val {
+                RuntimeValue::Function(func) => Some(func.location),
+                _ => None,
+            }))
+            .filter(|&pc| pc < instructions.len())
+            .collect::<BTreeSet<_>>();
+
+        let mut leaders = entries.clone();
+        for (pc, instr) in instructions.iter().enumerate() {
+            if let Some(target) = jump_target(instr).filter(|&t| t < instructions.len()) {
+                leaders.insert(target);
+            }
+            if ends_block(instr) && pc + 1 < instructions.len() {
+                leaders.insert(pc + 1);
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        let starts = leaders.iter().copied().collect::<Vec<_>>();
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(instructions.len());
+            let last = &instructions[end - 1];
+
+            let mut successors = Vec::new();
+            let falls_through = !matches!(
+                last,
+                Bytecode::Stop | Bytecode::Return | Bytecode::Goto(_) | Bytecode::RuntimeError(_)
+            );
+            let (jump_kind, fall_kind) = match last {
+                Bytecode::IfTrue(_) => (EdgeKind::True, EdgeKind::False),
+                Bytecode::IfFalse(_) => (EdgeKind::False, EdgeKind::True),
+                Bytecode::NextIterOrJump(_) => (EdgeKind::Done, EdgeKind::Next),
+                _ => (EdgeKind::Always, EdgeKind::Always),
+            };
+            if let Some(target) = jump_target(last).filter(|&t| t < instructions.len()) {
+                successors.push(Edge {
+                    target,
+                    kind: jump_kind,
+                });
+            }
+            if falls_through && end < instructions.len() {
+                successors.push(Edge {
+                    target: end,
+                    kind: fall_kind,
+                });
+            }
+
+            blocks.insert(
+                start,
+                BasicBlock {
+                    start,
+                    end,
+                    successors,
+                },
+            );
+        }
+
+        let functions = entries
+            .iter()
+            .map(|&entry| {
+                let mut reachable = BTreeMap::new();
+                let mut stack = vec![entry];
+                while let Some(start) = stack.pop() {
+                    if reachable.contains_key(&start) {
+                        continue;
+                    }
+                    let Some(block) = blocks.get(&start).cloned() else {
+                        continue;
+                    };
+
+                    stack.extend(
+                        block
+                            .successors
+                            .iter()
+                            .map(|edge| edge.target)
+                            .filter(|target| !entries.contains(target)),
+                    );
+                    reachable.insert(start, block);
+                }
+
+                FunctionGraph {
+                    entry,
+                    blocks: reachable,
+                }
+            })
+            .collect();
+
+        Self { functions }
+    }
+
+    /// Renders the graph in Graphviz's DOT language, with a cluster for each function.
+    pub fn to_dot(&self, program: &Program<Bytecode>) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph program {{").unwrap();
+        writeln!(out, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        for function in &self.functions {
+            writeln!(out, "  subgraph cluster_{} {{", function.entry).unwrap();
+            writeln!(out, "    label=\"{}\";", function.name()).unwrap();
+
+            for block in function.blocks.values() {
+                let listing = (block.start..block.end)
+                    .map(|pc| {
+                        format!(
+                            "{pc}: {}\\l",
+                            describe(&program.instructions[pc], &program.constants)
+                        )
+                    })
+                    .collect::<String>();
+                writeln!(out, "    b{} [label=\"{listing}\"];", block.start).unwrap();
+            }
+
+            writeln!(out, "  }}").unwrap();
+        }
+
+        for function in &self.functions {
+            for block in function.blocks.values() {
+                for edge in &block.successors {
+                    let label = match edge.kind {
+                        EdgeKind::Always => String::new(),
+                        EdgeKind::True => " [label=\"true\"]".to_string(),
+                        EdgeKind::False => " [label=\"false\"]".to_string(),
+                        EdgeKind::Next => " [label=\"next\"]".to_string(),
+                        EdgeKind::Done => " [label=\"done\"]".to_string(),
+                    };
+                    writeln!(out, "  b{} -> b{}{label};", block.start, edge.target).unwrap();
+                }
+            }
+        }
+
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+fn jump_target(instr: &Bytecode) -> Option<usize> {
+    match instr {
+        Bytecode::Goto(target)
+        | Bytecode::IfTrue(target)
+        | Bytecode::IfFalse(target)
+        | Bytecode::NextIterOrJump(target) => Some(*target),
+        _ => None,
+    }
+}
+
+fn ends_block(instr: &Bytecode) -> bool {
+    jump_target(instr).is_some()
+        || matches!(
+            instr,
+            Bytecode::Stop | Bytecode::Return | Bytecode::RuntimeError(_)
+        )
+}
+
+// Constants are shown the way they're written in source code rather than as their Rust
+// representation, which is unreadable for e.g. functions
+fn describe(instr: &Bytecode, constants: &[RuntimeValue]) -> String {
+    let text = match instr {
+        Bytecode::LoadConst(index) => match &constants[*index as usize] {
+            RuntimeValue::Function(func) => format!("LoadConst(fn@{})", func.location),
+            val => format!("LoadConst({})", val.repr_string()),
+        },
+        Bytecode::CopyConst(index) => {
+            format!("CopyConst({})", constants[*index as usize].repr_string())
+        }
+        instr => format!("{instr:?}"),
+    };
+
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,307 @@
+//! Inlining of small functions into the places they are called from, which saves the overhead of
+//! the calls.
+//!
+//! Only functions declared in a global that is never assigned again are inlined, as every call
+//! through that global is then known to call the function, and only where the declaration is
+//! known to have run, as the global is uninitialized before. The inlined body keeps the stack layout
+//! of a call, with the slot that would hold the function keeping the caller's base pointer instead
+//! (see [`Instruction::EnterInlined`]), so the body's instructions can be copied as they are.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    compiler::{ir_value::IrValue, Instruction, Label, Program},
+    grammar::ast::Span,
+};
+
+/// Functions with more instructions than this are never inlined.
+const MAX_BODY_LEN: usize = 48;
+
+struct Inlinable {
+    arity: usize,
+    // The instructions of the function, from its first instruction up to and including the
+    // `Return` ending it
+    body: std::ops::Range<usize>,
+    // The `StoreGlobal` declaring the function
+    store: usize,
+}
+
+/// Replaces calls of small, non-recursive and non-memoized functions with the functions' bodies.
+/// Labels in the copied bodies are replaced with labels from `new_label`, as each copy needs its
+/// own.
+pub fn inline_functions(program: &mut Program<Instruction>, mut new_label: impl FnMut() -> Label) {
+    let positions = top_level_positions(&program.instructions);
+    let functions = find_inlinable_functions(&program.instructions, &positions);
+    if functions.is_empty() {
+        return;
+    }
+
+    // Copies of bodies can at most double the size of the program
+    let mut budget = program.instructions.len();
+
+    // The function loads and calls to replace, by their index
+    let mut loads = Vec::new();
+    let mut calls = HashMap::new();
+    for (i, instruction) in program.instructions.iter().enumerate() {
+        let Instruction::LoadGlobal(addr) = instruction else {
+            continue;
+        };
+        let Some(func) = functions.get(addr) else {
+            continue;
+        };
+        // Loads that can run before the declaration find the global uninitialized
+        if positions[i] <= func.store {
+            continue;
+        }
+        let Some(call) = find_call(&program.instructions[i + 1..], func.arity) else {
+            continue;
+        };
+
+        let cost = func.body.len() + 2;
+        if cost > budget {
+            break;
+        }
+        budget -= cost;
+
+        loads.push(i);
+        calls.insert(i + 1 + call, func);
+    }
+
+    let instructions = std::mem::take(&mut program.instructions);
+    let source_map = std::mem::take(&mut program.source_map);
+    let body_of = |func: &Inlinable| {
+        instructions[func.body.clone()]
+            .iter()
+            .cloned()
+            .zip(source_map[func.body.clone()].iter().copied())
+            .collect::<Vec<_>>()
+    };
+
+    for (i, (instruction, span)) in instructions.iter().zip(&source_map).enumerate() {
+        if loads.binary_search(&i).is_ok() {
+            // The slot of the function only holds the caller's base pointer once inlined
+            program.add_instruction(Instruction::Value(IrValue::Null), *span);
+        } else if let Some(func) = calls.get(&i) {
+            for (instruction, span) in
+                inlined_body(body_of(func), func.arity, *span, &mut new_label)
+            {
+                program.add_instruction(instruction, span);
+            }
+        } else {
+            program.add_instruction(instruction.clone(), *span);
+        }
+    }
+}
+
+// Functions are declared as
+//
+//     Value(function) Goto(after) Label(function) ...body... Return Label(after) StoreGlobal(addr)
+//
+// so the ones stored in a global are found by that pattern, keyed by the address of the global
+fn find_inlinable_functions(
+    instructions: &[Instruction],
+    positions: &[usize],
+) -> HashMap<usize, Inlinable> {
+    let mut functions = HashMap::new();
+
+    for (i, instruction) in instructions.iter().enumerate() {
+        let Instruction::Value(IrValue::Function(func)) = instruction else {
+            continue;
+        };
+        if func.is_memoized || func.min_arity != func.arity {
+            continue;
+        }
+        let (Some(Instruction::Goto(after)), Some(Instruction::Label(start))) =
+            (instructions.get(i + 1), instructions.get(i + 2))
+        else {
+            continue;
+        };
+        if *start != func.location {
+            continue;
+        }
+
+        let body_start = i + 3;
+        let Some(body_end) = instructions[body_start..]
+            .iter()
+            .take(MAX_BODY_LEN + 1)
+            .position(
+                |instruction| matches!(instruction, Instruction::Label(label) if label == after),
+            )
+            .map(|len| body_start + len)
+        else {
+            continue;
+        };
+        let Some(Instruction::StoreGlobal(addr)) = instructions.get(body_end + 1) else {
+            continue;
+        };
+
+        let body = &instructions[body_start..body_end];
+        let is_inlinable = body.iter().all(|instruction| match instruction {
+            // Recursion, nested functions and closures need a real call
+            Instruction::LoadGlobal(other) => other != addr,
+            Instruction::Value(IrValue::Function(_))
+            | Instruction::MakeClosure(_)
+            | Instruction::LoadUpvalue(_)
+            | Instruction::StoreUpvalue(_)
+            | Instruction::Defer => false,
+            _ => true,
+        });
+
+        if is_inlinable {
+            functions.insert(
+                *addr,
+                Inlinable {
+                    arity: func.arity,
+                    body: body_start..body_end,
+                    store: body_end + 1,
+                },
+            );
+        }
+    }
+
+    // A global assigned anywhere else might not hold the function when it is called
+    functions.retain(|addr, _| {
+        instructions
+            .iter()
+            .filter(|instruction| match instruction {
+                Instruction::StoreGlobal(other) | Instruction::PopToGlobal(other) => other == addr,
+                _ => false,
+            })
+            .count()
+            == 1
+    });
+
+    // The declaration must run before everything after it, so it can't be inside another
+    // function, nor be jumped past, like it is in a branch or a loop
+    functions.retain(|_, func| {
+        positions[func.store] == func.store && !can_be_skipped(instructions, func.store)
+    });
+
+    functions
+}
+
+// For each instruction, the instruction at the top level of the program after which it runs. That
+// is the instruction itself at the top level, while the body of a function runs when the function
+// is called, which is after the outermost function around it is declared.
+fn top_level_positions(instructions: &[Instruction]) -> Vec<usize> {
+    // The declarations of the functions around the current instruction, outermost first, along
+    // with the labels their bodies end at
+    let mut enclosing: Vec<(usize, Label)> = Vec::new();
+    let mut positions = Vec::with_capacity(instructions.len());
+
+    for (i, instruction) in instructions.iter().enumerate() {
+        if let Instruction::Label(label) = instruction {
+            if enclosing.last().is_some_and(|(_, after)| after == label) {
+                enclosing.pop();
+            }
+        }
+        positions.push(enclosing.first().map_or(i, |&(declaration, _)| declaration));
+
+        if let (Instruction::Value(IrValue::Function(_)), Some(Instruction::Goto(after))) =
+            (instruction, instructions.get(i + 1))
+        {
+            enclosing.push((i, *after));
+        }
+    }
+
+    positions
+}
+
+// Whether a jump before the instruction at `index` can go past it. Jumps after it that go back
+// before it have to pass it again, or take one of the jumps before it, to get past it.
+fn can_be_skipped(instructions: &[Instruction], index: usize) -> bool {
+    let labels_after = instructions[index + 1..]
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Label(label) => Some(*label),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    instructions[..index]
+        .iter()
+        .any(|instruction| match instruction {
+            Instruction::Goto(target)
+            | Instruction::IfTrue(target)
+            | Instruction::IfFalse(target)
+            | Instruction::NextIterOrJump(target) => labels_after.contains(target),
+            _ => false,
+        })
+}
+
+// Finds the call of a function just loaded onto the stack, given the instructions after the load,
+// by following how many values they leave above the function. Only arguments made of the
+// instructions in `stack_effect` are followed, as they are straight-line code.
+fn find_call(instructions: &[Instruction], arity: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, instruction) in instructions.iter().enumerate() {
+        if let Instruction::Call(num_args) = instruction {
+            if *num_args == depth {
+                return (*num_args == arity).then_some(i);
+            }
+        }
+
+        let (pops, pushes) = stack_effect(instruction)?;
+        depth = depth.checked_sub(pops)? + pushes;
+    }
+
+    None
+}
+
+// How many values an instruction pops and pushes
+fn stack_effect(instruction: &Instruction) -> Option<(usize, usize)> {
+    use Instruction::*;
+
+    let effect = match instruction {
+        Value(_) | ConstantInt(_) | LoadLocal(_) | LoadGlobal(_) | LoadUpvalue(_) => (0, 1),
+        Not | Neg | BitwiseNot => (1, 1),
+        Add | Sub | Mul | Div | DivFloor | Mod | Pow | Eq | NotEq | Less | LessEq | Greater
+        | GreaterEq | Range | Xor | BitwiseAnd | BitwiseOr | BitwiseXor | LeftShift
+        | RightShift | Index | IndexOrNull | IsIn => (2, 1),
+        Call(num_args) | MethodCall(_, num_args) => (num_args + 1, 1),
+        StdlibCall(_, num_args) | CreateTuple(num_args) => (*num_args, 1),
+        _ => return None,
+    };
+
+    Some(effect)
+}
+
+fn inlined_body(
+    body: Vec<(Instruction, Span)>,
+    arity: usize,
+    call_span: Span,
+    new_label: &mut impl FnMut() -> Label,
+) -> Vec<(Instruction, Span)> {
+    let mut labels = HashMap::new();
+    for (instruction, _) in &body {
+        if let Instruction::Label(label) = instruction {
+            labels.insert(*label, new_label());
+        }
+    }
+    let end_label = new_label();
+    let relabel = |label: &Label| labels.get(label).copied().unwrap_or(*label);
+
+    let mut inlined = vec![(Instruction::EnterInlined(arity), call_span)];
+    let last = body.len().saturating_sub(1);
+    for (i, (instruction, span)) in body.into_iter().enumerate() {
+        let instruction = match instruction {
+            Instruction::Return => {
+                inlined.push((Instruction::ExitInlined, span));
+                if i == last {
+                    continue;
+                }
+                Instruction::Goto(end_label)
+            }
+            Instruction::Label(label) => Instruction::Label(relabel(&label)),
+            Instruction::Goto(label) => Instruction::Goto(relabel(&label)),
+            Instruction::IfTrue(label) => Instruction::IfTrue(relabel(&label)),
+            Instruction::IfFalse(label) => Instruction::IfFalse(relabel(&label)),
+            Instruction::NextIterOrJump(label) => Instruction::NextIterOrJump(relabel(&label)),
+            instruction => instruction,
+        };
+        inlined.push((instruction, span));
+    }
+    inlined.push((Instruction::Label(end_label), call_span));
+
+    inlined
+}
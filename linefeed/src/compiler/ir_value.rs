@@ -1,7 +1,12 @@
+use std::rc::Rc;
+
 use crate::{
     compiler::Label,
     grammar::ast::AstValue,
-    vm::runtime_value::{function::RuntimeFunction, number::RuntimeNumber, regex::RegexModifiers},
+    vm::runtime_value::{
+        number::RuntimeNumber, regex::RegexModifiers, symbol::RuntimeSymbol,
+        variant::RuntimeVariant,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -10,14 +15,66 @@ pub enum IrValue {
     Uninit,
     Bool(bool),
     Int(isize),
-    Num(RuntimeNumber),
+    Num(IrNumber),
     Str(String),
     Regex(String, RegexModifiers),
+    Symbol(RuntimeSymbol),
+    Variant(RuntimeVariant),
     List(Vec<IrValue>),
     Tuple(Vec<IrValue>),
     Set(Vec<IrValue>),
     Map(Vec<(IrValue, IrValue)>),
-    Function(RuntimeFunction<Label>),
+    Function(IrFunction),
+}
+
+/// A number constant. Unlike [`RuntimeNumber`], it owns its big integers, so programs can be shared
+/// between threads, and it becomes a [`RuntimeNumber`] when the program is lowered to bytecode.
+#[derive(Debug, Clone)]
+pub enum IrNumber {
+    Int(isize),
+    BigInt(rug::Integer),
+    Float(f64),
+}
+
+/// A function value before it is placed in the VM, where it gets its address and upvalues.
+#[derive(Debug, Clone)]
+pub struct IrFunction {
+    pub arity: usize,
+    pub min_arity: usize,
+    pub location: Label,
+    pub is_memoized: bool,
+}
+
+impl IrNumber {
+    pub fn neg(&self) -> Self {
+        match self {
+            IrNumber::Int(i) => i
+                .checked_neg()
+                .map(IrNumber::Int)
+                .unwrap_or_else(|| IrNumber::BigInt(-rug::Integer::from(*i))),
+            IrNumber::BigInt(i) => IrNumber::BigInt(-i.clone()),
+            IrNumber::Float(f) => IrNumber::Float(-f),
+        }
+    }
+}
+
+impl From<i64> for IrNumber {
+    fn from(i: i64) -> Self {
+        match isize::try_from(i) {
+            Ok(small) => IrNumber::Int(small),
+            Err(_) => IrNumber::BigInt(i.into()),
+        }
+    }
+}
+
+impl From<IrNumber> for RuntimeNumber {
+    fn from(n: IrNumber) -> Self {
+        match n {
+            IrNumber::Int(i) => RuntimeNumber::SmallInt(i),
+            IrNumber::BigInt(i) => RuntimeNumber::BigInt(Rc::new(i)),
+            IrNumber::Float(f) => RuntimeNumber::Float(f),
+        }
+    }
 }
 
 impl IrValue {
@@ -41,12 +98,13 @@ impl TryFrom<&AstValue<'_>> for IrValue {
         let res = match val {
             AstValue::Null => IrValue::Null,
             AstValue::Bool(b) => IrValue::Bool(*b),
-            AstValue::Int(n) => IrValue::Num(RuntimeNumber::from(*n)),
-            AstValue::Float(n) => IrValue::Num(RuntimeNumber::Float(*n)),
+            AstValue::Int(n) => IrValue::Num(IrNumber::from(*n)),
+            AstValue::Float(n) => IrValue::Num(IrNumber::Float(*n)),
             AstValue::Str(s) => IrValue::Str(s.to_string()),
             AstValue::List(xs) => IrValue::List(collect_try_from(xs)?),
             AstValue::Tuple(xs) => IrValue::Tuple(collect_try_from(xs)?),
             AstValue::Regex(s, modifiers) => IrValue::Regex(s.clone(), modifiers.clone()),
+            AstValue::Symbol(name) => IrValue::Symbol(RuntimeSymbol::new(name)),
             AstValue::Func(_) => return Err("Functions are not simple values".to_string()),
         };
 
@@ -0,0 +1,129 @@
+//! Cleanup of the jumps and labels left behind by compiling each expression on its own, such as
+//! jumps to other jumps and code after a `return` or `break`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::compiler::{ir_value::IrValue, Instruction, Label, Program};
+
+/// Makes jumps go straight to where they end up, and removes labels nothing jumps to along with
+/// code that can't be reached. This is repeated until nothing changes, as each step can make way
+/// for the others.
+pub fn thread_jumps(program: &mut Program<Instruction>) {
+    loop {
+        let retargeted = retarget_jumps(program);
+        let removed = remove_dead_code(program);
+        if !retargeted && !removed {
+            break;
+        }
+    }
+}
+
+// Jumps to a label at a `Goto` are changed to jump where the `Goto` does
+fn retarget_jumps(program: &mut Program<Instruction>) -> bool {
+    let mut forwards = HashMap::new();
+    for (i, instruction) in program.instructions.iter().enumerate() {
+        if let Instruction::Label(label) = instruction {
+            let next = program.instructions[i + 1..]
+                .iter()
+                .find(|instruction| !matches!(instruction, Instruction::Label(_)));
+            if let Some(Instruction::Goto(target)) = next {
+                forwards.insert(*label, *target);
+            }
+        }
+    }
+
+    // Loops without a way out, like `Label(a) Goto(a)`, are only followed once around
+    let resolve = |mut label: Label| {
+        let mut seen = HashSet::new();
+        while let Some(&next) = forwards.get(&label) {
+            if !seen.insert(label) {
+                break;
+            }
+            label = next;
+        }
+        label
+    };
+
+    let mut changed = false;
+    for target in program.instructions.iter_mut().filter_map(jump_target) {
+        let resolved = resolve(*target);
+        if resolved != *target {
+            *target = resolved;
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn remove_dead_code(program: &mut Program<Instruction>) -> bool {
+    let mut referenced = HashSet::new();
+    for instruction in &mut program.instructions {
+        if let Instruction::Value(IrValue::Function(func)) = instruction {
+            referenced.insert(func.location);
+        } else if let Some(target) = jump_target(instruction) {
+            referenced.insert(*target);
+        }
+    }
+
+    let instructions = std::mem::take(&mut program.instructions);
+    let source_map = std::mem::take(&mut program.source_map);
+
+    let mut changed = false;
+    let mut reachable = true;
+    for (i, (instruction, span)) in instructions.iter().zip(&source_map).enumerate() {
+        let jumps_to_next = |target: &Label| {
+            instructions[i + 1..]
+                .iter()
+                .map_while(|instruction| match instruction {
+                    Instruction::Label(label) => Some(label),
+                    _ => None,
+                })
+                .any(|label| label == target)
+        };
+
+        let kept = match instruction {
+            Instruction::Label(label) if referenced.contains(label) => {
+                reachable = true;
+                Some(instruction.clone())
+            }
+            Instruction::Label(_) => None,
+            _ if !reachable => None,
+            Instruction::Goto(target) if jumps_to_next(target) => None,
+            // Both ways lead to the same place, but the condition must still be popped
+            Instruction::IfTrue(target) | Instruction::IfFalse(target) if jumps_to_next(target) => {
+                changed = true;
+                Some(Instruction::Pop)
+            }
+            _ => Some(instruction.clone()),
+        };
+
+        match kept {
+            Some(kept) => {
+                if matches!(
+                    kept,
+                    Instruction::Goto(_)
+                        | Instruction::Return
+                        | Instruction::Stop
+                        | Instruction::RuntimeError(_)
+                ) {
+                    reachable = false;
+                }
+                program.add_instruction(kept, *span);
+            }
+            None => changed = true,
+        }
+    }
+
+    changed
+}
+
+fn jump_target(instruction: &mut Instruction) -> Option<&mut Label> {
+    match instruction {
+        Instruction::Goto(target)
+        | Instruction::IfTrue(target)
+        | Instruction::IfFalse(target)
+        | Instruction::NextIterOrJump(target) => Some(target),
+        _ => None,
+    }
+}
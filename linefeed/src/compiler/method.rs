@@ -8,7 +8,9 @@ pub enum Method {
     ToLowerCase,
     Split,
     SplitLines,
+    Paragraphs,
     Nums,
+    Digits,
     Join,
     Length,
     Count,
@@ -17,9 +19,18 @@ pub enum Method {
     IsMatch,
     Contains,
     StartsWith,
+    EndsWith,
+    IndexOf,
+    Trim,
+    TrimStart,
+    TrimEnd,
+    Replace,
+    Repeat,
+    Format,
     Sort,
     Enumerate,
     GetAll,
+    GetOr,
     Values,
     Keys,
     Remove,
@@ -28,40 +39,166 @@ pub enum Method {
     Flat,
     First,
     Last,
+    Nth,
+    IsEmpty,
     Transpose,
+    GridToMap,
+    ToStrings,
     Reverse,
+    Tally,
+    Pairwise,
+    Deltas,
+    MostCommon,
+    PushFront,
+    PushBack,
+    PopFront,
+    PopBack,
+    Width,
+    Height,
+    Neighbors4,
+    Neighbors8,
+    X,
+    Y,
+    Z,
+    Dot,
+    Cross,
 }
 
 impl Method {
     define_names! {
+        /// Appends a value to the end of a list.
         Append => "append",
+        /// Adds a value to a set or counter.
         Add => "add",
+        /// Returns the string in upper case.
         ToUpperCase => "upper",
+        /// Returns the string in lower case.
         ToLowerCase => "lower",
+        /// Splits the string at each occurrence of the separator.
         Split => "split",
+        /// Splits the string into its lines.
         SplitLines => "lines",
+        /// Splits the string into paragraphs, separated by blank lines.
+        Paragraphs => "paragraphs",
+        /// Finds all numbers in the string, including negative numbers and decimals.
         Nums => "nums",
+        /// Returns the digits of an integer, or of a string of digits, as a list of numbers.
+        Digits => "digits",
+        /// Returns the number of items, or characters for strings.
         Length => "len",
+        /// Counts the occurrences of a value in a list, or of a substring in a string. Without an
+        /// argument, counts the items, using up an iterator.
         Count => "count",
+        /// Returns all matches of the regex in the string, like `find` does for the first one.
         FindAll => "find_all",
+        /// Returns the first match of the regex in the string, or null. A match is a tuple of its
+        /// groups followed by the full match, or a map from group names to their text if the regex
+        /// has named groups like `(?P<x>\d+)`.
         Find => "find",
+        /// Returns whether the regex matches the string.
         IsMatch => "is_match",
+        /// Joins the items into a string, optionally with a separator between them.
         Join => "join",
+        /// Returns whether the collection contains the value, or the string contains the substring.
+        /// Maps check their keys.
         Contains => "contains",
+        /// Returns whether the string starts with the given prefix.
         StartsWith => "starts_with",
+        /// Returns whether the string ends with the given suffix.
+        EndsWith => "ends_with",
+        /// Returns the index of the first occurrence of the substring, or null if there is none.
+        IndexOf => "index_of",
+        /// Returns the string without whitespace at either end.
+        Trim => "trim",
+        /// Returns the string without whitespace at the start.
+        TrimStart => "trim_start",
+        /// Returns the string without whitespace at the end.
+        TrimEnd => "trim_end",
+        /// Replaces every occurrence of a substring or every match of a regex with another string.
+        /// For a regex, `$1` or `${name}` in the replacement is the text of that group.
+        Replace => "replace",
+        /// Returns the string repeated the given number of times.
+        Repeat => "repeat",
+        /// Fills the `{}` fields of the string with the arguments in order, or `{0}`, `{1}` and so
+        /// on by index. A field can have a spec after a colon for the width, alignment, fill and
+        /// precision, like `{:>5}`, `{:*^9}`, `{:03}` or `{:.2}`.
+        Format => "format",
+        /// Returns the items sorted, optionally by the keys the given function returns for them.
         Sort => "sort",
+        /// Pairs each item of an iterable with its index, as `(index, item)` tuples, counting from
+        /// the optional start or 0.
         Enumerate => "enumerate",
+        /// Looks up each key of an iterable in the map.
         GetAll => "get_all",
+        /// Looks up a key in the map, returning the given default if it is missing. Unlike
+        /// indexing, the map's own default value isn't used or inserted.
+        GetOr => "get_or",
+        /// Returns the values of a map.
         Values => "values",
+        /// Returns the keys of a map.
         Keys => "keys",
+        /// Removes a value from a set.
         Remove => "remove",
+        /// Rotates a 2D point by the given number of quarter turns.
         Rot => "rot",
+        /// Formats an integer in binary, optionally padded with zeros to the given width.
         Binary => "binary",
+        /// Flattens a list of iterables into a single list.
         Flat => "flat",
+        /// Returns the first item.
         First => "first",
+        /// Returns the last item, using up an iterator.
         Last => "last",
+        /// Returns the item at the given index, or null if there are too few items. An iterator
+        /// is advanced past the item.
+        Nth => "nth",
+        /// Returns whether there are no items, without advancing an iterator.
+        IsEmpty => "is_empty",
+        /// Swaps the rows and columns of a list of lists.
         Transpose => "transpose",
+        /// Returns a map from the `(x, y)` position of each cell of a grid, given as a list of
+        /// lists or strings, to its value.
+        GridToMap => "to_map",
+        /// Joins each row of a grid that is a list into a string, e.g. for printing.
+        ToStrings => "to_strings",
+        /// Returns the list in reverse order.
         Reverse => "reverse",
+        /// Counts how often each item occurs, as a counter.
+        Tally => "tally",
+        /// Returns each pair of consecutive items as a tuple.
+        Pairwise => "pairwise",
+        /// Returns the differences between consecutive items.
+        Deltas => "deltas",
+        /// Returns the items of a counter with their counts as `(item, count)` tuples, most common
+        /// first, optionally only the given number of them.
+        MostCommon => "most_common",
+        /// Adds a value to the front of a deque.
+        PushFront => "push_front",
+        /// Adds a value to the back of a deque.
+        PushBack => "push_back",
+        /// Removes and returns the first value of a deque.
+        PopFront => "pop_front",
+        /// Removes and returns the last value of a deque.
+        PopBack => "pop_back",
+        /// Returns the number of columns of a grid.
+        Width => "width",
+        /// Returns the number of rows of a grid.
+        Height => "height",
+        /// Returns the positions above, left of, right of and below a position that are inside
+        /// the grid.
+        Neighbors4 => "neighbors4",
+        /// Returns the positions around a position, diagonals included, that are inside the grid.
+        Neighbors8 => "neighbors8",
+        /// Returns the first component of a vector.
+        X => "x",
+        /// Returns the second component of a vector.
+        Y => "y",
+        /// Returns the third component of a 3D vector.
+        Z => "z",
+        /// Returns the dot product of two vectors of the same length.
+        Dot => "dot",
+        /// Returns the cross product of two 3D vectors, or its z component for two 2D vectors.
+        Cross => "cross",
     }
 
     /// Returns the number of arguments this method expects.
@@ -73,18 +210,29 @@ impl Method {
             Self::ToLowerCase => 0..=0,
             Self::Split => 1..=1,
             Self::SplitLines => 0..=0,
+            Self::Paragraphs => 0..=0,
             Self::Nums => 0..=0,
+            Self::Digits => 0..=0,
             Self::Length => 0..=0,
-            Self::Count => 1..=1,
+            Self::Count => 0..=1,
             Self::FindAll => 1..=1,
             Self::Find => 1..=1,
             Self::IsMatch => 1..=1,
             Self::Join => 0..=1,
             Self::Contains => 1..=1,
             Self::StartsWith => 1..=1,
+            Self::EndsWith => 1..=1,
+            Self::IndexOf => 1..=1,
+            Self::Trim => 0..=0,
+            Self::TrimStart => 0..=0,
+            Self::TrimEnd => 0..=0,
+            Self::Replace => 2..=2,
+            Self::Repeat => 1..=1,
+            Self::Format => 0..=usize::MAX,
             Self::Sort => 0..=1,
-            Self::Enumerate => 0..=0,
+            Self::Enumerate => 0..=1,
             Self::GetAll => 1..=1,
+            Self::GetOr => 2..=2,
             Self::Values => 0..=0,
             Self::Keys => 0..=0,
             Self::Remove => 1..=1,
@@ -93,14 +241,40 @@ impl Method {
             Self::Flat => 0..=0,
             Self::First => 0..=0,
             Self::Last => 0..=0,
+            Self::Nth => 1..=1,
+            Self::IsEmpty => 0..=0,
             Self::Transpose => 0..=0,
+            Self::GridToMap => 0..=0,
+            Self::ToStrings => 0..=0,
             Self::Reverse => 0..=0,
+            Self::Tally => 0..=0,
+            Self::Pairwise => 0..=0,
+            Self::Deltas => 0..=0,
+            Self::MostCommon => 0..=1,
+            Self::PushFront => 1..=1,
+            Self::PushBack => 1..=1,
+            Self::PopFront => 0..=0,
+            Self::PopBack => 0..=0,
+            Self::Width => 0..=0,
+            Self::Height => 0..=0,
+            Self::Neighbors4 => 1..=1,
+            Self::Neighbors8 => 1..=1,
+            Self::X => 0..=0,
+            Self::Y => 0..=0,
+            Self::Z => 0..=0,
+            Self::Dot => 1..=1,
+            Self::Cross => 1..=1,
         }
     }
 }
 
+// Defines the names of a set of builtins along with their documentation, which is taken from the
+// doc comments on each entry
 macro_rules! define_names {
-    ($($variant:ident => $name:expr),* $(,)?) => {
+    ($($(#[doc = $doc:literal])* $variant:ident => $name:expr),* $(,)?) => {
+        /// Every builtin of this kind, in the order they are defined.
+        pub const ALL: &'static [Self] = &[$(Self::$variant),*];
+
         pub fn name(&self) -> &'static str {
             match self {
                 $(Self::$variant => $name),*
@@ -113,6 +287,14 @@ macro_rules! define_names {
                 _ => None,
             }
         }
+
+        /// The documentation of the builtin, as a single paragraph of Markdown.
+        pub fn doc(&self) -> String {
+            let lines: &[&str] = match self {
+                $(Self::$variant => &[$($doc),*]),*
+            };
+            lines.iter().map(|line| line.trim()).collect::<Vec<_>>().join(" ")
+        }
     };
 }
 
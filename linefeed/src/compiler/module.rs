@@ -0,0 +1,187 @@
+//! Loading the files imported by `import` statements.
+//!
+//! An imported file is compiled as a function that is called where it's imported, returning its
+//! top-level variables, which are then assigned to the same names in the importing scope. The
+//! file's variables are locals of that function, so files don't see each other's variables, and
+//! functions defined in an imported file keep using the file's variables through upvalues.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chumsky::Parser as _;
+
+use crate::{
+    compiler::{analysis, CompileError},
+    grammar::{
+        ast::{Expr, Span, Spanned},
+        lexer,
+    },
+    parse_tokens_at,
+};
+
+/// Spans in imported files are moved at least this far ahead, so they can't be mistaken for spans
+/// in the importing program, and each file gets its own range above it.
+const MODULE_SPAN_BASE: usize = usize::MAX / 2;
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub path: PathBuf,
+    pub src: Arc<str>,
+    /// How far the spans of this file are moved ahead.
+    pub offset: usize,
+    /// The variables assigned at the top level of this file, including those it imports.
+    pub exports: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Modules {
+    base_dir: Option<PathBuf>,
+    modules: Vec<Module>,
+    by_path: HashMap<PathBuf, usize>,
+    // The files currently being loaded, innermost last, to find circular imports
+    loading: Vec<PathBuf>,
+}
+
+impl Modules {
+    /// Imports in the main program are resolved relative to `base_dir`, or the working directory
+    /// if there is none.
+    pub fn new(base_dir: Option<PathBuf>) -> Self {
+        Self {
+            base_dir,
+            ..Default::default()
+        }
+    }
+
+    pub fn get(&self, index: usize) -> &Module {
+        &self.modules[index]
+    }
+
+    /// Finds the imported file containing `span`, and the span relative to that file.
+    pub fn locate(&self, span: Span) -> Option<(&Module, Span)> {
+        let module = self
+            .modules
+            .iter()
+            .rfind(|module| module.offset <= span.start)?;
+        let span = Span::new(span.start - module.offset, span.end - module.offset);
+        Some((module, span))
+    }
+
+    /// Loads the file imported as `import_path` by the import at `span`, along with the files it
+    /// imports, and returns its index. Files that have already been loaded are reused.
+    pub fn load(&mut self, import_path: &str, span: Span) -> Result<usize, CompileError> {
+        let path = self.resolve(import_path, span);
+        let path = std::fs::canonicalize(&path).unwrap_or(path);
+
+        if self.loading.contains(&path) {
+            return Err(CompileError::Spanned {
+                span,
+                msg: format!("Circular import of '{import_path}'"),
+            });
+        }
+        if let Some(&index) = self.by_path.get(&path) {
+            return Ok(index);
+        }
+
+        let src = std::fs::read_to_string(&path).map_err(|err| CompileError::Spanned {
+            span,
+            msg: format!("Failed to read '{}': {err}", path.display()),
+        })?;
+
+        let offset = match self.modules.last() {
+            Some(last) => last.offset + last.src.len() + 1,
+            None => MODULE_SPAN_BASE,
+        };
+        let index = self.modules.len();
+        self.modules.push(Module {
+            path: path.clone(),
+            src: src.into(),
+            offset,
+            exports: Vec::new(),
+        });
+
+        self.loading.push(path.clone());
+        let result = self.find_exports(index);
+        self.loading.pop();
+
+        self.modules[index].exports = result?;
+        self.by_path.insert(path, index);
+        Ok(index)
+    }
+
+    fn find_exports(&mut self, index: usize) -> Result<Vec<String>, CompileError> {
+        let (assignments, imports) = self.modules[index].with_ast(|ast| {
+            (
+                analysis::find_all_assignments(&ast),
+                analysis::find_scope_imports(&ast),
+            )
+        })?;
+
+        let mut exports = assignments
+            .into_iter()
+            .map(|Spanned(name, _)| name)
+            // Compiler generated variables aren't valid identifiers
+            .filter(|name| !name.starts_with('!'))
+            .collect::<Vec<_>>();
+
+        for Spanned(import_path, span) in imports {
+            let imported = self.load(&import_path, span)?;
+            exports.extend(self.modules[imported].exports.iter().cloned());
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        exports.retain(|name| seen.insert(name.clone()));
+        Ok(exports)
+    }
+
+    // Imports are relative to the directory of the file containing them
+    fn resolve(&self, import_path: &str, span: Span) -> PathBuf {
+        let dir = match self.locate(span) {
+            Some((module, _)) => module.path.parent().map(Path::to_path_buf),
+            None => self.base_dir.clone(),
+        };
+
+        match dir {
+            Some(dir) => dir.join(import_path),
+            None => PathBuf::from(import_path),
+        }
+    }
+}
+
+impl Module {
+    /// Parses the file and passes its syntax tree, with spans moved ahead by the file's offset, to
+    /// `f`.
+    pub fn with_ast<R>(&self, f: impl FnOnce(Spanned<Expr>) -> R) -> Result<R, CompileError> {
+        let src = &*self.src;
+        let shift = |span: Span| Span::new(span.start + self.offset, span.end + self.offset);
+        let error = |span: Span, msg: String| CompileError::Spanned {
+            span: shift(span),
+            msg,
+        };
+
+        let tokens = match lexer::lexer().parse(src).into_output_errors() {
+            (Some(tokens), e) if e.is_empty() => tokens,
+            (_, e) => {
+                let e = e.first().expect("lexing failed with an error");
+                return Err(error(*e.span(), e.to_string()));
+            }
+        };
+        let tokens = tokens
+            .into_iter()
+            .map(|Spanned(token, span)| Spanned(token, shift(span)))
+            .collect::<Vec<_>>();
+
+        match parse_tokens_at(src, &tokens, self.offset) {
+            Ok(ast) => Ok(f(ast)),
+            Err(e) => {
+                let e = e.first().expect("parsing failed with an error");
+                Err(CompileError::Spanned {
+                    span: *e.span(),
+                    msg: e.to_string(),
+                })
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::{cmp::Reverse, collections::BinaryHeap};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RegisterManager {
     max_registers: usize,
     registers: BinaryHeap<Reverse<usize>>,
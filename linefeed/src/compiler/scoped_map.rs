@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ScopedMap<K, V> {
     scopes: Vec<HashMap<K, V>>,
 }
@@ -9,7 +9,8 @@ pub struct ScopedMap<K, V> {
 pub enum VarType<T> {
     Local(T),
     Global(T),
-    // Upvalue,
+    // A local of an enclosing function scope
+    Upvalue(T),
 }
 
 impl<T> VarType<T> {
@@ -17,6 +18,7 @@ impl<T> VarType<T> {
         match self {
             Self::Local(val) => val,
             Self::Global(val) => val,
+            Self::Upvalue(val) => val,
         }
     }
 }
@@ -52,10 +54,10 @@ where
     pub fn get(&self, key: &K) -> Option<VarType<&V>> {
         let cur_scope = self.scopes.len() - 1;
         self.scopes.iter().enumerate().rev().find_map(|(i, scope)| {
-            scope.get(key).and_then(|v| match i {
-                0 => Some(VarType::Global(v)),
-                _ if i == cur_scope => Some(VarType::Local(v)),
-                _ => None,
+            scope.get(key).map(|v| match i {
+                0 => VarType::Global(v),
+                _ if i == cur_scope => VarType::Local(v),
+                _ => VarType::Upvalue(v),
             })
         })
     }
@@ -67,10 +69,10 @@ where
             .enumerate()
             .rev()
             .find_map(|(i, scope)| {
-                scope.get_mut(key).and_then(|v| match i {
-                    0 => Some(VarType::Global(v)),
-                    _ if i == cur_scope => Some(VarType::Local(v)),
-                    _ => None,
+                scope.get_mut(key).map(|v| match i {
+                    0 => VarType::Global(v),
+                    _ if i == cur_scope => VarType::Local(v),
+                    _ => VarType::Upvalue(v),
                 })
             })
     }
@@ -84,6 +86,16 @@ where
         }
     }
 
+    /// Looks up a variable in the scope at `depth`, where 0 is the global scope.
+    pub fn get_in_scope(&self, depth: usize, name: &K) -> Option<&V> {
+        self.scopes[depth].get(name)
+    }
+
+    /// The depth of the current scope, where 0 is the global scope.
+    pub fn depth(&self) -> usize {
+        self.scopes.len() - 1
+    }
+
     pub fn get_local(&self, name: &K) -> Option<&V> {
         self.scopes.last().unwrap().get(name)
     }
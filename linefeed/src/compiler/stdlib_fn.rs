@@ -5,14 +5,39 @@ use crate::compiler::method::define_names;
 #[derive(Debug, Clone)]
 pub enum StdlibFn {
     Print,
+    Eprint,
+    Inspect,
     Input,
+    ReadLine,
     ParseInt,
+    ParseFloat,
+    ToInt,
+    ToBase,
+    ToFloat,
+    TryNum,
+    TryInt,
+    TryFloat,
+    TryIndex,
+    TryGet,
     Repr,
     Stringify,
+    DeepCopy,
+    TypeOf,
+    IsNum,
+    IsStr,
+    IsBool,
+    IsList,
+    IsTuple,
+    IsMap,
+    IsSet,
+    IsFunction,
     ToList,
     ToTuple,
     ToMap,
     MapWithDefault,
+    ListWithCapacity,
+    MapWithCapacity,
+    MapToGrid,
     ToSet,
     Product,
     Sum,
@@ -22,48 +47,361 @@ pub enum StdlibFn {
     Min,
     Abs,
     Sqrt,
+    Isqrt,
+    Gcd,
+    Lcm,
+    Floor,
+    Ceil,
+    Round,
+    Sin,
+    Cos,
+    Tan,
+    Log,
+    Log2,
+    Rand,
+    RandInt,
+    Shuffle,
+    Choice,
+    Seed,
+    Now,
+    Timestamp,
+    FormatTime,
+    ParseTime,
+    Args,
+    Env,
+    Fetch,
+    HttpGet,
+    Exit,
+    ParseCsv,
+    Md5,
+    Sha256,
     Counter,
+    ToDeque,
+    ToGrid,
     Manhattan,
     ModInv,
+    Histogram,
+    FreqTable,
+    ChunkBy,
+    SplitWhen,
+    CountIf,
+    Filter,
+    Reduce,
+    Sorted,
+    Permutations,
+    Combinations,
+    Bfs,
+    Dijkstra,
+    AStar,
+    Vec2,
+    Vec3,
+    Rational,
+    WrapAdd,
+    WrapSub,
+    WrapMul,
+    Error,
+    AssertEq,
+    DeepEq,
+    ApproxEq,
+    Regex,
+    Eval,
 }
 
 impl StdlibFn {
     define_names! {
+        /// Prints the arguments separated by spaces, followed by a newline, and returns the last
+        /// one.
         Print => "print",
+        /// Like `print`, but writes to standard error, which keeps logging out of the output.
+        Eprint => "eprint",
+        /// Prints the repr of a value to standard error, after an optional label, and returns the
+        /// value.
+        Inspect => "inspect",
+        /// Reads all of standard input as a string.
         Input => "input",
-        ParseInt => "int",
+        /// Reads a single line of standard input without its line ending, or null at the end of
+        /// the input. An optional prompt is printed first, without a newline.
+        ReadLine => "read_line",
+        /// Parses a string containing an integer.
+        ParseInt => "parse_int",
+        /// Parses a string containing a number, like `"3.14"` or `"1e-3"`, as a float.
+        ParseFloat => "parse_float",
+        /// Converts a number, string or boolean to an integer. Floats are truncated towards zero,
+        /// or rounded with the mode given as the second argument: `"trunc"`, `"floor"`, `"ceil"` or
+        /// `"round"`. For strings, the second argument is the base, e.g. `int("ff", 16)`.
+        ToInt => "int",
+        /// Writes an integer in a base between 2 and 36 as a string, e.g. `to_base(255, 16)` is
+        /// `"ff"`.
+        ToBase => "to_base",
+        /// Converts a number, string or boolean to a float.
+        ToFloat => "float",
+        /// Parses a string as an integer, or as a float if it isn't one, returning null if it is
+        /// neither. Numbers are returned as they are.
+        TryNum => "try_num",
+        /// Like `int`, but returns null instead of failing when the value can't be converted.
+        TryInt => "try_int",
+        /// Like `float`, but returns null instead of failing when the value can't be converted.
+        TryFloat => "try_float",
+        /// Indexes the first argument with the second, returning null instead of failing when the
+        /// index is out of bounds or of the wrong type.
+        TryIndex => "try_index",
+        /// Indexes into nested collections with each of the keys in turn, e.g.
+        /// `try_get(grid, y, x)`, returning null as soon as a lookup fails.
+        TryGet => "try_get",
+        /// Returns the value as a string the way it would be written in code, e.g. with quotes
+        /// around strings.
         Repr => "repr",
+        /// Returns the value as a string the way `print` shows it.
         Stringify => "str",
+        /// Returns a copy of the value that shares nothing with it, so changing a list or map
+        /// inside one doesn't change the other.
+        DeepCopy => "copy",
+        /// Returns the name of the value's type: `"null"`, `"boolean"`, `"number"`, `"str"`,
+        /// `"list"`, `"tuple"`, `"map"`, `"set"`, `"function"` and so on.
+        TypeOf => "type",
+        /// Returns whether the value is a number.
+        IsNum => "is_num",
+        /// Returns whether the value is a string.
+        IsStr => "is_str",
+        /// Returns whether the value is a boolean.
+        IsBool => "is_bool",
+        /// Returns whether the value is a list.
+        IsList => "is_list",
+        /// Returns whether the value is a tuple.
+        IsTuple => "is_tuple",
+        /// Returns whether the value is a map.
+        IsMap => "is_map",
+        /// Returns whether the value is a set.
+        IsSet => "is_set",
+        /// Returns whether the value is a function.
+        IsFunction => "is_function",
+        /// Collects the items of an iterable into a list.
         ToList => "list",
+        /// Collects the items of an iterable into a tuple.
         ToTuple => "tuple",
+        /// Collects an iterable of key-value pairs into a map. Given a function first, as in
+        /// `map(f, xs)`, returns a list of the function applied to each item of the iterable
+        /// instead.
         ToMap => "map",
+        /// Creates an empty map that returns the given value for missing keys.
         MapWithDefault => "defaultmap",
+        /// Creates an empty list with room for the given number of items, so appending that many
+        /// doesn't have to grow it.
+        ListWithCapacity => "list_with_capacity",
+        /// Creates an empty map with room for the given number of entries.
+        MapWithCapacity => "map_with_capacity",
+        /// Lays out a map from `(x, y)` positions to values as a grid, a list of rows, spanning
+        /// the smallest to the largest position in the map. Missing positions get the optional
+        /// default value, or null.
+        MapToGrid => "map_to_grid",
+        /// Creates a set, empty or with the items of the given iterable.
         ToSet => "set",
+        /// Multiplies the items of an iterable together.
         Product => "mul",
+        /// Adds the items of an iterable together.
         Sum => "sum",
+        /// Returns whether all of the arguments, or the items of a single iterable argument, are
+        /// truthy.
         All => "all",
+        /// Returns whether any of the arguments, or the items of a single iterable argument, are
+        /// truthy.
         Any => "any",
+        /// Returns the largest of the arguments, or of the items of a single iterable argument.
         Max => "max",
+        /// Returns the smallest of the arguments, or of the items of a single iterable argument.
         Min => "min",
+        /// Returns the absolute value of a number.
         Abs => "abs",
+        /// Returns the square root of a number.
         Sqrt => "sqrt",
+        /// Returns the square root of a non-negative integer, rounded down to an integer.
+        Isqrt => "isqrt",
+        /// Returns the greatest common divisor of the integer arguments, or of the items of a
+        /// single iterable argument.
+        Gcd => "gcd",
+        /// Returns the least common multiple of the integer arguments, or of the items of a single
+        /// iterable argument.
+        Lcm => "lcm",
+        /// Rounds a number down to an integer.
+        Floor => "floor",
+        /// Rounds a number up to an integer.
+        Ceil => "ceil",
+        /// Rounds a number to the nearest integer, with halves rounded away from zero.
+        Round => "round",
+        /// Returns the sine of an angle in radians.
+        Sin => "sin",
+        /// Returns the cosine of an angle in radians.
+        Cos => "cos",
+        /// Returns the tangent of an angle in radians.
+        Tan => "tan",
+        /// Returns the natural logarithm of a number, or its logarithm in the base given as the
+        /// second argument.
+        Log => "log",
+        /// Returns the base 2 logarithm of a number.
+        Log2 => "log2",
+        /// Returns a random float between 0, included, and 1, excluded.
+        Rand => "rand",
+        /// Returns a random integer between the two arguments, both included.
+        RandInt => "rand_int",
+        /// Returns the items of an iterable as a new list in random order.
+        Shuffle => "shuffle",
+        /// Returns a random item of an iterable.
+        Choice => "choice",
+        /// Seeds the random functions with an integer, so they give the same results on every run.
+        Seed => "seed",
+        /// Returns the current time as seconds since the Unix epoch, with a fractional part.
+        Now => "now",
+        /// Returns the current UTC time as a string like `"2024-12-01 05:00:00"`.
+        Timestamp => "timestamp",
+        /// Formats seconds since the Unix epoch as a UTC date, optionally with a format using the
+        /// specifiers `%Y`, `%m`, `%d`, `%H`, `%M` and `%S`. The default is `"%Y-%m-%d %H:%M:%S"`.
+        FormatTime => "format_time",
+        /// Parses a UTC date into seconds since the Unix epoch, optionally with a format like the
+        /// one of `format_time`.
+        ParseTime => "parse_time",
+        /// Returns the arguments given after the program file on the command line, as a list of
+        /// strings.
+        Args => "args",
+        /// Returns the value of an environment variable, or null if it isn't set.
+        Env => "env",
+        /// Downloads a URL and returns the response body as a string, optionally sending a map of
+        /// headers. Only available when linefeed is built with the `fetch` feature.
+        Fetch => "fetch",
+        /// Like `fetch`, but the map of headers to send is required.
+        HttpGet => "http_get",
+        /// Stops the program with the given exit code, or 0 if none is given.
+        Exit => "exit",
+        /// Parses delimiter-separated text into a list of rows, each a list of string fields. The
+        /// delimiter defaults to `","`, and fields can be quoted with `"`. With `true` as the third
+        /// argument, the first row is a header and the other rows become maps from its names.
+        ParseCsv => "parse_csv",
+        /// Returns the MD5 hash of a string as 32 lowercase hex digits.
+        Md5 => "md5",
+        /// Returns the SHA-256 hash of a string as 64 lowercase hex digits.
+        Sha256 => "sha256",
+        /// Creates a counter, empty or counting the items of the given iterable.
         Counter => "counter",
+        /// Creates a deque, empty or with the items of the given iterable, for pushing and popping
+        /// at both ends.
+        ToDeque => "deque",
+        /// Creates a grid from a list of rows, which are strings or lists of the same length.
+        /// Cells are indexed by `(x, y)` position and iterated over as `(position, value)` tuples.
+        ToGrid => "grid",
+        /// Returns the Manhattan distance of a 2D point from the origin, or between two points.
         Manhattan => "manhattan",
+        /// Returns the modular multiplicative inverse of the first argument modulo the second.
         ModInv => "mod_inv",
+        /// Prints a bar chart of how often each item of an iterable occurs, optionally with a
+        /// maximum bar width.
+        Histogram => "histogram",
+        /// Prints a table of the distinct items of an iterable with their counts and share of the
+        /// total.
+        FreqTable => "freq_table",
+        /// Groups consecutive items of an iterable for which the function returns equal keys.
+        ChunkBy => "chunk_by",
+        /// Splits an iterable into groups separated by the items for which the function returns
+        /// true.
+        SplitWhen => "split_when",
+        /// Counts the items of an iterable for which the function returns true, or the truthy
+        /// items if no function is given.
+        CountIf => "count_if",
+        /// Returns a list of the items of an iterable for which the function returns true, as in
+        /// `filter(f, xs)`.
+        Filter => "filter",
+        /// Combines the items of an iterable into a single value, as in `reduce(f, xs, init)`,
+        /// calling the function with the value so far and each item in turn, starting from `init`.
+        Reduce => "reduce",
+        /// Returns a new list of the items of an iterable in sorted order. The optional second
+        /// argument is a function giving the key to sort each item by, or a map of options with
+        /// the `"key"` function and whether to `"reverse"` the order.
+        Sorted => "sorted",
+        /// Returns a lazy iterator of tuples of every ordering of `k` of the items of an iterable,
+        /// or of all of them if `k` is left out, as in `permutations(xs, k)`.
+        Permutations => "permutations",
+        /// Returns a lazy iterator of tuples of every way to pick `k` of the items of an iterable,
+        /// keeping their order, as in `combinations(xs, k)`.
+        Combinations => "combinations",
+        /// Searches breadth-first, as in `bfs(start, neighbors, goal)`, calling `neighbors` for the
+        /// nodes next to each node until reaching the goal, which is a node or a function saying
+        /// whether a node is the goal. Returns the shortest path as a list of nodes, or null.
+        Bfs => "bfs",
+        /// Like `bfs`, but the neighbors are `(node, cost)` pairs, and the cheapest path is
+        /// returned as a `(cost, path)` tuple.
+        Dijkstra => "dijkstra",
+        /// Like `dijkstra`, as in `a_star(start, neighbors, goal, heuristic)`, but looking first at
+        /// the nodes that `heuristic` estimates to be closest to the goal. The estimate must never
+        /// be more than the actual cost.
+        AStar => "a_star",
+        /// Creates a 2D vector, as in `vec2(x, y)`, which is the same as the tuple `(x, y)`.
+        Vec2 => "vec2",
+        /// Creates a 3D vector, as in `vec3(x, y, z)`, which is the same as the tuple `(x, y, z)`.
+        Vec3 => "vec3",
+        /// Creates the exact fraction `num / den`, as in `rational(3, 4)`. Arithmetic with
+        /// integers stays exact, while arithmetic with floats gives a float.
+        Rational => "rational",
+        /// Adds two integers, wrapping around at the bit width given as the third argument.
+        WrapAdd => "wrap_add",
+        /// Subtracts two integers, wrapping around at the bit width given as the third argument.
+        WrapSub => "wrap_sub",
+        /// Multiplies two integers, wrapping around at the bit width given as the third argument.
+        WrapMul => "wrap_mul",
+        /// Stops the program with an error showing the given message at the call, like a failing
+        /// builtin would.
+        Error => "error",
+        /// Fails with an error showing both values if they aren't equal.
+        AssertEq => "assert_eq",
+        /// Returns whether two values have the same structure and contents, also for lists and
+        /// maps that contain themselves. Tuples of numbers compare equal however they are stored.
+        DeepEq => "deep_eq",
+        /// Like `deep_eq`, but numbers at any depth only have to be within the tolerance given as
+        /// the third argument of each other, `1e-9` if it is left out.
+        ApproxEq => "approx_eq",
+        /// Compiles a string into a regex, optionally with flags written like after a regex
+        /// literal, e.g. `"i"`.
+        Regex => "regex",
+        /// Runs a string of code with its own variables and returns its value. An optional map
+        /// of options can give the code its own `"input"`, `"capture"` its output, returning a
+        /// tuple of the value and the output, and limit it to `"max_instructions"`.
+        Eval => "eval",
     }
 
     /// Returns the number of arguments this function expects.
     pub fn num_args(&self) -> RangeInclusive<usize> {
         match self {
             Self::Print => 0..=usize::MAX,
+            Self::Eprint => 0..=usize::MAX,
+            Self::Inspect => 1..=2,
             Self::Input => 0..=0, // TODO: in the future future, read from an optional file path here?
+            Self::ReadLine => 0..=1,
             Self::ParseInt => 1..=1,
+            Self::ParseFloat => 1..=1,
+            Self::ToInt => 1..=2,
+            Self::ToBase => 2..=2,
+            Self::ToFloat => 1..=1,
+            Self::TryNum => 1..=1,
+            Self::TryInt => 1..=2,
+            Self::TryFloat => 1..=1,
+            Self::TryIndex => 2..=2,
+            Self::TryGet => 2..=usize::MAX,
             Self::Repr => 1..=1,
             Self::Stringify => 1..=1,
+            Self::DeepCopy => 1..=1,
+            Self::TypeOf => 1..=1,
+            Self::IsNum => 1..=1,
+            Self::IsStr => 1..=1,
+            Self::IsBool => 1..=1,
+            Self::IsList => 1..=1,
+            Self::IsTuple => 1..=1,
+            Self::IsMap => 1..=1,
+            Self::IsSet => 1..=1,
+            Self::IsFunction => 1..=1,
             Self::ToList => 1..=1,
             Self::ToTuple => 1..=1,
-            Self::ToMap => 1..=1,
+            Self::ToMap => 1..=2,
             Self::MapWithDefault => 1..=1,
+            Self::ListWithCapacity => 1..=1,
+            Self::MapWithCapacity => 1..=1,
+            Self::MapToGrid => 1..=2,
             Self::ToSet => 0..=1,
             Self::Product => 1..=1,
             Self::Sum => 1..=1,
@@ -73,9 +411,74 @@ impl StdlibFn {
             Self::Min => 1..=usize::MAX,
             Self::Abs => 1..=1,
             Self::Sqrt => 1..=1,
+            Self::Isqrt => 1..=1,
+            Self::Gcd => 1..=usize::MAX,
+            Self::Lcm => 1..=usize::MAX,
+            Self::Floor => 1..=1,
+            Self::Ceil => 1..=1,
+            Self::Round => 1..=1,
+            Self::Sin => 1..=1,
+            Self::Cos => 1..=1,
+            Self::Tan => 1..=1,
+            Self::Log => 1..=2,
+            Self::Log2 => 1..=1,
+            Self::Rand => 0..=0,
+            Self::RandInt => 2..=2,
+            Self::Shuffle => 1..=1,
+            Self::Choice => 1..=1,
+            Self::Seed => 1..=1,
+            Self::Now => 0..=0,
+            Self::Timestamp => 0..=0,
+            Self::FormatTime => 1..=2,
+            Self::ParseTime => 1..=2,
+            Self::Args => 0..=0,
+            Self::Env => 1..=1,
+            Self::Fetch => 1..=2,
+            Self::HttpGet => 2..=2,
+            Self::Exit => 0..=1,
+            Self::ParseCsv => 1..=3,
+            Self::Md5 => 1..=1,
+            Self::Sha256 => 1..=1,
             Self::Counter => 0..=1,
+            Self::ToDeque => 0..=1,
+            Self::ToGrid => 1..=1,
             Self::Manhattan => 1..=2,
             Self::ModInv => 2..=2,
+            Self::Histogram => 1..=2,
+            Self::FreqTable => 1..=1,
+            Self::ChunkBy => 2..=2,
+            Self::SplitWhen => 2..=2,
+            Self::CountIf => 1..=2,
+            Self::Filter => 2..=2,
+            Self::Reduce => 3..=3,
+            Self::Sorted => 1..=2,
+            Self::Permutations => 1..=2,
+            Self::Combinations => 2..=2,
+            Self::Bfs => 3..=3,
+            Self::Dijkstra => 3..=3,
+            Self::AStar => 4..=4,
+            Self::Vec2 => 2..=2,
+            Self::Vec3 => 3..=3,
+            Self::Rational => 2..=2,
+            Self::WrapAdd => 3..=3,
+            Self::WrapSub => 3..=3,
+            Self::WrapMul => 3..=3,
+            Self::Error => 1..=1,
+            Self::AssertEq => 2..=2,
+            Self::DeepEq => 2..=2,
+            Self::ApproxEq => 2..=3,
+            Self::Regex => 1..=2,
+            Self::Eval => 1..=2,
         }
     }
+
+    /// Variadic functions that reduce over their arguments. Given a single argument, they reduce
+    /// over its items instead, and `*xs` arguments spread the items of `xs` into the arguments,
+    /// so `max(xs)`, `max(a, b)` and `max(a, *xs)` all work as expected.
+    pub fn reduces_variadic_args(&self) -> bool {
+        matches!(
+            self,
+            Self::All | Self::Any | Self::Max | Self::Min | Self::Gcd | Self::Lcm
+        )
+    }
 }
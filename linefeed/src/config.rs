@@ -0,0 +1,254 @@
+//! Layered configuration shared by the CLI, the language server and embedders.
+//!
+//! Settings are read from the user-level config file (see [`user_config_path`]) and then from the
+//! nearest `linefeed.toml` found by walking up from the program's directory, with the project file
+//! taking precedence. Every setting is optional, so both files only need to mention what they
+//! change:
+//!
+//! ```toml
+//! opt_level = 1
+//! deterministic = true
+//!
+//! [memo]
+//! cache_size = 100000
+//!
+//! [sandbox]
+//! fs = true
+//! env = false
+//! network = false
+//!
+//! [repl]
+//! prelude = ["utils.lf"]
+//!
+//! [lints]
+//! constant_condition = "deny"
+//! unused_pure_expression = "allow"
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    lint::{Lint, LintLevel},
+    vm::limits::RunLimits,
+};
+
+/// Name of the per-project configuration file.
+pub const PROJECT_CONFIG_FILE: &str = "linefeed.toml";
+
+/// Highest supported value for [`Config::opt_level`].
+pub const MAX_OPT_LEVEL: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// How aggressively the compiler may optimise, from 0 (not at all) to [`MAX_OPT_LEVEL`].
+    pub opt_level: u8,
+    /// Avoids output that differs between otherwise identical runs, such as timing reports.
+    pub deterministic: bool,
+    /// Maximum number of results cached across all `memoized` functions. `None` means no limit,
+    /// and 0 disables memoization entirely.
+    pub memo_cache_size: Option<usize>,
+    pub sandbox: Sandbox,
+    /// Files to evaluate when starting the REPL, resolved relative to the config file listing them.
+    pub repl_prelude: Vec<PathBuf>,
+    /// Lints whose level differs from the default (see [`Config::lint_level`]).
+    pub lints: BTreeMap<Lint, LintLevel>,
+    /// Limits that stop runaway programs. These can only be set from the command line, since a
+    /// limit that suits one program is rarely right for a whole project.
+    pub limits: RunLimits,
+    /// Arguments for the program, which are the ones after the program file on the command line.
+    pub args: Vec<String>,
+}
+
+/// Capabilities granted to programs. Everything is allowed unless a config file says otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sandbox {
+    /// Lets programs import other files.
+    pub fs: bool,
+    /// Lets programs read environment variables with `env`.
+    pub env: bool,
+    /// Lets programs download with `fetch`.
+    pub network: bool,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self {
+            fs: true,
+            env: true,
+            network: true,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            opt_level: 1,
+            deterministic: false,
+            memo_cache_size: None,
+            sandbox: Sandbox::default(),
+            repl_prelude: Vec::new(),
+            lints: BTreeMap::new(),
+            limits: RunLimits::default(),
+            args: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub msg: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid config file {}: {}",
+            self.path.display(),
+            self.msg
+        )
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// The on-disk format, where every setting may be left out so that layers can be merged
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigLayer {
+    opt_level: Option<u8>,
+    deterministic: Option<bool>,
+    memo: MemoLayer,
+    sandbox: SandboxLayer,
+    repl: ReplLayer,
+    lints: BTreeMap<String, LintLevel>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MemoLayer {
+    cache_size: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SandboxLayer {
+    fs: Option<bool>,
+    env: Option<bool>,
+    network: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ReplLayer {
+    prelude: Option<Vec<PathBuf>>,
+}
+
+impl Config {
+    /// Loads the user config followed by the project config applying to `dir`.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::load_from(user_config_path().as_deref(), dir)
+    }
+
+    /// Like [`Config::load`], but with an explicit user config file instead of the default one.
+    pub fn load_from(
+        user_config: Option<&Path>,
+        dir: impl AsRef<Path>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+
+        if let Some(path) = user_config.filter(|path| path.is_file()) {
+            config.apply_file(path)?;
+        }
+
+        if let Some(path) = find_project_config(dir.as_ref()) {
+            config.apply_file(&path)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Applies the settings in `src` on top of this config. Relative paths are resolved against
+    /// `base_dir`.
+    pub fn apply_toml(&mut self, src: &str, base_dir: &Path) -> Result<(), String> {
+        let layer: ConfigLayer = toml::from_str(src).map_err(|err| err.message().to_string())?;
+
+        if let Some(opt_level) = layer.opt_level {
+            if opt_level > MAX_OPT_LEVEL {
+                return Err(format!(
+                    "opt_level must be between 0 and {MAX_OPT_LEVEL}, got {opt_level}"
+                ));
+            }
+            self.opt_level = opt_level;
+        }
+
+        if let Some(deterministic) = layer.deterministic {
+            self.deterministic = deterministic;
+        }
+
+        if let Some(cache_size) = layer.memo.cache_size {
+            self.memo_cache_size = Some(cache_size);
+        }
+
+        let sandbox = layer.sandbox;
+        self.sandbox.fs = sandbox.fs.unwrap_or(self.sandbox.fs);
+        self.sandbox.env = sandbox.env.unwrap_or(self.sandbox.env);
+        self.sandbox.network = sandbox.network.unwrap_or(self.sandbox.network);
+
+        if let Some(prelude) = layer.repl.prelude {
+            self.repl_prelude = prelude.iter().map(|path| base_dir.join(path)).collect();
+        }
+
+        for (name, level) in layer.lints {
+            let lint = Lint::from_name(&name).ok_or_else(|| format!("unknown lint `{name}`"))?;
+            self.lints.insert(lint, level);
+        }
+
+        Ok(())
+    }
+
+    /// All lints warn unless configured otherwise.
+    pub fn lint_level(&self, lint: Lint) -> LintLevel {
+        self.lints.get(&lint).copied().unwrap_or(LintLevel::Warn)
+    }
+
+    fn apply_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let to_error = |msg: String| ConfigError {
+            path: path.to_path_buf(),
+            msg,
+        };
+
+        let src = fs::read_to_string(path).map_err(|err| to_error(err.to_string()))?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        self.apply_toml(&src, base_dir).map_err(to_error)
+    }
+}
+
+/// The user-level config file: `$LINEFEED_CONFIG` if set, otherwise `linefeed/config.toml` in
+/// `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn user_config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("LINEFEED_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_home.join("linefeed").join("config.toml"))
+}
+
+/// Finds the `linefeed.toml` in `dir` or the closest of its ancestors.
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
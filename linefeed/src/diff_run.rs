@@ -0,0 +1,146 @@
+//! Running two programs on the same input and comparing what they did, e.g. to check that a
+//! refactored solution still behaves like the original, or how much faster it got.
+
+use std::{
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    base_dir, compiler::Compiler, config::Config, read_program, run_with_hooks, EXIT_FAILURE,
+    EXIT_SUCCESS,
+};
+
+/// What a single run printed, its exit status and what it cost.
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// `None` if the program didn't get to run, because it failed to parse or compile.
+    pub instructions_executed: Option<usize>,
+    pub time: Duration,
+}
+
+/// Runs the program in the file at `path` on `input`, capturing its output.
+pub fn run_summary(path: &Path, config: &Config, input: &[u8]) -> RunSummary {
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+
+    // The timing report would end up on the terminal between the two reports
+    let config = Config {
+        deterministic: true,
+        ..config.clone()
+    };
+
+    let start = Instant::now();
+    let mut instructions_executed = None;
+    let status = match read_program(path, &mut stderr) {
+        Some(src) => run_with_hooks(
+            &src,
+            Compiler::default().with_base_dir(base_dir(path)),
+            &config,
+            input,
+            &mut stdout,
+            &mut stderr,
+            |vm| vm,
+            |vm| instructions_executed = Some(vm.instructions_executed),
+        ),
+        None => EXIT_FAILURE,
+    };
+
+    RunSummary {
+        status,
+        stdout,
+        stderr,
+        instructions_executed,
+        time: start.elapsed(),
+    }
+}
+
+/// Runs the programs in the files `a` and `b` on the same input and writes a comparison of their
+/// output, exit status, instruction count and run time to `out`. Returns a failure exit status if
+/// their output or exit status differ.
+pub fn diff_run(a: &Path, b: &Path, config: &Config, input: &[u8], mut out: impl Write) -> i32 {
+    let (run_a, run_b) = (run_summary(a, config, input), run_summary(b, config, input));
+    let (name_a, name_b) = (a.display(), b.display());
+    let name_width = name_a.to_string().len().max(name_b.to_string().len()) + 1;
+
+    let lines_a = String::from_utf8_lossy(&run_a.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let lines_b = String::from_utf8_lossy(&run_b.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    let same_stdout = run_a.stdout == run_b.stdout;
+    if same_stdout {
+        writeln!(out, "stdout:       identical ({} lines)", lines_a.len()).unwrap();
+    } else {
+        // Output that only differs in a trailing newline has no differing line
+        let line = (0..lines_a.len().max(lines_b.len()))
+            .find(|&i| lines_a.get(i) != lines_b.get(i))
+            .unwrap_or(lines_a.len().min(lines_b.len()));
+        let show = |lines: &[String]| match lines.get(line) {
+            Some(text) => format!("{text:?}"),
+            None => "<end of output>".to_string(),
+        };
+
+        writeln!(out, "stdout:       differs at line {}", line + 1).unwrap();
+        writeln!(
+            out,
+            "  {:name_width$} {}",
+            format!("{name_a}:"),
+            show(&lines_a)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "  {:name_width$} {}",
+            format!("{name_b}:"),
+            show(&lines_b)
+        )
+        .unwrap();
+    }
+
+    let same_status = run_a.status == run_b.status;
+    if same_status {
+        writeln!(out, "exit status:  {} in both", run_a.status).unwrap();
+    } else {
+        writeln!(out, "exit status:  {} vs {}", run_a.status, run_b.status).unwrap();
+    }
+    for (name, run) in [(&name_a, &run_a), (&name_b, &run_b)] {
+        if run.status != EXIT_SUCCESS {
+            writeln!(out, "  {name} failed with:").unwrap();
+            for line in String::from_utf8_lossy(&run.stderr).lines() {
+                writeln!(out, "    {line}").unwrap();
+            }
+        }
+    }
+
+    match (run_a.instructions_executed, run_b.instructions_executed) {
+        (Some(count_a), Some(count_b)) => {
+            let change = if count_a == 0 {
+                String::new()
+            } else {
+                let percent = (count_b as f64 - count_a as f64) / count_a as f64 * 100.0;
+                format!(" ({percent:+.1}%)")
+            };
+            writeln!(out, "instructions: {count_a} vs {count_b}{change}").unwrap();
+        }
+        (count_a, count_b) => {
+            let show = |count: Option<usize>| count.map_or("-".to_string(), |c| c.to_string());
+            writeln!(out, "instructions: {} vs {}", show(count_a), show(count_b)).unwrap();
+        }
+    }
+
+    writeln!(out, "time:         {:?} vs {:?}", run_a.time, run_b.time).unwrap();
+
+    if same_stdout && same_status {
+        EXIT_SUCCESS
+    } else {
+        EXIT_FAILURE
+    }
+}
@@ -0,0 +1,180 @@
+//! Reference documentation for the builtins, generated from their definitions.
+//!
+//! Every builtin function, method and snippet is documented by the doc comment on its entry in
+//! `define_names!`, so the reference can't fall behind what the compiler accepts.
+
+use std::{fmt::Write as _, ops::RangeInclusive};
+
+use crate::{
+    compiler::{method::Method, stdlib_fn::StdlibFn},
+    grammar::snippets::Snippet,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BuiltinKind {
+    Function,
+    Method,
+    Snippet,
+}
+
+impl BuiltinKind {
+    fn heading(&self) -> &'static str {
+        match self {
+            Self::Function => "Functions",
+            Self::Method => "Methods",
+            Self::Snippet => "Snippets",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BuiltinDoc {
+    pub kind: BuiltinKind,
+    pub name: &'static str,
+    pub num_args: RangeInclusive<usize>,
+    /// A paragraph of Markdown.
+    pub doc: String,
+}
+
+impl BuiltinDoc {
+    /// How the builtin is called, e.g. `.sort(…)` for methods taking arguments.
+    pub fn signature(&self) -> String {
+        let args = if *self.num_args.end() == 0 { "" } else { "…" };
+        match self.kind {
+            BuiltinKind::Method => format!(".{}({args})", self.name),
+            BuiltinKind::Function | BuiltinKind::Snippet => format!("{}({args})", self.name),
+        }
+    }
+
+    pub fn arity(&self) -> String {
+        let (min, max) = (*self.num_args.start(), *self.num_args.end());
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+
+        match (min, max) {
+            (0, usize::MAX) => "Takes any number of arguments.".to_string(),
+            (min, usize::MAX) => format!("Takes at least {min} {}.", plural(min)),
+            (min, max) if min == max => format!("Takes {min} {}.", plural(min)),
+            (0, max) => format!("Takes up to {max} {}.", plural(max)),
+            (min, max) => format!("Takes {min} to {max} {}.", plural(max)),
+        }
+    }
+}
+
+/// All builtins, grouped by kind and sorted by name.
+pub fn builtins() -> Vec<BuiltinDoc> {
+    let functions = StdlibFn::ALL.iter().map(|f| BuiltinDoc {
+        kind: BuiltinKind::Function,
+        name: f.name(),
+        num_args: f.num_args(),
+        doc: f.doc(),
+    });
+    let methods = Method::ALL.iter().map(|m| BuiltinDoc {
+        kind: BuiltinKind::Method,
+        name: m.name(),
+        num_args: m.num_args(),
+        doc: m.doc(),
+    });
+    let snippets = Snippet::ALL.iter().map(|s| BuiltinDoc {
+        kind: BuiltinKind::Snippet,
+        name: s.name(),
+        num_args: s.num_args()..=s.num_args(),
+        doc: s.doc(),
+    });
+
+    let mut builtins = functions.chain(methods).chain(snippets).collect::<Vec<_>>();
+    builtins.sort_by_key(|builtin| (builtin.kind, builtin.name));
+    builtins
+}
+
+/// Renders the reference for the whole standard library.
+pub fn std_reference(format: DocFormat) -> String {
+    match format {
+        DocFormat::Markdown => markdown_reference(&builtins()),
+        DocFormat::Html => html_reference(&builtins()),
+    }
+}
+
+fn markdown_reference(builtins: &[BuiltinDoc]) -> String {
+    let mut out = String::from("# Linefeed standard library\n");
+
+    let mut kind = None;
+    for builtin in builtins {
+        if kind != Some(builtin.kind) {
+            kind = Some(builtin.kind);
+            write!(out, "\n## {}\n", builtin.kind.heading()).unwrap();
+        }
+
+        write!(
+            out,
+            "\n### `{}`\n\n{}\n\n{}\n",
+            builtin.signature(),
+            builtin.arity(),
+            builtin.doc
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+fn html_reference(builtins: &[BuiltinDoc]) -> String {
+    let mut out = String::from(concat!(
+        "<!DOCTYPE html>\n",
+        "<html>\n",
+        "<head>\n",
+        "<meta charset=\"utf-8\">\n",
+        "<title>Linefeed standard library</title>\n",
+        "</head>\n",
+        "<body>\n",
+        "<h1>Linefeed standard library</h1>\n",
+    ));
+
+    let mut kind = None;
+    for builtin in builtins {
+        if kind != Some(builtin.kind) {
+            kind = Some(builtin.kind);
+            writeln!(out, "<h2>{}</h2>", builtin.kind.heading()).unwrap();
+        }
+
+        writeln!(
+            out,
+            "<h3 id=\"{}-{}\"><code>{}</code></h3>\n<p>{}</p>\n<p>{}</p>",
+            builtin.kind.heading().to_lowercase(),
+            builtin.name,
+            escape_html(&builtin.signature()),
+            builtin.arity(),
+            inline_markdown_to_html(&builtin.doc)
+        )
+        .unwrap();
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// The docs only use `code spans` for markup
+fn inline_markdown_to_html(text: &str) -> String {
+    text.split('`')
+        .enumerate()
+        .map(|(i, part)| {
+            if i % 2 == 1 {
+                format!("<code>{}</code>", escape_html(part))
+            } else {
+                escape_html(part)
+            }
+        })
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,380 @@
+//! Formats programs in a consistent style: two spaces of indentation, one statement per line in
+//! blocks that span several lines, and a single space around operators.
+//!
+//! The AST decides the layout: where each statement of a block, each arm of a match and each block
+//! itself starts. It can't be printed back by itself, since the parser desugars some syntax and
+//! expands snippets, so the text comes from the tokens, comments included. Line breaks the program
+//! already has inside statements are kept, so long expressions stay split the way they were. A
+//! program is only formatted if its tokens are unchanged by it.
+
+use std::collections::{HashMap, HashSet};
+
+use chumsky::Parser as _;
+
+use crate::grammar::{
+    ast::{Expr, Span, Spanned},
+    lexer::{self, Token},
+    snippets::for_each_child,
+};
+
+const INDENT: &str = "  ";
+
+/// `src` formatted, or `None` if it doesn't parse.
+pub fn format(src: &str) -> Option<String> {
+    let tokens = lexer::lexer_with_comments().parse(src).into_result().ok()?;
+    let code = tokens
+        .iter()
+        .filter(|Spanned(token, _)| !matches!(token, Token::Comment(_)))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut ast = crate::parse_tokens(src, &code).ok()?;
+
+    let mut layouts = Layouts {
+        src,
+        tokens: &tokens,
+        blocks: HashMap::new(),
+    };
+    let mut top_level = HashSet::new();
+    layouts.statement_starts(&ast, &mut top_level);
+    layouts.visit(&mut ast);
+
+    let mut printer = Printer {
+        src,
+        tokens: &tokens,
+        blocks: &layouts.blocks,
+        out: String::new(),
+        line_indent: 0,
+        pending_newline: false,
+        contexts: vec![Context {
+            close: usize::MAX,
+            multiline: true,
+            items: top_level,
+            indent: 0,
+            outer_indent: 0,
+            brackets: Vec::new(),
+            is_empty: true,
+        }],
+    };
+    printer.print();
+    let formatted = printer.out;
+
+    // Formatting only moves tokens around, so anything else is a bug in the formatter, which
+    // mustn't cost the user their program
+    let reformatted = lexer::lexer_with_comments()
+        .parse(formatted.as_str())
+        .into_result()
+        .ok()?;
+    let same_tokens = tokens.len() == reformatted.len()
+        && tokens
+            .iter()
+            .zip(&reformatted)
+            .all(|(Spanned(a, _), Spanned(b, _))| match (a, b) {
+                (Token::Comment(a), Token::Comment(b)) => a.trim_end() == b.trim_end(),
+                (a, b) => a == b,
+            });
+
+    same_tokens.then_some(formatted)
+}
+
+/// A `{ ... }` that is laid out as a block: the body of a block or the arms of a match.
+struct Block {
+    close: usize,
+    /// Where each statement or arm starts
+    items: HashSet<usize>,
+    /// Whether the block spans several lines, so each item gets a line of its own
+    multiline: bool,
+}
+
+/// Finds the blocks of a program, keyed by where their opening brace is.
+struct Layouts<'a, 'src> {
+    src: &'a str,
+    tokens: &'a [Spanned<Token<'src>>],
+    blocks: HashMap<usize, Block>,
+}
+
+impl Layouts<'_, '_> {
+    fn visit(&mut self, expr: &mut Spanned<Expr>) {
+        let span = expr.span();
+
+        match &expr.0 {
+            Expr::Block(inner) if self.is_braced(span) => {
+                let mut items = HashSet::new();
+                self.statement_starts(inner, &mut items);
+                // An empty block has a null body with the span of the block
+                items.remove(&span.start);
+                self.add_block(span.start, span.end - 1, items);
+            }
+            Expr::Match(target, arms) => {
+                let open = self.tokens.iter().find(|Spanned(token, token_span)| {
+                    *token == Token::Ctrl('{')
+                        && token_span.start >= target.span().end
+                        && token_span.end <= span.end
+                });
+                if let (Some(Spanned(_, open)), true) = (open, self.is_ctrl_at(span.end - 1, '}')) {
+                    let items = arms
+                        .iter()
+                        .map(|(pattern, body)| pattern.span().start.min(body.span().start))
+                        .collect();
+                    self.add_block(open.start, span.end - 1, items);
+                }
+            }
+            _ => {}
+        }
+
+        for_each_child(expr, |child| self.visit(child));
+    }
+
+    // Nested blocks are visited later, so a block wrapping another with the same braces is replaced
+    // by the inner one, which knows the statements
+    fn add_block(&mut self, open: usize, close: usize, items: HashSet<usize>) {
+        let multiline = self.src[open..close].contains('\n');
+        self.blocks.insert(
+            open,
+            Block {
+                close,
+                items,
+                multiline,
+            },
+        );
+    }
+
+    // Sequences of statements are nested, and consecutive blocks like `if`s that aren't separated
+    // by semicolons are grouped in a block without braces
+    fn statement_starts(&self, expr: &Spanned<Expr>, starts: &mut HashSet<usize>) {
+        match &expr.0 {
+            Expr::Sequence(items) => {
+                for item in items {
+                    self.statement_starts(item, starts);
+                }
+            }
+            Expr::Block(inner) if !self.is_braced(expr.span()) => {
+                self.statement_starts(inner, starts)
+            }
+            _ => {
+                starts.insert(expr.span().start);
+            }
+        }
+    }
+
+    fn is_braced(&self, span: Span) -> bool {
+        span.end > span.start
+            && self.is_ctrl_at(span.start, '{')
+            && self.is_ctrl_at(span.end - 1, '}')
+    }
+
+    fn is_ctrl_at(&self, offset: usize, c: char) -> bool {
+        self.tokens
+            .binary_search_by_key(&offset, |Spanned(_, span)| span.start)
+            .is_ok_and(|i| self.tokens[i].0 == Token::Ctrl(c))
+    }
+}
+
+/// A block being printed. The whole program is one too.
+struct Context {
+    close: usize,
+    multiline: bool,
+    items: HashSet<usize>,
+    indent: usize,
+    /// The indentation of the line with the opening brace, which the closing brace gets as well
+    outer_indent: usize,
+    /// The indentation of the line each bracket still open in the block was opened on
+    brackets: Vec<usize>,
+    is_empty: bool,
+}
+
+struct Printer<'a, 'src> {
+    src: &'a str,
+    tokens: &'a [Spanned<Token<'src>>],
+    blocks: &'a HashMap<usize, Block>,
+    out: String,
+    line_indent: usize,
+    pending_newline: bool,
+    contexts: Vec<Context>,
+}
+
+impl Printer<'_, '_> {
+    fn print(&mut self) {
+        let (src, tokens) = (self.src, self.tokens);
+        let mut prev: Option<&Token> = None;
+        let mut prev_end = 0;
+        let mut prev_unary = false;
+
+        for (i, Spanned(token, span)) in tokens.iter().enumerate() {
+            let text = src[span.into_range()].trim_end();
+            // `else` goes on the line of the brace closing the block before it
+            let newlines = match (prev, token) {
+                (Some(Token::Ctrl('}')), Token::Else) => 0,
+                _ => src[prev_end..span.start].matches('\n').count(),
+            };
+            let blank_line = newlines > 1;
+            prev_end = span.end;
+
+            let context = self.contexts.last().expect("top-level context");
+            if *token == Token::Ctrl('}') && span.start == context.close {
+                let context = self.contexts.pop().expect("block context");
+                if context.is_empty {
+                    self.pending_newline = false;
+                } else if context.multiline {
+                    self.newline(false, context.outer_indent);
+                } else {
+                    self.out.push(' ');
+                }
+                self.out.push('}');
+                prev = Some(token);
+                prev_unary = false;
+                continue;
+            }
+
+            let starts_item = context.multiline && context.items.contains(&span.start);
+            let is_comment = matches!(token, Token::Comment(_));
+            if is_comment && newlines == 0 && !self.out.is_empty() {
+                // A comment after code on the same line stays there
+                self.out.push(' ');
+            } else if self.pending_newline || newlines > 0 || (starts_item && !context.is_empty) {
+                let after_open = context.is_empty;
+                let indent = self.indent_at(i);
+                self.newline(blank_line && !after_open, indent);
+            } else if let Some(prev) = prev {
+                let is_layout_brace = |offset| self.blocks.contains_key(&offset);
+                let space = match (prev, token) {
+                    (Token::Ctrl('{'), Token::Ctrl('}')) => false,
+                    // Blocks written on one line get spaces inside their braces, maps don't
+                    (Token::Ctrl('{'), _) => is_layout_brace(tokens[i - 1].1.start),
+                    (_, Token::Ctrl('}')) => false,
+                    (prev, next) => needs_space(prev, prev_unary, next),
+                };
+                if space {
+                    self.out.push(' ');
+                }
+            }
+
+            self.out.push_str(text);
+            self.contexts
+                .last_mut()
+                .expect("top-level context")
+                .is_empty = false;
+
+            prev_unary =
+                matches!(token, Token::Op("-" | "~" | "*" | "!")) && !prev.is_some_and(ends_value);
+            prev = Some(token);
+
+            match token {
+                Token::Comment(_) => self.pending_newline = true,
+                Token::Ctrl('{') if self.blocks.contains_key(&span.start) => {
+                    let block = &self.blocks[&span.start];
+                    self.contexts.push(Context {
+                        close: block.close,
+                        multiline: block.multiline,
+                        items: block.items.clone(),
+                        indent: self.line_indent + usize::from(block.multiline),
+                        outer_indent: self.line_indent,
+                        brackets: Vec::new(),
+                        is_empty: true,
+                    });
+                    self.pending_newline = block.multiline;
+                }
+                Token::Ctrl('(' | '[' | '{') => {
+                    let indent = self.line_indent;
+                    self.context().brackets.push(indent);
+                }
+                Token::Ctrl(')' | ']' | '}') => {
+                    self.context().brackets.pop();
+                }
+                _ => {}
+            }
+        }
+
+        let len = self.out.trim_end().len();
+        self.out.truncate(len);
+        self.out.push('\n');
+    }
+
+    fn context(&mut self) -> &mut Context {
+        self.contexts.last_mut().expect("top-level context")
+    }
+
+    fn newline(&mut self, blank_line: bool, indent: usize) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+            if blank_line {
+                self.out.push('\n');
+            }
+        }
+        self.out.push_str(&INDENT.repeat(indent));
+        self.line_indent = indent;
+        self.pending_newline = false;
+    }
+
+    // Statements start at the indentation of their block. Lines that continue one are indented once
+    // more than the line that opened the innermost bracket still open, or than the statement, and
+    // lines starting with closing brackets line up with the line that opened them
+    fn indent_at(&self, i: usize) -> usize {
+        let context = self.contexts.last().expect("top-level context");
+
+        // Comments are indented like what follows them
+        let Some(next) = self.tokens[i..]
+            .iter()
+            .position(|Spanned(token, _)| !matches!(token, Token::Comment(_)))
+            .map(|offset| i + offset)
+        else {
+            return context.indent;
+        };
+
+        let Spanned(token, span) = &self.tokens[next];
+        let closes_block = *token == Token::Ctrl('}') && span.start == context.close;
+        if context.is_empty || closes_block || context.items.contains(&span.start) {
+            return context.indent;
+        }
+
+        let closing = self.tokens[next..]
+            .iter()
+            .take_while(|Spanned(token, span)| {
+                matches!(token, Token::Ctrl(')' | ']' | '}')) && span.start != context.close
+            })
+            .count();
+        let brackets = &context.brackets;
+        match closing {
+            0 => brackets.last().map_or(context.indent, |indent| indent + 1),
+            closing => brackets
+                .get(brackets.len().saturating_sub(closing))
+                .copied()
+                .unwrap_or(context.indent),
+        }
+    }
+}
+
+fn needs_space(prev: &Token, prev_unary: bool, next: &Token) -> bool {
+    match (prev, next) {
+        // Operators next to each other could be lexed as a single one
+        (Token::Op(_), Token::Op(_)) => true,
+        (_, Token::Ctrl(',' | ';' | ')' | ']' | ':' | '.')) => false,
+        (Token::Ctrl('(' | '[' | '.'), _) => false,
+        // `x?.f()` and `x?[i]`
+        (Token::Op("?"), _) => false,
+        (prev, Token::Op("?")) => !ends_value(prev),
+        (Token::RangeExclusive | Token::RangeInclusive, next) => *next == Token::Ctrl('{'),
+        (prev, Token::RangeExclusive | Token::RangeInclusive) => !ends_value(prev),
+        // Calls and indexing
+        (prev, Token::Ctrl('(' | '[')) => !ends_value(prev),
+        // The modifiers of a regex, like `r/a+/i`
+        (Token::Regex(_), Token::Ident(_)) => false,
+        _ => !prev_unary,
+    }
+}
+
+fn ends_value(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Ident(_)
+            | Token::Int(_)
+            | Token::Float(_)
+            | Token::Str(_)
+            | Token::Regex(_)
+            | Token::Symbol(_)
+            | Token::Null
+            | Token::Bool(_)
+            | Token::Break
+            | Token::Continue
+            | Token::Ctrl(')' | ']' | '}')
+    )
+}
@@ -1,3 +1,5 @@
 pub mod ast;
+pub mod lang_version;
 pub mod lexer;
 pub mod parser;
+pub mod snippets;
@@ -42,6 +42,22 @@ pub enum Expr<'src> {
         Box<Spanned<Self>>,
     ),
     Match(Box<Spanned<Self>>, Vec<(Spanned<Self>, Spanned<Self>)>),
+    // A named `test "..." { ... }` block, only executed by the test runner
+    Test(String, Box<Spanned<Self>>),
+    // A `*xs` call argument, spreading the items of an iterable into the arguments
+    Spread(Box<Spanned<Self>>),
+    // An `import "path.lf"` statement, assigning the top-level variables of another file
+    Import(String),
+    // A `x?.method(..)` or `x?[i]` around the method call or index it guards, which is null
+    // instead of an error when `x` is null
+    NullSafe(Box<Spanned<Self>>),
+    // An `enum Name { A, B }` declaration, assigning `Name` the list of its variants
+    Enum(&'src str, Vec<&'src str>),
+    // A `Name.A` variant of an enum, resolved to its value at compile time
+    Variant(&'src str, &'src str),
+    // A `defer expr` statement, holding `expr` as a function without arguments that is called
+    // when the enclosing function returns, or when the program ends if it is at the top level
+    Defer(Box<Spanned<Self>>),
 }
 
 #[derive(Clone, Debug)]
@@ -52,6 +68,7 @@ pub enum AstValue<'src> {
     Float(f64),
     Str(String),
     Regex(String, RegexModifiers),
+    Symbol(&'src str),
     List(Vec<Self>),
     Tuple(Vec<Self>),
     Func(Func<'src>),
@@ -90,6 +107,8 @@ pub enum BinaryOp {
     BitwiseXor,
     LeftShift,
     RightShift,
+    /// `a ?? b`, which is `b` if `a` is null.
+    Coalesce,
 }
 
 #[derive(Clone, Debug)]
@@ -102,6 +121,10 @@ pub enum UnaryOp {
 #[derive(Debug, Clone)]
 pub struct Func<'src> {
     pub args: Vec<&'src str>,
+    /// The span of each argument's name.
+    pub arg_spans: Vec<Span>,
+    /// The default values of the last arguments, which are evaluated when a call leaves them out.
+    pub defaults: Vec<Spanned<Expr<'src>>>,
     pub body: Rc<Spanned<Expr<'src>>>,
     pub is_memoized: bool,
 }
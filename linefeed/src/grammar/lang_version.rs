@@ -0,0 +1,114 @@
+//! Versions of the language, so the syntax can change without breaking existing programs.
+//!
+//! A program opts into a version with a `#!lang v2` pragma on its first line, which is a comment
+//! to the lexer. Programs without one are version 1. Names that become keywords in a later version
+//! keep working as names in earlier ones, where the `future_keyword` lint warns about them so they
+//! can be renamed before moving to the new version.
+
+use chumsky::error::Rich;
+
+use crate::grammar::{
+    ast::{AstValue, Expr, Pattern, Span, Spanned},
+    snippets::for_each_child,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LangVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+/// Names that are keywords from version 2 on, reserved for syntax that needs them.
+const V2_KEYWORDS: &[&str] = &["collect", "enum", "defer"];
+
+impl LangVersion {
+    /// Reads the version from the `#!lang` pragma on the first line of `src`, if there is one.
+    pub fn from_src(src: &str) -> Result<Self, (Span, String)> {
+        let first_line = src.lines().next().unwrap_or_default();
+        let Some(version) = first_line.strip_prefix("#!lang") else {
+            return Ok(Self::V1);
+        };
+
+        match version.trim() {
+            "v1" => Ok(Self::V1),
+            "v2" => Ok(Self::V2),
+            version => Err((
+                Span::new(0, first_line.len()),
+                format!("Unknown language version '{version}', expected 'v1' or 'v2'"),
+            )),
+        }
+    }
+
+    /// The version in which `name` became a keyword, if it is one in any version.
+    pub fn keyword_since(name: &str) -> Option<Self> {
+        V2_KEYWORDS.contains(&name).then_some(Self::V2)
+    }
+
+    pub fn is_keyword(self, name: &str) -> bool {
+        Self::keyword_since(name).is_some_and(|since| since <= self)
+    }
+}
+
+impl std::fmt::Display for LangVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::V1 => write!(f, "v1"),
+            Self::V2 => write!(f, "v2"),
+        }
+    }
+}
+
+/// Reports the names in the program that are keywords in `version`. Keywords used as syntax, like
+/// `collect for`, are already part of the syntax tree, so any left are used as names.
+pub fn check_keyword_names<'src>(
+    ast: &mut Spanned<Expr<'src>>,
+    version: LangVersion,
+) -> Result<(), Vec<Rich<'src, String>>> {
+    let mut names = Vec::new();
+    find_names(ast, &mut names);
+
+    let errors = names
+        .into_iter()
+        .filter(|(name, _)| version.is_keyword(name))
+        .map(|(name, span)| {
+            Rich::custom(
+                span,
+                format!("'{name}' is a keyword in lang {version} and can't be used as a name"),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn find_names<'src>(expr: &mut Spanned<Expr<'src>>, names: &mut Vec<(&'src str, Span)>) {
+    let span = expr.span();
+    match &expr.0 {
+        Expr::Local(name) | Expr::Enum(name, _) => names.push((name, span)),
+        Expr::Assign(pattern, _)
+        | Expr::For(pattern, ..)
+        | Expr::ListComprehension(_, pattern, _) => pattern_names(pattern, names),
+        Expr::Value(AstValue::Func(func)) => names.extend(
+            func.args
+                .iter()
+                .copied()
+                .zip(func.arg_spans.iter().copied()),
+        ),
+        _ => {}
+    }
+
+    for_each_child(expr, |child| find_names(child, names));
+}
+
+fn pattern_names<'src>(pattern: &Spanned<Pattern<'src>>, names: &mut Vec<(&'src str, Span)>) {
+    match &pattern.0 {
+        Pattern::Ident(name) => names.push((name, pattern.span())),
+        Pattern::Sequence(patterns) => patterns.iter().for_each(|p| pattern_names(p, names)),
+        Pattern::Index(..) | Pattern::Value(_) => {}
+    }
+}
@@ -11,6 +11,7 @@ pub enum Token<'src> {
     Float(f64),
     Str(String),
     Regex(String),
+    Symbol(&'src str),
     Op(&'src str),
     Ctrl(char),
     Ident(&'src str),
@@ -32,6 +33,8 @@ pub enum Token<'src> {
     Memoized,
     RangeExclusive,
     RangeInclusive,
+    // A `# ...` comment up to the end of the line. Only kept by `lexer_with_comments`
+    Comment(&'src str),
 }
 
 impl fmt::Display for Token<'_> {
@@ -43,6 +46,7 @@ impl fmt::Display for Token<'_> {
             Token::Float(n) => write!(f, "{}", n),
             Token::Str(s) => write!(f, "{}", s),
             Token::Regex(r) => write!(f, "{}", r),
+            Token::Symbol(s) => write!(f, ":{}", s),
             Token::Op(s) => write!(f, "{}", s),
             Token::Ctrl(c) => write!(f, "{}", c),
             Token::Ident(s) => write!(f, "{}", s),
@@ -64,11 +68,25 @@ impl fmt::Display for Token<'_> {
             Token::Memoized => write!(f, "memoized"),
             Token::RangeExclusive => write!(f, ".."),
             Token::RangeInclusive => write!(f, "..="),
+            Token::Comment(s) => write!(f, "{}", s),
         }
     }
 }
 
+/// The tokens of a program, without its comments.
 pub fn lexer<'src>(
+) -> impl Parser<'src, &'src str, Vec<Spanned<Token<'src>>>, extra::Err<Rich<'src, char, Span>>> {
+    lexer_with_comments().map(|tokens| {
+        tokens
+            .into_iter()
+            .filter(|Spanned(token, _)| !matches!(token, Token::Comment(_)))
+            .collect()
+    })
+}
+
+/// The tokens of a program, including a [`Token::Comment`] for each comment, for tools that have
+/// to keep them, like the formatter.
+pub fn lexer_with_comments<'src>(
 ) -> impl Parser<'src, &'src str, Vec<Spanned<Token<'src>>>, extra::Err<Rich<'src, char, Span>>> {
     let int = text::int(10)
         .to_slice()
@@ -90,13 +108,31 @@ pub fn lexer<'src>(
         .then_ignore(just('"'))
         .map(Token::Str);
 
+    // The escapes of strings. A backslash followed by anything else is kept, so `"\d+"` stays as is
+    let escape = just('\\').ignore_then(choice((
+        just('n').to('\n'),
+        just('t').to('\t'),
+        just('\\'),
+        just('"'),
+    )));
+
     let simple_str = just('"')
+        .ignore_then(choice((escape.clone(), none_of('"'))).repeated().collect())
+        .then_ignore(just('"'))
+        .map(Token::Str);
+
+    // Triple-quoted strings can contain quotes, and a newline right after the opening quotes is
+    // dropped so the text can start on its own line. The closing quotes are the last three of the
+    // quotes that end the string, so the text can end with a quote too
+    let closing_quotes = just("\"\"\"").then(just('"').not());
+    let multiline_str = just("\"\"\"")
+        .ignore_then(just('\r').or_not().then(just('\n')).or_not())
         .ignore_then(
-            choice((just(r"\n").to('\n'), none_of('"')))
+            choice((escape, any().and_is(closing_quotes.clone().not())))
                 .repeated()
                 .collect(),
         )
-        .then_ignore(just('"'))
+        .then_ignore(closing_quotes)
         .map(Token::Str);
 
     let regex_str = just("r/")
@@ -104,19 +140,22 @@ pub fn lexer<'src>(
         .then_ignore(just('/'))
         .map(Token::Regex);
 
-    let str_ = raw_str.or(simple_str);
+    let str_ = choice((raw_str, multiline_str, simple_str));
 
     let range = choice((
         just("..=").to(Token::RangeInclusive),
         just("..").to(Token::RangeExclusive),
     ));
 
-    let op = one_of("+-*/!=<>%&|^~")
+    let op = one_of("+-*/!=<>%&|^~?")
         .repeated()
         .at_least(1)
         .to_slice()
         .map(Token::Op);
 
+    // A colon directly followed by a name is a symbol, so map entries need a space after the colon
+    let symbol = just(':').ignore_then(text::ident()).map(Token::Symbol);
+
     let ctrl = one_of("()[]{};,|.:").map(Token::Ctrl);
 
     let ident = text::ident().map(|ident: &str| match ident {
@@ -142,23 +181,25 @@ pub fn lexer<'src>(
         _ => Token::Ident(ident),
     });
 
-    let token = num
+    let comment = just('#')
+        .then(any().and_is(just('\n').not()).repeated())
+        .to_slice()
+        .map(Token::Comment);
+
+    let token = comment
+        .or(num)
         .or(str_)
         .or(regex_str)
         .or(range)
         .or(op)
+        .or(symbol)
         .or(ctrl)
         .or(ident)
         .recover_with(skip_then_retry_until(any().ignored(), end()))
         .boxed();
 
-    let comment = just('#')
-        .then(any().and_is(just('\n').not()).repeated())
-        .padded();
-
     token
         .map_with(|tok, e| Spanned(tok, e.span()))
-        .padded_by(comment.repeated())
         .padded()
         .repeated()
         .collect()
@@ -31,8 +31,13 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
 
         let block = expr
             .clone()
+            .or_not()
             .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}')))
-            .map_with(|expr, e| Spanned(Expr::Block(Box::new(expr)), e.span()))
+            .map_with(|expr, e| {
+                // An empty block evaluates to null, like a loop that never runs its body
+                let expr = expr.unwrap_or_else(|| Spanned(Expr::Value(AstValue::Null), e.span()));
+                Spanned(Expr::Block(Box::new(expr)), e.span())
+            })
             .recover_with(via_parser(nested_braces_delim.clone()))
             .memoized()
             .boxed();
@@ -83,7 +88,30 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
             })
             .memoized();
 
-        let block_expr = choice((block.clone(), if_, while_, for_))
+        // `collect` is only a keyword when followed by a `for` loop, which then evaluates to a list
+        // of the values of its body, like a list comprehension
+        let collect = just(Token::Ident("collect"))
+            .ignore_then(just(Token::For))
+            .ignore_then(loop_var.clone())
+            .then(just(Token::In).ignore_then(expr.clone()))
+            .then(block.clone())
+            .map_with(|((var, iter), body), e| {
+                Spanned(
+                    Expr::ListComprehension(Box::new(body), var, Box::new(iter)),
+                    e.span(),
+                )
+            })
+            .memoized()
+            .boxed();
+
+        // `test` is only a keyword when followed by a test name, so it remains usable as an identifier
+        let test = just(Token::Ident("test"))
+            .ignore_then(select! { Token::Str(name) => name }.labelled("test name"))
+            .then(block.clone())
+            .map_with(|(name, body), e| Spanned(Expr::Test(name, Box::new(body)), e.span()))
+            .memoized();
+
+        let block_expr = choice((block.clone(), if_, while_, for_, collect.clone(), test))
             .memoized()
             .boxed()
             .labelled("block expression");
@@ -97,12 +125,31 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
                 .collect::<Vec<_>>()
                 .boxed();
 
-            // Argument lists are just identifiers separated by commas, surrounded by parentheses
+            // Argument lists are identifiers separated by commas, surrounded by parentheses. The
+            // last arguments can be given default values, e.g. `(a, b = 10)`
             let args = ident
+                .map_with(|name, e| (name, e.span()))
+                .then(just(Token::Op("=")).ignore_then(expr.clone()).or_not())
                 .separated_by(just(Token::Ctrl(',')))
                 .allow_trailing()
                 .collect::<Vec<_>>()
                 .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')))
+                .validate(|args: Vec<(_, Option<Spanned<Expr>>)>, e, emitter| {
+                    let first_default = args.iter().position(|(_, default)| default.is_some());
+                    if first_default
+                        .is_some_and(|i| args[i..].iter().any(|(_, default)| default.is_none()))
+                    {
+                        emitter.emit(Rich::custom(
+                            e.span(),
+                            "Arguments without a default value must come before those with one",
+                        ));
+                    }
+
+                    let (names, defaults): (Vec<_>, Vec<_>) = args.into_iter().unzip();
+                    let (names, spans): (Vec<_>, Vec<_>) = names.into_iter().unzip();
+                    let defaults = defaults.into_iter().flatten().collect::<Vec<_>>();
+                    (names, spans, defaults)
+                })
                 .labelled("function args")
                 .memoized()
                 .boxed();
@@ -120,8 +167,11 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
                         .or(inline_expr.clone()),
                 )
                 .map_with(|(((is_memoized, name), args), body), e| {
+                    let (args, arg_spans, defaults) = args;
                     let val = Expr::Value(AstValue::Func(Func {
                         args,
+                        arg_spans,
+                        defaults,
                         body: Rc::new(body),
                         is_memoized: is_memoized.is_some(),
                     }));
@@ -153,13 +203,18 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
             // TODO: This should probably be in the lexer
             // Variable assignment
             let update_assign_op = choice((
-                just(Token::Op("+=")).to("+="),
-                just(Token::Op("-=")).to("-="),
-                just(Token::Op("*=")).to("*="),
-                just(Token::Op("/=")).to("/="),
-                just(Token::Op("%=")).to("%="),
-                just(Token::Op("&=")).to("&="),
-                just(Token::Op("|=")).to("|="),
+                just(Token::Op("+=")).to(BinaryOp::Add),
+                just(Token::Op("-=")).to(BinaryOp::Sub),
+                just(Token::Op("*=")).to(BinaryOp::Mul),
+                just(Token::Op("/=")).to(BinaryOp::Div),
+                just(Token::Op("//=")).to(BinaryOp::DivFloor),
+                just(Token::Op("%=")).to(BinaryOp::Mod),
+                just(Token::Op("**=")).to(BinaryOp::Pow),
+                just(Token::Op("&=")).to(BinaryOp::BitwiseAnd),
+                just(Token::Op("|=")).to(BinaryOp::BitwiseOr),
+                just(Token::Op("^=")).to(BinaryOp::BitwiseXor),
+                just(Token::Op("<<=")).to(BinaryOp::LeftShift),
+                just(Token::Op(">>=")).to(BinaryOp::RightShift),
             ));
 
             let update_assign = ident
@@ -169,16 +224,7 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
                     let new_val = Spanned(
                         Expr::Binary(
                             Box::new(Spanned(Expr::Local(name), e.span())),
-                            match op {
-                                "+=" => BinaryOp::Add,
-                                "-=" => BinaryOp::Sub,
-                                "*=" => BinaryOp::Mul,
-                                "/=" => BinaryOp::Div,
-                                "%=" => BinaryOp::Mod,
-                                "&=" => BinaryOp::BitwiseAnd,
-                                "|=" => BinaryOp::BitwiseOr,
-                                _ => unreachable!(),
-                            },
+                            op,
                             Box::new(val),
                         ),
                         e.span(),
@@ -263,6 +309,29 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
             let val = value_parser();
             let standalone_keyword = standalone_keyword_parser();
 
+            // Like `test`, `enum` is only a keyword when followed by a declaration
+            let enum_ = just(Token::Ident("enum"))
+                .ignore_then(ident.labelled("enum name"))
+                .then(
+                    ident
+                        .labelled("variant")
+                        .separated_by(just(Token::Ctrl(',')))
+                        .allow_trailing()
+                        .collect::<Vec<_>>()
+                        .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}'))),
+                )
+                .map(|(name, variants)| Expr::Enum(name, variants))
+                .labelled("enum")
+                .boxed();
+
+            // `Name.A` without parentheses, which would make it a method call
+            let variant = ident
+                .then_ignore(just(Token::Ctrl('.')))
+                .then(ident)
+                .then_ignore(just(Token::Ctrl('(')).not())
+                .map(|(enum_name, name)| Expr::Variant(enum_name, name))
+                .labelled("enum variant");
+
             // 'Atoms' are expressions that contain no ambiguity
             let atom = val
                 .or(standalone_keyword)
@@ -274,6 +343,8 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
                 .or(list_comprehension)
                 .or(func)
                 .or(match_expr)
+                .or(enum_)
+                .or(variant)
                 .or(ident.map(Expr::Local))
                 .map_with(|expr, e| Spanned(expr, e.span()))
                 // Atoms can also just be normal expressions, but surrounded with parentheses
@@ -285,7 +356,15 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
                 .memoized()
                 .boxed(); // Boxing significantly improves compile time
 
-            let call_with_args = items
+            let call_arg = just(Token::Op("*"))
+                .ignore_then(expr.clone())
+                .map_with(|arg, e| Spanned(Expr::Spread(Box::new(arg)), e.span()))
+                .or(expr.clone());
+
+            let call_with_args = call_arg
+                .separated_by(just(Token::Ctrl(',')))
+                .allow_trailing()
+                .collect::<Vec<_>>()
                 .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')))
                 .map_with(|args, e| Spanned(args, e.span()))
                 .labelled("function call args")
@@ -305,27 +384,38 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
 
             let index_into = index_into_parser(atom.clone(), inline_expr.clone());
 
-            let index_assign = index_assign_parser(index_into.clone(), inline_expr.clone())
-                .labelled("index assignment");
-
-            let call_or_index = func_call.or(index_assign).or(index_into).or(atom.clone());
+            let call_or_index = func_call.or(index_into).or(atom.clone());
+
+            let postfix_op = choice((
+                call_with_args.clone().map(|args| Postfix::Call(args.0)),
+                null_safe_index_parser(inline_expr.clone())
+                    .map(|(null_safe, idx)| Postfix::Index(idx, null_safe)),
+                just(Token::Op("?"))
+                    .or_not()
+                    .then_ignore(just(Token::Ctrl('.')))
+                    .then(ident)
+                    .then(call_with_args)
+                    .map(|((null_safe, method), args)| {
+                        Postfix::MethodCall(method, args.0, null_safe.is_some())
+                    }),
+            ));
 
-            let method_call = call_or_index
+            // After the leading calls or indexes, method calls, calls and indexing can follow
+            // each other in any order, e.g. `f(x)[0].len()` or `xs.map(f)[0]`
+            let postfix = call_or_index
                 .clone()
-                .foldl_with(
-                    just(Token::Ctrl('.'))
-                        .ignore_then(ident)
-                        .then(call_with_args)
-                        .repeated()
-                        .at_least(1),
-                    |val, (method, args), e| {
-                        Spanned(Expr::MethodCall(Box::new(val), method, args.0), e.span())
-                    },
-                )
+                .foldl_with(postfix_op.repeated().at_least(1), |val, op, e| {
+                    op.apply(val, e.span())
+                })
                 .memoized()
                 .boxed();
 
-            let with_method_call = choice((method_call, call_or_index)).boxed();
+            let with_postfix = postfix.or(call_or_index).boxed();
+
+            let index_assign = index_assign_parser(with_postfix.clone(), inline_expr.clone())
+                .labelled("index assignment");
+
+            let with_method_call = index_assign.or(with_postfix).boxed();
 
             let neg = just(Token::Op("-"))
                 .repeated()
@@ -359,6 +449,7 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
                     bitwise_parser,
                     compare_parser,
                     contains_parser,
+                    coalesce_parser,
                     logical_parser,
                 ],
             );
@@ -376,7 +467,51 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
                 .memoized()
                 .boxed();
 
-            range.or(logical).or(block_expr.clone()).or(return_)
+            // Like `test`, `import` is only a keyword when followed by what to import. `import name`
+            // is short for `import "name.lf"`
+            let import = just(Token::Ident("import"))
+                .ignore_then(
+                    select! {
+                        Token::Str(path) => path,
+                        Token::Ident(name) => format!("{name}.lf"),
+                    }
+                    .labelled("module path"),
+                )
+                .map_with(|path, e| Spanned(Expr::Import(path), e.span()))
+                .labelled("import")
+                .boxed();
+
+            // Like `test`, `defer` is only a keyword when followed by an expression. One that starts
+            // with a parenthesis, bracket or operator would make it a call, index or operand, so
+            // such expressions have to be wrapped in a block to be deferred
+            let defer = just(Token::Ident("defer"))
+                .then_ignore(
+                    any()
+                        .filter(|tok| matches!(tok, Token::Ctrl('(' | '[') | Token::Op(_)))
+                        .not(),
+                )
+                .ignore_then(inline_expr.clone())
+                .map_with(|body, e| {
+                    let func = Expr::Value(AstValue::Func(Func {
+                        args: Vec::new(),
+                        arg_spans: Vec::new(),
+                        defaults: Vec::new(),
+                        body: Rc::new(body),
+                        is_memoized: false,
+                    }));
+                    Spanned(Expr::Defer(Box::new(Spanned(func, e.span()))), e.span())
+                })
+                .labelled("defer")
+                .boxed();
+
+            // Tried before `range`, which would otherwise take `collect` to be a variable
+            import
+                .or(defer)
+                .or(collect.clone())
+                .or(range)
+                .or(logical)
+                .or(block_expr.clone())
+                .or(return_)
         });
 
         let postfix_if = inline_expr
@@ -428,7 +563,16 @@ pub fn expr_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned
             .memoized()
             .boxed();
 
-        block_chain
+        // A `{` followed by `key:` starts a map literal rather than a block, so the block parser's
+        // error recovery doesn't get to swallow it
+        let starts_with_map = just(Token::Ctrl('{'))
+            .then(inline_expr.clone())
+            .then(just(Token::Ctrl(':')))
+            .rewind();
+
+        starts_with_map
+            .ignore_then(inline_expr.clone())
+            .or(block_chain)
             .or(postfix_if)
             .or(postfix_unless)
             // Expressions, chained by semicolons, are statements
@@ -462,6 +606,7 @@ fn value_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Expr<'src>
         Token::Int(n) => Expr::Value(AstValue::Int(n)),
         Token::Float(n) => Expr::Value(AstValue::Float(n)),
         Token::Str(s) => Expr::Value(AstValue::Str(s)),
+        Token::Symbol(s) => Expr::Value(AstValue::Symbol(s)),
     }
     .labelled("value")
 }
@@ -624,6 +769,23 @@ fn bitwise_parser<'src, I: ParserInput<'src>>(
         .boxed()
 }
 
+fn coalesce_parser<'src, I: ParserInput<'src>>(
+    prev: impl Parser<'src, I, Spanned<Expr<'src>>>,
+) -> BoxedParser<'src, 'src, I> {
+    prev.clone()
+        .foldl_with(
+            just(Token::Op("??")).ignore_then(prev).repeated(),
+            |a, b, e| {
+                Spanned(
+                    Expr::Binary(Box::new(a), BinaryOp::Coalesce, Box::new(b)),
+                    e.span(),
+                )
+            },
+        )
+        .memoized()
+        .boxed()
+}
+
 fn logical_parser<'src, I: ParserInput<'src>>(
     prev: impl Parser<'src, I, Spanned<Expr<'src>>>,
 ) -> BoxedParser<'src, 'src, I> {
@@ -656,14 +818,25 @@ where
         .fold(prev, move |prev, parser| parser(prev))
 }
 
+// An index `[i]`, which is null-safe when written `?[i]`
+fn null_safe_index_parser<'src, I: ParserInput<'src>>(
+    expr: impl Parser<'src, I, Spanned<Expr<'src>>>,
+) -> impl Parser<'src, I, (bool, Spanned<Expr<'src>>)> {
+    just(Token::Op("?"))
+        .or_not()
+        .map(|null_safe| null_safe.is_some())
+        .then(expr.delimited_by(just(Token::Ctrl('[')), just(Token::Ctrl(']'))))
+}
+
 fn index_into_parser<'src, I: ParserInput<'src>>(
     atom: impl Parser<'src, I, Spanned<Expr<'src>>>,
     expr: impl Parser<'src, I, Spanned<Expr<'src>>>,
 ) -> impl Parser<'src, I, Spanned<Expr<'src>>> {
-    let index = expr.delimited_by(just(Token::Ctrl('[')), just(Token::Ctrl(']')));
+    let index = null_safe_index_parser(expr);
 
-    let index_into = atom.foldl_with(index.repeated().at_least(1), |val, idx, e| {
-        Spanned(Expr::Index(Box::new(val), Box::new(idx)), e.span())
+    let index_into = atom.foldl_with(index.repeated().at_least(1), |val, (null_safe, idx), e| {
+        let index = Spanned(Expr::Index(Box::new(val), Box::new(idx)), e.span());
+        null_safe_if(null_safe, index)
     });
 
     index_into.labelled("index").memoized()
@@ -674,9 +847,14 @@ fn index_assign_parser<'src, I: ParserInput<'src>>(
     val_parser: impl Parser<'src, I, Spanned<Expr<'src>>>,
 ) -> impl Parser<'src, I, Spanned<Expr<'src>>> {
     index_parser
+        .try_map(|expr, span| match &expr.0 {
+            Expr::Index(..) => Ok(expr),
+            Expr::NullSafe(inner) if matches!(inner.0, Expr::Index(..)) => Ok(expr),
+            _ => Err(Rich::custom(span, "Only indexed values can be assigned to")),
+        })
         .then_ignore(just(Token::Op("=")))
         .then(val_parser)
-        .map_with(|(indexed, value), e| match indexed.0 {
+        .validate(|(indexed, value), e, emitter| match indexed.0 {
             Expr::Index(target, idx) => Spanned(
                 Expr::Assign(
                     Spanned(Pattern::Index(target, idx), e.span()),
@@ -684,6 +862,13 @@ fn index_assign_parser<'src, I: ParserInput<'src>>(
                 ),
                 e.span(),
             ),
+            Expr::NullSafe(_) => {
+                emitter.emit(Rich::custom(
+                    indexed.span(),
+                    "Cannot assign to a null-safe index `?[...]`",
+                ));
+                Spanned(Expr::ParseError, e.span())
+            }
             _ => unreachable!(),
         })
 }
@@ -783,3 +968,37 @@ fn pattern_parser<'src, I: ParserInput<'src>>() -> impl Parser<'src, I, Spanned<
     .labelled("pattern")
     .boxed()
 }
+
+// Wraps a method call or index written with `?.` or `?[` so it is skipped on a null receiver
+fn null_safe_if(null_safe: bool, expr: Spanned<Expr>) -> Spanned<Expr> {
+    if null_safe {
+        let span = expr.span();
+        Spanned(Expr::NullSafe(Box::new(expr)), span)
+    } else {
+        expr
+    }
+}
+
+// An operation following an expression, e.g. the `(x)` in `f(x)`. Indexes and method calls
+// written with a leading `?` are null-safe.
+enum Postfix<'src> {
+    Call(Vec<Spanned<Expr<'src>>>),
+    Index(Spanned<Expr<'src>>, bool),
+    MethodCall(&'src str, Vec<Spanned<Expr<'src>>>, bool),
+}
+
+impl<'src> Postfix<'src> {
+    fn apply(self, val: Spanned<Expr<'src>>, span: Span) -> Spanned<Expr<'src>> {
+        let val = Box::new(val);
+        match self {
+            Postfix::Call(args) => Spanned(Expr::Call(val, args), span),
+            Postfix::Index(idx, null_safe) => {
+                null_safe_if(null_safe, Spanned(Expr::Index(val, Box::new(idx)), span))
+            }
+            Postfix::MethodCall(method, args, null_safe) => null_safe_if(
+                null_safe,
+                Spanned(Expr::MethodCall(val, method, args), span),
+            ),
+        }
+    }
+}
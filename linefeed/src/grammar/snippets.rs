@@ -0,0 +1,303 @@
+//! Built-in snippets: calls that are expanded into regular code right after parsing.
+//!
+//! Snippets cover boilerplate that nearly every Advent of Code solution repeats:
+//!
+//! - `part(n, answer)` prints `Part <n>: <answer>`
+//! - `aoc_parts(part1, part2)` prints both answers, i.e. `part(1, part1); part(2, part2)`
+//! - `dbg(value)` prints where it is and the source of its argument along with the value, i.e.
+//!   `inspect(value, "[<line>:<col>] <source> =")`
+//!
+//! Expansion is hygienic: snippets only ever introduce calls to builtins, each argument is
+//! evaluated exactly once and in order, and a program that defines its own variable or function
+//! with a snippet's name keeps using that instead. A program that defines a function with the name
+//! of a builtin a snippet calls can't use that snippet, as the call would go to its function.
+
+use std::{collections::HashSet, rc::Rc};
+
+use chumsky::error::Rich;
+
+use crate::{
+    compiler::analysis,
+    grammar::ast::{AstValue, BinaryOp, Expr, Pattern, Span, Spanned},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Snippet {
+    Part,
+    AocParts,
+    Dbg,
+}
+
+impl Snippet {
+    crate::compiler::method::define_names! {
+        /// Prints an answer labelled with its part number, as `Part <n>: <answer>`.
+        Part => "part",
+        /// Prints the answers to both parts, the same as `part(1, part1); part(2, part2)`.
+        AocParts => "aoc_parts",
+        /// Prints the line and column of the call, the source of the argument and its repr to
+        /// standard error, and returns the value, so it can be wrapped around any expression.
+        Dbg => "dbg",
+    }
+
+    pub(crate) fn num_args(&self) -> usize {
+        match self {
+            Self::Part => 2,
+            Self::AocParts => 2,
+            Self::Dbg => 1,
+        }
+    }
+
+    /// The builtins the expansion calls.
+    fn builtins(&self) -> &'static [&'static str] {
+        match self {
+            Self::Part | Self::AocParts => &["print", "str"],
+            Self::Dbg => &["inspect"],
+        }
+    }
+
+    fn expand<'src>(
+        &self,
+        mut args: Vec<Spanned<Expr<'src>>>,
+        span: Span,
+        src: &Source,
+    ) -> Expr<'src> {
+        match self {
+            Self::Part => {
+                let answer = args.pop().unwrap();
+                let n = args.pop().unwrap();
+
+                // print("Part " + str(n) + ":", answer)
+                let prefix = binary(string("Part ", span), call("str", vec![n], span), span);
+                let label = binary(prefix, string(":", span), span);
+                Expr::Call(Box::new(local("print", span)), vec![label, answer])
+            }
+
+            Self::AocParts => {
+                let parts = args
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, answer)| {
+                        let n = Spanned(Expr::Value(AstValue::Int(i as i64 + 1)), span);
+                        Spanned(Self::Part.expand(vec![n, answer], span, src), span)
+                    })
+                    .collect();
+                Expr::Sequence(parts)
+            }
+
+            Self::Dbg => {
+                let value = args.pop().unwrap();
+                let (line, col) = src.line_col(span.start);
+                let label = format!("[{line}:{col}] {} =", src.text(value.span()));
+                Expr::Call(
+                    Box::new(local("inspect", span)),
+                    vec![value, string(&label, span)],
+                )
+            }
+        }
+    }
+}
+
+// The source the program was parsed from, whose spans start `offset` bytes in when it's imported
+struct Source<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl Source<'_> {
+    fn text(&self, span: Span) -> &str {
+        &self.text[span.start - self.offset..span.end - self.offset]
+    }
+
+    // Both start at 1, and columns count characters rather than bytes
+    fn line_col(&self, pos: usize) -> (usize, usize) {
+        let before = &self.text[..pos - self.offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.matches('\n').count() + 1;
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+/// Expands all snippet calls in the program, reporting calls with the wrong number of arguments.
+/// The program was parsed from `src`, with spans moved `offset` bytes ahead.
+pub fn expand_snippets<'src>(
+    ast: &mut Spanned<Expr<'src>>,
+    src: &str,
+    offset: usize,
+) -> Result<(), Vec<Rich<'src, String>>> {
+    let user_defined = analysis::find_all_assignments(ast)
+        .into_iter()
+        .map(|Spanned(name, _)| name)
+        .collect::<HashSet<_>>();
+
+    let user_functions = analysis::find_function_definitions(ast);
+
+    let src = Source { text: src, offset };
+    let mut errors = Vec::new();
+    expand(ast, &src, &user_defined, &user_functions, &mut errors);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn expand<'src>(
+    expr: &mut Spanned<Expr<'src>>,
+    src: &Source,
+    user_defined: &HashSet<String>,
+    user_functions: &HashSet<String>,
+    errors: &mut Vec<Rich<'src, String>>,
+) {
+    // Expand the innermost snippets first, so snippet calls can be nested in snippet arguments
+    for_each_child(expr, |child| {
+        expand(child, src, user_defined, user_functions, errors)
+    });
+
+    let span = expr.span();
+    let Expr::Call(func, args) = &mut expr.0 else {
+        return;
+    };
+    let Expr::Local(name) = func.0 else {
+        return;
+    };
+    let Some(snippet) = Snippet::from_name(name).filter(|_| !user_defined.contains(name)) else {
+        return;
+    };
+
+    if args.len() != snippet.num_args() {
+        errors.push(Rich::custom(
+            span,
+            format!(
+                "Snippet {} expects {} arguments, got {}",
+                snippet.name(),
+                snippet.num_args(),
+                args.len()
+            ),
+        ));
+        return;
+    }
+
+    // A call to a builtin would go to the program's own function with its name instead
+    let builtins = snippet.builtins().iter();
+    if let Some(builtin) = builtins.copied().find(|b| user_functions.contains(*b)) {
+        errors.push(Rich::custom(
+            span,
+            format!(
+                "Snippet {} calls the builtin {builtin}, so it can't be used in a program that \
+                 defines its own function {builtin}",
+                snippet.name()
+            ),
+        ));
+        return;
+    }
+
+    expr.0 = snippet.expand(std::mem::take(args), span, src);
+}
+
+/// Calls `f` on each direct child expression of `expr`, including the body of a function.
+pub fn for_each_child<'src>(
+    expr: &mut Spanned<Expr<'src>>,
+    mut f: impl FnMut(&mut Spanned<Expr<'src>>),
+) {
+    match &mut expr.0 {
+        Expr::Value(AstValue::Func(func)) => {
+            func.defaults.iter_mut().for_each(&mut f);
+            f(Rc::make_mut(&mut func.body))
+        }
+
+        Expr::List(items) | Expr::Tuple(items) | Expr::Sequence(items) => {
+            items.iter_mut().for_each(f)
+        }
+
+        Expr::Map(items) => {
+            for (key, val) in items {
+                f(key);
+                f(val);
+            }
+        }
+
+        Expr::Match(target, arms) => {
+            f(target);
+            for (pattern, body) in arms {
+                f(pattern);
+                f(body);
+            }
+        }
+
+        Expr::Call(func, args) | Expr::MethodCall(func, _, args) => {
+            f(func);
+            args.iter_mut().for_each(f);
+        }
+
+        Expr::Assign(pattern, val) => {
+            if let Pattern::Index(target, index) = &mut pattern.0 {
+                f(target);
+                f(index);
+            }
+            f(val);
+        }
+
+        Expr::If(cond, then, otherwise) => {
+            f(cond);
+            f(then);
+            f(otherwise);
+        }
+
+        Expr::For(_, iterable, body) | Expr::ListComprehension(body, _, iterable) => {
+            f(iterable);
+            f(body);
+        }
+
+        Expr::Index(lhs, rhs) | Expr::Binary(lhs, _, rhs) | Expr::While(lhs, rhs) => {
+            f(lhs);
+            f(rhs);
+        }
+
+        Expr::Unary(_, inner)
+        | Expr::Block(inner)
+        | Expr::Return(inner)
+        | Expr::Test(_, inner)
+        | Expr::Spread(inner)
+        | Expr::NullSafe(inner)
+        | Expr::Defer(inner) => f(inner),
+
+        Expr::Value(_)
+        | Expr::Local(_)
+        | Expr::Break
+        | Expr::Continue
+        | Expr::ParseError
+        | Expr::Import(_)
+        | Expr::Enum(..)
+        | Expr::Variant(..) => {}
+    }
+}
+
+// All nodes created by an expansion get the span of the snippet call, so errors point at it
+
+fn local(name: &'static str, span: Span) -> Spanned<Expr<'static>> {
+    Spanned(Expr::Local(name), span)
+}
+
+fn string<'src>(s: &str, span: Span) -> Spanned<Expr<'src>> {
+    Spanned(Expr::Value(AstValue::Str(s.to_string())), span)
+}
+
+fn call<'src>(
+    name: &'static str,
+    args: Vec<Spanned<Expr<'src>>>,
+    span: Span,
+) -> Spanned<Expr<'src>> {
+    Spanned(Expr::Call(Box::new(local(name, span)), args), span)
+}
+
+fn binary<'src>(
+    lhs: Spanned<Expr<'src>>,
+    rhs: Spanned<Expr<'src>>,
+    span: Span,
+) -> Spanned<Expr<'src>> {
+    Spanned(
+        Expr::Binary(Box::new(lhs), BinaryOp::Add, Box::new(rhs)),
+        span,
+    )
+}
@@ -1,6 +1,9 @@
 #![feature(trait_alias)]
 
-use std::io::{self, Read, Write};
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+};
 
 use std::time::Instant;
 
@@ -9,87 +12,463 @@ use chumsky::error::Rich;
 use chumsky::prelude::*;
 
 use crate::{
-    compiler::Compiler,
+    compiler::{cfg::ControlFlowGraph, module::Modules, Compiler},
+    config::Config,
     grammar::{
         ast::{Expr, Span, Spanned},
+        lang_version::{self, LangVersion},
         lexer::{self, Token},
         parser::expr_parser,
+        snippets::expand_snippets,
     },
     vm::{BytecodeInterpreter, RuntimeError},
 };
 
+#[cfg(feature = "vm-state")]
+use crate::vm::{runtime_value::RuntimeValue, state::VmState};
+
+pub mod alloc_stats;
+pub mod batch;
 pub mod compiler;
+pub mod config;
+pub mod diff_run;
+pub mod docs;
+pub mod formatter;
 pub mod grammar;
+pub mod lint;
+pub mod python;
+pub mod repl;
+pub mod test_runner;
+pub mod type_check;
 pub mod vm;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub use chumsky;
 
-pub fn run(src: impl AsRef<str>) {
+type Interpreter = BytecodeInterpreter<io::Stdin, io::Stdout, io::Stderr>;
+
+/// Exit status reported when a program runs to completion.
+pub const EXIT_SUCCESS: i32 = 0;
+
+/// Exit status reported when a program fails to parse, compile or run.
+pub const EXIT_FAILURE: i32 = 1;
+
+pub fn run(src: impl AsRef<str>) -> i32 {
     let mut stdin = io::stdin();
     let mut stdout = io::stdout();
     let mut stderr = io::stderr();
-    run_with_handles(src, &mut stdin, &mut stdout, &mut stderr);
+    run_with_handles(src, &mut stdin, &mut stdout, &mut stderr)
 }
 
+/// Runs a program with the given handles and returns its exit status.
 pub fn run_with_handles(
     src: impl AsRef<str>,
-    mut stdin: impl Read,
-    mut stdout: impl Write,
+    stdin: impl Read,
+    stdout: impl Write,
+    stderr: impl Write,
+) -> i32 {
+    run_with_config(src, &Config::default(), stdin, stdout, stderr)
+}
+
+/// Runs a program with the given handles and settings, and returns its exit status.
+pub fn run_with_config(
+    src: impl AsRef<str>,
+    config: &Config,
+    stdin: impl Read,
+    stdout: impl Write,
+    stderr: impl Write,
+) -> i32 {
+    let compiler = Compiler::default();
+    run_with_hooks(
+        src.as_ref(),
+        compiler,
+        config,
+        stdin,
+        stdout,
+        stderr,
+        |vm| vm,
+        |_| {},
+    )
+}
+
+/// Runs the program in the file at `path`, whose imports are resolved relative to the file, and
+/// returns its exit status.
+pub fn run_file(
+    path: &Path,
+    config: &Config,
+    stdin: impl Read,
+    stdout: impl Write,
     mut stderr: impl Write,
-) {
+) -> i32 {
+    let Some(src) = read_program(path, &mut stderr) else {
+        return EXIT_FAILURE;
+    };
+    let compiler = Compiler::default().with_base_dir(base_dir(path));
+    run_with_hooks(
+        &src,
+        compiler,
+        config,
+        stdin,
+        stdout,
+        stderr,
+        |vm| vm,
+        |_| {},
+    )
+}
+
+/// Runs a program on top of `seed_stack` (see [`BytecodeInterpreter::with_seed_stack`]) and
+/// returns its exit status along with the final state of the VM, which is captured even if the
+/// program fails at runtime. The state is `None` if the program failed to parse or compile.
+#[cfg(feature = "vm-state")]
+pub fn run_with_state(
+    src: impl AsRef<str>,
+    seed_stack: Vec<RuntimeValue>,
+    stdin: impl Read,
+    stdout: impl Write,
+    stderr: impl Write,
+) -> (i32, Option<VmState>) {
+    let config = Config {
+        deterministic: true,
+        ..Config::default()
+    };
+
+    let mut state = None;
+    let status = run_with_hooks(
+        src.as_ref(),
+        Compiler::default(),
+        &config,
+        stdin,
+        stdout,
+        stderr,
+        |vm| vm.with_seed_stack(seed_stack),
+        |vm| state = Some(vm.state()),
+    );
+    (status, state)
+}
+
+/// Evaluates a list expression such as `[1, "a", (2, 3)]` into the values of a seed stack.
+#[cfg(feature = "vm-state")]
+pub fn eval_seed_stack(src: &str) -> Result<Vec<RuntimeValue>, String> {
+    let mut errors = Vec::new();
+    let (status, state) = run_with_state(src, Vec::new(), io::empty(), io::sink(), &mut errors);
+    if status != EXIT_SUCCESS {
+        return Err(String::from_utf8_lossy(&errors).into_owned());
+    }
+
+    match state.and_then(|state| state.stack.last().cloned()) {
+        Some(RuntimeValue::List(items)) => Ok(items.as_slice().to_vec()),
+        Some(val) => Err(format!("Seed stack must be a list, got {}", val.kind_str())),
+        None => Err("Seed stack must be a list".to_string()),
+    }
+}
+
+/// What [`check`] does besides parsing and compiling.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CheckOptions {
+    /// Warn about code that is valid but probably a mistake, see [`lint`].
+    pub lints: bool,
+    /// Report operations that fail on the types of their values, see [`type_check`].
+    pub types: bool,
+}
+
+/// Parses and compiles a program without running it, optionally linting and type checking it as
+/// well, and returns the exit status. Lints only fail the check if they are configured to `deny`,
+/// while type errors always do.
+pub fn check(
+    src: impl AsRef<str>,
+    config: &Config,
+    options: CheckOptions,
+    stderr: impl Write,
+) -> i32 {
+    check_with_compiler(src.as_ref(), Compiler::default(), config, options, stderr)
+}
+
+/// Like [`check`] for the program in the file at `path`, whose imports are resolved relative to
+/// the file.
+pub fn check_file(
+    path: &Path,
+    config: &Config,
+    options: CheckOptions,
+    mut stderr: impl Write,
+) -> i32 {
+    let Some(src) = read_program(path, &mut stderr) else {
+        return EXIT_FAILURE;
+    };
+    let compiler = Compiler::default().with_base_dir(base_dir(path));
+    check_with_compiler(&src, compiler, config, options, stderr)
+}
+
+fn check_with_compiler(
+    src: &str,
+    mut compiler: Compiler,
+    config: &Config,
+    options: CheckOptions,
+    mut stderr: impl Write,
+) -> i32 {
+    let tokens = match lexer::lexer().parse(src).into_output_errors() {
+        (Some(tokens), e) if e.is_empty() => tokens,
+        (_, e) => {
+            pretty_print_errors(stderr, src, e);
+            return EXIT_FAILURE;
+        }
+    };
+    let ast = match parse_tokens(src, &tokens) {
+        Ok(ast) => ast,
+        Err(errs) => {
+            pretty_print_errors(stderr, src, errs);
+            return EXIT_FAILURE;
+        }
+    };
+
+    if let Err(err) = compiler.compile(&ast) {
+        let span = err.span().unwrap_or(Span::new(0, 0));
+        pretty_print_error_at(stderr, src, compiler.modules(), span, err.msg());
+        return EXIT_FAILURE;
+    }
+
+    let mut status = EXIT_SUCCESS;
+
+    if options.lints {
+        let diagnostics = lint::lint(src, &ast, config);
+        lint::print_lints(&mut stderr, src, &diagnostics);
+
+        if diagnostics.iter().any(|d| d.level == lint::LintLevel::Deny) {
+            status = EXIT_FAILURE;
+        }
+    }
+
+    if options.types {
+        let errors = type_check::check_types(&ast);
+        type_check::print_type_errors(&mut stderr, src, &errors);
+
+        if !errors.is_empty() {
+            status = EXIT_FAILURE;
+        }
+    }
+
+    status
+}
+
+/// Compiles a program without running it and writes its control-flow graph to `out` in
+/// Graphviz's DOT language, returning the exit status.
+pub fn emit_dot(src: impl AsRef<str>, mut out: impl Write, stderr: impl Write) -> i32 {
     let src = src.as_ref();
+
+    let tokens = match lexer::lexer().parse(src).into_output_errors() {
+        (Some(tokens), e) if e.is_empty() => tokens,
+        (_, e) => {
+            pretty_print_errors(stderr, src, e);
+            return EXIT_FAILURE;
+        }
+    };
+    let ast = match parse_tokens(src, &tokens) {
+        Ok(ast) => ast,
+        Err(errs) => {
+            pretty_print_errors(stderr, src, errs);
+            return EXIT_FAILURE;
+        }
+    };
+
     let mut compiler = Compiler::default();
+    let program = match compiler.compile(&ast) {
+        Ok(program) => program,
+        Err(err) => {
+            let span = err.span().unwrap_or(Span::new(0, 0));
+            pretty_print_error_at(stderr, src, compiler.modules(), span, err.msg());
+            return EXIT_FAILURE;
+        }
+    };
+
+    let cfg = ControlFlowGraph::new(&program);
+    write!(out, "{}", cfg.to_dot(&program)).unwrap();
+    EXIT_SUCCESS
+}
+
+/// Transpiles a program to Python without running it and writes the Python source to `out`,
+/// returning the exit status. See [`python`] for how the languages differ.
+pub fn emit_python(src: impl AsRef<str>, mut out: impl Write, stderr: impl Write) -> i32 {
+    let src = src.as_ref();
+
+    let tokens = match lexer::lexer().parse(src).into_output_errors() {
+        (Some(tokens), e) if e.is_empty() => tokens,
+        (_, e) => {
+            pretty_print_errors(stderr, src, e);
+            return EXIT_FAILURE;
+        }
+    };
+    let ast = match parse_tokens(src, &tokens) {
+        Ok(ast) => ast,
+        Err(errs) => {
+            pretty_print_errors(stderr, src, errs);
+            return EXIT_FAILURE;
+        }
+    };
+
+    match python::transpile(&ast) {
+        Ok(python) => {
+            write!(out, "{python}").unwrap();
+            EXIT_SUCCESS
+        }
+        Err(err) => {
+            let span = err.span().unwrap_or(Span::new(0, 0));
+            pretty_print_error_at(stderr, src, &Modules::default(), span, err.msg());
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Compiles a program without running it and writes it to `out` as a WebAssembly module, returning
+/// the exit status. See [`wasm`] for what can be compiled.
+#[cfg(feature = "wasm")]
+pub fn emit_wasm(src: impl AsRef<str>, mut out: impl Write, stderr: impl Write) -> i32 {
+    let src = src.as_ref();
+
+    let tokens = match lexer::lexer().parse(src).into_output_errors() {
+        (Some(tokens), e) if e.is_empty() => tokens,
+        (_, e) => {
+            pretty_print_errors(stderr, src, e);
+            return EXIT_FAILURE;
+        }
+    };
+    let ast = match parse_tokens(src, &tokens) {
+        Ok(ast) => ast,
+        Err(errs) => {
+            pretty_print_errors(stderr, src, errs);
+            return EXIT_FAILURE;
+        }
+    };
 
+    let mut compiler = Compiler::default();
+    let module = match compiler
+        .compile_ir(&ast)
+        .and_then(|program| wasm::compile_to_wasm(&program))
+    {
+        Ok(module) => module,
+        Err(err) => {
+            let span = err.span().unwrap_or(Span::new(0, 0));
+            pretty_print_error_at(stderr, src, compiler.modules(), span, err.msg());
+            return EXIT_FAILURE;
+        }
+    };
+
+    out.write_all(&module).unwrap();
+    EXIT_SUCCESS
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_with_hooks<I: Read, O: Write, E: Write>(
+    src: &str,
+    mut compiler: Compiler,
+    config: &Config,
+    mut stdin: I,
+    mut stdout: O,
+    mut stderr: E,
+    setup: impl FnOnce(Interpreter) -> Interpreter,
+    finish: impl FnOnce(&BytecodeInterpreter<&mut I, &mut O, &mut E>),
+) -> i32 {
     let parse_start = Instant::now();
     let tokens = match lexer::lexer().parse(src).into_output_errors() {
         (Some(tokens), e) if e.is_empty() => tokens,
-        (_, e) => return pretty_print_errors(stderr, src, e),
+        (_, e) => {
+            pretty_print_errors(stderr, src, e);
+            return EXIT_FAILURE;
+        }
     };
     let ast = match parse_tokens(src, &tokens) {
         Ok(ast) => ast,
-        Err(errs) => return pretty_print_errors(stderr, src, errs),
+        Err(errs) => {
+            pretty_print_errors(stderr, src, errs);
+            return EXIT_FAILURE;
+        }
     };
     let parse_time = Instant::now().duration_since(parse_start);
 
     let compile_start = Instant::now();
+    compiler = compiler
+        .with_opt_level(config.opt_level)
+        .with_sandbox(config.sandbox);
     let program = match compiler.compile(&ast) {
         Ok(program) => program,
         Err(err) => {
             let span = err.span().unwrap_or(Span::new(0, 0));
-            return pretty_print_errors(stderr, src, vec![Rich::<&str>::custom(span, err.msg())]);
+            pretty_print_error_at(stderr, src, compiler.modules(), span, err.msg());
+            return EXIT_FAILURE;
         }
     };
     let compile_time = Instant::now().duration_since(compile_start);
 
+    let warnings = lint::compiler_diagnostics(src, compiler.take_warnings(), config);
+    lint::print_lints(&mut stderr, src, &warnings);
+    if warnings.iter().any(|d| d.level == lint::LintLevel::Deny) {
+        return EXIT_FAILURE;
+    }
+
     #[cfg(feature = "debug-vm")]
     program.disassemble(src.as_ref());
 
     let run_start = Instant::now();
+    let allocations_start = alloc_stats::allocations();
 
-    let bytecode_interpreter = BytecodeInterpreter::new(program);
+    let bytecode_interpreter = setup(BytecodeInterpreter::new(program).with_config(config));
     #[cfg(feature = "profile-vm")]
     let bytecode_interpreter = bytecode_interpreter.with_source(src);
     let mut bytecode_interpreter =
         bytecode_interpreter.with_handles(&mut stdin, &mut stdout, &mut stderr);
 
-    if let Err((span, err)) = bytecode_interpreter.run() {
-        return pretty_print_errors(stderr, src, vec![Rich::<RuntimeError>::custom(span, err)]);
-    }
+    let result = bytecode_interpreter.run();
+    finish(&bytecode_interpreter);
+
+    let status = match result {
+        Ok(()) => EXIT_SUCCESS,
+        Err((_, RuntimeError::Exit(code))) => code,
+        Err((span, err)) => {
+            pretty_print_error_at(stderr, src, compiler.modules(), span, err);
+            return EXIT_FAILURE;
+        }
+    };
 
     let run_time = Instant::now().duration_since(run_start);
     let instrs_executed = bytecode_interpreter.instructions_executed;
+    let allocations = alloc_stats::allocations() - allocations_start;
 
-    eprintln!(
-        "Parse time: {parse_time:?}, Compile time: {compile_time:?}, Run time: {run_time:?}. {instrs_executed} instructions executed.",
-    );
+    // Timings vary between runs, so they're left out when runs must be reproducible
+    if !config.deterministic {
+        // Nothing is counted unless the binary installs the counting allocator
+        let allocations = if allocations > 0 {
+            format!(", {allocations} allocations")
+        } else {
+            String::new()
+        };
+        eprintln!(
+            "Parse time: {parse_time:?}, Compile time: {compile_time:?}, Run time: {run_time:?}. {instrs_executed} instructions executed{allocations}.",
+        );
+    }
+
+    status
 }
 
 pub fn parse_tokens<'src>(
     src: &'src str,
     tokens: &'src [Spanned<Token<'src>>],
 ) -> Result<Spanned<Expr<'src>>, Vec<Rich<'src, String>>> {
+    parse_tokens_at(src, tokens, 0)
+}
+
+/// Like [`parse_tokens`], for tokens whose spans have been moved `offset` bytes ahead.
+pub(crate) fn parse_tokens_at<'src>(
+    src: &'src str,
+    tokens: &'src [Spanned<Token<'src>>],
+    offset: usize,
+) -> Result<Spanned<Expr<'src>>, Vec<Rich<'src, String>>> {
+    let version = LangVersion::from_src(src).map_err(|(span, msg)| {
+        let span = Span::new(span.start + offset, span.end + offset);
+        vec![Rich::custom(span, msg)]
+    })?;
+
+    let end = offset + src.len();
     let (ast, parse_errs) = expr_parser()
-        .parse(tokens.map((src.len()..src.len()).into(), |Spanned(t, s)| (t, s)))
+        .parse(tokens.map((end..end).into(), |Spanned(t, s)| (t, s)))
         .into_output_errors();
 
     if !parse_errs.is_empty() {
@@ -99,7 +478,41 @@ pub fn parse_tokens<'src>(
             .collect());
     }
 
-    Ok(ast.unwrap())
+    let mut ast = ast.unwrap();
+    expand_snippets(&mut ast, src, offset)?;
+    lang_version::check_keyword_names(&mut ast, version)?;
+    Ok(ast)
+}
+
+/// Reports an error at `span`, which is either in `src` or in one of the files it imports.
+pub fn pretty_print_error_at(
+    mut sink: impl Write,
+    src: &str,
+    modules: &Modules,
+    span: Span,
+    msg: impl ToString,
+) {
+    match modules.locate(span) {
+        Some((module, span)) => {
+            writeln!(sink, "In {}:", module.path.display()).unwrap();
+            pretty_print_errors(sink, &*module.src, vec![Rich::<&str>::custom(span, msg)]);
+        }
+        None => pretty_print_errors(sink, src, vec![Rich::<&str>::custom(span, msg)]),
+    }
+}
+
+fn read_program(path: &Path, mut stderr: impl Write) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(src) => Some(src),
+        Err(err) => {
+            writeln!(stderr, "error: failed to read {}: {err}", path.display()).unwrap();
+            None
+        }
+    }
+}
+
+fn base_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new("."))
 }
 
 pub fn pretty_print_errors(
@@ -110,7 +523,9 @@ pub fn pretty_print_errors(
     let errs = errs.into_iter().map(|e| e.map_token(|c| c.to_string()));
 
     errs.for_each(|e| {
-        let report = Report::build(ReportKind::Error, (), e.span().start);
+        // Errors at the end of input can have their start after their end, which ariadne rejects
+        let span = e.span().start.min(e.span().end)..e.span().start.max(e.span().end);
+        let report = Report::build(ReportKind::Error, (), span.start);
 
         let report = match e.reason() {
             chumsky::error::RichReason::ExpectedFound { expected, found } => report
@@ -132,7 +547,7 @@ pub fn pretty_print_errors(
                     }
                 ))
                 .with_label(
-                    Label::new(e.span().into_range())
+                    Label::new(span.clone())
                         .with_message(format!(
                             "Unexpected token {}",
                             e.found()
@@ -142,7 +557,7 @@ pub fn pretty_print_errors(
                         .with_color(Color::Red),
                 ),
             chumsky::error::RichReason::Custom(msg) => report.with_message(msg).with_label(
-                Label::new(e.span().into_range())
+                Label::new(span.clone())
                     .with_message(format!("{}", msg.fg(Color::Red)))
                     .with_color(Color::Red),
             ),
@@ -0,0 +1,497 @@
+//! Lints: warnings about code that is valid but most likely not what the author meant.
+//!
+//! Lints run over the AST after parsing. Each [`Lint`] has a level that can be changed in the
+//! `[lints]` table of a config file, and individual diagnostics can be silenced with a
+//! `# lint: allow(<name>, ...)` comment, either at the end of the offending line or on its own line
+//! right above it.
+//!
+//! Unused variables, unreachable code and constant conditions are also found by the compiler for
+//! every program it compiles, so running a program warns about them too (see
+//! [`compiler_diagnostics`]).
+
+use std::{collections::HashSet, io::Write};
+
+use ariadne::{Color, Label, Report, ReportKind, Source};
+use serde::Deserialize;
+
+use crate::{
+    compiler::{analysis, method::define_names, CompileWarning},
+    config::Config,
+    grammar::{
+        ast::{AstValue, Expr, Func, Pattern, Span, Spanned},
+        lang_version::LangVersion,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Lint {
+    UnusedPureExpression,
+    ConstantCondition,
+    ShadowedLoopVariable,
+    AssignmentInCondition,
+    FutureKeyword,
+    UnusedVariable,
+    UnreachableCode,
+}
+
+impl Lint {
+    define_names! {
+        UnusedPureExpression => "unused_pure_expression",
+        ConstantCondition => "constant_condition",
+        ShadowedLoopVariable => "shadowed_loop_variable",
+        AssignmentInCondition => "assignment_in_condition",
+        FutureKeyword => "future_keyword",
+        UnusedVariable => "unused_variable",
+        UnreachableCode => "unreachable_code",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintDiagnostic {
+    pub lint: Lint,
+    pub level: LintLevel,
+    pub span: Span,
+    pub msg: String,
+}
+
+/// The lints the compiler warns about, see [`compiler_warnings`].
+const COMPILER_LINTS: [Lint; 3] = [
+    Lint::UnusedVariable,
+    Lint::UnreachableCode,
+    Lint::ConstantCondition,
+];
+
+/// Runs all enabled lints over a parsed program.
+pub fn lint(src: &str, ast: &Spanned<Expr>, config: &Config) -> Vec<LintDiagnostic> {
+    let version = LangVersion::from_src(src).unwrap_or_default();
+    let found = Linter::new(ast, version).run(ast);
+    diagnostics(src, found, config)
+}
+
+/// Finds the lints the compiler warns about in a program it compiles, whatever their level.
+pub(crate) fn compiler_warnings(ast: &Spanned<Expr>) -> Vec<CompileWarning> {
+    Linter::new(ast, LangVersion::default())
+        .run(ast)
+        .into_iter()
+        .filter(|(lint, ..)| COMPILER_LINTS.contains(lint))
+        .map(|(lint, span, msg)| CompileWarning { lint, span, msg })
+        .collect()
+}
+
+/// Applies the levels in `config` and the `# lint: allow(...)` comments in `src` to the warnings
+/// from compiling `src`.
+pub fn compiler_diagnostics(
+    src: &str,
+    warnings: Vec<CompileWarning>,
+    config: &Config,
+) -> Vec<LintDiagnostic> {
+    let found = warnings
+        .into_iter()
+        .map(|warning| (warning.lint, warning.span, warning.msg))
+        .collect();
+    diagnostics(src, found, config)
+}
+
+fn diagnostics(
+    src: &str,
+    found: Vec<(Lint, Span, String)>,
+    config: &Config,
+) -> Vec<LintDiagnostic> {
+    let suppressions = find_suppressions(src);
+    let line_of = |offset: usize| src[..offset.min(src.len())].matches('\n').count();
+
+    found
+        .into_iter()
+        .filter_map(|(lint, span, msg)| {
+            let level = config.lint_level(lint);
+            let line = line_of(span.start);
+            let suppressed = suppressions
+                .iter()
+                .any(|(allowed_line, allowed)| *allowed_line == line && allowed.contains(&lint));
+
+            (level != LintLevel::Allow && !suppressed).then_some(LintDiagnostic {
+                lint,
+                level,
+                span,
+                msg,
+            })
+        })
+        .collect()
+}
+
+/// Prints lint diagnostics in the same style as compile errors.
+pub fn print_lints(mut sink: impl Write, src: &str, diagnostics: &[LintDiagnostic]) {
+    for diagnostic in diagnostics {
+        let (kind, color) = match diagnostic.level {
+            LintLevel::Deny => (ReportKind::Error, Color::Red),
+            _ => (ReportKind::Warning, Color::Yellow),
+        };
+
+        Report::build(kind, (), diagnostic.span.start)
+            .with_message(&diagnostic.msg)
+            .with_label(
+                Label::new(diagnostic.span.into_range())
+                    .with_message(format!(
+                        "silence with `# lint: allow({})`",
+                        diagnostic.lint.name()
+                    ))
+                    .with_color(color),
+            )
+            .finish()
+            .write(Source::from(src), &mut sink)
+            .unwrap();
+    }
+}
+
+// Returns the lints allowed by `# lint: allow(...)` comments, by the 0-indexed line they apply to
+fn find_suppressions(src: &str) -> Vec<(usize, Vec<Lint>)> {
+    src.lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let (code, comment) = text.split_once('#')?;
+            let names = comment
+                .trim_start()
+                .strip_prefix("lint:")?
+                .trim_start()
+                .strip_prefix("allow(")?
+                .split_once(')')?
+                .0;
+
+            let lints = names
+                .split(',')
+                .filter_map(|name| Lint::from_name(name.trim()))
+                .collect();
+
+            // A comment on a line of its own applies to the line below it
+            let target_line = if code.trim().is_empty() {
+                line + 1
+            } else {
+                line
+            };
+            Some((target_line, lints))
+        })
+        .collect()
+}
+
+struct Linter<'src> {
+    found: Vec<(Lint, Span, String)>,
+    // Loop variables of the loops currently being visited, innermost last
+    loop_vars: Vec<&'src str>,
+    version: LangVersion,
+    // The top level of the program, followed by the functions currently being visited
+    scopes: Vec<Scope<'src>>,
+}
+
+// The variables of a function or of the top level, resolved the way the compiler allocates them
+struct Scope<'src> {
+    vars: HashSet<String>,
+    // Those of `vars` read anywhere in the function, including in the functions inside it
+    reads: HashSet<&'src str>,
+    assignments: Vec<(&'src str, Span)>,
+}
+
+impl<'src> Linter<'src> {
+    fn new(ast: &Spanned<Expr>, version: LangVersion) -> Self {
+        let globals = analysis::find_all_assignments(ast)
+            .into_iter()
+            .map(|Spanned(name, _)| name)
+            .collect();
+
+        Self {
+            found: Vec::new(),
+            loop_vars: Vec::new(),
+            version,
+            scopes: vec![Scope {
+                vars: globals,
+                reads: HashSet::new(),
+                assignments: Vec::new(),
+            }],
+        }
+    }
+
+    fn run(mut self, ast: &Spanned<Expr<'src>>) -> Vec<(Lint, Span, String)> {
+        self.visit(ast);
+        self.found
+    }
+
+    fn report(&mut self, lint: Lint, span: Span, msg: impl Into<String>) {
+        self.found.push((lint, span, msg.into()));
+    }
+
+    fn visit(&mut self, expr: &Spanned<Expr<'src>>) {
+        match &expr.0 {
+            Expr::Sequence(exprs) => {
+                // Every value but the last one in a sequence is thrown away
+                if let Some((_, discarded)) = exprs.split_last() {
+                    for expr in discarded.iter().filter(|expr| is_pure(expr)) {
+                        self.report(
+                            Lint::UnusedPureExpression,
+                            expr.span(),
+                            "The result of this expression is never used",
+                        );
+                    }
+                }
+
+                let jump = exprs.iter().position(|expr| {
+                    matches!(expr.0, Expr::Return(_) | Expr::Break | Expr::Continue)
+                });
+                if let Some((first, last)) = jump
+                    .and_then(|i| exprs.get(i + 1..))
+                    .and_then(|rest| Some((rest.first()?, rest.last()?)))
+                {
+                    self.report(
+                        Lint::UnreachableCode,
+                        Span::new(first.span().start, last.span().end),
+                        "This code is never run, as it comes after leaving the function or loop",
+                    );
+                }
+
+                exprs.iter().for_each(|expr| self.visit(expr));
+            }
+
+            Expr::If(cond, then, otherwise) => {
+                self.check_condition(cond, "if");
+                self.visit(cond);
+                self.visit(then);
+                self.visit(otherwise);
+            }
+
+            Expr::While(cond, body) => {
+                // `while true` is the idiomatic infinite loop
+                if !matches!(cond.0, Expr::Value(AstValue::Bool(true))) {
+                    self.check_condition(cond, "while");
+                }
+                self.visit(cond);
+                self.visit(body);
+            }
+
+            Expr::For(pattern, iterable, body) => {
+                self.visit(iterable);
+                self.visit_loop(pattern, body);
+            }
+
+            Expr::ListComprehension(body, pattern, iterable) => {
+                self.visit(iterable);
+                self.visit_loop(pattern, body);
+            }
+
+            Expr::Value(AstValue::Func(func)) => {
+                // Functions have their own scope, so outer loop variables can't be shadowed
+                let outer_loop_vars = std::mem::take(&mut self.loop_vars);
+                for (arg, span) in func.args.iter().zip(&func.arg_spans) {
+                    self.check_name(arg, *span);
+                }
+                self.enter_function(func);
+                func.defaults.iter().for_each(|default| self.visit(default));
+                self.visit(&func.body);
+                self.exit_function();
+                self.loop_vars = outer_loop_vars;
+            }
+
+            Expr::Assign(pattern, val) => {
+                // Functions are often declared to be called from elsewhere, so they're left out.
+                // Top-level variables are too, as the program may be a module whose variables are
+                // read by the programs importing it.
+                if self.scopes.len() > 1 && !matches!(val.0, Expr::Value(AstValue::Func(_))) {
+                    let mut names = Vec::new();
+                    pattern_idents(pattern, &mut names);
+                    let Scope {
+                        vars, assignments, ..
+                    } = self.scopes.last_mut().unwrap();
+                    assignments.extend(names.into_iter().filter(|(name, _)| vars.contains(*name)));
+                }
+                self.visit_pattern(pattern);
+                self.visit(val);
+            }
+
+            Expr::Call(func, args) => {
+                self.visit(func);
+                args.iter().for_each(|arg| self.visit(arg));
+            }
+
+            Expr::MethodCall(target, _, args) => {
+                self.visit(target);
+                args.iter().for_each(|arg| self.visit(arg));
+            }
+
+            Expr::List(items) | Expr::Tuple(items) => {
+                items.iter().for_each(|item| self.visit(item))
+            }
+
+            Expr::Map(items) => {
+                for (key, val) in items {
+                    self.visit(key);
+                    self.visit(val);
+                }
+            }
+
+            Expr::Match(target, arms) => {
+                self.visit(target);
+                for (pattern, body) in arms {
+                    self.visit(pattern);
+                    self.visit(body);
+                }
+            }
+
+            Expr::Index(target, index) | Expr::Binary(target, _, index) => {
+                self.visit(target);
+                self.visit(index);
+            }
+
+            Expr::Unary(_, inner)
+            | Expr::Block(inner)
+            | Expr::Return(inner)
+            | Expr::Test(_, inner)
+            | Expr::Spread(inner)
+            | Expr::NullSafe(inner)
+            | Expr::Defer(inner) => self.visit(inner),
+
+            Expr::Local(name) => {
+                if let Some(scope) = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .find(|scope| scope.vars.contains(*name))
+                {
+                    scope.reads.insert(*name);
+                }
+                self.check_name(name, expr.span());
+            }
+
+            Expr::Value(_)
+            | Expr::Break
+            | Expr::Continue
+            | Expr::ParseError
+            | Expr::Import(_)
+            | Expr::Variant(..) => {}
+
+            Expr::Enum(name, _) => self.check_name(name, expr.span()),
+        }
+    }
+
+    fn visit_loop(&mut self, pattern: &Spanned<Pattern<'src>>, body: &Spanned<Expr<'src>>) {
+        let mut names = Vec::new();
+        pattern_idents(pattern, &mut names);
+
+        for (name, span) in &names {
+            if self.loop_vars.contains(name) {
+                self.report(
+                    Lint::ShadowedLoopVariable,
+                    *span,
+                    format!("Loop variable '{name}' shadows the variable of an enclosing loop"),
+                );
+            }
+        }
+
+        let depth = self.loop_vars.len();
+        self.loop_vars.extend(names.iter().map(|(name, _)| *name));
+        self.visit_pattern(pattern);
+        self.visit(body);
+        self.loop_vars.truncate(depth);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Spanned<Pattern<'src>>) {
+        match &pattern.0 {
+            Pattern::Sequence(patterns) => patterns.iter().for_each(|p| self.visit_pattern(p)),
+            Pattern::Index(target, index) => {
+                self.visit(target);
+                self.visit(index);
+            }
+            Pattern::Ident(name) => self.check_name(name, pattern.span()),
+            Pattern::Value(_) => {}
+        }
+    }
+
+    fn check_name(&mut self, name: &str, span: Span) {
+        if let Some(since) = LangVersion::keyword_since(name).filter(|&v| v > self.version) {
+            self.report(
+                Lint::FutureKeyword,
+                span,
+                format!("'{name}' is a keyword in lang {since}, rename it before moving to it"),
+            );
+        }
+    }
+
+    // A function's variables are its arguments and what it assigns that isn't a variable of a scope
+    // around it
+    fn enter_function(&mut self, func: &Func) {
+        let mut vars = func
+            .args
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<HashSet<_>>();
+        for Spanned(name, _) in analysis::find_all_assignments(&func.body) {
+            if !self.scopes.iter().any(|scope| scope.vars.contains(&name)) {
+                vars.insert(name);
+            }
+        }
+
+        self.scopes.push(Scope {
+            vars,
+            reads: HashSet::new(),
+            assignments: Vec::new(),
+        });
+    }
+
+    fn exit_function(&mut self) {
+        let scope = self
+            .scopes
+            .pop()
+            .expect("scope of the function being visited");
+        let mut reported = HashSet::new();
+        for (name, span) in scope.assignments {
+            if name.starts_with('_') || scope.reads.contains(name) || !reported.insert(name) {
+                continue;
+            }
+            self.report(
+                Lint::UnusedVariable,
+                span,
+                format!("Variable '{name}' is assigned but never read"),
+            );
+        }
+    }
+
+    fn check_condition(&mut self, cond: &Spanned<Expr>, keyword: &str) {
+        if matches!(cond.0, Expr::Assign(..)) {
+            self.report(
+                Lint::AssignmentInCondition,
+                cond.span(),
+                format!("Assignment used as the condition of `{keyword}`, did you mean `==`?"),
+            );
+        } else if let Ok(Some(_)) = analysis::eval_simple_constant(cond) {
+            self.report(
+                Lint::ConstantCondition,
+                cond.span(),
+                format!("The condition of this `{keyword}` is always the same"),
+            );
+        }
+    }
+}
+
+fn pattern_idents<'src>(pattern: &Spanned<Pattern<'src>>, names: &mut Vec<(&'src str, Span)>) {
+    match &pattern.0 {
+        Pattern::Ident(name) => names.push((*name, pattern.span())),
+        Pattern::Sequence(patterns) => patterns.iter().for_each(|p| pattern_idents(p, names)),
+        Pattern::Index(..) | Pattern::Value(_) => {}
+    }
+}
+
+// Expressions without side effects, so leaving out their result means they do nothing
+fn is_pure(expr: &Spanned<Expr>) -> bool {
+    match &expr.0 {
+        Expr::Value(_) | Expr::Local(_) | Expr::Variant(..) => true,
+        Expr::List(items) | Expr::Tuple(items) => items.iter().all(is_pure),
+        Expr::Map(items) => items.iter().all(|(key, val)| is_pure(key) && is_pure(val)),
+        Expr::Unary(_, inner) | Expr::NullSafe(inner) => is_pure(inner),
+        Expr::Binary(lhs, _, rhs) | Expr::Index(lhs, rhs) => is_pure(lhs) && is_pure(rhs),
+        _ => false,
+    }
+}
@@ -1,5 +1,259 @@
+use std::{
+    io::{BufRead, BufReader, IsTerminal as _, Read as _},
+    path::Path,
+};
+
+use linefeed::{
+    alloc_stats::CountingAllocator,
+    config::{Config, ConfigError},
+    vm::limits::{self, RunLimits},
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
 fn main() {
-    let filename = std::env::args().nth(1).unwrap();
+    let mut args = std::env::args().skip(1);
+    let status = match args.next().unwrap().as_str() {
+        "test" => {
+            let dir = args.next().unwrap_or_else(|| ".".to_string());
+            linefeed::test_runner::run_tests(dir, std::io::stdout())
+        }
+        "repl" => match Config::load(Path::new(".")) {
+            Ok(config) => linefeed::repl::run_repl(
+                &config,
+                std::io::stdin().lock(),
+                std::io::stdout(),
+                std::io::stderr(),
+            ),
+            Err(err) => {
+                eprintln!("{err}");
+                linefeed::EXIT_FAILURE
+            }
+        },
+        "compile" => {
+            let mut emit = None;
+            let filename = loop {
+                match args.next().unwrap().as_str() {
+                    flag if flag.starts_with("--emit=") => {
+                        emit = Some(flag["--emit=".len()..].to_string())
+                    }
+                    filename => break filename.to_string(),
+                }
+            };
+
+            let src = std::fs::read_to_string(&filename).unwrap();
+            match emit.as_deref() {
+                Some("dot") => linefeed::emit_dot(src, std::io::stdout(), std::io::stderr()),
+                #[cfg(feature = "wasm")]
+                Some("wasm") => linefeed::emit_wasm(src, std::io::stdout(), std::io::stderr()),
+                Some(format) => {
+                    eprintln!("Unknown output format '{format}', expected 'dot' or 'wasm'");
+                    linefeed::EXIT_FAILURE
+                }
+                None => {
+                    eprintln!("Missing output format, e.g. --emit=dot");
+                    linefeed::EXIT_FAILURE
+                }
+            }
+        }
+        "emit-python" => {
+            let src = std::fs::read_to_string(args.next().unwrap()).unwrap();
+            linefeed::emit_python(src, std::io::stdout(), std::io::stderr())
+        }
+        "diff-run" => {
+            let (a, b) = (args.next().unwrap(), args.next().unwrap());
+            let input = match (args.next().as_deref(), args.next()) {
+                (Some("--input"), Some(path)) => std::fs::read(path).unwrap(),
+                _ => {
+                    let mut input = Vec::new();
+                    std::io::stdin().read_to_end(&mut input).unwrap();
+                    input
+                }
+            };
+
+            let (a, b) = (Path::new(&a), Path::new(&b));
+            match load_config(a) {
+                Ok(config) => {
+                    linefeed::diff_run::diff_run(a, b, &config, &input, std::io::stdout())
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    linefeed::EXIT_FAILURE
+                }
+            }
+        }
+        "doc" => {
+            let (mut std, mut format) = (false, linefeed::docs::DocFormat::Markdown);
+            for arg in args.by_ref() {
+                match arg.as_str() {
+                    "--std" => std = true,
+                    "--html" => format = linefeed::docs::DocFormat::Html,
+                    _ => {}
+                }
+            }
+
+            if std {
+                print!("{}", linefeed::docs::std_reference(format));
+                linefeed::EXIT_SUCCESS
+            } else {
+                eprintln!("Nothing to document, expected --std");
+                linefeed::EXIT_FAILURE
+            }
+        }
+        "check" => {
+            let mut options = linefeed::CheckOptions::default();
+            let filename = loop {
+                match args.next().unwrap().as_str() {
+                    "--lints" => options.lints = true,
+                    "--types" => options.types = true,
+                    filename => break filename.to_string(),
+                }
+            };
+
+            let path = Path::new(&filename);
+            match load_config(path) {
+                Ok(config) => linefeed::check_file(path, &config, options, std::io::stderr()),
+                Err(err) => {
+                    eprintln!("{err}");
+                    linefeed::EXIT_FAILURE
+                }
+            }
+        }
+        #[cfg(feature = "vm-state")]
+        flag @ ("--seed-stack" | "--dump-state") => {
+            run_with_state(std::iter::once(flag.to_string()).chain(args))
+        }
+        "run" => run(args),
+        arg => run(std::iter::once(arg.to_string()).chain(args)),
+    };
+    std::process::exit(status);
+}
+
+/// Handles `[--time-limit <duration>] [--memory-limit <size>] [--repl-after-run] <file> [args]`,
+/// which runs a program, stopping it with an error if it goes over the limits, and optionally
+/// continues in the REPL afterwards. Arguments after the file are passed to the program.
+fn run(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut limits = RunLimits::default();
+    let mut repl_after_run = false;
+    let filename = loop {
+        let res = match args.next().as_deref() {
+            Some("--repl-after-run") => {
+                repl_after_run = true;
+                Ok(())
+            }
+            Some(flag @ "--time-limit") => {
+                flag_value(flag, args.next(), limits::parse_duration).map(|t| limits.time = Some(t))
+            }
+            Some(flag @ "--memory-limit") => flag_value(flag, args.next(), limits::parse_byte_size)
+                .map(|bytes| limits.memory = Some(bytes)),
+            Some(filename) => break filename.to_string(),
+            None => Err("Missing program file".to_string()),
+        };
+
+        if let Err(err) = res {
+            eprintln!("{err}");
+            return linefeed::EXIT_FAILURE;
+        }
+    };
+
+    let args = args.collect();
+    let path = Path::new(&filename);
+    match load_config(path) {
+        Ok(config) if repl_after_run => linefeed::repl::run_repl_after_file(
+            path,
+            &Config {
+                limits,
+                args,
+                ..config
+            },
+            std::io::stdin(),
+            repl_input(),
+            std::io::stdout(),
+            std::io::stderr(),
+        ),
+        Ok(config) => linefeed::run_file(
+            path,
+            &Config {
+                limits,
+                args,
+                ..config
+            },
+            std::io::stdin(),
+            std::io::stdout(),
+            std::io::stderr(),
+        ),
+        Err(err) => {
+            eprintln!("{err}");
+            linefeed::EXIT_FAILURE
+        }
+    }
+}
+
+/// Where the REPL reads from after a run. A program given piped input has used it up, so the REPL
+/// reads from the terminal instead.
+fn repl_input() -> Box<dyn BufRead> {
+    if std::io::stdin().is_terminal() {
+        // Not locked, since the program reads stdin too
+        return Box::new(BufReader::new(std::io::stdin()));
+    }
+
+    match std::fs::File::open("/dev/tty") {
+        Ok(tty) => Box::new(BufReader::new(tty)),
+        Err(_) => Box::new(std::io::empty()),
+    }
+}
+
+fn flag_value<T>(
+    flag: &str,
+    value: Option<String>,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<T, String> {
+    parse(&value.ok_or_else(|| format!("Missing value for {flag}"))?)
+}
+
+/// Loads the config applying to the directory containing `program`.
+fn load_config(program: &Path) -> Result<Config, ConfigError> {
+    let program = std::fs::canonicalize(program).unwrap_or_else(|_| program.to_path_buf());
+    Config::load(program.parent().unwrap_or(Path::new(".")))
+}
+
+/// Handles `[--seed-stack <list>] [--dump-state <path>] <file>`, which start the VM from a fixed
+/// stack and write its final state to a file, for reproducing and diffing runs.
+#[cfg(feature = "vm-state")]
+fn run_with_state(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut seed_stack = Vec::new();
+    let mut dump_path = None;
+
+    let filename = loop {
+        match args.next().as_deref() {
+            Some("--seed-stack") => {
+                seed_stack = match linefeed::eval_seed_stack(&args.next().unwrap()) {
+                    Ok(seed_stack) => seed_stack,
+                    Err(err) => {
+                        eprintln!("Invalid seed stack: {err}");
+                        return linefeed::EXIT_FAILURE;
+                    }
+                }
+            }
+            Some("--dump-state") => dump_path = args.next(),
+            Some(filename) => break filename.to_string(),
+            None => panic!("missing program file"),
+        }
+    };
+
     let src = std::fs::read_to_string(filename).unwrap();
-    linefeed::run(src);
+    let (status, state) = linefeed::run_with_state(
+        src,
+        seed_stack,
+        std::io::stdin(),
+        std::io::stdout(),
+        std::io::stderr(),
+    );
+
+    if let (Some(path), Some(state)) = (dump_path, state) {
+        std::fs::write(path, state.to_string()).unwrap();
+    }
+
+    status
 }
@@ -0,0 +1,1240 @@
+//! Transpilation of programs to Python.
+//!
+//! Linefeed's semantics are close to Python's, so most of a program maps to Python directly: blocks
+//! and `if` expressions become statements, functions become `def`s or lambdas, and values become
+//! their Python counterparts. Where the two languages differ, such as in how values are printed or
+//! how integer division rounds, the output calls small `lf_` helpers defined at its top. Regexes,
+//! enums, `match`, imports and `defer` have no readable counterpart and are reported as errors.
+//!
+//! Python can't have statements inside expressions, so an expression that needs them, like an `if`
+//! with blocks passed to a function, is computed into a temporary variable first. Its statements
+//! then run before the rest of the statement it is part of, which only matters if the parts
+//! before it have side effects.
+
+use std::collections::BTreeSet;
+
+use rustc_hash::FxHashSet;
+
+use crate::{
+    compiler::CompileError,
+    grammar::ast::{AstValue, BinaryOp, Expr, Func, Pattern, Span, Spanned, UnaryOp},
+};
+
+/// Transpiles a parsed program to the source of a Python program doing the same.
+pub fn transpile(ast: &Spanned<Expr>) -> Result<String, CompileError> {
+    let mut transpiler = Transpiler {
+        scopes: vec![Scope::default()],
+        ..Default::default()
+    };
+    transpiler.lower(ast, Target::Discard)?;
+    Ok(transpiler.finish())
+}
+
+// Python's operator precedences, from loosest to tightest
+const LAMBDA: u8 = 0;
+const CONDITIONAL: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const NOT: u8 = 4;
+const COMPARISON: u8 = 5;
+const BIT_OR: u8 = 6;
+const BIT_XOR: u8 = 7;
+const BIT_AND: u8 = 8;
+const SHIFT: u8 = 9;
+const SUM: u8 = 10;
+const PRODUCT: u8 = 11;
+const UNARY: u8 = 12;
+const POWER: u8 = 13;
+const ATOM: u8 = 14;
+
+// Names that can't be used for variables as they are, either because Python reserves them or
+// because the output relies on them
+const RESERVED: &[&str] = &[
+    "False",
+    "None",
+    "True",
+    "and",
+    "as",
+    "assert",
+    "async",
+    "await",
+    "class",
+    "def",
+    "del",
+    "elif",
+    "except",
+    "finally",
+    "from",
+    "global",
+    "is",
+    "lambda",
+    "nonlocal",
+    "not",
+    "pass",
+    "raise",
+    "try",
+    "with",
+    "yield",
+    "abs",
+    "all",
+    "any",
+    "bool",
+    "enumerate",
+    "float",
+    "functools",
+    "int",
+    "isinstance",
+    "iter",
+    "itertools",
+    "json",
+    "len",
+    "list",
+    "map",
+    "math",
+    "max",
+    "min",
+    "next",
+    "print",
+    "range",
+    "set",
+    "sorted",
+    "str",
+    "sum",
+    "sys",
+    "tuple",
+];
+
+/// A Python expression, with the precedence of its outermost operator so it is only
+/// parenthesized where needed.
+#[derive(Debug, Clone)]
+struct Py {
+    code: String,
+    prec: u8,
+}
+
+impl Py {
+    fn new(code: impl Into<String>, prec: u8) -> Self {
+        Self {
+            code: code.into(),
+            prec,
+        }
+    }
+
+    fn atom(code: impl Into<String>) -> Self {
+        Self::new(code, ATOM)
+    }
+
+    /// The expression as an operand of an operator with the given precedence.
+    fn at(&self, prec: u8) -> String {
+        if self.prec < prec {
+            format!("({})", self.code)
+        } else {
+            self.code.clone()
+        }
+    }
+}
+
+/// Where the value of a lowered expression goes.
+#[derive(Debug, Clone, Copy)]
+enum Target<'a> {
+    Discard,
+    Assign(&'a str),
+    // Returned from the function it ends
+    Return,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    // Linefeed names of the variables defined in the function
+    names: FxHashSet<String>,
+    // The `global` and `nonlocal` statements the function needs to assign outer variables
+    declarations: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Helper {
+    Print,
+    Str,
+    Repr,
+    Sorted,
+    Div,
+    DivFloor,
+    Mod,
+    Range,
+    ReadLine,
+}
+
+impl Helper {
+    fn dependencies(self) -> &'static [Helper] {
+        match self {
+            Helper::Print => &[Helper::Str],
+            Helper::Str => &[Helper::Repr, Helper::Sorted],
+            Helper::Repr => &[Helper::Str],
+            Helper::Mod => &[Helper::DivFloor],
+            _ => &[],
+        }
+    }
+
+    fn imports(self) -> &'static [&'static str] {
+        match self {
+            Helper::Print | Helper::ReadLine => &["import sys"],
+            Helper::Repr => &["import json"],
+            Helper::Div => &["from fractions import Fraction"],
+            Helper::Mod => &["import math"],
+            Helper::Range => &["import itertools"],
+            _ => &[],
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Helper::Print => {
+                r#"def lf_print(*values, file=sys.stdout):
+    print(*map(lf_str, values), file=file)
+    return values[-1] if values else None"#
+            }
+            Helper::Str => {
+                r#"def lf_str(value):
+    if value is None:
+        return "null"
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    if isinstance(value, float) and value.is_integer() and abs(value) < 1e16:
+        return str(int(value))
+    if isinstance(value, list):
+        return "[" + ", ".join(map(lf_repr, value)) + "]"
+    if isinstance(value, tuple):
+        return "(" + ", ".join(map(lf_repr, value)) + ")"
+    if isinstance(value, set):
+        return "{" + ", ".join(map(lf_repr, lf_sorted(value))) + "}"
+    if isinstance(value, dict):
+        items = lf_sorted(value.items())
+        return "{" + ", ".join(f"{lf_repr(k)}: {lf_repr(v)}" for k, v in items) + "}"
+    return str(value)"#
+            }
+            Helper::Repr => {
+                r#"def lf_repr(value):
+    return json.dumps(value, ensure_ascii=False) if isinstance(value, str) else lf_str(value)"#
+            }
+            Helper::Sorted => {
+                r#"def lf_sorted(values):
+    # Sets and maps are printed in order, as far as their items can be compared
+    try:
+        return sorted(values)
+    except TypeError:
+        return list(values)"#
+            }
+            Helper::Div => {
+                r#"def lf_div(a, b):
+    # Dividing integers gives an exact fraction, which is an integer if it divides evenly
+    q = Fraction(a, b) if isinstance(a, int) and isinstance(b, int) else a / b
+    return q.numerator if isinstance(q, Fraction) and q.denominator == 1 else q"#
+            }
+            Helper::DivFloor => {
+                r#"def lf_div_floor(a, b):
+    # Integer division rounds towards zero
+    if isinstance(a, int) and isinstance(b, int):
+        q = abs(a) // abs(b)
+        return q if (a < 0) == (b < 0) else -q
+    return a // b"#
+            }
+            Helper::Mod => {
+                r#"def lf_mod(a, b):
+    # The remainder has the sign of the dividend
+    if isinstance(a, float) or isinstance(b, float):
+        return math.fmod(a, b)
+    return a - b * lf_div_floor(a, b)"#
+            }
+            Helper::Range => {
+                r#"def lf_range(start, end):
+    # Ranges count down when they start above their end
+    if end is None:
+        return itertools.count(start)
+    return range(start, end) if start <= end else range(start, end, -1)"#
+            }
+            Helper::ReadLine => {
+                r#"def lf_read_line(prompt=""):
+    print(prompt, end="", flush=True)
+    line = sys.stdin.readline()
+    return line.removesuffix("\n").removesuffix("\r") if line else None"#
+            }
+        }
+    }
+}
+
+// The state to go back to when an expression turns out to need statements
+struct Snapshot {
+    lines: usize,
+    temps: usize,
+    scopes: Vec<Scope>,
+    helpers: BTreeSet<Helper>,
+    imports: BTreeSet<&'static str>,
+    separate_next: bool,
+}
+
+#[derive(Default)]
+struct Transpiler {
+    lines: Vec<String>,
+    indent: usize,
+    // The global scope, followed by the functions being transpiled, innermost last
+    scopes: Vec<Scope>,
+    helpers: BTreeSet<Helper>,
+    imports: BTreeSet<&'static str>,
+    temps: usize,
+    // Top-level functions are surrounded by two blank lines, as usual in Python
+    separate_next: bool,
+}
+
+impl Transpiler {
+    fn finish(self) -> String {
+        let mut helpers = self.helpers;
+        loop {
+            let needed = helpers
+                .iter()
+                .flat_map(|helper| helper.dependencies())
+                .copied()
+                .filter(|helper| !helpers.contains(helper))
+                .collect::<Vec<_>>();
+            if needed.is_empty() {
+                break;
+            }
+            helpers.extend(needed);
+        }
+
+        let mut imports = self.imports;
+        imports.extend(helpers.iter().flat_map(|helper| helper.imports()));
+
+        let mut sections = Vec::new();
+        if !imports.is_empty() {
+            sections.push(imports.into_iter().collect::<Vec<_>>().join("\n"));
+        }
+        sections.extend(helpers.iter().map(|helper| helper.source().to_string()));
+        sections.push(self.lines.join("\n"));
+
+        let mut out = sections.join("\n\n\n");
+        out.push('\n');
+        out
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        if self.separate_next && self.indent == 0 {
+            self.separate();
+        }
+        let indent = "    ".repeat(self.indent);
+        self.lines.push(format!("{indent}{}", line.as_ref()));
+    }
+
+    fn separate(&mut self) {
+        if self.lines.last().is_some_and(|line| !line.is_empty()) {
+            self.lines.extend([String::new(), String::new()]);
+        }
+        self.separate_next = false;
+    }
+
+    // Emits the statements from `f` as an indented block, which is `pass` if there are none
+    fn indented(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), CompileError>,
+    ) -> Result<(), CompileError> {
+        self.indent += 1;
+        let start = self.lines.len();
+        f(self)?;
+        if self.lines.len() == start {
+            self.line("pass");
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    fn temp(&mut self, prefix: &str) -> String {
+        self.temps += 1;
+        format!("{prefix}{}", self.temps)
+    }
+
+    fn helper(&mut self, helper: Helper, args: &[&Py]) -> Py {
+        self.helpers.insert(helper);
+        let name = match helper {
+            Helper::Print => "lf_print",
+            Helper::Str => "lf_str",
+            Helper::Repr => "lf_repr",
+            Helper::Sorted => "lf_sorted",
+            Helper::Div => "lf_div",
+            Helper::DivFloor => "lf_div_floor",
+            Helper::Mod => "lf_mod",
+            Helper::Range => "lf_range",
+            Helper::ReadLine => "lf_read_line",
+        };
+        let args = args.iter().map(|arg| arg.code.as_str()).collect::<Vec<_>>();
+        Py::atom(format!("{name}({})", args.join(", ")))
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            lines: self.lines.len(),
+            temps: self.temps,
+            scopes: self.scopes.clone(),
+            helpers: self.helpers.clone(),
+            imports: self.imports.clone(),
+            separate_next: self.separate_next,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.lines.truncate(snapshot.lines);
+        self.temps = snapshot.temps;
+        self.scopes = snapshot.scopes;
+        self.helpers = snapshot.helpers;
+        self.imports = snapshot.imports;
+        self.separate_next = snapshot.separate_next;
+    }
+
+    // Transpiles an expression only if it doesn't need any statements
+    fn try_expr(&mut self, expr: &Spanned<Expr>) -> Result<Option<Py>, CompileError> {
+        let snapshot = self.snapshot();
+        let py = self.expr(expr)?;
+        if self.lines.len() == snapshot.lines {
+            Ok(Some(py))
+        } else {
+            self.restore(snapshot);
+            Ok(None)
+        }
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.names.contains(name))
+    }
+
+    // Returns the Python name of a variable being assigned, declaring it in the current function
+    // unless it refers to a variable of an enclosing function or the global scope
+    fn bind(&mut self, name: &str) -> String {
+        let py = python_name(name);
+        let (outer, current) = self.scopes.split_at_mut(self.scopes.len() - 1);
+        let current = &mut current[0];
+        if current.names.contains(name) {
+            return py;
+        }
+
+        match outer.iter().rposition(|scope| scope.names.contains(name)) {
+            Some(0) => current.declarations.insert(format!("global {py}")),
+            Some(_) => current.declarations.insert(format!("nonlocal {py}")),
+            None => current.names.insert(name.to_string()),
+        };
+        py
+    }
+
+    fn lower(&mut self, expr: &Spanned<Expr>, target: Target) -> Result<(), CompileError> {
+        match &expr.0 {
+            Expr::Sequence(exprs) => match exprs.split_last() {
+                Some((last, init)) => {
+                    for expr in init {
+                        self.lower(expr, Target::Discard)?;
+                    }
+                    self.lower(last, target)?;
+                }
+                None => self.finish_value(target, Py::atom("None")),
+            },
+            Expr::Block(inner) => self.lower(inner, target)?,
+
+            Expr::If(cond, then, otherwise) => {
+                let cond = self.expr(cond)?;
+                if !matches!(target, Target::Discard) {
+                    if let Some(py) = self.conditional(&cond, then, otherwise)? {
+                        self.finish_value(target, py);
+                        return Ok(());
+                    }
+                }
+                self.if_statement(&cond, then, otherwise, target)?;
+            }
+            Expr::While(cond, body) => {
+                self.while_loop(cond, body)?;
+                self.finish_statement(target);
+            }
+            Expr::For(pattern, iterable, body) => {
+                let iterable = self.expr(iterable)?;
+                let pattern = self.pattern(pattern, false)?;
+                self.line(format!("for {pattern} in {}:", iterable.at(OR)));
+                self.indented(|t| t.lower(body, Target::Discard))?;
+                self.finish_statement(target);
+            }
+            Expr::Break => self.line("break"),
+            Expr::Continue => self.line("continue"),
+            Expr::Return(value) => self.return_(value, expr.span())?,
+
+            Expr::Assign(pattern, value) => {
+                let assigned = self.assign(pattern, value)?;
+                if !matches!(target, Target::Discard) {
+                    self.finish_value(target, assigned);
+                }
+            }
+            // Tests are only run by the test runner
+            Expr::Test(..) => {}
+            Expr::Value(AstValue::Null) if !matches!(target, Target::Assign(_)) => {}
+
+            _ => {
+                let py = self.expr(expr)?;
+                self.finish_value(target, py);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish_value(&mut self, target: Target, py: Py) {
+        match target {
+            Target::Discard => self.line(py.code),
+            Target::Assign(name) => self.line(format!("{name} = {}", py.code)),
+            Target::Return if py.code == "None" => {}
+            Target::Return => self.line(format!("return {}", py.code)),
+        }
+    }
+
+    // Statements like loops are null as values
+    fn finish_statement(&mut self, target: Target) {
+        if let Target::Assign(name) = target {
+            self.line(format!("{name} = None"));
+        }
+    }
+
+    fn conditional(
+        &mut self,
+        cond: &Py,
+        then: &Spanned<Expr>,
+        otherwise: &Spanned<Expr>,
+    ) -> Result<Option<Py>, CompileError> {
+        let snapshot = self.snapshot();
+        match (self.try_expr(then)?, self.try_expr(otherwise)?) {
+            (Some(then), Some(otherwise)) => Ok(Some(Py::new(
+                format!(
+                    "{} if {} else {}",
+                    then.at(OR),
+                    cond.at(OR),
+                    otherwise.at(CONDITIONAL)
+                ),
+                CONDITIONAL,
+            ))),
+            _ => {
+                self.restore(snapshot);
+                Ok(None)
+            }
+        }
+    }
+
+    fn if_statement(
+        &mut self,
+        cond: &Py,
+        then: &Spanned<Expr>,
+        otherwise: &Spanned<Expr>,
+        target: Target,
+    ) -> Result<(), CompileError> {
+        self.line(format!("if {}:", cond.code));
+        self.indented(|t| t.lower(then, target))?;
+
+        let mut otherwise = otherwise;
+        loop {
+            match &otherwise.0 {
+                // Returning at the end of a function returns null anyway
+                Expr::Value(AstValue::Null) if !matches!(target, Target::Assign(_)) => {
+                    return Ok(())
+                }
+                Expr::If(cond, then, next) => {
+                    if let Some(cond) = self.try_expr(cond)? {
+                        self.line(format!("elif {}:", cond.code));
+                        self.indented(|t| t.lower(then, target))?;
+                        otherwise = next;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+
+            self.line("else:");
+            return self.indented(|t| t.lower(otherwise, target));
+        }
+    }
+
+    fn while_loop(
+        &mut self,
+        cond: &Spanned<Expr>,
+        body: &Spanned<Expr>,
+    ) -> Result<(), CompileError> {
+        if let Some(cond) = self.try_expr(cond)? {
+            self.line(format!("while {}:", cond.code));
+            return self.indented(|t| t.lower(body, Target::Discard));
+        }
+
+        // The statements computing the condition have to run before each iteration
+        self.line("while True:");
+        self.indented(|t| {
+            let cond = t.expr(cond)?;
+            t.line(format!("if not {}:", cond.at(NOT)));
+            t.indented(|t| {
+                t.line("break");
+                Ok(())
+            })?;
+            t.lower(body, Target::Discard)
+        })
+    }
+
+    fn return_(&mut self, value: &Spanned<Expr>, span: Span) -> Result<(), CompileError> {
+        if self.scopes.len() == 1 {
+            return Err(unsupported("Returning outside of a function", span));
+        }
+
+        match self.expr(value)? {
+            py if py.code == "None" => self.line("return"),
+            py => self.line(format!("return {}", py.code)),
+        }
+        Ok(())
+    }
+
+    // Emits an assignment, returning the assigned value as an expression
+    fn assign(
+        &mut self,
+        pattern: &Spanned<Pattern>,
+        value: &Spanned<Expr>,
+    ) -> Result<Py, CompileError> {
+        if let (Pattern::Ident(name), Expr::Value(AstValue::Func(func))) = (&pattern.0, &value.0) {
+            let name = self.bind(name);
+            self.def(&name, func)?;
+            return Ok(Py::atom(name));
+        }
+
+        // `x += 1` is parsed as `x = x + 1`
+        if let (Pattern::Ident(name), Expr::Binary(lhs, op, rhs)) = (&pattern.0, &value.0) {
+            if let (Expr::Local(local), Some(op)) = (&lhs.0, augmented_op(op)) {
+                if local == name {
+                    let rhs = self.expr(rhs)?;
+                    let name = self.bind(name);
+                    self.line(format!("{name} {op}= {}", rhs.code));
+                    return Ok(Py::atom(name));
+                }
+            }
+        }
+
+        let value = self.expr(value)?;
+        let target = self.pattern(pattern, false)?;
+        self.line(format!("{target} = {}", value.code));
+        Ok(match &pattern.0 {
+            Pattern::Sequence(_) => Py::atom(format!("({target})")),
+            _ => Py::atom(target),
+        })
+    }
+
+    // Returns the assignment target for a pattern, binding the variables in it
+    fn pattern(
+        &mut self,
+        pattern: &Spanned<Pattern>,
+        nested: bool,
+    ) -> Result<String, CompileError> {
+        match &pattern.0 {
+            Pattern::Ident(name) => Ok(self.bind(name)),
+            Pattern::Sequence(items) => {
+                let mut targets = Vec::new();
+                for item in items {
+                    targets.push(self.pattern(item, true)?);
+                }
+                let targets = match targets.as_slice() {
+                    [single] => format!("{single},"),
+                    _ => targets.join(", "),
+                };
+                Ok(if nested {
+                    format!("({targets})")
+                } else {
+                    targets
+                })
+            }
+            Pattern::Index(indexed, index) => {
+                let indexed = self.expr(indexed)?;
+                let index = self.expr(index)?;
+                Ok(format!("{}[{}]", indexed.at(ATOM), index.code))
+            }
+            Pattern::Value(_) => Err(unsupported("Matching a value", pattern.span())),
+        }
+    }
+
+    // Emits `def name(args):` with the function's body, returning its parameters
+    fn def(&mut self, name: &str, func: &Func) -> Result<String, CompileError> {
+        let first_default = func.args.len() - func.defaults.len();
+        let mut params = Vec::new();
+        let mut late_defaults = Vec::new();
+        for (i, arg) in func.args.iter().enumerate() {
+            let param = python_name(arg);
+            let Some(default) = i.checked_sub(first_default).map(|i| &func.defaults[i]) else {
+                params.push(param);
+                continue;
+            };
+
+            // Python evaluates defaults once, when the function is defined, and Linefeed every
+            // time one is left out, so only constants can be Python defaults
+            match &default.0 {
+                Expr::Value(
+                    value @ (AstValue::Null
+                    | AstValue::Bool(_)
+                    | AstValue::Int(_)
+                    | AstValue::Float(_)
+                    | AstValue::Str(_)),
+                ) => {
+                    let value = self.value(value, default.span())?;
+                    params.push(format!("{param}={}", value.code));
+                }
+                _ => {
+                    params.push(format!("{param}=None"));
+                    late_defaults.push((param, default));
+                }
+            }
+        }
+        let params = params.join(", ");
+
+        if self.indent == 0 {
+            self.separate();
+        }
+        if func.is_memoized {
+            self.imports.insert("import functools");
+            self.line("@functools.cache");
+        }
+        self.line(format!("def {name}({params}):"));
+        let body_start = self.lines.len();
+        self.scopes.push(Scope {
+            names: func.args.iter().map(|arg| arg.to_string()).collect(),
+            declarations: BTreeSet::new(),
+        });
+
+        self.indent += 1;
+        for (param, default) in late_defaults {
+            self.line(format!("if {param} is None:"));
+            self.indented(|t| {
+                let default = t.expr(default)?;
+                t.line(format!("{param} = {}", default.code));
+                Ok(())
+            })?;
+        }
+        self.lower(&func.body, Target::Return)?;
+
+        let scope = self.scopes.pop().unwrap();
+        let indent = "    ".repeat(self.indent);
+        let declarations = scope
+            .declarations
+            .into_iter()
+            .map(|declaration| format!("{indent}{declaration}"));
+        self.lines.splice(body_start..body_start, declarations);
+        if self.lines.len() == body_start {
+            self.line("pass");
+        }
+        self.indent -= 1;
+        self.separate_next = self.indent == 0;
+
+        Ok(params)
+    }
+
+    fn func_value(&mut self, func: &Func) -> Result<Py, CompileError> {
+        let name = self.temp("lf_fn");
+        let (start, separate_next) = (self.lines.len(), self.separate_next);
+        let params = self.def(&name, func)?;
+
+        // A function that only returns an expression reads better as a lambda
+        let def_line = format!("def {name}({params}):");
+        let body = match &self.lines[start..] {
+            [.., def, body] if !func.is_memoized && def.trim_start() == def_line => body
+                .trim_start()
+                .strip_prefix("return ")
+                .map(str::to_string),
+            _ => None,
+        };
+        if let Some(body) = body {
+            let lambda = match params.as_str() {
+                "" => format!("lambda: {body}"),
+                params => format!("lambda {params}: {body}"),
+            };
+            self.lines.truncate(start);
+            self.separate_next = separate_next;
+            return Ok(Py::new(lambda, LAMBDA));
+        }
+
+        Ok(Py::atom(name))
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Result<Py, CompileError> {
+        let span = expr.span();
+        let py = match &expr.0 {
+            Expr::Value(value) => self.value(value, span)?,
+            Expr::Local(name) => Py::atom(python_name(name)),
+            Expr::List(items) => Py::atom(format!("[{}]", self.items(items)?.join(", "))),
+            Expr::Tuple(items) => match self.items(items)?.as_slice() {
+                [single] => Py::atom(format!("({single},)")),
+                items => Py::atom(format!("({})", items.join(", "))),
+            },
+            Expr::Map(entries) => {
+                let mut items = Vec::new();
+                for (key, value) in entries {
+                    let (key, value) = (self.expr(key)?, self.expr(value)?);
+                    items.push(format!("{}: {}", key.code, value.code));
+                }
+                Py::atom(format!("{{{}}}", items.join(", ")))
+            }
+            Expr::Index(indexed, index) => {
+                let indexed = self.expr(indexed)?;
+                let index = match &index.0 {
+                    Expr::Binary(start, BinaryOp::Range, end) => {
+                        let (start, end) = (self.slice_bound(start)?, self.slice_bound(end)?);
+                        format!("{start}:{end}")
+                    }
+                    _ => self.expr(index)?.code,
+                };
+                Py::atom(format!("{}[{index}]", indexed.at(ATOM)))
+            }
+
+            Expr::Unary(op, operand) => {
+                let operand = self.expr(operand)?;
+                match op {
+                    UnaryOp::Neg => Py::new(format!("-{}", operand.at(UNARY)), UNARY),
+                    UnaryOp::Not => Py::new(format!("not {}", operand.at(NOT)), NOT),
+                    UnaryOp::BitwiseNot => Py::new(format!("~{}", operand.at(UNARY)), UNARY),
+                }
+            }
+            Expr::Binary(lhs, op, rhs) => self.binary(lhs, op, rhs)?,
+            Expr::Call(func, args) => self.call(func, args)?,
+            Expr::MethodCall(target, name, args) => self.method(target, name, args, span)?,
+            Expr::ListComprehension(body, pattern, iterable) => {
+                self.comprehension(body, pattern, iterable)?
+            }
+
+            Expr::Block(inner) => self.expr(inner)?,
+            Expr::Sequence(exprs) => match exprs.split_last() {
+                Some((last, init)) => {
+                    for expr in init {
+                        self.lower(expr, Target::Discard)?;
+                    }
+                    self.expr(last)?
+                }
+                None => Py::atom("None"),
+            },
+            Expr::Assign(pattern, value) => self.assign(pattern, value)?,
+            Expr::If(cond, then, otherwise) => {
+                let cond = self.expr(cond)?;
+                match self.conditional(&cond, then, otherwise)? {
+                    Some(py) => py,
+                    None => {
+                        let temp = self.temp("lf_tmp");
+                        self.if_statement(&cond, then, otherwise, Target::Assign(&temp))?;
+                        Py::atom(temp)
+                    }
+                }
+            }
+            Expr::While(..) | Expr::For(..) => {
+                self.lower(expr, Target::Discard)?;
+                Py::atom("None")
+            }
+            Expr::Break | Expr::Continue | Expr::Return(_) => {
+                self.lower(expr, Target::Discard)?;
+                Py::atom("None")
+            }
+
+            Expr::Spread(_) => return Err(unsupported("Spreading outside of a call", span)),
+            Expr::NullSafe(_) => return Err(unsupported("Null-safe access", span)),
+            Expr::Match(..) => return Err(unsupported("Match", span)),
+            Expr::Enum(..) | Expr::Variant(..) => return Err(unsupported("Enums", span)),
+            Expr::Import(_) => return Err(unsupported("Imports", span)),
+            Expr::Defer(_) => return Err(unsupported("Deferred code", span)),
+            Expr::Test(..) => return Err(unsupported("Tests", span)),
+            Expr::ParseError => return Err(unsupported("Code with errors", span)),
+        };
+
+        Ok(py)
+    }
+
+    fn value(&mut self, value: &AstValue, span: Span) -> Result<Py, CompileError> {
+        let py = match value {
+            AstValue::Null => Py::atom("None"),
+            AstValue::Bool(true) => Py::atom("True"),
+            AstValue::Bool(false) => Py::atom("False"),
+            AstValue::Int(i) => Py::atom(i.to_string()),
+            AstValue::Float(f) if f.is_finite() => Py::atom(format!("{f:?}")),
+            AstValue::Float(f) => {
+                self.imports.insert("import math");
+                Py::atom(if f.is_nan() { "math.nan" } else { "math.inf" })
+            }
+            AstValue::Str(s) => Py::atom(string_literal(s)),
+            AstValue::List(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.value(item, span)?.code);
+                }
+                Py::atom(format!("[{}]", values.join(", ")))
+            }
+            AstValue::Tuple(items) => {
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.value(item, span)?.code);
+                }
+                match values.as_slice() {
+                    [single] => Py::atom(format!("({single},)")),
+                    values => Py::atom(format!("({})", values.join(", "))),
+                }
+            }
+            AstValue::Func(func) => self.func_value(func)?,
+            AstValue::Regex(..) => return Err(unsupported("Regexes", span)),
+            AstValue::Symbol(_) => return Err(unsupported("Symbols", span)),
+        };
+
+        Ok(py)
+    }
+
+    fn items(&mut self, items: &[Spanned<Expr>]) -> Result<Vec<String>, CompileError> {
+        let mut codes = Vec::new();
+        for item in items {
+            codes.push(self.expr(item)?.code);
+        }
+        Ok(codes)
+    }
+
+    // Call arguments, where `*xs` spreads an iterable
+    fn args(&mut self, args: &[Spanned<Expr>]) -> Result<Vec<Py>, CompileError> {
+        let mut pys = Vec::new();
+        for arg in args {
+            pys.push(match &arg.0 {
+                Expr::Spread(iterable) => {
+                    let iterable = self.expr(iterable)?;
+                    Py::new(format!("*{}", iterable.at(BIT_OR)), LAMBDA)
+                }
+                _ => self.expr(arg)?,
+            });
+        }
+        Ok(pys)
+    }
+
+    fn slice_bound(&mut self, bound: &Spanned<Expr>) -> Result<String, CompileError> {
+        match &bound.0 {
+            Expr::Value(AstValue::Null) => Ok(String::new()),
+            _ => Ok(self.expr(bound)?.code),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        lhs: &Spanned<Expr>,
+        op: &BinaryOp,
+        rhs: &Spanned<Expr>,
+    ) -> Result<Py, CompileError> {
+        if let BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce = op {
+            return self.short_circuit(lhs, op, rhs);
+        }
+
+        let (a, b) = (self.expr(lhs)?, self.expr(rhs)?);
+        let infix =
+            |op: &str, prec: u8| Py::new(format!("{} {op} {}", a.at(prec), b.at(prec + 1)), prec);
+        // Python chains comparisons, so nested ones are parenthesized
+        let compare = |op: &str| {
+            Py::new(
+                format!("{} {op} {}", a.at(COMPARISON + 1), b.at(COMPARISON + 1)),
+                COMPARISON,
+            )
+        };
+
+        let py = match op {
+            BinaryOp::Add => infix("+", SUM),
+            BinaryOp::Sub => infix("-", SUM),
+            BinaryOp::Mul => infix("*", PRODUCT),
+            BinaryOp::Div => self.helper(Helper::Div, &[&a, &b]),
+            BinaryOp::DivFloor => self.helper(Helper::DivFloor, &[&a, &b]),
+            BinaryOp::Mod => self.helper(Helper::Mod, &[&a, &b]),
+            BinaryOp::Pow => Py::new(format!("{} ** {}", a.at(POWER + 1), b.at(UNARY)), POWER),
+            BinaryOp::Xor => Py::new(format!("bool({}) != bool({})", a.code, b.code), COMPARISON),
+            BinaryOp::Eq => compare("=="),
+            BinaryOp::NotEq => compare("!="),
+            BinaryOp::Less => compare("<"),
+            BinaryOp::LessEq => compare("<="),
+            BinaryOp::Greater => compare(">"),
+            BinaryOp::GreaterEq => compare(">="),
+            BinaryOp::In => compare("in"),
+            // Only ranges with known bounds are known to count up
+            BinaryOp::Range => match (&lhs.0, &rhs.0) {
+                (Expr::Value(AstValue::Int(start)), Expr::Value(AstValue::Int(end)))
+                    if start <= end =>
+                {
+                    call("range", &[a.clone(), b.clone()])
+                }
+                _ => self.helper(Helper::Range, &[&a, &b]),
+            },
+            BinaryOp::BitwiseAnd => infix("&", BIT_AND),
+            BinaryOp::BitwiseOr => infix("|", BIT_OR),
+            BinaryOp::BitwiseXor => infix("^", BIT_XOR),
+            BinaryOp::LeftShift => infix("<<", SHIFT),
+            BinaryOp::RightShift => infix(">>", SHIFT),
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Coalesce => unreachable!(),
+        };
+
+        Ok(py)
+    }
+
+    // The right-hand side of `and`, `or` and `??` is only evaluated when needed, so its
+    // statements, if it has any, go in an `if`
+    fn short_circuit(
+        &mut self,
+        lhs: &Spanned<Expr>,
+        op: &BinaryOp,
+        rhs: &Spanned<Expr>,
+    ) -> Result<Py, CompileError> {
+        let mut a = self.expr(lhs)?;
+        if let Some(b) = self.try_expr(rhs)? {
+            return Ok(match op {
+                BinaryOp::And => Py::new(format!("{} and {}", a.at(AND), b.at(AND + 1)), AND),
+                BinaryOp::Or => Py::new(format!("{} or {}", a.at(OR), b.at(OR + 1)), OR),
+                _ => {
+                    // The left-hand side is evaluated once
+                    if a.prec != ATOM || a.code.contains(['(', '[']) {
+                        let temp = self.temp("lf_tmp");
+                        self.line(format!("{temp} = {}", a.code));
+                        a = Py::atom(temp);
+                    }
+                    Py::new(
+                        format!(
+                            "{} if {} is not None else {}",
+                            a.code,
+                            a.code,
+                            b.at(CONDITIONAL)
+                        ),
+                        CONDITIONAL,
+                    )
+                }
+            });
+        }
+
+        let temp = self.temp("lf_tmp");
+        self.line(format!("{temp} = {}", a.code));
+        let cond = match op {
+            BinaryOp::And => temp.clone(),
+            BinaryOp::Or => format!("not {temp}"),
+            _ => format!("{temp} is None"),
+        };
+        self.line(format!("if {cond}:"));
+        self.indented(|t| t.lower(rhs, Target::Assign(&temp)))?;
+        Ok(Py::atom(temp))
+    }
+
+    fn call(&mut self, func: &Spanned<Expr>, args: &[Spanned<Expr>]) -> Result<Py, CompileError> {
+        if let Expr::Local(name) = &func.0 {
+            if !self.is_defined(name) {
+                if let Some(py) = self.stdlib_call(name, args)? {
+                    return Ok(py);
+                }
+            }
+        }
+
+        let func = self.expr(func)?;
+        let args = self.args(args)?;
+        Ok(call(&func.at(ATOM), &args))
+    }
+
+    fn stdlib_call(
+        &mut self,
+        name: &str,
+        args: &[Spanned<Expr>],
+    ) -> Result<Option<Py>, CompileError> {
+        let python = match name {
+            "print" | "eprint" | "str" | "repr" | "read_line" | "input" => None,
+            "int" | "float" | "list" | "tuple" | "set" | "sum" | "all" | "any" | "max" | "min"
+            | "abs" => Some(name.to_string()),
+            "parse_int" => Some("int".to_string()),
+            "parse_float" => Some("float".to_string()),
+            "mul" => Some("math.prod".to_string()),
+            "sqrt" | "isqrt" | "gcd" | "lcm" | "floor" | "ceil" | "sin" | "cos" | "tan" | "log"
+            | "log2" => Some(format!("math.{name}")),
+            _ => return Ok(None),
+        };
+
+        let mut args = self.args(args)?;
+        let py = match (name, python) {
+            (_, Some(python)) => {
+                if python.starts_with("math.") {
+                    self.imports.insert("import math");
+                }
+                call(&python, &args)
+            }
+            ("print", _) => self.helper(Helper::Print, &args.iter().collect::<Vec<_>>()),
+            ("eprint", _) => {
+                self.imports.insert("import sys");
+                args.push(Py::new("file=sys.stderr", LAMBDA));
+                self.helper(Helper::Print, &args.iter().collect::<Vec<_>>())
+            }
+            ("str", _) => self.helper(Helper::Str, &args.iter().collect::<Vec<_>>()),
+            ("repr", _) => self.helper(Helper::Repr, &args.iter().collect::<Vec<_>>()),
+            ("read_line", _) => self.helper(Helper::ReadLine, &args.iter().collect::<Vec<_>>()),
+            _ => {
+                self.imports.insert("import sys");
+                Py::atom("sys.stdin.read()")
+            }
+        };
+
+        Ok(Some(py))
+    }
+
+    fn method(
+        &mut self,
+        target: &Spanned<Expr>,
+        name: &str,
+        args: &[Spanned<Expr>],
+        span: Span,
+    ) -> Result<Py, CompileError> {
+        let target = self.expr(target)?;
+        let args = self.args(args)?;
+        let method = |method: &str| call(&format!("{}.{method}", target.at(ATOM)), &args);
+
+        let py = match (name, args.as_slice()) {
+            (
+                "append" | "add" | "remove" | "count" | "split" | "replace" | "upper" | "lower",
+                _,
+            ) => method(name),
+            ("lines", []) => method("splitlines"),
+            ("trim", []) => method("strip"),
+            ("trim_start", []) => method("lstrip"),
+            ("trim_end", []) => method("rstrip"),
+            ("starts_with", [_]) => method("startswith"),
+            ("ends_with", [_]) => method("endswith"),
+            ("get_or", [_, _]) => method("get"),
+            ("len", []) => call("len", &[target.clone()]),
+            ("is_empty", []) => Py::new(format!("len({}) == 0", target.code), COMPARISON),
+            ("contains", [item]) => Py::new(
+                format!(
+                    "{} in {}",
+                    item.at(COMPARISON + 1),
+                    target.at(COMPARISON + 1)
+                ),
+                COMPARISON,
+            ),
+            ("join", []) | ("join", [_]) => {
+                let separator = args.first().map_or("\"\"".to_string(), |sep| sep.at(ATOM));
+                self.helpers.insert(Helper::Str);
+                Py::atom(format!("{separator}.join(map(lf_str, {}))", target.code))
+            }
+            ("repeat", [times]) => Py::new(
+                format!("{} * {}", target.at(PRODUCT), times.at(PRODUCT + 1)),
+                PRODUCT,
+            ),
+            ("keys", []) => Py::atom(format!("list({}.keys())", target.at(ATOM))),
+            ("values", []) => Py::atom(format!("list({}.values())", target.at(ATOM))),
+            ("enumerate", _) => call("enumerate", &[&[target.clone()], args.as_slice()].concat()),
+            ("reverse", []) => Py::atom(format!("{}[::-1]", target.at(ATOM))),
+            ("sort", []) => call("sorted", &[target.clone()]),
+            ("sort", [key]) => Py::atom(format!("sorted({}, key={})", target.code, key.code)),
+            ("first", []) => Py::atom(format!("next(iter({}))", target.code)),
+            ("last", []) => Py::atom(format!("{}[-1]", target.at(ATOM))),
+            _ => return Err(unsupported(&format!("The method '{name}'"), span)),
+        };
+
+        Ok(py)
+    }
+
+    fn comprehension(
+        &mut self,
+        body: &Spanned<Expr>,
+        pattern: &Spanned<Pattern>,
+        iterable: &Spanned<Expr>,
+    ) -> Result<Py, CompileError> {
+        let iterable = self.expr(iterable)?;
+
+        // A condition after the loop is parsed as an `if` that continues when it doesn't hold
+        let (body, cond) = match &body.0 {
+            Expr::If(cond, then, otherwise) if matches!(otherwise.0, Expr::Continue) => {
+                (then.as_ref(), Some(cond.as_ref()))
+            }
+            _ => (body, None),
+        };
+
+        // The loop variables only exist in the comprehension
+        let names = self.scopes.last().unwrap().names.clone();
+        let pattern = self.pattern(pattern, false)?;
+
+        let snapshot = self.snapshot();
+        let body_py = self.try_expr(body)?;
+        let cond_py = match cond {
+            Some(cond) => self.try_expr(cond)?.map(Some),
+            None => Some(None),
+        };
+        let py = match (body_py, cond_py) {
+            (Some(body), Some(cond)) => {
+                let cond = cond.map_or(String::new(), |cond| format!(" if {}", cond.at(OR)));
+                Py::atom(format!(
+                    "[{} for {pattern} in {}{cond}]",
+                    body.at(CONDITIONAL),
+                    iterable.at(OR)
+                ))
+            }
+            _ => {
+                self.restore(snapshot);
+                let list = self.temp("lf_tmp");
+                self.line(format!("{list} = []"));
+                self.line(format!("for {pattern} in {}:", iterable.at(OR)));
+                self.indented(|t| {
+                    if let Some(cond) = cond {
+                        let cond = t.expr(cond)?;
+                        t.line(format!("if not {}:", cond.at(NOT)));
+                        t.indented(|t| {
+                            t.line("continue");
+                            Ok(())
+                        })?;
+                    }
+                    let body = t.expr(body)?;
+                    t.line(format!("{list}.append({})", body.code));
+                    Ok(())
+                })?;
+                Py::atom(list)
+            }
+        };
+
+        self.scopes.last_mut().unwrap().names = names;
+        Ok(py)
+    }
+}
+
+fn call(func: &str, args: &[Py]) -> Py {
+    let args = args.iter().map(|arg| arg.code.as_str()).collect::<Vec<_>>();
+    Py::atom(format!("{func}({})", args.join(", ")))
+}
+
+// The operator of Python's augmented assignment for an operator, if they mean the same
+fn augmented_op(op: &BinaryOp) -> Option<&'static str> {
+    Some(match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Pow => "**",
+        BinaryOp::BitwiseAnd => "&",
+        BinaryOp::BitwiseOr => "|",
+        BinaryOp::BitwiseXor => "^",
+        BinaryOp::LeftShift => "<<",
+        BinaryOp::RightShift => ">>",
+        _ => return None,
+    })
+}
+
+fn python_name(name: &str) -> String {
+    if RESERVED.contains(&name) || name.starts_with("lf_") {
+        format!("{name}_")
+    } else {
+        name.to_string()
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{:04x}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn unsupported(what: &str, span: Span) -> CompileError {
+    CompileError::Spanned {
+        span,
+        msg: format!("{what} can't be transpiled to Python"),
+    }
+}
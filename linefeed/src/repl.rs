@@ -0,0 +1,265 @@
+//! The `linefeed repl` mode.
+//!
+//! Every input is compiled with the same compiler and appended to the program of a single VM, so
+//! variables and functions defined by one input are available to the next. Inputs that end in
+//! the middle of an expression, like an unclosed block, are continued on the next line.
+//!
+//! The value of each input is printed unless it is null or the input ends with a semicolon.
+//!
+//! With `linefeed --repl-after-run <file>`, the program is run first and the REPL continues from
+//! its final state, so its variables and functions can be inspected and called.
+
+use std::{
+    io::{self, BufRead, Read, Write},
+    path::Path,
+};
+
+use chumsky::{
+    error::{Rich, RichReason},
+    Parser as _,
+};
+
+use crate::{
+    compiler::{Compiler, Program},
+    config::Config,
+    grammar::{ast::Span, lexer},
+    parse_tokens, pretty_print_error_at, pretty_print_errors,
+    vm::{runtime_value::RuntimeValue, BytecodeInterpreter, RuntimeError},
+    EXIT_FAILURE, EXIT_SUCCESS,
+};
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+enum EvalError {
+    /// The input ended before the expression did.
+    Incomplete,
+    /// The error has been reported.
+    Failed,
+    /// The input called `exit`, so no more inputs should be run.
+    Exited,
+}
+
+pub struct Repl<I, O, E> {
+    compiler: Compiler,
+    vm: BytecodeInterpreter<I, O, E>,
+    // The source of each input along with the pc its code starts at, so errors in functions
+    // defined by earlier inputs are reported against the right source
+    inputs: Vec<(usize, String)>,
+    exit_code: Option<i32>,
+}
+
+impl<O: Write, E: Write> Repl<io::Empty, O, E> {
+    /// Programs run in the REPL have no standard input, since it's used for the REPL's input.
+    pub fn new(config: &Config, stdout: O, stderr: E) -> Self {
+        Repl::with_stdin(config, io::empty(), stdout, stderr)
+    }
+}
+
+impl<I: Read, O: Write, E: Write> Repl<I, O, E> {
+    /// Like [`Repl::new`], but programs read `stdin`, which must not be where the REPL's input
+    /// comes from.
+    pub fn with_stdin(config: &Config, stdin: I, stdout: O, stderr: E) -> Self {
+        let vm = BytecodeInterpreter::new(Program::new())
+            .with_config(config)
+            .with_handles(stdin, stdout, stderr);
+
+        Self {
+            compiler: Compiler::default().with_sandbox(config.sandbox),
+            vm,
+            inputs: Vec::new(),
+            exit_code: None,
+        }
+    }
+
+    /// Makes imports resolve relative to `dir` instead of the working directory.
+    pub fn with_base_dir(mut self, dir: &Path) -> Self {
+        self.compiler = self.compiler.with_base_dir(dir);
+        self
+    }
+
+    /// Runs the files of the `[repl] prelude` setting, stopping at the first one that fails.
+    /// Returns whether all of them ran successfully.
+    pub fn load_prelude(&mut self, config: &Config) -> bool {
+        config
+            .repl_prelude
+            .iter()
+            .all(|path| self.load_file(path).is_ok_and(|ran| ran))
+    }
+
+    /// Runs the program in the file at `path`. Returns whether it ran successfully, or an error if
+    /// it couldn't be read. A program that fails partway keeps the variables it assigned before
+    /// failing.
+    pub fn load_file(&mut self, path: &Path) -> io::Result<bool> {
+        let src = match std::fs::read_to_string(path) {
+            Ok(src) => src,
+            Err(err) => {
+                let path = path.display();
+                writeln!(self.vm.stderr, "error: failed to read {path}: {err}").unwrap();
+                return Err(err);
+            }
+        };
+
+        match self.eval(src) {
+            Ok(_) => Ok(true),
+            Err(EvalError::Incomplete) => {
+                let path = path.display();
+                writeln!(self.vm.stderr, "error: {path} ends unexpectedly").unwrap();
+                Ok(false)
+            }
+            Err(EvalError::Failed | EvalError::Exited) => Ok(false),
+        }
+    }
+
+    /// The code passed to `exit` by an input, if one has called it.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Reads inputs until `input` is exhausted or one of them calls `exit`, printing the value of
+    /// each.
+    pub fn run(&mut self, mut input: impl BufRead) {
+        let mut src = String::new();
+
+        loop {
+            let prompt = if src.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            write!(self.vm.stdout, "{prompt}").unwrap();
+            self.vm.stdout.flush().unwrap();
+
+            let mut line = String::new();
+            if input.read_line(&mut line).unwrap() == 0 {
+                writeln!(self.vm.stdout).unwrap();
+                break;
+            }
+
+            src.push_str(&line);
+            if src.trim().is_empty() {
+                src.clear();
+                continue;
+            }
+
+            match self.eval(src.clone()) {
+                Ok(val) => {
+                    if val != RuntimeValue::Null && !src.trim_end().ends_with(';') {
+                        writeln!(self.vm.stdout, "{}", val.repr_string()).unwrap();
+                    }
+                    src.clear();
+                }
+                Err(EvalError::Failed) => src.clear(),
+                Err(EvalError::Incomplete) => {}
+                Err(EvalError::Exited) => break,
+            }
+        }
+    }
+
+    fn eval(&mut self, src: String) -> Result<RuntimeValue, EvalError> {
+        let tokens = match lexer::lexer().parse(&src).into_output_errors() {
+            (Some(tokens), e) if e.is_empty() => tokens,
+            (_, e) if is_incomplete(&e) => return Err(EvalError::Incomplete),
+            (_, e) => {
+                pretty_print_errors(&mut self.vm.stderr, &src, e);
+                return Err(EvalError::Failed);
+            }
+        };
+        let ast = match parse_tokens(&src, &tokens) {
+            Ok(ast) => ast,
+            Err(e) if is_incomplete(&e) => return Err(EvalError::Incomplete),
+            Err(e) => {
+                pretty_print_errors(&mut self.vm.stderr, &src, e);
+                return Err(EvalError::Failed);
+            }
+        };
+
+        // Compiling allocates the input's new variables, which must be undone if it fails
+        let start_pc = self.vm.program_len();
+        let compiler = self.compiler.clone();
+        let program = match self.compiler.compile_at(&ast, start_pc) {
+            Ok(program) => program,
+            Err(err) => {
+                let span = err.span().unwrap_or(Span::new(0, 0));
+                let modules = self.compiler.modules();
+                pretty_print_error_at(&mut self.vm.stderr, &src, modules, span, err.msg());
+                self.compiler = compiler;
+                return Err(EvalError::Failed);
+            }
+        };
+
+        // Inputs are lines of a program still being written, so the compiler's warnings about
+        // them would mostly be noise
+        self.compiler.take_warnings();
+        self.vm.extend_program(program);
+        self.inputs.push((start_pc, src));
+
+        if let Err((span, err)) = self.vm.run() {
+            if let RuntimeError::Exit(code) = err {
+                self.exit_code = Some(code);
+                return Err(EvalError::Exited);
+            }
+
+            let (_, src) = self
+                .inputs
+                .iter()
+                .rfind(|(start_pc, _)| *start_pc < self.vm.pc())
+                .expect("the failing instruction belongs to an input");
+            let modules = self.compiler.modules();
+            pretty_print_error_at(&mut self.vm.stderr, src, modules, span, err);
+
+            self.vm.recover(self.compiler.num_globals());
+            return Err(EvalError::Failed);
+        }
+
+        Ok(self.vm.pop_result().unwrap_or(RuntimeValue::Null))
+    }
+}
+
+// An input is incomplete if every error is about it ending too early
+fn is_incomplete<T>(errs: &[Rich<T>]) -> bool {
+    !errs.is_empty()
+        && errs
+            .iter()
+            .all(|e| matches!(e.reason(), RichReason::ExpectedFound { found: None, .. }))
+}
+
+/// Runs the REPL on `input` until it is exhausted, after loading the configured prelude.
+pub fn run_repl(
+    config: &Config,
+    input: impl BufRead,
+    stdout: impl Write,
+    stderr: impl Write,
+) -> i32 {
+    let mut repl = Repl::new(config, stdout, stderr);
+    if !repl.load_prelude(config) {
+        return repl.exit_code().unwrap_or(EXIT_FAILURE);
+    }
+
+    repl.run(input);
+    repl.exit_code().unwrap_or(EXIT_SUCCESS)
+}
+
+/// Runs the program in the file at `path`, then the REPL on `input` with the program's variables
+/// and functions. The REPL is started even if the program fails, to inspect what led up to it, but
+/// not if it calls `exit`.
+pub fn run_repl_after_file(
+    path: &Path,
+    config: &Config,
+    stdin: impl Read,
+    input: impl BufRead,
+    stdout: impl Write,
+    stderr: impl Write,
+) -> i32 {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut repl = Repl::with_stdin(config, stdin, stdout, stderr).with_base_dir(dir);
+    if !repl.load_prelude(config) || repl.load_file(path).is_err() {
+        return repl.exit_code().unwrap_or(EXIT_FAILURE);
+    }
+    if let Some(code) = repl.exit_code() {
+        return code;
+    }
+
+    repl.run(input);
+    repl.exit_code().unwrap_or(EXIT_SUCCESS)
+}
@@ -0,0 +1,258 @@
+//! The `linefeed test` runner.
+//!
+//! Test files are discovered by their `_test.lf` suffix. Each top-level `test "name" { ... }`
+//! block in a file runs in a fresh VM together with the rest of the file, so helpers and setup
+//! code defined outside of tests are available to every test. Other test blocks are skipped the
+//! same way they are in a regular run.
+
+use std::{
+    fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chumsky::Parser as _;
+
+use crate::{
+    compiler::Compiler,
+    config::Config,
+    grammar::{
+        ast::{Expr, Span, Spanned},
+        lexer,
+    },
+    parse_tokens, pretty_print_error_at, pretty_print_errors,
+    vm::{BytecodeInterpreter, RuntimeError},
+    EXIT_FAILURE, EXIT_SUCCESS,
+};
+
+const TEST_FILE_SUFFIX: &str = "_test.lf";
+
+struct Failure {
+    name: String,
+    output: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Summary {
+    passed: usize,
+    failures: Vec<Failure>,
+}
+
+/// Runs every test found in `*_test.lf` files below `dir`, writing a report to `out`, and returns
+/// the exit status: [`EXIT_FAILURE`] if any test failed or a test file could not be loaded.
+pub fn run_tests(dir: impl AsRef<Path>, mut out: impl Write) -> i32 {
+    let dir = dir.as_ref();
+
+    let config = match Config::load(dir) {
+        Ok(config) => config,
+        Err(err) => {
+            writeln!(out, "error: {err}").unwrap();
+            return EXIT_FAILURE;
+        }
+    };
+
+    let mut files = Vec::new();
+    if let Err(err) = find_test_files(dir, &mut files) {
+        writeln!(out, "error: failed to read {}: {err}", dir.display()).unwrap();
+        return EXIT_FAILURE;
+    }
+
+    let mut summary = Summary::default();
+    for file in &files {
+        let label = file.strip_prefix(dir).unwrap_or(file).display().to_string();
+        match fs::read_to_string(file) {
+            Ok(src) => {
+                let base_dir = file.parent().unwrap_or(dir);
+                run_test_file(&label, &src, base_dir, &config, &mut out, &mut summary)
+            }
+            Err(err) => {
+                writeln!(out, "test {label} ... FAILED").unwrap();
+                summary.failures.push(Failure {
+                    name: label,
+                    output: format!("failed to read file: {err}\n").into_bytes(),
+                });
+            }
+        }
+    }
+
+    if !summary.failures.is_empty() {
+        writeln!(out, "\nfailures:\n").unwrap();
+        for failure in &summary.failures {
+            writeln!(out, "---- {} ----", failure.name).unwrap();
+            out.write_all(&failure.output).unwrap();
+            writeln!(out).unwrap();
+        }
+    }
+
+    writeln!(
+        out,
+        "\ntest result: {}. {} passed; {} failed",
+        if summary.failures.is_empty() {
+            "ok"
+        } else {
+            "FAILED"
+        },
+        summary.passed,
+        summary.failures.len()
+    )
+    .unwrap();
+
+    if summary.failures.is_empty() {
+        EXIT_SUCCESS
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+fn find_test_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            find_test_files(&path, files)?;
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(TEST_FILE_SUFFIX))
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_test_file(
+    label: &str,
+    src: &str,
+    base_dir: &Path,
+    config: &Config,
+    out: &mut impl Write,
+    summary: &mut Summary,
+) {
+    let mut load_errors = Vec::new();
+
+    let tokens = match lexer::lexer().parse(src).into_output_errors() {
+        (Some(tokens), e) if e.is_empty() => tokens,
+        (_, e) => {
+            pretty_print_errors(&mut load_errors, src, e);
+            return report_load_failure(label, load_errors, out, summary);
+        }
+    };
+
+    let ast = match parse_tokens(src, &tokens) {
+        Ok(ast) => ast,
+        Err(errs) => {
+            pretty_print_errors(&mut load_errors, src, errs);
+            return report_load_failure(label, load_errors, out, summary);
+        }
+    };
+
+    let mut tests = Vec::new();
+    collect_tests(&ast, &mut tests);
+
+    for (index, test_name) in tests.iter().enumerate() {
+        let name = format!("{label}: {test_name}");
+        let program = select_test(&ast, index, &mut 0);
+
+        match run_test(src, &program, base_dir, config) {
+            Ok(()) => {
+                writeln!(out, "test {name} ... ok").unwrap();
+                summary.passed += 1;
+            }
+            Err(output) => {
+                writeln!(out, "test {name} ... FAILED").unwrap();
+                summary.failures.push(Failure { name, output });
+            }
+        }
+    }
+}
+
+fn report_load_failure(label: &str, output: Vec<u8>, out: &mut impl Write, summary: &mut Summary) {
+    writeln!(out, "test {label} ... FAILED").unwrap();
+    summary.failures.push(Failure {
+        name: label.to_string(),
+        output,
+    });
+}
+
+/// Runs a single program, returning everything it printed along with the error report if it
+/// fails to compile or run. Imports are resolved relative to `base_dir`.
+fn run_test(
+    src: &str,
+    program: &Spanned<Expr>,
+    base_dir: &Path,
+    config: &Config,
+) -> Result<(), Vec<u8>> {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+
+    let mut compiler = Compiler::default()
+        .with_base_dir(base_dir)
+        .with_sandbox(config.sandbox);
+    let result = match compiler.compile(program) {
+        Ok(bytecode) => BytecodeInterpreter::new(bytecode)
+            .with_config(config)
+            .with_handles(io::empty(), &mut stdout, &mut stderr)
+            .run()
+            .or_else(|(span, err)| match err {
+                RuntimeError::Exit(EXIT_SUCCESS) => Ok(()),
+                err => Err((span, err.to_string())),
+            }),
+        Err(err) => {
+            let span = err.span().unwrap_or(Span::new(0, 0));
+            Err((span, err.msg().to_string()))
+        }
+    };
+
+    let Err((span, msg)) = result else {
+        return Ok(());
+    };
+
+    let mut output = stdout;
+    output.extend(stderr);
+    pretty_print_error_at(&mut output, src, compiler.modules(), span, msg);
+    Err(output)
+}
+
+/// Collects the names of all top-level tests, i.e. those not nested inside functions or control
+/// flow, in source order.
+fn collect_tests(expr: &Spanned<Expr>, tests: &mut Vec<String>) {
+    match &expr.0 {
+        Expr::Test(name, _) => tests.push(name.clone()),
+        Expr::Block(inner) => collect_tests(inner, tests),
+        Expr::Sequence(exprs) => exprs.iter().for_each(|expr| collect_tests(expr, tests)),
+        _ => {}
+    }
+}
+
+/// Produces a copy of the program where the test with the given index (as ordered by
+/// [`collect_tests`]) is replaced by its body, so that it runs in place.
+fn select_test<'src>(
+    expr: &Spanned<Expr<'src>>,
+    index: usize,
+    seen: &mut usize,
+) -> Spanned<Expr<'src>> {
+    let selected = match &expr.0 {
+        Expr::Test(_, body) => {
+            *seen += 1;
+            if *seen - 1 == index {
+                return (**body).clone();
+            }
+            expr.0.clone()
+        }
+        Expr::Block(inner) => Expr::Block(Box::new(select_test(inner, index, seen))),
+        Expr::Sequence(exprs) => Expr::Sequence(
+            exprs
+                .iter()
+                .map(|expr| select_test(expr, index, seen))
+                .collect(),
+        ),
+        other => other.clone(),
+    };
+
+    Spanned(selected, expr.span())
+}
@@ -0,0 +1,431 @@
+//! Static type checking: errors that are certain to happen if the code runs, like `"a" - 1` or
+//! calling a number, found without running the program.
+//!
+//! The checking is gradual. Only literals, operators on values of known types and variables have a
+//! known type, while e.g. the results of calls are unknown and never reported. A variable has the
+//! type of the values assigned to it if every assignment to its name anywhere in the program gives
+//! the same type, and it isn't bound any other way, such as by being a function argument or a
+//! loop variable. Types are only reported when certain, so a program passing the check can still
+//! fail at runtime.
+
+use std::{collections::HashMap, io::Write};
+
+use ariadne::{Color, Label, Report, ReportKind, Source};
+
+use crate::{
+    compiler::{analysis::match_pattern_bindings, stdlib_fn::StdlibFn},
+    grammar::ast::{AstValue, BinaryOp, Expr, Pattern, Span, Spanned, UnaryOp},
+};
+
+/// The types the checker can tell apart. Everything else is [`Type::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Null,
+    Bool,
+    Num,
+    Str,
+    Regex,
+    Symbol,
+    List,
+    Tuple,
+    Map,
+    Function,
+    Range,
+    Unknown,
+}
+
+impl Type {
+    /// The name of the type, as used by runtime errors.
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Null => "null",
+            Type::Bool => "boolean",
+            Type::Num => "number",
+            Type::Str => "str",
+            Type::Regex => "regex",
+            Type::Symbol => "symbol",
+            Type::List => "list",
+            Type::Tuple => "tuple",
+            Type::Map => "map",
+            Type::Function => "function",
+            Type::Range => "range",
+            Type::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub span: Span,
+    pub msg: String,
+}
+
+/// Finds the type errors in a parsed program.
+pub fn check_types(ast: &Spanned<Expr>) -> Vec<TypeError> {
+    let mut bindings = Bindings::default();
+    bindings.visit(ast);
+
+    let mut checker = Checker {
+        vars: bindings.infer(),
+        errors: Vec::new(),
+    };
+    checker.visit(ast);
+    checker.errors
+}
+
+/// Prints type errors in the same style as compile errors.
+pub fn print_type_errors(mut sink: impl Write, src: &str, errors: &[TypeError]) {
+    for error in errors {
+        Report::build(ReportKind::Error, (), error.span.start)
+            .with_message(&error.msg)
+            .with_label(
+                Label::new(error.span.into_range())
+                    .with_message("this fails whenever it runs")
+                    .with_color(Color::Red),
+            )
+            .finish()
+            .write(Source::from(src), &mut sink)
+            .unwrap();
+    }
+}
+
+// The assignments to each variable name in the program
+#[derive(Default)]
+struct Bindings<'a, 'src> {
+    assignments: Vec<(&'src str, &'a Spanned<Expr<'src>>)>,
+    // Names bound in ways that don't tell their type
+    untyped: Vec<String>,
+    // Imports assign variables that can only be known by loading the imported file
+    has_imports: bool,
+}
+
+impl<'a, 'src> Bindings<'a, 'src> {
+    fn visit(&mut self, expr: &'a Spanned<Expr<'src>>) {
+        match &expr.0 {
+            Expr::Assign(Spanned(Pattern::Ident(name), _), val) => {
+                self.assignments.push((*name, val.as_ref()));
+            }
+            Expr::Assign(pattern, _)
+            | Expr::For(pattern, ..)
+            | Expr::ListComprehension(_, pattern, _) => self.untype_pattern(pattern),
+            Expr::Value(AstValue::Func(func)) => self
+                .untyped
+                .extend(func.args.iter().map(|arg| arg.to_string())),
+            Expr::Match(_, arms) => {
+                for (pattern, _) in arms {
+                    self.untyped.extend(match_pattern_bindings(pattern));
+                }
+            }
+            Expr::Enum(name, _) => self.untyped.push(name.to_string()),
+            Expr::Import(_) => self.has_imports = true,
+            _ => {}
+        }
+
+        for_each_child(expr, |child| self.visit(child));
+    }
+
+    fn untype_pattern(&mut self, pattern: &Spanned<Pattern>) {
+        match &pattern.0 {
+            Pattern::Ident(name) => self.untyped.push(name.to_string()),
+            Pattern::Sequence(patterns) => patterns.iter().for_each(|p| self.untype_pattern(p)),
+            Pattern::Index(..) | Pattern::Value(_) => {}
+        }
+    }
+
+    // Gives each variable the type all of its assignments agree on. Assignments can depend on
+    // other variables, so this is repeated until no type changes. Variables are left out while
+    // none of their assignments have a type yet, and become unknown once two assignments
+    // disagree, so a type only ever changes from missing to known to unknown.
+    fn infer(&self) -> HashMap<String, Type> {
+        let mut vars = HashMap::new();
+        if self.has_imports {
+            return vars;
+        }
+
+        for name in &self.untyped {
+            vars.insert(name.clone(), Type::Unknown);
+        }
+
+        loop {
+            let mut changed = false;
+            for (name, val) in &self.assignments {
+                let Some(ty) = type_of(val, &vars) else {
+                    continue;
+                };
+                let joined = match vars.get(*name) {
+                    Some(&known) => join(known, ty),
+                    None => ty,
+                };
+                if vars.insert(name.to_string(), joined) != Some(joined) {
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break vars;
+            }
+        }
+    }
+}
+
+struct Checker {
+    vars: HashMap<String, Type>,
+    errors: Vec<TypeError>,
+}
+
+impl Checker {
+    fn visit(&mut self, expr: &Spanned<Expr>) {
+        match &expr.0 {
+            Expr::Binary(lhs, op, rhs) => {
+                if let (Some(lhs_ty), Some(rhs_ty)) = (self.known(lhs), self.known(rhs)) {
+                    if let Err(action) = binary_type(op, lhs_ty, rhs_ty) {
+                        self.report(
+                            expr.span(),
+                            format!(
+                                "Cannot {action} types '{}' and '{}'",
+                                lhs_ty.name(),
+                                rhs_ty.name()
+                            ),
+                        );
+                    }
+                }
+            }
+
+            Expr::Unary(op, inner) => {
+                if let Some(ty) = self.known(inner) {
+                    match (op, unary_type(op, ty)) {
+                        (UnaryOp::Neg, None) => {
+                            self.report(expr.span(), format!("Cannot negate type '{}'", ty.name()))
+                        }
+                        (UnaryOp::BitwiseNot, None) => self
+                            .report(expr.span(), format!("Cannot use ~ on type '{}'", ty.name())),
+                        _ => {}
+                    }
+                }
+            }
+
+            Expr::Call(func, _) => {
+                // Names of standard library functions call the function, whatever the variable
+                // holds
+                let is_stdlib_call =
+                    matches!(&func.0, Expr::Local(name) if StdlibFn::from_name(name).is_some());
+                if let Some(ty) = self.known(func).filter(|_| !is_stdlib_call) {
+                    if ty != Type::Function {
+                        self.report(
+                            func.span(),
+                            format!("Cannot call type {} as a function", ty.name()),
+                        );
+                    }
+                }
+            }
+
+            _ => {}
+        }
+
+        for_each_child(expr, |child| self.visit(child));
+    }
+
+    fn known(&self, expr: &Spanned<Expr>) -> Option<Type> {
+        type_of(expr, &self.vars).filter(|ty| *ty != Type::Unknown)
+    }
+
+    fn report(&mut self, span: Span, msg: String) {
+        self.errors.push(TypeError { span, msg });
+    }
+}
+
+// The type of the values of an expression, or `None` if it uses a variable without a type, which
+// is either not assigned anywhere or only assigned values of variables without a type
+fn type_of(expr: &Spanned<Expr>, vars: &HashMap<String, Type>) -> Option<Type> {
+    let ty = match &expr.0 {
+        Expr::Value(val) => match val {
+            AstValue::Null => Type::Null,
+            AstValue::Bool(_) => Type::Bool,
+            AstValue::Int(_) | AstValue::Float(_) => Type::Num,
+            AstValue::Str(_) => Type::Str,
+            AstValue::Regex(..) => Type::Regex,
+            AstValue::Symbol(_) => Type::Symbol,
+            AstValue::List(_) => Type::List,
+            AstValue::Tuple(_) => Type::Tuple,
+            AstValue::Func(_) => Type::Function,
+        },
+        Expr::List(_) | Expr::ListComprehension(..) => Type::List,
+        Expr::Tuple(_) => Type::Tuple,
+        Expr::Map(_) => Type::Map,
+        Expr::Local(name) => return vars.get(*name).copied(),
+        Expr::Assign(_, val) | Expr::Block(val) => return type_of(val, vars),
+        Expr::Sequence(exprs) => match exprs.last() {
+            Some(last) => return type_of(last, vars),
+            None => Type::Unknown,
+        },
+        Expr::If(_, then, otherwise) => join(type_of(then, vars)?, type_of(otherwise, vars)?),
+        Expr::Unary(op, inner) => match type_of(inner, vars)? {
+            Type::Unknown => Type::Unknown,
+            ty => unary_type(op, ty).unwrap_or(Type::Unknown),
+        },
+        Expr::Binary(lhs, op, rhs) => match (type_of(lhs, vars)?, type_of(rhs, vars)?) {
+            (Type::Unknown, _) | (_, Type::Unknown) => Type::Unknown,
+            (lhs, rhs) => binary_type(op, lhs, rhs).unwrap_or(Type::Unknown),
+        },
+        _ => Type::Unknown,
+    };
+
+    Some(ty)
+}
+
+fn join(a: Type, b: Type) -> Type {
+    if a == b {
+        a
+    } else {
+        Type::Unknown
+    }
+}
+
+// The type of the result of a unary operator, or `None` if it can't be used on `ty`
+fn unary_type(op: &UnaryOp, ty: Type) -> Option<Type> {
+    match (op, ty) {
+        (UnaryOp::Not, _) => Some(Type::Bool),
+        (UnaryOp::Neg, Type::Num | Type::Tuple) | (UnaryOp::BitwiseNot, Type::Num) => Some(ty),
+        _ => None,
+    }
+}
+
+// The type of the result of a binary operator on values of known types, or what the operator
+// would do as worded by its runtime error if it can't be used on them
+fn binary_type(op: &BinaryOp, lhs: Type, rhs: Type) -> Result<Type, &'static str> {
+    use Type::*;
+
+    let nums_only = |action| (action, ((lhs, rhs) == (Num, Num)).then_some(Num));
+    let (action, result) = match op {
+        BinaryOp::Add => (
+            "add",
+            match (lhs, rhs) {
+                (Num, Num) => Some(Num),
+                (Str, Str | Num) => Some(Str),
+                (List, List) => Some(List),
+                (Tuple, Tuple) => Some(Tuple),
+                _ => None,
+            },
+        ),
+        BinaryOp::Sub => (
+            "subtract",
+            match (lhs, rhs) {
+                (Num, Num) => Some(Num),
+                (Tuple, Tuple) => Some(Tuple),
+                _ => None,
+            },
+        ),
+        BinaryOp::Mul => (
+            "multiply",
+            match (lhs, rhs) {
+                (Num, Num) => Some(Num),
+                (Tuple, Num) | (Num, Tuple) => Some(Tuple),
+                _ => None,
+            },
+        ),
+        BinaryOp::Div | BinaryOp::DivFloor => nums_only("divide"),
+        BinaryOp::Mod => nums_only("modulo"),
+        BinaryOp::Pow => nums_only("power"),
+        BinaryOp::BitwiseAnd => nums_only("use & on"),
+        BinaryOp::BitwiseOr => nums_only("use | on"),
+        BinaryOp::BitwiseXor => nums_only("use ^ on"),
+        BinaryOp::LeftShift => nums_only("use << on"),
+        BinaryOp::RightShift => nums_only("use >> on"),
+        BinaryOp::Xor => ("xor", ((lhs, rhs) == (Bool, Bool)).then_some(Bool)),
+        BinaryOp::Range => (
+            "make range from",
+            matches!((lhs, rhs), (Num | Null, Num | Null)).then_some(Range),
+        ),
+        BinaryOp::Eq
+        | BinaryOp::NotEq
+        | BinaryOp::Less
+        | BinaryOp::LessEq
+        | BinaryOp::Greater
+        | BinaryOp::GreaterEq
+        | BinaryOp::In => return Ok(Bool),
+        BinaryOp::And | BinaryOp::Or => return Ok(join(join(lhs, rhs), Bool)),
+        BinaryOp::Coalesce => return Ok(Unknown),
+    };
+
+    result.ok_or(action)
+}
+
+// The same as `snippets::for_each_child`, but for walking the AST without changing it. Index
+// patterns of assignments and loops are walked too, as they hold expressions.
+fn for_each_child<'a, 'src>(
+    expr: &'a Spanned<Expr<'src>>,
+    mut f: impl FnMut(&'a Spanned<Expr<'src>>),
+) {
+    match &expr.0 {
+        Expr::Value(AstValue::Func(func)) => {
+            func.defaults.iter().for_each(&mut f);
+            f(&func.body);
+        }
+        Expr::List(items) | Expr::Tuple(items) | Expr::Sequence(items) => items.iter().for_each(f),
+        Expr::Map(items) => {
+            for (key, val) in items {
+                f(key);
+                f(val);
+            }
+        }
+        Expr::Match(target, arms) => {
+            f(target);
+            for (pattern, body) in arms {
+                f(pattern);
+                f(body);
+            }
+        }
+        Expr::Call(func, args) | Expr::MethodCall(func, _, args) => {
+            f(func);
+            args.iter().for_each(f);
+        }
+        Expr::Assign(pattern, val) => {
+            pattern_children(pattern, &mut f);
+            f(val);
+        }
+        Expr::If(cond, then, otherwise) => {
+            f(cond);
+            f(then);
+            f(otherwise);
+        }
+        Expr::For(pattern, iterable, body) | Expr::ListComprehension(body, pattern, iterable) => {
+            pattern_children(pattern, &mut f);
+            f(iterable);
+            f(body);
+        }
+        Expr::While(lhs, rhs) | Expr::Index(lhs, rhs) | Expr::Binary(lhs, _, rhs) => {
+            f(lhs);
+            f(rhs);
+        }
+        Expr::Unary(_, inner)
+        | Expr::Block(inner)
+        | Expr::Return(inner)
+        | Expr::Test(_, inner)
+        | Expr::Spread(inner)
+        | Expr::NullSafe(inner)
+        | Expr::Defer(inner) => f(inner),
+        Expr::Value(_)
+        | Expr::Local(_)
+        | Expr::Break
+        | Expr::Continue
+        | Expr::ParseError
+        | Expr::Import(_)
+        | Expr::Enum(..)
+        | Expr::Variant(..) => {}
+    }
+}
+
+// Index patterns like `xs[i] = ...` hold expressions
+fn pattern_children<'a, 'src>(
+    pattern: &'a Spanned<Pattern<'src>>,
+    f: &mut impl FnMut(&'a Spanned<Expr<'src>>),
+) {
+    match &pattern.0 {
+        Pattern::Index(target, index) => {
+            f(target);
+            f(index);
+        }
+        Pattern::Sequence(patterns) => patterns.iter().for_each(|p| pattern_children(p, f)),
+        Pattern::Ident(_) | Pattern::Value(_) => {}
+    }
+}
@@ -1,15 +1,24 @@
-use std::io::{Read, Write};
+use std::{
+    cell::RefCell,
+    io::{Read, Write},
+    rc::Rc,
+};
 
 use rustc_hash::FxHashMap;
 use yansi::Paint;
 
 use crate::{
     compiler::{register_manager::DEFAULT_MAX_REGISTERS, Program},
+    config::{Config, Sandbox},
     grammar::ast::Span,
     vm::{
         bytecode::Bytecode,
+        limits::{LimitTracker, RunLimits, CHECK_INTERVAL},
         runtime_value::{
-            function::{MemoizationKey, RuntimeFunction},
+            cycles::{self, Tracked},
+            function::{Capture, MemoizationKey, RuntimeFunction, Upvalue, Upvalues},
+            list::RuntimeList,
+            regex::RegexModifiers,
             string::RuntimeString,
             tuple::RuntimeTuple,
             RuntimeValue,
@@ -20,12 +29,27 @@ use crate::{
 pub use runtime_error::RuntimeError;
 
 pub mod bytecode;
+mod csv;
+mod datetime;
+mod eval;
+mod hash;
+mod host;
+#[cfg(feature = "jit")]
+mod jit;
+pub mod limits;
 #[cfg(feature = "profile-vm")]
 pub mod profiler;
+mod random;
 pub mod runtime_error;
 pub mod runtime_value;
+mod search;
+#[cfg(feature = "vm-state")]
+pub mod state;
 pub mod stdlib;
 
+/// The most values a comprehension reserves room for up front, however long its source is.
+const MAX_RESERVED_CAPACITY: usize = 1 << 16;
+
 pub struct BytecodeInterpreter<I, O, E> {
     program: Program<Bytecode>,
     // TODO: Optimisation: use stack-allocated array instead of Vec?
@@ -39,6 +63,20 @@ pub struct BytecodeInterpreter<I, O, E> {
     pub instructions_executed: usize,
     memoized_functions: FxHashMap<MemoizationKey, RuntimeValue>,
     ongoing_memoizations: FxHashMap<usize, MemoizationKey>,
+    memo_cache_size: Option<usize>,
+    limits: RunLimits,
+    limit_tracker: LimitTracker,
+    // The running user functions, innermost last
+    frames: Vec<Frame>,
+    // Upvalues still referring to a stack slot, ordered by slot
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    // Functions from `defer` waiting to be called, with the frame depth they were deferred at
+    deferred: Vec<(usize, Rc<RuntimeFunction>)>,
+    rng: random::Rng,
+    args: Vec<String>,
+    sandbox: Sandbox,
+    #[cfg(feature = "jit")]
+    jit: jit::Jit,
     #[cfg(feature = "profile-vm")]
     profiler: profiler::Profiler,
     #[cfg(feature = "profile-vm")]
@@ -47,6 +85,7 @@ pub struct BytecodeInterpreter<I, O, E> {
 
 impl BytecodeInterpreter<std::io::Stdin, std::io::Stdout, std::io::Stderr> {
     pub fn new(program: Program<Bytecode>) -> Self {
+        assert_stays_in_program(&program);
         Self {
             program,
             stack: vec![],
@@ -59,6 +98,17 @@ impl BytecodeInterpreter<std::io::Stdin, std::io::Stdout, std::io::Stderr> {
             instructions_executed: 0,
             memoized_functions: FxHashMap::default(),
             ongoing_memoizations: FxHashMap::default(),
+            memo_cache_size: None,
+            limits: RunLimits::default(),
+            limit_tracker: LimitTracker::start(RunLimits::default()),
+            frames: Vec::new(),
+            open_upvalues: Vec::new(),
+            deferred: Vec::new(),
+            rng: random::Rng::from_clock(),
+            args: Vec::new(),
+            sandbox: Sandbox::default(),
+            #[cfg(feature = "jit")]
+            jit: jit::Jit::default(),
             #[cfg(feature = "profile-vm")]
             profiler: profiler::Profiler::new(),
             #[cfg(feature = "profile-vm")]
@@ -149,6 +199,17 @@ where
             instructions_executed: self.instructions_executed,
             memoized_functions: self.memoized_functions,
             ongoing_memoizations: self.ongoing_memoizations,
+            memo_cache_size: self.memo_cache_size,
+            limits: self.limits,
+            limit_tracker: self.limit_tracker,
+            frames: self.frames,
+            open_upvalues: self.open_upvalues,
+            deferred: self.deferred,
+            rng: self.rng,
+            args: self.args,
+            sandbox: self.sandbox,
+            #[cfg(feature = "jit")]
+            jit: self.jit,
             #[cfg(feature = "profile-vm")]
             profiler: self.profiler,
             #[cfg(feature = "profile-vm")]
@@ -156,20 +217,119 @@ where
         }
     }
 
+    /// Applies the settings of `config` that concern running programs: the memo cache size, the
+    /// limits, the program's arguments and the sandbox.
+    pub fn with_config(self, config: &Config) -> Self {
+        self.with_memo_cache_size(config.memo_cache_size)
+            .with_limits(config.limits)
+            .with_args(config.args.clone())
+            .with_sandbox(config.sandbox)
+    }
+
+    /// Caps the number of results cached for memoized functions. Once the cache is full, new
+    /// results are computed as usual but no longer stored.
+    pub fn with_memo_cache_size(mut self, memo_cache_size: Option<usize>) -> Self {
+        self.memo_cache_size = memo_cache_size;
+        self
+    }
+
+    /// Stops the program with an error once it has executed `limit` instructions.
+    pub fn with_instruction_limit(mut self, limit: Option<usize>) -> Self {
+        self.limits.instructions = limit;
+        self
+    }
+
+    /// Stops the program with an error once it goes over any of `limits`. The time and memory
+    /// limits are measured from the start of each call to [`Self::run`].
+    pub fn with_limits(mut self, limits: RunLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Gives the program the arguments it reads with `args()`.
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Restricts what the program may reach outside of itself, such as environment variables.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// Appends `program`, compiled with [`crate::compiler::Compiler::compile_at`] to start where
+    /// the current program ends, and continues execution from its first instruction. The stack
+    /// is kept, so the globals of earlier programs keep their values.
+    pub fn extend_program(&mut self, program: Program<Bytecode>) {
+        self.pc = self.program.instructions.len();
+        self.program.extend(program);
+        assert_stays_in_program(&self.program);
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn program_len(&self) -> usize {
+        self.program.instructions.len()
+    }
+
+    /// The number of functions compiled to native code so far.
+    #[cfg(feature = "jit")]
+    pub fn jit_compiled_functions(&self) -> usize {
+        self.jit.compiled_functions()
+    }
+
+    /// Removes and returns the value left on top of the stack by a finished program.
+    pub fn pop_result(&mut self) -> Option<RuntimeValue> {
+        self.stack.pop()
+    }
+
+    /// Throws away everything a failed run left behind above the first `num_globals` stack slots,
+    /// so another program can be run with the same globals.
+    pub fn recover(&mut self, num_globals: usize) {
+        self.close_upvalues(num_globals);
+        self.stack.truncate(num_globals);
+        self.bp = 0;
+        self.frames = Vec::new();
+        self.deferred = Vec::new();
+        self.ongoing_memoizations = FxHashMap::default();
+    }
+
+    /// Starts execution with `stack` already in place instead of an empty stack. Globals are
+    /// allocated from the bottom of the stack, so the seeded values become the initial values of
+    /// the program's globals, in the order they are first assigned in the source.
+    #[cfg(feature = "vm-state")]
+    pub fn with_seed_stack(mut self, stack: Vec<RuntimeValue>) -> Self {
+        self.stack = stack;
+        self
+    }
+
+    #[cfg(feature = "vm-state")]
+    pub fn state(&self) -> state::VmState {
+        state::VmState {
+            stack: self.stack.clone(),
+            registers: self.registers.to_vec(),
+            pc: self.pc,
+            bp: self.bp,
+            instructions_executed: self.instructions_executed,
+        }
+    }
+
     pub fn run(&mut self) -> Result<(), (Span, RuntimeError)> {
         #[cfg(feature = "profile-vm")]
         self.profiler.start();
 
-        let result = self.run_inner().map_err(|err| {
-            let source_span = self
-                .program
-                .source_map
-                .get(self.pc - 1)
-                .cloned()
-                .unwrap_or(Span::new(0, 0));
+        self.limit_tracker = LimitTracker::start(self.limits);
+        let mut result = self.run_inner().map_err(|err| (self.error_span(), err));
 
-            (source_span, err)
-        });
+        // Deferred code still runs when the program fails or exits, but the first error wins
+        while let Err(err) = self.run_deferred(0) {
+            if result.is_ok() {
+                result = Err((self.error_span(), err));
+            }
+        }
 
         #[cfg(feature = "profile-vm")]
         {
@@ -180,15 +340,64 @@ where
         result
     }
 
+    fn error_span(&self) -> Span {
+        self.program
+            .source_map
+            .get(self.pc - 1)
+            .cloned()
+            .unwrap_or(Span::new(0, 0))
+    }
+
+    // Calls the functions deferred at `depth` or deeper, most recently deferred first
+    fn run_deferred(&mut self, depth: usize) -> Result<(), RuntimeError> {
+        while let Some((_, func)) = self.deferred.pop_if(|(d, _)| *d >= depth) {
+            self.call_user_function(&func, Vec::new())?;
+        }
+        Ok(())
+    }
+
     fn run_inner(&mut self) -> Result<(), RuntimeError> {
+        // The program has already run to its end, past the `Stop` ending it
+        if self.pc >= self.program.instructions.len() {
+            return Ok(());
+        }
+
+        // Unlimited runs get a loop of their own, so they don't pay for checking the limits
+        if self.limits.is_unlimited() {
+            loop {
+                match self.execute_cur_instruction()? {
+                    ControlFlow::Continue => {}
+                    ControlFlow::Stop => return Ok(()),
+                }
+            }
+        }
+
         loop {
-            match self.execute_cur_instruction()? {
+            match self.step_checked()? {
                 ControlFlow::Continue => {}
                 ControlFlow::Stop => break Ok(()),
             }
         }
     }
 
+    // Executes the current instruction, unless the run has gone over one of its limits. Every loop
+    // executing instructions of a limited run goes through here, including those running the
+    // callbacks of standard library functions
+    #[inline(always)]
+    fn step_checked(&mut self) -> Result<ControlFlow, RuntimeError> {
+        let instruction_limit = self.limits.instructions.unwrap_or(usize::MAX);
+        if self.instructions_executed >= instruction_limit {
+            return Err(RuntimeError::Plain(format!(
+                "Instruction limit of {instruction_limit} exceeded"
+            )));
+        }
+        if self.instructions_executed.is_multiple_of(CHECK_INTERVAL) {
+            self.limit_tracker.check()?;
+        }
+
+        self.execute_cur_instruction()
+    }
+
     // Force inline to reduce function call overhead, which matters in a tight loop. For example,
     // this took a program that ran approx. 800 mil instructions from ~10s to ~7.8s.
     #[inline(always)]
@@ -205,7 +414,12 @@ where
         self.pc += 1;
         self.instructions_executed += 1;
 
-        match &self.program.instructions[pc] {
+        // SAFETY: `pc` is the index of an instruction whenever an instruction is executed. Every
+        // program ends with `Stop` and only jumps within itself (see `assert_stays_in_program`),
+        // so any other instruction is followed by one, while the locations of functions are
+        // checked before calling them, and `run` doesn't start past the `Stop` ending the program
+        let instruction = unsafe { self.program.instructions.get_unchecked(pc) };
+        match instruction {
             Bytecode::Stop => {
                 #[cfg(feature = "profile-vm")]
                 self.profiler
@@ -217,34 +431,62 @@ where
                 self.push_stack(RuntimeValue::Int(*i));
             }
 
-            Bytecode::Value(val) => {
+            Bytecode::LoadConst(index) => {
+                let val = self.program.constants[*index as usize].clone();
+                self.push_stack(val);
+            }
+
+            Bytecode::CopyConst(index) => {
+                let val = &self.program.constants[*index as usize];
                 // Perform a "deep" clone here. Otherwise, the same, shared value is inserted onto the
                 // stack. For things with mutable access, this is BAD. Assign list repeatedly to a
                 // variable? Same list is shared, it's not a new list. Value is no longer referenced on
-                // the stack? Too bad, it's still in the program instructions, so it'll keep living.
+                // the stack? Too bad, it's still in the program's constants, so it'll keep living.
                 self.push_stack(val.deep_clone());
             }
 
             Bytecode::Add => binary_op!(self, add),
+
             Bytecode::Sub => binary_op!(self, sub),
+
             Bytecode::Mul => binary_op!(self, mul),
+
             Bytecode::Div => binary_op!(self, div),
+
             Bytecode::DivFloor => binary_op!(self, div_floor),
+
             Bytecode::Mod => binary_op!(self, modulo),
+
             Bytecode::Pow => binary_op!(self, pow),
+
             Bytecode::Eq => binary_op!(self, eq_bool),
+
             Bytecode::NotEq => binary_op!(self, not_eq_bool),
+
             Bytecode::Less => binary_op!(self, less_than),
+
             Bytecode::LessEq => binary_op!(self, less_than_or_eq),
+
             Bytecode::Greater => binary_op!(self, greater_than),
+
             Bytecode::GreaterEq => binary_op!(self, greater_than_or_eq),
+
             Bytecode::Range => binary_op!(self, range),
+
             Bytecode::Xor => binary_op!(self, xor),
+
             Bytecode::BitwiseAnd => binary_op!(self, bitwise_and),
+
             Bytecode::BitwiseOr => binary_op!(self, bitwise_or),
+
             Bytecode::BitwiseXor => binary_op!(self, bitwise_xor),
+
             Bytecode::LeftShift => binary_op!(self, left_shift),
+
             Bytecode::RightShift => binary_op!(self, right_shift),
+
+            Bytecode::Neg => unary_mapper_method!(self, neg),
+
             Bytecode::BitwiseNot => unary_mapper_method!(self, bitwise_not),
 
             Bytecode::Not => {
@@ -283,6 +525,35 @@ where
                 self.set(*addr, val)?;
             }
 
+            Bytecode::PopToLocal(offset) => {
+                let addr = self.bp + offset;
+                let val = self.pop_stack();
+                self.set(addr, val)?;
+            }
+
+            Bytecode::PopToGlobal(addr) => {
+                let val = self.pop_stack();
+                self.set(*addr, val)?;
+            }
+
+            Bytecode::LoadUpvalue(index) => {
+                let val = match &*self.cur_upvalue(*index).borrow() {
+                    Upvalue::Open(slot) => self.get(*slot)?.clone(),
+                    Upvalue::Closed(val) => val.clone(),
+                };
+                self.push_stack(val);
+            }
+
+            Bytecode::StoreUpvalue(index) => {
+                let val = self.peek_stack()?.clone();
+                let upvalue = self.cur_upvalue(*index).clone();
+                let mut upvalue = upvalue.borrow_mut();
+                match &mut *upvalue {
+                    Upvalue::Open(slot) => self.set(*slot, val)?,
+                    Upvalue::Closed(closed) => *closed = val,
+                }
+            }
+
             Bytecode::Pop => {
                 self.pop_stack();
             }
@@ -359,20 +630,19 @@ where
                     }
                 };
 
-                if func.arity != num_args {
-                    return Err(RuntimeError::TypeMismatch(format!(
-                        "Expected {} arguments, got {}",
-                        func.arity, num_args
-                    )));
-                }
+                func.check_num_args(num_args)?;
+                self.check_function_location(func.location)?;
 
                 let func_location = func.location;
+                let upvalues = func.upvalues.clone();
+                let missing_args = func.arity - num_args;
 
                 if func.is_memoized {
                     let args = self.stack[self.stack.len() - num_args..].to_vec();
 
                     let memo_key = MemoizationKey {
                         func_location,
+                        upvalues: upvalues.clone(),
                         args,
                     };
 
@@ -388,17 +658,34 @@ where
                     }
                 }
 
-                // Store pc and bp (2 slots), then start new stack frame after that
-                let new_bp = func_index + 2;
+                // Arguments left out get their default values when the function starts
+                for _ in 0..missing_args {
+                    self.push_stack(RuntimeValue::Uninit);
+                }
 
-                // First slot is the return address; pop function instance and insert return address
-                self.stack[new_bp - 2] = RuntimeValue::Int(self.pc as isize);
-                // Second slot is the old base pointer
-                self.stack
-                    .insert(new_bp - 1, RuntimeValue::Int(self.bp as isize));
+                // Hot functions may run as native code instead, unless the run is limited, as
+                // native code doesn't count its instructions
+                #[cfg(feature = "jit")]
+                if !func.is_memoized && upvalues.rc().is_none() && self.limits.is_unlimited() {
+                    let args = &self.stack[func_index + 1..];
+                    if let Some(result) =
+                        self.jit
+                            .call(&self.program, func_location, args, func_index + 1)
+                    {
+                        self.stack.truncate(func_index);
+                        self.push_stack(result);
+                        return Ok(ControlFlow::Continue);
+                    }
+                }
 
-                // And then set the new base pointer and jump to the function
-                self.bp = new_bp;
+                // The caller's pc and bp are kept in the frame, so the new stack frame starts
+                // right at the arguments, after the function itself
+                self.frames.push(Frame {
+                    return_addr: self.pc,
+                    bp: self.bp,
+                    upvalues,
+                });
+                self.bp = func_index + 1;
                 self.pc = func_location;
 
                 #[cfg(feature = "profile-vm")]
@@ -409,32 +696,75 @@ where
                 #[cfg(feature = "profile-vm")]
                 self.profiler.record_return();
                 let return_val = self.pop_stack();
-                let frame_index = self.bp - 2;
+                let frame_index = self.bp - 1;
+                self.run_deferred(self.frames.len())?;
+
+                // The frame's locals are about to be discarded, so closures must keep their own copy
+                self.close_upvalues(frame_index);
 
-                let return_addr = self.stack[self.bp - 2].address()?;
-                self.bp = self.stack[self.bp - 1].address()?;
-                self.pc = return_addr;
+                let frame = self.frames.pop().ok_or_else(|| {
+                    RuntimeError::InternalBug("Returned without a function to return from".into())
+                })?;
+                self.bp = frame.bp;
+                self.pc = frame.return_addr;
 
                 if let Some(memo_key) = self.ongoing_memoizations.remove(&frame_index) {
-                    self.memoized_functions.insert(memo_key, return_val.clone());
+                    if self
+                        .memo_cache_size
+                        .is_none_or(|max| self.memoized_functions.len() < max)
+                    {
+                        self.memoized_functions.insert(memo_key, return_val.clone());
+                    }
                 }
 
                 self.stack.truncate(frame_index);
                 self.push_stack(return_val);
             }
 
+            Bytecode::EnterInlined(num_args) => {
+                let frame_index = self.stack.len() - 1 - num_args;
+                self.stack[frame_index] = RuntimeValue::Int(self.bp as isize);
+                self.bp = frame_index + 1;
+            }
+
+            Bytecode::ExitInlined => {
+                let return_val = self.pop_stack();
+                let frame_index = self.bp - 1;
+                self.bp = self.get(frame_index)?.address()?;
+                self.stack.truncate(frame_index);
+                self.push_stack(return_val);
+            }
+
+            Bytecode::Defer => {
+                let func = function_arg(self.pop_stack(), "deferred code")?;
+                self.deferred.push((self.frames.len(), func));
+                self.push_stack(RuntimeValue::Null);
+            }
+
+            Bytecode::MakeClosure(captures) => {
+                let captures = captures.clone();
+                let func = function_arg(self.pop_stack(), "closure")?;
+
+                let upvalues = captures
+                    .iter()
+                    .map(|capture| match *capture {
+                        Capture::Local(offset) => self.capture_local(self.bp + offset),
+                        Capture::Upvalue(index) => self.cur_upvalue(index).clone(),
+                    })
+                    .collect();
+
+                self.push_stack(RuntimeValue::Function(Rc::new(RuntimeFunction {
+                    upvalues: Upvalues::new(upvalues),
+                    ..(*func).clone()
+                })));
+            }
+
             Bytecode::Append => {
                 let val = self.pop_stack();
                 let into = self.peek_stack_mut()?;
                 into.append(val)?;
             }
 
-            Bytecode::Remove => {
-                let val = self.pop_stack();
-                let from = self.peek_stack_mut()?;
-                from.remove(val)?;
-            }
-
             Bytecode::Index => {
                 let index = self.pop_stack();
                 let into = self.peek_stack_mut()?;
@@ -442,6 +772,13 @@ where
                 *into = value;
             }
 
+            Bytecode::IndexOrNull => {
+                let index = self.pop_stack();
+                let into = self.peek_stack_mut()?;
+                let value = into.index_or_null(&index)?;
+                *into = value;
+            }
+
             Bytecode::SetIndex => {
                 let value = self.pop_stack();
                 let index = self.pop_stack();
@@ -472,35 +809,34 @@ where
                 self.push_stack(has_value);
             }
 
-            Bytecode::Sort(num_args) => {
-                let mut args = self.pop_args(*num_args);
-                let target = self.pop_stack();
+            Bytecode::SwapPop => {
+                self.swap();
+                self.pop_stack();
+            }
 
-                let key_func = match args.pop() {
-                    Some(RuntimeValue::Function(func)) => Some(func.clone()),
-                    None => None,
-                    Some(other) => {
-                        return Err(RuntimeError::TypeMismatch(format!(
-                            "Expected function as sort key, got {}",
-                            other.kind_str()
-                        )));
-                    }
-                };
+            Bytecode::ToIter => unary_mapper_method!(self, to_iter),
 
-                let key_fn = key_func.as_ref().map(|func| {
-                    |item: &RuntimeValue| self.call_user_function(func, vec![item.clone()])
-                });
+            Bytecode::IsUninit => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(val, RuntimeValue::Uninit)));
+            }
 
-                let res = target.sort(key_fn)?;
-                self.push_stack(res);
+            Bytecode::IsNull => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(val, RuntimeValue::Null)));
             }
 
-            Bytecode::SwapPop => {
-                self.swap();
-                self.pop_stack();
+            Bytecode::IsTupleOfLen(len) => {
+                let len = *len;
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(val.is_tuple_of_len(len)));
             }
 
-            Bytecode::ToIter => unary_mapper_method!(self, to_iter),
+            Bytecode::IsListOfLen(len) => {
+                let len = *len;
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(val.is_list_of_len(len)));
+            }
 
             Bytecode::CreateTuple(size) => {
                 let value = if *size == 2 {
@@ -514,103 +850,637 @@ where
                 self.push_stack(value);
             }
 
-            Bytecode::ToUpperCase => unary_mapper_method!(self, to_uppercase),
-            Bytecode::ToLowerCase => unary_mapper_method!(self, to_lowercase),
-            Bytecode::Split => binary_op!(self, split),
-            Bytecode::SplitLines => unary_mapper_method!(self, lines),
-            Bytecode::Nums => unary_mapper_method!(self, nums),
-            Bytecode::Join(num_args) => method_with_optional_arg!(self, join, *num_args),
-            Bytecode::Length => unary_mapper_method!(self, length),
-            Bytecode::Count => binary_op!(self, count),
-            Bytecode::FindAll => binary_op!(self, find_all),
-            Bytecode::Find => binary_op!(self, find),
-            Bytecode::IsMatch => binary_op!(self, is_match),
-            Bytecode::Contains => binary_op!(self, contains),
-            Bytecode::StartsWith => binary_op!(self, starts_with),
             Bytecode::IsIn => binary_op_swapped!(self, contains),
-            Bytecode::Enumerate => unary_mapper_method!(self, enumerate),
-            Bytecode::GetAll => binary_op!(self, get_all),
-            Bytecode::Values => unary_mapper_method!(self, values),
-            Bytecode::Keys => unary_mapper_method!(self, keys),
-            Bytecode::Rot => binary_op!(self, rot),
-            Bytecode::Binary(num_args) => method_with_optional_arg!(self, binary, *num_args),
-            Bytecode::Flat => unary_mapper_method!(self, flat),
-            Bytecode::First => unary_mapper_method!(self, first),
-            Bytecode::Last => unary_mapper_method!(self, last),
-            Bytecode::Transpose => unary_mapper_method!(self, transpose),
-            Bytecode::Reverse => unary_mapper_method!(self, reverse),
-
-            Bytecode::ParseInt => stdlib_fn!(self, parse_int),
-            Bytecode::ToList => stdlib_fn!(self, to_list),
-            Bytecode::ToTuple => stdlib_fn!(self, to_tuple),
-            Bytecode::ToMap => stdlib_fn!(self, to_map),
-            Bytecode::MapWithDefault => stdlib_fn!(self, map_with_default),
-            Bytecode::ToSet(num_args) => stdlib_fn_with_optional_arg!(self, to_set, *num_args),
-            Bytecode::ToCounter(num_args) => {
-                stdlib_fn_with_optional_arg!(self, to_counter, *num_args)
-            }
-            Bytecode::Product => stdlib_fn!(self, mul),
-            Bytecode::Sum => stdlib_fn!(self, sum),
-            Bytecode::AllTrue(num_args) => stdlib_fn!(self, all, *num_args),
-            Bytecode::AnyTrue(num_args) => stdlib_fn!(self, any, *num_args),
-            Bytecode::Max(num_args) => stdlib_fn!(self, max, *num_args),
-            Bytecode::Min(num_args) => stdlib_fn!(self, min, *num_args),
-            Bytecode::Abs => stdlib_fn!(self, abs),
-            Bytecode::Sqrt => stdlib_fn!(self, sqrt),
-            Bytecode::Manhattan(num_args) => stdlib_fn!(self, manhattan, *num_args),
-            Bytecode::ModInv(num_args) => stdlib_fn!(self, mod_inv, *num_args),
 
-            Bytecode::PrintValue(num_args) => {
-                let vals = self.pop_args(*num_args);
+            // Builtins are kept out of line, so the code for the instructions above stays small
+            _ => self.execute_builtin(pc)?,
+        }
 
-                let mut last_val = None;
-                for val in vals {
-                    if last_val.is_some() {
-                        write!(self.stdout, " ").unwrap();
-                    }
-                    write!(self.stdout, "{val}").unwrap();
+        #[cfg(feature = "profile-vm")]
+        self.profiler
+            .record(&self.program.instructions[pc], span, instr_start.elapsed());
 
-                    last_val = Some(val);
-                }
-                writeln!(self.stdout).unwrap();
+        Ok(ControlFlow::Continue)
+    }
 
-                self.push_stack(last_val.unwrap_or(RuntimeValue::Null));
+    // Executes the instructions for builtins, which are rarely what hot loops spend their time on
+    #[inline(never)]
+    fn execute_builtin(&mut self, pc: usize) -> Result<(), RuntimeError> {
+        match &self.program.instructions[pc] {
+            Bytecode::PushFront => {
+                let val = self.pop_stack();
+                let into = self.peek_stack_mut()?;
+                into.push_front(val)?;
             }
 
-            Bytecode::ReprString => {
+            Bytecode::PushBack => {
                 let val = self.pop_stack();
-                let repr = val.repr_string();
-                self.push_stack(RuntimeValue::Str(RuntimeString::new(repr)));
+                let into = self.peek_stack_mut()?;
+                into.push_back(val)?;
             }
 
-            Bytecode::Stringify => {
+            Bytecode::Remove => {
                 let val = self.pop_stack();
-                let s = val.to_string();
-                self.push_stack(RuntimeValue::Str(RuntimeString::new(s)));
+                let from = self.peek_stack_mut()?;
+                from.remove(val)?;
             }
 
-            Bytecode::ReadInput => {
-                let mut input = String::new();
-                self.stdin
-                    .read_to_string(&mut input)
-                    .map_err(|e| RuntimeError::InternalBug(format!("Failed to read stdin: {e}")))?;
+            Bytecode::Sort(num_args) => {
+                let mut args = self.pop_args(*num_args);
+                let target = self.pop_stack();
 
-                self.push_stack(RuntimeValue::Str(RuntimeString::new(input)));
-            }
+                let key_func = match args.pop() {
+                    Some(RuntimeValue::Function(func)) => Some(func.clone()),
+                    None => None,
+                    Some(other) => {
+                        return Err(RuntimeError::TypeMismatch(format!(
+                            "Expected function as sort key, got {}",
+                            other.kind_str()
+                        )));
+                    }
+                };
 
-            Bytecode::RuntimeError(err) => return Err(RuntimeError::Plain(*err.clone())),
+                let key_fn = key_func.as_ref().map(|func| {
+                    |item: &RuntimeValue| self.call_user_function(func, vec![item.clone()])
+                });
 
-            #[allow(unreachable_patterns)]
-            to_implement => {
+                let res = target.sort(key_fn)?;
+                self.push_stack(res);
+            }
+
+            Bytecode::ChunkBy => {
+                let func = function_arg(self.pop_stack(), "chunk_by key")?;
+                let iterable = self.pop_stack();
+
+                let res = stdlib::chunk_by(iterable, |item| {
+                    self.call_user_function(&func, vec![item.clone()])
+                })?;
+                self.push_stack(res);
+            }
+
+            Bytecode::SplitWhen => {
+                let func = function_arg(self.pop_stack(), "split_when predicate")?;
+                let iterable = self.pop_stack();
+
+                let res = stdlib::split_when(iterable, |item| {
+                    self.call_user_function(&func, vec![item.clone()])
+                })?;
+                self.push_stack(res);
+            }
+
+            Bytecode::CountIf(num_args) => {
+                let mut args = self.pop_args(*num_args).into_iter();
+                let iterable = args.next().expect("count_if takes an iterable");
+                let pred = args
+                    .next()
+                    .map(|pred| function_arg(pred, "count_if predicate"))
+                    .transpose()?;
+
+                let res = match pred {
+                    Some(func) => stdlib::count_if(iterable, |item| {
+                        self.call_user_function(&func, vec![item.clone()])
+                    })?,
+                    None => stdlib::count_if(iterable, |item| Ok(item.clone()))?,
+                };
+                self.push_stack(res);
+            }
+
+            Bytecode::MapItems => {
+                let iterable = self.pop_stack();
+                let func = function_arg(self.pop_stack(), "map function")?;
+
+                let res = stdlib::map_items(iterable, |item| {
+                    self.call_user_function(&func, vec![item.clone()])
+                })?;
+                self.push_stack(res);
+            }
+
+            Bytecode::Filter => {
+                let iterable = self.pop_stack();
+                let func = function_arg(self.pop_stack(), "filter predicate")?;
+
+                let res = stdlib::filter(iterable, |item| {
+                    self.call_user_function(&func, vec![item.clone()])
+                })?;
+                self.push_stack(res);
+            }
+
+            Bytecode::Reduce => {
+                let init = self.pop_stack();
+                let iterable = self.pop_stack();
+                let func = function_arg(self.pop_stack(), "reduce function")?;
+
+                let res = stdlib::reduce(iterable, init, |acc, item| {
+                    self.call_user_function(&func, vec![acc, item.clone()])
+                })?;
+                self.push_stack(res);
+            }
+
+            Bytecode::Bfs => {
+                let args = self.pop_args(3);
+                let res = self.bfs(args)?;
+                self.push_stack(res);
+            }
+
+            Bytecode::Dijkstra => {
+                let args = self.pop_args(3);
+                let res = self.dijkstra(args)?;
+                self.push_stack(res);
+            }
+
+            Bytecode::AStar => {
+                let args = self.pop_args(4);
+                let res = self.a_star(args)?;
+                self.push_stack(res);
+            }
+
+            Bytecode::Sorted(num_args) => {
+                let mut args = self.pop_args(*num_args).into_iter();
+                let iterable = args.next().expect("sorted takes an iterable");
+                let (key_func, reverse) = sort_options(args.next())?;
+
+                let key_fn = key_func.as_ref().map(|func| {
+                    |item: &RuntimeValue| self.call_user_function(func, vec![item.clone()])
+                });
+
+                let res = stdlib::sorted(iterable, key_fn, reverse)?;
+                self.push_stack(res);
+            }
+
+            Bytecode::ListWithSizeHint => {
+                let iter = self.pop_stack();
+                let capacity = match &iter {
+                    RuntimeValue::Iterator(iter) => iter.size_hint().unwrap_or(0),
+                    _ => 0,
+                };
+                // Comprehensions can filter out most values of a huge range, so the hint is only
+                // trusted up to a point
+                let list = RuntimeList::with_capacity(capacity.min(MAX_RESERVED_CAPACITY));
+                self.push_stack(RuntimeValue::List(list));
+            }
+
+            Bytecode::IsStr => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(val, RuntimeValue::Str(_))));
+            }
+
+            Bytecode::IsNum => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(
+                    val,
+                    RuntimeValue::Int(_) | RuntimeValue::Num(_)
+                )));
+            }
+
+            Bytecode::IsBool => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(val, RuntimeValue::Bool(_))));
+            }
+
+            Bytecode::IsList => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(val, RuntimeValue::List(_))));
+            }
+
+            Bytecode::IsTuple => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(
+                    val,
+                    RuntimeValue::Tuple(_) | RuntimeValue::Vec2(_) | RuntimeValue::Vec3(_)
+                )));
+            }
+
+            Bytecode::IsMap => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(val, RuntimeValue::Map(_))));
+            }
+
+            Bytecode::IsSet => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(val, RuntimeValue::Set(_))));
+            }
+
+            Bytecode::IsFunction => {
+                let val = self.pop_stack();
+                self.push_stack(RuntimeValue::Bool(matches!(val, RuntimeValue::Function(_))));
+            }
+
+            Bytecode::ToUpperCase => unary_mapper_method!(self, to_uppercase),
+
+            Bytecode::ToLowerCase => unary_mapper_method!(self, to_lowercase),
+
+            Bytecode::Split => binary_op!(self, split),
+
+            Bytecode::SplitLines => unary_mapper_method!(self, lines),
+
+            Bytecode::Paragraphs => unary_mapper_method!(self, paragraphs),
+
+            Bytecode::Nums => unary_mapper_method!(self, nums),
+
+            Bytecode::Digits => unary_mapper_method!(self, digits),
+
+            Bytecode::Join(num_args) => method_with_optional_arg!(self, join, *num_args),
+
+            Bytecode::Length => unary_mapper_method!(self, length),
+
+            Bytecode::Count(num_args) => method_with_optional_arg!(self, count, *num_args),
+
+            Bytecode::FindAll => binary_op!(self, find_all),
+
+            Bytecode::Find => binary_op!(self, find),
+
+            Bytecode::IsMatch => binary_op!(self, is_match),
+
+            Bytecode::Contains => binary_op!(self, contains),
+
+            Bytecode::StartsWith => binary_op!(self, starts_with),
+
+            Bytecode::EndsWith => binary_op!(self, ends_with),
+
+            Bytecode::IndexOf => binary_op!(self, index_of),
+
+            Bytecode::Trim => unary_mapper_method!(self, trim),
+
+            Bytecode::TrimStart => unary_mapper_method!(self, trim_start),
+
+            Bytecode::TrimEnd => unary_mapper_method!(self, trim_end),
+
+            Bytecode::Replace => {
+                let to = self.pop_stack();
+                let from = self.pop_stack();
+                let target = self.pop_stack();
+                self.push_stack(target.replace(&from, &to)?);
+            }
+
+            Bytecode::Repeat => binary_op!(self, repeat),
+
+            Bytecode::Format(num_args) => {
+                let args = self.pop_args(*num_args);
+                let target = self.pop_stack();
+                self.push_stack(target.format(&args)?);
+            }
+
+            Bytecode::Enumerate(num_args) => method_with_optional_arg!(self, enumerate, *num_args),
+
+            Bytecode::GetAll => binary_op!(self, get_all),
+
+            Bytecode::Values => unary_mapper_method!(self, values),
+
+            Bytecode::Keys => unary_mapper_method!(self, keys),
+
+            Bytecode::Rot => binary_op!(self, rot),
+
+            Bytecode::Binary(num_args) => method_with_optional_arg!(self, binary, *num_args),
+
+            Bytecode::Flat => unary_mapper_method!(self, flat),
+
+            Bytecode::First => unary_mapper_method!(self, first),
+
+            Bytecode::Last => unary_mapper_method!(self, last),
+
+            Bytecode::Nth => binary_op!(self, nth),
+
+            Bytecode::GetOr => {
+                let default = self.pop_stack();
+                let key = self.pop_stack();
+                let target = self.pop_stack();
+                self.push_stack(target.get_or(&key, default)?);
+            }
+
+            Bytecode::IsEmpty => unary_mapper_method!(self, is_empty),
+
+            Bytecode::Transpose => unary_mapper_method!(self, transpose),
+
+            Bytecode::GridToMap => unary_mapper_method!(self, grid_to_map),
+
+            Bytecode::ToStrings => unary_mapper_method!(self, grid_to_strings),
+
+            Bytecode::Reverse => unary_mapper_method!(self, reverse),
+
+            Bytecode::Tally => unary_mapper_method!(self, tally),
+
+            Bytecode::MostCommon(num_args) => {
+                method_with_optional_arg!(self, most_common, *num_args)
+            }
+
+            Bytecode::PopFront => unary_mapper_method!(self, pop_front),
+
+            Bytecode::PopBack => unary_mapper_method!(self, pop_back),
+
+            Bytecode::Width => unary_mapper_method!(self, width),
+
+            Bytecode::Height => unary_mapper_method!(self, height),
+
+            Bytecode::Neighbors4 => binary_op!(self, neighbors4),
+
+            Bytecode::Neighbors8 => binary_op!(self, neighbors8),
+
+            Bytecode::X => unary_mapper_method!(self, x),
+
+            Bytecode::Y => unary_mapper_method!(self, y),
+
+            Bytecode::Z => unary_mapper_method!(self, z),
+
+            Bytecode::Dot => binary_op!(self, dot),
+
+            Bytecode::Cross => binary_op!(self, cross),
+
+            Bytecode::Pairwise => unary_mapper_method!(self, pairwise),
+
+            Bytecode::Deltas => unary_mapper_method!(self, deltas),
+
+            Bytecode::ParseInt => stdlib_fn!(self, parse_int),
+
+            Bytecode::ParseFloat => stdlib_fn!(self, parse_float),
+
+            Bytecode::ToInt(num_args) => stdlib_fn!(self, to_int, *num_args),
+
+            Bytecode::ToBase => stdlib_fn!(self, to_base, 2),
+
+            Bytecode::ToFloat => stdlib_fn!(self, to_float),
+
+            Bytecode::Eval(num_args) => {
+                let args = self.pop_args(*num_args);
+                let result = self.eval(args)?;
+                self.push_stack(result);
+            }
+
+            Bytecode::TryNum => stdlib_fn!(self, try_num),
+
+            Bytecode::TryInt(num_args) => stdlib_fn!(self, try_int, *num_args),
+
+            Bytecode::TryFloat => stdlib_fn!(self, try_float),
+
+            Bytecode::TryIndex => stdlib_fn!(self, try_index, 2),
+
+            Bytecode::TryGet(num_args) => stdlib_fn!(self, try_get, *num_args),
+
+            Bytecode::ToList => stdlib_fn!(self, to_list),
+
+            Bytecode::ToTuple => stdlib_fn!(self, to_tuple),
+
+            Bytecode::ToMap => stdlib_fn!(self, to_map),
+
+            Bytecode::MapWithDefault => stdlib_fn!(self, map_with_default),
+
+            Bytecode::ListWithCapacity => stdlib_fn!(self, list_with_capacity),
+
+            Bytecode::MapWithCapacity => stdlib_fn!(self, map_with_capacity),
+
+            Bytecode::MapToGrid(num_args) => stdlib_fn!(self, map_to_grid, *num_args),
+
+            Bytecode::ToSet(num_args) => stdlib_fn_with_optional_arg!(self, to_set, *num_args),
+
+            Bytecode::ToCounter(num_args) => {
+                stdlib_fn_with_optional_arg!(self, to_counter, *num_args)
+            }
+
+            Bytecode::ToDeque(num_args) => {
+                stdlib_fn_with_optional_arg!(self, to_deque, *num_args)
+            }
+
+            Bytecode::ToGrid => stdlib_fn!(self, to_grid),
+
+            Bytecode::Product => stdlib_fn!(self, mul),
+
+            Bytecode::Sum => stdlib_fn!(self, sum),
+
+            Bytecode::AllTrue(num_args) => stdlib_fn!(self, all, *num_args),
+
+            Bytecode::AnyTrue(num_args) => stdlib_fn!(self, any, *num_args),
+
+            Bytecode::Max(num_args) => stdlib_fn!(self, max, *num_args),
+
+            Bytecode::Min(num_args) => stdlib_fn!(self, min, *num_args),
+
+            Bytecode::Abs => stdlib_fn!(self, abs),
+
+            Bytecode::Sqrt => stdlib_fn!(self, sqrt),
+
+            Bytecode::Isqrt => stdlib_fn!(self, isqrt),
+
+            Bytecode::Gcd(num_args) => stdlib_fn!(self, gcd, *num_args),
+
+            Bytecode::Lcm(num_args) => stdlib_fn!(self, lcm, *num_args),
+
+            Bytecode::Floor => stdlib_fn!(self, floor),
+
+            Bytecode::Ceil => stdlib_fn!(self, ceil),
+
+            Bytecode::Round => stdlib_fn!(self, round),
+
+            Bytecode::Sin => stdlib_fn!(self, sin),
+
+            Bytecode::Cos => stdlib_fn!(self, cos),
+
+            Bytecode::Tan => stdlib_fn!(self, tan),
+
+            Bytecode::Log(num_args) => stdlib_fn!(self, log, *num_args),
+
+            Bytecode::Log2 => stdlib_fn!(self, log2),
+
+            Bytecode::Rand => {
+                let val = random::rand(&mut self.rng);
+                self.push_stack(val);
+            }
+
+            Bytecode::RandInt => {
+                let args = self.pop_args(2);
+                let val = random::rand_int(&mut self.rng, args)?;
+                self.push_stack(val);
+            }
+
+            Bytecode::Shuffle => {
+                let arg = self.pop_stack();
+                let val = random::shuffle(&mut self.rng, arg)?;
+                self.push_stack(val);
+            }
+
+            Bytecode::Choice => {
+                let arg = self.pop_stack();
+                let val = random::choice(&mut self.rng, arg)?;
+                self.push_stack(val);
+            }
+
+            Bytecode::Seed => {
+                let arg = self.pop_stack();
+                let val = random::seed(&mut self.rng, arg)?;
+                self.push_stack(val);
+            }
+
+            Bytecode::Args => self.push_stack(host::args(&self.args)),
+
+            Bytecode::Env => {
+                let name = self.pop_stack();
+                let val = host::env(&self.sandbox, name)?;
+                self.push_stack(val);
+            }
+
+            Bytecode::Fetch(num_args) => {
+                let args = self.pop_args(*num_args);
+                let val = host::fetch(&self.sandbox, args)?;
+                self.push_stack(val);
+            }
+
+            Bytecode::HttpGet => {
+                let headers = self.pop_stack();
+                let url = self.pop_stack();
+                let val = host::http_get(&self.sandbox, url, headers)?;
+                self.push_stack(val);
+            }
+
+            Bytecode::Exit(num_args) => {
+                let code = self.pop_args(*num_args).pop();
+                return Err(RuntimeError::Exit(host::exit_code(code)?));
+            }
+
+            Bytecode::ParseCsv(num_args) => {
+                let args = self.pop_args(*num_args);
+                self.push_stack(csv::parse_csv(args)?);
+            }
+
+            Bytecode::Md5 => {
+                let arg = self.pop_stack();
+                self.push_stack(hash::md5(arg)?);
+            }
+
+            Bytecode::Sha256 => {
+                let arg = self.pop_stack();
+                self.push_stack(hash::sha256(arg)?);
+            }
+
+            Bytecode::Now => self.push_stack(datetime::now()),
+
+            Bytecode::Timestamp => self.push_stack(datetime::timestamp()?),
+
+            Bytecode::FormatTime(num_args) => {
+                let args = self.pop_args(*num_args);
+                self.push_stack(datetime::format_time(args)?);
+            }
+
+            Bytecode::ParseTime(num_args) => {
+                let args = self.pop_args(*num_args);
+                self.push_stack(datetime::parse_time(args)?);
+            }
+
+            Bytecode::Manhattan(num_args) => stdlib_fn!(self, manhattan, *num_args),
+
+            Bytecode::ModInv(num_args) => stdlib_fn!(self, mod_inv, *num_args),
+
+            Bytecode::WrapAdd => stdlib_fn!(self, wrap_add, 3),
+
+            Bytecode::WrapSub => stdlib_fn!(self, wrap_sub, 3),
+
+            Bytecode::WrapMul => stdlib_fn!(self, wrap_mul, 3),
+
+            Bytecode::Error => stdlib_fn!(self, error),
+
+            Bytecode::AssertEq => stdlib_fn!(self, assert_eq, 2),
+
+            Bytecode::DeepEq => stdlib_fn!(self, deep_eq, 2),
+
+            Bytecode::ApproxEq(num_args) => stdlib_fn!(self, approx_eq, *num_args),
+
+            Bytecode::Permutations(num_args) => stdlib_fn!(self, permutations, *num_args),
+
+            Bytecode::Combinations => stdlib_fn!(self, combinations, 2),
+
+            Bytecode::Vec2 => stdlib_fn!(self, vec2, 2),
+
+            Bytecode::Vec3 => stdlib_fn!(self, vec3, 3),
+
+            Bytecode::Rational => stdlib_fn!(self, rational, 2),
+
+            Bytecode::CompileRegex(num_args) => {
+                let regex = self.compile_regex(*num_args)?;
+                self.push_stack(regex);
+            }
+
+            Bytecode::PrintValue(num_args) => {
+                let vals = self.pop_args(*num_args);
+                let last_val = print_values(&mut self.stdout, vals);
+                self.push_stack(last_val);
+            }
+
+            Bytecode::EprintValue(num_args) => {
+                let vals = self.pop_args(*num_args);
+                let last_val = print_values(&mut self.stderr, vals);
+                self.push_stack(last_val);
+            }
+
+            Bytecode::Inspect(num_args) => {
+                let mut args = self.pop_args(*num_args).into_iter();
+                let val = args.next().unwrap();
+                match args.next() {
+                    Some(label) => writeln!(self.stderr, "{label} {}", val.repr_string()),
+                    None => writeln!(self.stderr, "{}", val.repr_string()),
+                }
+                .unwrap();
+                self.push_stack(val);
+            }
+
+            Bytecode::Histogram(num_args) => {
+                let args = self.pop_args(*num_args);
+                write!(self.stdout, "{}", stdlib::histogram(args)?).unwrap();
+                self.push_stack(RuntimeValue::Null);
+            }
+
+            Bytecode::FreqTable => {
+                let val = self.pop_stack();
+                write!(self.stdout, "{}", stdlib::freq_table(val)?).unwrap();
+                self.push_stack(RuntimeValue::Null);
+            }
+
+            Bytecode::ReprString => {
+                let val = self.pop_stack();
+                let repr = val.repr_string();
+                self.push_stack(RuntimeValue::Str(RuntimeString::new(repr)));
+            }
+
+            Bytecode::Stringify => {
+                let val = self.pop_stack();
+                let s = val.to_string();
+                self.push_stack(RuntimeValue::Str(RuntimeString::new(s)));
+            }
+
+            Bytecode::DeepCopy => {
+                let val = self.pop_stack();
+                self.push_stack(val.deep_clone());
+            }
+
+            Bytecode::TypeOf => stdlib_fn!(self, type_of),
+
+            Bytecode::ReadInput => {
+                let mut input = String::new();
+                self.stdin
+                    .read_to_string(&mut input)
+                    .map_err(|e| RuntimeError::InternalBug(format!("Failed to read stdin: {e}")))?;
+
+                self.push_stack(RuntimeValue::Str(RuntimeString::new(input)));
+            }
+
+            Bytecode::ReadLine(num_args) => {
+                if let Some(prompt) = self.pop_args(*num_args).pop() {
+                    write!(self.stdout, "{prompt}").unwrap();
+                    self.stdout.flush().unwrap();
+                }
+
+                let line = self
+                    .read_line()
+                    .map_err(|e| RuntimeError::InternalBug(format!("Failed to read stdin: {e}")))?;
+                self.push_stack(line.map_or(RuntimeValue::Null, |line| {
+                    RuntimeValue::Str(RuntimeString::new(line))
+                }));
+            }
+
+            Bytecode::RuntimeError(err) => return Err(RuntimeError::Plain(*err.clone())),
+
+            to_implement => {
                 return Err(RuntimeError::NotImplemented(to_implement.clone()));
             }
         }
 
-        #[cfg(feature = "profile-vm")]
-        self.profiler
-            .record(&self.program.instructions[pc], span, instr_start.elapsed());
-
-        Ok(ControlFlow::Continue)
+        Ok(())
     }
 
     pub fn pop_stack(&mut self) -> RuntimeValue {
@@ -629,6 +1499,28 @@ where
         self.stack.split_off(self.stack.len() - num_args)
     }
 
+    // Reads a byte at a time, since stdin isn't buffered by the interpreter and reading further
+    // would take input away from the next read
+    fn read_line(&mut self) -> std::io::Result<Option<String>> {
+        let mut line = Vec::new();
+        let mut byte = [0];
+        loop {
+            match self.stdin.read(&mut byte) {
+                Ok(0) if line.is_empty() => return Ok(None),
+                Ok(0) => break,
+                Ok(_) if byte[0] == b'\n' => break,
+                Ok(_) => line.push(byte[0]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+    }
+
     // TODO: It's probably very slow to check this every time, but it provides good diagnostics.
     // Provide feature flag to enable checks?
     pub fn set(&mut self, index: usize, value: RuntimeValue) -> Result<(), RuntimeError> {
@@ -666,6 +1558,17 @@ where
         self.stack.swap(len - 1, len - 2);
     }
 
+    // Function values can come from outside of the program, e.g. from Rust code calling into the
+    // VM, so their location is checked before jumping to it, which keeps `pc` inside the program
+    fn check_function_location(&self, location: usize) -> Result<(), RuntimeError> {
+        if location >= self.program.instructions.len() {
+            return Err(RuntimeError::InternalBug(format!(
+                "Function at {location} is outside of the program"
+            )));
+        }
+        Ok(())
+    }
+
     pub fn call_user_function(
         &mut self,
         func: &RuntimeFunction,
@@ -683,33 +1586,36 @@ where
             .italic()
         );
 
-        if func.arity != args.len() {
-            return Err(RuntimeError::TypeMismatch(format!(
-                "Expected {} arguments, got {}",
-                func.arity,
-                args.len()
-            )));
-        }
+        func.check_num_args(args.len())?;
+        self.check_function_location(func.location)?;
 
-        let saved_pc = self.pc;
-        let saved_bp = self.bp;
         let stack_base = self.stack.len();
 
+        // Run until the function itself returns, i.e. a return while its frame is the innermost one
+        let depth = self.frames.len();
+        self.frames.push(Frame {
+            return_addr: self.pc,
+            bp: self.bp,
+            upvalues: func.upvalues.clone(),
+        });
+
         self.bp = stack_base;
         self.pc = func.location;
         self.stack.extend(args);
+        self.stack
+            .resize(stack_base + func.arity, RuntimeValue::Uninit);
 
-        let mut depth = 1;
-        loop {
-            match &self.program.instructions[self.pc] {
-                Bytecode::Call(_) => depth += 1,
-                Bytecode::Return if depth == 1 => break,
-                Bytecode::Return => depth -= 1,
-                _ => {}
+        let unlimited = self.limits.is_unlimited();
+        while !(matches!(self.program.instructions[self.pc], Bytecode::Return)
+            && self.frames.len() == depth + 1)
+        {
+            if unlimited {
+                self.execute_cur_instruction()?;
+            } else {
+                self.step_checked()?;
             }
-
-            self.execute_cur_instruction()?;
         }
+        self.run_deferred(depth + 1)?;
 
         #[cfg(feature = "debug-vm")]
         {
@@ -722,13 +1628,92 @@ where
 
         let result = self.pop_stack();
 
+        self.close_upvalues(stack_base);
+        let frame = self
+            .frames
+            .pop()
+            .expect("the function's frame is the innermost one");
+
         self.stack.truncate(stack_base);
-        self.pc = saved_pc;
-        self.bp = saved_bp;
+        self.pc = frame.return_addr;
+        self.bp = frame.bp;
 
         Ok(result)
     }
 
+    // Regexes built at runtime go through the same pool as regex literals, so building one inside
+    // a loop only compiles it once
+    fn compile_regex(&mut self, num_args: usize) -> Result<RuntimeValue, RuntimeError> {
+        let mut args = self.pop_args(num_args).into_iter();
+        let pattern = args.next().expect("regex takes at least one argument");
+        let flags = args.next();
+
+        let RuntimeValue::Str(pattern) = &pattern else {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "Cannot make a regex from type '{}'",
+                pattern.kind_str()
+            )));
+        };
+
+        let modifiers = match &flags {
+            None => RegexModifiers::from_flags(""),
+            Some(RuntimeValue::Str(flags)) => RegexModifiers::from_flags(flags.as_str()),
+            Some(flags) => {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "Regex flags must be a string, got '{}'",
+                    flags.kind_str()
+                )))
+            }
+        }
+        .map_err(RuntimeError::Plain)?;
+
+        let regex = self
+            .program
+            .regex_pool
+            .get_or_compile(pattern.as_str(), modifiers)
+            .map_err(|e| RuntimeError::Plain(format!("Invalid regex: {e}")))?;
+
+        Ok(RuntimeValue::Regex(regex))
+    }
+
+    fn cur_upvalue(&self, index: usize) -> &Rc<RefCell<Upvalue>> {
+        self.frames
+            .last()
+            .expect("upvalues are only used inside functions")
+            .upvalues
+            .get(index)
+    }
+
+    // Closures capturing the same variable share its upvalue, so they see each other's changes
+    fn capture_local(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let index = self
+            .open_upvalues
+            .partition_point(|upvalue| open_slot(upvalue) < slot);
+
+        match self.open_upvalues.get(index) {
+            Some(upvalue) if open_slot(upvalue) == slot => upvalue.clone(),
+            _ => {
+                let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+                cycles::track(Tracked::Upvalue(Rc::downgrade(&upvalue)));
+                self.open_upvalues.insert(index, upvalue.clone());
+                upvalue
+            }
+        }
+    }
+
+    // Moves the values of all captured variables at or above `from_slot` into their upvalues
+    fn close_upvalues(&mut self, from_slot: usize) {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let slot = open_slot(upvalue);
+            if slot < from_slot {
+                break;
+            }
+
+            *upvalue.borrow_mut() = Upvalue::Closed(self.stack[slot].clone());
+            self.open_upvalues.pop();
+        }
+    }
+
     pub fn dbg_print(&self) {
         eprintln!("======== Bytecode Interpreter State ========");
         eprintln!("{}", format!("pc: {}", self.pc).dim());
@@ -794,7 +1779,101 @@ where
     }
 }
 
+// Checks what `execute_cur_instruction` relies on to fetch instructions without bounds checks:
+// running off the end of a program is impossible when it ends with `Stop`, and so is jumping out
+// of it when every jump goes to one of its instructions. Programs from the compiler always do.
+fn assert_stays_in_program(program: &Program<Bytecode>) {
+    let len = program.instructions.len();
+    assert!(
+        len == 0 || matches!(program.instructions.last(), Some(Bytecode::Stop)),
+        "Programs must end with Stop"
+    );
+    for instruction in &program.instructions {
+        if let Bytecode::Goto(target)
+        | Bytecode::IfTrue(target)
+        | Bytecode::IfFalse(target)
+        | Bytecode::NextIterOrJump(target) = instruction
+        {
+            assert!(*target < len, "Jump to {target} is outside of the program");
+        }
+    }
+}
+
+// Writes the values separated by spaces and followed by a newline, returning the last one
+fn print_values(sink: &mut impl Write, vals: Vec<RuntimeValue>) -> RuntimeValue {
+    let mut last_val = None;
+    for val in vals {
+        if last_val.is_some() {
+            write!(sink, " ").unwrap();
+        }
+        write!(sink, "{val}").unwrap();
+
+        last_val = Some(val);
+    }
+    writeln!(sink).unwrap();
+
+    last_val.unwrap_or(RuntimeValue::Null)
+}
+
+fn function_arg(val: RuntimeValue, purpose: &str) -> Result<Rc<RuntimeFunction>, RuntimeError> {
+    match val {
+        RuntimeValue::Function(func) => Ok(func),
+        other => Err(RuntimeError::TypeMismatch(format!(
+            "Expected function as {purpose}, got {}",
+            other.kind_str()
+        ))),
+    }
+}
+
+// The second argument of `sorted`, which is either the key function or a map of options
+fn sort_options(
+    val: Option<RuntimeValue>,
+) -> Result<(Option<Rc<RuntimeFunction>>, bool), RuntimeError> {
+    let options = match val {
+        None => return Ok((None, false)),
+        Some(RuntimeValue::Function(func)) => return Ok((Some(func), false)),
+        Some(RuntimeValue::Map(options)) => options,
+        Some(other) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "Expected function or map of options for sorted, got {}",
+                other.kind_str()
+            )))
+        }
+    };
+
+    let (mut key_func, mut reverse) = (None, false);
+    for (key, value) in options.borrow().map.iter() {
+        match key.to_string().as_str() {
+            "key" => key_func = Some(function_arg(value.clone(), "sort key")?),
+            "reverse" => reverse = value.bool(),
+            key => {
+                return Err(RuntimeError::Plain(format!(
+                    "Unknown sorted option '{key}', expected 'key' or 'reverse'"
+                )))
+            }
+        }
+    }
+
+    Ok((key_func, reverse))
+}
+
+fn open_slot(upvalue: &RefCell<Upvalue>) -> usize {
+    match *upvalue.borrow() {
+        Upvalue::Open(slot) => slot,
+        Upvalue::Closed(_) => unreachable!("closed upvalues are not tracked"),
+    }
+}
+
 enum ControlFlow {
     Continue,
     Stop,
 }
+
+// A running user function
+struct Frame {
+    // Where the caller continues once the function returns
+    return_addr: usize,
+    // The caller's base pointer
+    bp: usize,
+    upvalues: Upvalues,
+}
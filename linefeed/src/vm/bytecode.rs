@@ -2,12 +2,18 @@ use std::{collections::HashMap, rc::Rc};
 
 use crate::{
     compiler::{
-        ir_value::IrValue, method::Method, stdlib_fn::StdlibFn, CompileError, Instruction, Label,
-        Program,
+        ir_value::IrValue, method::Method, stdlib_fn::StdlibFn, Code, CompileError, Instruction,
+        Label, Program,
     },
     vm::runtime_value::{
-        function::RuntimeFunction, list::RuntimeList, map::RuntimeMap, regex::RuntimeRegex,
-        set::RuntimeSet, string::RuntimeString, tuple::RuntimeTuple, RuntimeValue,
+        function::{Capture, RuntimeFunction},
+        list::RuntimeList,
+        map::RuntimeMap,
+        regex::RegexPool,
+        set::RuntimeSet,
+        string::RuntimeString,
+        tuple::RuntimeTuple,
+        RuntimeValue,
     },
 };
 
@@ -21,9 +27,14 @@ pub enum Bytecode {
     StoreLocal(usize),
     LoadGlobal(usize),
     StoreGlobal(usize),
+    LoadUpvalue(usize),
+    StoreUpvalue(usize),
+    PopToLocal(usize),
+    PopToGlobal(usize),
 
     // Values
-    Value(RuntimeValue),
+    LoadConst(u32),
+    CopyConst(u32),
     ConstantInt(isize),
 
     // Stack manipulation
@@ -63,6 +74,7 @@ pub enum Bytecode {
 
     // Logic
     Not,
+    Neg,
     BitwiseNot,
 
     // Control flow
@@ -76,26 +88,62 @@ pub enum Bytecode {
     GetBasePtr,
     Call(usize),
     Return,
+    MakeClosure(Box<Vec<Capture>>),
+    Defer,
+    EnterInlined(usize),
+    ExitInlined,
 
     // Builtins
     PrintValue(usize),
+    EprintValue(usize),
+    Inspect(usize),
     ReadInput,
+    ReadLine(usize),
     Index,
+    IndexOrNull,
     SetIndex,
     NextIter,
     NextIterOrJump(usize),
     ToIter,
+    ListWithSizeHint,
     ParseInt,
+    ParseFloat,
+    ToInt(usize),
+    ToBase,
+    ToFloat,
+    Eval(usize),
+    TryNum,
+    TryInt(usize),
+    TryFloat,
+    TryIndex,
+    TryGet(usize),
     ToList,
     ToTuple,
     CreateTuple(usize),
+    IsNum,
+    IsStr,
+    IsBool,
+    IsList,
+    IsTuple,
+    IsMap,
+    IsSet,
+    IsFunction,
+    IsUninit,
+    IsNull,
+    IsTupleOfLen(usize),
+    IsListOfLen(usize),
     ToMap,
     MapWithDefault,
+    ListWithCapacity,
+    MapWithCapacity,
+    MapToGrid(usize),
     ToSet(usize),
     Product,
     Sum,
     ReprString,
     Stringify,
+    DeepCopy,
+    TypeOf,
     IsIn,
     AllTrue(usize),
     AnyTrue(usize),
@@ -103,9 +151,63 @@ pub enum Bytecode {
     Min(usize),
     Abs,
     Sqrt,
+    Isqrt,
+    Gcd(usize),
+    Lcm(usize),
+    Floor,
+    Ceil,
+    Round,
+    Sin,
+    Cos,
+    Tan,
+    Log(usize),
+    Log2,
+    Rand,
+    RandInt,
+    Shuffle,
+    Choice,
+    Seed,
+    Now,
+    Timestamp,
+    FormatTime(usize),
+    ParseTime(usize),
+    Args,
+    Env,
+    Fetch(usize),
+    HttpGet,
+    Exit(usize),
+    ParseCsv(usize),
+    Md5,
+    Sha256,
     ToCounter(usize),
+    ToDeque(usize),
+    ToGrid,
     Manhattan(usize),
     ModInv(usize),
+    Histogram(usize),
+    FreqTable,
+    ChunkBy,
+    SplitWhen,
+    CountIf(usize),
+    MapItems,
+    Filter,
+    Reduce,
+    Sorted(usize),
+    Permutations(usize),
+    Combinations,
+    Bfs,
+    Dijkstra,
+    AStar,
+    Vec2,
+    Vec3,
+    Rational,
+    WrapAdd,
+    WrapSub,
+    WrapMul,
+    Error,
+    AssertEq,
+    DeepEq,
+    ApproxEq(usize),
 
     // Methods
     Append,
@@ -113,18 +215,29 @@ pub enum Bytecode {
     ToLowerCase,
     Split,
     SplitLines,
+    Paragraphs,
     Nums,
+    Digits,
     Join(usize),
     Length,
-    Count,
+    Count(usize),
     FindAll,
     Find,
     IsMatch,
     Contains,
     StartsWith,
+    EndsWith,
+    IndexOf,
+    Trim,
+    TrimStart,
+    TrimEnd,
+    Replace,
+    Repeat,
+    Format(usize),
     Sort(usize),
-    Enumerate,
+    Enumerate(usize),
     GetAll,
+    GetOr,
     Values,
     Keys,
     Remove,
@@ -133,8 +246,30 @@ pub enum Bytecode {
     Flat,
     First,
     Last,
+    Nth,
+    IsEmpty,
     Transpose,
+    GridToMap,
+    ToStrings,
     Reverse,
+    Tally,
+    MostCommon(usize),
+    PushFront,
+    PushBack,
+    PopFront,
+    PopBack,
+    Width,
+    Height,
+    Neighbors4,
+    Neighbors8,
+    X,
+    Y,
+    Z,
+    Dot,
+    Cross,
+    Pairwise,
+    Deltas,
+    CompileRegex(usize),
 }
 
 const _: () = {
@@ -142,10 +277,65 @@ const _: () = {
     assert!(SIZE == 16);
 };
 
+impl Code for Bytecode {
+    type Constants = Vec<RuntimeValue>;
+
+    fn append_constants(
+        constants: &mut Vec<RuntimeValue>,
+        instructions: &mut [Self],
+        other: Vec<RuntimeValue>,
+    ) {
+        let offset = constants.len() as u32;
+        for instr in instructions {
+            if let Bytecode::LoadConst(index) | Bytecode::CopyConst(index) = instr {
+                *index += offset;
+            }
+        }
+        constants.extend(other);
+    }
+}
+
+/// The constants of a program being lowered to bytecode, where identical literals share one
+/// constant.
+#[derive(Default)]
+pub struct ConstantPool {
+    constants: Vec<RuntimeValue>,
+    // Keyed by the debug representation of the value, which unlike equality of runtime values
+    // tells e.g. `1` and `1.0` apart
+    indices: HashMap<String, u32>,
+}
+
+impl ConstantPool {
+    fn add(
+        &mut self,
+        value: IrValue,
+        label_mapper: &LabelMapper,
+        regex_pool: &mut RegexPool,
+    ) -> Result<u32, CompileError> {
+        let key = format!("{value:?}");
+        if let Some(&index) = self.indices.get(&key) {
+            return Ok(index);
+        }
+
+        let index = u32::try_from(self.constants.len())
+            .map_err(|_| CompileError::Plain("Program has too many constants".to_string()))?;
+        self.constants
+            .push(Bytecode::into_runtime_value_with_mapper(
+                value,
+                label_mapper,
+                regex_pool,
+            )?);
+        self.indices.insert(key, index);
+        Ok(index)
+    }
+}
+
 impl Bytecode {
     pub fn from_instruction(
         instruction: Instruction,
         label_mapper: &LabelMapper,
+        regex_pool: &mut RegexPool,
+        constants: &mut ConstantPool,
     ) -> Result<Option<Self>, CompileError> {
         let bytecode = match instruction {
             Instruction::Label(_) => return Ok(None),
@@ -155,9 +345,20 @@ impl Bytecode {
             Instruction::StoreLocal(offset) => Bytecode::StoreLocal(offset),
             Instruction::LoadGlobal(addr) => Bytecode::LoadGlobal(addr),
             Instruction::StoreGlobal(addr) => Bytecode::StoreGlobal(addr),
+            Instruction::LoadUpvalue(index) => Bytecode::LoadUpvalue(index),
+            Instruction::StoreUpvalue(index) => Bytecode::StoreUpvalue(index),
+            Instruction::PopToLocal(offset) => Bytecode::PopToLocal(offset),
+            Instruction::PopToGlobal(addr) => Bytecode::PopToGlobal(addr),
             Instruction::GetBasePtr => Bytecode::GetBasePtr,
             Instruction::Value(value) => {
-                Bytecode::Value(Self::into_runtime_value_with_mapper(value, label_mapper)?)
+                let index = constants.add(value, label_mapper, regex_pool)?;
+                // Values that can be changed must be copied every time they're loaded, while the
+                // rest can be shared with the constant
+                if constants.constants[index as usize].is_immutable() {
+                    Bytecode::LoadConst(index)
+                } else {
+                    Bytecode::CopyConst(index)
+                }
             }
             Instruction::ConstantInt(i) => Bytecode::ConstantInt(i),
             Instruction::Add => Bytecode::Add,
@@ -181,6 +382,7 @@ impl Bytecode {
             Instruction::LeftShift => Bytecode::LeftShift,
             Instruction::RightShift => Bytecode::RightShift,
             Instruction::Not => Bytecode::Not,
+            Instruction::Neg => Bytecode::Neg,
             Instruction::BitwiseNot => Bytecode::BitwiseNot,
             Instruction::Stop => Bytecode::Stop,
             Instruction::Goto(label) => Bytecode::Goto(label_mapper.get(label)?),
@@ -198,27 +400,67 @@ impl Bytecode {
             Instruction::GetRegister(register) => Bytecode::GetRegister(register),
             Instruction::Call(num_args) => Bytecode::Call(num_args),
             Instruction::Return => Bytecode::Return,
+            Instruction::MakeClosure(captures) => Bytecode::MakeClosure(Box::new(captures)),
+            Instruction::Defer => Bytecode::Defer,
+            Instruction::EnterInlined(num_args) => Bytecode::EnterInlined(num_args),
+            Instruction::ExitInlined => Bytecode::ExitInlined,
             Instruction::Index => Bytecode::Index,
+            Instruction::IndexOrNull => Bytecode::IndexOrNull,
             Instruction::SetIndex => Bytecode::SetIndex,
             Instruction::NextIter => Bytecode::NextIter,
             Instruction::NextIterOrJump(label) => {
                 Bytecode::NextIterOrJump(label_mapper.get(label)?)
             }
             Instruction::ToIter => Bytecode::ToIter,
+            Instruction::ListWithSizeHint => Bytecode::ListWithSizeHint,
             Instruction::IsIn => Bytecode::IsIn,
             Instruction::CreateTuple(size) => Bytecode::CreateTuple(size),
+            Instruction::IsStr => Bytecode::IsStr,
+            Instruction::IsUninit => Bytecode::IsUninit,
+            Instruction::IsNull => Bytecode::IsNull,
+            Instruction::IsTupleOfLen(len) => Bytecode::IsTupleOfLen(len),
+            Instruction::IsListOfLen(len) => Bytecode::IsListOfLen(len),
             Instruction::StdlibCall(func, num_args) => match func {
                 StdlibFn::Print => Bytecode::PrintValue(num_args),
+                StdlibFn::Eprint => Bytecode::EprintValue(num_args),
+                StdlibFn::Inspect => Bytecode::Inspect(num_args),
                 StdlibFn::Input => Bytecode::ReadInput,
+                StdlibFn::ReadLine => Bytecode::ReadLine(num_args),
                 StdlibFn::ParseInt => Bytecode::ParseInt,
+                StdlibFn::ParseFloat => Bytecode::ParseFloat,
+                StdlibFn::ToInt => Bytecode::ToInt(num_args),
+                StdlibFn::ToBase => Bytecode::ToBase,
+                StdlibFn::ToFloat => Bytecode::ToFloat,
+                StdlibFn::Eval => Bytecode::Eval(num_args),
+                StdlibFn::TryNum => Bytecode::TryNum,
+                StdlibFn::TryInt => Bytecode::TryInt(num_args),
+                StdlibFn::TryFloat => Bytecode::TryFloat,
+                StdlibFn::TryIndex => Bytecode::TryIndex,
+                StdlibFn::TryGet => Bytecode::TryGet(num_args),
                 StdlibFn::ToList => Bytecode::ToList,
                 StdlibFn::ToTuple => Bytecode::ToTuple,
+                StdlibFn::ToMap if num_args == 2 => Bytecode::MapItems,
                 StdlibFn::ToMap => Bytecode::ToMap,
                 StdlibFn::MapWithDefault => Bytecode::MapWithDefault,
+                StdlibFn::ListWithCapacity => Bytecode::ListWithCapacity,
+                StdlibFn::MapWithCapacity => Bytecode::MapWithCapacity,
+                StdlibFn::MapToGrid => Bytecode::MapToGrid(num_args),
                 StdlibFn::ToSet => Bytecode::ToSet(num_args),
                 StdlibFn::Counter => Bytecode::ToCounter(num_args),
+                StdlibFn::ToDeque => Bytecode::ToDeque(num_args),
+                StdlibFn::ToGrid => Bytecode::ToGrid,
                 StdlibFn::Repr => Bytecode::ReprString,
                 StdlibFn::Stringify => Bytecode::Stringify,
+                StdlibFn::DeepCopy => Bytecode::DeepCopy,
+                StdlibFn::TypeOf => Bytecode::TypeOf,
+                StdlibFn::IsNum => Bytecode::IsNum,
+                StdlibFn::IsStr => Bytecode::IsStr,
+                StdlibFn::IsBool => Bytecode::IsBool,
+                StdlibFn::IsList => Bytecode::IsList,
+                StdlibFn::IsTuple => Bytecode::IsTuple,
+                StdlibFn::IsMap => Bytecode::IsMap,
+                StdlibFn::IsSet => Bytecode::IsSet,
+                StdlibFn::IsFunction => Bytecode::IsFunction,
                 StdlibFn::Product => Bytecode::Product,
                 StdlibFn::Sum => Bytecode::Sum,
                 StdlibFn::All => Bytecode::AllTrue(num_args),
@@ -226,9 +468,61 @@ impl Bytecode {
                 StdlibFn::Max => Bytecode::Max(num_args),
                 StdlibFn::Abs => Bytecode::Abs,
                 StdlibFn::Sqrt => Bytecode::Sqrt,
+                StdlibFn::Isqrt => Bytecode::Isqrt,
+                StdlibFn::Gcd => Bytecode::Gcd(num_args),
+                StdlibFn::Lcm => Bytecode::Lcm(num_args),
+                StdlibFn::Floor => Bytecode::Floor,
+                StdlibFn::Ceil => Bytecode::Ceil,
+                StdlibFn::Round => Bytecode::Round,
+                StdlibFn::Sin => Bytecode::Sin,
+                StdlibFn::Cos => Bytecode::Cos,
+                StdlibFn::Tan => Bytecode::Tan,
+                StdlibFn::Log => Bytecode::Log(num_args),
+                StdlibFn::Log2 => Bytecode::Log2,
+                StdlibFn::Rand => Bytecode::Rand,
+                StdlibFn::RandInt => Bytecode::RandInt,
+                StdlibFn::Shuffle => Bytecode::Shuffle,
+                StdlibFn::Choice => Bytecode::Choice,
+                StdlibFn::Seed => Bytecode::Seed,
+                StdlibFn::Now => Bytecode::Now,
+                StdlibFn::Timestamp => Bytecode::Timestamp,
+                StdlibFn::FormatTime => Bytecode::FormatTime(num_args),
+                StdlibFn::ParseTime => Bytecode::ParseTime(num_args),
+                StdlibFn::Args => Bytecode::Args,
+                StdlibFn::Env => Bytecode::Env,
+                StdlibFn::Fetch => Bytecode::Fetch(num_args),
+                StdlibFn::HttpGet => Bytecode::HttpGet,
+                StdlibFn::Exit => Bytecode::Exit(num_args),
+                StdlibFn::ParseCsv => Bytecode::ParseCsv(num_args),
+                StdlibFn::Md5 => Bytecode::Md5,
+                StdlibFn::Sha256 => Bytecode::Sha256,
                 StdlibFn::Min => Bytecode::Min(num_args),
                 StdlibFn::Manhattan => Bytecode::Manhattan(num_args),
                 StdlibFn::ModInv => Bytecode::ModInv(num_args),
+                StdlibFn::Histogram => Bytecode::Histogram(num_args),
+                StdlibFn::FreqTable => Bytecode::FreqTable,
+                StdlibFn::ChunkBy => Bytecode::ChunkBy,
+                StdlibFn::SplitWhen => Bytecode::SplitWhen,
+                StdlibFn::CountIf => Bytecode::CountIf(num_args),
+                StdlibFn::Filter => Bytecode::Filter,
+                StdlibFn::Reduce => Bytecode::Reduce,
+                StdlibFn::Sorted => Bytecode::Sorted(num_args),
+                StdlibFn::Permutations => Bytecode::Permutations(num_args),
+                StdlibFn::Combinations => Bytecode::Combinations,
+                StdlibFn::Bfs => Bytecode::Bfs,
+                StdlibFn::Dijkstra => Bytecode::Dijkstra,
+                StdlibFn::AStar => Bytecode::AStar,
+                StdlibFn::Vec2 => Bytecode::Vec2,
+                StdlibFn::Vec3 => Bytecode::Vec3,
+                StdlibFn::Rational => Bytecode::Rational,
+                StdlibFn::WrapAdd => Bytecode::WrapAdd,
+                StdlibFn::WrapSub => Bytecode::WrapSub,
+                StdlibFn::WrapMul => Bytecode::WrapMul,
+                StdlibFn::Error => Bytecode::Error,
+                StdlibFn::AssertEq => Bytecode::AssertEq,
+                StdlibFn::DeepEq => Bytecode::DeepEq,
+                StdlibFn::ApproxEq => Bytecode::ApproxEq(num_args),
+                StdlibFn::Regex => Bytecode::CompileRegex(num_args),
             },
             Instruction::MethodCall(method, num_args) => match method {
                 Method::Append | Method::Add => Bytecode::Append,
@@ -236,18 +530,29 @@ impl Bytecode {
                 Method::ToLowerCase => Bytecode::ToLowerCase,
                 Method::Split => Bytecode::Split,
                 Method::SplitLines => Bytecode::SplitLines,
+                Method::Paragraphs => Bytecode::Paragraphs,
                 Method::Nums => Bytecode::Nums,
+                Method::Digits => Bytecode::Digits,
                 Method::Join => Bytecode::Join(num_args),
                 Method::Length => Bytecode::Length,
-                Method::Count => Bytecode::Count,
+                Method::Count => Bytecode::Count(num_args),
                 Method::FindAll => Bytecode::FindAll,
                 Method::Find => Bytecode::Find,
                 Method::IsMatch => Bytecode::IsMatch,
                 Method::Contains => Bytecode::Contains,
                 Method::StartsWith => Bytecode::StartsWith,
+                Method::EndsWith => Bytecode::EndsWith,
+                Method::IndexOf => Bytecode::IndexOf,
+                Method::Trim => Bytecode::Trim,
+                Method::TrimStart => Bytecode::TrimStart,
+                Method::TrimEnd => Bytecode::TrimEnd,
+                Method::Replace => Bytecode::Replace,
+                Method::Repeat => Bytecode::Repeat,
+                Method::Format => Bytecode::Format(num_args),
                 Method::Sort => Bytecode::Sort(num_args),
-                Method::Enumerate => Bytecode::Enumerate,
+                Method::Enumerate => Bytecode::Enumerate(num_args),
                 Method::GetAll => Bytecode::GetAll,
+                Method::GetOr => Bytecode::GetOr,
                 Method::Values => Bytecode::Values,
                 Method::Keys => Bytecode::Keys,
                 Method::Remove => Bytecode::Remove,
@@ -256,8 +561,29 @@ impl Bytecode {
                 Method::Flat => Bytecode::Flat,
                 Method::First => Bytecode::First,
                 Method::Last => Bytecode::Last,
+                Method::Nth => Bytecode::Nth,
+                Method::IsEmpty => Bytecode::IsEmpty,
                 Method::Transpose => Bytecode::Transpose,
+                Method::GridToMap => Bytecode::GridToMap,
+                Method::ToStrings => Bytecode::ToStrings,
                 Method::Reverse => Bytecode::Reverse,
+                Method::Tally => Bytecode::Tally,
+                Method::MostCommon => Bytecode::MostCommon(num_args),
+                Method::PushFront => Bytecode::PushFront,
+                Method::PushBack => Bytecode::PushBack,
+                Method::PopFront => Bytecode::PopFront,
+                Method::PopBack => Bytecode::PopBack,
+                Method::Width => Bytecode::Width,
+                Method::Height => Bytecode::Height,
+                Method::Neighbors4 => Bytecode::Neighbors4,
+                Method::Neighbors8 => Bytecode::Neighbors8,
+                Method::X => Bytecode::X,
+                Method::Y => Bytecode::Y,
+                Method::Z => Bytecode::Z,
+                Method::Dot => Bytecode::Dot,
+                Method::Cross => Bytecode::Cross,
+                Method::Pairwise => Bytecode::Pairwise,
+                Method::Deltas => Bytecode::Deltas,
             },
         };
 
@@ -268,18 +594,21 @@ impl Bytecode {
     fn into_runtime_value_with_mapper(
         value: IrValue,
         label_mapper: &LabelMapper,
+        regex_pool: &mut RegexPool,
     ) -> Result<RuntimeValue, CompileError> {
         let res = match value {
             IrValue::Null => RuntimeValue::Null,
             IrValue::Uninit => RuntimeValue::Uninit,
             IrValue::Bool(b) => RuntimeValue::Bool(b),
             IrValue::Int(i) => RuntimeValue::Int(i),
-            IrValue::Num(n) => RuntimeValue::Num(n),
+            IrValue::Num(n) => RuntimeValue::Num(n.into()),
             IrValue::Str(s) => RuntimeValue::Str(RuntimeString::new(s)),
             IrValue::List(xs) => {
                 let items = xs
                     .into_iter()
-                    .map(|item| Self::into_runtime_value_with_mapper(item, label_mapper))
+                    .map(|item| {
+                        Self::into_runtime_value_with_mapper(item, label_mapper, regex_pool)
+                    })
                     .collect::<Result<_, _>>()?;
 
                 RuntimeValue::List(RuntimeList::from_vec(items))
@@ -287,7 +616,9 @@ impl Bytecode {
             IrValue::Tuple(xs) => {
                 let items = xs
                     .into_iter()
-                    .map(|item| Self::into_runtime_value_with_mapper(item, label_mapper))
+                    .map(|item| {
+                        Self::into_runtime_value_with_mapper(item, label_mapper, regex_pool)
+                    })
                     .collect::<Result<_, _>>()?;
 
                 RuntimeTuple::from_vec(items)
@@ -295,7 +626,9 @@ impl Bytecode {
             IrValue::Set(xs) => {
                 let items = xs
                     .into_iter()
-                    .map(|item| Self::into_runtime_value_with_mapper(item, label_mapper))
+                    .map(|item| {
+                        Self::into_runtime_value_with_mapper(item, label_mapper, regex_pool)
+                    })
                     .collect::<Result<_, _>>()?;
 
                 RuntimeValue::Set(RuntimeSet::from_set(items))
@@ -305,8 +638,8 @@ impl Bytecode {
                     .into_iter()
                     .map(|(key, value)| {
                         Ok((
-                            Self::into_runtime_value_with_mapper(key, label_mapper)?,
-                            Self::into_runtime_value_with_mapper(value, label_mapper)?,
+                            Self::into_runtime_value_with_mapper(key, label_mapper, regex_pool)?,
+                            Self::into_runtime_value_with_mapper(value, label_mapper, regex_pool)?,
                         ))
                     })
                     .collect::<Result<_, _>>()?;
@@ -316,14 +649,19 @@ impl Bytecode {
             IrValue::Function(func) => RuntimeValue::Function(Rc::new(RuntimeFunction {
                 location: label_mapper.get(func.location)?,
                 arity: func.arity,
+                min_arity: func.min_arity,
                 is_memoized: func.is_memoized,
+                upvalues: Default::default(),
             })),
             IrValue::Regex(s, modifiers) => {
-                let regex = RuntimeRegex::compile(&s, modifiers)
+                let regex = regex_pool
+                    .get_or_compile(&s, modifiers)
                     .map_err(|e| CompileError::Plain(format!("Invalid regex: {e}")))?;
 
                 RuntimeValue::Regex(regex)
             }
+            IrValue::Symbol(symbol) => RuntimeValue::Symbol(symbol),
+            IrValue::Variant(variant) => RuntimeValue::Variant(variant),
         };
 
         Ok(res)
@@ -332,17 +670,62 @@ impl Bytecode {
 
 impl Program<Instruction> {
     pub fn into_bytecode(self) -> Result<Program<Bytecode>, CompileError> {
-        let label_mapper = LabelMapper::from(&self);
+        self.into_bytecode_at(0)
+    }
+
+    /// Converts the program to bytecode that will be located at `start_pc` in the VM.
+    pub fn into_bytecode_at(mut self, start_pc: usize) -> Result<Program<Bytecode>, CompileError> {
+        self.fuse_instructions();
+        let label_mapper = LabelMapper::new(&self, start_pc);
+
+        // Regexes already in the program's pool are reused instead of being compiled again
+        let mut regex_pool = std::mem::take(&mut self.regex_pool);
+        let mut constants = ConstantPool::default();
 
         let mut bytecode_program = Program::new();
         for (instruction, span) in self.instructions.into_iter().zip(self.source_map) {
-            if let Some(bytecode) = Bytecode::from_instruction(instruction, &label_mapper)? {
+            if let Some(bytecode) = Bytecode::from_instruction(
+                instruction,
+                &label_mapper,
+                &mut regex_pool,
+                &mut constants,
+            )? {
                 bytecode_program.add_instruction(bytecode, span);
             }
         }
 
+        // The VM keeps the pool, so regexes built at runtime share the ones from literals
+        bytecode_program.regex_pool = regex_pool;
+        bytecode_program.constants = constants.constants;
+
         Ok(bytecode_program)
     }
+
+    // Replaces common sequences of instructions with a single instruction that does the same, so
+    // there are fewer instructions to dispatch. Labels are instructions too, so a sequence never
+    // has a jump target in the middle of it.
+    fn fuse_instructions(&mut self) {
+        let instructions = std::mem::take(&mut self.instructions);
+        let source_map = std::mem::take(&mut self.source_map);
+
+        let mut iter = instructions.into_iter().zip(source_map).peekable();
+        while let Some((instruction, span)) = iter.next() {
+            let fused = match (&instruction, iter.peek()) {
+                (Instruction::StoreLocal(offset), Some((Instruction::Pop, _))) => {
+                    Some(Instruction::PopToLocal(*offset))
+                }
+                (Instruction::StoreGlobal(addr), Some((Instruction::Pop, _))) => {
+                    Some(Instruction::PopToGlobal(*addr))
+                }
+                _ => None,
+            };
+
+            if fused.is_some() {
+                iter.next();
+            }
+            self.add_instruction(fused.unwrap_or(instruction), span);
+        }
+    }
 }
 
 pub struct LabelMapper {
@@ -351,8 +734,14 @@ pub struct LabelMapper {
 
 impl From<&Program<Instruction>> for LabelMapper {
     fn from(program: &Program<Instruction>) -> Self {
+        Self::new(program, 0)
+    }
+}
+
+impl LabelMapper {
+    pub fn new(program: &Program<Instruction>, start_pc: usize) -> Self {
         let mut label_locations = HashMap::new();
-        let mut pc = 0;
+        let mut pc = start_pc;
 
         for instruction in program.instructions.iter() {
             match instruction {
@@ -367,9 +756,7 @@ impl From<&Program<Instruction>> for LabelMapper {
 
         Self { label_locations }
     }
-}
 
-impl LabelMapper {
     pub fn get(&self, label: Label) -> Result<usize, CompileError> {
         self.label_locations
             .get(&label)
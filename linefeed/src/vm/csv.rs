@@ -0,0 +1,151 @@
+//! The `parse_csv` builtin, which splits delimiter-separated text into rows of fields.
+//!
+//! Fields can be quoted with `"` to contain delimiters, newlines or `""` for a literal quote, as
+//! in RFC 4180. Fields are always strings, and empty lines are skipped.
+
+use crate::vm::{
+    runtime_value::{list::RuntimeList, map::RuntimeMap, string::RuntimeString, RuntimeValue},
+    stdlib::RuntimeResult,
+    RuntimeError,
+};
+
+fn parse_rows(s: &str, delimiter: char) -> Result<Vec<Vec<String>>, RuntimeError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut chars = s.chars().peekable();
+    // Whether the current field was quoted, so a quoted empty field still counts on its own line
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() && !quoted => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => field.push(c),
+                        None => {
+                            return Err(RuntimeError::ParseError(format!(
+                                "Unclosed quote in row {} of CSV",
+                                rows.len() + 1
+                            )))
+                        }
+                    }
+                }
+            }
+            c if c == delimiter => {
+                row.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if !row.is_empty() || !field.is_empty() || quoted {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                quoted = false;
+            }
+            c => field.push(c),
+        }
+    }
+
+    if !row.is_empty() || !field.is_empty() || quoted {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+fn str_value(s: String) -> RuntimeValue {
+    RuntimeValue::Str(RuntimeString::new(s))
+}
+
+/// A list of rows, each a list of fields. With a header, the first row names the fields and the
+/// rest become maps from those names instead.
+pub fn parse_csv(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let mut args = args.into_iter();
+    let s = match args.next() {
+        Some(RuntimeValue::Str(s)) => s,
+        Some(other) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "parse_csv expects a string, got '{}'",
+                other.kind_str()
+            )))
+        }
+        None => unreachable!("parse_csv called with no arguments"),
+    };
+
+    let delimiter = match args.next() {
+        None => ',',
+        Some(RuntimeValue::Str(d)) => {
+            let mut chars = d.as_str().chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '"' && c != '\n' => c,
+                _ => {
+                    return Err(RuntimeError::Plain(format!(
+                        "CSV delimiter must be a single character other than a quote or newline, got {:?}",
+                        d.as_str()
+                    )))
+                }
+            }
+        }
+        Some(other) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "CSV delimiter must be a string, got '{}'",
+                other.kind_str()
+            )))
+        }
+    };
+
+    let header = args.next().is_some_and(|header| header.bool());
+
+    let rows = parse_rows(s.as_str(), delimiter)?;
+
+    if !header {
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                RuntimeValue::List(RuntimeList::from_vec(
+                    row.into_iter().map(str_value).collect(),
+                ))
+            })
+            .collect();
+        return Ok(RuntimeValue::List(RuntimeList::from_vec(rows)));
+    }
+
+    let mut rows = rows.into_iter();
+    let names: Vec<RuntimeValue> = rows
+        .next()
+        .unwrap_or_default()
+        .into_iter()
+        .map(str_value)
+        .collect();
+
+    let records = rows
+        .enumerate()
+        .map(|(i, row)| {
+            if row.len() != names.len() {
+                return Err(RuntimeError::ParseError(format!(
+                    "Row {} of CSV has {} fields, but the header has {}",
+                    i + 2,
+                    row.len(),
+                    names.len()
+                )));
+            }
+
+            let record = RuntimeMap::with_capacity(names.len());
+            for (name, field) in names.iter().zip(row) {
+                record.insert(name.clone(), str_value(field));
+            }
+            Ok(RuntimeValue::Map(record))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(RuntimeValue::List(RuntimeList::from_vec(records)))
+}
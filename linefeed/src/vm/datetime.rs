@@ -0,0 +1,240 @@
+//! The date and time builtins `now`, `timestamp`, `format_time` and `parse_time`.
+//!
+//! Times are seconds since the Unix epoch, and dates are always in UTC. Formats take the
+//! strftime specifiers `%Y`, `%m`, `%d`, `%H`, `%M`, `%S` and `%%`, which is enough for logging
+//! and for the dates found in puzzle inputs.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::vm::{
+    runtime_value::{number::RuntimeNumber, string::RuntimeString, RuntimeValue},
+    stdlib::RuntimeResult,
+    RuntimeError,
+};
+
+const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl DateTime {
+    fn from_unix(secs: i64) -> Self {
+        let (days, secs) = (
+            secs.div_euclid(SECONDS_PER_DAY),
+            secs.rem_euclid(SECONDS_PER_DAY),
+        );
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: (secs / 3600) as u32,
+            minute: (secs / 60 % 60) as u32,
+            second: (secs % 60) as u32,
+        }
+    }
+
+    fn to_unix(self) -> i64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        days * SECONDS_PER_DAY
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64
+    }
+
+    fn format(&self, format: &str) -> Result<String, RuntimeError> {
+        let mut res = String::new();
+        let mut chars = format.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                res.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('Y') => res.push_str(&format!("{:04}", self.year)),
+                Some('m') => res.push_str(&format!("{:02}", self.month)),
+                Some('d') => res.push_str(&format!("{:02}", self.day)),
+                Some('H') => res.push_str(&format!("{:02}", self.hour)),
+                Some('M') => res.push_str(&format!("{:02}", self.minute)),
+                Some('S') => res.push_str(&format!("{:02}", self.second)),
+                Some('%') => res.push('%'),
+                other => return Err(unknown_specifier(other)),
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn parse(s: &str, format: &str) -> Result<Self, RuntimeError> {
+        let mismatch =
+            || RuntimeError::ParseError(format!("{s:?} does not match the time format {format:?}"));
+
+        let mut date = Self::from_unix(0);
+        let mut rest = s;
+        let mut chars = format.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                rest = rest.strip_prefix(c).ok_or_else(mismatch)?;
+                continue;
+            }
+
+            let spec = chars.next();
+            if spec == Some('%') {
+                rest = rest.strip_prefix('%').ok_or_else(mismatch)?;
+                continue;
+            }
+
+            // Years are four digits and everything else two, so fields may be written together
+            let width = if spec == Some('Y') { 4 } else { 2 };
+            let digits = rest
+                .get(..width)
+                .filter(|d| d.bytes().all(|b| b.is_ascii_digit()));
+            let n: u32 = digits
+                .ok_or_else(mismatch)?
+                .parse()
+                .map_err(|_| mismatch())?;
+            rest = &rest[width..];
+
+            match spec {
+                Some('Y') => date.year = n as i64,
+                Some('m') => date.month = n,
+                Some('d') => date.day = n,
+                Some('H') => date.hour = n,
+                Some('M') => date.minute = n,
+                Some('S') => date.second = n,
+                other => return Err(unknown_specifier(other)),
+            }
+        }
+
+        let valid = rest.is_empty()
+            && (1..=12).contains(&date.month)
+            && (1..=days_in_month(date.year, date.month)).contains(&date.day)
+            && date.hour < 24
+            && date.minute < 60
+            && date.second < 60;
+
+        if !valid {
+            return Err(mismatch());
+        }
+        Ok(date)
+    }
+}
+
+fn unknown_specifier(spec: Option<char>) -> RuntimeError {
+    match spec {
+        Some(c) => RuntimeError::Plain(format!("Unknown time format specifier '%{c}'")),
+        None => RuntimeError::Plain("Time format cannot end with '%'".to_string()),
+    }
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since the Unix epoch of a date in the proleptic Gregorian calendar, using the algorithm
+// from http://howardhinnant.github.io/date_algorithms.html, which counts years from March
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn seconds_since_epoch() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |time| time.as_secs_f64())
+}
+
+/// The current time as seconds since the Unix epoch, with a fractional part.
+pub fn now() -> RuntimeValue {
+    RuntimeValue::Num(RuntimeNumber::Float(seconds_since_epoch()))
+}
+
+/// The current time as a string in the default format, e.g. for logging progress.
+pub fn timestamp() -> RuntimeResult {
+    let date = DateTime::from_unix(seconds_since_epoch().floor() as i64);
+    Ok(RuntimeValue::Str(RuntimeString::new(
+        date.format(DEFAULT_FORMAT)?,
+    )))
+}
+
+pub fn format_time(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let (secs, format) = match args.as_slice() {
+        [RuntimeValue::Num(secs), rest @ ..] => (secs.floor_int(), format_arg(rest.first())?),
+        [other, ..] => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "format_time expects seconds since the epoch, got '{}'",
+                other.kind_str()
+            )))
+        }
+        [] => unreachable!("format_time called with no arguments"),
+    };
+
+    let date = DateTime::from_unix(secs as i64);
+    Ok(RuntimeValue::Str(RuntimeString::new(date.format(&format)?)))
+}
+
+pub fn parse_time(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let (s, format) = match args.as_slice() {
+        [RuntimeValue::Str(s), rest @ ..] => (s.as_str().to_string(), format_arg(rest.first())?),
+        [other, ..] => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "parse_time expects a string, got '{}'",
+                other.kind_str()
+            )))
+        }
+        [] => unreachable!("parse_time called with no arguments"),
+    };
+
+    let date = DateTime::parse(&s, &format)?;
+    Ok(RuntimeValue::Num(RuntimeNumber::from(date.to_unix())))
+}
+
+fn format_arg(format: Option<&RuntimeValue>) -> Result<String, RuntimeError> {
+    match format {
+        None => Ok(DEFAULT_FORMAT.to_string()),
+        Some(RuntimeValue::Str(s)) => Ok(s.as_str().to_string()),
+        Some(other) => Err(RuntimeError::TypeMismatch(format!(
+            "Time format must be a string, got '{}'",
+            other.kind_str()
+        ))),
+    }
+}
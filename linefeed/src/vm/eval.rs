@@ -0,0 +1,182 @@
+//! The `eval` builtin, which runs a string of code in a child VM.
+//!
+//! The child is compiled on its own, so it has its own globals and can't see or change the
+//! variables of the program calling `eval`. Only its result is handed back, which is why it can't
+//! return functions: they refer to code in the child's program, which is gone once it finishes.
+
+use std::io::{Cursor, Read, Write};
+
+use chumsky::Parser as _;
+
+use crate::{
+    compiler::Compiler,
+    config::Config,
+    grammar::lexer,
+    parse_tokens,
+    vm::{
+        limits::RunLimits,
+        runtime_value::{string::RuntimeString, RuntimeValue},
+        BytecodeInterpreter, RuntimeError,
+    },
+};
+
+/// How the child VM of an `eval` call is run, from its optional map of options.
+#[derive(Debug, Default)]
+struct EvalOptions {
+    /// Given to the child as its input instead of the parent's input.
+    input: Option<String>,
+    /// Collects the child's output instead of printing it, returning it along with the result.
+    capture: bool,
+    max_instructions: Option<usize>,
+}
+
+impl EvalOptions {
+    fn from_value(options: Option<&RuntimeValue>) -> Result<Self, RuntimeError> {
+        let Some(options) = options else {
+            return Ok(Self::default());
+        };
+        let RuntimeValue::Map(map) = options else {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "eval options must be a map, got '{}'",
+                options.kind_str()
+            )));
+        };
+
+        let mut res = Self::default();
+        for (key, value) in map.borrow().map.iter() {
+            match (key.to_string().as_str(), value) {
+                ("input", RuntimeValue::Str(s)) => res.input = Some(s.as_str().to_string()),
+                ("capture", value) => res.capture = value.bool(),
+                ("max_instructions", RuntimeValue::Num(n)) => {
+                    res.max_instructions = Some(n.floor_int().max(0) as usize)
+                }
+                (key @ ("input" | "max_instructions"), value) => {
+                    return Err(RuntimeError::TypeMismatch(format!(
+                        "Invalid value of type '{}' for eval option '{key}'",
+                        value.kind_str()
+                    )))
+                }
+                (key, _) => {
+                    return Err(RuntimeError::Plain(format!(
+                        "Unknown eval option '{key}', expected 'input', 'capture' or \
+                         'max_instructions'"
+                    )))
+                }
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+impl<I: Read, O: Write, E: Write> BytecodeInterpreter<I, O, E> {
+    /// Runs `eval(code, options?)`, returning the value of the code, or a tuple of the value and
+    /// what the code printed if its output is captured.
+    pub(super) fn eval(&mut self, args: Vec<RuntimeValue>) -> Result<RuntimeValue, RuntimeError> {
+        let RuntimeValue::Str(code) = &args[0] else {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "eval expects a string of code, got '{}'",
+                args[0].kind_str()
+            )));
+        };
+        let options = EvalOptions::from_value(args.get(1))?;
+
+        let src = code.as_str();
+        let tokens = match lexer::lexer().parse(src).into_output_errors() {
+            (Some(tokens), e) if e.is_empty() => tokens,
+            (_, e) => return Err(eval_error("Failed to parse code", &e[0])),
+        };
+        let ast =
+            parse_tokens(src, &tokens).map_err(|e| eval_error("Failed to parse code", &e[0]))?;
+        let program = Compiler::default()
+            .with_sandbox(self.sandbox)
+            .compile(&ast)
+            .map_err(|err| eval_error("Failed to compile code", err.msg()))?;
+
+        // The child can't run for longer than the parent has left
+        let remaining = self
+            .limits
+            .instructions
+            .map(|limit| limit.saturating_sub(self.instructions_executed));
+        let limit = match (remaining, options.max_instructions) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        // The handles are trait objects so the child of a child has the same type as the child,
+        // which would otherwise be instantiated without end
+        let mut output = Vec::new();
+        let stdin: Box<dyn Read + '_> = match &options.input {
+            Some(input) => Box::new(Cursor::new(input.as_bytes())),
+            None => Box::new(&mut self.stdin),
+        };
+        let stdout: &mut dyn Write = if options.capture {
+            &mut output
+        } else {
+            &mut self.stdout
+        };
+        let stderr: &mut dyn Write = &mut self.stderr;
+
+        let config = Config {
+            memo_cache_size: self.memo_cache_size,
+            limits: RunLimits {
+                instructions: limit,
+                ..self.limit_tracker.remaining()
+            },
+            args: self.args.clone(),
+            sandbox: self.sandbox,
+            ..Config::default()
+        };
+        let mut child = BytecodeInterpreter::new(program)
+            .with_config(&config)
+            .with_handles(stdin, stdout, stderr);
+
+        let result = child.run();
+        let executed = child.instructions_executed;
+        let value = child.pop_result().unwrap_or(RuntimeValue::Null);
+        drop(child);
+
+        self.instructions_executed += executed;
+        match result {
+            Ok(()) => {}
+            // Exiting from eval'd code exits the whole program
+            Err((_, err @ RuntimeError::Exit(_))) => return Err(err),
+            Err((_, err)) => return Err(eval_error("Error in eval'd code", err)),
+        }
+
+        if contains_function(&value) {
+            return Err(RuntimeError::Plain(
+                "eval can't return functions, since they only exist in the eval'd code".to_string(),
+            ));
+        }
+
+        if options.capture {
+            let output = String::from_utf8_lossy(&output);
+            Ok(RuntimeValue::from((
+                value,
+                RuntimeValue::Str(RuntimeString::new(output)),
+            )))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+fn eval_error(what: &str, err: impl ToString) -> RuntimeError {
+    RuntimeError::Plain(format!("{what}: {}", err.to_string()))
+}
+
+fn contains_function(value: &RuntimeValue) -> bool {
+    match value {
+        RuntimeValue::Function(_) => true,
+        RuntimeValue::List(_)
+        | RuntimeValue::Tuple(_)
+        | RuntimeValue::Set(_)
+        | RuntimeValue::Map(_)
+        | RuntimeValue::Deque(_)
+        | RuntimeValue::Grid(_) => value
+            .to_iter_inner()
+            .is_ok_and(|items| items.to_vec().iter().any(contains_function)),
+        _ => false,
+    }
+}
@@ -0,0 +1,170 @@
+//! Builtins that reach outside the program into the process running it, which are the ones the
+//! [`Sandbox`] in the config can take away.
+
+use crate::{
+    config::Sandbox,
+    vm::{
+        runtime_value::{
+            list::RuntimeList, map::RuntimeMap, number::RuntimeNumber, string::RuntimeString,
+            RuntimeValue,
+        },
+        stdlib::RuntimeResult,
+        RuntimeError,
+    },
+};
+
+/// The arguments given after the program file on the command line.
+pub fn args(args: &[String]) -> RuntimeValue {
+    let args = args
+        .iter()
+        .map(|arg| RuntimeValue::Str(RuntimeString::new(arg.as_str())))
+        .collect();
+    RuntimeValue::List(RuntimeList::from_vec(args))
+}
+
+/// The value of an environment variable, or null if it isn't set.
+pub fn env(sandbox: &Sandbox, name: RuntimeValue) -> RuntimeResult {
+    let RuntimeValue::Str(name) = name else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "env expects a variable name, got '{}'",
+            name.kind_str()
+        )));
+    };
+
+    if !sandbox.env {
+        return Err(RuntimeError::Plain(format!(
+            "Cannot read environment variable {:?}, as the sandbox config disallows it",
+            name.as_str()
+        )));
+    }
+
+    Ok(match std::env::var_os(name.as_str()) {
+        Some(val) => RuntimeValue::Str(RuntimeString::new(val.to_string_lossy())),
+        None => RuntimeValue::Null,
+    })
+}
+
+/// The code to exit the process with when the program calls `exit`, which defaults to success.
+pub fn exit_code(code: Option<RuntimeValue>) -> Result<i32, RuntimeError> {
+    match code {
+        None => Ok(crate::EXIT_SUCCESS),
+        Some(RuntimeValue::Num(RuntimeNumber::SmallInt(n))) if i32::try_from(n).is_ok() => {
+            Ok(n as i32)
+        }
+        Some(other) => Err(RuntimeError::TypeMismatch(format!(
+            "exit expects an integer exit code, got {}",
+            other.repr_string()
+        ))),
+    }
+}
+
+/// The body of the response to a GET request, e.g. for downloading puzzle inputs. The optional
+/// map of headers is sent along, which is where a session cookie goes.
+pub fn fetch(sandbox: &Sandbox, args: Vec<RuntimeValue>) -> RuntimeResult {
+    match args.as_slice() {
+        [url] => get(sandbox, "fetch", url, None),
+        [url, headers] => get(sandbox, "fetch", url, Some(headers)),
+        _ => unreachable!("fetch called with {} arguments", args.len()),
+    }
+}
+
+/// Like [`fetch`], with the map of headers required.
+pub fn http_get(sandbox: &Sandbox, url: RuntimeValue, headers: RuntimeValue) -> RuntimeResult {
+    get(sandbox, "http_get", &url, Some(&headers))
+}
+
+fn get(
+    sandbox: &Sandbox,
+    name: &str,
+    url: &RuntimeValue,
+    headers: Option<&RuntimeValue>,
+) -> RuntimeResult {
+    let RuntimeValue::Str(url) = url else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "{name} expects a URL, got '{}'",
+            url.kind_str()
+        )));
+    };
+    let url = url.as_str().to_string();
+    let headers = match headers {
+        None => Vec::new(),
+        Some(RuntimeValue::Map(headers)) => request_headers(name, headers)?,
+        Some(other) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "{name} expects the headers to be a map, got '{}'",
+                other.kind_str()
+            )))
+        }
+    };
+
+    if !sandbox.network {
+        return Err(RuntimeError::Plain(format!(
+            "Cannot fetch {url:?}, as the sandbox config disallows network access"
+        )));
+    }
+
+    let body = download(&url, &headers)?;
+    Ok(RuntimeValue::Str(RuntimeString::new(body)))
+}
+
+// Each header becomes a `name: value` line of the request, so one containing a line break could add
+// lines of its own to it
+fn request_headers(
+    name: &str,
+    headers: &RuntimeMap,
+) -> Result<Vec<(String, String)>, RuntimeError> {
+    headers
+        .borrow()
+        .iter()
+        .map(|(header, value)| {
+            let (RuntimeValue::Str(header), RuntimeValue::Str(value)) = (header, value) else {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "{name} expects the names and values of headers to be strings, got '{}' and '{}'",
+                    header.kind_str(),
+                    value.kind_str()
+                )));
+            };
+            let (header, value) = (header.as_str(), value.as_str());
+            if header.contains(['\r', '\n']) || value.contains(['\r', '\n']) {
+                return Err(RuntimeError::Plain(format!(
+                    "{name} can't send the header {header:?}, as it contains a line break"
+                )));
+            }
+            Ok((header.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+// Requests go through curl rather than an HTTP crate, since it handles HTTPS and redirects
+// without pulling a TLS stack into the build
+#[cfg(feature = "fetch")]
+fn download(url: &str, headers: &[(String, String)]) -> Result<String, RuntimeError> {
+    let mut cmd = std::process::Command::new("curl");
+    cmd.args(["--silent", "--show-error", "--fail", "--location"]);
+    for (name, value) in headers {
+        cmd.arg("--header").arg(format!("{name}: {value}"));
+    }
+
+    let output = cmd.arg("--").arg(url).output().map_err(|err| {
+        RuntimeError::Plain(format!(
+            "Cannot fetch {url:?}, as curl could not be run: {err}"
+        ))
+    })?;
+
+    if !output.status.success() {
+        return Err(RuntimeError::Plain(format!(
+            "Failed to fetch {url:?}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|_| RuntimeError::Plain(format!("The response from {url:?} is not valid UTF-8")))
+}
+
+#[cfg(not(feature = "fetch"))]
+fn download(url: &str, _headers: &[(String, String)]) -> Result<String, RuntimeError> {
+    Err(RuntimeError::Plain(format!(
+        "Cannot fetch {url:?}, as linefeed was built without the \"fetch\" feature"
+    )))
+}
@@ -0,0 +1,776 @@
+//! Compilation of hot functions to native code with Cranelift.
+//!
+//! The VM counts the calls of each function, and once a function has been called [`HOT_CALLS`]
+//! times, it is compiled if it only works with numbers and booleans in its own locals: no calls,
+//! globals, closures, strings or collections, though loops over ranges are fine. Such a function
+//! can't change anything outside of itself, so its native code can give up at any point, such as
+//! on a value it doesn't handle or an integer overflowing into a big integer, and leave the call to
+//! the interpreter, which starts it over from the beginning.
+
+use cranelift_codegen::{
+    entity::EntityRef,
+    ir::{condcodes::IntCC, types::I64, AbiParam, Block, InstBuilder, MemFlags, Value},
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+use rustc_hash::FxHashMap;
+
+use crate::{
+    compiler::Program,
+    vm::{
+        bytecode::Bytecode,
+        runtime_value::{number::RuntimeNumber, RuntimeValue},
+    },
+};
+
+/// How many times a function is called by the interpreter before it is compiled.
+pub const HOT_CALLS: u32 = 1000;
+
+// The tags of the values native code works with, which are kept as a tag and two payloads. Only
+// ranges and iterators use the second payload, for their end.
+const NULL: i64 = 0;
+const UNINIT: i64 = 1;
+const BOOL: i64 = 2;
+const NUM: i64 = 3;
+const INT: i64 = 4;
+const RANGE: i64 = 5;
+const ITERATOR: i64 = 6;
+
+// Native code is given the tags and payloads of the arguments, interleaved, and the absolute base
+// pointer of the call. If it finishes, the tag and payload of the result are written over the
+// first argument.
+type NativeFn = unsafe extern "C" fn(*mut i64, i64) -> i64;
+
+const FINISHED: i64 = 0;
+const GAVE_UP: i64 = 1;
+
+enum State {
+    Counting(u32),
+    Compiled(NativeFn),
+    Unsupported,
+}
+
+/// The native code of the functions compiled so far, and the call counts of the rest.
+#[derive(Default)]
+pub struct Jit {
+    // Created on the first compilation, so programs without hot functions don't pay for it
+    module: Option<JITModule>,
+    functions: FxHashMap<usize, State>,
+}
+
+impl Jit {
+    /// Counts a call of the function at `location` with `args`, and runs it with native code if
+    /// the function is hot and could be compiled. Returns `None` if the interpreter should run
+    /// the call instead.
+    pub fn call(
+        &mut self,
+        program: &Program<Bytecode>,
+        location: usize,
+        args: &[RuntimeValue],
+        bp: usize,
+    ) -> Option<RuntimeValue> {
+        let calls = match self.functions.entry(location).or_insert(State::Counting(0)) {
+            State::Counting(calls) => {
+                *calls += 1;
+                *calls
+            }
+            State::Compiled(native) => return run(*native, args, bp),
+            State::Unsupported => return None,
+        };
+        if calls < HOT_CALLS {
+            return None;
+        }
+
+        match self.compile(program, location, args.len()) {
+            Some(native) => {
+                self.functions.insert(location, State::Compiled(native));
+                run(native, args, bp)
+            }
+            None => {
+                self.functions.insert(location, State::Unsupported);
+                None
+            }
+        }
+    }
+
+    pub fn compiled_functions(&self) -> usize {
+        self.functions
+            .values()
+            .filter(|state| matches!(state, State::Compiled(_)))
+            .count()
+    }
+
+    fn compile(
+        &mut self,
+        program: &Program<Bytecode>,
+        location: usize,
+        arity: usize,
+    ) -> Option<NativeFn> {
+        let states = analyze(program, location, arity)?;
+
+        if self.module.is_none() {
+            self.module = Some(new_module()?);
+        }
+        let module = self.module.as_mut()?;
+
+        let mut ctx = module.make_context();
+        let pointer_type = module.target_config().pointer_type();
+        ctx.func.signature.params.push(AbiParam::new(pointer_type));
+        ctx.func.signature.params.push(AbiParam::new(I64));
+        ctx.func.signature.returns.push(AbiParam::new(I64));
+
+        let mut builder_ctx = FunctionBuilderContext::new();
+        let builder = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+        Codegen::new(builder, program, &states).emit(location, arity);
+
+        let id = module
+            .declare_anonymous_function(&ctx.func.signature)
+            .ok()?;
+        module.define_function(id, &mut ctx).ok()?;
+        module.clear_context(&mut ctx);
+        module.finalize_definitions().ok()?;
+
+        let code = module.get_finalized_function(id);
+        // SAFETY: the function was compiled with the signature of `NativeFn`
+        Some(unsafe { std::mem::transmute::<*const u8, NativeFn>(code) })
+    }
+}
+
+fn new_module() -> Option<JITModule> {
+    let mut flags = settings::builder();
+    flags.set("use_colocated_libcalls", "false").ok()?;
+    flags.set("is_pic", "false").ok()?;
+    let isa = cranelift_native::builder()
+        .ok()?
+        .finish(settings::Flags::new(flags))
+        .ok()?;
+
+    Some(JITModule::new(JITBuilder::with_isa(
+        isa,
+        default_libcall_names(),
+    )))
+}
+
+fn run(native: NativeFn, args: &[RuntimeValue], bp: usize) -> Option<RuntimeValue> {
+    let mut slots = Vec::with_capacity(2 * args.len() + 2);
+    for arg in args {
+        let (tag, payload) = to_slot(arg)?;
+        slots.extend([tag, payload]);
+    }
+    // There is always room for the result, even without arguments
+    slots.resize(slots.len().max(2), 0);
+
+    // SAFETY: the function was compiled for this many arguments, and only writes the result
+    let status = unsafe { native(slots.as_mut_ptr(), bp as i64) };
+    (status == FINISHED).then(|| from_slot(slots[0], slots[1]))
+}
+
+fn to_slot(val: &RuntimeValue) -> Option<(i64, i64)> {
+    let slot = match val {
+        RuntimeValue::Null => (NULL, 0),
+        RuntimeValue::Uninit => (UNINIT, 0),
+        RuntimeValue::Bool(b) => (BOOL, *b as i64),
+        RuntimeValue::Num(RuntimeNumber::SmallInt(i)) => (NUM, *i as i64),
+        RuntimeValue::Int(i) => (INT, *i as i64),
+        _ => return None,
+    };
+
+    Some(slot)
+}
+
+fn from_slot(tag: i64, payload: i64) -> RuntimeValue {
+    match tag {
+        NULL => RuntimeValue::Null,
+        UNINIT => RuntimeValue::Uninit,
+        BOOL => RuntimeValue::Bool(payload != 0),
+        NUM => RuntimeValue::Num(RuntimeNumber::SmallInt(payload as isize)),
+        _ => RuntimeValue::Int(payload as isize),
+    }
+}
+
+/// The stack before an instruction: how many values the function has on it, and the base
+/// pointers of the function and the functions inlined into it, innermost last, all relative to
+/// the function's base pointer.
+#[derive(Debug, Clone, PartialEq)]
+struct StackState {
+    depth: usize,
+    bases: Vec<usize>,
+}
+
+// Finds the instructions of the function at `location` and the stack before each of them, which
+// must be the same however the instruction is reached. Returns `None` if the function can't be
+// compiled.
+fn analyze(
+    program: &Program<Bytecode>,
+    location: usize,
+    arity: usize,
+) -> Option<Vec<Option<StackState>>> {
+    let mut states = vec![None; program.instructions.len()];
+    *states.get_mut(location)? = Some(StackState {
+        depth: arity,
+        bases: vec![0],
+    });
+    let mut work = vec![location];
+    // The `Goto`s after `SetStackPtr`, from `break` and `continue`
+    let mut breaks = Vec::new();
+
+    loop {
+        while let Some(pc) = work.pop() {
+            let state = states[pc].clone()?;
+            if let Bytecode::SetStackPtr = program.instructions[pc] {
+                breaks.push(pc + 1);
+            }
+
+            for (succ, next) in successors(program, pc, &state)? {
+                match states.get(succ)? {
+                    Some(existing) if *existing == next => {}
+                    Some(_) => return None,
+                    None => {
+                        states[succ] = Some(next);
+                        work.push(succ);
+                    }
+                }
+            }
+        }
+
+        // `break` and `continue` reset the stack to how it is where they jump to, which is known
+        // once some other way there has been followed
+        let mut resolved = false;
+        for &goto in &breaks {
+            if let (None, Some(Bytecode::Goto(target))) =
+                (&states[goto], program.instructions.get(goto))
+            {
+                if let Some(state) = states[*target].clone() {
+                    states[goto] = Some(state);
+                    work.push(goto);
+                    resolved = true;
+                }
+            }
+        }
+        if !resolved {
+            break;
+        }
+    }
+
+    for &goto in &breaks {
+        let (before, after) = (states[goto - 1].as_ref()?, states[goto].as_ref()?);
+        if after.depth >= before.depth || after.bases != before.bases {
+            return None;
+        }
+    }
+
+    Some(states)
+}
+
+fn successors(
+    program: &Program<Bytecode>,
+    pc: usize,
+    state: &StackState,
+) -> Option<Vec<(usize, StackState)>> {
+    let depth = state.depth;
+    let base = *state.bases.last()?;
+    let with_depth = |depth| StackState {
+        depth,
+        bases: state.bases.clone(),
+    };
+    // The depth left after popping `n` values, if there are that many
+    let pop = |n: usize| depth.checked_sub(n);
+
+    let next_depth = match program.instructions.get(pc)? {
+        Bytecode::LoadLocal(offset) => (base + offset < depth).then_some(depth + 1)?,
+        Bytecode::StoreLocal(offset) => (base + offset < depth).then_some(depth)?,
+        Bytecode::PopToLocal(offset) => pop(1).filter(|&depth| base + offset < depth)?,
+        Bytecode::LoadConst(index) | Bytecode::CopyConst(index) => {
+            to_slot(program.constants.get(*index as usize)?)?;
+            depth + 1
+        }
+        Bytecode::ConstantInt(_) | Bytecode::GetStackPtr | Bytecode::GetBasePtr => depth + 1,
+        Bytecode::Pop => pop(1)?,
+        Bytecode::Dup => pop(1)? + 2,
+        Bytecode::Swap => pop(2)? + 2,
+        Bytecode::SwapPop
+        | Bytecode::Add
+        | Bytecode::Sub
+        | Bytecode::Mul
+        | Bytecode::DivFloor
+        | Bytecode::Mod
+        | Bytecode::Eq
+        | Bytecode::NotEq
+        | Bytecode::Less
+        | Bytecode::LessEq
+        | Bytecode::Greater
+        | Bytecode::GreaterEq
+        | Bytecode::Range
+        | Bytecode::Xor
+        | Bytecode::BitwiseAnd
+        | Bytecode::BitwiseOr
+        | Bytecode::BitwiseXor => pop(2)? + 1,
+        Bytecode::Not | Bytecode::Neg | Bytecode::BitwiseNot | Bytecode::ToIter => pop(1)? + 1,
+
+        Bytecode::Goto(target) => return Some(vec![(*target, with_depth(depth))]),
+        Bytecode::IfTrue(target) | Bytecode::IfFalse(target) => {
+            let depth = pop(1)?;
+            return Some(vec![
+                (*target, with_depth(depth)),
+                (pc + 1, with_depth(depth)),
+            ]);
+        }
+        Bytecode::NextIterOrJump(target) => {
+            iterated_local(program, pc)?;
+            let depth = pop(1)?;
+            return Some(vec![
+                (*target, with_depth(depth)),
+                (pc + 1, with_depth(depth + 1)),
+            ]);
+        }
+        // The stack after is found from the target of the `Goto`, see `analyze`
+        Bytecode::SetStackPtr => {
+            pop(1)?;
+            return matches!(program.instructions.get(pc + 1), Some(Bytecode::Goto(_)))
+                .then(Vec::new);
+        }
+
+        Bytecode::EnterInlined(num_args) => {
+            let func_index = pop(num_args + 1)?;
+            let mut bases = state.bases.clone();
+            bases.push(func_index + 1);
+            return Some(vec![(pc + 1, StackState { depth, bases })]);
+        }
+        Bytecode::ExitInlined => {
+            pop(1)?;
+            let mut bases = state.bases.clone();
+            bases.pop();
+            if bases.is_empty() {
+                return None;
+            }
+            // Only the return value is left, in the inlined function's slot
+            return Some(vec![(pc + 1, StackState { depth: base, bases })]);
+        }
+        Bytecode::Return => {
+            pop(1)?;
+            return (state.bases.len() == 1).then(Vec::new);
+        }
+
+        _ => return None,
+    };
+
+    Some(vec![(pc + 1, with_depth(next_depth))])
+}
+
+// The for loop loads its iterator from a local right before advancing it. The interpreter
+// advances the iterator shared by both copies, so native code advances the local instead.
+fn iterated_local(program: &Program<Bytecode>, pc: usize) -> Option<usize> {
+    match program.instructions.get(pc.checked_sub(1)?)? {
+        Bytecode::LoadLocal(offset) => Some(*offset),
+        _ => None,
+    }
+}
+
+struct Codegen<'a, 'f> {
+    b: FunctionBuilder<'f>,
+    program: &'a Program<Bytecode>,
+    states: &'a [Option<StackState>],
+    blocks: FxHashMap<usize, Block>,
+    give_up: Block,
+    args: Value,
+    bp: Value,
+}
+
+impl<'a, 'f> Codegen<'a, 'f> {
+    fn new(
+        mut b: FunctionBuilder<'f>,
+        program: &'a Program<Bytecode>,
+        states: &'a [Option<StackState>],
+    ) -> Self {
+        let entry = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        let (args, bp) = (b.block_params(entry)[0], b.block_params(entry)[1]);
+
+        let blocks = states
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| state.is_some())
+            .map(|(pc, _)| (pc, b.create_block()))
+            .collect();
+        let give_up = b.create_block();
+
+        Self {
+            b,
+            program,
+            states,
+            blocks,
+            give_up,
+            args,
+            bp,
+        }
+    }
+
+    fn emit(mut self, location: usize, arity: usize) {
+        // Every slot gets a tag and two payloads
+        let num_slots = self
+            .states
+            .iter()
+            .flatten()
+            .map(|state| state.depth + 1)
+            .max()
+            .unwrap_or(0);
+        let zero = self.b.ins().iconst(I64, 0);
+        for slot in 0..num_slots {
+            for component in 0..3 {
+                let var = var(slot, component);
+                self.b.declare_var(var, I64);
+                self.b.def_var(var, zero);
+            }
+        }
+
+        for arg in 0..arity {
+            let offset = 16 * arg as i32;
+            let tag = self
+                .b
+                .ins()
+                .load(I64, MemFlags::trusted(), self.args, offset);
+            let payload = self
+                .b
+                .ins()
+                .load(I64, MemFlags::trusted(), self.args, offset + 8);
+            self.b.def_var(var(arg, 0), tag);
+            self.b.def_var(var(arg, 1), payload);
+        }
+        self.jump_to(location);
+
+        for (pc, state) in self.states.iter().enumerate() {
+            if let Some(state) = state {
+                self.b.switch_to_block(self.blocks[&pc]);
+                self.instruction(pc, state);
+            }
+        }
+
+        self.b.switch_to_block(self.give_up);
+        let gave_up = self.b.ins().iconst(I64, GAVE_UP);
+        self.b.ins().return_(&[gave_up]);
+
+        self.b.seal_all_blocks();
+        self.b.finalize();
+    }
+
+    fn instruction(&mut self, pc: usize, state: &StackState) {
+        let depth = state.depth;
+        let base = *state.bases.last().unwrap();
+        let next = pc + 1;
+        // The operands of unary and binary operations
+        let top = depth.wrapping_sub(1);
+        let (lhs, rhs) = (depth.wrapping_sub(2), top);
+
+        match &self.program.instructions[pc] {
+            Bytecode::LoadLocal(offset) => self.copy(base + offset, depth),
+            Bytecode::StoreLocal(offset) | Bytecode::PopToLocal(offset) => {
+                self.copy(top, base + offset)
+            }
+            Bytecode::LoadConst(index) | Bytecode::CopyConst(index) => {
+                let (tag, payload) = to_slot(&self.program.constants[*index as usize]).unwrap();
+                let payload = self.b.ins().iconst(I64, payload);
+                self.set(depth, tag, payload);
+            }
+            Bytecode::ConstantInt(i) => {
+                let payload = self.b.ins().iconst(I64, *i as i64);
+                self.set(depth, INT, payload);
+            }
+
+            Bytecode::Pop => {}
+            Bytecode::Dup => self.copy(top, depth),
+            Bytecode::Swap => {
+                let (a, b) = (self.get(lhs), self.get(rhs));
+                self.set_all(lhs, b);
+                self.set_all(rhs, a);
+            }
+            Bytecode::SwapPop => self.copy(top, lhs),
+
+            Bytecode::Add | Bytecode::Sub => {
+                let is_add = matches!(self.program.instructions[pc], Bytecode::Add);
+                let [_, a, _] = self.get(lhs);
+                let [_, b, _] = self.get(rhs);
+                let (nums_block, other_block) = (self.b.create_block(), self.b.create_block());
+                let nums = self.both_tags(lhs, rhs, NUM);
+                self.b.ins().brif(nums, nums_block, &[], other_block, &[]);
+
+                self.b.switch_to_block(nums_block);
+                let (result, overflowed) = if is_add {
+                    self.b.ins().sadd_overflow(a, b)
+                } else {
+                    self.b.ins().ssub_overflow(a, b)
+                };
+                self.give_up_if(overflowed);
+                self.set(lhs, NUM, result);
+                self.jump_to(next);
+
+                // Addresses, such as stack pointers, are plain integers
+                self.b.switch_to_block(other_block);
+                let ints = self.both_tags(lhs, rhs, INT);
+                self.give_up_unless(ints);
+                let result = if is_add {
+                    self.b.ins().iadd(a, b)
+                } else {
+                    self.b.ins().isub(a, b)
+                };
+                self.set(lhs, INT, result);
+                return self.jump_to(next);
+            }
+            Bytecode::Mul => {
+                let (a, b) = self.num_operands(lhs, rhs);
+                let (result, overflowed) = self.b.ins().smul_overflow(a, b);
+                self.give_up_if(overflowed);
+                self.set(lhs, NUM, result);
+            }
+            Bytecode::DivFloor | Bytecode::Mod => {
+                let (a, b) = self.num_operands(lhs, rhs);
+                let by_zero = self.b.ins().icmp_imm(IntCC::Equal, b, 0);
+                self.give_up_if(by_zero);
+                let a_is_min = self.b.ins().icmp_imm(IntCC::Equal, a, i64::MIN);
+                let b_is_minus_one = self.b.ins().icmp_imm(IntCC::Equal, b, -1);
+                let overflows = self.b.ins().band(a_is_min, b_is_minus_one);
+                self.give_up_if(overflows);
+                let result = match self.program.instructions[pc] {
+                    Bytecode::DivFloor => self.b.ins().sdiv(a, b),
+                    _ => self.b.ins().srem(a, b),
+                };
+                self.set(lhs, NUM, result);
+            }
+            Bytecode::BitwiseAnd | Bytecode::BitwiseOr | Bytecode::BitwiseXor => {
+                let (a, b) = self.num_operands(lhs, rhs);
+                let result = match self.program.instructions[pc] {
+                    Bytecode::BitwiseAnd => self.b.ins().band(a, b),
+                    Bytecode::BitwiseOr => self.b.ins().bor(a, b),
+                    _ => self.b.ins().bxor(a, b),
+                };
+                self.set(lhs, NUM, result);
+            }
+            Bytecode::Less | Bytecode::LessEq | Bytecode::Greater | Bytecode::GreaterEq => {
+                let cc = match self.program.instructions[pc] {
+                    Bytecode::Less => IntCC::SignedLessThan,
+                    Bytecode::LessEq => IntCC::SignedLessThanOrEqual,
+                    Bytecode::Greater => IntCC::SignedGreaterThan,
+                    _ => IntCC::SignedGreaterThanOrEqual,
+                };
+                let (a, b) = self.num_operands(lhs, rhs);
+                let result = self.b.ins().icmp(cc, a, b);
+                self.set_bool(lhs, result);
+            }
+            Bytecode::Eq | Bytecode::NotEq => {
+                // Values of different types are never equal, but ranges and iterators aren't
+                // compared here
+                let [a_tag, a, _] = self.get(lhs);
+                let [b_tag, b, _] = self.get(rhs);
+                let a_simple = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, a_tag, RANGE);
+                let b_simple = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, b_tag, RANGE);
+                let simple = self.b.ins().band(a_simple, b_simple);
+                self.give_up_unless(simple);
+                let same_tag = self.b.ins().icmp(IntCC::Equal, a_tag, b_tag);
+                let same_payload = self.b.ins().icmp(IntCC::Equal, a, b);
+                let mut result = self.b.ins().band(same_tag, same_payload);
+                if let Bytecode::NotEq = self.program.instructions[pc] {
+                    result = self.b.ins().bxor_imm(result, 1);
+                }
+                self.set_bool(lhs, result);
+            }
+            Bytecode::Range => {
+                let (start, end) = self.num_operands(lhs, rhs);
+                let tag = self.b.ins().iconst(I64, RANGE);
+                self.set_all(lhs, [tag, start, end]);
+            }
+            Bytecode::Xor => {
+                let bools = self.both_tags(lhs, rhs, BOOL);
+                self.give_up_unless(bools);
+                let [_, a, _] = self.get(lhs);
+                let [_, b, _] = self.get(rhs);
+                let result = self.b.ins().bxor(a, b);
+                self.set(lhs, BOOL, result);
+            }
+
+            Bytecode::Not => {
+                let truthy = self.truthy(top);
+                let result = self.b.ins().bxor_imm(truthy, 1);
+                self.set_bool(top, result);
+            }
+            Bytecode::Neg | Bytecode::BitwiseNot => {
+                let is_num = self.has_tag(top, NUM);
+                self.give_up_unless(is_num);
+                let [_, a, _] = self.get(top);
+                let result = match self.program.instructions[pc] {
+                    Bytecode::Neg => {
+                        let is_min = self.b.ins().icmp_imm(IntCC::Equal, a, i64::MIN);
+                        self.give_up_if(is_min);
+                        self.b.ins().ineg(a)
+                    }
+                    _ => self.b.ins().bnot(a),
+                };
+                self.set(top, NUM, result);
+            }
+            Bytecode::ToIter => {
+                let [tag, start, end] = self.get(top);
+                let is_range = self.b.ins().icmp_imm(IntCC::Equal, tag, RANGE);
+                let is_iter = self.b.ins().icmp_imm(IntCC::Equal, tag, ITERATOR);
+                let iterable = self.b.ins().bor(is_range, is_iter);
+                self.give_up_unless(iterable);
+                let tag = self.b.ins().iconst(I64, ITERATOR);
+                self.set_all(top, [tag, start, end]);
+            }
+
+            Bytecode::Goto(target) => return self.jump_to(*target),
+            Bytecode::IfTrue(target) | Bytecode::IfFalse(target) => {
+                let truthy = self.truthy(top);
+                let (jump, fall_through) = (self.blocks[target], self.blocks[&next]);
+                match self.program.instructions[pc] {
+                    Bytecode::IfTrue(_) => self.b.ins().brif(truthy, jump, &[], fall_through, &[]),
+                    _ => self.b.ins().brif(truthy, fall_through, &[], jump, &[]),
+                };
+                return;
+            }
+            Bytecode::NextIterOrJump(target) => {
+                // Ranges count towards their end, which is excluded
+                let iterator = base + iterated_local(self.program, pc).unwrap();
+                let is_iter = self.has_tag(iterator, ITERATOR);
+                self.give_up_unless(is_iter);
+                let [_, cur, end] = self.get(iterator);
+                let done = self.b.ins().icmp(IntCC::Equal, cur, end);
+                let more = self.b.create_block();
+                self.b.ins().brif(done, self.blocks[target], &[], more, &[]);
+
+                self.b.switch_to_block(more);
+                self.set(top, NUM, cur);
+                let up = self.b.ins().icmp(IntCC::SignedLessThan, cur, end);
+                let (one, minus_one) = (self.b.ins().iconst(I64, 1), self.b.ins().iconst(I64, -1));
+                let step = self.b.ins().select(up, one, minus_one);
+                let cur = self.b.ins().iadd(cur, step);
+                self.b.def_var(var(iterator, 1), cur);
+            }
+
+            Bytecode::GetStackPtr => {
+                let ptr = self.b.ins().iadd_imm(self.bp, depth as i64 - 1);
+                self.set(depth, INT, ptr);
+            }
+            Bytecode::GetBasePtr => {
+                let ptr = self.b.ins().iadd_imm(self.bp, base as i64);
+                self.set(depth, INT, ptr);
+            }
+            Bytecode::SetStackPtr => {
+                // Only the stack pointers saved by the loop being left are expected
+                let target_depth = self.states[next].as_ref().unwrap().depth;
+                let is_int = self.has_tag(top, INT);
+                self.give_up_unless(is_int);
+                let [_, ptr, _] = self.get(top);
+                let expected = self.b.ins().iadd_imm(self.bp, target_depth as i64 - 1);
+                let as_expected = self.b.ins().icmp(IntCC::Equal, ptr, expected);
+                self.give_up_unless(as_expected);
+            }
+
+            Bytecode::EnterInlined(num_args) => {
+                let bp = self.b.ins().iadd_imm(self.bp, base as i64);
+                self.set(depth - 1 - num_args, INT, bp);
+            }
+            Bytecode::ExitInlined => self.copy(top, base - 1),
+            Bytecode::Return => {
+                let [tag, payload, _] = self.get(top);
+                let returnable = self
+                    .b
+                    .ins()
+                    .icmp_imm(IntCC::UnsignedLessThanOrEqual, tag, INT);
+                self.give_up_unless(returnable);
+                self.b.ins().store(MemFlags::trusted(), tag, self.args, 0);
+                self.b
+                    .ins()
+                    .store(MemFlags::trusted(), payload, self.args, 8);
+                let finished = self.b.ins().iconst(I64, FINISHED);
+                self.b.ins().return_(&[finished]);
+                return;
+            }
+
+            instruction => unreachable!("{instruction:?} can't be compiled to native code"),
+        }
+
+        self.jump_to(next);
+    }
+
+    fn get(&mut self, slot: usize) -> [Value; 3] {
+        [0, 1, 2].map(|component| self.b.use_var(var(slot, component)))
+    }
+
+    fn set_all(&mut self, slot: usize, values: [Value; 3]) {
+        for (component, value) in values.into_iter().enumerate() {
+            self.b.def_var(var(slot, component), value);
+        }
+    }
+
+    fn set(&mut self, slot: usize, tag: i64, payload: Value) {
+        let tag = self.b.ins().iconst(I64, tag);
+        let zero = self.b.ins().iconst(I64, 0);
+        self.set_all(slot, [tag, payload, zero]);
+    }
+
+    fn set_bool(&mut self, slot: usize, condition: Value) {
+        let payload = self.b.ins().uextend(I64, condition);
+        self.set(slot, BOOL, payload);
+    }
+
+    fn copy(&mut self, from: usize, to: usize) {
+        let values = self.get(from);
+        self.set_all(to, values);
+    }
+
+    fn has_tag(&mut self, slot: usize, tag: i64) -> Value {
+        let actual = self.b.use_var(var(slot, 0));
+        self.b.ins().icmp_imm(IntCC::Equal, actual, tag)
+    }
+
+    fn both_tags(&mut self, a: usize, b: usize, tag: i64) -> Value {
+        let (a, b) = (self.has_tag(a, tag), self.has_tag(b, tag));
+        self.b.ins().band(a, b)
+    }
+
+    fn num_operands(&mut self, lhs: usize, rhs: usize) -> (Value, Value) {
+        let nums = self.both_tags(lhs, rhs, NUM);
+        self.give_up_unless(nums);
+        let [_, a, _] = self.get(lhs);
+        let [_, b, _] = self.get(rhs);
+        (a, b)
+    }
+
+    // Null and uninitialized are falsy, booleans and numbers are truthy unless zero, and ranges
+    // and iterators are always truthy
+    fn truthy(&mut self, slot: usize) -> Value {
+        let [tag, payload, _] = self.get(slot);
+        let has_payload = self
+            .b
+            .ins()
+            .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, tag, BOOL);
+        let nonzero = self.b.ins().icmp_imm(IntCC::NotEqual, payload, 0);
+        let always = self
+            .b
+            .ins()
+            .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, tag, RANGE);
+        let truthy_payload = self.b.ins().bor(nonzero, always);
+        self.b.ins().band(has_payload, truthy_payload)
+    }
+
+    fn give_up_if(&mut self, condition: Value) {
+        let ok = self.b.create_block();
+        self.b.ins().brif(condition, self.give_up, &[], ok, &[]);
+        self.b.switch_to_block(ok);
+    }
+
+    fn give_up_unless(&mut self, condition: Value) {
+        let ok = self.b.create_block();
+        self.b.ins().brif(condition, ok, &[], self.give_up, &[]);
+        self.b.switch_to_block(ok);
+    }
+
+    fn jump_to(&mut self, pc: usize) {
+        self.b.ins().jump(self.blocks[&pc], &[]);
+    }
+}
+
+fn var(slot: usize, component: usize) -> Variable {
+    Variable::new(3 * slot + component)
+}
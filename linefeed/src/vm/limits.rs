@@ -0,0 +1,163 @@
+//! Limits on how long a run may take and how much it may use, which stop runaway programs, such
+//! as an accidental infinite loop, with an error instead of hanging.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{alloc_stats, vm::RuntimeError};
+
+/// How many instructions are executed between checks of the clock and the heap, which are too
+/// slow to check after every instruction.
+pub const CHECK_INTERVAL: usize = 1 << 12;
+
+/// What a run may use before it is stopped. Everything is unlimited by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunLimits {
+    pub instructions: Option<usize>,
+    /// Wall-clock time, counted from when the run starts.
+    pub time: Option<Duration>,
+    /// Bytes of heap memory the run may take on top of what its thread had in use when it started.
+    /// Memory is only measured when [`alloc_stats::CountingAllocator`] is the global allocator.
+    pub memory: Option<usize>,
+}
+
+impl RunLimits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The limits of a run in progress.
+#[derive(Debug, Clone, Copy)]
+pub struct LimitTracker {
+    limits: RunLimits,
+    deadline: Option<Instant>,
+    memory_base: isize,
+}
+
+impl LimitTracker {
+    pub fn start(limits: RunLimits) -> Self {
+        Self {
+            limits,
+            // A deadline too far ahead to represent is never reached
+            deadline: limits
+                .time
+                .and_then(|time| Instant::now().checked_add(time)),
+            memory_base: alloc_stats::allocated_bytes(),
+        }
+    }
+
+    /// Fails if the run has gone past its deadline or memory limit.
+    pub fn check(&self) -> Result<(), RuntimeError> {
+        if let (Some(deadline), Some(time)) = (self.deadline, self.limits.time) {
+            if Instant::now() >= deadline {
+                return Err(RuntimeError::Plain(format!(
+                    "Time limit of {time:?} exceeded"
+                )));
+            }
+        }
+
+        if let Some(memory) = self.limits.memory {
+            if self.memory_used() > memory {
+                return Err(RuntimeError::Plain(format!(
+                    "Memory limit of {} exceeded",
+                    ByteSize(memory)
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The time and memory the run has left, as limits for a nested run. Instructions are left
+    /// out, since the nested run's instructions are counted separately.
+    pub fn remaining(&self) -> RunLimits {
+        RunLimits {
+            instructions: None,
+            time: self
+                .deadline
+                .map(|deadline| deadline.saturating_duration_since(Instant::now())),
+            memory: self
+                .limits
+                .memory
+                .map(|memory| memory.saturating_sub(self.memory_used())),
+        }
+    }
+
+    fn memory_used(&self) -> usize {
+        usize::try_from(alloc_stats::allocated_bytes() - self.memory_base).unwrap_or(0)
+    }
+}
+
+/// Parses a duration like `5s`, `500ms` or `2m`. A number without a unit is in seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (num, unit) = split_unit(s);
+    let secs_per_unit = match unit.to_lowercase().as_str() {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 3600.0,
+        _ => {
+            return Err(format!(
+                "Invalid duration '{s}', expected e.g. '500ms', '5s' or '2m'"
+            ))
+        }
+    };
+
+    match num.parse::<f64>() {
+        Ok(n) if n >= 0.0 => Duration::try_from_secs_f64(n * secs_per_unit)
+            .map_err(|_| format!("Duration '{s}' is too long")),
+        _ => Err(format!(
+            "Invalid duration '{s}', expected e.g. '500ms', '5s' or '2m'"
+        )),
+    }
+}
+
+/// Parses a number of bytes like `512MB`, `2G` or `100k`. The units are powers of 1024, and a
+/// number without a unit is in bytes.
+pub fn parse_byte_size(s: &str) -> Result<usize, String> {
+    let (num, unit) = split_unit(s);
+    let unit = unit.to_lowercase();
+    let power = match unit.trim_end_matches('b') {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        _ => return Err(format!("Invalid size '{s}', expected e.g. '64MB' or '2GB'")),
+    };
+
+    match num.parse::<f64>() {
+        Ok(n) if n >= 0.0 && n.is_finite() => Ok((n * 1024f64.powi(power)) as usize),
+        _ => Err(format!("Invalid size '{s}', expected e.g. '64MB' or '2GB'")),
+    }
+}
+
+fn split_unit(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    let unit_start = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    s.split_at(unit_start)
+}
+
+/// Shows a number of bytes in the largest unit it has a whole number of.
+struct ByteSize(usize);
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let units = ["B", "KB", "MB", "GB"];
+        let power = (0..units.len())
+            .rev()
+            .find(|&power| self.0 >= 1 << (10 * power))
+            .unwrap_or(0);
+        let amount = self.0 as f64 / (1u64 << (10 * power)) as f64;
+
+        if amount.fract() == 0.0 {
+            write!(f, "{amount} {}", units[power])
+        } else {
+            write!(f, "{amount:.1} {}", units[power])
+        }
+    }
+}
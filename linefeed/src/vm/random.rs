@@ -0,0 +1,144 @@
+//! The random builtins `rand`, `rand_int`, `shuffle`, `choice` and `seed`.
+//!
+//! The numbers come from SplitMix64, which is fast and small but not suitable for anything
+//! security related. Every run starts from a seed taken from the clock, and `seed(n)` restarts the
+//! sequence so a program gives the same numbers each time it is run.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::vm::{
+    runtime_value::{list::RuntimeList, number::RuntimeNumber, RuntimeValue},
+    stdlib::RuntimeResult,
+    RuntimeError,
+};
+
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn from_clock() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos());
+        Self::from_seed(nanos as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A float in `[0, 1)`, using the 53 bits a float can hold exactly.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An integer in `[0, bound)`, where `bound` must be positive. Numbers past the largest
+    /// multiple of `bound` are drawn again, so every result is equally likely.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let n = self.next_u64();
+            if n < zone {
+                return n % bound;
+            }
+        }
+    }
+}
+
+pub fn rand(rng: &mut Rng) -> RuntimeValue {
+    RuntimeValue::Num(RuntimeNumber::Float(rng.next_f64()))
+}
+
+/// A random integer between `a` and `b`, both included.
+pub fn rand_int(rng: &mut Rng, args: Vec<RuntimeValue>) -> RuntimeResult {
+    let [a, b] = args.as_slice() else {
+        unreachable!("rand_int called with wrong number of arguments");
+    };
+
+    let (Some(low), Some(high)) = (small_int(a), small_int(b)) else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "rand_int expects two integers, got {} and {}",
+            a.repr_string(),
+            b.repr_string()
+        )));
+    };
+
+    if low > high {
+        return Err(RuntimeError::Plain(format!(
+            "Cannot pick a random integer between {low} and {high}, as {low} is larger"
+        )));
+    }
+
+    // The span is one more than the largest u64 only when the bounds cover every i64
+    let offset = match u64::try_from(high as i128 - low as i128 + 1) {
+        Ok(span) => rng.below(span),
+        Err(_) => rng.next_u64(),
+    };
+    Ok(RuntimeValue::Num(RuntimeNumber::from(
+        low as i128 + offset as i128,
+    )))
+}
+
+/// The items of an iterable as a new list in random order.
+pub fn shuffle(rng: &mut Rng, val: RuntimeValue) -> RuntimeResult {
+    let mut items = items("shuffle", &val)?;
+
+    // Fisher-Yates
+    for i in (1..items.len()).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        items.swap(i, j);
+    }
+
+    Ok(RuntimeValue::List(RuntimeList::from_vec(items)))
+}
+
+pub fn choice(rng: &mut Rng, val: RuntimeValue) -> RuntimeResult {
+    let mut items = items("choice", &val)?;
+    if items.is_empty() {
+        return Err(RuntimeError::Plain(
+            "Cannot choose from an empty iterable".to_string(),
+        ));
+    }
+
+    let i = rng.below(items.len() as u64) as usize;
+    Ok(items.swap_remove(i))
+}
+
+pub fn seed(rng: &mut Rng, val: RuntimeValue) -> RuntimeResult {
+    let Some(seed) = small_int(&val) else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "seed expects an integer, got {}",
+            val.repr_string()
+        )));
+    };
+
+    *rng = Rng::from_seed(seed as u64);
+    Ok(RuntimeValue::Null)
+}
+
+fn small_int(val: &RuntimeValue) -> Option<i64> {
+    match val {
+        RuntimeValue::Num(RuntimeNumber::SmallInt(n)) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+fn items(name: &str, val: &RuntimeValue) -> Result<Vec<RuntimeValue>, RuntimeError> {
+    let iter = val.to_iter_inner().map_err(|_| {
+        RuntimeError::TypeMismatch(format!(
+            "{name} expects an iterable, got {}",
+            val.kind_str()
+        ))
+    })?;
+    Ok(iter.to_vec())
+}
@@ -13,6 +13,8 @@ pub enum RuntimeError {
     InternalBug(String),
     IndexOutOfBounds(isize, usize),
     ParseError(String),
+    /// Not an error, but how `exit` unwinds out of the program, carrying the process exit code.
+    Exit(i32),
 }
 
 impl RuntimeError {
@@ -60,6 +62,7 @@ impl std::fmt::Display for RuntimeError {
             RuntimeError::ParseError(msg) => {
                 write!(f, "Parse error: {msg}")
             }
+            RuntimeError::Exit(code) => write!(f, "Program exited with code {code}"),
         }
     }
 }
@@ -7,8 +7,10 @@ use crate::{
     vm::{
         runtime_value::{
             counter::RuntimeCounter,
+            deque::RuntimeDeque,
             function::RuntimeFunction,
-            iterator::{EnumeratedListIterator, EnumeratedStringIterator, RuntimeIterator},
+            grid::RuntimeGrid,
+            iterator::{EnumeratedIterator, RuntimeIterator},
             list::RuntimeList,
             map::{MapIterator, RuntimeMap},
             number::RuntimeNumber,
@@ -17,15 +19,23 @@ use crate::{
             regex::RuntimeRegex,
             set::RuntimeSet,
             string::RuntimeString,
+            symbol::RuntimeSymbol,
             tuple::RuntimeTuple,
+            variant::RuntimeVariant,
             vec2::RuntimeVec2,
+            vec3::RuntimeVec3,
         },
         RuntimeError,
     },
 };
 
+pub mod compare;
+mod copies;
 pub mod counter;
+pub mod cycles;
+pub mod deque;
 pub mod function;
+pub mod grid;
 pub mod iterator;
 pub mod list;
 pub mod map;
@@ -35,9 +45,12 @@ pub mod range;
 pub mod regex;
 pub mod set;
 pub mod string;
+pub mod symbol;
 pub mod tuple;
 mod utils;
+pub mod variant;
 pub mod vec2;
+pub mod vec3;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RuntimeValue {
@@ -48,12 +61,17 @@ pub enum RuntimeValue {
     Num(RuntimeNumber),
     Str(RuntimeString),
     Regex(RuntimeRegex),
+    Symbol(RuntimeSymbol),
+    Variant(RuntimeVariant),
     List(RuntimeList),
     Tuple(RuntimeTuple),
     Vec2(RuntimeVec2),
+    Vec3(RuntimeVec3),
     Set(RuntimeSet),
     Map(RuntimeMap),
     Counter(RuntimeCounter),
+    Deque(RuntimeDeque),
+    Grid(RuntimeGrid),
     Function(Rc<RuntimeFunction>),
     Range(Box<RuntimeRange>),
     Iterator(Box<RuntimeIterator>),
@@ -76,15 +94,20 @@ impl RuntimeValue {
             RuntimeValue::Num(_) => "number",
             RuntimeValue::Str(_) => "str",
             RuntimeValue::Regex(_) => "regex",
+            RuntimeValue::Symbol(_) => "symbol",
+            RuntimeValue::Variant(_) => "enum variant",
             RuntimeValue::List(_) => "list",
             RuntimeValue::Tuple(_) => "tuple",
             RuntimeValue::Vec2(_) => "vector2",
+            RuntimeValue::Vec3(_) => "vector3",
             RuntimeValue::Set(_) => "set",
             RuntimeValue::Function(_) => "function",
             RuntimeValue::Range(_) => "range",
             RuntimeValue::Iterator(_) => "iterator",
             RuntimeValue::Map(_) => "map",
             RuntimeValue::Counter(_) => "counter",
+            RuntimeValue::Deque(_) => "deque",
+            RuntimeValue::Grid(_) => "grid",
         }
     }
 
@@ -98,6 +121,9 @@ impl RuntimeValue {
             )),
             (RuntimeValue::List(a), RuntimeValue::List(b)) => Ok(RuntimeValue::List(a.concat(b))),
             (RuntimeValue::Set(a), RuntimeValue::Set(b)) => Ok(RuntimeValue::Set(a.union(b))),
+            (RuntimeValue::Counter(a), RuntimeValue::Counter(b)) => {
+                Ok(RuntimeValue::Counter(a.sum(b)))
+            }
             (RuntimeValue::Tuple(a), RuntimeValue::Tuple(b)) => {
                 Ok(RuntimeValue::Tuple(a.element_wise_add(b)?))
             }
@@ -108,6 +134,19 @@ impl RuntimeValue {
             (RuntimeValue::Tuple(t), RuntimeValue::Vec2(v)) => {
                 Ok(RuntimeValue::Tuple(t.element_wise_add(&v.to_tuple())?))
             }
+            (RuntimeValue::Vec3(v1), RuntimeValue::Vec3(v2)) => v1.add(v2),
+            (RuntimeValue::Vec3(v), RuntimeValue::Tuple(t)) => {
+                Ok(RuntimeValue::Tuple(v.to_tuple().element_wise_add(t)?))
+            }
+            (RuntimeValue::Tuple(t), RuntimeValue::Vec3(v)) => {
+                Ok(RuntimeValue::Tuple(t.element_wise_add(&v.to_tuple())?))
+            }
+            (RuntimeValue::Vec2(v2), RuntimeValue::Vec3(v3)) => Ok(RuntimeValue::Tuple(
+                v2.to_tuple().element_wise_add(&v3.to_tuple())?,
+            )),
+            (RuntimeValue::Vec3(v3), RuntimeValue::Vec2(v2)) => Ok(RuntimeValue::Tuple(
+                v3.to_tuple().element_wise_add(&v2.to_tuple())?,
+            )),
             _ => Err(RuntimeError::invalid_binary_op_for_types(
                 "add", self, other,
             )),
@@ -128,7 +167,23 @@ impl RuntimeValue {
             (RuntimeValue::Tuple(t), RuntimeValue::Vec2(v)) => {
                 Ok(RuntimeValue::Tuple(t.element_wise_sub(&v.to_tuple())?))
             }
+            (RuntimeValue::Vec3(v1), RuntimeValue::Vec3(v2)) => v1.sub(v2),
+            (RuntimeValue::Vec3(v), RuntimeValue::Tuple(t)) => {
+                Ok(RuntimeValue::Tuple(v.to_tuple().element_wise_sub(t)?))
+            }
+            (RuntimeValue::Tuple(t), RuntimeValue::Vec3(v)) => {
+                Ok(RuntimeValue::Tuple(t.element_wise_sub(&v.to_tuple())?))
+            }
+            (RuntimeValue::Vec2(v2), RuntimeValue::Vec3(v3)) => Ok(RuntimeValue::Tuple(
+                v2.to_tuple().element_wise_sub(&v3.to_tuple())?,
+            )),
+            (RuntimeValue::Vec3(v3), RuntimeValue::Vec2(v2)) => Ok(RuntimeValue::Tuple(
+                v3.to_tuple().element_wise_sub(&v2.to_tuple())?,
+            )),
             (RuntimeValue::Set(a), RuntimeValue::Set(b)) => Ok(RuntimeValue::Set(a.difference(b))),
+            (RuntimeValue::Counter(a), RuntimeValue::Counter(b)) => {
+                Ok(RuntimeValue::Counter(a.difference(b)))
+            }
             _ => Err(RuntimeError::invalid_binary_op_for_types(
                 "subtract", self, other,
             )),
@@ -147,6 +202,8 @@ impl RuntimeValue {
             }
             (RuntimeValue::Vec2(v), _) => v.scalar_mul(other),
             (_, RuntimeValue::Vec2(v)) => v.scalar_mul(self),
+            (RuntimeValue::Vec3(v), _) => v.scalar_mul(other),
+            (_, RuntimeValue::Vec3(v)) => v.scalar_mul(self),
             _ => Err(RuntimeError::invalid_binary_op_for_types(
                 "multiply", self, other,
             )),
@@ -239,6 +296,20 @@ impl RuntimeValue {
         }
     }
 
+    pub fn neg(&self) -> Result<Self, RuntimeError> {
+        match self {
+            RuntimeValue::Int(a) => Ok(RuntimeValue::Int(-a)),
+            RuntimeValue::Num(a) => Ok(RuntimeValue::Num(a.neg())),
+            RuntimeValue::Tuple(t) => Ok(RuntimeValue::Tuple(t.neg()?)),
+            RuntimeValue::Vec2(v) => v.neg(),
+            RuntimeValue::Vec3(v) => v.neg(),
+            _ => Err(RuntimeError::TypeMismatch(format!(
+                "Cannot negate type '{}'",
+                self.kind_str()
+            ))),
+        }
+    }
+
     pub fn bitwise_not(&self) -> Result<Self, RuntimeError> {
         match self {
             RuntimeValue::Num(a) => Ok(RuntimeValue::Num(a.bitwise_not()?)),
@@ -320,10 +391,13 @@ impl RuntimeValue {
             }
             (RuntimeValue::Tuple(tuple), RuntimeValue::Num(i)) => tuple.index(i)?,
             (RuntimeValue::Vec2(v), RuntimeValue::Num(i)) => v.index(i)?,
+            (RuntimeValue::Vec3(v), RuntimeValue::Num(i)) => v.index(i)?,
             (RuntimeValue::Str(s), RuntimeValue::Num(i)) => RuntimeValue::Str(s.index(i)?),
             (RuntimeValue::Str(s), RuntimeValue::Range(r)) => RuntimeValue::Str(s.substr(r)?),
             (RuntimeValue::Map(map), index) => map.get(index),
             (RuntimeValue::Counter(counter), index) => counter.get(index),
+            (RuntimeValue::Deque(deque), RuntimeValue::Num(i)) => deque.index(i)?,
+            (RuntimeValue::Grid(grid), pos) => grid.get(pos)?,
             _ => {
                 return Err(RuntimeError::TypeMismatch(format!(
                     "Cannot index into '{}' with type '{}'",
@@ -336,10 +410,20 @@ impl RuntimeValue {
         Ok(res)
     }
 
+    /// Indexes like `index`, but gives null for an index that is out of bounds. Maps already give
+    /// null for missing keys.
+    pub fn index_or_null(&self, index: &Self) -> Result<Self, RuntimeError> {
+        match self.index(index) {
+            Err(RuntimeError::IndexOutOfBounds(..)) => Ok(RuntimeValue::Null),
+            res => res,
+        }
+    }
+
     pub fn set_index(&self, index: &Self, value: Self) -> Result<(), RuntimeError> {
         match (self, index) {
             (RuntimeValue::List(list), RuntimeValue::Num(i)) => list.set_index(i, value)?,
             (RuntimeValue::Map(map), index) => map.insert(index.clone(), value),
+            (RuntimeValue::Grid(grid), pos) => grid.set(pos, value)?,
             _ => {
                 return Err(RuntimeError::TypeMismatch(format!(
                     "Cannot index into '{}' with type '{}'",
@@ -359,10 +443,13 @@ impl RuntimeValue {
             RuntimeValue::List(list) => RuntimeIterator::from(list.clone()),
             RuntimeValue::Tuple(tuple) => RuntimeIterator::from(tuple.clone()),
             RuntimeValue::Vec2(v) => RuntimeIterator::from(v.to_tuple()),
+            RuntimeValue::Vec3(v) => RuntimeIterator::from(v.to_tuple()),
             RuntimeValue::Str(s) => RuntimeIterator::from(s.clone()),
             RuntimeValue::Map(m) => RuntimeIterator::from(m.clone()),
             RuntimeValue::Set(s) => RuntimeIterator::from(s.clone()),
             RuntimeValue::Counter(c) => RuntimeIterator::from(c.clone()),
+            RuntimeValue::Deque(d) => RuntimeIterator::from(RuntimeList::from_vec(d.to_vec())),
+            RuntimeValue::Grid(g) => RuntimeIterator::from(RuntimeList::from_vec(g.items())),
             _ => {
                 return Err(RuntimeError::TypeMismatch(format!(
                     "Cannot iterate over '{}'",
@@ -394,6 +481,8 @@ impl RuntimeValue {
             RuntimeValue::Str(s) => RuntimeValue::Num(RuntimeNumber::from(s.len())),
             RuntimeValue::Set(s) => RuntimeValue::Num(RuntimeNumber::from(s.len())),
             RuntimeValue::Map(m) => RuntimeValue::Num(RuntimeNumber::from(m.len())),
+            RuntimeValue::Deque(d) => RuntimeValue::Num(RuntimeNumber::from(d.len())),
+            RuntimeValue::Grid(g) => RuntimeValue::Num(RuntimeNumber::from(g.len())),
             RuntimeValue::Range(r) => {
                 let len = r.len().ok_or_else(|| {
                     RuntimeError::TypeMismatch("Cannot get length of unbounded range".to_string())
@@ -411,7 +500,11 @@ impl RuntimeValue {
         Ok(res)
     }
 
-    pub fn count(&self, item: &Self) -> Result<Self, RuntimeError> {
+    pub fn count(&self, item: Option<Self>) -> Result<Self, RuntimeError> {
+        let Some(item) = &item else {
+            return self.count_items();
+        };
+
         let res = match (self, item) {
             (RuntimeValue::List(list), _) => RuntimeValue::Num(RuntimeNumber::from(
                 list.as_slice().iter().filter(|x| *x == item).count(),
@@ -429,6 +522,75 @@ impl RuntimeValue {
         Ok(res)
     }
 
+    // Iterators are consumed by counting them, while lists and ranges are counted without going
+    // through their items
+    fn count_items(&self) -> Result<Self, RuntimeError> {
+        let count = match self {
+            RuntimeValue::Str(s) => Some(s.as_str().chars().count()),
+            _ => self
+                .to_iter_inner()
+                .map_err(|_| RuntimeError::invalid_method_for_type(Method::Count, self))?
+                .count_remaining(),
+        };
+
+        let count = count.ok_or_else(|| {
+            RuntimeError::TypeMismatch("Cannot count the items of an unbounded range".to_string())
+        })?;
+        Ok(RuntimeValue::Num(RuntimeNumber::from(count)))
+    }
+
+    /// Returns the item at index `n`, or null if there are too few items. Iterators are advanced
+    /// past it.
+    pub fn nth(&self, n: &Self) -> Result<Self, RuntimeError> {
+        let n = match n {
+            RuntimeValue::Num(num) if num.floor_int() >= 0 => num.floor_int() as usize,
+            _ => {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "nth expects a non-negative integer, got '{n}'"
+                )))
+            }
+        };
+
+        let res = match self {
+            RuntimeValue::List(list) => list.as_slice().get(n).cloned(),
+            RuntimeValue::Range(r) if r.first().is_none() => {
+                return Err(RuntimeError::TypeMismatch(
+                    "Cannot get nth of range without a start".to_string(),
+                ))
+            }
+            RuntimeValue::Range(r) => r.nth(n).map(|n| RuntimeValue::Num(RuntimeNumber::from(n))),
+            _ => {
+                let iter = self
+                    .to_iter_inner()
+                    .map_err(|_| RuntimeError::invalid_method_for_type(Method::Nth, self))?;
+                iter.skip(n);
+                iter.next()
+            }
+        };
+
+        Ok(res.unwrap_or(RuntimeValue::Null))
+    }
+
+    /// Returns whether there are no items. Iterators aren't advanced.
+    pub fn is_empty(&self) -> Result<Self, RuntimeError> {
+        let is_empty = match self {
+            RuntimeValue::List(list) => list.is_empty(),
+            RuntimeValue::Tuple(tuple) => tuple.len() == 0,
+            RuntimeValue::Vec2(_) | RuntimeValue::Vec3(_) => false,
+            RuntimeValue::Str(s) => s.len() == 0,
+            RuntimeValue::Set(set) => set.len() == 0,
+            RuntimeValue::Map(map) => map.is_empty(),
+            RuntimeValue::Counter(counter) => counter.len() == 0,
+            RuntimeValue::Deque(deque) => deque.is_empty(),
+            RuntimeValue::Grid(grid) => grid.is_empty(),
+            RuntimeValue::Range(r) => r.is_empty(),
+            RuntimeValue::Iterator(iter) => iter.is_empty(),
+            _ => return Err(RuntimeError::invalid_method_for_type(Method::IsEmpty, self)),
+        };
+
+        Ok(RuntimeValue::Bool(is_empty))
+    }
+
     pub fn eq_bool(&self, other: &Self) -> Result<Self, RuntimeError> {
         Ok(RuntimeValue::Bool(self == other))
     }
@@ -442,9 +604,19 @@ impl RuntimeValue {
         other: &Self,
         checker: impl FnOnce(Ordering) -> bool,
     ) -> Result<Self, RuntimeError> {
-        self.partial_cmp(other)
-            .map(|actual| RuntimeValue::Bool(checker(actual)))
-            .ok_or_else(|| RuntimeError::invalid_binary_op_for_types("compare", self, other))
+        let counters = matches!(
+            (self, other),
+            (RuntimeValue::Counter(_), RuntimeValue::Counter(_))
+        );
+
+        match self.partial_cmp(other) {
+            Some(actual) => Ok(RuntimeValue::Bool(checker(actual))),
+            // Neither counter has at most as many of each item as the other
+            None if counters => Ok(RuntimeValue::Bool(false)),
+            None => Err(RuntimeError::invalid_binary_op_for_types(
+                "compare", self, other,
+            )),
+        }
     }
 
     pub fn less_than(&self, other: &Self) -> Result<Self, RuntimeError> {
@@ -484,15 +656,22 @@ impl RuntimeValue {
         }
     }
 
-    pub fn enumerate(&self) -> Result<Self, RuntimeError> {
-        match self {
-            RuntimeValue::List(list) => Ok(RuntimeValue::Iterator(Box::new(
-                RuntimeIterator::from(EnumeratedListIterator::new(list.clone())),
-            ))),
-            RuntimeValue::Str(s) => Ok(RuntimeValue::Iterator(Box::new(RuntimeIterator::from(
-                EnumeratedStringIterator::new(s.clone()),
+    pub fn enumerate(&self, start: Option<RuntimeValue>) -> Result<Self, RuntimeError> {
+        let start = match start {
+            None => RuntimeNumber::from(0),
+            Some(RuntimeValue::Num(n)) if n.is_integer() => n,
+            Some(other) => {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "Enumerate start must be an integer, got '{other}'"
+                )))
+            }
+        };
+
+        match self.to_iter_inner() {
+            Ok(iter) => Ok(RuntimeValue::Iterator(Box::new(RuntimeIterator::from(
+                EnumeratedIterator::new(iter, start),
             )))),
-            _ => Err(RuntimeError::invalid_method_for_type(
+            Err(_) => Err(RuntimeError::invalid_method_for_type(
                 Method::Enumerate,
                 self,
             )),
@@ -544,6 +723,24 @@ impl RuntimeValue {
                 .ok_or_else(|| {
                     RuntimeError::TypeMismatch("Cannot get last of unbounded range".to_string())
                 }),
+            // Consumes the iterator, skipping straight to its last item where possible
+            RuntimeValue::Iterator(iter) => {
+                let remaining = iter.len();
+                if remaining == usize::MAX {
+                    return Err(RuntimeError::TypeMismatch(
+                        "Cannot get last of unbounded range".to_string(),
+                    ));
+                }
+                iter.skip(remaining.saturating_sub(1));
+
+                let mut last = None;
+                while let Some(item) = iter.next() {
+                    last = Some(item);
+                }
+                last.ok_or_else(|| {
+                    RuntimeError::TypeMismatch("Cannot get last of empty iterator".to_string())
+                })
+            }
             _ => Err(RuntimeError::TypeMismatch(format!(
                 "Cannot get last of '{}'",
                 self.kind_str()
@@ -561,6 +758,26 @@ impl RuntimeValue {
         Ok(RuntimeValue::List(list.transpose()?))
     }
 
+    pub fn grid_to_map(&self) -> Result<Self, RuntimeError> {
+        let RuntimeValue::List(list) = self else {
+            return Err(RuntimeError::invalid_method_for_type(
+                Method::GridToMap,
+                self,
+            ));
+        };
+        Ok(RuntimeValue::Map(list.grid_to_map()?))
+    }
+
+    pub fn grid_to_strings(&self) -> Result<Self, RuntimeError> {
+        let RuntimeValue::List(list) = self else {
+            return Err(RuntimeError::invalid_method_for_type(
+                Method::ToStrings,
+                self,
+            ));
+        };
+        Ok(RuntimeValue::List(list.grid_to_strings()?))
+    }
+
     pub fn reverse(&self) -> Result<Self, RuntimeError> {
         let RuntimeValue::List(list) = self else {
             return Err(RuntimeError::invalid_method_for_type(Method::Reverse, self));
@@ -569,6 +786,65 @@ impl RuntimeValue {
         Ok(self.clone())
     }
 
+    pub fn tally(&self) -> Result<Self, RuntimeError> {
+        let iter = self
+            .to_iter_inner()
+            .map_err(|_| RuntimeError::invalid_method_for_type(Method::Tally, self))?;
+
+        Ok(RuntimeValue::Counter(RuntimeCounter::try_from(iter)?))
+    }
+
+    pub fn most_common(&self, n: Option<RuntimeValue>) -> Result<Self, RuntimeError> {
+        let RuntimeValue::Counter(counter) = self else {
+            return Err(RuntimeError::invalid_method_for_type(
+                Method::MostCommon,
+                self,
+            ));
+        };
+
+        let n = match n {
+            None => None,
+            Some(RuntimeValue::Num(n)) if n.floor_int() >= 0 => Some(n.floor_int() as usize),
+            Some(n) => {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "most_common expects a non-negative integer, got '{n}'"
+                )))
+            }
+        };
+
+        Ok(RuntimeValue::List(counter.most_common(n)))
+    }
+
+    /// Every pair of consecutive elements, as a list of 2-tuples.
+    pub fn pairwise(&self) -> Result<Self, RuntimeError> {
+        let items = self
+            .to_iter_inner()
+            .map_err(|_| RuntimeError::invalid_method_for_type(Method::Pairwise, self))?
+            .to_vec();
+
+        let pairs = items
+            .windows(2)
+            .map(|pair| RuntimeTuple::from_vec(pair.to_vec()))
+            .collect();
+
+        Ok(RuntimeValue::List(RuntimeList::from_vec(pairs)))
+    }
+
+    /// The difference between every pair of consecutive elements, i.e. `b - a` for each pair.
+    pub fn deltas(&self) -> Result<Self, RuntimeError> {
+        let items = self
+            .to_iter_inner()
+            .map_err(|_| RuntimeError::invalid_method_for_type(Method::Deltas, self))?
+            .to_vec();
+
+        let deltas = items
+            .windows(2)
+            .map(|pair| pair[1].sub(&pair[0]))
+            .collect::<Result<_, _>>()?;
+
+        Ok(RuntimeValue::List(RuntimeList::from_vec(deltas)))
+    }
+
     pub fn range(&self, other: &Self) -> Result<Self, RuntimeError> {
         let range = match (self, other) {
             (RuntimeValue::Num(start), RuntimeValue::Num(end)) => {
@@ -611,13 +887,34 @@ impl RuntimeValue {
             RuntimeValue::List(xs) => !xs.as_slice().is_empty(),
             RuntimeValue::Tuple(xs) => !xs.as_slice().is_empty(),
             RuntimeValue::Vec2(_) => true,
+            RuntimeValue::Vec3(_) => true,
             RuntimeValue::Set(xs) => !xs.borrow().is_empty(),
             RuntimeValue::Map(m) => !m.is_empty(),
             RuntimeValue::Function(_) => true,
             RuntimeValue::Range(_) => true,
             RuntimeValue::Iterator(_) => true,
             RuntimeValue::Regex(_) => true,
+            RuntimeValue::Symbol(_) => true,
+            RuntimeValue::Variant(_) => true,
             RuntimeValue::Counter(c) => !c.borrow().is_empty(),
+            RuntimeValue::Deque(d) => !d.is_empty(),
+            RuntimeValue::Grid(g) => !g.is_empty(),
+        }
+    }
+
+    /// Whether the value can't be changed, nor holds any value that can, in which case a deep
+    /// clone is the same as a clone.
+    pub fn is_immutable(&self) -> bool {
+        match self {
+            RuntimeValue::Tuple(t) => t.as_slice().iter().all(RuntimeValue::is_immutable),
+            RuntimeValue::List(_)
+            | RuntimeValue::Set(_)
+            | RuntimeValue::Map(_)
+            | RuntimeValue::Counter(_)
+            | RuntimeValue::Deque(_)
+            | RuntimeValue::Grid(_)
+            | RuntimeValue::Iterator(_) => false,
+            _ => true,
         }
     }
 
@@ -630,14 +927,20 @@ impl RuntimeValue {
             RuntimeValue::Num(n) => RuntimeValue::Num(n.clone()),
             RuntimeValue::Str(s) => RuntimeValue::Str(s.clone()),
             RuntimeValue::List(xs) => RuntimeValue::List(xs.deep_clone()),
-            RuntimeValue::Tuple(xs) => RuntimeValue::Tuple(xs.clone()),
+            RuntimeValue::Tuple(xs) => RuntimeValue::Tuple(xs.deep_clone()),
             RuntimeValue::Vec2(v) => RuntimeValue::Vec2(*v),
+            RuntimeValue::Vec3(v) => RuntimeValue::Vec3(*v),
             RuntimeValue::Map(m) => RuntimeValue::Map(m.deep_clone()),
             RuntimeValue::Set(s) => RuntimeValue::Set(s.deep_clone()),
             RuntimeValue::Counter(c) => RuntimeValue::Counter(c.deep_clone()),
+            RuntimeValue::Deque(d) => RuntimeValue::Deque(d.deep_clone()),
+            RuntimeValue::Grid(g) => RuntimeValue::Grid(g.deep_clone()),
             RuntimeValue::Function(_) => self.clone(),
             RuntimeValue::Regex(r) => RuntimeValue::Regex(r.clone()),
-            _ => unimplemented!("deep_clone for {:?}", self),
+            RuntimeValue::Symbol(s) => RuntimeValue::Symbol(*s),
+            RuntimeValue::Variant(v) => RuntimeValue::Variant(*v),
+            RuntimeValue::Range(r) => RuntimeValue::Range(r.clone()),
+            RuntimeValue::Iterator(iter) => RuntimeValue::Iterator(Box::new(iter.deep_clone())),
         }
     }
 }
@@ -681,6 +984,9 @@ impl std::fmt::Display for RuntimeValue {
             RuntimeValue::Vec2(v) => {
                 write!(f, "({}, {})", v.x, v.y)
             }
+            RuntimeValue::Vec3(v) => {
+                write!(f, "({}, {}, {})", v.x, v.y, v.z)
+            }
             RuntimeValue::Set(xs) => {
                 write!(f, "{{")?;
                 let xs = xs.borrow();
@@ -710,10 +1016,18 @@ impl std::fmt::Display for RuntimeValue {
             RuntimeValue::Counter(c) => {
                 std::fmt::Display::fmt(&RuntimeValue::Map(c.into_runtime_map()), f)
             }
+            RuntimeValue::Deque(d) => {
+                write!(f, "deque([")?;
+                write_items(f, d.to_vec().iter(), |f, x| x.repr_fmt(f))?;
+                write!(f, "])")
+            }
+            RuntimeValue::Grid(grid) => write!(f, "{grid}"),
             RuntimeValue::Function(func) => write!(f, "<function@{}>", func.location),
             RuntimeValue::Range(range) => write!(f, "{range}"),
             RuntimeValue::Iterator(iterator) => write!(f, "{iterator}"),
             RuntimeValue::Regex(regex) => write!(f, "{regex}"),
+            RuntimeValue::Symbol(symbol) => write!(f, "{symbol}"),
+            RuntimeValue::Variant(variant) => write!(f, "{variant}"),
         }
     }
 }
@@ -725,6 +1039,7 @@ impl RuntimeValue {
     pub fn repr_fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             RuntimeValue::Str(s) => write!(f, "{:?}", s.as_str()),
+            RuntimeValue::Symbol(symbol) => write!(f, ":{symbol}"),
             _ => {
                 use std::fmt::Display;
                 self.fmt(f)
@@ -762,10 +1077,16 @@ impl std::cmp::PartialOrd for RuntimeValue {
             (RuntimeValue::Int(a), RuntimeValue::Int(b)) => a.partial_cmp(b),
             (RuntimeValue::Num(a), RuntimeValue::Num(b)) => a.partial_cmp(b),
             (RuntimeValue::Str(a), RuntimeValue::Str(b)) => a.partial_cmp(b),
+            (RuntimeValue::Symbol(a), RuntimeValue::Symbol(b)) => a.partial_cmp(b),
+            (RuntimeValue::Variant(a), RuntimeValue::Variant(b)) => a.partial_cmp(b),
             (RuntimeValue::List(a), RuntimeValue::List(b)) => a.partial_cmp(b),
             (RuntimeValue::Tuple(a), RuntimeValue::Tuple(b)) => a.partial_cmp(b),
             (RuntimeValue::Vec2(a), RuntimeValue::Vec2(b)) => a.partial_cmp(b),
+            (RuntimeValue::Vec3(a), RuntimeValue::Vec3(b)) => a.partial_cmp(b),
             (RuntimeValue::Set(a), RuntimeValue::Set(b)) => a.partial_cmp(b),
+            (RuntimeValue::Counter(a), RuntimeValue::Counter(b)) => a.partial_cmp(b),
+            (RuntimeValue::Deque(a), RuntimeValue::Deque(b)) => a.partial_cmp(b),
+            (RuntimeValue::Grid(a), RuntimeValue::Grid(b)) => a.partial_cmp(b),
             _ => None,
         }
     }
@@ -778,12 +1099,65 @@ impl RuntimeValue {
             RuntimeValue::List(list) => list.append(val)?,
             RuntimeValue::Set(set) => set.append(val)?,
             RuntimeValue::Counter(counter) => counter.add(val, 1),
+            RuntimeValue::Deque(deque) => deque.append(val)?,
             _ => return Err(RuntimeError::invalid_method_for_type(Method::Append, self)),
         };
 
         Ok(())
     }
 
+    pub fn push_front(&mut self, val: Self) -> Result<(), RuntimeError> {
+        self.as_deque(Method::PushFront)?.push_front(val);
+        Ok(())
+    }
+
+    pub fn push_back(&mut self, val: Self) -> Result<(), RuntimeError> {
+        self.as_deque(Method::PushBack)?.push_back(val);
+        Ok(())
+    }
+
+    pub fn pop_front(&self) -> Result<Self, RuntimeError> {
+        self.as_deque(Method::PopFront)?.pop_front()
+    }
+
+    pub fn pop_back(&self) -> Result<Self, RuntimeError> {
+        self.as_deque(Method::PopBack)?.pop_back()
+    }
+
+    pub fn width(&self) -> Result<Self, RuntimeError> {
+        let grid = self.as_grid(Method::Width)?;
+        Ok(RuntimeValue::Num(RuntimeNumber::from(grid.width())))
+    }
+
+    pub fn height(&self) -> Result<Self, RuntimeError> {
+        let grid = self.as_grid(Method::Height)?;
+        Ok(RuntimeValue::Num(RuntimeNumber::from(grid.height())))
+    }
+
+    pub fn neighbors4(&self, pos: &Self) -> Result<Self, RuntimeError> {
+        let grid = self.as_grid(Method::Neighbors4)?;
+        Ok(RuntimeValue::List(grid.neighbors4(pos)?))
+    }
+
+    pub fn neighbors8(&self, pos: &Self) -> Result<Self, RuntimeError> {
+        let grid = self.as_grid(Method::Neighbors8)?;
+        Ok(RuntimeValue::List(grid.neighbors8(pos)?))
+    }
+
+    fn as_grid(&self, method: Method) -> Result<&RuntimeGrid, RuntimeError> {
+        match self {
+            RuntimeValue::Grid(grid) => Ok(grid),
+            _ => Err(RuntimeError::invalid_method_for_type(method, self)),
+        }
+    }
+
+    fn as_deque(&self, method: Method) -> Result<&RuntimeDeque, RuntimeError> {
+        match self {
+            RuntimeValue::Deque(deque) => Ok(deque),
+            _ => Err(RuntimeError::invalid_method_for_type(method, self)),
+        }
+    }
+
     pub fn remove(&mut self, val: Self) -> Result<(), RuntimeError> {
         match self {
             RuntimeValue::Set(set) => set.remove(val),
@@ -840,6 +1214,17 @@ impl RuntimeValue {
         Ok(RuntimeValue::List(s.lines()))
     }
 
+    pub fn paragraphs(&self) -> Result<Self, RuntimeError> {
+        let RuntimeValue::Str(s) = self else {
+            return Err(RuntimeError::invalid_method_for_type(
+                Method::Paragraphs,
+                self,
+            ));
+        };
+
+        Ok(RuntimeValue::List(s.paragraphs()))
+    }
+
     pub fn nums(&self) -> Result<Self, RuntimeError> {
         let RuntimeValue::Str(s) = self else {
             return Err(RuntimeError::invalid_method_for_type(Method::Nums, self));
@@ -848,6 +1233,20 @@ impl RuntimeValue {
         Ok(RuntimeValue::List(s.nums()))
     }
 
+    pub fn digits(&self) -> Result<Self, RuntimeError> {
+        let digits = match self {
+            RuntimeValue::Str(s) => s.digits()?,
+            RuntimeValue::Num(n) => n.digits()?,
+            _ => return Err(RuntimeError::invalid_method_for_type(Method::Digits, self)),
+        };
+
+        let digits = digits
+            .into_iter()
+            .map(|d| RuntimeValue::Num(RuntimeNumber::from(d as isize)))
+            .collect();
+        Ok(RuntimeValue::List(RuntimeList::from_vec(digits)))
+    }
+
     pub fn join(&self, separator: Option<RuntimeValue>) -> Result<Self, RuntimeError> {
         let Ok(Self::Iterator(iter)) = self.to_iter() else {
             return Err(RuntimeError::invalid_method_for_type(Method::Join, self));
@@ -908,13 +1307,30 @@ impl RuntimeValue {
         }
     }
 
+    /// Whether the value is a tuple with `len` items. Pairs of integers are stored as 2D vectors,
+    /// which count as tuples too.
+    pub fn is_tuple_of_len(&self, len: usize) -> bool {
+        match self {
+            RuntimeValue::Tuple(tuple) => tuple.len() == len,
+            RuntimeValue::Vec2(_) => len == 2,
+            RuntimeValue::Vec3(_) => len == 3,
+            _ => false,
+        }
+    }
+
+    pub fn is_list_of_len(&self, len: usize) -> bool {
+        matches!(self, RuntimeValue::List(list) if list.len() == len)
+    }
+
     pub fn contains(&self, item: &Self) -> Result<Self, RuntimeError> {
         let contains = match (self, item) {
             (RuntimeValue::Map(m), k) => m.contains_key(k),
             (RuntimeValue::List(l), v) => l.contains(v),
             (RuntimeValue::Set(l), v) => l.contains(v),
+            (RuntimeValue::Deque(d), v) => d.contains(v),
             (RuntimeValue::Tuple(t), v) => t.contains(v),
             (RuntimeValue::Vec2(v), item) => v.contains(item),
+            (RuntimeValue::Vec3(v), item) => v.contains(item),
             (RuntimeValue::Range(r), RuntimeValue::Num(n)) => r.contains(n),
             (RuntimeValue::Str(s1), RuntimeValue::Str(s2)) => s1.contains(s2),
             _ => {
@@ -941,6 +1357,90 @@ impl RuntimeValue {
         }
     }
 
+    pub fn ends_with(&self, suffix: &Self) -> Result<Self, RuntimeError> {
+        match (self, suffix) {
+            (RuntimeValue::Str(s), RuntimeValue::Str(p)) => Ok(RuntimeValue::Bool(s.ends_with(p))),
+            _ => Err(RuntimeError::invalid_method_for_type(
+                Method::EndsWith,
+                self,
+            )),
+        }
+    }
+
+    pub fn index_of(&self, substr: &Self) -> Result<Self, RuntimeError> {
+        match (self, substr) {
+            (RuntimeValue::Str(s), RuntimeValue::Str(sub)) => {
+                Ok(s.index_of(sub).map_or(RuntimeValue::Null, |i| {
+                    RuntimeValue::Num(RuntimeNumber::from(i as isize))
+                }))
+            }
+            _ => Err(RuntimeError::invalid_method_for_type(Method::IndexOf, self)),
+        }
+    }
+
+    pub fn trim(&self) -> Result<Self, RuntimeError> {
+        self.map_string(Method::Trim, RuntimeString::trim)
+    }
+
+    pub fn trim_start(&self) -> Result<Self, RuntimeError> {
+        self.map_string(Method::TrimStart, RuntimeString::trim_start)
+    }
+
+    pub fn trim_end(&self) -> Result<Self, RuntimeError> {
+        self.map_string(Method::TrimEnd, RuntimeString::trim_end)
+    }
+
+    fn map_string(
+        &self,
+        method: Method,
+        f: impl FnOnce(&RuntimeString) -> RuntimeString,
+    ) -> Result<Self, RuntimeError> {
+        match self {
+            RuntimeValue::Str(s) => Ok(RuntimeValue::Str(f(s))),
+            _ => Err(RuntimeError::invalid_method_for_type(method, self)),
+        }
+    }
+
+    pub fn replace(&self, from: &Self, to: &Self) -> Result<Self, RuntimeError> {
+        match (self, from, to) {
+            (RuntimeValue::Str(s), RuntimeValue::Str(from), RuntimeValue::Str(to)) => {
+                Ok(RuntimeValue::Str(s.replace(from, to)))
+            }
+            (RuntimeValue::Str(s), RuntimeValue::Regex(regex), RuntimeValue::Str(to)) => {
+                Ok(RuntimeValue::Str(regex.replace_all(s, to)))
+            }
+            (RuntimeValue::Str(_), _, _) => Err(RuntimeError::TypeMismatch(format!(
+                "replace expects a string or regex and a replacement string, got '{}' and '{}'",
+                from.kind_str(),
+                to.kind_str()
+            ))),
+            _ => Err(RuntimeError::invalid_method_for_type(Method::Replace, self)),
+        }
+    }
+
+    pub fn repeat(&self, n: &Self) -> Result<Self, RuntimeError> {
+        let RuntimeValue::Str(s) = self else {
+            return Err(RuntimeError::invalid_method_for_type(Method::Repeat, self));
+        };
+
+        let n = n.to_i32().filter(|n| *n >= 0).ok_or_else(|| {
+            RuntimeError::TypeMismatch(format!(
+                "repeat expects a non-negative integer, got {}",
+                n.repr_string()
+            ))
+        })?;
+
+        Ok(RuntimeValue::Str(s.repeat(n as usize)))
+    }
+
+    pub fn format(&self, args: &[Self]) -> Result<Self, RuntimeError> {
+        let RuntimeValue::Str(s) = self else {
+            return Err(RuntimeError::invalid_method_for_type(Method::Format, self));
+        };
+
+        Ok(RuntimeValue::Str(s.format(args)?))
+    }
+
     pub fn get_all(&self, iterable: &Self) -> Result<Self, RuntimeError> {
         match self {
             RuntimeValue::Map(map) => {
@@ -952,6 +1452,13 @@ impl RuntimeValue {
         }
     }
 
+    pub fn get_or(&self, key: &Self, default: Self) -> Result<Self, RuntimeError> {
+        match self {
+            RuntimeValue::Map(map) => Ok(map.get_or(key, default)),
+            _ => Err(RuntimeError::invalid_method_for_type(Method::GetOr, self)),
+        }
+    }
+
     pub fn values(&self) -> Result<Self, RuntimeError> {
         match self {
             RuntimeValue::Map(map) => {
@@ -975,10 +1482,82 @@ impl RuntimeValue {
         }
     }
 
+    pub fn x(&self) -> Result<Self, RuntimeError> {
+        self.component(Method::X, 0)
+    }
+
+    pub fn y(&self) -> Result<Self, RuntimeError> {
+        self.component(Method::Y, 1)
+    }
+
+    pub fn z(&self) -> Result<Self, RuntimeError> {
+        self.component(Method::Z, 2)
+    }
+
+    fn component(&self, method: Method, i: usize) -> Result<Self, RuntimeError> {
+        let items = self.vector_items(method)?;
+        items
+            .as_slice()
+            .get(i)
+            .cloned()
+            .ok_or(RuntimeError::IndexOutOfBounds(i as isize, items.len()))
+    }
+
+    pub fn dot(&self, other: &Self) -> Result<Self, RuntimeError> {
+        let a = self.vector_items(Method::Dot)?;
+        let b = other.vector_items(Method::Dot)?;
+        if a.len() != b.len() {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "Cannot take the dot product of vectors of different lengths: {} and {}",
+                a.len(),
+                b.len()
+            )));
+        }
+
+        let zero = RuntimeValue::Num(RuntimeNumber::from(0));
+        a.as_slice()
+            .iter()
+            .zip(b.as_slice())
+            .try_fold(zero, |acc, (x, y)| acc.add(&x.mul(y)?))
+    }
+
+    /// The cross product of two 3D vectors, or the z component of it for two 2D vectors.
+    pub fn cross(&self, other: &Self) -> Result<Self, RuntimeError> {
+        let a = self.vector_items(Method::Cross)?;
+        let b = other.vector_items(Method::Cross)?;
+        let det = |i: usize, j: usize| {
+            let (a, b) = (a.as_slice(), b.as_slice());
+            a[i].mul(&b[j])?.sub(&a[j].mul(&b[i])?)
+        };
+
+        match (a.len(), b.len()) {
+            (2, 2) => det(0, 1),
+            (3, 3) => Ok(RuntimeTuple::from_vec(vec![
+                det(1, 2)?,
+                det(2, 0)?,
+                det(0, 1)?,
+            ])),
+            (a, b) => Err(RuntimeError::TypeMismatch(format!(
+                "Cannot take the cross product of vectors of lengths {a} and {b}, expected two 2D \
+                 or two 3D vectors"
+            ))),
+        }
+    }
+
+    fn vector_items(&self, method: Method) -> Result<RuntimeTuple, RuntimeError> {
+        match self {
+            RuntimeValue::Tuple(tuple) => Ok(tuple.clone()),
+            RuntimeValue::Vec2(v) => Ok(v.to_tuple()),
+            RuntimeValue::Vec3(v) => Ok(v.to_tuple()),
+            _ => Err(RuntimeError::invalid_method_for_type(method, self)),
+        }
+    }
+
     pub fn rot(&self, times: &Self) -> Result<Self, RuntimeError> {
         match self {
             RuntimeValue::Tuple(tuple) => Ok(RuntimeValue::Tuple(tuple.rot(times)?)),
             RuntimeValue::Vec2(v) => v.rot(times),
+            RuntimeValue::Vec3(v) => Ok(RuntimeValue::Tuple(v.to_tuple().rot(times)?)),
             _ => Err(RuntimeError::invalid_method_for_type(Method::Rot, self)),
         }
     }
@@ -0,0 +1,89 @@
+//! Structural comparison of values, for `deep_eq` and `approx_eq`.
+//!
+//! Unlike `==`, the comparison looks through the kind of sequence a value is stored as, so a
+//! tuple of floats can equal a `Vec2`, and it keeps track of the lists and maps it is inside of,
+//! so a list that contains itself can be compared without recursing forever.
+
+use rustc_hash::FxHashSet;
+
+use crate::vm::runtime_value::{number::RuntimeNumber, RuntimeValue};
+
+/// Whether `a` and `b` have the same structure and equal values.
+pub fn deep_eq(a: &RuntimeValue, b: &RuntimeValue) -> bool {
+    Comparison {
+        numbers_eq: |a: &RuntimeNumber, b: &RuntimeNumber| a == b,
+        entered: FxHashSet::default(),
+    }
+    .eq(a, b)
+}
+
+/// Like `deep_eq`, but numbers only have to be within `epsilon` of each other.
+pub fn approx_eq(a: &RuntimeValue, b: &RuntimeValue, epsilon: f64) -> bool {
+    Comparison {
+        numbers_eq: |a: &RuntimeNumber, b: &RuntimeNumber| {
+            a == b || (a.float() - b.float()).abs() <= epsilon
+        },
+        entered: FxHashSet::default(),
+    }
+    .eq(a, b)
+}
+
+struct Comparison<F> {
+    numbers_eq: F,
+    /// The addresses of the pairs of lists or maps currently being compared. Meeting a pair again
+    /// means the values are cyclic in the same way, and nothing so far tells them apart.
+    entered: FxHashSet<(usize, usize)>,
+}
+
+impl<F: Fn(&RuntimeNumber, &RuntimeNumber) -> bool> Comparison<F> {
+    fn eq(&mut self, a: &RuntimeValue, b: &RuntimeValue) -> bool {
+        match (a, b) {
+            (RuntimeValue::Num(a), RuntimeValue::Num(b)) => (self.numbers_eq)(a, b),
+            (RuntimeValue::List(list_a), RuntimeValue::List(list_b)) => {
+                self.enter(list_a.addr(), list_b.addr(), |this| {
+                    this.all_eq(&list_a.as_slice(), &list_b.as_slice())
+                })
+            }
+            (RuntimeValue::Deque(deque_a), RuntimeValue::Deque(deque_b)) => {
+                self.enter(deque_a.addr(), deque_b.addr(), |this| {
+                    this.all_eq(&deque_a.to_vec(), &deque_b.to_vec())
+                })
+            }
+            (RuntimeValue::Map(map_a), RuntimeValue::Map(map_b)) => {
+                self.enter(map_a.addr(), map_b.addr(), |this| {
+                    let (a, b) = (map_a.borrow(), map_b.borrow());
+                    a.len() == b.len()
+                        && a.iter()
+                            .all(|(key, val)| b.get(key).is_some_and(|other| this.eq(val, other)))
+                })
+            }
+            (
+                RuntimeValue::Tuple(_) | RuntimeValue::Vec2(_) | RuntimeValue::Vec3(_),
+                RuntimeValue::Tuple(_) | RuntimeValue::Vec2(_) | RuntimeValue::Vec3(_),
+            ) => self.all_eq(&tuple_items(a), &tuple_items(b)),
+            _ => a == b,
+        }
+    }
+
+    fn all_eq(&mut self, a: &[RuntimeValue], b: &[RuntimeValue]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.eq(a, b))
+    }
+
+    fn enter(&mut self, a: usize, b: usize, compare: impl FnOnce(&mut Self) -> bool) -> bool {
+        if !self.entered.insert((a, b)) {
+            return true;
+        }
+        let res = compare(self);
+        self.entered.remove(&(a, b));
+        res
+    }
+}
+
+fn tuple_items(value: &RuntimeValue) -> Vec<RuntimeValue> {
+    match value {
+        RuntimeValue::Tuple(tuple) => tuple.as_slice().to_vec(),
+        RuntimeValue::Vec2(vec) => vec.to_tuple().as_slice().to_vec(),
+        RuntimeValue::Vec3(vec) => vec.to_tuple().as_slice().to_vec(),
+        _ => unreachable!("only called on tuples"),
+    }
+}
@@ -0,0 +1,87 @@
+//! Finishing copies of lists and maps that hold containers.
+//!
+//! A copy of a list or map shares its values with the original until either of them is changed.
+//! The containers among those values can't simply be shared, though, since changing one through
+//! the copy would change the original as well. Instead of copying them right away, which would
+//! make copying a grid as slow as its size, the copy keeps sharing them and is left pending. As
+//! long as no container has been changed, a pending copy holds the same as a finished one would,
+//! so pending copies are finished right before any container is changed, and before a container is
+//! read out of one of them. Finishing a copy copies its containers the same way, which may leave
+//! new pending copies, so finishing goes on until none are left.
+
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+use crate::vm::runtime_value::{
+    list::RuntimeList,
+    map::{InnerRuntimeMap, RuntimeMap},
+    RuntimeValue,
+};
+
+thread_local! {
+    static PENDING: RefCell<Vec<Pending>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A copy that still shares its containers with the original, without keeping it alive.
+pub enum Pending {
+    List(Weak<RefCell<Rc<Vec<RuntimeValue>>>>),
+    Map(Weak<RefCell<Rc<InnerRuntimeMap>>>),
+}
+
+impl Pending {
+    fn is_alive(&self) -> bool {
+        match self {
+            Self::List(list) => list.strong_count() > 0,
+            Self::Map(map) => map.strong_count() > 0,
+        }
+    }
+}
+
+/// Leaves a copy pending until the next time copies are finished.
+pub fn defer(copy: Pending) {
+    PENDING.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        // Copies that are dropped before they are finished are cleared out before growing, so
+        // copying in a loop doesn't keep growing the list
+        if pending.len() == pending.capacity() {
+            pending.retain(Pending::is_alive);
+        }
+        pending.push(copy);
+    });
+}
+
+/// Finishes every pending copy. Must be called before changing a container, and before handing
+/// out a container held by a list or map.
+#[inline]
+pub fn finish() {
+    if PENDING.with(|pending| !pending.borrow().is_empty()) {
+        finish_all();
+    }
+}
+
+#[cold]
+fn finish_all() {
+    loop {
+        let pending = PENDING.with(|pending| std::mem::take(&mut *pending.borrow_mut()));
+        if pending.is_empty() {
+            break;
+        }
+
+        for copy in pending {
+            match copy {
+                Pending::List(list) => {
+                    if let Some(list) = list.upgrade() {
+                        RuntimeList::finish_copy(&list);
+                    }
+                }
+                Pending::Map(map) => {
+                    if let Some(map) = map.upgrade() {
+                        RuntimeMap::finish_copy(&map);
+                    }
+                }
+            }
+        }
+    }
+}
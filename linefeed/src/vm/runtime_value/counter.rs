@@ -4,9 +4,12 @@ use rustc_hash::FxHashMap;
 
 use crate::vm::{
     runtime_value::{
+        copies,
         iterator::RuntimeIterator,
+        list::RuntimeList,
         map::{MapIterator, RuntimeMap},
         number::RuntimeNumber,
+        tuple::RuntimeTuple,
         RuntimeValue,
     },
     RuntimeError,
@@ -42,6 +45,7 @@ impl RuntimeCounter {
     }
 
     pub fn borrow_mut(&self) -> std::cell::RefMut<'_, InnerRuntimeCounter> {
+        copies::finish();
         self.0.borrow_mut()
     }
 
@@ -89,6 +93,51 @@ impl RuntimeCounter {
         self.borrow().contains_key(key)
     }
 
+    /// The items with their counts as `(item, count)` tuples, with the highest counts first and
+    /// equal counts ordered by item. With `n`, only the first `n` are returned.
+    pub fn most_common(&self, n: Option<usize>) -> RuntimeList {
+        let counter = self.borrow();
+        let mut entries = counter.iter().collect::<Vec<_>>();
+        entries.sort_by(|(a, a_count), (b, b_count)| {
+            b_count
+                .cmp(a_count)
+                .then_with(|| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        });
+
+        let entries = entries
+            .into_iter()
+            .take(n.unwrap_or(usize::MAX))
+            .map(|(item, &count)| {
+                RuntimeTuple::from_vec(vec![
+                    item.clone(),
+                    RuntimeValue::Num(RuntimeNumber::from(count)),
+                ])
+            })
+            .collect();
+
+        RuntimeList::from_vec(entries)
+    }
+
+    /// A new counter with the counts of both counters added together.
+    pub fn sum(&self, other: &Self) -> Self {
+        let sum = self.deep_clone();
+        for (item, &count) in other.borrow().iter() {
+            sum.add(item.clone(), count);
+        }
+        sum
+    }
+
+    /// A new counter with the counts of `other` taken from this one. Like subtracting sets, only
+    /// the items with something left are kept.
+    pub fn difference(&self, other: &Self) -> Self {
+        let difference = self.deep_clone();
+        for (item, &count) in other.borrow().iter() {
+            difference.sub(item, count);
+        }
+        difference.borrow_mut().retain(|_, count| *count > 0);
+        difference
+    }
+
     pub fn values(&self) -> Vec<RuntimeValue> {
         self.borrow()
             .values()
@@ -137,11 +186,25 @@ impl std::hash::Hash for RuntimeCounter {
     }
 }
 
+// Counters are ordered like multisets: one is at most another if it has at most as many of each
+// item, so counters that each have more of some item can't be compared
 impl std::cmp::PartialOrd for RuntimeCounter {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let a = self.borrow();
-        let b = other.borrow();
-        a.len().partial_cmp(&b.len())
+        use std::cmp::Ordering;
+
+        let (a, b) = (self.borrow(), other.borrow());
+        let count = |counter: &InnerRuntimeCounter, item| counter.get(item).copied().unwrap_or(0);
+        let a_within_b = a.iter().all(|(item, &n)| n <= count(&b, item))
+            && b.iter().all(|(item, &n)| count(&a, item) <= n);
+        let b_within_a = b.iter().all(|(item, &n)| n <= count(&a, item))
+            && a.iter().all(|(item, &n)| count(&b, item) <= n);
+
+        match (a_within_b, b_within_a) {
+            (true, true) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            _ => None,
+        }
     }
 }
 
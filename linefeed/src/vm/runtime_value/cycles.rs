@@ -0,0 +1,324 @@
+//! Collecting reference cycles, which reference counting alone never frees, like a list that
+//! contains itself or a closure that captures the variable holding it.
+//!
+//! Every container that values can be stored in after it is created is tracked when it is
+//! allocated. Once enough of them have been allocated, the tracked containers that are still alive
+//! are collected by trial deletion: the references to each container from the containers reachable
+//! from the tracked ones are counted, and any container with more references than that is held
+//! from outside, e.g. by the VM's stack or by Rust code. Everything reachable from those is alive,
+//! and the rest can only be reached from itself, so the contents of those containers are dropped,
+//! which breaks their cycles.
+//!
+//! Collection can start whenever a container is allocated, also in the middle of a builtin. This
+//! is safe, as any container the builtin is using is either borrowed or reachable from a value the
+//! builtin holds, and both keep it alive. Counters, grids and iterators are not looked into, so the
+//! values in them are always considered alive.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::{Rc, Weak},
+};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::vm::runtime_value::{
+    function::{RuntimeFunction, Upvalue},
+    map::InnerRuntimeMap,
+    RuntimeValue,
+};
+
+// Collecting is skipped until this many containers have been allocated. It is kept small, as the
+// tracking keeps the allocation of a freed container around until the next collection.
+const MIN_THRESHOLD: usize = 1_000;
+
+thread_local! {
+    static TRACKED: RefCell<Vec<Tracked>> = const { RefCell::new(Vec::new()) };
+    // Doubles along with the containers that survive, so the time spent collecting stays
+    // proportional to the number of allocations
+    static THRESHOLD: Cell<usize> = const { Cell::new(MIN_THRESHOLD) };
+}
+
+/// A container that can end up in a cycle, without keeping it alive.
+pub enum Tracked {
+    List(Weak<RefCell<Rc<Vec<RuntimeValue>>>>),
+    Map(Weak<RefCell<Rc<InnerRuntimeMap>>>),
+    Set(Weak<RefCell<FxHashSet<RuntimeValue>>>),
+    Deque(Weak<RefCell<VecDeque<RuntimeValue>>>),
+    Upvalue(Weak<RefCell<Upvalue>>),
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Node> {
+        match self {
+            Self::List(list) => list.upgrade().map(Node::List),
+            Self::Map(map) => map.upgrade().map(Node::Map),
+            Self::Set(set) => set.upgrade().map(Node::Set),
+            Self::Deque(deque) => deque.upgrade().map(Node::Deque),
+            Self::Upvalue(upvalue) => upvalue.upgrade().map(Node::Upvalue),
+        }
+    }
+}
+
+/// Starts tracking a newly allocated container, collecting cycles first if enough containers have
+/// been allocated since the last collection.
+pub fn track(container: Tracked) {
+    let due = TRACKED.with(|tracked| {
+        let mut tracked = tracked.borrow_mut();
+        tracked.push(container);
+        tracked.len() >= THRESHOLD.get()
+    });
+
+    if due {
+        collect();
+    }
+}
+
+// Anything shared that references other values, including the immutable tuples and functions,
+// since a container only reachable through one of them is still alive if it is. The values of a
+// list or map are nodes of their own, as they can be shared by its copies.
+#[derive(Clone)]
+enum Node {
+    List(Rc<RefCell<Rc<Vec<RuntimeValue>>>>),
+    Map(Rc<RefCell<Rc<InnerRuntimeMap>>>),
+    Set(Rc<RefCell<FxHashSet<RuntimeValue>>>),
+    Deque(Rc<RefCell<VecDeque<RuntimeValue>>>),
+    Upvalue(Rc<RefCell<Upvalue>>),
+    Values(Rc<Vec<RuntimeValue>>),
+    MapEntries(Rc<InnerRuntimeMap>),
+    Function(Rc<RuntimeFunction>),
+    Upvalues(Rc<[Rc<RefCell<Upvalue>>]>),
+}
+
+impl Node {
+    fn of(val: &RuntimeValue) -> Option<Self> {
+        match val {
+            RuntimeValue::List(list) => Some(Self::List(list.rc().clone())),
+            RuntimeValue::Map(map) => Some(Self::Map(map.rc().clone())),
+            RuntimeValue::Set(set) => Some(Self::Set(set.rc().clone())),
+            RuntimeValue::Deque(deque) => Some(Self::Deque(deque.rc().clone())),
+            RuntimeValue::Tuple(tuple) => Some(Self::Values(tuple.rc().clone())),
+            RuntimeValue::Function(func) => Some(Self::Function(func.clone())),
+            _ => None,
+        }
+    }
+
+    fn addr(&self) -> usize {
+        match self {
+            Self::List(rc) => Rc::as_ptr(rc) as usize,
+            Self::Map(rc) => Rc::as_ptr(rc) as usize,
+            Self::Set(rc) => Rc::as_ptr(rc) as usize,
+            Self::Deque(rc) => Rc::as_ptr(rc) as usize,
+            Self::Upvalue(rc) => Rc::as_ptr(rc) as usize,
+            Self::Values(rc) => Rc::as_ptr(rc) as usize,
+            Self::MapEntries(rc) => Rc::as_ptr(rc) as usize,
+            Self::Function(rc) => Rc::as_ptr(rc) as usize,
+            Self::Upvalues(rc) => Rc::as_ptr(rc) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Self::List(rc) => Rc::strong_count(rc),
+            Self::Map(rc) => Rc::strong_count(rc),
+            Self::Set(rc) => Rc::strong_count(rc),
+            Self::Deque(rc) => Rc::strong_count(rc),
+            Self::Upvalue(rc) => Rc::strong_count(rc),
+            Self::Values(rc) => Rc::strong_count(rc),
+            Self::MapEntries(rc) => Rc::strong_count(rc),
+            Self::Function(rc) => Rc::strong_count(rc),
+            Self::Upvalues(rc) => Rc::strong_count(rc),
+        }
+    }
+
+    // Whether Rust code is using the container right now, in which case it must be alive
+    fn is_borrowed(&self) -> bool {
+        match self {
+            Self::List(rc) => rc.try_borrow_mut().is_err(),
+            Self::Map(rc) => rc.try_borrow_mut().is_err(),
+            Self::Set(rc) => rc.try_borrow_mut().is_err(),
+            Self::Deque(rc) => rc.try_borrow_mut().is_err(),
+            Self::Upvalue(rc) => rc.try_borrow_mut().is_err(),
+            Self::Values(_) | Self::MapEntries(_) | Self::Function(_) | Self::Upvalues(_) => false,
+        }
+    }
+
+    fn for_each_child(&self, mut f: impl FnMut(Node)) {
+        let visit = |val: &RuntimeValue, f: &mut dyn FnMut(Node)| {
+            if let Some(node) = Self::of(val) {
+                f(node);
+            }
+        };
+
+        match self {
+            Self::List(rc) => f(Self::Values(rc.borrow().clone())),
+            Self::Map(rc) => f(Self::MapEntries(rc.borrow().clone())),
+            Self::MapEntries(inner) => {
+                for (key, val) in &inner.map {
+                    visit(key, &mut f);
+                    visit(val, &mut f);
+                }
+                if let Some(val) = &inner.default_value {
+                    visit(val, &mut f);
+                }
+            }
+            Self::Set(rc) => rc.borrow().iter().for_each(|val| visit(val, &mut f)),
+            Self::Deque(rc) => rc.borrow().iter().for_each(|val| visit(val, &mut f)),
+            Self::Upvalue(rc) => {
+                if let Upvalue::Closed(val) = &*rc.borrow() {
+                    visit(val, &mut f);
+                }
+            }
+            Self::Values(rc) => rc.iter().for_each(|val| visit(val, &mut f)),
+            Self::Function(func) => {
+                if let Some(upvalues) = func.upvalues.rc() {
+                    f(Self::Upvalues(upvalues.clone()));
+                }
+            }
+            Self::Upvalues(rc) => {
+                for upvalue in rc.iter() {
+                    f(Self::Upvalue(upvalue.clone()));
+                }
+            }
+        }
+    }
+
+    // Moves the values out of a container, leaving it empty, and returns whether there was one.
+    // Immutable nodes are left as they are, as every cycle goes through at least one container.
+    // The values of a list or map are a node in the graph too, which keeps them alive until the
+    // graph is dropped.
+    fn take_contents(&self, junk: &mut Vec<RuntimeValue>) -> bool {
+        match self {
+            Self::List(rc) => drop(rc.take()),
+            Self::Map(rc) => drop(rc.take()),
+            Self::Set(rc) => junk.extend(rc.borrow_mut().drain()),
+            Self::Deque(rc) => junk.extend(rc.borrow_mut().drain(..)),
+            Self::Upvalue(rc) => {
+                if let Upvalue::Closed(val) = rc.replace(Upvalue::Closed(RuntimeValue::Null)) {
+                    junk.push(val);
+                }
+            }
+            Self::Values(_) | Self::MapEntries(_) | Self::Function(_) | Self::Upvalues(_) => {
+                return false
+            }
+        }
+        true
+    }
+}
+
+struct Entry {
+    node: Node,
+    // References to the node from other nodes
+    internal_refs: usize,
+    borrowed: bool,
+    alive: bool,
+}
+
+#[derive(Default)]
+struct Graph {
+    entries: FxHashMap<usize, Entry>,
+    to_scan: Vec<usize>,
+}
+
+impl Graph {
+    fn add(&mut self, node: Node) -> usize {
+        let addr = node.addr();
+        self.entries.entry(addr).or_insert_with(|| {
+            self.to_scan.push(addr);
+            Entry {
+                node,
+                internal_refs: 0,
+                borrowed: false,
+                alive: false,
+            }
+        });
+        addr
+    }
+
+    // Counts the references between all nodes reachable from the ones added so far
+    fn scan(&mut self) {
+        while let Some(addr) = self.to_scan.pop() {
+            let node = self.entries[&addr].node.clone();
+            if node.is_borrowed() {
+                self.entries.get_mut(&addr).unwrap().borrowed = true;
+                continue;
+            }
+            node.for_each_child(|child| {
+                let child = self.add(child);
+                self.entries.get_mut(&child).unwrap().internal_refs += 1;
+            });
+        }
+    }
+
+    // Every node holds one more reference from the graph itself, and any other reference from
+    // outside of the nodes keeps it and everything it reaches alive
+    fn mark_alive(&mut self) {
+        let mut to_mark: Vec<usize> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.borrowed || entry.node.strong_count() > entry.internal_refs + 1
+            })
+            .map(|(&addr, _)| addr)
+            .collect();
+
+        while let Some(addr) = to_mark.pop() {
+            let entry = self.entries.get_mut(&addr).unwrap();
+            if entry.alive {
+                continue;
+            }
+            entry.alive = true;
+            // The children of a borrowed node were never added, and are alive on their own
+            if entry.borrowed {
+                continue;
+            }
+            entry.node.clone().for_each_child(|child| {
+                let child = child.addr();
+                if !self.entries[&child].alive {
+                    to_mark.push(child);
+                }
+            });
+        }
+    }
+}
+
+/// Frees the tracked containers that are only reachable from themselves, returning how many there
+/// were. Called automatically as containers are allocated, but can be called at any time.
+pub fn collect() -> usize {
+    let mut tracked = TRACKED.with(|tracked| std::mem::take(&mut *tracked.borrow_mut()));
+
+    let mut graph = Graph::default();
+    for node in tracked.iter().filter_map(Tracked::upgrade) {
+        graph.add(node);
+    }
+    graph.scan();
+    graph.mark_alive();
+
+    let mut junk = Vec::new();
+    let mut garbage = 0;
+    for entry in graph.entries.values().filter(|entry| !entry.alive) {
+        if entry.node.take_contents(&mut junk) {
+            garbage += 1;
+        }
+    }
+
+    tracked.retain(|container| {
+        container
+            .upgrade()
+            .is_some_and(|node| graph.entries[&node.addr()].alive)
+    });
+
+    // The cycles are broken, so dropping the nodes frees them
+    drop(graph);
+    drop(junk);
+
+    THRESHOLD.set(MIN_THRESHOLD.max(tracked.len() * 2));
+    TRACKED.with(|cur| {
+        // Keep the capacity, which is the same from one collection to the next
+        let mut cur = cur.borrow_mut();
+        tracked.append(&mut cur);
+        *cur = tracked;
+    });
+
+    garbage
+}
@@ -0,0 +1,128 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use crate::vm::{
+    runtime_value::{
+        copies,
+        cycles::{self, Tracked},
+        number::RuntimeNumber,
+        operations::LfAppend,
+        utils::resolve_index,
+        RuntimeValue,
+    },
+    RuntimeError,
+};
+
+/// A double-ended queue, which pushes and pops at both ends in constant time. Made for the queue of
+/// a breadth-first search, where a list would have to shift every item to take the first one.
+#[derive(Debug, Clone)]
+pub struct RuntimeDeque(Rc<RefCell<VecDeque<RuntimeValue>>>);
+
+impl RuntimeDeque {
+    pub fn new() -> Self {
+        Self::from_vec(Vec::new())
+    }
+
+    pub fn from_vec(vec: Vec<RuntimeValue>) -> Self {
+        Self::from_deque(VecDeque::from(vec))
+    }
+
+    fn from_deque(deque: VecDeque<RuntimeValue>) -> Self {
+        let deque = Rc::new(RefCell::new(deque));
+        cycles::track(Tracked::Deque(Rc::downgrade(&deque)));
+        Self(deque)
+    }
+
+    /// The shared storage, for counting the references to it when collecting cycles.
+    pub fn rc(&self) -> &Rc<RefCell<VecDeque<RuntimeValue>>> {
+        &self.0
+    }
+
+    pub fn to_vec(&self) -> Vec<RuntimeValue> {
+        self.0.borrow().iter().cloned().collect()
+    }
+
+    /// Identifies the deque, which is shared by every value referring to it.
+    pub fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    pub fn deep_clone(&self) -> Self {
+        Self::from_deque(self.0.borrow().iter().map(|v| v.deep_clone()).collect())
+    }
+
+    pub fn index(&self, index: &RuntimeNumber) -> Result<RuntimeValue, RuntimeError> {
+        let i = resolve_index(self.len(), index)?;
+        Ok(self.0.borrow()[i].clone())
+    }
+
+    pub fn contains(&self, value: &RuntimeValue) -> bool {
+        self.0.borrow().contains(value)
+    }
+
+    pub fn push_front(&self, value: RuntimeValue) {
+        copies::finish();
+        self.0.borrow_mut().push_front(value);
+    }
+
+    pub fn push_back(&self, value: RuntimeValue) {
+        copies::finish();
+        self.0.borrow_mut().push_back(value);
+    }
+
+    pub fn pop_front(&self) -> Result<RuntimeValue, RuntimeError> {
+        copies::finish();
+        self.0
+            .borrow_mut()
+            .pop_front()
+            .ok_or_else(|| RuntimeError::Plain("Cannot pop from an empty deque".to_string()))
+    }
+
+    pub fn pop_back(&self) -> Result<RuntimeValue, RuntimeError> {
+        copies::finish();
+        self.0
+            .borrow_mut()
+            .pop_back()
+            .ok_or_else(|| RuntimeError::Plain("Cannot pop from an empty deque".to_string()))
+    }
+}
+
+impl Default for RuntimeDeque {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for RuntimeDeque {
+    fn eq(&self, other: &Self) -> bool {
+        *self.0.borrow() == *other.0.borrow()
+    }
+}
+
+impl Eq for RuntimeDeque {}
+
+impl std::hash::Hash for RuntimeDeque {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.borrow().hash(state);
+    }
+}
+
+impl std::cmp::PartialOrd for RuntimeDeque {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.0.borrow().partial_cmp(&other.0.borrow())
+    }
+}
+
+impl LfAppend for RuntimeDeque {
+    fn append(&mut self, other: RuntimeValue) -> Result<(), RuntimeError> {
+        self.push_back(other);
+        Ok(())
+    }
+}
@@ -1,15 +1,105 @@
-use crate::vm::runtime_value::RuntimeValue;
+use std::{cell::RefCell, hash::Hash, rc::Rc};
+
+use crate::vm::{runtime_value::RuntimeValue, RuntimeError};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RuntimeFunction<L = usize> {
     pub arity: usize,
+    /// The number of arguments without default values, which a call must give at least.
+    pub min_arity: usize,
     pub location: L,
     pub is_memoized: bool,
-    // TODO: Support default arguments
+    pub upvalues: Upvalues,
+}
+
+impl<L> RuntimeFunction<L> {
+    pub fn check_num_args(&self, num_args: usize) -> Result<(), RuntimeError> {
+        if (self.min_arity..=self.arity).contains(&num_args) {
+            return Ok(());
+        }
+
+        let expected = if self.min_arity == self.arity {
+            self.arity.to_string()
+        } else {
+            format!("{} to {}", self.min_arity, self.arity)
+        };
+        Err(RuntimeError::TypeMismatch(format!(
+            "Expected {expected} arguments, got {num_args}"
+        )))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MemoizationKey {
     pub func_location: usize,
+    // Closures created by the same `fn` expression can capture different values, so their results
+    // are cached separately
+    pub upvalues: Upvalues,
     pub args: Vec<RuntimeValue>,
 }
+
+/// Where a closure finds a variable it captures when it is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    /// A local variable of the enclosing function, by its offset from the base pointer.
+    Local(usize),
+    /// A variable that the enclosing function has itself captured, by its index in the enclosing
+    /// function's upvalues.
+    Upvalue(usize),
+}
+
+/// A variable captured by a closure.
+///
+/// While the function owning the variable is running, the upvalue is open and refers to the
+/// variable's slot on the stack, so the function and its closures see each other's changes. When
+/// the function returns, the upvalue is closed and takes over the value, keeping it alive for as
+/// long as the closures need it.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(RuntimeValue),
+}
+
+/// The upvalues of a function, shared between all copies of the function value. Two functions
+/// only compare equal if they share their upvalues.
+#[derive(Debug, Clone, Default)]
+pub struct Upvalues(Option<Rc<[Rc<RefCell<Upvalue>>]>>);
+
+impl Upvalues {
+    pub fn new(upvalues: Vec<Rc<RefCell<Upvalue>>>) -> Self {
+        if upvalues.is_empty() {
+            Self::default()
+        } else {
+            Self(Some(upvalues.into()))
+        }
+    }
+
+    pub fn get(&self, index: usize) -> &Rc<RefCell<Upvalue>> {
+        &self.0.as_ref().expect("function has no upvalues")[index]
+    }
+
+    /// The shared upvalues, for counting the references to them when collecting cycles.
+    pub fn rc(&self) -> Option<&Rc<[Rc<RefCell<Upvalue>>]>> {
+        self.0.as_ref()
+    }
+
+    fn ptr(&self) -> *const () {
+        self.0.as_ref().map_or(std::ptr::null(), |upvalues| {
+            Rc::as_ptr(upvalues) as *const ()
+        })
+    }
+}
+
+impl PartialEq for Upvalues {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr() == other.ptr()
+    }
+}
+
+impl Eq for Upvalues {}
+
+impl Hash for Upvalues {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.ptr().hash(state);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::vm::{
+    runtime_value::{
+        copies, list::RuntimeList, string::RuntimeString, tuple::RuntimeTuple, vec2::RuntimeVec2,
+        RuntimeValue,
+    },
+    RuntimeError,
+};
+
+/// A rectangular grid of cells, indexed by `(x, y)` positions where `x` is the column and `y` is
+/// the row, counting from the top left.
+#[derive(Debug, Clone)]
+pub struct RuntimeGrid(Rc<RefCell<InnerRuntimeGrid>>);
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd)]
+struct InnerRuntimeGrid {
+    width: usize,
+    height: usize,
+    /// The cells row by row.
+    cells: Vec<RuntimeValue>,
+}
+
+const NEIGHBORS4: [(i32, i32); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const NEIGHBORS8: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+impl RuntimeGrid {
+    /// Creates a grid from a list of rows, which are strings of cells or lists of them.
+    pub fn from_rows(rows: &RuntimeList) -> Result<Self, RuntimeError> {
+        let mut cells = Vec::new();
+        let mut width = None;
+
+        for row in rows.as_slice().iter() {
+            let row = match row {
+                RuntimeValue::List(l) => l.as_slice().to_vec(),
+                RuntimeValue::Str(s) => s
+                    .as_str()
+                    .chars()
+                    .map(|c| RuntimeValue::Str(RuntimeString::new(c)))
+                    .collect(),
+                _ => {
+                    return Err(RuntimeError::TypeMismatch(format!(
+                        "Cannot create grid: rows must be lists or strings, got '{}'",
+                        row.kind_str()
+                    )))
+                }
+            };
+
+            match width {
+                Some(width) if width != row.len() => {
+                    return Err(RuntimeError::Plain(format!(
+                        "Cannot create grid: rows must have the same length, got {width} and {}",
+                        row.len()
+                    )))
+                }
+                _ => width = Some(row.len()),
+            }
+            cells.extend(row);
+        }
+
+        if i32::try_from(cells.len()).is_err() {
+            return Err(RuntimeError::Plain("Grid is too large".to_string()));
+        }
+
+        Ok(Self(Rc::new(RefCell::new(InnerRuntimeGrid {
+            width: width.unwrap_or(0),
+            height: rows.len(),
+            cells,
+        }))))
+    }
+
+    pub fn width(&self) -> usize {
+        self.0.borrow().width
+    }
+
+    pub fn height(&self) -> usize {
+        self.0.borrow().height
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Identifies the grid, which is shared by every value referring to it.
+    pub fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+
+    pub fn deep_clone(&self) -> Self {
+        let inner = self.0.borrow();
+        Self(Rc::new(RefCell::new(InnerRuntimeGrid {
+            width: inner.width,
+            height: inner.height,
+            cells: inner.cells.iter().map(|v| v.deep_clone()).collect(),
+        })))
+    }
+
+    pub fn get(&self, pos: &RuntimeValue) -> Result<RuntimeValue, RuntimeError> {
+        let i = self.cell_index(pos)?;
+        Ok(self.0.borrow().cells[i].clone())
+    }
+
+    pub fn set(&self, pos: &RuntimeValue, value: RuntimeValue) -> Result<(), RuntimeError> {
+        let i = self.cell_index(pos)?;
+        copies::finish();
+        self.0.borrow_mut().cells[i] = value;
+        Ok(())
+    }
+
+    /// The positions next to `pos` horizontally and vertically that are inside the grid.
+    pub fn neighbors4(&self, pos: &RuntimeValue) -> Result<RuntimeList, RuntimeError> {
+        self.neighbors(pos, &NEIGHBORS4)
+    }
+
+    /// The positions next to `pos`, diagonals included, that are inside the grid.
+    pub fn neighbors8(&self, pos: &RuntimeValue) -> Result<RuntimeList, RuntimeError> {
+        self.neighbors(pos, &NEIGHBORS8)
+    }
+
+    fn neighbors(
+        &self,
+        pos: &RuntimeValue,
+        offsets: &[(i32, i32)],
+    ) -> Result<RuntimeList, RuntimeError> {
+        let (x, y) = position(pos)?;
+        let neighbors = offsets
+            .iter()
+            .map(|(dx, dy)| (x + dx, y + dy))
+            .filter(|&(x, y)| self.contains(x, y))
+            .map(|(x, y)| RuntimeValue::Vec2(RuntimeVec2::new(x, y)))
+            .collect();
+
+        Ok(RuntimeList::from_vec(neighbors))
+    }
+
+    /// Each cell as a `(position, value)` tuple, row by row.
+    pub fn items(&self) -> Vec<RuntimeValue> {
+        let inner = self.0.borrow();
+        inner
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let pos = RuntimeVec2::new((i % inner.width) as i32, (i / inner.width) as i32);
+                RuntimeTuple::from_vec(vec![RuntimeValue::Vec2(pos), cell.clone()])
+            })
+            .collect()
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width() && (y as usize) < self.height()
+    }
+
+    fn cell_index(&self, pos: &RuntimeValue) -> Result<usize, RuntimeError> {
+        let (x, y) = position(pos)?;
+        let (width, height) = (self.width(), self.height());
+
+        if x < 0 || x as usize >= width {
+            return Err(RuntimeError::IndexOutOfBounds(x as isize, width));
+        }
+        if y < 0 || y as usize >= height {
+            return Err(RuntimeError::IndexOutOfBounds(y as isize, height));
+        }
+
+        Ok(y as usize * width + x as usize)
+    }
+}
+
+fn position(pos: &RuntimeValue) -> Result<(i32, i32), RuntimeError> {
+    match pos {
+        RuntimeValue::Vec2(v) => Ok((v.x, v.y)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "Grid positions must be integer (x, y) pairs, got {}",
+            pos.repr_string()
+        ))),
+    }
+}
+
+impl PartialEq for RuntimeGrid {
+    fn eq(&self, other: &Self) -> bool {
+        *self.0.borrow() == *other.0.borrow()
+    }
+}
+
+impl Eq for RuntimeGrid {}
+
+impl std::hash::Hash for RuntimeGrid {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.borrow().hash(state);
+    }
+}
+
+impl std::cmp::PartialOrd for RuntimeGrid {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.0.borrow().partial_cmp(&other.0.borrow())
+    }
+}
+
+// Printed row by row like the input it usually comes from
+impl fmt::Display for RuntimeGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.0.borrow();
+        for (y, row) in inner.cells.chunks(inner.width.max(1)).enumerate() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+            for cell in row {
+                write!(f, "{cell}")?;
+            }
+        }
+        Ok(())
+    }
+}
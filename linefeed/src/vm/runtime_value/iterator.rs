@@ -1,6 +1,7 @@
 use std::{cell::RefCell, convert::identity, rc::Rc};
 
 use crate::vm::runtime_value::{
+    copies,
     counter::RuntimeCounter,
     list::RuntimeList,
     map::{MapIterator, RuntimeMap},
@@ -21,14 +22,17 @@ enum IteratorKind {
     Range(RangeIterator),
     Map(MapIterator),
     Set(SetIterator),
-    Enumerated(EnumeratedListIterator),
-    EnumeratedString(EnumeratedStringIterator),
+    Enumerated(EnumeratedIterator),
+    Permutations(PermutationsIterator),
+    Combinations(CombinationsIterator),
     String(StringIterator),
     Empty,
 }
 
 impl RuntimeIterator {
     pub fn next(&self) -> Option<RuntimeValue> {
+        // Advancing changes the iterator, which a pending copy may still share
+        copies::finish();
         match &mut *self.0.borrow_mut() {
             IteratorKind::List(iter) => iter.next(),
             IteratorKind::Tuple(iter) => iter.next(),
@@ -36,12 +40,68 @@ impl RuntimeIterator {
             IteratorKind::Map(iter) => iter.next(),
             IteratorKind::Set(iter) => iter.next(),
             IteratorKind::Enumerated(iter) => iter.next(),
-            IteratorKind::EnumeratedString(iter) => iter.next(),
+            IteratorKind::Permutations(iter) => iter.next(),
+            IteratorKind::Combinations(iter) => iter.next(),
             IteratorKind::String(iter) => iter.next(),
             IteratorKind::Empty => None,
         }
     }
 
+    /// An iterator over copies of the values this one has left, which leaves this one where it
+    /// was. Map and set iterators can't be duplicated, so their values are collected first and
+    /// both iterators continue over those.
+    pub fn deep_clone(&self) -> Self {
+        if matches!(
+            &*self.0.borrow(),
+            IteratorKind::Map(_) | IteratorKind::Set(_)
+        ) {
+            let rest = self.to_vec();
+            let copy = rest.iter().map(RuntimeValue::deep_clone).collect();
+            *self.0.borrow_mut() = IteratorKind::List(ListIterator {
+                list: RuntimeList::from_vec(rest),
+                index: 0,
+            });
+            return Self::from(RuntimeList::from_vec(copy));
+        }
+
+        let copy = match &*self.0.borrow() {
+            IteratorKind::List(iter) => IteratorKind::List(ListIterator {
+                list: RuntimeList::from_vec(
+                    iter.list.as_slice()[iter.index.min(iter.list.len())..]
+                        .iter()
+                        .map(RuntimeValue::deep_clone)
+                        .collect(),
+                ),
+                index: 0,
+            }),
+            IteratorKind::Tuple(iter) => IteratorKind::Tuple(TupleIterator {
+                tuple: iter.tuple.deep_clone(),
+                index: iter.index,
+            }),
+            IteratorKind::Range(iter) => IteratorKind::Range(iter.clone()),
+            IteratorKind::Enumerated(iter) => IteratorKind::Enumerated(EnumeratedIterator {
+                inner: iter.inner.deep_clone(),
+                index: iter.index.clone(),
+            }),
+            IteratorKind::Permutations(iter) => IteratorKind::Permutations(PermutationsIterator {
+                pool: iter.pool.iter().map(RuntimeValue::deep_clone).collect(),
+                k: iter.k,
+                indices: iter.indices.clone(),
+                cycles: iter.cycles.clone(),
+                remaining: iter.remaining,
+            }),
+            IteratorKind::Combinations(iter) => IteratorKind::Combinations(CombinationsIterator {
+                pool: iter.pool.iter().map(RuntimeValue::deep_clone).collect(),
+                indices: iter.indices.clone(),
+                remaining: iter.remaining,
+            }),
+            IteratorKind::String(iter) => IteratorKind::String(iter.clone()),
+            IteratorKind::Map(_) | IteratorKind::Set(_) => unreachable!("collected above"),
+            IteratorKind::Empty => IteratorKind::Empty,
+        };
+        Self(Rc::new(RefCell::new(copy)))
+    }
+
     pub fn to_vec(&self) -> Vec<RuntimeValue> {
         self.map_to_vec(identity)
     }
@@ -53,8 +113,9 @@ impl RuntimeIterator {
             IteratorKind::Range(iter) => iter.len().unwrap_or(usize::MAX),
             IteratorKind::Map(iter) => iter.len(),
             IteratorKind::Set(iter) => iter.len(),
-            IteratorKind::Enumerated(iter) => iter.list.len().saturating_sub(iter.index),
-            IteratorKind::EnumeratedString(iter) => iter.string.len().saturating_sub(iter.index),
+            IteratorKind::Enumerated(iter) => iter.inner.len(),
+            IteratorKind::Permutations(iter) => iter.remaining,
+            IteratorKind::Combinations(iter) => iter.remaining,
             IteratorKind::String(iter) => iter.chars.len().saturating_sub(iter.index),
             IteratorKind::Empty => 0,
         }
@@ -64,6 +125,69 @@ impl RuntimeIterator {
         self.len() == 0
     }
 
+    /// How many values are left, if that is known without consuming the iterator. Only lists,
+    /// tuples and bounded ranges know, since the others are rarely turned into big lists.
+    pub fn size_hint(&self) -> Option<usize> {
+        match &*self.0.borrow() {
+            IteratorKind::List(_) | IteratorKind::Tuple(_) => Some(self.len()),
+            IteratorKind::Range(iter) => iter.len(),
+            IteratorKind::Enumerated(iter) => iter.inner.size_hint(),
+            _ => None,
+        }
+    }
+
+    /// Skips the next `n` values. Lists, tuples and ranges jump straight past them, while other
+    /// iterators have to produce each of them.
+    pub fn skip(&self, n: usize) {
+        copies::finish();
+        let skipped = match &mut *self.0.borrow_mut() {
+            IteratorKind::List(iter) => {
+                iter.index = iter.index.saturating_add(n);
+                true
+            }
+            IteratorKind::Tuple(iter) => {
+                iter.index = iter.index.saturating_add(n);
+                true
+            }
+            IteratorKind::Enumerated(iter) => {
+                iter.inner.skip(n);
+                iter.index = &iter.index + &RuntimeNumber::from(n);
+                true
+            }
+            IteratorKind::Range(iter) => {
+                iter.skip(n);
+                true
+            }
+            _ => false,
+        };
+
+        if !skipped {
+            for _ in 0..n {
+                if self.next().is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Consumes the iterator, returning how many values it had left, or `None` if it never ends.
+    pub fn count_remaining(&self) -> Option<usize> {
+        let known_len = match &*self.0.borrow() {
+            IteratorKind::List(_) | IteratorKind::Tuple(_) => Some(self.len()),
+            IteratorKind::Range(iter) => Some(iter.len()?),
+            IteratorKind::Enumerated(iter) => iter.inner.size_hint(),
+            _ => None,
+        };
+
+        match known_len {
+            Some(len) => {
+                self.skip(len);
+                Some(len)
+            }
+            None => Some(self.fold(0, |count, _| count + 1)),
+        }
+    }
+
     pub fn map_to_vec<F>(&self, f: F) -> Vec<RuntimeValue>
     where
         F: Fn(RuntimeValue) -> RuntimeValue,
@@ -107,7 +231,7 @@ impl Iterator for ListIterator {
     type Item = RuntimeValue;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let value = self.list.as_slice().get(self.index).cloned();
+        let value = self.list.get(self.index);
         self.index += 1;
         value
     }
@@ -128,57 +252,160 @@ impl Iterator for TupleIterator {
     }
 }
 
-pub struct EnumeratedListIterator {
-    list: RuntimeList,
-    index: usize,
+/// Pairs each value of another iterator with a count, as `(index, value)` tuples.
+pub struct EnumeratedIterator {
+    inner: RuntimeIterator,
+    index: RuntimeNumber,
 }
 
-impl EnumeratedListIterator {
-    pub fn new(list: RuntimeList) -> Self {
-        Self { list, index: 0 }
+impl EnumeratedIterator {
+    pub fn new(inner: RuntimeIterator, start: RuntimeNumber) -> Self {
+        Self {
+            inner,
+            index: start,
+        }
     }
 }
 
-impl Iterator for EnumeratedListIterator {
+impl Iterator for EnumeratedIterator {
     type Item = RuntimeValue;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let value = self.list.as_slice().get(self.index).cloned()?;
-        let index_val = RuntimeValue::Num(RuntimeNumber::from(self.index));
-        let enumerated = RuntimeValue::from((index_val, value));
-        self.index += 1;
-        Some(enumerated)
+        let value = self.inner.next()?;
+        let next_index = &self.index + &RuntimeNumber::from(1);
+        let index = std::mem::replace(&mut self.index, next_index);
+        Some(RuntimeValue::from((RuntimeValue::Num(index), value)))
     }
 }
 
-pub struct EnumeratedStringIterator {
-    string: RuntimeString,
-    index: usize,
+/// The orderings of `k` of the values in a pool, in lexicographic order of their positions in the
+/// pool.
+pub struct PermutationsIterator {
+    pool: Vec<RuntimeValue>,
+    k: usize,
+    /// The positions in the pool, where the first `k` are the next permutation
+    indices: Vec<usize>,
+    /// How many more times each of the first `k` positions will change before the one before it
+    cycles: Vec<usize>,
+    remaining: usize,
 }
 
-impl EnumeratedStringIterator {
-    pub fn new(s: RuntimeString) -> Self {
+impl PermutationsIterator {
+    pub fn new(pool: Vec<RuntimeValue>, k: usize) -> Self {
+        let n = pool.len();
+        let remaining = if k > n {
+            0
+        } else {
+            (n - k + 1..=n).fold(1usize, usize::saturating_mul)
+        };
+
         Self {
-            string: s,
-            index: 0,
+            indices: (0..n).collect(),
+            cycles: (n.saturating_sub(k) + 1..=n).rev().collect(),
+            pool,
+            k,
+            remaining,
         }
     }
+
+    fn advance(&mut self) -> bool {
+        let n = self.pool.len();
+        for i in (0..self.k).rev() {
+            self.cycles[i] -= 1;
+            if self.cycles[i] == 0 {
+                self.indices[i..].rotate_left(1);
+                self.cycles[i] = n - i;
+            } else {
+                self.indices.swap(i, n - self.cycles[i]);
+                return true;
+            }
+        }
+
+        false
+    }
 }
 
-impl Iterator for EnumeratedStringIterator {
+impl Iterator for PermutationsIterator {
     type Item = RuntimeValue;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // Use byte indexing (ASCII assumption per string.rs:103-104)
-        let byte = *self.string.as_str().as_bytes().get(self.index)?;
-        let ch = RuntimeString::new(char::from(byte));
-        let index_val = RuntimeValue::Num(RuntimeNumber::from(self.index));
-        let enumerated = RuntimeValue::from((index_val, RuntimeValue::Str(ch)));
-        self.index += 1;
-        Some(enumerated)
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let items = self.indices[..self.k]
+            .iter()
+            .map(|&i| self.pool[i].clone())
+            .collect();
+        self.remaining -= 1;
+        if !self.advance() {
+            self.remaining = 0;
+        }
+        Some(RuntimeTuple::from_vec(items))
+    }
+}
+
+/// The selections of `k` of the values in a pool, each keeping the order of the pool.
+pub struct CombinationsIterator {
+    pool: Vec<RuntimeValue>,
+    /// The positions in the pool of the next combination, in increasing order
+    indices: Vec<usize>,
+    remaining: usize,
+}
+
+impl CombinationsIterator {
+    pub fn new(pool: Vec<RuntimeValue>, k: usize) -> Self {
+        let n = pool.len();
+        let remaining = if k > n {
+            0
+        } else {
+            // After each step, the count is the number of ways to pick `i + 1` of the values
+            let count = (0..k.min(n - k)).try_fold(1u128, |count, i| {
+                let count = count * (n - i) as u128 / (i + 1) as u128;
+                (count <= usize::MAX as u128).then_some(count)
+            });
+            count.map_or(usize::MAX, |count| count as usize)
+        };
+
+        Self {
+            pool,
+            indices: (0..k).collect(),
+            remaining,
+        }
+    }
+
+    fn advance(&mut self) -> bool {
+        let (n, k) = (self.pool.len(), self.indices.len());
+        let Some(i) = (0..k).rev().find(|&i| self.indices[i] != i + n - k) else {
+            return false;
+        };
+
+        self.indices[i] += 1;
+        for j in i + 1..k {
+            self.indices[j] = self.indices[j - 1] + 1;
+        }
+        true
     }
 }
 
+impl Iterator for CombinationsIterator {
+    type Item = RuntimeValue;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let items = self.indices.iter().map(|&i| self.pool[i].clone()).collect();
+        self.remaining -= 1;
+        if !self.advance() {
+            self.remaining = 0;
+        }
+        Some(RuntimeTuple::from_vec(items))
+    }
+}
+
+#[derive(Clone)]
 pub struct StringIterator {
     chars: Vec<RuntimeString>,
     index: usize,
@@ -235,15 +462,21 @@ impl From<RuntimeString> for RuntimeIterator {
     }
 }
 
-impl From<EnumeratedListIterator> for RuntimeIterator {
-    fn from(iter: EnumeratedListIterator) -> Self {
+impl From<EnumeratedIterator> for RuntimeIterator {
+    fn from(iter: EnumeratedIterator) -> Self {
         Self(Rc::new(RefCell::new(IteratorKind::Enumerated(iter))))
     }
 }
 
-impl From<EnumeratedStringIterator> for RuntimeIterator {
-    fn from(iter: EnumeratedStringIterator) -> Self {
-        Self(Rc::new(RefCell::new(IteratorKind::EnumeratedString(iter))))
+impl From<PermutationsIterator> for RuntimeIterator {
+    fn from(iter: PermutationsIterator) -> Self {
+        Self(Rc::new(RefCell::new(IteratorKind::Permutations(iter))))
+    }
+}
+
+impl From<CombinationsIterator> for RuntimeIterator {
+    fn from(iter: CombinationsIterator) -> Self {
+        Self(Rc::new(RefCell::new(IteratorKind::Combinations(iter))))
     }
 }
 
@@ -1,5 +1,5 @@
 use std::{
-    cell::{Ref, RefCell},
+    cell::{Ref, RefCell, RefMut},
     rc::Rc,
 };
 
@@ -7,29 +7,72 @@ use rustc_hash::FxHashMap;
 
 use crate::vm::{
     runtime_value::{
+        copies::{self, Pending},
+        cycles::{self, Tracked},
+        map::RuntimeMap,
         number::RuntimeNumber,
         operations::LfAppend,
         range::RuntimeRange,
+        string::RuntimeString,
         utils::{resolve_index, resolve_slice_indices},
+        vec2::RuntimeVec2,
         RuntimeValue,
     },
     RuntimeError,
 };
 
+/// A list is shared by every value referring to it, while copies of it share its values until
+/// either of them is changed, so copying a list is cheap. The containers in a list are copied
+/// along with it, but only once a container is changed or read out of the copy (see
+/// `copies`), so copying a nested list is cheap too.
 #[derive(Debug, Clone)]
-pub struct RuntimeList(Rc<RefCell<Vec<RuntimeValue>>>);
+pub struct RuntimeList(Rc<RefCell<Rc<Vec<RuntimeValue>>>>);
 
 impl RuntimeList {
     pub fn new() -> Self {
         Self::from_vec(Vec::new())
     }
 
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from_vec(Vec::with_capacity(capacity))
+    }
+
     pub fn from_vec(vec: Vec<RuntimeValue>) -> Self {
-        Self(Rc::new(RefCell::new(vec)))
+        Self::from_values(Rc::new(vec))
+    }
+
+    fn from_values(values: Rc<Vec<RuntimeValue>>) -> Self {
+        let list = Rc::new(RefCell::new(values));
+        cycles::track(Tracked::List(Rc::downgrade(&list)));
+        Self(list)
+    }
+
+    /// The shared storage, for counting the references to it when collecting cycles.
+    pub fn rc(&self) -> &Rc<RefCell<Rc<Vec<RuntimeValue>>>> {
+        &self.0
     }
 
     pub fn as_slice(&self) -> Ref<'_, [RuntimeValue]> {
-        Ref::map(self.0.borrow(), |v| v.as_slice())
+        Ref::map(self.values(), |v| v.as_slice())
+    }
+
+    // The containers in the list can be changed through the values handed out, so the list must
+    // not be a pending copy
+    fn values(&self) -> Ref<'_, Vec<RuntimeValue>> {
+        copies::finish();
+        Ref::map(self.0.borrow(), |v| &**v)
+    }
+
+    // Values shared with a copy of the list are copied before changing them, which only copies the
+    // handles, since the copies are finished first
+    fn values_mut(&self) -> RefMut<'_, Vec<RuntimeValue>> {
+        copies::finish();
+        RefMut::map(self.0.borrow_mut(), Rc::make_mut)
+    }
+
+    /// Identifies the list, which is shared by every value referring to it.
+    pub fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
     }
 
     pub fn len(&self) -> usize {
@@ -41,25 +84,41 @@ impl RuntimeList {
     }
 
     pub fn deep_clone(&self) -> Self {
-        Self::from_vec(self.0.borrow().iter().map(|v| v.deep_clone()).collect())
+        let copy = Self::from_values(Rc::clone(&self.0.borrow()));
+        if !copy.is_empty() {
+            copies::defer(Pending::List(Rc::downgrade(&copy.0)));
+        }
+        copy
+    }
+
+    // Containers could be changed through either copy, so each copy gets its own, while values
+    // that can't be changed stay shared
+    pub(super) fn finish_copy(list: &RefCell<Rc<Vec<RuntimeValue>>>) {
+        let values = Rc::clone(&list.borrow());
+        if values.iter().all(RuntimeValue::is_immutable) {
+            return;
+        }
+        let copy = values.iter().map(RuntimeValue::deep_clone).collect();
+        *list.borrow_mut() = Rc::new(copy);
+    }
+
+    pub fn get(&self, i: usize) -> Option<RuntimeValue> {
+        let value = self.0.borrow().get(i).cloned()?;
+        if value.is_immutable() {
+            return Some(value);
+        }
+        self.values().get(i).cloned()
     }
 
     pub fn index(&self, index: &RuntimeNumber) -> Result<RuntimeValue, RuntimeError> {
         let i = resolve_index(self.len(), index)?;
 
-        let value = self
-            .0
-            .borrow()
-            .get(i)
-            .ok_or_else(|| {
-                RuntimeError::InternalBug(format!(
-                    "Index {i} is out of bounds for list of length {}",
-                    self.len()
-                ))
-            })?
-            .clone();
-
-        Ok(value)
+        self.get(i).ok_or_else(|| {
+            RuntimeError::InternalBug(format!(
+                "Index {i} is out of bounds for list of length {}",
+                self.len()
+            ))
+        })
     }
 
     pub fn set_index(
@@ -68,7 +127,7 @@ impl RuntimeList {
         value: RuntimeValue,
     ) -> Result<(), RuntimeError> {
         let i = resolve_index(self.len(), index)?;
-        self.0.borrow_mut()[i] = value;
+        self.values_mut()[i] = value;
         Ok(())
     }
 
@@ -78,17 +137,16 @@ impl RuntimeList {
 
     pub fn slice(&self, range: &RuntimeRange) -> Result<Self, RuntimeError> {
         let (start, end) = resolve_slice_indices(self.len(), range)?;
-        Ok(Self::from_vec(self.0.borrow()[start..end + 1].to_vec()))
+        Ok(Self::from_vec(self.values()[start..end + 1].to_vec()))
     }
 
     pub fn sort(&self) {
-        self.0
-            .borrow_mut()
+        self.values_mut()
             .sort_by(|a, b| a.partial_cmp(b).expect("unhandled uncomparable value"));
     }
 
     pub fn reverse(&self) {
-        self.0.borrow_mut().reverse();
+        self.values_mut().reverse();
     }
 
     pub fn sort_by_key(
@@ -96,8 +154,7 @@ impl RuntimeList {
         mut key_fn: impl FnMut(&RuntimeValue) -> Result<RuntimeValue, RuntimeError>,
     ) -> Result<(), RuntimeError> {
         let keys = self
-            .0
-            .borrow()
+            .values()
             .iter()
             .map(|item| {
                 let key = key_fn(item)?;
@@ -105,7 +162,7 @@ impl RuntimeList {
             })
             .collect::<Result<FxHashMap<RuntimeValue, RuntimeValue>, RuntimeError>>()?;
 
-        self.0.borrow_mut().sort_by(|a, b| {
+        self.values_mut().sort_by(|a, b| {
             let key_a = keys.get(a).expect("key not found for item a");
             let key_b = keys.get(b).expect("key not found for item b");
             key_a
@@ -117,8 +174,8 @@ impl RuntimeList {
     }
 
     pub fn concat(&self, other: &Self) -> Self {
-        let mut new_vec = self.0.borrow().clone();
-        new_vec.extend_from_slice(&other.0.borrow());
+        let mut new_vec = self.values().clone();
+        new_vec.extend_from_slice(&other.values());
         Self::from_vec(new_vec)
     }
 
@@ -150,6 +207,67 @@ impl RuntimeList {
 
         Ok(Self::from_vec(result))
     }
+
+    /// Treats the list as a grid of rows, which are lists or strings, and returns a map from the
+    /// `(x, y)` position of each cell to its value.
+    pub fn grid_to_map(&self) -> Result<RuntimeMap, RuntimeError> {
+        let mut map = FxHashMap::default();
+
+        for (y, row) in self.as_slice().iter().enumerate() {
+            let cells = match row {
+                RuntimeValue::List(l) => l.as_slice().to_vec(),
+                RuntimeValue::Str(s) => s
+                    .as_str()
+                    .chars()
+                    .map(|c| RuntimeValue::Str(RuntimeString::new(c)))
+                    .collect(),
+                _ => {
+                    return Err(RuntimeError::TypeMismatch(format!(
+                        "Cannot convert grid to map: rows must be lists or strings, got '{}'",
+                        row.kind_str()
+                    )))
+                }
+            };
+
+            for (x, cell) in cells.into_iter().enumerate() {
+                map.insert(grid_position(x, y)?, cell);
+            }
+        }
+
+        Ok(RuntimeMap::from_map(map))
+    }
+
+    /// Treats the list as a grid of rows and joins each row that is a list into a string.
+    pub fn grid_to_strings(&self) -> Result<Self, RuntimeError> {
+        let rows = self
+            .as_slice()
+            .iter()
+            .map(|row| match row {
+                RuntimeValue::Str(_) => Ok(row.clone()),
+                RuntimeValue::List(l) => {
+                    let line = l
+                        .as_slice()
+                        .iter()
+                        .map(|cell| cell.to_string())
+                        .collect::<String>();
+                    Ok(RuntimeValue::Str(RuntimeString::new(line)))
+                }
+                _ => Err(RuntimeError::TypeMismatch(format!(
+                    "Cannot convert grid to strings: rows must be lists or strings, got '{}'",
+                    row.kind_str()
+                ))),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self::from_vec(rows))
+    }
+}
+
+fn grid_position(x: usize, y: usize) -> Result<RuntimeValue, RuntimeError> {
+    match (i32::try_from(x), i32::try_from(y)) {
+        (Ok(x), Ok(y)) => Ok(RuntimeValue::Vec2(RuntimeVec2::new(x, y))),
+        _ => Err(RuntimeError::Plain("Grid is too large".to_string())),
+    }
 }
 
 impl Default for RuntimeList {
@@ -183,7 +301,7 @@ impl std::cmp::PartialOrd for RuntimeList {
 
 impl LfAppend for RuntimeList {
     fn append(&mut self, other: RuntimeValue) -> Result<(), RuntimeError> {
-        self.0.borrow_mut().push(other.clone());
+        self.values_mut().push(other.clone());
         Ok(())
     }
 }
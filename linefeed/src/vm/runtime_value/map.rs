@@ -1,17 +1,30 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    rc::Rc,
+};
 
 use ouroboros::self_referencing;
 use rustc_hash::FxHashMap;
 
 use crate::vm::{
-    runtime_value::{iterator::RuntimeIterator, number::RuntimeNumber, RuntimeValue},
+    runtime_value::{
+        copies::{self, Pending},
+        cycles::{self, Tracked},
+        iterator::RuntimeIterator,
+        list::RuntimeList,
+        number::RuntimeNumber,
+        vec2::RuntimeVec2,
+        RuntimeValue,
+    },
     RuntimeError,
 };
 
+/// Like lists, copies of a map share its entries until either of them is changed, and the
+/// containers among them are only copied once one is changed or read out of the copy.
 #[derive(Debug, Clone)]
-pub struct RuntimeMap(Rc<RefCell<InnerRuntimeMap>>);
+pub struct RuntimeMap(Rc<RefCell<Rc<InnerRuntimeMap>>>);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct InnerRuntimeMap {
     pub map: FxHashMap<RuntimeValue, RuntimeValue>,
     pub default_value: Option<RuntimeValue>,
@@ -22,11 +35,24 @@ impl RuntimeMap {
         Self::from_map(FxHashMap::default())
     }
 
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from_map(FxHashMap::with_capacity_and_hasher(
+            capacity,
+            Default::default(),
+        ))
+    }
+
     pub fn from_map(map: FxHashMap<RuntimeValue, RuntimeValue>) -> Self {
-        Self(Rc::new(RefCell::new(InnerRuntimeMap {
+        Self::from_inner(Rc::new(InnerRuntimeMap {
             map,
             default_value: None,
-        })))
+        }))
+    }
+
+    fn from_inner(inner: Rc<InnerRuntimeMap>) -> Self {
+        let map = Rc::new(RefCell::new(inner));
+        cycles::track(Tracked::Map(Rc::downgrade(&map)));
+        Self(map)
     }
 
     pub fn new_with_default_value(default_value: RuntimeValue) -> Self {
@@ -35,52 +61,73 @@ impl RuntimeMap {
         runtime_map
     }
 
+    /// Identifies the map, which is shared by every value referring to it.
+    pub fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+
+    /// The shared storage, for counting the references to it when collecting cycles.
+    pub fn rc(&self) -> &Rc<RefCell<Rc<InnerRuntimeMap>>> {
+        &self.0
+    }
+
     pub fn len(&self) -> usize {
-        self.borrow().len()
+        self.0.borrow().len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.borrow().is_empty()
+        self.0.borrow().is_empty()
     }
 
-    pub fn borrow(&self) -> std::cell::Ref<'_, InnerRuntimeMap> {
-        self.0.borrow()
+    /// The entries, after finishing the copy if the map is one (see `copies`).
+    pub fn borrow(&self) -> Ref<'_, InnerRuntimeMap> {
+        copies::finish();
+        Ref::map(self.0.borrow(), |inner| &**inner)
     }
 
-    pub fn borrow_mut(&self) -> std::cell::RefMut<'_, InnerRuntimeMap> {
-        self.0.borrow_mut()
+    /// The entries, which are copied first if they are shared with a copy of the map.
+    pub fn borrow_mut(&self) -> RefMut<'_, InnerRuntimeMap> {
+        copies::finish();
+        RefMut::map(self.0.borrow_mut(), Rc::make_mut)
     }
 
     pub fn deep_clone(&self) -> Self {
-        let new_map = Self::from_map(
-            self.borrow()
-                .iter()
-                .map(|(k, v)| (k.deep_clone(), v.deep_clone()))
-                .collect(),
-        );
-
-        if let Some(default_value) = &self.borrow().default_value {
-            new_map.borrow_mut().default_value = Some(default_value.deep_clone());
+        let copy = Self::from_inner(Rc::clone(&self.0.borrow()));
+        if !copy.is_empty() || copy.0.borrow().default_value.is_some() {
+            copies::defer(Pending::Map(Rc::downgrade(&copy.0)));
         }
+        copy
+    }
 
-        new_map
+    pub(super) fn finish_copy(map: &RefCell<Rc<InnerRuntimeMap>>) {
+        let inner = Rc::clone(&map.borrow());
+        if inner.is_immutable() {
+            return;
+        }
+        *map.borrow_mut() = Rc::new(inner.deep_clone());
     }
 
     pub fn get(&self, key: &RuntimeValue) -> RuntimeValue {
-        // Fast-path
-        {
-            let inner = self.0.borrow();
-            if let Some(value) = inner.map.get(key) {
-                return value.clone();
-            }
+        if let Some(value) = self.lookup(key) {
+            return value;
         }
 
         self.insert_default_value_if_missing(key);
 
-        self.borrow()
-            .get(key)
-            .cloned()
-            .unwrap_or(RuntimeValue::Null)
+        self.lookup(key).unwrap_or(RuntimeValue::Null)
+    }
+
+    /// Looks up `key`, falling back to `default` without inserting the map's default value.
+    pub fn get_or(&self, key: &RuntimeValue, default: RuntimeValue) -> RuntimeValue {
+        self.lookup(key).unwrap_or(default)
+    }
+
+    fn lookup(&self, key: &RuntimeValue) -> Option<RuntimeValue> {
+        let value = self.0.borrow().map.get(key).cloned()?;
+        if value.is_immutable() {
+            return Some(value);
+        }
+        self.borrow().map.get(key).cloned()
     }
 
     pub fn insert(&self, key: RuntimeValue, value: RuntimeValue) {
@@ -88,7 +135,46 @@ impl RuntimeMap {
     }
 
     pub fn contains_key(&self, key: &RuntimeValue) -> bool {
-        self.borrow().contains_key(key)
+        self.0.borrow().contains_key(key)
+    }
+
+    /// Lays out a map from `(x, y)` positions to values as a grid of rows, spanning from the
+    /// smallest to the largest `x` and `y` among the keys. Positions missing from the map get
+    /// `default`.
+    pub fn to_grid(&self, default: &RuntimeValue) -> Result<RuntimeList, RuntimeError> {
+        let inner = self.0.borrow();
+        let positions = inner
+            .keys()
+            .map(|key| {
+                grid_position(key).ok_or_else(|| {
+                    RuntimeError::TypeMismatch(format!(
+                        "Cannot convert map to grid: keys must be integer (x, y) positions, got {}",
+                        key.repr_string()
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let Some(min_x) = positions.iter().map(|&(x, _)| x).min() else {
+            return Ok(RuntimeList::new());
+        };
+        let max_x = positions.iter().map(|&(x, _)| x).max().unwrap_or(min_x);
+        let min_y = positions.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let max_y = positions.iter().map(|&(_, y)| y).max().unwrap_or(min_y);
+
+        let rows = (min_y..=max_y)
+            .map(|y| {
+                let row = (min_x..=max_x)
+                    .map(|x| {
+                        let pos = RuntimeValue::Vec2(RuntimeVec2::new(x, y));
+                        inner.get(&pos).unwrap_or(default).deep_clone()
+                    })
+                    .collect();
+                RuntimeValue::List(RuntimeList::from_vec(row))
+            })
+            .collect();
+
+        Ok(RuntimeList::from_vec(rows))
     }
 
     fn insert_default_value_if_missing(&self, key: &RuntimeValue) {
@@ -110,6 +196,38 @@ impl RuntimeMap {
     }
 }
 
+fn grid_position(key: &RuntimeValue) -> Option<(i32, i32)> {
+    match key {
+        RuntimeValue::Vec2(v) => Some((v.x, v.y)),
+        _ => None,
+    }
+}
+
+impl InnerRuntimeMap {
+    // Only entries that can't be changed are shared between copies of a map
+    fn is_immutable(&self) -> bool {
+        self.map
+            .iter()
+            .all(|(k, v)| k.is_immutable() && v.is_immutable())
+            && self
+                .default_value
+                .as_ref()
+                .is_none_or(RuntimeValue::is_immutable)
+    }
+
+    // Containers could be changed through either copy, so each copy gets its own
+    fn deep_clone(&self) -> Self {
+        Self {
+            map: self
+                .map
+                .iter()
+                .map(|(k, v)| (k.deep_clone(), v.deep_clone()))
+                .collect(),
+            default_value: self.default_value.as_ref().map(RuntimeValue::deep_clone),
+        }
+    }
+}
+
 impl std::ops::Deref for InnerRuntimeMap {
     type Target = FxHashMap<RuntimeValue, RuntimeValue>;
 
@@ -132,8 +250,8 @@ impl Default for RuntimeMap {
 
 impl PartialEq for RuntimeMap {
     fn eq(&self, other: &Self) -> bool {
-        let a = self.borrow();
-        let b = other.borrow();
+        let a = self.0.borrow();
+        let b = other.0.borrow();
 
         a.len() == b.len() && a.iter().all(|(key, val)| b.get(key) == Some(val))
     }
@@ -143,7 +261,7 @@ impl Eq for RuntimeMap {}
 
 impl std::hash::Hash for RuntimeMap {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        let set = self.borrow();
+        let set = self.0.borrow();
         let mut items = set.iter().collect::<Vec<_>>();
         items.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
         items.hash(state);
@@ -152,8 +270,8 @@ impl std::hash::Hash for RuntimeMap {
 
 impl std::cmp::PartialOrd for RuntimeMap {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let a = self.borrow();
-        let b = other.borrow();
+        let a = self.0.borrow();
+        let b = other.0.borrow();
         a.len().partial_cmp(&b.len())
     }
 }
@@ -5,14 +5,118 @@ use std::rc::Rc;
 pub enum RuntimeNumber {
     SmallInt(isize),
     BigInt(Rc<rug::Integer>),
+    /// An exact fraction, which is never a whole number as those are kept as integers.
+    Rational(Rc<rug::Rational>),
     Float(f64),
 }
 
+/// How a float is turned into an integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Towards zero.
+    Trunc,
+    Floor,
+    Ceil,
+    /// To the nearest integer, with halves away from zero.
+    Round,
+}
+
+impl Rounding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "trunc" => Some(Self::Trunc),
+            "floor" => Some(Self::Floor),
+            "ceil" => Some(Self::Ceil),
+            "round" => Some(Self::Round),
+            _ => None,
+        }
+    }
+}
+
+fn check_radix(radix: u32) -> Result<(), RuntimeError> {
+    if !(2..=36).contains(&radix) {
+        return Err(RuntimeError::Plain(format!(
+            "Base must be between 2 and 36, got {radix}"
+        )));
+    }
+    Ok(())
+}
+
 impl RuntimeNumber {
+    /// Creates the fraction `num / den`, which becomes an integer if it is a whole number.
+    pub fn rational(num: &Self, den: &Self) -> Result<Self, RuntimeError> {
+        let (Some(num), Some(den)) = (num.to_rational(), den.to_rational()) else {
+            return Err(RuntimeError::TypeMismatch(
+                "Cannot create a rational from floating point numbers".to_string(),
+            ));
+        };
+
+        if *den.numer() == 0 {
+            return Err(RuntimeError::Plain(
+                "Cannot create a rational with a zero denominator".to_string(),
+            ));
+        }
+
+        Ok(Self::from_rational(num / den))
+    }
+
+    pub fn from_rational(r: rug::Rational) -> Self {
+        if !r.is_integer() {
+            return Rational(Rc::new(r));
+        }
+
+        let (num, _) = r.into_numer_denom();
+        Self::from_integer(num)
+    }
+
+    /// Wraps a big integer, which is kept small if it fits in an isize.
+    pub fn from_integer(i: rug::Integer) -> Self {
+        match i.to_isize() {
+            Some(i) => SmallInt(i),
+            None => BigInt(Rc::new(i)),
+        }
+    }
+
+    fn to_integer(&self) -> Option<rug::Integer> {
+        match self {
+            SmallInt(i) => Some(rug::Integer::from(*i)),
+            BigInt(i) => Some(i.as_ref().clone()),
+            Rational(_) | Float(_) => None,
+        }
+    }
+
+    /// The number as an exact fraction, which floats can't be turned into.
+    fn to_rational(&self) -> Option<rug::Rational> {
+        match self {
+            SmallInt(i) => Some(rug::Rational::from(*i)),
+            BigInt(i) => Some(rug::Rational::from(i.as_ref())),
+            Rational(r) => Some(r.as_ref().clone()),
+            Float(_) => None,
+        }
+    }
+
+    // Arithmetic where a rational is involved, which stays exact unless the other side is a float
+    fn rational_op(
+        &self,
+        other: &Self,
+        exact: fn(rug::Rational, &rug::Rational) -> rug::Rational,
+        approx: fn(f64, f64) -> f64,
+    ) -> Self {
+        match (self.to_rational(), other.to_rational()) {
+            (Some(a), Some(b)) => Self::from_rational(exact(a, &b)),
+            _ => Float(approx(self.float(), other.float())),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, SmallInt(_) | BigInt(_))
+    }
+
     pub fn floor_int(&self) -> isize {
         match self {
             SmallInt(i) => *i,
             BigInt(i) => i.to_isize().unwrap(),
+            Rational(r) => r.to_f64().floor() as isize,
             Float(f) => f.floor() as isize,
         }
     }
@@ -29,6 +133,7 @@ impl RuntimeNumber {
         match self {
             SmallInt(i) => SmallInt(*i),
             BigInt(i) => BigInt(Rc::clone(i)),
+            Rational(r) => Self::from_rational(r.as_ref().clone().floor()),
             Float(f) => Float(f.floor()),
         }
     }
@@ -37,6 +142,7 @@ impl RuntimeNumber {
         match self {
             SmallInt(i) => *i != 0,
             BigInt(i) => **i != 0,
+            Rational(_) => true,
             Float(f) => *f != 0.0,
         }
     }
@@ -45,6 +151,7 @@ impl RuntimeNumber {
         match self {
             SmallInt(i) => *i as f64,
             BigInt(i) => i.to_f64(),
+            Rational(r) => r.to_f64(),
             Float(f) => *f,
         }
     }
@@ -60,6 +167,7 @@ impl RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a % (*b as f64)),
             (Float(a), BigInt(b)) => Float(a % b.to_f64()),
             (Float(a), Float(b)) => Float(a % b),
+            (a, b) => a.rational_op(b, |a, b| a.clone() - (a / b).trunc() * b, |a, b| a % b),
         }
     }
 
@@ -95,6 +203,11 @@ impl RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a.powi(*b as i32)),
             (Float(a), BigInt(b)) => Float(a.powi(b.to_i32().unwrap())),
             (Float(a), Float(b)) => Float(a.powf(*b)),
+            (Rational(a), SmallInt(b)) => match i32::try_from(*b) {
+                Ok(exp) => Self::from_rational(a.as_ref().clone().pow(exp)),
+                Err(_) => Float(a.to_f64().powf(*b as f64)),
+            },
+            (a, b) => Float(a.float().powf(b.float())),
         }
     }
 
@@ -109,14 +222,97 @@ impl RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a / (*b as f64)).floor(),
             (Float(a), BigInt(b)) => Float(a / b.to_f64()).floor(),
             (Float(a), Float(b)) => Float(a / b).floor(),
+            (a, b) => a.rational_op(b, |a, b| a / b, |a, b| a / b).floor(),
         }
     }
 
     pub fn parse_int(s: &str) -> Result<Self, RuntimeError> {
         match s.trim().parse::<isize>() {
             Ok(i) => Ok(Self::from(i)),
+            // Integers too large for an isize are still valid
+            Err(err) => match s.trim().parse::<rug::Integer>() {
+                Ok(i) => Ok(BigInt(Rc::new(i))),
+                Err(_) => Err(RuntimeError::ParseError(format!(
+                    "{s:?} is not a valid integer, {err}",
+                ))),
+            },
+        }
+    }
+
+    /// Parses an integer written in the given base, which must be between 2 and 36.
+    pub fn parse_int_radix(s: &str, radix: u32) -> Result<Self, RuntimeError> {
+        check_radix(radix)?;
+        let s = s.trim();
+        match isize::from_str_radix(s, radix) {
+            Ok(i) => Ok(Self::from(i)),
+            Err(err) => match rug::Integer::from_str_radix(s, radix as i32) {
+                Ok(i) => Ok(Self::from_integer(i)),
+                Err(_) => Err(RuntimeError::ParseError(format!(
+                    "{s:?} is not a valid base {radix} integer, {err}",
+                ))),
+            },
+        }
+    }
+
+    /// Writes an integer in the given base with lowercase digits and a leading `-` if negative.
+    pub fn to_radix(&self, radix: u32) -> Result<String, RuntimeError> {
+        check_radix(radix)?;
+        match self {
+            SmallInt(i) => Ok(rug::Integer::from(*i).to_string_radix(radix as i32)),
+            BigInt(i) => Ok(i.to_string_radix(radix as i32)),
+            Rational(_) | Float(_) => Err(RuntimeError::TypeMismatch(format!(
+                "Cannot write {} numbers in base {radix}",
+                if let Float(_) = self {
+                    "floating point"
+                } else {
+                    "rational"
+                }
+            ))),
+        }
+    }
+
+    pub fn parse_float(s: &str) -> Result<Self, RuntimeError> {
+        match s.trim().parse::<f64>() {
+            Ok(f) => Ok(Float(f)),
             Err(err) => Err(RuntimeError::ParseError(format!(
-                "{s:?} is not a valid integer, {err}",
+                "{s:?} is not a valid number, {err}",
+            ))),
+        }
+    }
+
+    /// Converts the number to an integer, rounding floats as given. Integers are unchanged, and
+    /// floats too large for a small integer become big integers.
+    pub fn to_int(&self, rounding: Rounding) -> Result<Self, RuntimeError> {
+        if let Rational(r) = self {
+            let r = r.as_ref().clone();
+            let rounded = match rounding {
+                Rounding::Trunc => r.trunc(),
+                Rounding::Floor => r.floor(),
+                Rounding::Ceil => r.ceil(),
+                Rounding::Round => r.round(),
+            };
+            return Ok(Self::from_rational(rounded));
+        }
+
+        let Float(f) = self else {
+            return Ok(self.clone());
+        };
+
+        let rounded = match rounding {
+            Rounding::Trunc => f.trunc(),
+            Rounding::Floor => f.floor(),
+            Rounding::Ceil => f.ceil(),
+            Rounding::Round => f.round(),
+        };
+
+        if (isize::MIN as f64..isize::MAX as f64).contains(&rounded) {
+            return Ok(SmallInt(rounded as isize));
+        }
+
+        match rug::Integer::from_f64(rounded) {
+            Some(i) => Ok(BigInt(Rc::new(i))),
+            None => Err(RuntimeError::TypeMismatch(format!(
+                "Cannot convert {f} to an integer"
             ))),
         }
     }
@@ -185,6 +381,9 @@ impl RuntimeNumber {
         match self {
             RuntimeNumber::SmallInt(a) => Ok(RuntimeNumber::SmallInt(!a)),
             RuntimeNumber::BigInt(a) => Ok(RuntimeNumber::BigInt(Rc::new((!a.as_ref()).into()))),
+            RuntimeNumber::Rational(_) => Err(RuntimeError::TypeMismatch(
+                "Cannot use ~ on rational numbers".to_string(),
+            )),
             RuntimeNumber::Float(_) => Err(RuntimeError::TypeMismatch(
                 "Cannot use ~ on floating point numbers".to_string(),
             )),
@@ -199,6 +398,9 @@ impl RuntimeNumber {
             RuntimeNumber::BigInt(a) => Ok(RuntimeNumber::BigInt(Rc::new(
                 (a.as_ref() << shift_amount).into(),
             ))),
+            RuntimeNumber::Rational(_) => Err(RuntimeError::TypeMismatch(
+                "Cannot shift rational numbers".to_string(),
+            )),
             RuntimeNumber::Float(_) => Err(RuntimeError::TypeMismatch(
                 "Cannot shift floating point numbers".to_string(),
             )),
@@ -213,6 +415,9 @@ impl RuntimeNumber {
             RuntimeNumber::BigInt(a) => Ok(RuntimeNumber::BigInt(Rc::new(
                 (a.as_ref() >> shift_amount).into(),
             ))),
+            RuntimeNumber::Rational(_) => Err(RuntimeError::TypeMismatch(
+                "Cannot shift rational numbers".to_string(),
+            )),
             RuntimeNumber::Float(_) => Err(RuntimeError::TypeMismatch(
                 "Cannot shift floating point numbers".to_string(),
             )),
@@ -229,6 +434,9 @@ impl RuntimeNumber {
             RuntimeNumber::BigInt(b) => b
                 .to_u32()
                 .ok_or_else(|| RuntimeError::Plain(format!("Shift amount too large: {b}"))),
+            RuntimeNumber::Rational(_) => Err(RuntimeError::TypeMismatch(
+                "Cannot shift by rational amount".to_string(),
+            )),
             RuntimeNumber::Float(_) => Err(RuntimeError::TypeMismatch(
                 "Cannot shift by floating point amount".to_string(),
             )),
@@ -244,6 +452,11 @@ impl RuntimeNumber {
                 || n.to_string_radix(2),
                 |width| format!("{:0>width$}", n.to_string_radix(2)),
             ),
+            RuntimeNumber::Rational(_) => {
+                return Err(RuntimeError::TypeMismatch(
+                    "Cannot convert rational numbers to binary".to_string(),
+                ))
+            }
             RuntimeNumber::Float(_) => {
                 return Err(RuntimeError::TypeMismatch(
                     "Cannot convert floating point numbers to binary".to_string(),
@@ -255,16 +468,94 @@ impl RuntimeNumber {
     }
 
     pub fn neg(&self) -> Self {
-        self * &RuntimeNumber::from(-1)
+        match self {
+            SmallInt(i) => i
+                .checked_neg()
+                .map(SmallInt)
+                .unwrap_or_else(|| BigInt(Rc::new(-rug::Integer::from(*i)))),
+            BigInt(i) => BigInt(Rc::new(-i.as_ref().clone())),
+            Rational(r) => Rational(Rc::new(-r.as_ref().clone())),
+            Float(f) => Float(-f),
+        }
+    }
+
+    /// The decimal digits of an integer, ignoring its sign.
+    pub fn digits(&self) -> Result<Vec<u32>, RuntimeError> {
+        if !self.is_integer() {
+            let kind = match self {
+                Float(_) => "float",
+                _ => "rational",
+            };
+            return Err(RuntimeError::TypeMismatch(format!(
+                "Cannot get digits of a {kind}, convert it with int() first"
+            )));
+        }
+
+        Ok(self
+            .abs()
+            .to_string()
+            .chars()
+            .filter_map(|c| c.to_digit(10))
+            .collect())
     }
 
     pub fn abs(&self) -> Self {
         match self {
             SmallInt(i) => SmallInt(i.abs()),
             BigInt(i) => BigInt(Rc::new(i.as_ref().clone().abs())),
+            Rational(r) => Rational(Rc::new(r.as_ref().clone().abs())),
             Float(f) => Float(f.abs()),
         }
     }
+
+    /// The greatest common divisor of two integers, which is never negative.
+    pub fn gcd(&self, other: &Self) -> Result<Self, RuntimeError> {
+        if let (SmallInt(a), SmallInt(b)) = (self, other) {
+            let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            return Ok(Self::from(a));
+        }
+
+        match (self.to_integer(), other.to_integer()) {
+            (Some(a), Some(b)) => Ok(Self::from_integer(a.gcd(&b))),
+            _ => Err(RuntimeError::TypeMismatch(format!(
+                "Cannot compute gcd of {self} and {other}, as they must be integers"
+            ))),
+        }
+    }
+
+    /// The least common multiple of two integers, which is never negative.
+    pub fn lcm(&self, other: &Self) -> Result<Self, RuntimeError> {
+        let gcd = self.gcd(other).map_err(|_| {
+            RuntimeError::TypeMismatch(format!(
+                "Cannot compute lcm of {self} and {other}, as they must be integers"
+            ))
+        })?;
+
+        if !gcd.bool() {
+            return Ok(SmallInt(0));
+        }
+        Ok(&self.abs().div_floor(&gcd) * &other.abs())
+    }
+
+    /// The integer square root, i.e. the square root rounded down, of a non-negative integer.
+    pub fn isqrt(&self) -> Result<Self, RuntimeError> {
+        if *self < SmallInt(0) {
+            return Err(RuntimeError::Plain(
+                "Cannot compute square root of negative number".to_string(),
+            ));
+        }
+
+        match self {
+            SmallInt(i) => Ok(SmallInt(i.isqrt())),
+            BigInt(i) => Ok(Self::from_integer(i.as_ref().clone().sqrt())),
+            Rational(_) | Float(_) => Err(RuntimeError::TypeMismatch(format!(
+                "Cannot compute integer square root of {self}, as it must be an integer"
+            ))),
+        }
+    }
 }
 
 // Macro for types that always fit in isize
@@ -308,6 +599,7 @@ impl std::fmt::Display for RuntimeNumber {
         match self {
             SmallInt(i) => write!(f, "{}", i),
             BigInt(i) => write!(f, "{}", i),
+            Rational(r) => write!(f, "{}", r),
             Float(fl) => write!(f, "{}", fl),
         }
     }
@@ -325,6 +617,9 @@ impl PartialEq for RuntimeNumber {
             (Float(a), SmallInt(b)) => *a == (*b as f64),
             (Float(a), BigInt(b)) => *a == b.to_f64(),
             (Float(a), Float(b)) => a == b,
+            (Rational(a), Rational(b)) => a == b,
+            // Rationals are never whole numbers, so they can only equal floats
+            (Rational(a), b) | (b, Rational(a)) => a.to_f64() == b.float(),
         }
     }
 }
@@ -356,6 +651,10 @@ impl std::cmp::PartialOrd for RuntimeNumber {
             (Float(a), SmallInt(b)) => a.partial_cmp(&(*b as f64)),
             (Float(a), BigInt(b)) => a.partial_cmp(&b.to_f64()),
             (Float(a), Float(b)) => a.partial_cmp(b),
+            (a, b) => match (a.to_rational(), b.to_rational()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => a.float().partial_cmp(&b.float()),
+            },
         }
     }
 }
@@ -377,6 +676,7 @@ impl Add for RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a + b as f64),
             (Float(a), BigInt(b)) => Float(a + b.to_f64()),
             (Float(a), Float(b)) => Float(a + b),
+            (a, b) => a.rational_op(&b, |a, b| a + b, |a, b| a + b),
         }
     }
 }
@@ -398,6 +698,7 @@ impl Add<&Self> for RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a + *b as f64),
             (Float(a), BigInt(b)) => Float(a + b.to_f64()),
             (Float(a), Float(b)) => Float(a + b),
+            (a, b) => a.rational_op(b, |a, b| a + b, |a, b| a + b),
         }
     }
 }
@@ -418,6 +719,7 @@ impl Add for &RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a + *b as f64),
             (Float(a), BigInt(b)) => Float(a + b.to_f64()),
             (Float(a), Float(b)) => Float(a + b),
+            (a, b) => a.rational_op(b, |a, b| a + b, |a, b| a + b),
         }
     }
 }
@@ -439,6 +741,7 @@ impl Sub for RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a - b as f64),
             (Float(a), BigInt(b)) => Float(a - b.to_f64()),
             (Float(a), Float(b)) => Float(a - b),
+            (a, b) => a.rational_op(&b, |a, b| a - b, |a, b| a - b),
         }
     }
 }
@@ -459,6 +762,7 @@ impl Sub for &RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a - *b as f64),
             (Float(a), BigInt(b)) => Float(a - b.to_f64()),
             (Float(a), Float(b)) => Float(a - b),
+            (a, b) => a.rational_op(b, |a, b| a - b, |a, b| a - b),
         }
     }
 }
@@ -480,6 +784,7 @@ impl Mul for RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a * b as f64),
             (Float(a), BigInt(b)) => Float(a * b.to_f64()),
             (Float(a), Float(b)) => Float(a * b),
+            (a, b) => a.rational_op(&b, |a, b| a * b, |a, b| a * b),
         }
     }
 }
@@ -500,6 +805,7 @@ impl Mul for &RuntimeNumber {
             (Float(a), SmallInt(b)) => Float(a * *b as f64),
             (Float(a), BigInt(b)) => Float(a * b.to_f64()),
             (Float(a), Float(b)) => Float(a * b),
+            (a, b) => a.rational_op(b, |a, b| a * b, |a, b| a * b),
         }
     }
 }
@@ -508,7 +814,7 @@ impl Div for RuntimeNumber {
     type Output = RuntimeNumber;
 
     fn div(self, other: Self) -> Self::Output {
-        RuntimeNumber::Float(self.float() / other.float())
+        &self / &other
     }
 }
 
@@ -516,6 +822,12 @@ impl Div for &RuntimeNumber {
     type Output = RuntimeNumber;
 
     fn div(self, other: Self) -> Self::Output {
-        RuntimeNumber::Float(self.float() / other.float())
+        // Integers divide to floats, but rationals stay exact unless dividing by zero
+        match (self, other) {
+            (Rational(_), _) | (_, Rational(_)) if other.bool() => {
+                self.rational_op(other, |a, b| a / b, |a, b| a / b)
+            }
+            _ => RuntimeNumber::Float(self.float() / other.float()),
+        }
     }
 }
@@ -49,6 +49,16 @@ impl RuntimeRange {
         self.start
     }
 
+    /// The value `n` steps from the start, if the range has a start and is long enough.
+    pub fn nth(&self, n: usize) -> Option<isize> {
+        if self.len().is_some_and(|len| n >= len) {
+            return None;
+        }
+        let n = isize::try_from(n).ok()?;
+        let step = if self.is_reverse() { -1 } else { 1 };
+        self.start?.checked_add(n * step)
+    }
+
     pub fn last(&self) -> Option<isize> {
         match (self.start, self.end) {
             (Some(start), Some(end)) if start <= end => Some(end - 1),
@@ -71,6 +81,7 @@ impl std::fmt::Display for RuntimeRange {
     }
 }
 
+#[derive(Clone)]
 pub struct RangeIterator {
     range: RuntimeRange,
     value: isize,
@@ -88,11 +99,11 @@ impl RangeIterator {
 
     pub fn len(&self) -> Option<usize> {
         match (self.range.start, self.range.end) {
-            (Some(start), Some(end)) => {
-                let distance = start.abs_diff(end);
-                let steps = distance / self.step.unsigned_abs();
-                let stepped = self.value.unsigned_abs() / self.step.unsigned_abs();
-                Some(steps - stepped)
+            (Some(_), Some(end)) => {
+                // Counts from the current value rather than the start, since the iterator may have
+                // been partially consumed
+                let remaining = (end - self.value) * self.step.signum();
+                Some(usize::try_from(remaining).unwrap_or(0) / self.step.unsigned_abs())
             }
             _ => None,
         }
@@ -101,6 +112,13 @@ impl RangeIterator {
     pub fn is_empty(&self) -> bool {
         self.len() == Some(0)
     }
+
+    /// Skips the next `n` values, stopping at the end of the range.
+    pub fn skip(&mut self, n: usize) {
+        let n = self.len().map_or(n, |len| n.min(len));
+        let n = isize::try_from(n).unwrap_or(isize::MAX);
+        self.value = self.value.saturating_add(n.saturating_mul(self.step));
+    }
 }
 
 impl Iterator for RangeIterator {
@@ -1,14 +1,14 @@
-use std::rc::Rc;
+use std::{collections::HashMap, sync::Arc};
 
 use regex::{Regex, RegexBuilder};
 
 use crate::vm::runtime_value::{
-    list::RuntimeList, number::RuntimeNumber, string::RuntimeString, tuple::RuntimeTuple,
-    RuntimeValue,
+    list::RuntimeList, map::RuntimeMap, number::RuntimeNumber, string::RuntimeString,
+    tuple::RuntimeTuple, RuntimeValue,
 };
 
 #[derive(Debug, Clone)]
-pub struct RuntimeRegex(Rc<RegexConfig>);
+pub struct RuntimeRegex(Arc<RegexConfig>);
 
 #[derive(Debug, Clone)]
 pub struct RegexConfig {
@@ -16,7 +16,7 @@ pub struct RegexConfig {
     pub modifiers: RegexModifiers,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct RegexModifiers {
     pub case_insensitive: bool,
 
@@ -28,6 +28,60 @@ pub struct RegexModifiers {
     pub parse_nums: bool,
 }
 
+impl RegexModifiers {
+    /// Parses modifiers written the same way as after a regex literal, e.g. `"in"`.
+    pub fn from_flags(flags: &str) -> Result<Self, String> {
+        let mut modifiers = Self::default();
+
+        for flag in flags.chars() {
+            match flag {
+                'i' => modifiers.case_insensitive = true,
+                'n' => modifiers.parse_nums = true,
+                _ => return Err(format!("Unknown regex flag '{flag}'")),
+            }
+        }
+
+        Ok(modifiers)
+    }
+}
+
+/// Compiled regexes, keyed by their pattern and modifiers, so every occurrence of the same regex
+/// shares a single compiled automaton.
+#[derive(Debug, Default, Clone)]
+pub struct RegexPool(HashMap<(String, RegexModifiers), RuntimeRegex>);
+
+impl RegexPool {
+    pub fn get_or_compile(
+        &mut self,
+        s: &str,
+        modifiers: RegexModifiers,
+    ) -> Result<RuntimeRegex, regex::Error> {
+        let key = (s.to_string(), modifiers);
+        if let Some(regex) = self.0.get(&key) {
+            return Ok(regex.clone());
+        }
+
+        let regex = RuntimeRegex::compile(s, key.1.clone())?;
+        self.0.insert(key, regex.clone());
+        Ok(regex)
+    }
+
+    /// Adds the regexes of `other` that aren't already in the pool.
+    pub fn extend(&mut self, other: RegexPool) {
+        for (key, regex) in other.0 {
+            self.0.entry(key).or_insert(regex);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 impl RuntimeRegex {
     pub fn compile(s: &str, modifiers: RegexModifiers) -> Result<Self, regex::Error> {
         let regex = RegexBuilder::new(s)
@@ -38,7 +92,7 @@ impl RuntimeRegex {
     }
 
     pub fn new(regex: RegexConfig) -> Self {
-        Self(Rc::new(regex))
+        Self(Arc::new(regex))
     }
 
     pub fn as_regex(&self) -> &Regex {
@@ -72,20 +126,39 @@ impl RuntimeRegex {
         self.0.regex.is_match(s.as_str())
     }
 
+    /// Replaces every match, where `$1` or `${name}` in the replacement is the text of a group and
+    /// `$$` is a literal dollar sign.
+    pub fn replace_all(&self, s: &RuntimeString, replacement: &RuntimeString) -> RuntimeString {
+        let replaced = self.0.regex.replace_all(s.as_str(), replacement.as_str());
+        RuntimeString::new(replaced)
+    }
+
+    fn group_value(&self, group: Option<regex::Match>) -> RuntimeValue {
+        group.map_or(RuntimeValue::Null, |g| {
+            if self.0.modifiers.parse_nums {
+                if let Ok(num) = g.as_str().parse::<isize>() {
+                    return RuntimeValue::Num(RuntimeNumber::from(num));
+                }
+            }
+
+            RuntimeValue::Str(RuntimeString::new(g.as_str()))
+        })
+    }
+
     fn process_capture(&self, captures: regex::Captures) -> RuntimeValue {
+        // With named groups, a map from the names reads better than remembering their positions
+        if self.0.regex.capture_names().any(|name| name.is_some()) {
+            let map = RuntimeMap::new();
+            for name in self.0.regex.capture_names().flatten() {
+                let name_value = RuntimeValue::Str(RuntimeString::new(name));
+                map.insert(name_value, self.group_value(captures.name(name)));
+            }
+            return RuntimeValue::Map(map);
+        }
+
         let mut group_values = captures
             .iter()
-            .map(|group| {
-                group.map_or(RuntimeValue::Null, |g| {
-                    if self.0.modifiers.parse_nums {
-                        if let Ok(num) = g.as_str().parse::<isize>() {
-                            return RuntimeValue::Num(RuntimeNumber::from(num));
-                        }
-                    }
-
-                    RuntimeValue::Str(RuntimeString::new(g.as_str()))
-                })
-            })
+            .map(|group| self.group_value(group))
             .collect::<Vec<_>>();
 
         // The full match is almost never useful, so just put it at the end, enabling the user
@@ -4,7 +4,13 @@ use ouroboros::self_referencing;
 use rustc_hash::FxHashSet;
 
 use crate::vm::{
-    runtime_value::{iterator::RuntimeIterator, operations::LfAppend, RuntimeValue},
+    runtime_value::{
+        copies,
+        cycles::{self, Tracked},
+        iterator::RuntimeIterator,
+        operations::LfAppend,
+        RuntimeValue,
+    },
     RuntimeError,
 };
 
@@ -17,7 +23,14 @@ impl RuntimeSet {
     }
 
     pub fn from_set(set: FxHashSet<RuntimeValue>) -> Self {
-        Self(Rc::new(RefCell::new(set)))
+        let set = Rc::new(RefCell::new(set));
+        cycles::track(Tracked::Set(Rc::downgrade(&set)));
+        Self(set)
+    }
+
+    /// The shared storage, for counting the references to it when collecting cycles.
+    pub fn rc(&self) -> &Rc<RefCell<FxHashSet<RuntimeValue>>> {
+        &self.0
     }
 
     pub fn borrow(&self) -> std::cell::Ref<'_, FxHashSet<RuntimeValue>> {
@@ -76,6 +89,7 @@ impl RuntimeSet {
     }
 
     pub fn remove(&mut self, value: RuntimeValue) {
+        copies::finish();
         self.0.borrow_mut().remove(&value);
     }
 
@@ -132,6 +146,7 @@ impl std::cmp::PartialOrd for RuntimeSet {
 
 impl LfAppend for RuntimeSet {
     fn append(&mut self, other: RuntimeValue) -> Result<(), RuntimeError> {
+        copies::finish();
         self.0.borrow_mut().insert(other);
         Ok(())
     }
@@ -66,28 +66,65 @@ impl RuntimeString {
         RuntimeList::from_vec(parts)
     }
 
+    /// Splits the string into blocks separated by blank lines. Lines within a block are joined by
+    /// `\n`, regardless of the line endings used in the input.
+    pub fn paragraphs(&self) -> RuntimeList {
+        let mut paragraphs = Vec::new();
+        let mut cur_lines = Vec::new();
+
+        for line in self.as_str().lines() {
+            if line.trim().is_empty() {
+                if !cur_lines.is_empty() {
+                    paragraphs.push(RuntimeValue::Str(Self::new(cur_lines.join("\n"))));
+                    cur_lines.clear();
+                }
+            } else {
+                cur_lines.push(line.trim_end());
+            }
+        }
+
+        if !cur_lines.is_empty() {
+            paragraphs.push(RuntimeValue::Str(Self::new(cur_lines.join("\n"))));
+        }
+
+        RuntimeList::from_vec(paragraphs)
+    }
+
     pub fn nums(&self) -> RuntimeList {
         static NUM_REGEX: LazyLock<Regex> =
-            LazyLock::new(|| Regex::new(r"-?\d+\.?\d*|-?\.\d+").unwrap());
+            LazyLock::new(|| Regex::new(r"-?\d+(?:\.\d+)?|-?\.\d+").unwrap());
 
         let parts = NUM_REGEX
             .find_iter(self.as_str())
             .filter_map(|m| {
                 let s = m.as_str();
-                // Try integer first, fall back to float
-                if let Ok(i) = s.parse::<isize>() {
-                    Some(RuntimeValue::Num(RuntimeNumber::from(i)))
-                } else if let Ok(f) = s.parse::<f64>() {
-                    Some(RuntimeValue::Num(RuntimeNumber::Float(f)))
-                } else {
-                    None
-                }
+                // Try integer first, so large integers stay exact, and fall back to float
+                RuntimeNumber::parse_int(s)
+                    .or_else(|_| RuntimeNumber::parse_float(s))
+                    .ok()
+                    .map(RuntimeValue::Num)
             })
             .collect();
 
         RuntimeList::from_vec(parts)
     }
 
+    /// The value of each character, which must all be decimal digits.
+    pub fn digits(&self) -> Result<Vec<u32>, RuntimeError> {
+        self.as_str()
+            .trim()
+            .chars()
+            .map(|c| {
+                c.to_digit(10).ok_or_else(|| {
+                    RuntimeError::ParseError(format!(
+                        "Cannot get digits of {:?}, since {c:?} is not a digit",
+                        self.as_str()
+                    ))
+                })
+            })
+            .collect()
+    }
+
     pub fn concat(&self, other: &RuntimeString) -> Self {
         Self::new(format!("{}{}", self.as_str(), other.as_str()))
     }
@@ -102,7 +139,7 @@ impl RuntimeString {
 
         // Not quite the best for Rust's UTF-8 strings, but all inputs for Linefeed's use-cases
         // will be valid ASCII, so indexing into the bytes directly should be fine for now.
-        let byte = self.as_str().as_bytes().get(i as usize).ok_or_else(|| {
+        let byte = self.as_str().as_bytes().get(i).ok_or_else(|| {
             RuntimeError::InternalBug(format!(
                 "Index {i} is out of bounds for string of length {}",
                 self.len()
@@ -120,10 +157,213 @@ impl RuntimeString {
         self.as_str().starts_with(prefix.as_str())
     }
 
+    pub fn ends_with(&self, suffix: &RuntimeString) -> bool {
+        self.as_str().ends_with(suffix.as_str())
+    }
+
+    /// The byte index of the first occurrence of the substring, matching how strings are indexed.
+    pub fn index_of(&self, substr: &RuntimeString) -> Option<usize> {
+        self.as_str().find(substr.as_str())
+    }
+
+    pub fn trim(&self) -> Self {
+        self.map_str(|s| s.trim().to_string())
+    }
+
+    pub fn trim_start(&self) -> Self {
+        self.map_str(|s| s.trim_start().to_string())
+    }
+
+    pub fn trim_end(&self) -> Self {
+        self.map_str(|s| s.trim_end().to_string())
+    }
+
+    pub fn replace(&self, from: &RuntimeString, to: &RuntimeString) -> Self {
+        self.map_str(|s| s.replace(from.as_str(), to.as_str()))
+    }
+
+    pub fn repeat(&self, n: usize) -> Self {
+        self.map_str(|s| s.repeat(n))
+    }
+
     pub fn substr(&self, range: &RuntimeRange) -> Result<Self, RuntimeError> {
         let (start, end) = resolve_slice_indices(self.len(), range)?;
         Ok(Self::new(&self.as_str()[start..end + 1]))
     }
+
+    /// Replaces each `{}` field with the next argument, or `{i}` with the argument at index `i`,
+    /// formatted by the [`FormatSpec`] after an optional `:`. `{{` and `}}` are literal braces.
+    pub fn format(&self, args: &[RuntimeValue]) -> Result<Self, RuntimeError> {
+        let mut res = String::new();
+        let mut chars = self.as_str().chars();
+        let mut next_arg = 0;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    res.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    res.push('}');
+                }
+                '}' => {
+                    return Err(RuntimeError::Plain(
+                        "Unmatched '}' in format string, use '}}' for a literal brace".to_string(),
+                    ))
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| {
+                        RuntimeError::Plain(
+                            "Unclosed '{' in format string, use '{{' for a literal brace"
+                                .to_string(),
+                        )
+                    })?;
+                    let (field, spec) = rest[..end].split_once(':').unwrap_or((&rest[..end], ""));
+                    chars = rest[end + 1..].chars();
+
+                    let index = if field.is_empty() {
+                        next_arg += 1;
+                        next_arg - 1
+                    } else {
+                        field.trim().parse().map_err(|_| {
+                            RuntimeError::Plain(format!(
+                                "Invalid format field {field:?}, expected an argument index"
+                            ))
+                        })?
+                    };
+
+                    let arg = args.get(index).ok_or_else(|| {
+                        RuntimeError::Plain(format!(
+                            "Format string refers to argument {index}, but got {} argument{}",
+                            args.len(),
+                            if args.len() == 1 { "" } else { "s" }
+                        ))
+                    })?;
+
+                    FormatSpec::parse(spec)?.write(&mut res, arg);
+                }
+                c => res.push(c),
+            }
+        }
+
+        Ok(Self::new(res))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// How a value is written in a format string, given as `[[fill]align][0][width][.precision]`
+/// like in Python and Rust. Alignment is `<`, `>` or `^`, and numbers are right-aligned and other
+/// values left-aligned by default. A leading `0` pads numbers with zeros after their sign, and the
+/// precision is the number of decimals of a number or the maximum length of anything else.
+#[derive(Debug, Clone, Copy)]
+struct FormatSpec {
+    fill: char,
+    align: Option<Align>,
+    zero_pad: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl FormatSpec {
+    fn parse(spec: &str) -> Result<Self, RuntimeError> {
+        let invalid = || RuntimeError::Plain(format!("Invalid format spec {spec:?}"));
+        let to_align = |c| match c {
+            '<' => Some(Align::Left),
+            '>' => Some(Align::Right),
+            '^' => Some(Align::Center),
+            _ => None,
+        };
+
+        let mut res = Self {
+            fill: ' ',
+            align: None,
+            zero_pad: false,
+            width: 0,
+            precision: None,
+        };
+
+        let mut rest = spec;
+        let mut chars = spec.chars();
+        match (chars.next(), chars.next().and_then(to_align)) {
+            (Some(fill), Some(align)) => {
+                res.fill = fill;
+                res.align = Some(align);
+                rest = chars.as_str();
+            }
+            (Some(c), None) if to_align(c).is_some() => {
+                res.align = to_align(c);
+                rest = &spec[1..];
+            }
+            _ => {}
+        }
+
+        if let Some(after_zero) = rest.strip_prefix('0') {
+            res.zero_pad = true;
+            rest = after_zero;
+        }
+
+        let (width, precision) = match rest.split_once('.') {
+            Some((width, precision)) => (width, Some(precision)),
+            None => (rest, None),
+        };
+        if !width.is_empty() {
+            res.width = width.parse().map_err(|_| invalid())?;
+        }
+        if let Some(precision) = precision {
+            res.precision = Some(precision.parse().map_err(|_| invalid())?);
+        }
+
+        Ok(res)
+    }
+
+    fn write(&self, out: &mut String, val: &RuntimeValue) {
+        let text = match (val, self.precision) {
+            (RuntimeValue::Num(n), Some(p)) => format!("{:.p$}", n.float()),
+            (_, Some(p)) => val.to_string().chars().take(p).collect(),
+            (_, None) => val.to_string(),
+        };
+
+        let len = text.chars().count();
+        if len >= self.width {
+            out.push_str(&text);
+            return;
+        }
+        let padding = self.width - len;
+
+        let is_num = matches!(val, RuntimeValue::Num(_));
+        if self.zero_pad && self.align.is_none() && is_num {
+            let (sign, digits) = match text.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", text.as_str()),
+            };
+            out.push_str(sign);
+            out.extend(std::iter::repeat_n('0', padding));
+            out.push_str(digits);
+            return;
+        }
+
+        let align = self
+            .align
+            .unwrap_or(if is_num { Align::Right } else { Align::Left });
+        let (before, after) = match align {
+            Align::Left => (0, padding),
+            Align::Right => (padding, 0),
+            Align::Center => (padding / 2, padding - padding / 2),
+        };
+
+        out.extend(std::iter::repeat_n(self.fill, before));
+        out.push_str(&text);
+        out.extend(std::iter::repeat_n(self.fill, after));
+    }
 }
 
 impl std::fmt::Display for RuntimeString {
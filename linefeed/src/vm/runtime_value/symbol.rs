@@ -0,0 +1,68 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, OnceLock},
+};
+
+/// An interned symbol like `:north`. Symbols with the same name share a single id, so comparing
+/// and hashing them never looks at the name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RuntimeSymbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, u32>,
+    names: Vec<&'static str>,
+}
+
+// Symbols only come from literals in the source code, so there are few enough of them that their
+// names can live for the rest of the process
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl RuntimeSymbol {
+    pub fn new(name: &str) -> Self {
+        let mut interner = interner().lock().unwrap();
+
+        if let Some(&id) = interner.ids.get(name) {
+            return Self(id);
+        }
+
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let id = interner.names.len() as u32;
+        interner.names.push(name);
+        interner.ids.insert(name, id);
+        Self(id)
+    }
+
+    pub fn name(&self) -> &'static str {
+        interner().lock().unwrap().names[self.0 as usize]
+    }
+}
+
+// Symbols are ordered by name rather than by id, so that sorting doesn't depend on the order in
+// which they were first seen
+impl PartialOrd for RuntimeSymbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RuntimeSymbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else {
+            self.name().cmp(other.name())
+        }
+    }
+}
+
+impl fmt::Display for RuntimeSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
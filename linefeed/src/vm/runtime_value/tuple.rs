@@ -1,7 +1,10 @@
 use std::rc::Rc;
 
 use crate::vm::{
-    runtime_value::{number::RuntimeNumber, utils::resolve_index, vec2::RuntimeVec2, RuntimeValue},
+    runtime_value::{
+        number::RuntimeNumber, utils::resolve_index, vec2::RuntimeVec2, vec3::RuntimeVec3,
+        RuntimeValue,
+    },
     RuntimeError,
 };
 
@@ -14,15 +17,36 @@ impl RuntimeTuple {
     }
 
     pub fn from_vec(vec: Vec<RuntimeValue>) -> RuntimeValue {
-        if vec.len() == 2 {
-            if let Ok(vec2) = RuntimeVec2::try_from((&vec[0], &vec[1])) {
-                return RuntimeValue::Vec2(vec2);
+        match vec.as_slice() {
+            [x, y] => {
+                if let Ok(vec2) = RuntimeVec2::try_from((x, y)) {
+                    return RuntimeValue::Vec2(vec2);
+                }
             }
+            [x, y, z] => {
+                if let Ok(vec3) = RuntimeVec3::try_from((x, y, z)) {
+                    return RuntimeValue::Vec3(vec3);
+                }
+            }
+            _ => {}
         }
 
         RuntimeValue::Tuple(Self::from_vec_inner(vec))
     }
 
+    /// Tuples can't be changed, so this only copies the ones holding a value that can.
+    pub fn deep_clone(&self) -> Self {
+        if self.0.iter().all(RuntimeValue::is_immutable) {
+            return self.clone();
+        }
+        Self::from_vec_inner(self.0.iter().map(RuntimeValue::deep_clone).collect())
+    }
+
+    /// The shared storage, for counting the references to it when collecting cycles.
+    pub fn rc(&self) -> &Rc<Vec<RuntimeValue>> {
+        &self.0
+    }
+
     pub fn as_slice(&self) -> &[RuntimeValue] {
         self.0.as_slice()
     }
@@ -93,6 +117,13 @@ impl RuntimeTuple {
         Ok(RuntimeTuple::from_vec_inner(result?))
     }
 
+    pub fn neg(&self) -> Result<Self, RuntimeError> {
+        let result: Result<Vec<RuntimeValue>, RuntimeError> =
+            self.0.iter().map(|elem| elem.neg()).collect();
+
+        Ok(RuntimeTuple::from_vec_inner(result?))
+    }
+
     pub fn rot(&self, times: &RuntimeValue) -> Result<Self, RuntimeError> {
         let (x, y) = match self.as_slice() {
             [RuntimeValue::Num(x), RuntimeValue::Num(y)] => (x, y),
@@ -0,0 +1,51 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::vm::runtime_value::symbol::RuntimeSymbol;
+
+/// A variant of an enum, like `Dir.Up` from `enum Dir { Up, Down }`. The full name of the variant
+/// is interned, so variants are compared and hashed without looking at it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RuntimeVariant {
+    name: RuntimeSymbol,
+    /// The position of the variant in the enum declaration.
+    index: u32,
+}
+
+impl RuntimeVariant {
+    pub fn new(enum_name: &str, name: &str, index: usize) -> Self {
+        Self {
+            name: RuntimeSymbol::new(&format!("{enum_name}.{name}")),
+            index: index as u32,
+        }
+    }
+
+    pub fn enum_name(&self) -> &'static str {
+        let (enum_name, _) = self.name.name().split_once('.').unwrap();
+        enum_name
+    }
+}
+
+// Variants of the same enum are ordered like they are declared
+impl PartialOrd for RuntimeVariant {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RuntimeVariant {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else {
+            self.enum_name()
+                .cmp(other.enum_name())
+                .then(self.index.cmp(&other.index))
+        }
+    }
+}
+
+impl fmt::Display for RuntimeVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
@@ -49,6 +49,14 @@ impl RuntimeVec2 {
         Ok(RuntimeValue::Vec2(RuntimeVec2::new(x, y)))
     }
 
+    pub fn neg(&self) -> Result<RuntimeValue, RuntimeError> {
+        let res = self.x.checked_neg().zip(self.y.checked_neg());
+
+        let (x, y) = unwrap_or_fallback!(res, self, neg);
+
+        Ok(RuntimeValue::Vec2(RuntimeVec2::new(x, y)))
+    }
+
     pub fn scalar_mul(&self, scalar: &RuntimeValue) -> Result<RuntimeValue, RuntimeError> {
         let res = scalar
             .to_i32()
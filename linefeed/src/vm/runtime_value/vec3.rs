@@ -0,0 +1,114 @@
+use crate::vm::runtime_error::RuntimeError;
+use crate::vm::runtime_value::{RuntimeNumber, RuntimeTuple, RuntimeValue};
+
+/// Stack-allocated 3D counterpart of [`RuntimeVec2`](super::vec2::RuntimeVec2), falling back to
+/// RuntimeTuple in the same way. The coordinates are 16 bits, as three 32-bit ones wouldn't fit in
+/// a RuntimeValue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RuntimeVec3 {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+macro_rules! unwrap_or_fallback {
+    ($opt:expr, $self:expr, $method:ident $(, $arg:expr)*) => {
+        match $opt {
+            Some(res) => res,
+            None => {
+                let tuple = $self.to_tuple();
+                return Ok(RuntimeValue::Tuple(tuple.$method($($arg),*)?));
+            }
+        }
+    };
+}
+
+impl RuntimeVec3 {
+    pub fn new(x: i16, y: i16, z: i16) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn to_tuple(&self) -> RuntimeTuple {
+        RuntimeTuple::from_vec_inner(
+            [self.x, self.y, self.z]
+                .into_iter()
+                .map(|n| RuntimeValue::Num(RuntimeNumber::SmallInt(n as isize)))
+                .collect(),
+        )
+    }
+
+    pub fn add(&self, other: &Self) -> Result<RuntimeValue, RuntimeError> {
+        let res = self.zip_with(other, i16::checked_add);
+        let v = unwrap_or_fallback!(res, self, element_wise_add, &other.to_tuple());
+        Ok(RuntimeValue::Vec3(v))
+    }
+
+    pub fn sub(&self, other: &Self) -> Result<RuntimeValue, RuntimeError> {
+        let res = self.zip_with(other, i16::checked_sub);
+        let v = unwrap_or_fallback!(res, self, element_wise_sub, &other.to_tuple());
+        Ok(RuntimeValue::Vec3(v))
+    }
+
+    pub fn neg(&self) -> Result<RuntimeValue, RuntimeError> {
+        let res = self.map(i16::checked_neg);
+        let v = unwrap_or_fallback!(res, self, neg);
+        Ok(RuntimeValue::Vec3(v))
+    }
+
+    pub fn scalar_mul(&self, scalar: &RuntimeValue) -> Result<RuntimeValue, RuntimeError> {
+        let res = scalar
+            .to_i32()
+            .and_then(|s| i16::try_from(s).ok())
+            .and_then(|s| self.map(|n| n.checked_mul(s)));
+        let v = unwrap_or_fallback!(res, self, scalar_multiply, scalar);
+        Ok(RuntimeValue::Vec3(v))
+    }
+
+    pub fn index(&self, index: &RuntimeNumber) -> Result<RuntimeValue, RuntimeError> {
+        let idx = index
+            .to_i32()
+            .ok_or_else(|| RuntimeError::IndexOutOfBounds(index.floor_int(), 3))?;
+
+        let normalized_idx = if idx < 0 { 3 + idx } else { idx };
+        let val = match normalized_idx {
+            0 => self.x,
+            1 => self.y,
+            2 => self.z,
+            _ => return Err(RuntimeError::IndexOutOfBounds(idx as isize, 3)),
+        };
+
+        Ok(RuntimeValue::Num(RuntimeNumber::SmallInt(val as isize)))
+    }
+
+    pub fn contains(&self, value: &RuntimeValue) -> bool {
+        value
+            .to_i32()
+            .is_some_and(|v| [self.x, self.y, self.z].into_iter().any(|n| n as i32 == v))
+    }
+
+    fn map(&self, f: impl Fn(i16) -> Option<i16>) -> Option<Self> {
+        Some(Self::new(f(self.x)?, f(self.y)?, f(self.z)?))
+    }
+
+    fn zip_with(&self, other: &Self, f: impl Fn(i16, i16) -> Option<i16>) -> Option<Self> {
+        Some(Self::new(
+            f(self.x, other.x)?,
+            f(self.y, other.y)?,
+            f(self.z, other.z)?,
+        ))
+    }
+}
+
+impl TryFrom<(&RuntimeValue, &RuntimeValue, &RuntimeValue)> for RuntimeVec3 {
+    type Error = ();
+
+    fn try_from(
+        (v1, v2, v3): (&RuntimeValue, &RuntimeValue, &RuntimeValue),
+    ) -> Result<Self, Self::Error> {
+        let to_i16 = |v: &RuntimeValue| v.to_i32().and_then(|n| i16::try_from(n).ok());
+        match (to_i16(v1), to_i16(v2), to_i16(v3)) {
+            (Some(x), Some(y), Some(z)) => Ok(RuntimeVec3::new(x, y, z)),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! The graph search builtins `bfs`, `dijkstra` and `a_star`.
+//!
+//! The graph is never built up front: the program gives a function returning the neighbors of a
+//! node, which is called as nodes are reached. The bookkeeping of the search, i.e. the queue and
+//! the nodes seen so far, is kept here so only the neighbor, goal and heuristic functions run as
+//! bytecode.
+
+// Nodes are runtime values, which may be lists and maps, but they don't change while being searched
+#![allow(clippy::mutable_key_type)]
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, VecDeque},
+    io::{Read, Write},
+    rc::Rc,
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::vm::{
+    function_arg,
+    runtime_value::{
+        function::RuntimeFunction, list::RuntimeList, number::RuntimeNumber, RuntimeValue,
+    },
+    BytecodeInterpreter, RuntimeError,
+};
+
+/// Where a search stops, which is either a node or a function saying whether a node is the goal.
+enum Goal {
+    Node(RuntimeValue),
+    Func(Rc<RuntimeFunction>),
+}
+
+impl Goal {
+    fn from_value(val: RuntimeValue) -> Self {
+        match val {
+            RuntimeValue::Function(func) => Goal::Func(func),
+            node => Goal::Node(node),
+        }
+    }
+}
+
+/// A node waiting in the priority queue of `dijkstra` and `a_star`. The queue is a max-heap, so the
+/// ordering is reversed to take the lowest priority first, and ties are taken first in first out.
+struct Queued {
+    priority: RuntimeValue,
+    order: usize,
+    cost: RuntimeValue,
+    node: RuntimeValue,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .partial_cmp(&self.priority)
+            .unwrap_or(Ordering::Equal)
+            .then(other.order.cmp(&self.order))
+    }
+}
+
+impl<I: Read, O: Write, E: Write> BytecodeInterpreter<I, O, E> {
+    /// Runs `bfs(start, neighbors, goal)`, returning the path with the fewest steps from the start
+    /// to the goal as a list of nodes, or null if the goal can't be reached.
+    pub(super) fn bfs(&mut self, args: Vec<RuntimeValue>) -> Result<RuntimeValue, RuntimeError> {
+        let [start, neighbors, goal] = <[RuntimeValue; 3]>::try_from(args)
+            .map_err(|_| RuntimeError::InternalBug("bfs takes 3 arguments".to_string()))?;
+        let neighbors = function_arg(neighbors, "bfs neighbors function")?;
+        let goal = Goal::from_value(goal);
+
+        let mut came_from = FxHashMap::default();
+        came_from.insert(start.clone(), None);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(node) = queue.pop_front() {
+            if self.is_goal(&goal, &node)? {
+                return Ok(RuntimeValue::List(path_to(&came_from, node)));
+            }
+
+            for next in self.neighbors_of(&neighbors, &node)? {
+                if !came_from.contains_key(&next) {
+                    came_from.insert(next.clone(), Some(node.clone()));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Ok(RuntimeValue::Null)
+    }
+
+    /// Runs `dijkstra(start, neighbors, goal)`, where the neighbors are `(node, cost)` pairs,
+    /// returning the cheapest path to the goal as a `(cost, path)` tuple, or null if the goal
+    /// can't be reached.
+    pub(super) fn dijkstra(
+        &mut self,
+        args: Vec<RuntimeValue>,
+    ) -> Result<RuntimeValue, RuntimeError> {
+        let [start, neighbors, goal] = <[RuntimeValue; 3]>::try_from(args)
+            .map_err(|_| RuntimeError::InternalBug("dijkstra takes 3 arguments".to_string()))?;
+        let neighbors = function_arg(neighbors, "dijkstra neighbors function")?;
+        self.cheapest_path("dijkstra", start, &neighbors, Goal::from_value(goal), None)
+    }
+
+    /// Runs `a_star(start, neighbors, goal, heuristic)`, which is `dijkstra` looking first at the
+    /// nodes the heuristic estimates to be closest to the goal. The heuristic must never estimate
+    /// more than the actual cost for the path to be the cheapest.
+    pub(super) fn a_star(&mut self, args: Vec<RuntimeValue>) -> Result<RuntimeValue, RuntimeError> {
+        let [start, neighbors, goal, heuristic] = <[RuntimeValue; 4]>::try_from(args)
+            .map_err(|_| RuntimeError::InternalBug("a_star takes 4 arguments".to_string()))?;
+        let neighbors = function_arg(neighbors, "a_star neighbors function")?;
+        let heuristic = function_arg(heuristic, "a_star heuristic")?;
+        let goal = Goal::from_value(goal);
+        self.cheapest_path("a_star", start, &neighbors, goal, Some(&heuristic))
+    }
+
+    fn cheapest_path(
+        &mut self,
+        name: &str,
+        start: RuntimeValue,
+        neighbors: &RuntimeFunction,
+        goal: Goal,
+        heuristic: Option<&RuntimeFunction>,
+    ) -> Result<RuntimeValue, RuntimeError> {
+        let zero = RuntimeValue::Num(RuntimeNumber::from(0));
+        let mut best_costs = FxHashMap::default();
+        let mut came_from = FxHashMap::default();
+        let mut queue = BinaryHeap::new();
+        let mut order = 0;
+
+        best_costs.insert(start.clone(), zero.clone());
+        came_from.insert(start.clone(), None);
+        queue.push(Queued {
+            priority: zero.clone(),
+            order,
+            cost: zero,
+            node: start,
+        });
+
+        while let Some(Queued { cost, node, .. }) = queue.pop() {
+            // The node was queued again with a lower cost, and has already been handled
+            if best_costs.get(&node).is_some_and(|best| *best < cost) {
+                continue;
+            }
+
+            if self.is_goal(&goal, &node)? {
+                let path = RuntimeValue::List(path_to(&came_from, node));
+                return Ok(RuntimeValue::from((cost, path)));
+            }
+
+            for edge in self.neighbors_of(neighbors, &node)? {
+                let (next, edge_cost) = weighted_edge(name, edge)?;
+                let next_cost = cost.add(&edge_cost)?;
+                if best_costs.get(&next).is_some_and(|best| *best <= next_cost) {
+                    continue;
+                }
+
+                let priority = match heuristic {
+                    Some(heuristic) => {
+                        let estimate = self.call_user_function(heuristic, vec![next.clone()])?;
+                        next_cost.add(&estimate)?
+                    }
+                    None => next_cost.clone(),
+                };
+
+                order += 1;
+                best_costs.insert(next.clone(), next_cost.clone());
+                came_from.insert(next.clone(), Some(node.clone()));
+                queue.push(Queued {
+                    priority,
+                    order,
+                    cost: next_cost,
+                    node: next,
+                });
+            }
+        }
+
+        Ok(RuntimeValue::Null)
+    }
+
+    fn is_goal(&mut self, goal: &Goal, node: &RuntimeValue) -> Result<bool, RuntimeError> {
+        match goal {
+            Goal::Node(goal) => Ok(goal == node),
+            Goal::Func(func) => Ok(self.call_user_function(func, vec![node.clone()])?.bool()),
+        }
+    }
+
+    fn neighbors_of(
+        &mut self,
+        neighbors: &RuntimeFunction,
+        node: &RuntimeValue,
+    ) -> Result<Vec<RuntimeValue>, RuntimeError> {
+        let res = self.call_user_function(neighbors, vec![node.clone()])?;
+        Ok(res.to_iter_inner()?.to_vec())
+    }
+}
+
+fn weighted_edge(
+    name: &str,
+    edge: RuntimeValue,
+) -> Result<(RuntimeValue, RuntimeValue), RuntimeError> {
+    let item = |i: isize| edge.index(&RuntimeValue::Num(RuntimeNumber::from(i)));
+
+    match (edge.is_tuple_of_len(2) || edge.is_list_of_len(2)).then(|| (item(0), item(1))) {
+        Some((Ok(next), Ok(cost @ RuntimeValue::Num(_)))) => Ok((next, cost)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{name} expects neighbors as (node, cost) pairs, got {}",
+            edge.repr_string()
+        ))),
+    }
+}
+
+// Walks back from the goal to the start, which is the node that came from nowhere
+fn path_to(
+    came_from: &FxHashMap<RuntimeValue, Option<RuntimeValue>>,
+    goal: RuntimeValue,
+) -> RuntimeList {
+    let mut path = vec![goal];
+    while let Some(Some(prev)) = path.last().and_then(|node| came_from.get(node)) {
+        path.push(prev.clone());
+    }
+
+    path.reverse();
+    RuntimeList::from_vec(path)
+}
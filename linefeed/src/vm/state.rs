@@ -0,0 +1,39 @@
+use std::fmt;
+
+use crate::vm::runtime_value::RuntimeValue;
+
+/// A snapshot of the interpreter's internal state, used to reproduce and compare runs (e.g. when
+/// fuzzing the compiler against itself).
+///
+/// The [`Display`](fmt::Display) format is stable: it only depends on the values in the snapshot,
+/// never on memory addresses or hash iteration order, so dumps from two runs can be diffed
+/// directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmState {
+    pub stack: Vec<RuntimeValue>,
+    pub registers: Vec<isize>,
+    pub pc: usize,
+    pub bp: usize,
+    pub instructions_executed: usize,
+}
+
+impl fmt::Display for VmState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "pc: {}", self.pc)?;
+        writeln!(f, "bp: {}", self.bp)?;
+        writeln!(f, "instructions executed: {}", self.instructions_executed)?;
+
+        writeln!(f, "stack ({}):", self.stack.len())?;
+        for (i, val) in self.stack.iter().enumerate() {
+            writeln!(f, "  [{i}] {}: {}", val.kind_str(), val.repr_string())?;
+        }
+
+        // Unused registers hold -1, so only the ones the program touched are listed
+        writeln!(f, "registers:")?;
+        for (i, reg) in self.registers.iter().enumerate().filter(|(_, &r)| r != -1) {
+            writeln!(f, "  r{i} = {reg}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,19 @@
+use std::{cmp::Ordering, fmt::Write as _};
+
 use crate::vm::{
     runtime_value::{
-        counter::RuntimeCounter, iterator::RuntimeIterator, list::RuntimeList, map::RuntimeMap,
-        number::RuntimeNumber, set::RuntimeSet, tuple::RuntimeTuple, RuntimeValue,
+        compare,
+        counter::RuntimeCounter,
+        deque::RuntimeDeque,
+        grid::RuntimeGrid,
+        iterator::{CombinationsIterator, PermutationsIterator, RuntimeIterator},
+        list::RuntimeList,
+        map::RuntimeMap,
+        number::{Rounding, RuntimeNumber},
+        set::RuntimeSet,
+        string::RuntimeString,
+        tuple::RuntimeTuple,
+        RuntimeValue,
     },
     RuntimeError,
 };
@@ -9,18 +21,176 @@ use crate::vm::{
 pub type RuntimeResult = Result<RuntimeValue, RuntimeError>;
 
 pub fn parse_int(val: RuntimeValue) -> Result<RuntimeValue, RuntimeError> {
-    let res = match val {
-        RuntimeValue::Num(n) => RuntimeValue::Num(n.floor()),
-        RuntimeValue::Str(s) => RuntimeValue::Num(RuntimeNumber::parse_int(s.as_str())?),
+    let RuntimeValue::Str(s) = val else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "Cannot parse '{}' as integer, use int() to convert other types",
+            val.kind_str()
+        )));
+    };
+
+    Ok(RuntimeValue::Num(RuntimeNumber::parse_int(s.as_str())?))
+}
+
+pub fn parse_float(val: RuntimeValue) -> RuntimeResult {
+    let RuntimeValue::Str(s) = val else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "Cannot parse '{}' as float, use float() to convert other types",
+            val.kind_str()
+        )));
+    };
+
+    Ok(RuntimeValue::Num(RuntimeNumber::parse_float(s.as_str())?))
+}
+
+/// `int(x)` truncates numbers towards zero, while `int(x, mode)` rounds them with the named
+/// mode. Strings must contain an integer, which `int(s, base)` reads in another base.
+pub fn to_int(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let rounding = match args.get(1) {
+        None => Rounding::Trunc,
+        Some(base @ RuntimeValue::Num(_)) => {
+            let RuntimeValue::Str(s) = &args[0] else {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "int() only takes a base when converting a string, got '{}'",
+                    args[0].kind_str()
+                )));
+            };
+            let num = RuntimeNumber::parse_int_radix(s.as_str(), radix_arg(base)?)?;
+            return Ok(RuntimeValue::Num(num));
+        }
+        Some(RuntimeValue::Str(mode)) => Rounding::from_name(mode.as_str()).ok_or_else(|| {
+            RuntimeError::Plain(format!(
+                "Unknown rounding mode {:?}, expected \"trunc\", \"floor\", \"ceil\" or \"round\"",
+                mode.as_str()
+            ))
+        })?,
+        Some(mode) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "Rounding mode must be a string, got '{}'",
+                mode.kind_str()
+            )))
+        }
+    };
+
+    let num = match &args[0] {
+        RuntimeValue::Num(n) => n.to_int(rounding)?,
+        RuntimeValue::Str(s) => RuntimeNumber::parse_int(s.as_str())?,
+        RuntimeValue::Bool(b) => RuntimeNumber::from(*b as u8),
+        val => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "Cannot convert '{}' to an integer",
+                val.kind_str()
+            )))
+        }
+    };
+
+    Ok(RuntimeValue::Num(num))
+}
+
+/// Writes an integer in another base, e.g. `to_base(255, 16)` is `"ff"`.
+pub fn to_base(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let [n, base] = args.as_slice() else {
+        unreachable!("to_base called with wrong number of arguments");
+    };
+
+    let RuntimeValue::Num(n) = n else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "to_base expects a number, got '{}'",
+            n.kind_str()
+        )));
+    };
+
+    Ok(RuntimeValue::Str(RuntimeString::new(
+        n.to_radix(radix_arg(base)?)?,
+    )))
+}
+
+fn radix_arg(base: &RuntimeValue) -> Result<u32, RuntimeError> {
+    base.to_i32()
+        .and_then(|n| u32::try_from(n).ok())
+        .ok_or_else(|| {
+            RuntimeError::TypeMismatch(format!(
+                "Base must be an integer, got {}",
+                base.repr_string()
+            ))
+        })
+}
+
+pub fn to_float(val: RuntimeValue) -> RuntimeResult {
+    let num = match &val {
+        RuntimeValue::Num(n) => RuntimeNumber::Float(n.float()),
+        RuntimeValue::Str(s) => RuntimeNumber::parse_float(s.as_str())?,
+        RuntimeValue::Bool(b) => RuntimeNumber::Float(*b as u8 as f64),
         _ => {
             return Err(RuntimeError::TypeMismatch(format!(
-                "Cannot parse '{}' as integer",
+                "Cannot convert '{}' to a float",
                 val.kind_str()
             )))
         }
     };
 
-    Ok(res)
+    Ok(RuntimeValue::Num(num))
+}
+
+/// The `try_` builtins return null where the builtin they are based on fails because of the
+/// value it was given. Other errors, like an unknown rounding mode, still abort the program.
+fn or_null(result: RuntimeResult) -> RuntimeResult {
+    match result {
+        Err(
+            RuntimeError::TypeMismatch(_)
+            | RuntimeError::IndexOutOfBounds(..)
+            | RuntimeError::ParseError(_),
+        ) => Ok(RuntimeValue::Null),
+        result => result,
+    }
+}
+
+pub fn try_num(val: RuntimeValue) -> RuntimeResult {
+    let num = match &val {
+        RuntimeValue::Num(_) => return Ok(val),
+        RuntimeValue::Str(s) => {
+            RuntimeNumber::parse_int(s.as_str()).or_else(|_| RuntimeNumber::parse_float(s.as_str()))
+        }
+        _ => return Ok(RuntimeValue::Null),
+    };
+
+    or_null(num.map(RuntimeValue::Num))
+}
+
+pub fn try_int(args: Vec<RuntimeValue>) -> RuntimeResult {
+    or_null(to_int(args))
+}
+
+pub fn try_float(val: RuntimeValue) -> RuntimeResult {
+    or_null(to_float(val))
+}
+
+pub fn try_index(args: Vec<RuntimeValue>) -> RuntimeResult {
+    or_null(args[0].index(&args[1]))
+}
+
+pub fn try_get(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let mut args = args.into_iter();
+    let mut val = args.next().expect("try_get takes at least two arguments");
+
+    for key in args {
+        val = match or_null(val.index(&key))? {
+            RuntimeValue::Null => return Ok(RuntimeValue::Null),
+            found => found,
+        };
+    }
+
+    Ok(val)
+}
+
+/// The name of the value's type as the program sees it, where numbers are one type however they
+/// are stored and vectors are tuples.
+pub fn type_of(val: RuntimeValue) -> RuntimeResult {
+    let name = match val {
+        RuntimeValue::Int(_) => "number",
+        RuntimeValue::Vec2(_) | RuntimeValue::Vec3(_) => "tuple",
+        _ => val.kind_str(),
+    };
+    Ok(RuntimeValue::Str(RuntimeString::new(name)))
 }
 
 pub fn to_list(val: RuntimeValue) -> Result<RuntimeValue, RuntimeError> {
@@ -59,6 +229,37 @@ pub fn map_with_default(default_value: RuntimeValue) -> Result<RuntimeValue, Run
     )))
 }
 
+pub fn list_with_capacity(capacity: RuntimeValue) -> RuntimeResult {
+    let capacity = capacity_arg(&capacity, "list_with_capacity")?;
+    Ok(RuntimeValue::List(RuntimeList::with_capacity(capacity)))
+}
+
+pub fn map_with_capacity(capacity: RuntimeValue) -> RuntimeResult {
+    let capacity = capacity_arg(&capacity, "map_with_capacity")?;
+    Ok(RuntimeValue::Map(RuntimeMap::with_capacity(capacity)))
+}
+
+fn capacity_arg(capacity: &RuntimeValue, fn_name: &str) -> Result<usize, RuntimeError> {
+    match capacity.to_i32() {
+        Some(n) if n >= 0 => Ok(n as usize),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{fn_name} expects a non-negative, small integer, got '{capacity}'"
+        ))),
+    }
+}
+
+pub fn map_to_grid(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let RuntimeValue::Map(map) = &args[0] else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "map_to_grid expects a map, got '{}'",
+            args[0].kind_str()
+        )));
+    };
+    let default = args.get(1).unwrap_or(&RuntimeValue::Null);
+
+    Ok(RuntimeValue::List(map.to_grid(default)?))
+}
+
 pub fn to_map(val: RuntimeValue) -> Result<RuntimeValue, RuntimeError> {
     if let RuntimeValue::Map(_) = val {
         return Ok(val.clone());
@@ -89,6 +290,32 @@ pub fn to_set(val: Option<RuntimeValue>) -> Result<RuntimeValue, RuntimeError> {
     Ok(RuntimeValue::Set(RuntimeSet::try_from(iter)?))
 }
 
+pub fn to_deque(val: Option<RuntimeValue>) -> Result<RuntimeValue, RuntimeError> {
+    let items = match val.as_ref().map(|v| v.to_iter_inner()) {
+        None => Vec::new(),
+        Some(Ok(iter)) => iter.to_vec(),
+        Some(Err(_)) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "Cannot convert type {} to a deque",
+                val.unwrap().kind_str()
+            )))
+        }
+    };
+
+    Ok(RuntimeValue::Deque(RuntimeDeque::from_vec(items)))
+}
+
+pub fn to_grid(val: RuntimeValue) -> Result<RuntimeValue, RuntimeError> {
+    let RuntimeValue::List(rows) = &val else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "Cannot create grid from '{}', expected a list of rows",
+            val.kind_str()
+        )));
+    };
+
+    Ok(RuntimeValue::Grid(RuntimeGrid::from_rows(rows)?))
+}
+
 pub fn to_counter(val: Option<RuntimeValue>) -> Result<RuntimeValue, RuntimeError> {
     let iter = match val.as_ref().map(|v| v.to_iter_inner()) {
         None => RuntimeIterator::from(()),
@@ -112,9 +339,16 @@ pub fn sum(val: RuntimeValue) -> RuntimeResult {
         )));
     };
 
-    iter.try_fold(RuntimeValue::Num(RuntimeNumber::from(0)), |acc, v| {
-        acc.add(&v)
-    })
+    iter.try_fold(
+        RuntimeValue::Num(RuntimeNumber::from(0)),
+        |acc, v| match v {
+            // Booleans aren't numbers, but summing them is a common way of counting
+            RuntimeValue::Bool(_) => Err(RuntimeError::TypeMismatch(
+                "Cannot sum booleans, use count_if to count the true ones".to_string(),
+            )),
+            v => acc.add(&v),
+        },
+    )
 }
 
 pub fn mul(val: RuntimeValue) -> RuntimeResult {
@@ -166,23 +400,36 @@ pub fn any(args: Vec<RuntimeValue>) -> RuntimeResult {
 }
 
 pub fn max(args: Vec<RuntimeValue>) -> RuntimeResult {
-    let iter = iterator_from_variadic_args(args);
-
-    let first = iter.next().ok_or_else(|| {
-        RuntimeError::Plain("Received empty iterator, cannot find maximum".to_string())
-    })?;
-
-    Ok(iter.fold(first, |max, value| if value > max { value } else { max }))
+    extremum("max", "maximum", args, Ordering::Greater)
 }
 
 pub fn min(args: Vec<RuntimeValue>) -> RuntimeResult {
+    extremum("min", "minimum", args, Ordering::Less)
+}
+
+fn extremum(name: &str, kind: &str, args: Vec<RuntimeValue>, keep: Ordering) -> RuntimeResult {
     let iter = iterator_from_variadic_args(args);
 
-    let first = iter.next().ok_or_else(|| {
-        RuntimeError::Plain("Received empty iterator, cannot find minimum".to_string())
+    let mut best = iter.next().ok_or_else(|| {
+        RuntimeError::Plain(format!("Received empty iterator, cannot find {kind}"))
     })?;
 
-    Ok(iter.fold(first, |min, value| if value < min { value } else { min }))
+    while let Some(value) = iter.next() {
+        match value.partial_cmp(&best) {
+            Some(ordering) if ordering == keep => best = value,
+            Some(_) => {}
+            // Typically caused by `max(0, xs)`, where the list is compared against the number
+            None => {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "Cannot compare types '{}' and '{}' in {name}. To include the items of an iterable, spread it with `*`, e.g. {name}(x, *xs)",
+                    best.kind_str(),
+                    value.kind_str()
+                )))
+            }
+        }
+    }
+
+    Ok(best)
 }
 
 pub fn abs(val: RuntimeValue) -> RuntimeResult {
@@ -213,6 +460,112 @@ pub fn sqrt(val: RuntimeValue) -> RuntimeResult {
     }
 }
 
+pub fn isqrt(val: RuntimeValue) -> RuntimeResult {
+    match val {
+        RuntimeValue::Num(n) => Ok(RuntimeValue::Num(n.isqrt()?)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "Cannot compute integer square root of type {}",
+            val.kind_str()
+        ))),
+    }
+}
+
+pub fn gcd(args: Vec<RuntimeValue>) -> RuntimeResult {
+    reduce_integers("gcd", args, RuntimeNumber::from(0), RuntimeNumber::gcd)
+}
+
+pub fn lcm(args: Vec<RuntimeValue>) -> RuntimeResult {
+    reduce_integers("lcm", args, RuntimeNumber::from(1), RuntimeNumber::lcm)
+}
+
+fn reduce_integers(
+    name: &str,
+    args: Vec<RuntimeValue>,
+    init: RuntimeNumber,
+    op: fn(&RuntimeNumber, &RuntimeNumber) -> Result<RuntimeNumber, RuntimeError>,
+) -> RuntimeResult {
+    let iter = iterator_from_variadic_args(args);
+    let mut acc = init;
+
+    while let Some(value) = iter.next() {
+        match &value {
+            RuntimeValue::Num(n) if n.is_integer() => acc = op(&acc, n)?,
+            _ => {
+                return Err(RuntimeError::TypeMismatch(format!(
+                    "{name} expects integers, got {}",
+                    value.repr_string()
+                )))
+            }
+        }
+    }
+
+    Ok(RuntimeValue::Num(acc))
+}
+
+pub fn floor(val: RuntimeValue) -> RuntimeResult {
+    round_with("floor", val, Rounding::Floor)
+}
+
+pub fn ceil(val: RuntimeValue) -> RuntimeResult {
+    round_with("ceil", val, Rounding::Ceil)
+}
+
+pub fn round(val: RuntimeValue) -> RuntimeResult {
+    round_with("round", val, Rounding::Round)
+}
+
+fn round_with(name: &str, val: RuntimeValue, rounding: Rounding) -> RuntimeResult {
+    match val {
+        RuntimeValue::Num(n) => Ok(RuntimeValue::Num(n.to_int(rounding)?)),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{name} expects a number, got '{}'",
+            val.kind_str()
+        ))),
+    }
+}
+
+pub fn sin(val: RuntimeValue) -> RuntimeResult {
+    float_fn("sin", &val, f64::sin)
+}
+
+pub fn cos(val: RuntimeValue) -> RuntimeResult {
+    float_fn("cos", &val, f64::cos)
+}
+
+pub fn tan(val: RuntimeValue) -> RuntimeResult {
+    float_fn("tan", &val, f64::tan)
+}
+
+pub fn log(args: Vec<RuntimeValue>) -> RuntimeResult {
+    match args.as_slice() {
+        [val] => float_fn("log", val, f64::ln),
+        [val, RuntimeValue::Num(base)] => {
+            let base = base.float();
+            float_fn("log", val, |f| f.log(base))
+        }
+        [_, base] => Err(RuntimeError::TypeMismatch(format!(
+            "log expects a number as the base, got '{}'",
+            base.kind_str()
+        ))),
+        _ => unreachable!("log called with wrong number of arguments"),
+    }
+}
+
+pub fn log2(val: RuntimeValue) -> RuntimeResult {
+    float_fn("log2", &val, f64::log2)
+}
+
+// Applies a float function to any number, so integers are converted first
+fn float_fn(name: &str, val: &RuntimeValue, f: impl Fn(f64) -> f64) -> RuntimeResult {
+    match val {
+        RuntimeValue::Num(n) => Ok(RuntimeValue::Num(RuntimeNumber::Float(f(n.float())))),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{name} expects a number, got '{}'",
+            val.kind_str()
+        ))),
+    }
+}
+
 pub fn manhattan(args: Vec<RuntimeValue>) -> RuntimeResult {
     let diff = match (args.first(), args.get(1)) {
         (Some(a), None) => a.clone(),
@@ -223,6 +576,7 @@ pub fn manhattan(args: Vec<RuntimeValue>) -> RuntimeResult {
     let diff = match diff {
         RuntimeValue::Tuple(t) => t,
         RuntimeValue::Vec2(v) => v.to_tuple(),
+        RuntimeValue::Vec3(v) => v.to_tuple(),
         _ => {
             return Err(RuntimeError::TypeMismatch(format!(
                 "cannot calculate manhattan distance for arguments of types: {}",
@@ -244,6 +598,41 @@ pub fn manhattan(args: Vec<RuntimeValue>) -> RuntimeResult {
     Ok(RuntimeValue::Num(sum))
 }
 
+pub fn vec2(args: Vec<RuntimeValue>) -> RuntimeResult {
+    vector("vec2", args)
+}
+
+pub fn vec3(args: Vec<RuntimeValue>) -> RuntimeResult {
+    vector("vec3", args)
+}
+
+// Small integer components make a stack-allocated vector, and anything else a tuple
+fn vector(name: &str, components: Vec<RuntimeValue>) -> RuntimeResult {
+    let not_number = |c: &&RuntimeValue| !matches!(c, RuntimeValue::Num(_));
+    if let Some(other) = components.iter().find(not_number) {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "{name} expects numbers, got '{}'",
+            other.kind_str()
+        )));
+    }
+
+    Ok(RuntimeTuple::from_vec(components))
+}
+
+pub fn rational(args: Vec<RuntimeValue>) -> RuntimeResult {
+    match args.as_slice() {
+        [RuntimeValue::Num(num), RuntimeValue::Num(den)] => {
+            Ok(RuntimeValue::Num(RuntimeNumber::rational(num, den)?))
+        }
+        [num, den] => Err(RuntimeError::TypeMismatch(format!(
+            "rational expects two integers, got '{}' and '{}'",
+            num.kind_str(),
+            den.kind_str()
+        ))),
+        _ => unreachable!("rational called with wrong number of arguments"),
+    }
+}
+
 pub fn mod_inv(args: Vec<RuntimeValue>) -> RuntimeResult {
     let (Some(a_val), Some(m_val)) = (args.first(), args.get(1)) else {
         return Err(RuntimeError::Plain(
@@ -321,3 +710,422 @@ pub fn mod_inv(args: Vec<RuntimeValue>) -> RuntimeResult {
 
     Ok(RuntimeValue::Num(x1))
 }
+
+const DEFAULT_HISTOGRAM_WIDTH: usize = 40;
+
+/// Collects the counts of a counter, or of the items produced by any other iterable.
+fn counts_of(val: &RuntimeValue, action: &str) -> Result<Vec<(RuntimeValue, isize)>, RuntimeError> {
+    let counter = match val {
+        RuntimeValue::Counter(counter) => counter.clone(),
+        _ => {
+            let iter = val.to_iter_inner().map_err(|_| {
+                RuntimeError::TypeMismatch(format!("Cannot {action} type {}", val.kind_str()))
+            })?;
+
+            RuntimeCounter::try_from(iter)?
+        }
+    };
+
+    let counts = counter
+        .borrow()
+        .iter()
+        .map(|(key, count)| (key.clone(), *count))
+        .collect();
+
+    Ok(counts)
+}
+
+/// Renders an ASCII bar chart with one bar per distinct value, ordered by value. The longest bar
+/// is `width` characters wide.
+pub fn histogram(args: Vec<RuntimeValue>) -> Result<String, RuntimeError> {
+    let width = match args.get(1) {
+        None => DEFAULT_HISTOGRAM_WIDTH,
+        Some(width) => width.to_i32().filter(|n| *n > 0).ok_or_else(|| {
+            RuntimeError::TypeMismatch(
+                "Histogram width must be a positive, small integer".to_string(),
+            )
+        })? as usize,
+    };
+
+    let mut counts = counts_of(&args[0], "draw histogram of")?;
+    counts.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    let labels = counts
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .collect::<Vec<_>>();
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let max_count = counts.iter().map(|(_, c)| *c).max().unwrap_or(0).max(1);
+
+    let mut output = String::new();
+    for (label, (_, count)) in labels.iter().zip(&counts) {
+        let ratio = (*count).max(0) as f64 / max_count as f64;
+        let bar_len = match (ratio * width as f64).round() as usize {
+            // Make sure that small, non-zero counts are still visible
+            0 if *count > 0 => 1,
+            len => len,
+        };
+
+        writeln!(
+            output,
+            "{label:<label_width$} | {} {count}",
+            "#".repeat(bar_len)
+        )
+        .map_err(|e| RuntimeError::InternalBug(e.to_string()))?;
+    }
+
+    Ok(output)
+}
+
+/// Renders a table of distinct values with their counts and share of the total, most frequent
+/// first.
+pub fn freq_table(val: RuntimeValue) -> Result<String, RuntimeError> {
+    let mut counts = counts_of(&val, "build frequency table of")?;
+    counts.sort_by(|(a_key, a_count), (b_key, b_count)| {
+        b_count
+            .cmp(a_count)
+            .then_with(|| a_key.partial_cmp(b_key).unwrap_or(Ordering::Equal))
+    });
+
+    let total = counts.iter().map(|(_, c)| *c).sum::<isize>();
+    let rows = counts
+        .iter()
+        .map(|(key, count)| {
+            let percent = if total == 0 {
+                0.0
+            } else {
+                *count as f64 / total as f64 * 100.0
+            };
+
+            (key.to_string(), count.to_string(), format!("{percent:.1}%"))
+        })
+        .collect::<Vec<_>>();
+
+    let header = ("value", "count", "%");
+    let value_width = rows
+        .iter()
+        .map(|(value, _, _)| value.chars().count())
+        .chain([header.0.len()])
+        .max()
+        .unwrap_or(0);
+    let count_width = rows
+        .iter()
+        .map(|(_, count, _)| count.len())
+        .chain([header.1.len()])
+        .max()
+        .unwrap_or(0);
+    let percent_width = rows
+        .iter()
+        .map(|(_, _, percent)| percent.len())
+        .chain([header.2.len()])
+        .max()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+    for (value, count, percent) in [header].into_iter().chain(
+        rows.iter()
+            .map(|(v, c, p)| (v.as_str(), c.as_str(), p.as_str())),
+    ) {
+        writeln!(
+            output,
+            "{value:<value_width$}  {count:>count_width$}  {percent:>percent_width$}"
+        )
+        .map_err(|e| RuntimeError::InternalBug(e.to_string()))?;
+    }
+
+    Ok(output)
+}
+
+/// Groups consecutive items for which `key_fn` returns equal keys.
+pub fn chunk_by(
+    val: RuntimeValue,
+    mut key_fn: impl FnMut(&RuntimeValue) -> RuntimeResult,
+) -> RuntimeResult {
+    let iter = val
+        .to_iter_inner()
+        .map_err(|_| RuntimeError::TypeMismatch(format!("Cannot chunk type {}", val.kind_str())))?;
+
+    let mut chunks = Vec::new();
+    let mut cur_chunk = Vec::new();
+    let mut cur_key = None;
+
+    while let Some(item) = iter.next() {
+        let key = key_fn(&item)?;
+
+        if cur_key.as_ref().is_some_and(|cur_key| *cur_key != key) {
+            chunks.push(RuntimeValue::List(RuntimeList::from_vec(std::mem::take(
+                &mut cur_chunk,
+            ))));
+        }
+
+        cur_key = Some(key);
+        cur_chunk.push(item);
+    }
+
+    if !cur_chunk.is_empty() {
+        chunks.push(RuntimeValue::List(RuntimeList::from_vec(cur_chunk)));
+    }
+
+    Ok(RuntimeValue::List(RuntimeList::from_vec(chunks)))
+}
+
+/// Splits the items into groups separated by the items that match `pred_fn`. The separators
+/// themselves are dropped, much like splitting a string.
+pub fn split_when(
+    val: RuntimeValue,
+    mut pred_fn: impl FnMut(&RuntimeValue) -> RuntimeResult,
+) -> RuntimeResult {
+    let iter = val
+        .to_iter_inner()
+        .map_err(|_| RuntimeError::TypeMismatch(format!("Cannot split type {}", val.kind_str())))?;
+
+    let mut groups = Vec::new();
+    let mut cur_group = Vec::new();
+
+    while let Some(item) = iter.next() {
+        if pred_fn(&item)?.bool() {
+            groups.push(RuntimeValue::List(RuntimeList::from_vec(std::mem::take(
+                &mut cur_group,
+            ))));
+        } else {
+            cur_group.push(item);
+        }
+    }
+
+    groups.push(RuntimeValue::List(RuntimeList::from_vec(cur_group)));
+
+    Ok(RuntimeValue::List(RuntimeList::from_vec(groups)))
+}
+
+/// Counts the items for which `pred_fn` returns a truthy value.
+pub fn count_if(
+    val: RuntimeValue,
+    mut pred_fn: impl FnMut(&RuntimeValue) -> RuntimeResult,
+) -> RuntimeResult {
+    let iter = val.to_iter_inner().map_err(|_| {
+        RuntimeError::TypeMismatch(format!("Cannot count items of type {}", val.kind_str()))
+    })?;
+
+    let mut count = 0;
+    while let Some(item) = iter.next() {
+        if pred_fn(&item)?.bool() {
+            count += 1;
+        }
+    }
+
+    Ok(RuntimeValue::Num(RuntimeNumber::from(count)))
+}
+
+/// Collects the result of `func` for each item into a list.
+pub fn map_items(
+    val: RuntimeValue,
+    mut func: impl FnMut(&RuntimeValue) -> RuntimeResult,
+) -> RuntimeResult {
+    let iter = val.to_iter_inner().map_err(|_| {
+        RuntimeError::TypeMismatch(format!("Cannot map over type {}", val.kind_str()))
+    })?;
+
+    let mut items = Vec::new();
+    while let Some(item) = iter.next() {
+        items.push(func(&item)?);
+    }
+
+    Ok(RuntimeValue::List(RuntimeList::from_vec(items)))
+}
+
+/// Collects the items for which `pred_fn` returns a truthy value into a list.
+pub fn filter(
+    val: RuntimeValue,
+    mut pred_fn: impl FnMut(&RuntimeValue) -> RuntimeResult,
+) -> RuntimeResult {
+    let iter = val.to_iter_inner().map_err(|_| {
+        RuntimeError::TypeMismatch(format!("Cannot filter type {}", val.kind_str()))
+    })?;
+
+    let mut items = Vec::new();
+    while let Some(item) = iter.next() {
+        if pred_fn(&item)?.bool() {
+            items.push(item);
+        }
+    }
+
+    Ok(RuntimeValue::List(RuntimeList::from_vec(items)))
+}
+
+/// Folds the items into a single value, starting from `init` and replacing it with the result of
+/// `func` on it and each item.
+pub fn reduce(
+    val: RuntimeValue,
+    init: RuntimeValue,
+    mut func: impl FnMut(RuntimeValue, &RuntimeValue) -> RuntimeResult,
+) -> RuntimeResult {
+    let iter = val.to_iter_inner().map_err(|_| {
+        RuntimeError::TypeMismatch(format!("Cannot reduce type {}", val.kind_str()))
+    })?;
+
+    let mut acc = init;
+    while let Some(item) = iter.next() {
+        acc = func(acc, &item)?;
+    }
+
+    Ok(acc)
+}
+
+/// Collects the items into a new sorted list. Items that compare equal keep their order, also when
+/// the order is reversed.
+pub fn sorted(
+    val: RuntimeValue,
+    key_fn: Option<impl FnMut(&RuntimeValue) -> RuntimeResult>,
+    reverse: bool,
+) -> RuntimeResult {
+    let iter = val
+        .to_iter_inner()
+        .map_err(|_| RuntimeError::TypeMismatch(format!("Cannot sort type {}", val.kind_str())))?;
+
+    let list = RuntimeList::from_vec(iter.to_vec());
+    if reverse {
+        list.reverse();
+    }
+    RuntimeValue::List(list.clone()).sort(key_fn)?;
+    if reverse {
+        list.reverse();
+    }
+
+    Ok(RuntimeValue::List(list))
+}
+
+pub fn permutations(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let pool = combinatoric_pool("permutations", &args[0])?;
+    let k = match args.get(1) {
+        Some(k) => combinatoric_size("permutations", k)?,
+        None => pool.len(),
+    };
+
+    Ok(RuntimeValue::Iterator(Box::new(RuntimeIterator::from(
+        PermutationsIterator::new(pool, k),
+    ))))
+}
+
+pub fn combinations(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let pool = combinatoric_pool("combinations", &args[0])?;
+    let k = combinatoric_size("combinations", &args[1])?;
+
+    Ok(RuntimeValue::Iterator(Box::new(RuntimeIterator::from(
+        CombinationsIterator::new(pool, k),
+    ))))
+}
+
+// The items are collected up front, since every one of them is needed again and again
+fn combinatoric_pool(name: &str, val: &RuntimeValue) -> Result<Vec<RuntimeValue>, RuntimeError> {
+    let iter = val.to_iter_inner().map_err(|_| {
+        RuntimeError::TypeMismatch(format!("Cannot get {name} of type {}", val.kind_str()))
+    })?;
+
+    Ok(iter.to_vec())
+}
+
+fn combinatoric_size(name: &str, k: &RuntimeValue) -> Result<usize, RuntimeError> {
+    match k {
+        RuntimeValue::Num(n) if n.to_i32().is_some_and(|n| n >= 0) => Ok(n.floor_int() as usize),
+        _ => Err(RuntimeError::TypeMismatch(format!(
+            "{name} expects a non-negative integer size, got '{k}'"
+        ))),
+    }
+}
+
+/// Applies `op` to the first two arguments and wraps the result to an unsigned integer of the
+/// bit width given by the third argument, i.e. the result is in the range `0..2**bits`.
+fn wrapping_op(
+    name: &str,
+    args: Vec<RuntimeValue>,
+    op: impl FnOnce(&RuntimeNumber, &RuntimeNumber) -> RuntimeNumber,
+) -> RuntimeResult {
+    let [a, b, bits] = args.as_slice() else {
+        unreachable!("{name} called with wrong number of arguments");
+    };
+
+    let (RuntimeValue::Num(a), RuntimeValue::Num(b)) = (a, b) else {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "{name} expects two integers, got {} and {}",
+            a.kind_str(),
+            b.kind_str()
+        )));
+    };
+
+    if let Some(n) = [a, b].into_iter().find(|n| !n.is_integer()) {
+        let kind = match n {
+            RuntimeNumber::Rational(_) => "rational",
+            _ => "floating point",
+        };
+        return Err(RuntimeError::TypeMismatch(format!(
+            "Cannot use {name} on {kind} numbers"
+        )));
+    }
+
+    let bits = bits
+        .to_i32()
+        .filter(|n| (1..=128).contains(n))
+        .ok_or_else(|| {
+            RuntimeError::TypeMismatch(format!(
+                "{name} bit width must be an integer between 1 and 128"
+            ))
+        })?;
+
+    let mask = RuntimeNumber::from(u128::MAX >> (128 - bits));
+    let res = op(a, b).bitwise_and(&mask)?;
+
+    Ok(RuntimeValue::Num(res))
+}
+
+pub fn wrap_add(args: Vec<RuntimeValue>) -> RuntimeResult {
+    wrapping_op("wrap_add", args, |a, b| a + b)
+}
+
+pub fn wrap_sub(args: Vec<RuntimeValue>) -> RuntimeResult {
+    wrapping_op("wrap_sub", args, |a, b| a - b)
+}
+
+pub fn wrap_mul(args: Vec<RuntimeValue>) -> RuntimeResult {
+    wrapping_op("wrap_mul", args, |a, b| a * b)
+}
+
+pub fn error(msg: RuntimeValue) -> RuntimeResult {
+    Err(RuntimeError::Plain(msg.to_string()))
+}
+
+pub fn assert_eq(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let [actual, expected] = args.as_slice() else {
+        unreachable!("assert_eq called with wrong number of arguments");
+    };
+
+    if actual != expected {
+        return Err(RuntimeError::Plain(format!(
+            "Assertion failed: expected {}, got {}",
+            expected.repr_string(),
+            actual.repr_string()
+        )));
+    }
+
+    Ok(RuntimeValue::Null)
+}
+
+pub fn deep_eq(args: Vec<RuntimeValue>) -> RuntimeResult {
+    Ok(RuntimeValue::Bool(compare::deep_eq(&args[0], &args[1])))
+}
+
+pub fn approx_eq(args: Vec<RuntimeValue>) -> RuntimeResult {
+    let epsilon = match args.get(2) {
+        None => 1e-9,
+        Some(RuntimeValue::Num(n)) if n.float() >= 0.0 => n.float(),
+        Some(other) => {
+            return Err(RuntimeError::TypeMismatch(format!(
+                "approx_eq expects a non-negative tolerance, got {}",
+                other.repr_string()
+            )))
+        }
+    };
+
+    Ok(RuntimeValue::Bool(compare::approx_eq(
+        &args[0], &args[1], epsilon,
+    )))
+}
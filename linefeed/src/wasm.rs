@@ -0,0 +1,2140 @@
+//! Compilation of programs to WebAssembly modules, for running them outside of the VM.
+//!
+//! Only a subset of the language is supported: null, booleans, numbers that fit in 64 bits,
+//! string constants, ranges of integers and functions without captured variables, along with
+//! `print` and `eprint`. Programs using anything else fail to compile.
+//!
+//! The module mirrors the VM rather than translating the program into WebAssembly's structured
+//! control flow. Values are kept on a stack in linear memory, in slots of 16 bytes holding a tag
+//! and a payload, and the instructions are split into blocks at their labels, with a loop in the
+//! exported `main` function jumping between them. Anything more than a few WebAssembly
+//! instructions is done by helper functions generated alongside `main`.
+//!
+//! The host provides output through the imports `env.write(fd, ptr, len)`, which writes bytes of
+//! the module's memory, `env.write_int(fd, n)` and `env.write_float(fd, x)`, which should format
+//! numbers like the VM, and `env.pow(x, y)` for powers of floats. Runtime errors are written to fd
+//! 2 before trapping.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, DataSection, EntityType, ExportKind, ExportSection,
+    Function, FunctionSection, GlobalSection, GlobalType, ImportSection, Instruction as Wasm,
+    MemArg, MemorySection, MemoryType, Module, TypeSection, ValType,
+};
+
+use crate::compiler::{
+    ir_value::{IrNumber, IrValue},
+    stdlib_fn::StdlibFn,
+    CompileError, Instruction, Label, Program,
+};
+
+// The tags of values
+const NULL: i32 = 0;
+const UNINIT: i32 = 1;
+const BOOL: i32 = 2;
+const INT: i32 = 3;
+const FLOAT: i32 = 4;
+const STR: i32 = 5;
+const FUNCTION: i32 = 6;
+const RANGE: i32 = 7;
+const ITERATOR: i32 = 8;
+
+const TYPE_NAMES: [(i32, &str); 9] = [
+    (NULL, "null"),
+    (UNINIT, "uninitialized"),
+    (BOOL, "boolean"),
+    (INT, "number"),
+    (FLOAT, "number"),
+    (STR, "str"),
+    (FUNCTION, "function"),
+    (RANGE, "range"),
+    (ITERATOR, "iterator"),
+];
+
+// The globals, which are indices of slots for the pointers of the stacks, and an address for the
+// heap
+const SP: u32 = 0;
+const BP: u32 = 1;
+const FP: u32 = 2;
+const HP: u32 = 3;
+
+// The value stack starts at address 0, followed by the frame stack, the data and the heap. Frames
+// hold the block to return to and the caller's base pointer.
+const STACK_SLOTS: i32 = 1 << 20;
+const MAX_FRAMES: i32 = 1 << 16;
+const FRAMES_BASE: i32 = STACK_SLOTS * 16;
+const DATA_BASE: i32 = FRAMES_BASE + MAX_FRAMES * 8;
+// Calls fail with a stack overflow once fewer slots than this are left, which leaves room for the
+// values pushed between calls
+const STACK_RESERVE: i32 = 1 << 12;
+
+const PAGE_SIZE: i32 = 1 << 16;
+
+const TAG: MemArg = mem_arg(0, 2);
+const PAYLOAD: MemArg = mem_arg(8, 3);
+const SLOT_START: MemArg = mem_arg(0, 3);
+const RETURN_BLOCK: MemArg = mem_arg(FRAMES_BASE as u64, 2);
+const RETURN_BP: MemArg = mem_arg(FRAMES_BASE as u64 + 4, 2);
+
+const OVERFLOW: &str = "Integer overflow, as big integers can't be compiled to WebAssembly";
+
+// The imported functions, which come before the functions of the module
+const WRITE: u32 = 0;
+const WRITE_INT: u32 = 1;
+const WRITE_FLOAT: u32 = 2;
+const POW: u32 = 3;
+const IMPORTS: [(&str, &[ValType], &[ValType]); 4] = [
+    ("write", &[ValType::I32, ValType::I32, ValType::I32], &[]),
+    ("write_int", &[ValType::I32, ValType::I64], &[]),
+    ("write_float", &[ValType::I32, ValType::F64], &[]),
+    ("pow", &[ValType::F64, ValType::F64], &[ValType::F64]),
+];
+
+// `main` is the first function of the module, followed by the helpers
+const MAIN: u32 = IMPORTS.len() as u32;
+
+/// Compiles a program to the bytes of a WebAssembly module exporting its memory and a `main`
+/// function running the program.
+pub fn compile_to_wasm(program: &Program<Instruction>) -> Result<Vec<u8>, CompileError> {
+    let mut runtime = Runtime::default();
+    let main = compile_main(program, &mut runtime)?;
+    Ok(runtime.finish(main))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    DivFloor,
+    Mod,
+    Pow,
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Range,
+    Xor,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    LeftShift,
+    RightShift,
+}
+
+impl BinOp {
+    fn from_instruction(instruction: &Instruction) -> Option<Self> {
+        let op = match instruction {
+            Instruction::Add => BinOp::Add,
+            Instruction::Sub => BinOp::Sub,
+            Instruction::Mul => BinOp::Mul,
+            Instruction::Div => BinOp::Div,
+            Instruction::DivFloor => BinOp::DivFloor,
+            Instruction::Mod => BinOp::Mod,
+            Instruction::Pow => BinOp::Pow,
+            Instruction::Eq => BinOp::Eq,
+            Instruction::NotEq => BinOp::NotEq,
+            Instruction::Less => BinOp::Less,
+            Instruction::LessEq => BinOp::LessEq,
+            Instruction::Greater => BinOp::Greater,
+            Instruction::GreaterEq => BinOp::GreaterEq,
+            Instruction::Range => BinOp::Range,
+            Instruction::Xor => BinOp::Xor,
+            Instruction::BitwiseAnd => BinOp::BitwiseAnd,
+            Instruction::BitwiseOr => BinOp::BitwiseOr,
+            Instruction::BitwiseXor => BinOp::BitwiseXor,
+            Instruction::LeftShift => BinOp::LeftShift,
+            Instruction::RightShift => BinOp::RightShift,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    // How the VM's error describes what the operator does
+    fn action(self) -> &'static str {
+        match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "subtract",
+            BinOp::Mul => "multiply",
+            BinOp::Div | BinOp::DivFloor => "divide",
+            BinOp::Mod => "modulo",
+            BinOp::Pow => "power",
+            BinOp::Eq | BinOp::NotEq => "compare",
+            BinOp::Less | BinOp::LessEq | BinOp::Greater | BinOp::GreaterEq => "compare",
+            BinOp::Range => "make range from",
+            BinOp::Xor => "xor",
+            BinOp::BitwiseAnd => "use & on",
+            BinOp::BitwiseOr => "use | on",
+            BinOp::BitwiseXor => "use ^ on",
+            BinOp::LeftShift => "use << on",
+            BinOp::RightShift => "use >> on",
+        }
+    }
+}
+
+/// The functions `main` calls for anything more than a few instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Helper {
+    /// `(tag, payload)`, pushing a value.
+    Push,
+    /// `(slot) -> tag`
+    Tag,
+    /// `(slot) -> payload`
+    Payload,
+    /// `(from, to)`, copying the value of one slot to another.
+    Copy,
+    Swap,
+    RemoveIndex,
+    /// `() -> payload`, popping an integer used as an address.
+    PopAddress,
+    /// `() -> bool`, popping a value and telling whether it is truthy.
+    PopTruthy,
+    /// `(tag)`, popping a value and pushing whether it has the tag.
+    IsTag,
+    Not,
+    Neg,
+    BitwiseNot,
+    Binary(BinOp),
+    /// `(a, b) -> a + b`, failing on overflow, and likewise for the other two.
+    AddChecked,
+    SubChecked,
+    MulChecked,
+    ToIter,
+    /// `() -> bool`, popping an iterator and pushing its next value if it has one.
+    Next,
+    /// `() -> address`, allocating 16 bytes on the heap.
+    Alloc,
+    /// `(num_args, return_block) -> block`, starting a call and returning the block to go to.
+    Call,
+    /// `() -> block`, returning from a call and returning the block to go back to.
+    Return,
+    /// `(num_args)`
+    EnterInlined,
+    ExitInlined,
+    /// `(fd, num_args)`
+    Print,
+    /// `(fd, slot)`
+    WriteValue,
+    /// `(tag)`, writing the name of a type to stderr.
+    WriteTypeName,
+    /// `(ptr, len)`, failing with a runtime error.
+    Fail,
+}
+
+impl Helper {
+    fn signature(self) -> (&'static [ValType], &'static [ValType]) {
+        use ValType::{I32, I64};
+
+        match self {
+            Helper::Push => (&[I32, I64], &[]),
+            Helper::Tag => (&[I32], &[I32]),
+            Helper::Payload => (&[I32], &[I64]),
+            Helper::Copy | Helper::Print | Helper::WriteValue | Helper::Fail => (&[I32, I32], &[]),
+            Helper::PopAddress => (&[], &[I64]),
+            Helper::PopTruthy | Helper::Next | Helper::Alloc | Helper::Return => (&[], &[I32]),
+            Helper::IsTag | Helper::EnterInlined | Helper::WriteTypeName => (&[I32], &[]),
+            Helper::AddChecked | Helper::SubChecked | Helper::MulChecked => (&[I64, I64], &[I64]),
+            Helper::Call => (&[I32, I32], &[I32]),
+            Helper::Swap
+            | Helper::RemoveIndex
+            | Helper::Not
+            | Helper::Neg
+            | Helper::BitwiseNot
+            | Helper::Binary(_)
+            | Helper::ToIter
+            | Helper::ExitInlined => (&[], &[]),
+        }
+    }
+}
+
+// Parts of the message of a runtime error
+enum Piece<'a> {
+    Str(&'a str),
+    // The name of the type with the tag in a local
+    TypeOf(u32),
+    // An i32 in a local
+    Int(u32),
+}
+
+/// Everything in the module besides `main`: the helpers, which are generated when first used, the
+/// types of all functions and the data.
+#[derive(Default)]
+struct Runtime {
+    types: Vec<(Vec<ValType>, Vec<ValType>)>,
+    helpers: HashMap<Helper, u32>,
+    // The helpers in the order of their indices, with their code once generated
+    functions: Vec<(Helper, Option<Function>)>,
+    data: Vec<u8>,
+    strings: HashMap<String, (i32, i32)>,
+}
+
+impl Runtime {
+    fn ty(&mut self, params: &[ValType], results: &[ValType]) -> u32 {
+        let ty = (params.to_vec(), results.to_vec());
+        match self.types.iter().position(|other| *other == ty) {
+            Some(index) => index as u32,
+            None => {
+                self.types.push(ty);
+                self.types.len() as u32 - 1
+            }
+        }
+    }
+
+    /// The index of a helper function, which is generated by [`Runtime::finish`].
+    fn helper(&mut self, helper: Helper) -> u32 {
+        if let Some(&index) = self.helpers.get(&helper) {
+            return index;
+        }
+
+        let index = MAIN + 1 + self.functions.len() as u32;
+        self.helpers.insert(helper, index);
+        self.functions.push((helper, None));
+        index
+    }
+
+    /// The address and length of a string in the data. Equal strings share their bytes, which
+    /// lets strings be compared by address.
+    fn string(&mut self, s: &str) -> (i32, i32) {
+        if let Some(&string) = self.strings.get(s) {
+            return string;
+        }
+
+        let string = (DATA_BASE + self.data.len() as i32, s.len() as i32);
+        self.data.extend_from_slice(s.as_bytes());
+        self.strings.insert(s.to_string(), string);
+        string
+    }
+
+    fn finish(mut self, main: Function) -> Vec<u8> {
+        // Generating a helper can use more helpers, which are added to the end
+        let mut generated = 0;
+        while let Some(&(helper, _)) = self.functions.get(generated) {
+            let code = self.generate(helper);
+            self.functions[generated].1 = Some(code);
+            generated += 1;
+        }
+
+        let mut imports = ImportSection::new();
+        for (name, params, results) in IMPORTS {
+            let ty = self.ty(params, results);
+            imports.import("env", name, EntityType::Function(ty));
+        }
+
+        let mut functions = FunctionSection::new();
+        let mut code = CodeSection::new();
+        let main_ty = self.ty(&[], &[]);
+        functions.function(main_ty);
+        code.function(&main);
+        for (helper, helper_code) in std::mem::take(&mut self.functions) {
+            let (params, results) = helper.signature();
+            functions.function(self.ty(params, results));
+            code.function(&helper_code.unwrap());
+        }
+
+        let mut types = TypeSection::new();
+        for (params, results) in &self.types {
+            types.function(params.iter().copied(), results.iter().copied());
+        }
+
+        let heap_base = (DATA_BASE + self.data.len() as i32 + 15) & !15;
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: (heap_base / PAGE_SIZE + 1) as u64,
+            maximum: None,
+            memory64: false,
+            shared: false,
+        });
+
+        let mut globals = GlobalSection::new();
+        for initial in [0, 0, 0, heap_base] {
+            let ty = GlobalType {
+                val_type: ValType::I32,
+                mutable: true,
+            };
+            globals.global(ty, &ConstExpr::i32_const(initial));
+        }
+
+        let mut exports = ExportSection::new();
+        exports.export("main", ExportKind::Func, MAIN);
+        exports.export("memory", ExportKind::Memory, 0);
+
+        let mut data = DataSection::new();
+        data.active(
+            0,
+            &ConstExpr::i32_const(DATA_BASE),
+            self.data.iter().copied(),
+        );
+
+        let mut module = Module::new();
+        module
+            .section(&types)
+            .section(&imports)
+            .section(&functions)
+            .section(&memories)
+            .section(&globals)
+            .section(&exports)
+            .section(&code)
+            .section(&data);
+        module.finish()
+    }
+
+    // Emits code writing a string to the file descriptor left on the stack by `fd`
+    fn write_str(&mut self, f: &mut Function, fd: Wasm, s: &str) {
+        let (ptr, len) = self.string(s);
+        emit(
+            f,
+            &[
+                fd,
+                Wasm::I32Const(ptr),
+                Wasm::I32Const(len),
+                Wasm::Call(WRITE),
+            ],
+        );
+    }
+
+    // Emits code failing with a runtime error made of `pieces`
+    fn fail(&mut self, f: &mut Function, pieces: &[Piece]) {
+        let stderr = || Wasm::I32Const(2);
+        self.write_str(f, stderr(), "Error: ");
+        for piece in pieces {
+            match piece {
+                Piece::Str(s) => self.write_str(f, stderr(), s),
+                Piece::TypeOf(local) => {
+                    let write_type_name = self.helper(Helper::WriteTypeName);
+                    emit(f, &[Wasm::LocalGet(*local), Wasm::Call(write_type_name)]);
+                }
+                Piece::Int(local) => emit(
+                    f,
+                    &[
+                        stderr(),
+                        Wasm::LocalGet(*local),
+                        Wasm::I64ExtendI32U,
+                        Wasm::Call(WRITE_INT),
+                    ],
+                ),
+            }
+        }
+        self.write_str(f, stderr(), "\n");
+        emit(f, &[Wasm::Unreachable]);
+    }
+
+    // Emits `if <condition on the stack> { fail }`
+    fn fail_if(&mut self, f: &mut Function, pieces: &[Piece]) {
+        emit(f, &[Wasm::If(BlockType::Empty)]);
+        self.fail(f, pieces);
+        emit(f, &[Wasm::End]);
+    }
+
+    fn generate(&mut self, helper: Helper) -> Function {
+        use ValType::{F64, I32, I64};
+
+        let push = self.helper(Helper::Push);
+        let tag = self.helper(Helper::Tag);
+        let payload = self.helper(Helper::Payload);
+        let copy = self.helper(Helper::Copy);
+
+        match helper {
+            Helper::Push => {
+                let mut f = Function::new([]);
+                emit(&mut f, &[Wasm::GlobalGet(SP)]);
+                emit(&mut f, &slot_addr());
+                emit(&mut f, &[Wasm::LocalGet(0), Wasm::I32Store(TAG)]);
+                emit(&mut f, &[Wasm::GlobalGet(SP)]);
+                emit(&mut f, &slot_addr());
+                emit(&mut f, &[Wasm::LocalGet(1), Wasm::I64Store(PAYLOAD)]);
+                emit(&mut f, &add_to_sp(1));
+                finish(f)
+            }
+
+            Helper::Tag | Helper::Payload => {
+                let mut f = Function::new([]);
+                emit(&mut f, &[Wasm::LocalGet(0)]);
+                emit(&mut f, &slot_addr());
+                match helper {
+                    Helper::Tag => emit(&mut f, &[Wasm::I32Load(TAG)]),
+                    _ => emit(&mut f, &[Wasm::I64Load(PAYLOAD)]),
+                }
+                finish(f)
+            }
+
+            Helper::Copy => {
+                let mut f = Function::new([]);
+                for mem in [SLOT_START, PAYLOAD] {
+                    emit(&mut f, &[Wasm::LocalGet(1)]);
+                    emit(&mut f, &slot_addr());
+                    emit(&mut f, &[Wasm::LocalGet(0)]);
+                    emit(&mut f, &slot_addr());
+                    emit(&mut f, &[Wasm::I64Load(mem), Wasm::I64Store(mem)]);
+                }
+                finish(f)
+            }
+
+            Helper::Swap => {
+                // The top value is kept in locals 0 and 1 while the one below is copied up
+                let mut f = Function::new([(2, I64)]);
+                for (local, mem) in [(0, SLOT_START), (1, PAYLOAD)] {
+                    emit(&mut f, &sp_minus(1));
+                    emit(&mut f, &slot_addr());
+                    emit(&mut f, &[Wasm::I64Load(mem), Wasm::LocalSet(local)]);
+                }
+                emit(&mut f, &sp_minus(2));
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::Call(copy)]);
+                for (local, mem) in [(0, SLOT_START), (1, PAYLOAD)] {
+                    emit(&mut f, &sp_minus(2));
+                    emit(&mut f, &slot_addr());
+                    emit(&mut f, &[Wasm::LocalGet(local), Wasm::I64Store(mem)]);
+                }
+                finish(f)
+            }
+
+            Helper::RemoveIndex => {
+                let pop_address = self.helper(Helper::PopAddress);
+                let mut f = Function::new([(1, I32)]);
+                emit(
+                    &mut f,
+                    &[Wasm::Call(pop_address), Wasm::I32WrapI64, Wasm::LocalTee(0)],
+                );
+                emit(&mut f, &slot_addr());
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(&mut f, &slot_addr());
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::LocalGet(0), Wasm::I32Sub]);
+                emit(&mut f, &slot_addr());
+                emit(
+                    &mut f,
+                    &[Wasm::MemoryCopy {
+                        src_mem: 0,
+                        dst_mem: 0,
+                    }],
+                );
+                emit(&mut f, &add_to_sp(-1));
+                finish(f)
+            }
+
+            Helper::PopAddress => {
+                let mut f = Function::new([(1, I32)]);
+                emit(&mut f, &add_to_sp(-1));
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(SP), Wasm::Call(tag), Wasm::LocalTee(0)],
+                );
+                emit(&mut f, &[Wasm::I32Const(INT), Wasm::I32Ne]);
+                self.fail_if(
+                    &mut f,
+                    &[Piece::Str("Invalid address of type "), Piece::TypeOf(0)],
+                );
+                emit(&mut f, &[Wasm::GlobalGet(SP), Wasm::Call(payload)]);
+                finish(f)
+            }
+
+            Helper::PopTruthy => {
+                let mut f = Function::new([(1, I32), (1, I64)]);
+                emit(&mut f, &add_to_sp(-1));
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(SP), Wasm::Call(tag), Wasm::LocalSet(0)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(SP), Wasm::Call(payload), Wasm::LocalSet(1)],
+                );
+
+                // Null and uninitialized values are the tags below booleans
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::I32Const(BOOL), Wasm::I32LtU],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::If(BlockType::Empty), Wasm::I32Const(0), Wasm::Return],
+                );
+                emit(&mut f, &[Wasm::End]);
+
+                emit(&mut f, &has_tag(0, BOOL));
+                emit(&mut f, &has_tag(0, INT));
+                emit(&mut f, &[Wasm::I32Or, Wasm::If(BlockType::Empty)]);
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::LocalGet(1),
+                        Wasm::I64Const(0),
+                        Wasm::I64Ne,
+                        Wasm::Return,
+                    ],
+                );
+                emit(&mut f, &[Wasm::End]);
+
+                emit(&mut f, &has_tag(0, FLOAT));
+                emit(&mut f, &[Wasm::If(BlockType::Empty), Wasm::LocalGet(1)]);
+                emit(
+                    &mut f,
+                    &[Wasm::F64ReinterpretI64, Wasm::F64Const(0.0), Wasm::F64Ne],
+                );
+                emit(&mut f, &[Wasm::Return, Wasm::End]);
+
+                // Strings are truthy unless empty
+                emit(&mut f, &has_tag(0, STR));
+                emit(&mut f, &[Wasm::If(BlockType::Empty), Wasm::LocalGet(1)]);
+                emit(&mut f, &[Wasm::I64Const(0xffff_ffff), Wasm::I64And]);
+                emit(
+                    &mut f,
+                    &[Wasm::I64Const(0), Wasm::I64Ne, Wasm::Return, Wasm::End],
+                );
+
+                emit(&mut f, &[Wasm::I32Const(1)]);
+                finish(f)
+            }
+
+            Helper::IsTag => {
+                let mut f = Function::new([]);
+                emit(&mut f, &add_to_sp(-1));
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(BOOL), Wasm::GlobalGet(SP), Wasm::Call(tag)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::I32Eq, Wasm::I64ExtendI32U],
+                );
+                emit(&mut f, &[Wasm::Call(push)]);
+                finish(f)
+            }
+
+            Helper::Not => {
+                let pop_truthy = self.helper(Helper::PopTruthy);
+                let mut f = Function::new([]);
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(BOOL), Wasm::Call(pop_truthy), Wasm::I32Eqz],
+                );
+                emit(&mut f, &[Wasm::I64ExtendI32U, Wasm::Call(push)]);
+                finish(f)
+            }
+
+            Helper::Neg | Helper::BitwiseNot => {
+                let mut f = Function::new([(1, I32), (1, I64)]);
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::Call(tag), Wasm::LocalSet(0)]);
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::Call(payload), Wasm::LocalSet(1)]);
+
+                // The payload is replaced in place, as the tag stays the same
+                let (int_op, float_op) = match helper {
+                    Helper::Neg => (
+                        vec![Wasm::I64Const(0), Wasm::LocalGet(1), Wasm::I64Sub],
+                        vec![Wasm::LocalGet(1), Wasm::I64Const(i64::MIN), Wasm::I64Xor],
+                    ),
+                    _ => (
+                        vec![Wasm::LocalGet(1), Wasm::I64Const(-1), Wasm::I64Xor],
+                        vec![],
+                    ),
+                };
+
+                emit(&mut f, &has_tag(0, INT));
+                emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                if helper == Helper::Neg {
+                    emit(
+                        &mut f,
+                        &[Wasm::LocalGet(1), Wasm::I64Const(i64::MIN), Wasm::I64Eq],
+                    );
+                    self.fail_if(&mut f, &[Piece::Str(OVERFLOW)]);
+                }
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &slot_addr());
+                emit(&mut f, &int_op);
+                emit(&mut f, &[Wasm::I64Store(PAYLOAD), Wasm::Return, Wasm::End]);
+
+                if !float_op.is_empty() {
+                    emit(&mut f, &has_tag(0, FLOAT));
+                    emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                    emit(&mut f, &sp_minus(1));
+                    emit(&mut f, &slot_addr());
+                    emit(&mut f, &float_op);
+                    emit(&mut f, &[Wasm::I64Store(PAYLOAD), Wasm::Return, Wasm::End]);
+                }
+
+                let msg = match helper {
+                    Helper::Neg => "Type mismatch: Cannot negate type '",
+                    _ => "Type mismatch: Cannot use ~ on type '",
+                };
+                self.fail(
+                    &mut f,
+                    &[Piece::Str(msg), Piece::TypeOf(0), Piece::Str("'")],
+                );
+                finish(f)
+            }
+
+            Helper::AddChecked | Helper::SubChecked | Helper::MulChecked => {
+                let mut f = Function::new([(1, I64)]);
+                let op = match helper {
+                    Helper::AddChecked => Wasm::I64Add,
+                    Helper::SubChecked => Wasm::I64Sub,
+                    _ => Wasm::I64Mul,
+                };
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::LocalGet(1), op, Wasm::LocalSet(2)],
+                );
+
+                match helper {
+                    // Overflowing when the result's sign differs from both operands' signs
+                    Helper::AddChecked => emit(
+                        &mut f,
+                        &[
+                            Wasm::LocalGet(0),
+                            Wasm::LocalGet(2),
+                            Wasm::I64Xor,
+                            Wasm::LocalGet(1),
+                            Wasm::LocalGet(2),
+                            Wasm::I64Xor,
+                            Wasm::I64And,
+                            Wasm::I64Const(0),
+                            Wasm::I64LtS,
+                        ],
+                    ),
+                    // Overflowing when the operands' signs differ and the result's sign differs
+                    // from the first operand's
+                    Helper::SubChecked => emit(
+                        &mut f,
+                        &[
+                            Wasm::LocalGet(0),
+                            Wasm::LocalGet(1),
+                            Wasm::I64Xor,
+                            Wasm::LocalGet(0),
+                            Wasm::LocalGet(2),
+                            Wasm::I64Xor,
+                            Wasm::I64And,
+                            Wasm::I64Const(0),
+                            Wasm::I64LtS,
+                        ],
+                    ),
+                    // Overflowing when dividing the result doesn't give back the operand, where
+                    // `-1 * MIN` is checked first as its division traps
+                    _ => {
+                        emit(
+                            &mut f,
+                            &[Wasm::LocalGet(0), Wasm::I64Const(-1), Wasm::I64Eq],
+                        );
+                        emit(
+                            &mut f,
+                            &[Wasm::LocalGet(1), Wasm::I64Const(i64::MIN), Wasm::I64Eq],
+                        );
+                        emit(&mut f, &[Wasm::I32And]);
+                        self.fail_if(&mut f, &[Piece::Str(OVERFLOW)]);
+                        emit(
+                            &mut f,
+                            &[
+                                Wasm::LocalGet(0),
+                                Wasm::I64Const(0),
+                                Wasm::I64Ne,
+                                Wasm::If(BlockType::Empty),
+                                Wasm::LocalGet(2),
+                                Wasm::LocalGet(0),
+                                Wasm::I64DivS,
+                                Wasm::LocalGet(1),
+                                Wasm::I64Ne,
+                            ],
+                        );
+                        self.fail_if(&mut f, &[Piece::Str(OVERFLOW)]);
+                        emit(&mut f, &[Wasm::End, Wasm::I32Const(0)]);
+                    }
+                }
+                self.fail_if(&mut f, &[Piece::Str(OVERFLOW)]);
+                emit(&mut f, &[Wasm::LocalGet(2)]);
+                finish(f)
+            }
+
+            Helper::Binary(op) => self.generate_binary(op),
+
+            Helper::ToIter => {
+                let alloc = self.helper(Helper::Alloc);
+                let mut f = Function::new([(1, I32), (1, I64), (1, I32)]);
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::Call(tag), Wasm::LocalSet(0)]);
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::Call(payload), Wasm::LocalSet(1)]);
+
+                emit(&mut f, &has_tag(0, ITERATOR));
+                emit(
+                    &mut f,
+                    &[Wasm::If(BlockType::Empty), Wasm::Return, Wasm::End],
+                );
+
+                // Iterators are shared by the copies of them, so their position is kept on the
+                // heap, followed by the end of the range
+                emit(&mut f, &has_tag(0, RANGE));
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::If(BlockType::Empty),
+                        Wasm::Call(alloc),
+                        Wasm::LocalTee(2),
+                    ],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(1), Wasm::I64Const(32), Wasm::I64ShrS],
+                );
+                emit(&mut f, &[Wasm::I64Store(SLOT_START), Wasm::LocalGet(2)]);
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(1), Wasm::I32WrapI64, Wasm::I64ExtendI32S],
+                );
+                emit(&mut f, &[Wasm::I64Store(PAYLOAD)]);
+                emit(&mut f, &add_to_sp(-1));
+                emit(&mut f, &[Wasm::I32Const(ITERATOR), Wasm::LocalGet(2)]);
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::I64ExtendI32U,
+                        Wasm::Call(push),
+                        Wasm::Return,
+                        Wasm::End,
+                    ],
+                );
+
+                self.fail(
+                    &mut f,
+                    &[
+                        Piece::Str("Type mismatch: Cannot iterate over type '"),
+                        Piece::TypeOf(0),
+                        Piece::Str("'"),
+                    ],
+                );
+                finish(f)
+            }
+
+            Helper::Next => {
+                let mut f = Function::new([(2, I32), (1, I64)]);
+                emit(&mut f, &add_to_sp(-1));
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(SP), Wasm::Call(tag), Wasm::LocalTee(0)],
+                );
+                emit(&mut f, &[Wasm::I32Const(ITERATOR), Wasm::I32Ne]);
+                self.fail_if(
+                    &mut f,
+                    &[
+                        Piece::Str("Type mismatch: Expected an iterator, got "),
+                        Piece::TypeOf(0),
+                    ],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(SP), Wasm::Call(payload), Wasm::I32WrapI64],
+                );
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::LocalTee(1),
+                        Wasm::I64Load(SLOT_START),
+                        Wasm::LocalTee(2),
+                    ],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(1), Wasm::I64Load(PAYLOAD), Wasm::I64GeS],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::If(BlockType::Empty), Wasm::I32Const(0), Wasm::Return],
+                );
+                emit(&mut f, &[Wasm::End]);
+
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(1), Wasm::LocalGet(2), Wasm::I64Const(1)],
+                );
+                emit(&mut f, &[Wasm::I64Add, Wasm::I64Store(SLOT_START)]);
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(INT), Wasm::LocalGet(2), Wasm::Call(push)],
+                );
+                emit(&mut f, &[Wasm::I32Const(1)]);
+                finish(f)
+            }
+
+            Helper::Alloc => {
+                let mut f = Function::new([(1, I32)]);
+                emit(&mut f, &[Wasm::GlobalGet(HP), Wasm::LocalSet(0)]);
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(HP), Wasm::I32Const(16), Wasm::I32Add],
+                );
+                emit(&mut f, &[Wasm::GlobalSet(HP), Wasm::GlobalGet(HP)]);
+                emit(
+                    &mut f,
+                    &[Wasm::MemorySize(0), Wasm::I32Const(16), Wasm::I32Shl],
+                );
+                emit(&mut f, &[Wasm::I32GtU, Wasm::If(BlockType::Empty)]);
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(1), Wasm::MemoryGrow(0), Wasm::I32Const(-1)],
+                );
+                emit(&mut f, &[Wasm::I32Eq]);
+                self.fail_if(&mut f, &[Piece::Str("Out of memory")]);
+                emit(&mut f, &[Wasm::End, Wasm::LocalGet(0)]);
+                finish(f)
+            }
+
+            Helper::Call => {
+                // Locals 2 to 6 are the function's slot, the function, its arity and minimum
+                // arity, and its tag
+                let mut f = Function::new([(1, I32), (1, I64), (3, I32)]);
+                emit(&mut f, &sp_minus(1));
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::I32Sub, Wasm::LocalTee(2)],
+                );
+                emit(&mut f, &[Wasm::Call(tag), Wasm::LocalTee(6)]);
+                emit(&mut f, &[Wasm::I32Const(FUNCTION), Wasm::I32Ne]);
+                self.fail_if(
+                    &mut f,
+                    &[
+                        Piece::Str("Type mismatch: Cannot call type "),
+                        Piece::TypeOf(6),
+                        Piece::Str(" as a function"),
+                    ],
+                );
+
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(2), Wasm::Call(payload), Wasm::LocalTee(3)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::I64Const(32), Wasm::I64ShrU, Wasm::I32WrapI64],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(0xffff), Wasm::I32And, Wasm::LocalSet(4)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(3), Wasm::I64Const(48), Wasm::I64ShrU],
+                );
+                emit(&mut f, &[Wasm::I32WrapI64, Wasm::LocalSet(5)]);
+
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::LocalGet(5), Wasm::I32LtU],
+                );
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::LocalGet(0),
+                        Wasm::LocalGet(4),
+                        Wasm::I32GtU,
+                        Wasm::I32Or,
+                    ],
+                );
+                emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                emit(&mut f, &[Wasm::LocalGet(4), Wasm::LocalGet(5), Wasm::I32Eq]);
+                self.fail_if(
+                    &mut f,
+                    &[
+                        Piece::Str("Type mismatch: Expected "),
+                        Piece::Int(4),
+                        Piece::Str(" arguments, got "),
+                        Piece::Int(0),
+                    ],
+                );
+                self.fail(
+                    &mut f,
+                    &[
+                        Piece::Str("Type mismatch: Expected "),
+                        Piece::Int(5),
+                        Piece::Str(" to "),
+                        Piece::Int(4),
+                        Piece::Str(" arguments, got "),
+                        Piece::Int(0),
+                    ],
+                );
+                emit(&mut f, &[Wasm::End]);
+
+                // Arguments left out get their default values when the function starts
+                emit(
+                    &mut f,
+                    &[Wasm::Block(BlockType::Empty), Wasm::Loop(BlockType::Empty)],
+                );
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::LocalGet(0),
+                        Wasm::LocalGet(4),
+                        Wasm::I32GeU,
+                        Wasm::BrIf(1),
+                    ],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(UNINIT), Wasm::I64Const(0), Wasm::Call(push)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalSet(0), Wasm::Br(0), Wasm::End, Wasm::End],
+                );
+
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::GlobalGet(FP),
+                        Wasm::I32Const(MAX_FRAMES),
+                        Wasm::I32GeU,
+                    ],
+                );
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::GlobalGet(SP),
+                        Wasm::I32Const(STACK_SLOTS - STACK_RESERVE),
+                    ],
+                );
+                emit(&mut f, &[Wasm::I32GtU, Wasm::I32Or]);
+                self.fail_if(&mut f, &[Piece::Str("Stack overflow")]);
+
+                emit(&mut f, &frame_addr());
+                emit(&mut f, &[Wasm::LocalGet(1), Wasm::I32Store(RETURN_BLOCK)]);
+                emit(&mut f, &frame_addr());
+                emit(&mut f, &[Wasm::GlobalGet(BP), Wasm::I32Store(RETURN_BP)]);
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(FP), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(&mut f, &[Wasm::GlobalSet(FP)]);
+
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(2), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalSet(BP), Wasm::LocalGet(3), Wasm::I32WrapI64],
+                );
+                finish(f)
+            }
+
+            Helper::Return => {
+                let mut f = Function::new([(1, I32)]);
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(BP), Wasm::I32Const(1), Wasm::I32Sub],
+                );
+                emit(&mut f, &[Wasm::LocalSet(0)]);
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(FP), Wasm::I32Const(1), Wasm::I32Sub],
+                );
+                emit(&mut f, &[Wasm::GlobalSet(FP)]);
+
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::LocalGet(0), Wasm::Call(copy)]);
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(&mut f, &[Wasm::GlobalSet(SP)]);
+
+                emit(&mut f, &frame_addr());
+                emit(&mut f, &[Wasm::I32Load(RETURN_BP), Wasm::GlobalSet(BP)]);
+                emit(&mut f, &frame_addr());
+                emit(&mut f, &[Wasm::I32Load(RETURN_BLOCK)]);
+                finish(f)
+            }
+
+            Helper::EnterInlined => {
+                // The function's slot keeps the caller's base pointer, see
+                // `Instruction::EnterInlined`
+                let mut f = Function::new([(1, I32)]);
+                emit(&mut f, &sp_minus(1));
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::I32Sub, Wasm::LocalTee(1)],
+                );
+                emit(&mut f, &slot_addr());
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(INT), Wasm::I32Store(TAG), Wasm::LocalGet(1)],
+                );
+                emit(&mut f, &slot_addr());
+                emit(&mut f, &[Wasm::GlobalGet(BP), Wasm::I64ExtendI32U]);
+                emit(&mut f, &[Wasm::I64Store(PAYLOAD)]);
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(1), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(&mut f, &[Wasm::GlobalSet(BP)]);
+                finish(f)
+            }
+
+            Helper::ExitInlined => {
+                // The caller's base pointer is read before the return value replaces it
+                let mut f = Function::new([(1, I32)]);
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(BP), Wasm::I32Const(1), Wasm::I32Sub],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalTee(0), Wasm::Call(payload), Wasm::I32WrapI64],
+                );
+                emit(&mut f, &[Wasm::GlobalSet(BP)]);
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::LocalGet(0), Wasm::Call(copy)]);
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(0), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(&mut f, &[Wasm::GlobalSet(SP)]);
+                finish(f)
+            }
+
+            Helper::Print => {
+                // Like the VM, the values are separated by spaces and the last one is returned
+                let write_value = self.helper(Helper::WriteValue);
+                let mut f = Function::new([(2, I32)]);
+                emit(
+                    &mut f,
+                    &[Wasm::GlobalGet(SP), Wasm::LocalGet(1), Wasm::I32Sub],
+                );
+                emit(&mut f, &[Wasm::LocalTee(2), Wasm::LocalSet(3)]);
+
+                emit(
+                    &mut f,
+                    &[Wasm::Block(BlockType::Empty), Wasm::Loop(BlockType::Empty)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(3), Wasm::GlobalGet(SP), Wasm::I32GeU],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::BrIf(1), Wasm::LocalGet(3), Wasm::LocalGet(2)],
+                );
+                emit(&mut f, &[Wasm::I32GtU, Wasm::If(BlockType::Empty)]);
+                self.write_str(&mut f, Wasm::LocalGet(0), " ");
+                emit(&mut f, &[Wasm::End]);
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::LocalGet(0),
+                        Wasm::LocalGet(3),
+                        Wasm::Call(write_value),
+                    ],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(3), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalSet(3), Wasm::Br(0), Wasm::End, Wasm::End],
+                );
+                self.write_str(&mut f, Wasm::LocalGet(0), "\n");
+
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(1), Wasm::I32Eqz, Wasm::If(BlockType::Empty)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(NULL), Wasm::I64Const(0), Wasm::Call(push)],
+                );
+                emit(&mut f, &[Wasm::Return, Wasm::End]);
+                emit(&mut f, &sp_minus(1));
+                emit(&mut f, &[Wasm::LocalGet(2), Wasm::Call(copy)]);
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(2), Wasm::I32Const(1), Wasm::I32Add],
+                );
+                emit(&mut f, &[Wasm::GlobalSet(SP)]);
+                finish(f)
+            }
+
+            Helper::WriteValue => {
+                let mut f = Function::new([(1, I32), (1, I64)]);
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(1), Wasm::Call(tag), Wasm::LocalSet(2)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(1), Wasm::Call(payload), Wasm::LocalSet(3)],
+                );
+                let fd = || Wasm::LocalGet(0);
+                let write_int = |f: &mut Function, shift: Wasm| {
+                    let low_bits = [Wasm::I32WrapI64, Wasm::I64ExtendI32S];
+                    emit(f, &[fd(), Wasm::LocalGet(3)]);
+                    match shift {
+                        Wasm::Nop => emit(f, &low_bits),
+                        shift => emit(f, &[Wasm::I64Const(32), shift]),
+                    }
+                    emit(f, &[Wasm::Call(WRITE_INT)]);
+                };
+
+                for (value_tag, text) in [(NULL, "null"), (UNINIT, "uninitialized")] {
+                    emit(&mut f, &has_tag(2, value_tag));
+                    emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                    self.write_str(&mut f, fd(), text);
+                    emit(&mut f, &[Wasm::Return, Wasm::End]);
+                }
+
+                emit(&mut f, &has_tag(2, BOOL));
+                emit(
+                    &mut f,
+                    &[Wasm::If(BlockType::Empty), Wasm::LocalGet(3), Wasm::I64Eqz],
+                );
+                emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                self.write_str(&mut f, fd(), "false");
+                emit(&mut f, &[Wasm::Else]);
+                self.write_str(&mut f, fd(), "true");
+                emit(&mut f, &[Wasm::End, Wasm::Return, Wasm::End]);
+
+                emit(&mut f, &has_tag(2, INT));
+                emit(
+                    &mut f,
+                    &[Wasm::If(BlockType::Empty), fd(), Wasm::LocalGet(3)],
+                );
+                emit(&mut f, &[Wasm::Call(WRITE_INT), Wasm::Return, Wasm::End]);
+
+                emit(&mut f, &has_tag(2, FLOAT));
+                emit(
+                    &mut f,
+                    &[Wasm::If(BlockType::Empty), fd(), Wasm::LocalGet(3)],
+                );
+                emit(&mut f, &[Wasm::F64ReinterpretI64, Wasm::Call(WRITE_FLOAT)]);
+                emit(&mut f, &[Wasm::Return, Wasm::End]);
+
+                emit(&mut f, &has_tag(2, STR));
+                emit(
+                    &mut f,
+                    &[Wasm::If(BlockType::Empty), fd(), Wasm::LocalGet(3)],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::I64Const(32), Wasm::I64ShrU, Wasm::I32WrapI64],
+                );
+                emit(
+                    &mut f,
+                    &[Wasm::LocalGet(3), Wasm::I32WrapI64, Wasm::Call(WRITE)],
+                );
+                emit(&mut f, &[Wasm::Return, Wasm::End]);
+
+                emit(&mut f, &has_tag(2, FUNCTION));
+                emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                self.write_str(&mut f, fd(), "<function@");
+                emit(
+                    &mut f,
+                    &[fd(), Wasm::LocalGet(3), Wasm::I64Const(0xffff_ffff)],
+                );
+                emit(&mut f, &[Wasm::I64And, Wasm::Call(WRITE_INT)]);
+                self.write_str(&mut f, fd(), ">");
+                emit(&mut f, &[Wasm::Return, Wasm::End]);
+
+                emit(&mut f, &has_tag(2, RANGE));
+                emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                write_int(&mut f, Wasm::I64ShrS);
+                self.write_str(&mut f, fd(), "..");
+                write_int(&mut f, Wasm::Nop);
+                emit(&mut f, &[Wasm::Return, Wasm::End]);
+
+                self.write_str(&mut f, fd(), "<iterator>");
+                finish(f)
+            }
+
+            Helper::WriteTypeName => {
+                let mut f = Function::new([]);
+                for (value_tag, name) in TYPE_NAMES {
+                    emit(&mut f, &has_tag(0, value_tag));
+                    emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                    self.write_str(&mut f, Wasm::I32Const(2), name);
+                    emit(&mut f, &[Wasm::Return, Wasm::End]);
+                }
+                finish(f)
+            }
+
+            Helper::Fail => {
+                let mut f = Function::new([]);
+                self.write_str(&mut f, Wasm::I32Const(2), "Error: ");
+                emit(
+                    &mut f,
+                    &[Wasm::I32Const(2), Wasm::LocalGet(0), Wasm::LocalGet(1)],
+                );
+                emit(&mut f, &[Wasm::Call(WRITE)]);
+                self.write_str(&mut f, Wasm::I32Const(2), "\n");
+                emit(&mut f, &[Wasm::Unreachable]);
+                finish(f)
+            }
+        }
+    }
+
+    // Pops two values and pushes the result of the operator. Integers are only combined as
+    // integers if both are, and otherwise as floats.
+    fn generate_binary(&mut self, op: BinOp) -> Function {
+        use ValType::{F64, I32, I64};
+
+        let push = self.helper(Helper::Push);
+        let tag = self.helper(Helper::Tag);
+        let payload = self.helper(Helper::Payload);
+
+        // The tags, payloads and float values of both operands
+        let (a_tag, b_tag, a, b, fa, fb) = (0, 1, 2, 3, 4, 5);
+        let mut f = Function::new([(2, I32), (2, I64), (2, F64), (1, I64)]);
+        emit(&mut f, &add_to_sp(-2));
+        for (tag_local, payload_local, offset) in [(a_tag, a, 0), (b_tag, b, 1)] {
+            emit(
+                &mut f,
+                &[Wasm::GlobalGet(SP), Wasm::I32Const(offset), Wasm::I32Add],
+            );
+            emit(&mut f, &[Wasm::Call(tag), Wasm::LocalSet(tag_local)]);
+            emit(
+                &mut f,
+                &[Wasm::GlobalGet(SP), Wasm::I32Const(offset), Wasm::I32Add],
+            );
+            emit(
+                &mut f,
+                &[Wasm::Call(payload), Wasm::LocalSet(payload_local)],
+            );
+        }
+
+        let both_ints = [
+            has_tag(a_tag, INT).as_slice(),
+            &has_tag(b_tag, INT),
+            &[Wasm::I32And],
+        ]
+        .concat();
+        let both_nums = [is_num(a_tag).as_slice(), &is_num(b_tag), &[Wasm::I32And]].concat();
+        // Converts both operands to floats, ints by value and floats by their bits
+        let mut to_floats = Vec::new();
+        for (tag_local, payload_local, float_local) in [(a_tag, a, fa), (b_tag, b, fb)] {
+            to_floats.extend([
+                Wasm::LocalGet(payload_local),
+                Wasm::F64ConvertI64S,
+                Wasm::LocalGet(payload_local),
+                Wasm::F64ReinterpretI64,
+                Wasm::LocalGet(tag_local),
+                Wasm::I32Const(INT),
+                Wasm::I32Eq,
+                Wasm::Select,
+                Wasm::LocalSet(float_local),
+            ]);
+        }
+        let ints = [Wasm::LocalGet(a), Wasm::LocalGet(b)];
+        let floats = [Wasm::LocalGet(fa), Wasm::LocalGet(fb)];
+
+        // Emits `if <condition> { <result with tag> }`, where the result is pushed
+        let case = |this: &mut Self,
+                    f: &mut Function,
+                    condition: &[Wasm],
+                    result: &mut dyn FnMut(&mut Self, &mut Function),
+                    result_tag: i32| {
+            emit(f, condition);
+            emit(f, &[Wasm::If(BlockType::Empty), Wasm::I32Const(result_tag)]);
+            result(this, f);
+            emit(f, &[Wasm::Call(push), Wasm::Return, Wasm::End]);
+        };
+
+        let checked = |this: &mut Self, helper| Wasm::Call(this.helper(helper));
+        let division_by_zero = |this: &mut Self, f: &mut Function| {
+            emit(f, &[Wasm::LocalGet(b), Wasm::I64Eqz]);
+            this.fail_if(f, &[Piece::Str("Division by zero")]);
+        };
+        let float_result = |f: &mut Function, op: &[Wasm]| {
+            emit(f, &to_floats);
+            emit(f, &floats);
+            emit(f, op);
+            emit(f, &[Wasm::I64ReinterpretF64]);
+        };
+        let bool_result = |f: &mut Function, op: &[Wasm]| {
+            emit(f, op);
+            emit(f, &[Wasm::I64ExtendI32U]);
+        };
+
+        match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul => {
+                let (helper, float_op) = match op {
+                    BinOp::Add => (Helper::AddChecked, Wasm::F64Add),
+                    BinOp::Sub => (Helper::SubChecked, Wasm::F64Sub),
+                    _ => (Helper::MulChecked, Wasm::F64Mul),
+                };
+                let helper = checked(self, helper);
+                case(
+                    self,
+                    &mut f,
+                    &both_ints,
+                    &mut |_, f| {
+                        emit(f, &ints);
+                        emit(f, &[helper.clone()]);
+                    },
+                    INT,
+                );
+                case(
+                    self,
+                    &mut f,
+                    &both_nums,
+                    &mut |_, f| float_result(f, &[float_op.clone()]),
+                    FLOAT,
+                );
+            }
+
+            BinOp::Div => {
+                case(
+                    self,
+                    &mut f,
+                    &both_nums,
+                    &mut |_, f| float_result(f, &[Wasm::F64Div]),
+                    FLOAT,
+                );
+            }
+
+            BinOp::DivFloor | BinOp::Mod => {
+                let (int_op, float_op) = match op {
+                    BinOp::DivFloor => (Wasm::I64DivS, vec![Wasm::F64Div, Wasm::F64Floor]),
+                    // Like Rust's `%` on floats, `a - trunc(a / b) * b`
+                    _ => (
+                        Wasm::I64RemS,
+                        vec![
+                            Wasm::F64Div,
+                            Wasm::F64Trunc,
+                            Wasm::LocalGet(fb),
+                            Wasm::F64Mul,
+                            Wasm::LocalSet(fb),
+                            Wasm::LocalGet(fa),
+                            Wasm::LocalGet(fb),
+                            Wasm::F64Sub,
+                        ],
+                    ),
+                };
+                case(
+                    self,
+                    &mut f,
+                    &both_ints,
+                    &mut |this, f| {
+                        division_by_zero(this, f);
+                        emit(
+                            f,
+                            &[Wasm::LocalGet(a), Wasm::I64Const(i64::MIN), Wasm::I64Eq],
+                        );
+                        emit(
+                            f,
+                            &[
+                                Wasm::LocalGet(b),
+                                Wasm::I64Const(-1),
+                                Wasm::I64Eq,
+                                Wasm::I32And,
+                            ],
+                        );
+                        this.fail_if(f, &[Piece::Str(OVERFLOW)]);
+                        emit(f, &ints);
+                        emit(f, &[int_op.clone()]);
+                    },
+                    INT,
+                );
+                case(
+                    self,
+                    &mut f,
+                    &both_nums,
+                    &mut |_, f| float_result(f, &float_op),
+                    FLOAT,
+                );
+            }
+
+            BinOp::Pow => {
+                // Exponentiation by squaring, with the result in local 6
+                let mul = checked(self, Helper::MulChecked);
+                let non_negative = [
+                    both_ints.as_slice(),
+                    &[
+                        Wasm::LocalGet(b),
+                        Wasm::I64Const(0),
+                        Wasm::I64GeS,
+                        Wasm::I32And,
+                    ],
+                ]
+                .concat();
+                case(
+                    self,
+                    &mut f,
+                    &non_negative,
+                    &mut |_, f| {
+                        emit(
+                            f,
+                            &[
+                                Wasm::I64Const(1),
+                                Wasm::LocalSet(6),
+                                Wasm::Block(BlockType::Empty),
+                                Wasm::Loop(BlockType::Empty),
+                                Wasm::LocalGet(b),
+                                Wasm::I64Eqz,
+                                Wasm::BrIf(1),
+                                Wasm::LocalGet(b),
+                                Wasm::I64Const(1),
+                                Wasm::I64And,
+                                Wasm::I32WrapI64,
+                                Wasm::If(BlockType::Empty),
+                                Wasm::LocalGet(6),
+                                Wasm::LocalGet(a),
+                                mul.clone(),
+                                Wasm::LocalSet(6),
+                                Wasm::End,
+                                Wasm::LocalGet(b),
+                                Wasm::I64Const(1),
+                                Wasm::I64ShrS,
+                                Wasm::LocalTee(b),
+                                Wasm::I64Eqz,
+                                Wasm::BrIf(1),
+                                Wasm::LocalGet(a),
+                                Wasm::LocalGet(a),
+                                mul.clone(),
+                                Wasm::LocalSet(a),
+                                Wasm::Br(0),
+                                Wasm::End,
+                                Wasm::End,
+                                Wasm::LocalGet(6),
+                            ],
+                        )
+                    },
+                    INT,
+                );
+                case(
+                    self,
+                    &mut f,
+                    &both_nums,
+                    &mut |_, f| float_result(f, &[Wasm::Call(POW)]),
+                    FLOAT,
+                );
+            }
+
+            BinOp::Eq | BinOp::NotEq => {
+                let (int_op, float_op) = match op {
+                    BinOp::Eq => (Wasm::I64Eq, Wasm::F64Eq),
+                    _ => (Wasm::I64Ne, Wasm::F64Ne),
+                };
+                let int_cmp = [ints[0].clone(), ints[1].clone(), int_op];
+                let float_cmp = [floats[0].clone(), floats[1].clone(), float_op];
+                case(
+                    self,
+                    &mut f,
+                    &both_ints,
+                    &mut |_, f| bool_result(f, &int_cmp),
+                    BOOL,
+                );
+                case(
+                    self,
+                    &mut f,
+                    &both_nums,
+                    &mut |_, f| {
+                        emit(f, &to_floats);
+                        bool_result(f, &float_cmp)
+                    },
+                    BOOL,
+                );
+
+                // Other values are equal if they are the same, as equal strings share their bytes
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::I32Const(BOOL),
+                        Wasm::LocalGet(a_tag),
+                        Wasm::LocalGet(b_tag),
+                    ],
+                );
+                emit(
+                    &mut f,
+                    &[
+                        Wasm::I32Eq,
+                        Wasm::LocalGet(a),
+                        Wasm::LocalGet(b),
+                        Wasm::I64Eq,
+                    ],
+                );
+                emit(&mut f, &[Wasm::I32And]);
+                if op == BinOp::NotEq {
+                    emit(&mut f, &[Wasm::I32Eqz]);
+                }
+                emit(
+                    &mut f,
+                    &[Wasm::I64ExtendI32U, Wasm::Call(push), Wasm::Return],
+                );
+            }
+
+            BinOp::Less | BinOp::LessEq | BinOp::Greater | BinOp::GreaterEq => {
+                let (int_op, float_op) = match op {
+                    BinOp::Less => (Wasm::I64LtS, Wasm::F64Lt),
+                    BinOp::LessEq => (Wasm::I64LeS, Wasm::F64Le),
+                    BinOp::Greater => (Wasm::I64GtS, Wasm::F64Gt),
+                    _ => (Wasm::I64GeS, Wasm::F64Ge),
+                };
+                let int_cmp = [ints[0].clone(), ints[1].clone(), int_op];
+                let float_cmp = [floats[0].clone(), floats[1].clone(), float_op];
+                case(
+                    self,
+                    &mut f,
+                    &both_ints,
+                    &mut |_, f| bool_result(f, &int_cmp),
+                    BOOL,
+                );
+                case(
+                    self,
+                    &mut f,
+                    &both_nums,
+                    &mut |_, f| {
+                        emit(f, &to_floats);
+                        bool_result(f, &float_cmp)
+                    },
+                    BOOL,
+                );
+            }
+
+            BinOp::Range => {
+                // Both bounds are kept in the payload, so they must fit in 32 bits
+                case(
+                    self,
+                    &mut f,
+                    &both_ints,
+                    &mut |this, f| {
+                        for bound in [a, b] {
+                            emit(f, &[Wasm::LocalGet(bound), Wasm::LocalGet(bound)]);
+                            emit(f, &[Wasm::I32WrapI64, Wasm::I64ExtendI32S, Wasm::I64Ne]);
+                            this.fail_if(
+                                f,
+                                &[Piece::Str(
+                                    "Ranges compiled to WebAssembly must have 32-bit bounds",
+                                )],
+                            );
+                        }
+                        emit(f, &[Wasm::LocalGet(a), Wasm::I64Const(32), Wasm::I64Shl]);
+                        emit(
+                            f,
+                            &[Wasm::LocalGet(b), Wasm::I64Const(0xffff_ffff), Wasm::I64And],
+                        );
+                        emit(f, &[Wasm::I64Or]);
+                    },
+                    RANGE,
+                );
+            }
+
+            BinOp::Xor => {
+                let both_bools = [
+                    has_tag(a_tag, BOOL).as_slice(),
+                    &has_tag(b_tag, BOOL),
+                    &[Wasm::I32And],
+                ]
+                .concat();
+                case(
+                    self,
+                    &mut f,
+                    &both_bools,
+                    &mut |_, f| {
+                        emit(f, &ints);
+                        emit(f, &[Wasm::I64Xor]);
+                    },
+                    BOOL,
+                );
+            }
+
+            BinOp::BitwiseAnd
+            | BinOp::BitwiseOr
+            | BinOp::BitwiseXor
+            | BinOp::LeftShift
+            | BinOp::RightShift => {
+                let int_op = match op {
+                    BinOp::BitwiseAnd => Wasm::I64And,
+                    BinOp::BitwiseOr => Wasm::I64Or,
+                    BinOp::BitwiseXor => Wasm::I64Xor,
+                    BinOp::LeftShift => Wasm::I64Shl,
+                    _ => Wasm::I64ShrS,
+                };
+                case(
+                    self,
+                    &mut f,
+                    &both_ints,
+                    &mut |_, f| {
+                        emit(f, &ints);
+                        emit(f, &[int_op.clone()]);
+                    },
+                    INT,
+                );
+            }
+        }
+
+        if !matches!(op, BinOp::Eq | BinOp::NotEq) {
+            self.fail(
+                &mut f,
+                &[
+                    Piece::Str("Type mismatch: Cannot "),
+                    Piece::Str(op.action()),
+                    Piece::Str(" types '"),
+                    Piece::TypeOf(a_tag),
+                    Piece::Str("' and '"),
+                    Piece::TypeOf(b_tag),
+                    Piece::Str("'"),
+                ],
+            );
+        }
+        finish(f)
+    }
+}
+
+// Splits the program into blocks at its labels and after its calls, which are where jumps and
+// returns can go, and compiles each block into a case of the loop in `main`
+fn compile_main(
+    program: &Program<Instruction>,
+    runtime: &mut Runtime,
+) -> Result<Function, CompileError> {
+    let mut block_starts = vec![0];
+    let mut blocks_of_labels = HashMap::new();
+    for (i, instruction) in program.instructions.iter().enumerate() {
+        if let Instruction::Label(label) = instruction {
+            if *block_starts.last().unwrap() != i {
+                block_starts.push(i);
+            }
+            blocks_of_labels.insert(*label, block_starts.len() as u32 - 1);
+        } else if let Instruction::Call(_) = instruction {
+            block_starts.push(i + 1);
+        }
+    }
+    let block_of = |label: &Label| blocks_of_labels[label];
+
+    let num_registers = program
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::SetRegister(reg) | Instruction::GetRegister(reg) => Some(reg + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    // The current block, an address, a boolean and the registers
+    let (pc, address, flag) = (0, 1, 2);
+    let register = |reg: &usize| 3 + *reg as u32;
+    let mut f = Function::new([(3, ValType::I32), (num_registers as u32, ValType::I64)]);
+
+    let num_blocks = block_starts.len() as u32;
+    emit(&mut f, &[Wasm::Loop(BlockType::Empty)]);
+    for _ in 0..num_blocks {
+        emit(&mut f, &[Wasm::Block(BlockType::Empty)]);
+    }
+    emit(
+        &mut f,
+        &[
+            Wasm::LocalGet(pc),
+            Wasm::BrTable(Cow::Owned((0..num_blocks).collect()), num_blocks - 1),
+        ],
+    );
+
+    for (block, &start) in block_starts.iter().enumerate() {
+        emit(&mut f, &[Wasm::End]);
+
+        // Going back to the loop jumps to the block in `pc`, which is outside of the blocks after
+        // this one
+        let dispatch = num_blocks - 1 - block as u32;
+        let jump = |f: &mut Function, target: u32, depth: u32| {
+            emit(
+                f,
+                &[
+                    Wasm::I32Const(target as i32),
+                    Wasm::LocalSet(pc),
+                    Wasm::Br(depth),
+                ],
+            );
+        };
+
+        let end = block_starts
+            .get(block + 1)
+            .copied()
+            .unwrap_or(program.instructions.len());
+        for (instruction, span) in program.instructions[start..end]
+            .iter()
+            .zip(&program.source_map[start..end])
+        {
+            let unsupported = |what: String| CompileError::Spanned {
+                span: *span,
+                msg: format!("{what} can't be compiled to WebAssembly"),
+            };
+
+            if let Some(op) = BinOp::from_instruction(instruction) {
+                let helper = runtime.helper(Helper::Binary(op));
+                emit(&mut f, &[Wasm::Call(helper)]);
+                continue;
+            }
+
+            match instruction {
+                Instruction::Value(val) => {
+                    let (tag, payload) = match val {
+                        IrValue::Null => (NULL, 0),
+                        IrValue::Uninit => (UNINIT, 0),
+                        IrValue::Bool(b) => (BOOL, *b as i64),
+                        IrValue::Int(i) => (INT, *i as i64),
+                        IrValue::Num(IrNumber::Int(i)) => (INT, *i as i64),
+                        IrValue::Num(IrNumber::Float(x)) => (FLOAT, x.to_bits() as i64),
+                        IrValue::Num(IrNumber::BigInt(_)) => {
+                            return Err(unsupported("Big integers".to_string()))
+                        }
+                        IrValue::Str(s) => {
+                            let (ptr, len) = runtime.string(s);
+                            (STR, (ptr as i64) << 32 | len as i64)
+                        }
+                        IrValue::Function(func) if func.is_memoized => {
+                            return Err(unsupported("Memoized functions".to_string()))
+                        }
+                        IrValue::Function(func) => (
+                            FUNCTION,
+                            block_of(&func.location) as i64
+                                | (func.arity as i64) << 32
+                                | (func.min_arity as i64) << 48,
+                        ),
+                        IrValue::Regex(..) => return Err(unsupported("Regexes".to_string())),
+                        IrValue::Symbol(_) => return Err(unsupported("Symbols".to_string())),
+                        IrValue::Variant(_) => {
+                            return Err(unsupported("Enum variants".to_string()))
+                        }
+                        IrValue::List(_) => return Err(unsupported("Lists".to_string())),
+                        IrValue::Tuple(_) => return Err(unsupported("Tuples".to_string())),
+                        IrValue::Set(_) => return Err(unsupported("Sets".to_string())),
+                        IrValue::Map(_) => return Err(unsupported("Maps".to_string())),
+                    };
+                    push_const(runtime, &mut f, tag, payload);
+                }
+
+                Instruction::ConstantInt(i) => push_const(runtime, &mut f, INT, *i as i64),
+
+                Instruction::LoadLocal(_) | Instruction::LoadGlobal(_) => {
+                    emit(&mut f, &var_slot(instruction));
+                    emit(
+                        &mut f,
+                        &[
+                            Wasm::GlobalGet(SP),
+                            Wasm::Call(runtime.helper(Helper::Copy)),
+                        ],
+                    );
+                    emit(&mut f, &add_to_sp(1));
+                }
+
+                Instruction::StoreLocal(_) | Instruction::StoreGlobal(_) => {
+                    emit(&mut f, &sp_minus(1));
+                    emit(&mut f, &var_slot(instruction));
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Copy))]);
+                }
+
+                Instruction::PopToLocal(_) | Instruction::PopToGlobal(_) => {
+                    emit(&mut f, &add_to_sp(-1));
+                    emit(&mut f, &[Wasm::GlobalGet(SP)]);
+                    emit(&mut f, &var_slot(instruction));
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Copy))]);
+                }
+
+                Instruction::Load => {
+                    let pop_address = runtime.helper(Helper::PopAddress);
+                    emit(&mut f, &[Wasm::Call(pop_address), Wasm::I32WrapI64]);
+                    emit(
+                        &mut f,
+                        &[
+                            Wasm::GlobalGet(SP),
+                            Wasm::Call(runtime.helper(Helper::Copy)),
+                        ],
+                    );
+                    emit(&mut f, &add_to_sp(1));
+                }
+
+                Instruction::Store => {
+                    let pop_address = runtime.helper(Helper::PopAddress);
+                    emit(&mut f, &[Wasm::Call(pop_address), Wasm::I32WrapI64]);
+                    emit(&mut f, &[Wasm::LocalSet(address)]);
+                    emit(&mut f, &sp_minus(1));
+                    emit(&mut f, &[Wasm::LocalGet(address)]);
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Copy))]);
+                }
+
+                Instruction::Pop => emit(&mut f, &add_to_sp(-1)),
+
+                Instruction::Dup => {
+                    emit(&mut f, &sp_minus(1));
+                    emit(
+                        &mut f,
+                        &[
+                            Wasm::GlobalGet(SP),
+                            Wasm::Call(runtime.helper(Helper::Copy)),
+                        ],
+                    );
+                    emit(&mut f, &add_to_sp(1));
+                }
+
+                Instruction::Swap => emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Swap))]),
+
+                Instruction::SwapPop => {
+                    emit(&mut f, &sp_minus(1));
+                    emit(&mut f, &sp_minus(2));
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Copy))]);
+                    emit(&mut f, &add_to_sp(-1));
+                }
+
+                Instruction::RemoveIndex => {
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::RemoveIndex))])
+                }
+
+                Instruction::GetStackPtr => {
+                    emit(&mut f, &[Wasm::I32Const(INT)]);
+                    emit(&mut f, &sp_minus(1));
+                    emit(
+                        &mut f,
+                        &[
+                            Wasm::I64ExtendI32S,
+                            Wasm::Call(runtime.helper(Helper::Push)),
+                        ],
+                    );
+                }
+
+                Instruction::SetStackPtr => {
+                    let pop_address = runtime.helper(Helper::PopAddress);
+                    emit(&mut f, &[Wasm::Call(pop_address), Wasm::I32WrapI64]);
+                    emit(
+                        &mut f,
+                        &[Wasm::I32Const(1), Wasm::I32Add, Wasm::GlobalSet(SP)],
+                    );
+                }
+
+                Instruction::GetBasePtr => {
+                    emit(&mut f, &[Wasm::I32Const(INT), Wasm::GlobalGet(BP)]);
+                    emit(
+                        &mut f,
+                        &[
+                            Wasm::I64ExtendI32S,
+                            Wasm::Call(runtime.helper(Helper::Push)),
+                        ],
+                    );
+                }
+
+                Instruction::SetRegister(reg) => {
+                    let pop_address = runtime.helper(Helper::PopAddress);
+                    emit(
+                        &mut f,
+                        &[Wasm::Call(pop_address), Wasm::LocalSet(register(reg))],
+                    );
+                }
+
+                Instruction::GetRegister(reg) => {
+                    emit(
+                        &mut f,
+                        &[Wasm::I32Const(INT), Wasm::LocalGet(register(reg))],
+                    );
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Push))]);
+                }
+
+                Instruction::Not => emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Not))]),
+                Instruction::Neg => emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Neg))]),
+                Instruction::BitwiseNot => {
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::BitwiseNot))])
+                }
+
+                Instruction::Stop => emit(&mut f, &[Wasm::Return]),
+
+                Instruction::Label(_) => {}
+
+                Instruction::Goto(label) => jump(&mut f, block_of(label), dispatch),
+
+                Instruction::IfTrue(label) | Instruction::IfFalse(label) => {
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::PopTruthy))]);
+                    if let Instruction::IfFalse(_) = instruction {
+                        emit(&mut f, &[Wasm::I32Eqz]);
+                    }
+                    emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                    jump(&mut f, block_of(label), dispatch + 1);
+                    emit(&mut f, &[Wasm::End]);
+                }
+
+                Instruction::RuntimeError(msg) => {
+                    let (ptr, len) = runtime.string(msg);
+                    emit(&mut f, &[Wasm::I32Const(ptr), Wasm::I32Const(len)]);
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Fail))]);
+                }
+
+                // The call continues in the next block once the function returns
+                Instruction::Call(num_args) => {
+                    let call = runtime.helper(Helper::Call);
+                    emit(&mut f, &[Wasm::I32Const(*num_args as i32)]);
+                    emit(
+                        &mut f,
+                        &[Wasm::I32Const(block as i32 + 1), Wasm::Call(call)],
+                    );
+                    emit(&mut f, &[Wasm::LocalSet(pc), Wasm::Br(dispatch)]);
+                }
+
+                Instruction::Return => {
+                    let ret = runtime.helper(Helper::Return);
+                    emit(
+                        &mut f,
+                        &[Wasm::Call(ret), Wasm::LocalSet(pc), Wasm::Br(dispatch)],
+                    );
+                }
+
+                Instruction::EnterInlined(num_args) => {
+                    let enter = runtime.helper(Helper::EnterInlined);
+                    emit(
+                        &mut f,
+                        &[Wasm::I32Const(*num_args as i32), Wasm::Call(enter)],
+                    );
+                }
+
+                Instruction::ExitInlined => {
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::ExitInlined))])
+                }
+
+                Instruction::StdlibCall(stdlib_fn @ (StdlibFn::Print | StdlibFn::Eprint), n) => {
+                    let fd = if matches!(stdlib_fn, StdlibFn::Print) {
+                        1
+                    } else {
+                        2
+                    };
+                    emit(&mut f, &[Wasm::I32Const(fd), Wasm::I32Const(*n as i32)]);
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Print))]);
+                }
+
+                Instruction::StdlibCall(stdlib_fn, _) => {
+                    return Err(unsupported(format!("The function {}", stdlib_fn.name())))
+                }
+
+                Instruction::ToIter => emit(&mut f, &[Wasm::Call(runtime.helper(Helper::ToIter))]),
+
+                Instruction::NextIterOrJump(label) => {
+                    emit(
+                        &mut f,
+                        &[Wasm::Call(runtime.helper(Helper::Next)), Wasm::I32Eqz],
+                    );
+                    emit(&mut f, &[Wasm::If(BlockType::Empty)]);
+                    jump(&mut f, block_of(label), dispatch + 1);
+                    emit(&mut f, &[Wasm::End]);
+                }
+
+                Instruction::NextIter => {
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Next))]);
+                    emit(&mut f, &[Wasm::LocalSet(flag), Wasm::I32Const(BOOL)]);
+                    emit(&mut f, &[Wasm::LocalGet(flag), Wasm::I64ExtendI32U]);
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::Push))]);
+                }
+
+                Instruction::IsNull | Instruction::IsUninit | Instruction::IsStr => {
+                    let tag = match instruction {
+                        Instruction::IsNull => NULL,
+                        Instruction::IsUninit => UNINIT,
+                        _ => STR,
+                    };
+                    emit(&mut f, &[Wasm::I32Const(tag)]);
+                    emit(&mut f, &[Wasm::Call(runtime.helper(Helper::IsTag))]);
+                }
+
+                Instruction::LoadUpvalue(_)
+                | Instruction::StoreUpvalue(_)
+                | Instruction::MakeClosure(_) => {
+                    return Err(unsupported(
+                        "Functions using variables of the functions around them".to_string(),
+                    ))
+                }
+
+                Instruction::Defer => return Err(unsupported("`defer`".to_string())),
+
+                Instruction::MethodCall(..) => return Err(unsupported("Methods".to_string())),
+
+                _ => return Err(unsupported(format!("`{instruction:?}`"))),
+            }
+        }
+    }
+
+    emit(&mut f, &[Wasm::End]);
+    Ok(finish(f))
+}
+
+fn push_const(runtime: &mut Runtime, f: &mut Function, tag: i32, payload: i64) {
+    emit(f, &[Wasm::I32Const(tag), Wasm::I64Const(payload)]);
+    emit(f, &[Wasm::Call(runtime.helper(Helper::Push))]);
+}
+
+// The slot of a local or global variable
+fn var_slot(instruction: &Instruction) -> Vec<Wasm<'static>> {
+    match instruction {
+        Instruction::LoadLocal(offset)
+        | Instruction::StoreLocal(offset)
+        | Instruction::PopToLocal(offset) => {
+            vec![
+                Wasm::GlobalGet(BP),
+                Wasm::I32Const(*offset as i32),
+                Wasm::I32Add,
+            ]
+        }
+        Instruction::LoadGlobal(addr)
+        | Instruction::StoreGlobal(addr)
+        | Instruction::PopToGlobal(addr) => vec![Wasm::I32Const(*addr as i32)],
+        _ => unreachable!("{instruction:?} doesn't refer to a variable"),
+    }
+}
+
+const fn mem_arg(offset: u64, align: u32) -> MemArg {
+    MemArg {
+        offset,
+        align,
+        memory_index: 0,
+    }
+}
+
+fn emit(f: &mut Function, instructions: &[Wasm]) {
+    for instruction in instructions {
+        f.instruction(instruction);
+    }
+}
+
+fn finish(mut f: Function) -> Function {
+    f.instruction(&Wasm::End);
+    f
+}
+
+// Turns the index of a slot on the stack into its address
+fn slot_addr() -> [Wasm<'static>; 2] {
+    [Wasm::I32Const(4), Wasm::I32Shl]
+}
+
+// The address of the frame at the top of the frame stack, to be used with the frame's offsets
+fn frame_addr() -> [Wasm<'static>; 3] {
+    [Wasm::GlobalGet(FP), Wasm::I32Const(3), Wasm::I32Shl]
+}
+
+fn sp_minus(n: i32) -> [Wasm<'static>; 3] {
+    [Wasm::GlobalGet(SP), Wasm::I32Const(n), Wasm::I32Sub]
+}
+
+fn add_to_sp(n: i32) -> [Wasm<'static>; 4] {
+    [
+        Wasm::GlobalGet(SP),
+        Wasm::I32Const(n),
+        Wasm::I32Add,
+        Wasm::GlobalSet(SP),
+    ]
+}
+
+fn has_tag(local: u32, tag: i32) -> [Wasm<'static>; 3] {
+    [Wasm::LocalGet(local), Wasm::I32Const(tag), Wasm::I32Eq]
+}
+
+// Whether the tag in a local is an integer or a float, which are next to each other
+fn is_num(local: u32) -> [Wasm<'static>; 5] {
+    [
+        Wasm::LocalGet(local),
+        Wasm::I32Const(INT),
+        Wasm::I32Sub,
+        Wasm::I32Const(1),
+        Wasm::I32LeU,
+    ]
+}
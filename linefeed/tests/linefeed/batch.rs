@@ -0,0 +1,121 @@
+use indoc::indoc;
+use linefeed::{
+    batch::run_batch, config::Config, vm::limits::RunLimits, EXIT_FAILURE, EXIT_SUCCESS,
+};
+
+const SUM_LINES: &str = indoc! {"
+    total = 0;
+    for line in input().lines() {
+      total += int(line);
+    };
+    print(total);
+"};
+
+#[test]
+fn outputs_are_in_the_order_of_the_inputs() {
+    let inputs = (0..50)
+        .map(|n| (0..=n).map(|i| format!("{i}\n")).collect::<String>())
+        .collect::<Vec<_>>();
+
+    let outputs = run_batch(SUM_LINES, &Config::default(), &inputs, Some(4)).unwrap();
+    assert_eq!(outputs.len(), inputs.len());
+    for (n, output) in outputs.iter().enumerate() {
+        assert_eq!(output.status, EXIT_SUCCESS);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{}\n", n * (n + 1) / 2)
+        );
+        assert!(output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn runs_fail_independently() {
+    let inputs = ["1\n2\n", "1\nx\n", "5\n"];
+    let outputs = run_batch(SUM_LINES, &Config::default(), &inputs, None).unwrap();
+
+    let statuses = outputs.iter().map(|o| o.status).collect::<Vec<_>>();
+    assert_eq!(statuses, [EXIT_SUCCESS, EXIT_FAILURE, EXIT_SUCCESS]);
+    assert_eq!(outputs[2].stdout, b"5\n");
+
+    let stderr = String::from_utf8(strip_ansi_escapes::strip(&outputs[1].stderr)).unwrap();
+    assert!(stderr.contains("\"x\" is not a valid integer"));
+}
+
+#[test]
+fn constants_are_not_shared_between_runs() {
+    let src = indoc! {"
+        seen = [];
+        seen.append(input());
+        print(seen, 10 ** 30 + 1, r/a+/);
+    "};
+
+    let outputs = run_batch(src, &Config::default(), &["a", "b", "c"], Some(3)).unwrap();
+    let stdout = outputs
+        .iter()
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stdout,
+        [
+            "[\"a\"] 1000000000000000000000000000001 /a+/\n",
+            "[\"b\"] 1000000000000000000000000000001 /a+/\n",
+            "[\"c\"] 1000000000000000000000000000001 /a+/\n",
+        ]
+    );
+}
+
+#[test]
+fn regexes_are_shared_by_all_runs() {
+    let src = indoc! {r#"
+        inp = input();
+        print(inp.find_all(r/\d+/n), inp.find_all(regex("[a-z]+")));
+    "#};
+
+    let inputs = ["1 a", "40 2", "none"];
+    let outputs = run_batch(src, &Config::default(), &inputs, Some(2)).unwrap();
+    let stdout = outputs
+        .iter()
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stdout,
+        ["[(1)] [(\"a\")]\n", "[(40), (2)] []\n", "[] [(\"none\")]\n"]
+    );
+}
+
+#[test]
+fn compile_errors_are_reported_once() {
+    let Err(report) = run_batch("print(missing)", &Config::default(), &["1", "2"], None) else {
+        panic!("program should fail to compile");
+    };
+
+    let report = String::from_utf8(strip_ansi_escapes::strip(report)).unwrap();
+    assert_eq!(
+        report
+            .matches("No such variable 'missing' in scope")
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn memory_limit_applies_to_each_run_on_its_own() {
+    let config = Config {
+        limits: RunLimits {
+            memory: Some(16 << 20),
+            ..RunLimits::default()
+        },
+        ..Config::default()
+    };
+    // Each run takes a few megabytes, so the limit would be reached if concurrent runs were
+    // charged for each other's memory
+    let src = "xs = [i for i in 0..200000]; print(len(xs));";
+    let inputs = [""; 32];
+
+    let outputs = run_batch(src, &config, &inputs, Some(8)).unwrap();
+    for output in outputs {
+        assert_eq!(output.status, EXIT_SUCCESS, "{:?}", output.stderr);
+        assert_eq!(output.stdout, b"200000\n");
+    }
+}
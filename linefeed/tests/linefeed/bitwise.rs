@@ -190,3 +190,47 @@ eval_and_assert!(
     "#}),
     empty()
 );
+
+eval_and_assert!(
+    bitwise_compound_assignment,
+    indoc::indoc! {r#"
+        x = 12;
+        x &= 10;
+        print(x);
+        x |= 5;
+        print(x);
+        x ^= 3;
+        print(x);
+        x <<= 2;
+        print(x);
+        x >>= 3;
+        print(x);
+    "#},
+    equals(indoc::indoc! {r#"
+        8
+        13
+        14
+        56
+        7
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    power_and_floor_div_compound_assignment,
+    indoc::indoc! {r#"
+        x = 3;
+        x **= 4;
+        print(x);
+        x //= 10;
+        print(x);
+        x **= 40;
+        print(x);
+    "#},
+    equals(indoc::indoc! {r#"
+        81
+        8
+        1329227995784915872903807060280344576
+    "#}),
+    empty()
+);
@@ -0,0 +1,68 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    list_with_capacity_starts_empty,
+    indoc! {r#"
+        xs = list_with_capacity(4);
+        print(xs, xs.len());
+        for i in 0..6 { xs.append(i); };
+        print(xs);
+    "#},
+    equals(indoc! {r#"
+        [] 0
+        [0, 1, 2, 3, 4, 5]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    map_with_capacity_starts_empty,
+    indoc! {r#"
+        m = map_with_capacity(100);
+        print(m, m.len());
+        m["a"] = 1;
+        print(m);
+    "#},
+    equals(indoc! {r#"
+        {} 0
+        {"a": 1}
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    capacity_must_be_a_non_negative_integer,
+    indoc! {r#"
+        list_with_capacity(-1);
+    "#},
+    empty(),
+    contains("list_with_capacity expects a non-negative, small integer")
+);
+
+eval_and_assert!(
+    comprehensions_over_known_and_unknown_lengths,
+    indoc! {r#"
+        print([x * x for x in 0..5]);
+        print([x for x in 10..0 if x % 3 == 0]);
+        print([x + 1 for x in [1, 2, 3]]);
+        print([c for c in "abc"]);
+        print([x for x in (1, 2)]);
+        print([x for x in set([5])]);
+        print([x for x in 0..0]);
+    "#},
+    equals(indoc! {r#"
+        [0, 1, 4, 9, 16]
+        [9, 6, 3]
+        [2, 3, 4]
+        ["a", "b", "c"]
+        [1, 2]
+        [5]
+        []
+    "#}),
+    empty()
+);
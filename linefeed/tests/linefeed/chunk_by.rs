@@ -0,0 +1,46 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    chunk_by_groups_consecutive_keys,
+    indoc! {r#"
+        print(chunk_by([1, 1, 2, 3, 3, 3, 1], fn (x) x));
+        print(chunk_by([1, 3, 2, 4, 5], fn (x) x % 2));
+        print(chunk_by("aabccc", fn (c) c));
+        print(chunk_by([], fn (x) x));
+    "#},
+    equals(indoc! {r#"
+        [[1, 1], [2], [3, 3, 3], [1]]
+        [[1, 3], [2, 4], [5]]
+        [["a", "a"], ["b"], ["c", "c", "c"]]
+        []
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    split_when_drops_separators,
+    indoc! {r#"
+        lines = "1\n2\n\n3\n\n4\n5".lines();
+        print(split_when(lines, fn (line) line == ""));
+        print(split_when([1, 0, 0, 2], fn (x) x == 0));
+    "#},
+    equals(indoc! {r#"
+        [["1", "2"], ["3"], ["4", "5"]]
+        [[1], [], [2]]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    chunk_by_requires_function,
+    indoc! {r#"
+        chunk_by([1, 2], 3);
+    "#},
+    empty(),
+    contains("Expected function as chunk_by key, got number")
+);
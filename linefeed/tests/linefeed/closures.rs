@@ -0,0 +1,147 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{empty, equals},
+};
+
+eval_and_assert!(
+    closure_reads_argument_of_enclosing_function,
+    indoc! {r#"
+        fn adder(n) fn (x) x + n;
+        add5 = adder(5);
+        print(add5(1), adder(10)(1));
+    "#},
+    equals("6 11"),
+    empty()
+);
+
+eval_and_assert!(
+    closure_keeps_captured_variable_alive_after_return,
+    indoc! {r#"
+        fn make_counter() {
+            count = 0;
+            fn () {
+                count += 1;
+                count
+            }
+        };
+
+        a = make_counter();
+        b = make_counter();
+        print(a(), a(), a(), b());
+    "#},
+    equals("1 2 3 1"),
+    empty()
+);
+
+eval_and_assert!(
+    closure_mutation_is_visible_to_enclosing_function,
+    indoc! {r#"
+        fn run() {
+            total = 0;
+            add = fn (x) total += x;
+            add(3);
+            add(4);
+            total
+        };
+        print(run());
+    "#},
+    equals("7"),
+    empty()
+);
+
+eval_and_assert!(
+    closures_share_captured_variable,
+    indoc! {r#"
+        fn pair() {
+            n = 0;
+            (fn () n += 1, fn () n)
+        };
+
+        (inc, get) = pair();
+        inc();
+        inc();
+        print(get());
+    "#},
+    equals("2"),
+    empty()
+);
+
+eval_and_assert!(
+    closure_captures_through_intermediate_function,
+    indoc! {r#"
+        fn outer() {
+            x = 1;
+            fn middle() fn () x *= 10;
+            middle()();
+            x
+        };
+        print(outer());
+    "#},
+    equals("10"),
+    empty()
+);
+
+eval_and_assert!(
+    closures_in_loop_share_loop_variable,
+    indoc! {r#"
+        fn make() {
+            fs = [];
+            for i in 0..3 {
+                fs.append(fn () i);
+            };
+            fs
+        };
+        print([f() for f in make()]);
+    "#},
+    equals("[2, 2, 2]"),
+    empty()
+);
+
+eval_and_assert!(
+    nested_function_can_recurse,
+    indoc! {r#"
+        fn run() {
+            fn fact(n) if n <= 1 { 1 } else { n * fact(n - 1) };
+            fact(5)
+        };
+        print(run());
+    "#},
+    equals("120"),
+    empty()
+);
+
+eval_and_assert!(
+    argument_shadows_global_in_closure,
+    indoc! {r#"
+        x = 100;
+        fn constant(x) fn () x;
+        print(constant(1)(), x);
+    "#},
+    equals("1 100"),
+    empty()
+);
+
+eval_and_assert!(
+    closure_as_sort_key,
+    indoc! {r#"
+        fn by_distance(xs, pivot) xs.sort(fn (x) abs(x - pivot));
+        print(by_distance([1, 5, 9, 4], 5));
+    "#},
+    equals("[5, 4, 1, 9]"),
+    empty()
+);
+
+eval_and_assert!(
+    memoized_closures_cache_separately,
+    indoc! {r#"
+        fn solve(xs) {
+            memoized fn prefix(i) if i == 0 { xs[0] } else { xs[i] + prefix(i - 1) };
+            prefix(xs.len() - 1)
+        };
+        print(solve([1, 2, 3]), solve([10, 20, 30]));
+    "#},
+    equals("6 60"),
+    empty()
+);
@@ -0,0 +1,66 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    permutations_in_order,
+    indoc! {r#"
+        print(list(permutations([1, 2, 3])));
+        print(list(permutations("abc", 2)));
+        print(list(permutations([1, 2], 3)), list(permutations([], 0)));
+    "#},
+    equals(indoc! {r#"
+        [(1, 2, 3), (1, 3, 2), (2, 1, 3), (2, 3, 1), (3, 1, 2), (3, 2, 1)]
+        [("a", "b"), ("a", "c"), ("b", "a"), ("b", "c"), ("c", "a"), ("c", "b")]
+        [] [()]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    combinations_keep_order,
+    indoc! {r#"
+        print(list(combinations(0..4, 2)));
+        print(list(combinations([1, 2], 0)), list(combinations([1, 2], 3)));
+        for a, b in combinations([10, 20, 30], 2) {
+            print(a + b);
+        };
+    "#},
+    equals(indoc! {r#"
+        [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]
+        [()] []
+        30
+        40
+        50
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    combinatoric_iterators_are_lazy,
+    indoc! {r#"
+        print(combinations(0..30, 15).nth(2));
+        print(combinations(0..20, 10).count());
+        print(permutations(0..8).last());
+        print(permutations(0..50).is_empty(), combinations([1], 2).is_empty());
+    "#},
+    equals(indoc! {r#"
+        (0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 16)
+        184756
+        (7, 6, 5, 4, 3, 2, 1, 0)
+        false true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    combinations_require_non_negative_size,
+    indoc! {r#"
+        combinations([1, 2], -1);
+    "#},
+    empty(),
+    contains("combinations expects a non-negative integer size, got '-1'")
+);
@@ -115,3 +115,57 @@ eval_and_assert!(
     empty(),
     contains("Type mismatch: Cannot compare types 'function' and 'function'")
 );
+
+eval_and_assert!(
+    deep_eq_compares_nested_values,
+    indoc! {r#"
+        m1 = {"k": (1, 2)};
+        m2 = {"k": (1, 2)};
+        print(deep_eq([1, [2, 3], m1], [1, [2, 3], m2]));
+        print(deep_eq([1, [2, 3], m1], [1, [2, 4], m2]));
+        print(deep_eq((1.0, 2.0), (1, 2)), deep_eq([1, 2], (1, 2)));
+    "#},
+    equals(indoc! {r#"
+        true
+        false
+        true false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    deep_eq_handles_self_referencing_lists,
+    indoc! {r#"
+        xs = [1];
+        xs.append(xs);
+        ys = [1];
+        ys.append(ys);
+        print(deep_eq(xs, ys), deep_eq(xs, [1, [1]]));
+    "#},
+    equals("true false"),
+    empty()
+);
+
+eval_and_assert!(
+    approx_eq_allows_float_error_at_any_depth,
+    indoc! {r#"
+        print(0.1 + 0.2 == 0.3, approx_eq(0.1 + 0.2, 0.3));
+        print(approx_eq([1.0, (0.5, 2.0001)], [1, (0.5, 2)], 0.001));
+        print(approx_eq(1, 1.1), approx_eq(1, 1.1, 0.2));
+    "#},
+    equals(indoc! {r#"
+        false true
+        true
+        false true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    approx_eq_rejects_negative_tolerance,
+    indoc! {r#"
+        approx_eq(1, 2, -1);
+    "#},
+    empty(),
+    contains("approx_eq expects a non-negative tolerance, got -1")
+);
@@ -0,0 +1,118 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use linefeed::config::{Config, PROJECT_CONFIG_FILE};
+
+// Each test gets its own scratch directory so they can run in parallel
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("linefeed-config-{}", std::process::id()))
+        .join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn defaults_without_config_files() {
+    let dir = scratch_dir("defaults");
+    assert_eq!(Config::load_from(None, &dir).unwrap(), Config::default());
+}
+
+#[test]
+fn project_config_overrides_user_config() {
+    let dir = scratch_dir("layers");
+    let user_config = dir.join("user.toml");
+    write(
+        &user_config,
+        r#"
+            opt_level = 0
+            deterministic = true
+
+            [sandbox]
+            env = false
+            network = false
+        "#,
+    );
+    write(
+        &dir.join("project").join(PROJECT_CONFIG_FILE),
+        r#"
+            opt_level = 1
+
+            [memo]
+            cache_size = 10
+
+            [sandbox]
+            network = true
+
+            [repl]
+            prelude = ["lib/prelude.lf"]
+        "#,
+    );
+
+    // The project config applies to nested directories too
+    let program_dir = dir.join("project").join("day01");
+    fs::create_dir_all(&program_dir).unwrap();
+
+    let config = Config::load_from(Some(&user_config), &program_dir).unwrap();
+
+    assert_eq!(config.opt_level, 1);
+    assert!(config.deterministic);
+    assert_eq!(config.memo_cache_size, Some(10));
+    assert!(config.sandbox.fs);
+    assert!(!config.sandbox.env);
+    assert!(config.sandbox.network);
+    assert_eq!(
+        config.repl_prelude,
+        vec![dir.join("project").join("lib/prelude.lf")]
+    );
+}
+
+#[test]
+fn unknown_settings_are_rejected() {
+    let dir = scratch_dir("unknown");
+    write(&dir.join(PROJECT_CONFIG_FILE), "[memo]\nsize = 10\n");
+
+    let err = Config::load_from(None, &dir).unwrap_err();
+    assert_eq!(err.path, dir.join(PROJECT_CONFIG_FILE));
+    assert!(err.msg.contains("unknown field `size`"), "{}", err.msg);
+}
+
+#[test]
+fn out_of_range_opt_level_is_rejected() {
+    let dir = scratch_dir("opt_level");
+    write(&dir.join(PROJECT_CONFIG_FILE), "opt_level = 5\n");
+
+    let err = Config::load_from(None, &dir).unwrap_err();
+    assert_eq!(err.msg, "opt_level must be between 0 and 1, got 5");
+}
+
+#[test]
+fn memoization_respects_cache_size() {
+    let src = r#"
+        memoized fn fib(n) if n < 2 { n } else { fib(n - 1) + fib(n - 2) };
+        print(fib(20));
+    "#;
+
+    for cache_size in [None, Some(0), Some(3)] {
+        let config = Config {
+            memo_cache_size: cache_size,
+            deterministic: true,
+            ..Config::default()
+        };
+
+        let mut stdout = Vec::new();
+        let status =
+            linefeed::run_with_config(src, &config, std::io::empty(), &mut stdout, std::io::sink());
+
+        assert_eq!(status, linefeed::EXIT_SUCCESS);
+        assert_eq!(String::from_utf8(stdout).unwrap().trim(), "6765");
+    }
+}
@@ -0,0 +1,64 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+use indoc::indoc;
+
+eval_and_assert!(
+    tally_counts_occurrences,
+    indoc! {r#"
+        print("abca".tally());
+        counts = [3, 1, 3].tally();
+        print(counts[3]);
+    "#},
+    equals(indoc! {r#"
+        {"a": 2, "b": 1, "c": 1}
+        2
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    pairwise_gives_consecutive_pairs,
+    indoc! {r#"
+        print([1, 4, 9, 16].pairwise());
+        print("abc".pairwise());
+        print([1].pairwise());
+    "#},
+    equals(indoc! {r#"
+        [(1, 4), (4, 9), (9, 16)]
+        [("a", "b"), ("b", "c")]
+        []
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    deltas_gives_differences_between_consecutive_values,
+    indoc! {r#"
+        xs = [1, 4, 9, 16];
+        print(xs.deltas());
+        print(xs.deltas().deltas());
+        print((1..4).deltas());
+        print([(1, 1), (3, 4)].deltas());
+        print([].deltas());
+    "#},
+    equals(indoc! {r#"
+        [3, 5, 7]
+        [2, 2]
+        [1, 1]
+        [(2, 3)]
+        []
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    deltas_of_non_numbers_is_an_error,
+    indoc! {r#"
+        print([1, "a"].deltas());
+    "#},
+    empty(),
+    contains("Cannot subtract types 'str' and 'number'")
+);
@@ -0,0 +1,68 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{empty, equals},
+};
+
+use indoc::indoc;
+use linefeed::{
+    chumsky::Parser as _,
+    compiler::{Compiler, Program},
+    vm::bytecode::Bytecode,
+};
+
+fn compile(src: &str) -> Program<Bytecode> {
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+    Compiler::default().compile(&ast).unwrap()
+}
+
+// Returns the number of constants in the compiled program for `src`
+fn constants(src: &str) -> usize {
+    compile(src).constants.len()
+}
+
+#[test]
+fn identical_literals_share_a_constant() {
+    let distinct = constants(r#"a = "x"; b = "y"; c = [1, 2]; d = [1, 3];"#);
+    let repeated = constants(r#"a = "x"; b = "x"; c = [1, 2]; d = [1, 2];"#);
+
+    assert_eq!(repeated, distinct - 2);
+}
+
+#[test]
+fn equal_literals_of_different_kinds_are_kept_apart() {
+    assert_eq!(constants("a = 1; b = 1.0;"), constants("a = 1; b = 2;"));
+}
+
+#[test]
+fn only_constants_that_can_change_are_copied() {
+    let program = compile(r#"a = "x"; b = (1, "y"); c = [1]; d = {"k": [2]};"#);
+    let (mut loaded, mut copied) = (0, 0);
+    for instr in &program.instructions {
+        match instr {
+            Bytecode::LoadConst(index) => {
+                assert!(program.constants[*index as usize].is_immutable());
+                loaded += 1;
+            }
+            Bytecode::CopyConst(index) => {
+                assert!(!program.constants[*index as usize].is_immutable());
+                copied += 1;
+            }
+            _ => {}
+        }
+    }
+
+    assert!(loaded > 0 && copied > 0);
+}
+
+eval_and_assert!(
+    shared_constants_still_give_separate_values,
+    indoc! {r#"
+        xs = [1];
+        ys = [1];
+        xs.append(2);
+        print(xs, ys, 1 * 2 ** 70, 1.0 * 2 ** 70);
+    "#},
+    equals("[1, 2] [1] 1180591620717411303424 1180591620717411300000"),
+    empty()
+);
@@ -0,0 +1,169 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    int_truncates_towards_zero,
+    indoc::indoc! {r#"
+        print(int(2.7), int(-2.7), int(5));
+        print(int("42"), int(" -7 "), int(true), int(false));
+        print(int("123456789012345678901234567890") + 1);
+    "#},
+    equals(indoc::indoc! {r#"
+        2 -2 5
+        42 -7 1 0
+        123456789012345678901234567891
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    int_with_rounding_mode,
+    indoc::indoc! {r#"
+        print(int(2.5, "floor"), int(2.5, "ceil"), int(2.5, "round"), int(2.5, "trunc"));
+        print(int(-2.5, "floor"), int(-2.5, "ceil"), int(-2.5, "round"), int(-2.5, "trunc"));
+        print(int(float("1e30"), "round") > 10 ** 29);
+    "#},
+    equals(indoc::indoc! {r#"
+        2 3 3 2
+        -3 -2 -3 -2
+        true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    int_result_is_an_integer,
+    "print(repr(int(6 / 2)), [1, 2, 3][int(4 / 3)])",
+    equals("3 2"),
+    empty()
+);
+
+eval_and_assert!(
+    float_converts_to_floating_point,
+    indoc::indoc! {r#"
+        print(float(3) / 2, float("2.5"), float(" 1e3 "), float(true));
+    "#},
+    equals("1.5 2.5 1000 1"),
+    empty()
+);
+
+eval_and_assert!(
+    parse_int_only_accepts_strings,
+    indoc::indoc! {r#"
+        print(parse_int("12") * 2);
+        parse_int(1.5);
+    "#},
+    equals("24"),
+    contains("Cannot parse 'number' as integer, use int() to convert other types")
+);
+
+eval_and_assert!(
+    parse_float_only_accepts_strings,
+    indoc::indoc! {r#"
+        print(parse_float("3.14"), parse_float(" -2 "), parse_float("1e-3"), parse_float(".5"));
+        print(repr(parse_float("4")) == repr(4.0));
+        parse_float(3);
+    "#},
+    equals("3.14 -2 0.001 0.5\ntrue"),
+    contains("Cannot parse 'number' as float, use float() to convert other types")
+);
+
+eval_and_assert!(
+    parse_float_rejects_malformed_strings,
+    r#"parse_float("3.14.15")"#,
+    empty(),
+    contains(r#""3.14.15" is not a valid number"#)
+);
+
+eval_and_assert!(
+    int_rejects_lists,
+    "int([1, 2])",
+    empty(),
+    contains("Cannot convert 'list' to an integer")
+);
+
+eval_and_assert!(
+    int_rejects_invalid_strings,
+    r#"int("2.5")"#,
+    empty(),
+    contains(r#""2.5" is not a valid integer"#)
+);
+
+eval_and_assert!(
+    int_rejects_unknown_rounding_modes,
+    r#"int(2.5, "up")"#,
+    empty(),
+    contains(r#"Unknown rounding mode "up""#)
+);
+
+eval_and_assert!(
+    int_rejects_infinity,
+    r#"int(float("inf"))"#,
+    empty(),
+    contains("Cannot convert inf to an integer")
+);
+
+eval_and_assert!(
+    float_rejects_invalid_strings,
+    r#"float("abc")"#,
+    empty(),
+    contains(r#""abc" is not a valid number"#)
+);
+
+eval_and_assert!(
+    int_with_base,
+    indoc::indoc! {r#"
+        print(int("ff", 16), int("FF", 16), int("1011", 2), int(" -777 ", 8), int("z", 36));
+        print(int("ffffffffffffffffffffffff", 16));
+    "#},
+    equals(indoc::indoc! {r#"
+        255 255 11 -511 35
+        79228162514264337593543950335
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    to_base_writes_integers,
+    indoc::indoc! {r#"
+        print(to_base(255, 16), to_base(11, 2), to_base(-511, 8), to_base(0, 2), to_base(35, 36));
+        print(to_base(2 ** 100, 16));
+        print(int(to_base(123456789, 7), 7));
+    "#},
+    equals(indoc::indoc! {r#"
+        ff 1011 -777 0 z
+        10000000000000000000000000
+        123456789
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    int_rejects_digits_outside_the_base,
+    r#"int("102", 2)"#,
+    empty(),
+    contains(r#""102" is not a valid base 2 integer"#)
+);
+
+eval_and_assert!(
+    int_with_base_only_takes_strings,
+    "int(12, 16)",
+    empty(),
+    contains("int() only takes a base when converting a string, got 'number'")
+);
+
+eval_and_assert!(
+    to_base_rejects_invalid_bases,
+    "to_base(10, 37)",
+    empty(),
+    contains("Base must be between 2 and 36, got 37")
+);
+
+eval_and_assert!(
+    to_base_rejects_floats,
+    "to_base(1.5, 2)",
+    empty(),
+    contains("Cannot write floating point numbers in base 2")
+);
@@ -0,0 +1,174 @@
+use std::io;
+
+use indoc::indoc;
+use linefeed::{alloc_stats, config::Config};
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{empty, equals},
+};
+
+eval_and_assert!(
+    copy_does_not_share_nested_values,
+    indoc! {r#"
+        a = {"xs": [1, 2], "pair": ([3], 4)};
+        b = copy(a);
+        b["xs"].append(5);
+        b["pair"][0].append(6);
+        print(a);
+        print(b);
+    "#},
+    equals(indoc! {r#"
+        {"pair": ([3], 4), "xs": [1, 2]}
+        {"pair": ([3, 6], 4), "xs": [1, 2, 5]}
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    copy_works_for_sets_and_ranges,
+    indoc! {r#"
+        s = set([1]);
+        t = copy(s);
+        t.add(2);
+        print(s.len(), t.len(), copy(2..5), copy("abc"), copy(null));
+    "#},
+    equals("1 2 2..5 abc null"),
+    empty()
+);
+
+eval_and_assert!(
+    copy_of_iterator_leaves_the_original_in_place,
+    indoc! {r#"
+        it = [10, 20, 30].enumerate();
+        it.nth(0);
+        print(copy(it).count(), it.nth(0));
+
+        r = (5..).enumerate();
+        print(copy(r).nth(2), r.nth(0));
+
+        m = set(["a"]).enumerate();
+        print(list(copy(m)), list(m));
+    "#},
+    equals(indoc! {r#"
+        2 (1, 20)
+        (2, 7) (0, 5)
+        [(0, "a")] [(0, "a")]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    copies_share_values_until_either_is_changed,
+    indoc! {r#"
+        grid = [[1, 2], [3, 4]];
+        a = copy(grid);
+        b = copy(grid);
+        a[0][0] = 9;
+        for row in b {
+            row.append(0);
+        };
+        grid.append([5]);
+        print(grid, a, b);
+
+        m = {"xs": [1]};
+        n = copy(m);
+        m["xs"].append(2);
+        n["ys"] = [3];
+        print(m, n);
+    "#},
+    equals(indoc! {r#"
+        [[1, 2], [3, 4], [5]] [[9, 2], [3, 4]] [[1, 2, 0], [3, 4, 0]]
+        {"xs": [1, 2]} {"xs": [1], "ys": [3]}
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    copy_of_map_while_iterating_over_it,
+    indoc! {r#"
+        m = {"xs": [1]};
+        for kv in m {
+            n = copy(m);
+            n["xs"].append(2);
+            print(m["xs"], n["xs"]);
+        };
+    "#},
+    equals("[1] [1, 2]"),
+    empty()
+);
+
+eval_and_assert!(
+    copy_keeps_nested_values_as_they_were_when_copied,
+    indoc! {r#"
+        row = [1];
+        grid = [row, [[2]]];
+        a = copy(grid);
+        row.append(3);
+        grid[1][0].append(4);
+        b = copy(a);
+        a[0].append(5);
+        print(grid, a, b);
+    "#},
+    equals("[[1, 3], [[2, 4]]] [[1, 5], [[2]]] [[1], [[2]]]"),
+    empty()
+);
+
+fn allocations(src: &str) -> usize {
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let before = alloc_stats::allocations();
+    let status = linefeed::run_with_config(
+        src,
+        &Config::default(),
+        io::empty(),
+        &mut stdout,
+        &mut stderr,
+    );
+    assert_eq!(status, linefeed::EXIT_SUCCESS, "{stderr:?}");
+    alloc_stats::allocations() - before
+}
+
+#[test]
+fn reading_a_copy_does_not_copy_its_values() {
+    // Both programs copy the list equally often and read as much, but only the first reads the
+    // copies, which would take one more allocation per copy if reads copied the shared values
+    let reading_copies = allocations(indoc! {r#"
+        a = [i for i in 0..1000];
+        c = [i for i in 0..1000];
+        for i in 0..1000 {
+            b = copy(a);
+            x = b[0] + b[999] + len(b) + c.len();
+        };
+    "#});
+    let reading_other = allocations(indoc! {r#"
+        a = [i for i in 0..1000];
+        c = [i for i in 0..1000];
+        for i in 0..1000 {
+            b = copy(a);
+            x = c[0] + c[999] + len(c) + b.len();
+        };
+    "#});
+
+    assert!(
+        reading_copies.abs_diff(reading_other) < 100,
+        "{reading_copies} allocations when reading copies, {reading_other} otherwise"
+    );
+}
+
+#[test]
+fn copying_a_nested_list_does_not_copy_its_rows() {
+    // The grid has 10000 rows, so copying each of them would take at least that many allocations
+    let copying = allocations(indoc! {r#"
+        grid = [[i, i] for i in 0..10000];
+        copied = copy(grid);
+    "#});
+    let sharing = allocations(indoc! {r#"
+        grid = [[i, i] for i in 0..10000];
+        shared = grid;
+    "#});
+
+    assert!(
+        copying.abs_diff(sharing) < 100,
+        "{copying} allocations when copying, {sharing} otherwise"
+    );
+}
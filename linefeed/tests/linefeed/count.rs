@@ -38,3 +38,37 @@ eval_and_assert!(
     empty(),
     contains("Type mismatch: Cannot count 'number' in 'str'")
 );
+
+eval_and_assert!(
+    count_if_counts_matching_items,
+    indoc! {r#"
+        xs = [1, 5, 8, 2, 9];
+        print(count_if(xs, fn(x) x > 4));
+        print(count_if(0..10, fn(x) x % 3 == 0));
+        print(count_if("a1b2", fn(c) c in "0123456789"));
+    "#},
+    equals(indoc! {r#"
+        3
+        4
+        2
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    count_if_without_predicate_counts_truthy_items,
+    indoc! {r#"
+        print(count_if([true, false, true]), count_if([x > 1 for x in [1, 2, 3]]));
+    "#},
+    equals("2 2"),
+    empty()
+);
+
+eval_and_assert!(
+    summing_booleans_suggests_count_if,
+    indoc! {r#"
+        print(sum([true, false]));
+    "#},
+    empty(),
+    contains("Cannot sum booleans, use count_if to count the true ones")
+);
@@ -1,6 +1,6 @@
 use crate::helpers::{
     eval_and_assert,
-    output::{empty, equals},
+    output::{contains, empty, equals},
 };
 
 use indoc::indoc;
@@ -68,3 +68,59 @@ eval_and_assert!(
     "#}),
     empty()
 );
+
+eval_and_assert!(
+    counter_most_common,
+    indoc! {r#"
+        c = counter("mississippi");
+        print(c.most_common());
+        print(c.most_common(2), c.most_common(0));
+    "#},
+    equals(indoc! {r#"
+        [("i", 4), ("s", 4), ("p", 2), ("m", 1)]
+        [("i", 4), ("s", 4)] []
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    counter_arithmetic,
+    indoc! {r#"
+        a = counter("aab");
+        b = counter("abc");
+        print(a + b);
+        print(a - b, b - a, a - a);
+        print(a);
+    "#},
+    equals(indoc! {r#"
+        {"a": 3, "b": 2, "c": 1}
+        {"a": 1} {"c": 1} {}
+        {"a": 2, "b": 1}
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    counter_comparison_is_inclusion,
+    indoc! {r#"
+        a = counter("aab");
+        print(a == counter("aba"), a != counter("ab"));
+        print(counter("ab") < a, a <= a, a < a, counter("aabc") > a);
+        print(a < counter("abc"), a > counter("abc"));
+    "#},
+    equals(indoc! {r#"
+        true true
+        true true false true
+        false false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    most_common_requires_counter,
+    indoc! {r#"
+        [1, 2].most_common();
+    "#},
+    empty(),
+    contains("Cannot call method 'most_common' on type 'list'")
+);
@@ -0,0 +1,78 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    parse_csv_splits_rows_and_fields,
+    indoc::indoc! {r#"
+        rows = parse_csv("a,b,c\n1,2,3\n\n4,,6\n");
+        print(rows);
+        print(parse_csv("x;y\nz;w", ";"));
+        print(sum([int(x) for x in parse_csv("1 2 3", " ")[0]]));
+    "#},
+    equals(indoc::indoc! {r#"
+        [["a", "b", "c"], ["1", "2", "3"], ["4", "", "6"]]
+        [["x", "y"], ["z", "w"]]
+        6
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    parse_csv_handles_quoted_fields,
+    indoc::indoc! {r#"
+        for row in parse_csv(input()) {
+          print(row.len(), row[0], row[1]);
+        };
+    "#},
+    "name,quote\r\n\"Doe, Jane\",\"She said \"\"hi\"\"\nand left\"\r\n\"\",x\r\n",
+    equals(indoc::indoc! {r#"
+        2 name quote
+        2 Doe, Jane She said "hi"
+        and left
+        2  x
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    parse_csv_with_header_gives_maps,
+    indoc::indoc! {r#"
+        records = parse_csv("id,name\n1,ada\n2,grace\n", ",", true);
+        print(records.len());
+        for r in records {
+          print(int(r["id"]), r["name"]);
+        };
+        print(parse_csv("id,name", ",", true));
+    "#},
+    equals(indoc::indoc! {r#"
+        2
+        1 ada
+        2 grace
+        []
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    parse_csv_header_mismatch,
+    r#"parse_csv("a,b\n1,2,3", ",", true);"#,
+    empty(),
+    contains("Row 2 of CSV has 3 fields, but the header has 2")
+);
+
+eval_and_assert!(
+    parse_csv_unclosed_quote,
+    r#"parse_csv(input());"#,
+    "a,\"b\nc",
+    empty(),
+    contains("Unclosed quote in row 1 of CSV")
+);
+
+eval_and_assert!(
+    parse_csv_invalid_delimiter,
+    r#"parse_csv("a,b", ", ");"#,
+    empty(),
+    contains("CSV delimiter must be a single character")
+);
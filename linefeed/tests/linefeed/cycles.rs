@@ -0,0 +1,65 @@
+use indoc::indoc;
+use linefeed::vm::runtime_value::cycles;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{empty, equals},
+    run_program,
+};
+
+eval_and_assert!(
+    cycles_that_are_still_used_survive_collection,
+    indoc! {r#"
+        xs = [1];
+        xs.append(xs);
+        m = {"name": "m"};
+        m["self"] = m;
+        fn make_counter() {
+          n = 0;
+          fn next() {
+            n += 1;
+            return next;
+          };
+          return next;
+        };
+        next = make_counter();
+
+        for i in 0..50000 {
+          garbage = [i];
+          garbage.append(garbage);
+          next = next();
+        };
+
+        print(xs[1][1][0], m["self"]["self"]["name"]);
+        next();
+        print(next()()()() == next);
+    "#},
+    equals(indoc! {"
+        1 m
+        true
+    "}),
+    empty()
+);
+
+#[test]
+fn unreachable_cycles_are_collected() {
+    let src = indoc! {r#"
+        fn make_cycles(i) {
+          xs = [i];
+          xs.append(xs);
+          m = {"list": xs};
+          xs.append(m);
+          fn f() {
+            return f;
+          };
+        };
+        for i in 0..100 {
+          make_cycles(i);
+        };
+    "#};
+    let (_, stderr) = run_program(src, std::io::empty());
+    assert_eq!(stderr, "");
+
+    // Each iteration leaves a list and a map, as well as the upvalue holding `f`
+    assert!(cycles::collect() >= 300);
+}
@@ -0,0 +1,68 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    format_time_of_known_dates,
+    indoc::indoc! {r#"
+        print(format_time(0));
+        print(format_time(951782400, "%d/%m/%Y"));
+        print(format_time(1733029200.75, "%Y%m%d %H:%M:%S 100%%"));
+        print(format_time(-1));
+    "#},
+    equals(indoc::indoc! {r#"
+        1970-01-01 00:00:00
+        29/02/2000
+        20241201 05:00:00 100%
+        1969-12-31 23:59:59
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    parse_time_round_trips,
+    indoc::indoc! {r#"
+        print(parse_time("1970-01-02 00:00:00"));
+        print(parse_time("2024-12-01", "%Y-%m-%d"));
+        print(parse_time("20000229", "%Y%m%d") == 951782400);
+        t = parse_time("2023-07-14 12:34:56");
+        print(format_time(t));
+    "#},
+    equals(indoc::indoc! {r#"
+        86400
+        1733011200
+        true
+        2023-07-14 12:34:56
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    now_and_timestamp_are_current,
+    indoc::indoc! {r#"
+        start = now();
+        print(start > 1700000000, now() >= start);
+        stamp = timestamp();
+        print(stamp.len(), parse_time(stamp) >= int(start) - 1);
+    "#},
+    equals(indoc::indoc! {r#"
+        true true
+        19 true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    parse_time_rejects_invalid_dates,
+    r#"parse_time("2023-02-29", "%Y-%m-%d");"#,
+    empty(),
+    contains(r#""2023-02-29" does not match the time format "%Y-%m-%d""#)
+);
+
+eval_and_assert!(
+    unknown_time_format_specifier,
+    r#"format_time(0, "%Q");"#,
+    empty(),
+    contains("Unknown time format specifier '%Q'")
+);
@@ -0,0 +1,109 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    defer_runs_when_the_function_returns_in_reverse_order,
+    indoc! {r#"
+        fn work(name) {
+          defer print("closed " + name);
+          defer print("flushed " + name);
+          print("working on " + name);
+          return name.len();
+        };
+        print(work("a.txt"));
+    "#},
+    equals(indoc! {"
+        working on a.txt
+        flushed a.txt
+        closed a.txt
+        5
+    "}),
+    empty()
+);
+
+eval_and_assert!(
+    defer_sees_the_latest_value_of_local_variables,
+    indoc! {r#"
+        fn count() {
+          n = 0;
+          defer print("counted", n);
+          for _ in 0..3 {
+            n += 1;
+          };
+        };
+        count();
+    "#},
+    equals("counted 3"),
+    empty()
+);
+
+eval_and_assert!(
+    defer_inside_a_callback_runs_when_the_callback_returns,
+    indoc! {r#"
+        fn key(x) {
+          defer print("key", x);
+          return -x;
+        };
+        print(sorted([2, 1], key));
+    "#},
+    equals(indoc! {"
+        key 2
+        key 1
+        [2, 1]
+    "}),
+    empty()
+);
+
+eval_and_assert!(
+    defer_at_the_top_level_runs_when_the_program_ends,
+    indoc! {r#"
+        defer print("done");
+        print("start");
+    "#},
+    equals(indoc! {"
+        start
+        done
+    "}),
+    empty()
+);
+
+eval_and_assert!(
+    defer_runs_when_an_error_unwinds_the_program,
+    indoc! {r#"
+        fn fail() {
+          defer print("cleaned up");
+          error("broken");
+        };
+        defer print("finished");
+        fail();
+    "#},
+    equals(indoc! {"
+        cleaned up
+        finished
+    "}),
+    contains("broken")
+);
+
+eval_and_assert!(
+    defer_runs_when_the_program_exits,
+    indoc! {r#"
+        defer print("finished");
+        exit();
+    "#},
+    equals("finished"),
+    empty()
+);
+
+eval_and_assert!(
+    defer_is_still_a_name_outside_of_statements,
+    indoc! {r#"
+        defer = 2;
+        print(defer * 3);
+    "#},
+    equals("6"),
+    empty()
+);
@@ -0,0 +1,90 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+use indoc::indoc;
+
+eval_and_assert!(
+    deque_pushes_and_pops_at_both_ends,
+    indoc! {r#"
+        q = deque([2, 3]);
+        q.push_front(1);
+        q.push_back(4);
+        print(q);
+        print(q.pop_front(), q.pop_back(), q.len());
+        print(q);
+    "#},
+    equals(indoc! {r#"
+        deque([1, 2, 3, 4])
+        1 4 2
+        deque([2, 3])
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    deque_can_be_created_empty_or_from_iterable,
+    indoc! {r#"
+        print(deque(), deque("ab"), deque(0..3));
+        print(deque().is_empty(), deque([1]) == deque([1]));
+    "#},
+    equals(indoc! {r#"
+        deque([]) deque(["a", "b"]) deque([0, 1, 2])
+        true true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    deque_can_be_iterated_indexed_and_searched,
+    indoc! {r#"
+        q = deque([1, 2, 3]);
+        for x in q {
+            print(x);
+        };
+        print(q[0], q[-1], 2 in q, 5 in q, list(q));
+    "#},
+    equals(indoc! {r#"
+        1
+        2
+        3
+        1 3 true false [1, 2, 3]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    deque_works_as_bfs_queue,
+    indoc! {r#"
+        edges = {1: [2, 3], 2: [4], 3: [4], 4: []};
+        seen = set([1]);
+        q = deque([(1, 0)]);
+        while q {
+            (node, dist) = q.pop_front();
+            print(node, dist);
+            for next in edges[node] {
+                if next not in seen {
+                    seen.add(next);
+                    q.push_back((next, dist + 1));
+                };
+            };
+        };
+    "#},
+    equals(indoc! {r#"
+        1 0
+        2 1
+        3 1
+        4 2
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    deque_pop_from_empty_is_error,
+    indoc! {r#"
+        deque().pop_back();
+    "#},
+    empty(),
+    contains("Cannot pop from an empty deque")
+);
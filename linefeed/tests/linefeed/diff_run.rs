@@ -0,0 +1,82 @@
+use std::{fs, path::PathBuf};
+
+use linefeed::{config::Config, diff_run::diff_run, EXIT_FAILURE, EXIT_SUCCESS};
+
+// Writes the programs to a scratch directory of their own, returning their paths
+fn write_programs(name: &str, a: &str, b: &str) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir()
+        .join(format!("linefeed-diff-run-{}", std::process::id()))
+        .join(name);
+    fs::create_dir_all(&dir).unwrap();
+
+    let (path_a, path_b) = (dir.join("a.lf"), dir.join("b.lf"));
+    fs::write(&path_a, a).unwrap();
+    fs::write(&path_b, b).unwrap();
+    (path_a, path_b)
+}
+
+fn run(a: &str, b: &str, input: &str, name: &str) -> (i32, String) {
+    let (path_a, path_b) = write_programs(name, a, b);
+    let mut out = Vec::new();
+    let status = diff_run(
+        &path_a,
+        &path_b,
+        &Config::default(),
+        input.as_bytes(),
+        &mut out,
+    );
+
+    let out = String::from_utf8(strip_ansi_escapes::strip(out)).unwrap();
+    (status, out)
+}
+
+#[test]
+fn identical_output_succeeds() {
+    let (status, out) = run(
+        "print(sum([int(l) for l in input().lines()]));",
+        "s = 0;\nfor l in input().lines() { s += int(l); };\nprint(s);",
+        "1\n2\n3\n",
+        "identical",
+    );
+
+    assert_eq!(status, EXIT_SUCCESS);
+    assert!(out.contains("stdout:       identical (1 lines)"), "{out}");
+    assert!(out.contains("exit status:  0 in both"), "{out}");
+    assert!(out.contains("instructions: "), "{out}");
+    assert!(out.contains("time: "), "{out}");
+}
+
+#[test]
+fn first_differing_line_is_reported() {
+    let (status, out) = run(
+        "print(1);\nprint(2);\nprint(3);",
+        "print(1);\nprint(4);\nprint(3);",
+        "",
+        "differs",
+    );
+
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(out.contains("stdout:       differs at line 2"), "{out}");
+    assert!(out.contains("a.lf: \"2\""), "{out}");
+    assert!(out.contains("b.lf: \"4\""), "{out}");
+}
+
+#[test]
+fn failing_program_is_reported_with_its_error() {
+    let (status, out) = run("print(1);", "print(1);\n[] - 1;", "", "fails");
+
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(out.contains("stdout:       identical"), "{out}");
+    assert!(out.contains("exit status:  0 vs 1"), "{out}");
+    assert!(out.contains("b.lf failed with:"), "{out}");
+    assert!(out.contains("Cannot subtract"), "{out}");
+}
+
+#[test]
+fn programs_that_do_not_compile_have_no_instruction_count() {
+    let (status, out) = run("print(1);", "print(1 +);", "", "no_compile");
+
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(out.contains("instructions: "), "{out}");
+    assert!(out.contains(" vs -"), "{out}");
+}
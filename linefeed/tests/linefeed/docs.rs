@@ -0,0 +1,55 @@
+use linefeed::docs::{builtins, std_reference, BuiltinKind, DocFormat};
+
+#[test]
+fn every_builtin_is_documented() {
+    let undocumented = builtins()
+        .into_iter()
+        .filter(|builtin| builtin.doc.is_empty())
+        .map(|builtin| builtin.name)
+        .collect::<Vec<_>>();
+
+    assert_eq!(undocumented, Vec::<&str>::new());
+}
+
+#[test]
+fn builtins_are_grouped_by_kind_and_sorted() {
+    let builtins = builtins();
+    let kinds = builtins.iter().map(|b| b.kind).collect::<Vec<_>>();
+    assert!(kinds.is_sorted());
+    assert!(kinds.contains(&BuiltinKind::Snippet));
+
+    let methods = builtins
+        .iter()
+        .filter(|b| b.kind == BuiltinKind::Method)
+        .map(|b| b.name)
+        .collect::<Vec<_>>();
+    assert!(methods.is_sorted());
+    assert!(methods.contains(&"tally"));
+}
+
+#[test]
+fn markdown_reference() {
+    let reference = std_reference(DocFormat::Markdown);
+
+    assert!(reference.starts_with("# Linefeed standard library\n"));
+    assert!(reference.contains(indoc::indoc! {"
+        ### `int(…)`
+
+        Takes 1 to 2 arguments.
+
+        Converts a number, string or boolean to an integer."
+    }));
+    assert!(reference.contains("### `.lines()`\n\nTakes 0 arguments.\n"));
+    assert!(reference.contains("### `print(…)`\n\nTakes any number of arguments.\n"));
+    assert!(reference.contains("## Snippets\n\n### `aoc_parts(…)`"));
+}
+
+#[test]
+fn html_reference_escapes_and_formats_code() {
+    let reference = std_reference(DocFormat::Html);
+
+    assert!(reference.starts_with("<!DOCTYPE html>"));
+    assert!(reference.contains("<h3 id=\"methods-enumerate\"><code>.enumerate(…)</code></h3>"));
+    assert!(reference.contains("as <code>(index, item)</code> tuples"));
+    assert!(reference.contains("<code>&quot;floor&quot;</code>"));
+}
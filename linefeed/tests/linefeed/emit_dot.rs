@@ -0,0 +1,83 @@
+use indoc::indoc;
+use linefeed::{chumsky::Parser as _, compiler::cfg::ControlFlowGraph, compiler::Compiler};
+
+fn dot(src: &str) -> String {
+    let mut out = Vec::new();
+    let status = linefeed::emit_dot(src, &mut out, std::io::sink());
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+    String::from_utf8(out).unwrap()
+}
+
+fn cfg(src: &str) -> ControlFlowGraph {
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+    ControlFlowGraph::new(&Compiler::default().compile(&ast).unwrap())
+}
+
+#[test]
+fn straight_line_program_is_a_single_block() {
+    let graph = cfg("x = 1; print(x + 2);");
+
+    assert_eq!(graph.functions.len(), 1);
+    assert_eq!(graph.functions[0].name(), "main");
+    assert_eq!(graph.functions[0].blocks.len(), 1);
+}
+
+#[test]
+fn each_function_gets_its_own_graph() {
+    let graph = cfg(indoc! {"
+        fn a(x) x + 1;
+        fn b(x) if x { 1 } else { 2 };
+        print(a(1), b(true));
+    "});
+
+    let names = graph.functions.iter().map(|f| f.name()).collect::<Vec<_>>();
+    assert_eq!(names.len(), 3);
+    assert_eq!(names[0], "main");
+    assert!(names[1..].iter().all(|name| name.starts_with("fn@")));
+
+    // The `if` splits `b` into the condition, both branches and the join
+    assert_eq!(graph.functions[2].blocks.len(), 4);
+}
+
+#[test]
+fn dot_output_lists_instructions_and_labels_branches() {
+    let out = dot(indoc! {r#"
+        for i in 0..3 {
+          print(if i > 1 { "i" } else { "j" })
+        };
+    "#});
+
+    assert!(out.starts_with("digraph program {\n"));
+    assert!(out.trim_end().ends_with('}'));
+    assert!(out.contains("subgraph cluster_0 {\n    label=\"main\";"));
+    assert!(out.contains("[label=\"next\"]"));
+    assert!(out.contains("[label=\"done\"]"));
+    assert!(out.contains("[label=\"true\"]"));
+    assert!(out.contains("[label=\"false\"]"));
+    assert!(out.contains(r#"LoadConst(\"i\")\l"#));
+}
+
+#[test]
+fn assignments_used_as_statements_store_and_pop_at_once() {
+    let out = dot(indoc! {r#"
+        x = 1;
+        fn f() {
+          y = 2;
+          y
+        };
+        print(x, f());
+    "#});
+
+    assert!(out.contains("PopToGlobal("));
+    assert!(out.contains("PopToLocal("));
+}
+
+#[test]
+fn compile_errors_are_reported() {
+    let mut stderr = Vec::new();
+    let status = linefeed::emit_dot("print(missing);", std::io::sink(), &mut stderr);
+
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(String::from_utf8_lossy(&stderr).contains("No such variable 'missing'"));
+}
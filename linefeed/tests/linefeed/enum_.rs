@@ -0,0 +1,146 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+use indoc::indoc;
+
+eval_and_assert!(
+    variants_compare_by_identity,
+    indoc! {r#"
+        enum Dir { Up, Down, Left, Right };
+        print(Dir.Up);
+        print(Dir.Up == Dir.Up);
+        print(Dir.Up == Dir.Down);
+        print(Dir.Up == :Up);
+    "#},
+    equals(indoc! {r#"
+        Dir.Up
+        true
+        false
+        false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    variants_can_be_map_keys,
+    indoc! {r#"
+        enum Dir { Up, Down, Left, Right };
+        steps = {Dir.Up: (0, -1), Dir.Down: (0, 1)};
+        print(steps[Dir.Down]);
+        print(Dir.Up in steps);
+        print(Dir.Left in steps);
+    "#},
+    equals(indoc! {r#"
+        (0, 1)
+        true
+        false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    enum_name_is_a_list_of_its_variants,
+    indoc! {r#"
+        enum Dir { Up, Down, Left, Right };
+        print(Dir);
+        xs = [Dir.Right, Dir.Up, Dir.Left];
+        xs.sort();
+        print(xs);
+    "#},
+    equals(indoc! {r#"
+        [Dir.Up, Dir.Down, Dir.Left, Dir.Right]
+        [Dir.Up, Dir.Left, Dir.Right]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    match_on_variants,
+    indoc! {r#"
+        enum Dir { Up, Down, Left, Right };
+
+        fn turn_right(dir) {
+            match dir {
+                Dir.Up => Dir.Right,
+                Dir.Right => Dir.Down,
+                Dir.Down => Dir.Left,
+                Dir.Left => Dir.Up,
+            }
+        };
+
+        print(turn_right(Dir.Up));
+        print(turn_right(turn_right(Dir.Left)));
+    "#},
+    equals(indoc! {r#"
+        Dir.Right
+        Dir.Right
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    match_on_variants_must_cover_all_of_them,
+    indoc! {r#"
+        enum Dir { Up, Down, Left, Right };
+        print(match Dir.Up {
+            Dir.Up => 1,
+            Dir.Down => 2,
+        });
+    "#},
+    empty(),
+    contains("Match on enum 'Dir' has no arm for Dir.Left, Dir.Right")
+);
+
+eval_and_assert!(
+    match_on_variants_with_catch_all,
+    indoc! {r#"
+        enum Dir { Up, Down, Left, Right };
+        print(match Dir.Left {
+            Dir.Up => "vertical",
+            Dir.Down => "vertical",
+            _ => "horizontal",
+        });
+    "#},
+    equals("horizontal"),
+    empty()
+);
+
+eval_and_assert!(
+    unknown_variant_is_an_error,
+    indoc! {r#"
+        enum Dir { Up, Down };
+        print(Dir.Sideways);
+    "#},
+    empty(),
+    contains("Enum 'Dir' has no variant 'Sideways'")
+);
+
+eval_and_assert!(
+    undeclared_enum_is_an_error,
+    indoc! {r#"
+        print(Dir.Up);
+    "#},
+    empty(),
+    contains("No such enum 'Dir'")
+);
+
+eval_and_assert!(
+    duplicate_variant_is_an_error,
+    indoc! {r#"
+        enum Dir { Up, Down, Up };
+    "#},
+    empty(),
+    contains("Variant 'Up' is declared more than once in enum 'Dir'")
+);
+
+eval_and_assert!(
+    enum_is_still_a_valid_name,
+    indoc! {r#"
+        enum = 3;
+        print(enum + 1);
+    "#},
+    equals("4"),
+    empty()
+);
@@ -1,6 +1,6 @@
 use crate::helpers::{
     eval_and_assert,
-    output::{empty, equals},
+    output::{contains, empty, equals},
 };
 
 use indoc::indoc;
@@ -144,3 +144,49 @@ eval_and_assert!(
     empty(),
     empty()
 );
+
+eval_and_assert!(
+    enumerate_any_iterable,
+    indoc! {r#"
+        print(list((5..8).enumerate()));
+        print(list("héj".enumerate()));
+        print(list({"a": 1}.enumerate()));
+        print(list(set([7]).enumerate()));
+        print(list([1, 2].enumerate().enumerate()));
+        print((0..).enumerate().nth(3));
+    "#},
+    equals(indoc! {r#"
+        [(0, 5), (1, 6), (2, 7)]
+        [(0, "h"), (1, "é"), (2, "j")]
+        [(0, ("a", 1))]
+        [(0, 7)]
+        [(0, (0, 1)), (1, (1, 2))]
+        (3, 3)
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    enumerate_from_start,
+    indoc! {r#"
+        for i, line in "a\nb".lines().enumerate(1) {
+            print(i, line);
+        };
+        print(list([1, 2].enumerate(-1)));
+    "#},
+    equals(indoc! {r#"
+        1 a
+        2 b
+        [(-1, 1), (0, 2)]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    enumerate_start_must_be_integer,
+    indoc! {r#"
+        [1].enumerate(0.5);
+    "#},
+    empty(),
+    contains("Enumerate start must be an integer, got '0.5'")
+);
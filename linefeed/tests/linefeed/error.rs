@@ -0,0 +1,34 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    error_stops_the_program_with_the_message,
+    indoc! {r#"
+        fn parse_dir(c) {
+          match c {
+            "L" => -1,
+            "R" => 1,
+            _ => error("Unknown direction " + repr(c)),
+          }
+        };
+        print(parse_dir("L"));
+        print(parse_dir("U"));
+        print("unreachable");
+    "#},
+    equals("-1"),
+    contains("Unknown direction \"U\"")
+);
+
+eval_and_assert!(
+    error_points_at_the_call,
+    indoc! {r#"
+        x = 1;
+        if x > 0 { error(x) };
+    "#},
+    empty(),
+    contains("<unknown>:2:12")
+);
@@ -0,0 +1,101 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    eval_returns_the_value_of_the_code,
+    indoc! {r#"
+        print(eval("1 + 2"), eval("[1, (2, 3)]"));
+    "#},
+    equals("3 [1, (2, 3)]"),
+    empty()
+);
+
+eval_and_assert!(
+    eval_has_its_own_variables,
+    indoc! {r#"
+        x = 10;
+        print(eval("x = 5; x * 2"), x);
+    "#},
+    equals("10 10"),
+    empty()
+);
+
+eval_and_assert!(
+    eval_cannot_see_the_callers_variables,
+    indoc! {r#"
+        y = 1;
+        eval("y");
+    "#},
+    empty(),
+    contains("No such variable 'y' in scope")
+);
+
+eval_and_assert!(
+    eval_shares_input_and_output_by_default,
+    indoc! {r#"
+        print(eval(input()));
+    "#},
+    "print(:child); 2 * 21",
+    equals(indoc! {r#"
+        child
+        42
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    eval_options_give_input_and_capture_output,
+    indoc! {r#"
+        opts = {"input": "3 4", "capture": true};
+        print(eval("nums = input().nums(); print(nums); sum(nums)", opts));
+    "#},
+    equals(r#"(7, "[3, 4]\n")"#),
+    empty()
+);
+
+eval_and_assert!(
+    eval_errors_are_reported_at_the_call,
+    indoc! {r#"
+        eval("[] - 1");
+    "#},
+    empty(),
+    contains("Error in eval'd code: Type mismatch")
+);
+
+eval_and_assert!(
+    eval_stops_at_the_instruction_limit,
+    indoc! {r#"
+        opts = {"max_instructions": 1000};
+        eval("while true { 1 }", opts);
+    "#},
+    empty(),
+    contains("Instruction limit of 1000 exceeded")
+);
+
+eval_and_assert!(
+    eval_cannot_return_functions,
+    indoc! {r#"
+        eval("fn f(x) x; [f]");
+    "#},
+    empty(),
+    contains("eval can't return functions")
+);
+
+eval_and_assert!(
+    eval_parses_inputs_that_are_expressions,
+    indoc! {r#"
+        packets = [eval(line) for line in input().lines() if line != ""];
+        print(packets);
+        print(packets[1][1][0] + packets[2][0]);
+    "#},
+    "[1,[2,[3,4]],[]]\n\n[[10],[20,30]]\n[5]\n",
+    equals(indoc! {r#"
+        [[1, [2, [3, 4]], []], [[10], [20, 30]], [5]]
+        25
+    "#}),
+    empty()
+);
@@ -0,0 +1,75 @@
+use indoc::indoc;
+use linefeed::{EXIT_FAILURE, EXIT_SUCCESS};
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+    run_program_with_status,
+};
+
+eval_and_assert!(
+    exit_stops_the_program_after_its_output,
+    indoc! {r#"
+        print("before");
+        exit();
+        print("after");
+    "#},
+    equals("before"),
+    empty()
+);
+
+eval_and_assert!(
+    exit_rejects_codes_that_are_not_integers,
+    indoc! {r#"
+        exit("1");
+    "#},
+    empty(),
+    contains("exit expects an integer exit code, got \"1\"")
+);
+
+#[test]
+fn exit_code_is_the_status_of_the_run() {
+    let src = indoc! {r#"
+        fn check(line) {
+          if line == "stop" {
+            exit(3);
+          };
+          print(line);
+        };
+        for line in input().lines() {
+          check(line);
+        };
+        print("done");
+    "#};
+
+    let (stdout, stderr, status) = run_program_with_status(src, "go\nstop\nnever\n".as_bytes());
+    assert_eq!(status, 3);
+    assert_eq!(stdout, "go\n");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn exit_defaults_to_success() {
+    let (stdout, stderr, status) = run_program_with_status("exit(); [] - 1;", "".as_bytes());
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn exit_is_not_caught_by_try_builtins_or_eval() {
+    let (stdout, stderr, status) = run_program_with_status(
+        r#"print(try_num(eval("exit(2)"))); print("after");"#,
+        "".as_bytes(),
+    );
+    assert_eq!(status, 2);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn exit_with_failure_code_prints_no_error() {
+    let (_, stderr, status) = run_program_with_status("exit(1);", "".as_bytes());
+    assert_eq!(status, EXIT_FAILURE);
+    assert_eq!(stderr, "");
+}
@@ -0,0 +1,102 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    format_fills_fields_in_order,
+    indoc::indoc! {r#"
+        print("{} + {} = {}".format(1, 2, 1 + 2));
+        print("{1} {0} {1}".format("a", "b"));
+        print("[{}] {}".format([1, 2], null));
+        print("no fields".format());
+    "#},
+    equals(indoc::indoc! {r#"
+        1 + 2 = 3
+        b a b
+        [[1, 2]] null
+        no fields
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    format_width_and_alignment,
+    indoc::indoc! {r#"
+        print("[{:5}]".format(42));
+        print("[{:5}]".format("ab"));
+        print("[{:<5}]".format(42));
+        print("[{:>5}]".format("ab"));
+        print("[{:^6}]".format("ab"));
+        print("[{:*^7}]".format("ab"));
+        print("[{:2}]".format("longer"));
+    "#},
+    equals(indoc::indoc! {r#"
+        [   42]
+        [ab   ]
+        [42   ]
+        [   ab]
+        [  ab  ]
+        [**ab***]
+        [longer]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    format_zero_padding_and_precision,
+    indoc::indoc! {r#"
+        print("{:03}".format(7));
+        print("{:05}".format(-42));
+        print("{:.2}".format(3.14159));
+        print("{:.2}".format(2));
+        print("{:8.3}|".format(-1.5));
+        print("{:08.1}".format(2.25));
+        print("{:.3}".format("abcdef"));
+    "#},
+    equals(indoc::indoc! {r#"
+        007
+        -0042
+        3.14
+        2.00
+          -1.500|
+        000002.2
+        abc
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    format_escapes_braces,
+    r#"print("{{{}}}".format(1), "}}{{".format());"#,
+    equals("{1} }{"),
+    empty()
+);
+
+eval_and_assert!(
+    format_with_too_few_arguments,
+    r#""{} {}".format(1);"#,
+    empty(),
+    contains("Format string refers to argument 1, but got 1 argument")
+);
+
+eval_and_assert!(
+    format_with_invalid_spec,
+    r#""{:x5}".format(1);"#,
+    empty(),
+    contains("Invalid format spec \"x5\"")
+);
+
+eval_and_assert!(
+    format_with_unclosed_field,
+    r#""{:5".format(1);"#,
+    empty(),
+    contains("Unclosed '{' in format string")
+);
+
+eval_and_assert!(
+    format_with_unmatched_brace,
+    r#""a } b".format();"#,
+    empty(),
+    contains("Unmatched '}' in format string")
+);
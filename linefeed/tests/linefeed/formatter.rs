@@ -0,0 +1,103 @@
+use indoc::indoc;
+use linefeed::formatter::format;
+
+#[test]
+fn indents_blocks_and_spaces_operators() {
+    let src = indoc! {"
+        total=0;
+        for i in 0 .. 10 {
+        if i==7 {break};
+            total+=i;
+        };
+        print( total );
+    "};
+
+    assert_eq!(
+        format(src).unwrap(),
+        indoc! {"
+            total = 0;
+            for i in 0..10 {
+              if i == 7 { break };
+              total += i;
+            };
+            print(total);
+        "}
+    );
+}
+
+#[test]
+fn keeps_comments_and_collapses_blank_lines() {
+    let src = indoc! {"
+        # Sums the even numbers
+        xs = [1,2,3,4]; # the input
+
+
+
+        evens = xs.filter(fn(x) x%2==0);
+        print(evens.sum());
+    "};
+
+    assert_eq!(
+        format(src).unwrap(),
+        indoc! {"
+            # Sums the even numbers
+            xs = [1, 2, 3, 4]; # the input
+
+            evens = xs.filter(fn (x) x % 2 == 0);
+            print(evens.sum());
+        "}
+    );
+}
+
+#[test]
+fn puts_statements_and_match_arms_on_their_own_lines() {
+    let src = indoc! {r#"
+        x= -1;y=xs?.len()??0;z=xs[1..=-x];
+        fn describe(x) {
+        match x {
+        1 => "one",
+        other => "many",
+        }
+        };
+        grid = [
+        [1, 2],
+        [3, 4],
+        ];
+    "#};
+
+    assert_eq!(
+        format(src).unwrap(),
+        indoc! {r#"
+            x = -1;
+            y = xs?.len() ?? 0;
+            z = xs[1..=-x];
+            fn describe(x) {
+              match x {
+                1 => "one",
+                other => "many",
+              }
+            };
+            grid = [
+              [1, 2],
+              [3, 4],
+            ];
+        "#}
+    );
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let src = indoc! {r#"
+        fn fib(n) { if n<2 { n } else { fib(n-1)+fib(n-2) } };
+        counts = {"a": 1, "b": 2}; # a map, not a block
+        while false {}
+    "#};
+
+    let formatted = format(src).unwrap();
+    assert_eq!(format(&formatted).unwrap(), formatted);
+}
+
+#[test]
+fn programs_that_dont_parse_are_left_alone() {
+    assert_eq!(format("x = (1 +"), None);
+}
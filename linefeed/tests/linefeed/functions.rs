@@ -2,7 +2,7 @@ use indoc::indoc;
 
 use crate::helpers::{
     eval_and_assert,
-    output::{empty, equals},
+    output::{contains, empty, equals},
 };
 
 eval_and_assert!(
@@ -39,3 +39,80 @@ eval_and_assert!(
     "#}),
     empty()
 );
+
+eval_and_assert!(
+    deep_recursion,
+    indoc! {r#"
+        fn depth(n) {
+            if n == 0 {
+                0
+            } else {
+                1 + depth(n - 1)
+            }
+        };
+        print(depth(100000));
+        print(map(fn (x) depth(x), [1, 2, 3]));
+    "#},
+    equals(indoc! {r#"
+        100000
+        [1, 2, 3]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    default_arguments_fill_in_for_left_out_ones,
+    indoc! {r#"
+        fn f(a, b = 10, c = a + b) (a, b, c);
+        print(f(1));
+        print(f(1, 2));
+        print(f(1, 2, 3));
+        print(f(1, 2, null));
+    "#},
+    equals(indoc! {r#"
+        (1, 10, 11)
+        (1, 2, 3)
+        (1, 2, 3)
+        (1, 2, null)
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    default_arguments_are_evaluated_at_call_time,
+    indoc! {r#"
+        fn push(x, xs = []) { xs.append(x); xs };
+        print(push(1), push(2));
+        n = 1;
+        g = fn(x = n) x;
+        n = 2;
+        print(g());
+        memoized fn sum_to(n, acc = 0) { if n == 0 { acc } else { sum_to(n - 1, acc + n) } };
+        print(sum_to(10));
+    "#},
+    equals(indoc! {r#"
+        [1] [2]
+        2
+        55
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    calls_must_give_arguments_without_defaults,
+    indoc! {r#"
+        fn f(a, b = 2) a + b;
+        f();
+    "#},
+    empty(),
+    contains("Expected 1 to 2 arguments, got 0")
+);
+
+eval_and_assert!(
+    default_arguments_must_come_last,
+    indoc! {r#"
+        fn f(a = 1, b) a + b;
+    "#},
+    empty(),
+    contains("Arguments without a default value must come before those with one")
+);
@@ -0,0 +1,70 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    bfs_finds_shortest_path,
+    indoc! {r##"
+        g = grid(["..#", ".##", "..."]);
+        fn open(p) [q for q in g.neighbors4(p) if g[q] == "."];
+        print(bfs((0, 0), open, (2, 2)));
+        print(bfs((0, 0), open, fn (p) p[1] == 1));
+        print(bfs((0, 0), open, (2, 0)));
+    "##},
+    equals(indoc! {r##"
+        [(0, 0), (0, 1), (0, 2), (1, 2), (2, 2)]
+        [(0, 0), (0, 1)]
+        null
+    "##}),
+    empty()
+);
+
+eval_and_assert!(
+    dijkstra_finds_cheapest_path,
+    indoc! {r#"
+        edges = {
+            "a": [("b", 7), ("c", 2)],
+            "b": [("d", 1)],
+            "c": [("b", 3), ("d", 8)],
+            "d": [],
+        };
+        print(dijkstra("a", fn (n) edges[n], "d"));
+        print(dijkstra("d", fn (n) edges[n], "a"));
+        print(dijkstra("a", fn (n) edges[n], fn (n) n == "a"));
+    "#},
+    equals(indoc! {r#"
+        (6, ["a", "c", "b", "d"])
+        null
+        (0, ["a"])
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    a_star_uses_heuristic,
+    indoc! {r#"
+        visited = [];
+        fn step(p) {
+            visited.append(p);
+            [(p + d, 1) for d in [(1, 0), (0, 1), (-1, 0), (0, -1)]]
+        };
+        (cost, path) = a_star((0, 0), step, (3, 3), fn (p) manhattan(p, (3, 3)));
+        print(cost, path.len(), visited.len() < 20);
+    "#},
+    equals(indoc! {r#"
+        6 7 true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    dijkstra_needs_cost_pairs,
+    indoc! {r#"
+        dijkstra(1, fn (n) [n + 1], 5);
+    "#},
+    empty(),
+    contains("dijkstra expects neighbors as (node, cost) pairs, got 2")
+);
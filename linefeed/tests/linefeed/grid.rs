@@ -0,0 +1,134 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    to_map_keys_cells_by_position,
+    indoc! {r##"
+        m = ["#.", ".#"].to_map();
+        print(m[(0, 0)], m[(1, 0)], m[(1, 1)], m.len());
+        n = [[1, 2], [3, 4]].to_map();
+        print(n[(0, 1)], n[(1, 0)]);
+    "##},
+    equals(indoc! {r##"
+        # . # 4
+        3 2
+    "##}),
+    empty()
+);
+
+eval_and_assert!(
+    map_to_grid_infers_bounds,
+    indoc! {r##"
+        sparse = map([((2, -1), "#"), ((4, 0), "#")]);
+        print(map_to_grid(sparse, "."));
+        print(map_to_grid(sparse));
+        empty = map([]);
+        print(map_to_grid(empty, "."));
+    "##},
+    equals(indoc! {r##"
+        [["#", ".", "."], [".", ".", "#"]]
+        [["#", null, null], [null, null, "#"]]
+        []
+    "##}),
+    empty()
+);
+
+eval_and_assert!(
+    grid_round_trips_through_map,
+    indoc! {r##"
+        m = ["#..", ".#."].to_map();
+        m[(2, 1)] = "@";
+        print(map_to_grid(m).to_strings());
+        print([[1, 2], "ab", [null]].to_strings());
+    "##},
+    equals(indoc! {r##"
+        ["#..", ".#@"]
+        ["12", "ab", "null"]
+    "##}),
+    empty()
+);
+
+eval_and_assert!(
+    map_to_grid_needs_position_keys,
+    indoc! {r##"
+        m = map([("a", 1)]);
+        map_to_grid(m);
+    "##},
+    empty(),
+    contains("keys must be integer (x, y) positions")
+);
+
+eval_and_assert!(
+    grid_is_indexed_by_position,
+    indoc! {r##"
+        g = grid(["#..", ".#@"]);
+        print(g.width(), g.height(), g.len());
+        print(g[(0, 0)], g[(2, 1)], g?[(3, 0)]);
+        g[(1, 0)] = "x";
+        print(g);
+    "##},
+    equals(indoc! {r##"
+        3 2 6
+        # @ null
+        #x.
+        .#@
+    "##}),
+    empty()
+);
+
+eval_and_assert!(
+    grid_neighbors_stay_inside,
+    indoc! {r##"
+        g = grid([[1, 2, 3], [4, 5, 6]]);
+        print(g.neighbors4((0, 0)));
+        print(g.neighbors4((1, 1)));
+        print(g.neighbors8((2, 0)));
+        print([g[p] for p in g.neighbors8((1, 0))]);
+    "##},
+    equals(indoc! {r##"
+        [(1, 0), (0, 1)]
+        [(1, 0), (0, 1), (2, 1)]
+        [(1, 0), (1, 1), (2, 1)]
+        [1, 3, 4, 5, 6]
+    "##}),
+    empty()
+);
+
+eval_and_assert!(
+    grid_iterates_over_positions_and_values,
+    indoc! {r##"
+        g = grid(["ab", "cd"]);
+        for pos, val in g {
+            print(pos, val);
+        };
+    "##},
+    equals(indoc! {r##"
+        (0, 0) a
+        (1, 0) b
+        (0, 1) c
+        (1, 1) d
+    "##}),
+    empty()
+);
+
+eval_and_assert!(
+    grid_rows_must_have_same_length,
+    indoc! {r##"
+        grid(["ab", "c"]);
+    "##},
+    empty(),
+    contains("rows must have the same length, got 2 and 1")
+);
+
+eval_and_assert!(
+    grid_index_out_of_bounds,
+    indoc! {r##"
+        grid(["ab"])[(0, 1)];
+    "##},
+    empty(),
+    contains("Index 1 out of bounds, length is 1")
+);
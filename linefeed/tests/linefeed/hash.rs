@@ -0,0 +1,60 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    md5_of_known_strings,
+    indoc::indoc! {r#"
+        print(md5(""));
+        print(md5("abc"));
+        print(md5("The quick brown fox jumps over the lazy dog"));
+        print(md5("abcdef609043"));
+    "#},
+    equals(indoc::indoc! {r#"
+        d41d8cd98f00b204e9800998ecf8427e
+        900150983cd24fb0d6963f7d28e17f72
+        9e107d9d372bb6826bd81d3542a419d6
+        000001dbbfa3a5c83a2d506429c7b00e
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    sha256_of_known_strings,
+    indoc::indoc! {r#"
+        print(sha256(""));
+        print(sha256("abc"));
+        print(sha256("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"));
+    "#},
+    equals(indoc::indoc! {r#"
+        e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855
+        ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad
+        248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    md5_of_long_and_unicode_strings,
+    indoc::indoc! {r#"
+        s = "";
+        for i in 0..1000 {
+          s = s + "a";
+        };
+        print(md5(s));
+        print(md5("æøå"));
+    "#},
+    equals(indoc::indoc! {r#"
+        cabe45dcc9ae5b66ba86600cca6b8ba8
+        93c0abe43cfa5d4f4fbdd46694442182
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    hash_expects_a_string,
+    "md5(5);",
+    empty(),
+    contains("md5 expects a string, got 'number'")
+);
@@ -33,13 +33,18 @@ macro_rules! eval_and_assert {
 
 pub(crate) use eval_and_assert;
 
-pub fn run_program(src: &str, mut input: impl Read) -> (String, String) {
+pub fn run_program(src: &str, input: impl Read) -> (String, String) {
+    let (stdout, stderr, _) = run_program_with_status(src, input);
+    (stdout, stderr)
+}
+
+pub fn run_program_with_status(src: &str, mut input: impl Read) -> (String, String, i32) {
     let mut stdout = Vec::new();
     let mut stderr = Vec::new();
 
-    linefeed::run_with_handles(src, &mut input, &mut stdout, &mut stderr);
+    let status = linefeed::run_with_handles(src, &mut input, &mut stdout, &mut stderr);
     let stdout_str = std::str::from_utf8(&stdout).unwrap().to_string();
     let stderr_str = std::str::from_utf8(&stderr).unwrap().to_string();
 
-    (stdout_str, stderr_str)
+    (stdout_str, stderr_str, status)
 }
@@ -29,35 +29,38 @@ impl Output {
             OutputSource::StdErr => &self.stderr,
         };
 
-        match assertion {
-            OutputAssertion::Equals(expected) => {
-                assert_eq!(
-                    normalise_string(actual),
-                    normalise_string(&expected),
-                    "{}",
-                    format_failure(source_label, "equals", expected, actual)
-                );
-            }
-            OutputAssertion::Contains(expected) => {
-                assert!(
-                    normalise_string(actual).contains(&normalise_string(&expected)),
-                    "{}",
-                    format_failure(source_label, "contains", expected, actual)
-                );
-            }
-            OutputAssertion::Empty => {
-                assert!(
-                    normalise_string(actual).is_empty(),
-                    "{}",
-                    format_failure(source_label, "empty", "", actual)
-                );
-            }
+        if let Err(failure) = assertion.check(source_label, actual) {
+            panic!("{failure}");
         }
 
         self
     }
 }
 
+impl OutputAssertion {
+    pub fn check(&self, source_label: &str, actual: &str) -> Result<(), String> {
+        let (kind, expected, holds) = match self {
+            OutputAssertion::Equals(expected) => (
+                "equals",
+                expected.as_str(),
+                normalise_string(actual) == normalise_string(expected),
+            ),
+            OutputAssertion::Contains(expected) => (
+                "contains",
+                expected.as_str(),
+                normalise_string(actual).contains(&normalise_string(expected)),
+            ),
+            OutputAssertion::Empty => ("empty", "", normalise_string(actual).is_empty()),
+        };
+
+        if holds {
+            Ok(())
+        } else {
+            Err(format_failure(source_label, kind, expected, actual))
+        }
+    }
+}
+
 fn format_failure(name: &str, kind: &str, expected: impl Display, actual: impl Display) -> String {
     format!(
         "Expected and actual {} differ:\n\n===== EXPECTED ({kind}) =====\n{}\n\n===== ACTUAL =====\n{}\n\n===== END =====\n",
@@ -0,0 +1,67 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    histogram_of_list,
+    indoc! {r#"
+        histogram([1, 3, 2, 3, 3, 1], 6);
+    "#},
+    equals(indoc! {r#"
+        1 | #### 2
+        2 | ## 1
+        3 | ###### 3
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    histogram_of_counter,
+    indoc! {r#"
+        c = counter();
+        for word in ["north", "south", "north", "east"] { c.add(word) };
+        histogram(c, 10);
+    "#},
+    equals(indoc! {r#"
+        east  | ##### 1
+        north | ########## 2
+        south | ##### 1
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    histogram_rejects_invalid_width,
+    indoc! {r#"
+        histogram([1, 2], 0);
+    "#},
+    empty(),
+    contains("Histogram width must be a positive, small integer")
+);
+
+eval_and_assert!(
+    freq_table_orders_by_count,
+    indoc! {r#"
+        freq_table("mississippi");
+    "#},
+    equals(indoc! {r#"
+        value  count      %
+        i          4  36.4%
+        s          4  36.4%
+        p          2  18.2%
+        m          1   9.1%
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    freq_table_of_non_iterable,
+    indoc! {r#"
+        freq_table(5);
+    "#},
+    empty(),
+    contains("Cannot build frequency table of type number")
+);
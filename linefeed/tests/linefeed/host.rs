@@ -0,0 +1,200 @@
+use linefeed::config::{Config, Sandbox};
+
+fn run(src: &str, config: Config) -> (i32, String, String) {
+    let config = Config {
+        deterministic: true,
+        ..config
+    };
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let status = linefeed::run_with_config(src, &config, &b""[..], &mut stdout, &mut stderr);
+    (
+        status,
+        String::from_utf8(stdout).unwrap(),
+        String::from_utf8(stderr).unwrap(),
+    )
+}
+
+#[test]
+fn args_are_passed_to_the_program() {
+    let config = Config {
+        args: vec!["--example".to_string(), "input.txt".to_string()],
+        ..Config::default()
+    };
+    let src = r#"
+        print(args());
+        print(if "--example" in args() { "example" } else { "real" });
+    "#;
+
+    let (status, stdout, _) = run(src, config);
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+    assert_eq!(stdout, "[\"--example\", \"input.txt\"]\nexample\n");
+}
+
+#[test]
+fn args_are_empty_by_default() {
+    let (_, stdout, _) = run("print(args());", Config::default());
+    assert_eq!(stdout, "[]\n");
+}
+
+#[test]
+fn env_reads_variables() {
+    std::env::set_var("LINEFEED_TEST_ENV_VAR", "hello");
+    let src = r#"
+        print(env("LINEFEED_TEST_ENV_VAR"));
+        print(env("LINEFEED_TEST_UNSET_ENV_VAR"));
+    "#;
+
+    let (status, stdout, _) = run(src, Config::default());
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+    assert_eq!(stdout, "hello\nnull\n");
+}
+
+#[test]
+fn env_is_disallowed_by_the_sandbox() {
+    let config = Config {
+        sandbox: Sandbox {
+            env: false,
+            ..Sandbox::default()
+        },
+        ..Config::default()
+    };
+
+    let (status, _, stderr) = run(r#"env("HOME");"#, config);
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains(
+            "Cannot read environment variable \"HOME\", as the sandbox config disallows it"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn fetch_is_disallowed_by_the_sandbox() {
+    let config = Config {
+        sandbox: Sandbox {
+            network: false,
+            ..Sandbox::default()
+        },
+        ..Config::default()
+    };
+
+    let (status, _, stderr) = run(r#"fetch("https://example.com");"#, config);
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains(
+            "Cannot fetch \"https://example.com\", as the sandbox config disallows network access"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn fetch_expects_headers_to_be_a_map() {
+    let (status, _, stderr) = run(r#"fetch("https://example.com", 1);"#, Config::default());
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains("fetch expects the headers to be a map, got 'number'"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn headers_must_be_strings_without_line_breaks() {
+    let (status, _, stderr) = run(
+        r#"http_get("https://example.com", {"Cookie": 1});"#,
+        Config::default(),
+    );
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains(
+            "http_get expects the names and values of headers to be strings, got 'string' and 'number'"
+        ),
+        "{stderr}"
+    );
+
+    let (status, _, stderr) = run(
+        r#"fetch("https://example.com", {"Cookie": "a\nHost: evil.example"});"#,
+        Config::default(),
+    );
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains("fetch can't send the header \"Cookie\", as it contains a line break"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn http_get_is_disallowed_by_the_sandbox() {
+    let config = Config {
+        sandbox: Sandbox {
+            network: false,
+            ..Sandbox::default()
+        },
+        ..Config::default()
+    };
+
+    let (status, _, stderr) = run(r#"http_get("https://example.com", {});"#, config);
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains(
+            "Cannot fetch \"https://example.com\", as the sandbox config disallows network access"
+        ),
+        "{stderr}"
+    );
+}
+
+#[cfg(not(feature = "fetch"))]
+#[test]
+fn fetch_needs_the_feature() {
+    let (status, _, stderr) = run(r#"fetch("https://example.com");"#, Config::default());
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains("as linefeed was built without the \"fetch\" feature"),
+        "{stderr}"
+    );
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_returns_the_body() {
+    let path = std::env::temp_dir().join("linefeed_fetch_test.txt");
+    std::fs::write(&path, "1\n2\n3\n").unwrap();
+    let src = format!(
+        r#"print(fetch("file://{}", {{"Cookie": "session=abc"}}).lines());"#,
+        path.display()
+    );
+
+    let (status, stdout, stderr) = run(&src, Config::default());
+    assert_eq!(status, linefeed::EXIT_SUCCESS, "{stderr}");
+    assert_eq!(stdout, "[\"1\", \"2\", \"3\"]\n");
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn http_get_returns_the_body() {
+    let path = std::env::temp_dir().join("linefeed_http_get_test.txt");
+    std::fs::write(&path, "body").unwrap();
+    let src = format!(
+        r#"print(http_get("file://{}", {{"Cookie": "session=abc"}}));"#,
+        path.display()
+    );
+
+    let (status, stdout, stderr) = run(&src, Config::default());
+    assert_eq!(status, linefeed::EXIT_SUCCESS, "{stderr}");
+    assert_eq!(stdout, "body\n");
+}
+
+#[cfg(feature = "fetch")]
+#[test]
+fn fetch_reports_failed_requests() {
+    let (status, _, stderr) = run(
+        r#"fetch("file:///nonexistent/linefeed/input.txt");"#,
+        Config::default(),
+    );
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains("Failed to fetch \"file:///nonexistent/linefeed/input.txt\""),
+        "{stderr}"
+    );
+}
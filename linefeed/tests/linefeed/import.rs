@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use indoc::indoc;
+use linefeed::{
+    config::{Config, Sandbox},
+    EXIT_FAILURE, EXIT_SUCCESS,
+};
+
+// Each test gets its own scratch directory so they can run in parallel
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("linefeed-import-{}", std::process::id()))
+        .join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+// Runs the file at `path`, returning its exit status, stdout and stderr
+fn run(path: &Path) -> (i32, String, String) {
+    run_with_config(path, &Config::default())
+}
+
+fn run_with_config(path: &Path, config: &Config) -> (i32, String, String) {
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let status = linefeed::run_file(path, config, std::io::empty(), &mut stdout, &mut stderr);
+
+    let stdout = String::from_utf8(stdout).unwrap();
+    let stderr = String::from_utf8(strip_ansi_escapes::strip(stderr)).unwrap();
+    (status, stdout, stderr)
+}
+
+#[test]
+fn imported_functions_can_be_called() {
+    let dir = scratch_dir("functions");
+    write(
+        &dir.join("utils.lf"),
+        indoc! {"
+            fn double(n) n * 2;
+            fn triple(n) n * 3;
+        "},
+    );
+    write(
+        &dir.join("main.lf"),
+        indoc! {r#"
+            import "utils.lf";
+            print(double(2), triple(2));
+        "#},
+    );
+
+    let (status, stdout, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(stderr, "");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "4 6\n");
+}
+
+#[test]
+fn files_only_see_their_own_variables() {
+    let dir = scratch_dir("scoping");
+    write(
+        &dir.join("counter.lf"),
+        indoc! {"
+            count = 0;
+            fn bump() {
+              count += 1;
+              count
+            };
+            fn uses_x() x;
+        "},
+    );
+    write(
+        &dir.join("main.lf"),
+        indoc! {"
+            x = 10;
+            import counter;
+            bump();
+            print(bump(), count);
+        "},
+    );
+
+    // The imported file can't see `x`, even though it's a global of the importing program
+    let (status, _, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("No such variable 'x' in scope"));
+
+    write(
+        &dir.join("counter.lf"),
+        indoc! {"
+            count = 0;
+            fn bump() {
+              count += 1;
+              count
+            };
+        "},
+    );
+
+    // `count` was copied when importing, while `bump` keeps using the file's own variable
+    let (status, stdout, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(stderr, "");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "2 0\n");
+}
+
+#[test]
+fn imports_are_relative_to_the_importing_file() {
+    let dir = scratch_dir("nested");
+    write(&dir.join("lib/math.lf"), "fn square(n) n * n;\n");
+    write(
+        &dir.join("lib/geometry.lf"),
+        indoc! {r#"
+            import "math.lf";
+            fn area(w, h) w * h;
+        "#},
+    );
+    write(
+        &dir.join("main.lf"),
+        indoc! {r#"
+            import "lib/geometry.lf";
+            print(area(2, 3), square(4));
+        "#},
+    );
+
+    let (status, stdout, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(stderr, "");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "6 16\n");
+}
+
+#[test]
+fn imports_can_be_inside_functions() {
+    let dir = scratch_dir("in_function");
+    write(&dir.join("consts.lf"), "answer = 42;\n");
+    write(
+        &dir.join("main.lf"),
+        indoc! {"
+            fn get() {
+              import consts;
+              answer
+            };
+            print(get());
+        "},
+    );
+
+    let (status, stdout, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(stderr, "");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "42\n");
+}
+
+#[test]
+fn circular_imports_are_rejected() {
+    let dir = scratch_dir("circular");
+    write(&dir.join("a.lf"), "import b;\nfn a() 1;\n");
+    write(&dir.join("b.lf"), "import a;\nfn b() 2;\n");
+    write(&dir.join("main.lf"), "import a;\nprint(a());\n");
+
+    let (status, _, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("Circular import of 'a.lf'"));
+}
+
+#[test]
+fn missing_files_are_reported_at_the_import() {
+    let dir = scratch_dir("missing");
+    write(&dir.join("main.lf"), "import missing;\n");
+
+    let (status, _, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("Failed to read"));
+    assert!(stderr.contains("import missing;"));
+}
+
+#[test]
+fn imports_are_disallowed_by_the_sandbox() {
+    let dir = scratch_dir("sandbox");
+    write(&dir.join("utils.lf"), "fn double(n) n * 2;\n");
+    write(&dir.join("main.lf"), "import utils;\nprint(double(2));\n");
+    let config = Config {
+        sandbox: Sandbox {
+            fs: false,
+            ..Sandbox::default()
+        },
+        ..Config::default()
+    };
+
+    let (status, stdout, stderr) = run_with_config(&dir.join("main.lf"), &config);
+    assert_eq!(status, EXIT_FAILURE);
+    assert_eq!(stdout, "");
+    assert!(
+        stderr.contains(
+            "Cannot import \"utils.lf\", as the sandbox config disallows file system access"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn errors_in_imported_files_show_the_imported_source() {
+    let dir = scratch_dir("errors");
+    write(&dir.join("utils.lf"), "fn broken() [] - 1;\n");
+    write(&dir.join("main.lf"), "import utils;\nbroken();\n");
+
+    let (status, _, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("utils.lf:"));
+    assert!(stderr.contains("fn broken() [] - 1;"));
+
+    write(&dir.join("utils.lf"), "fn broken( 1;\n");
+    let (status, _, stderr) = run(&dir.join("main.lf"));
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("utils.lf:"));
+    assert!(stderr.contains("fn broken( 1;"));
+}
@@ -0,0 +1,111 @@
+use indoc::indoc;
+use linefeed::{
+    chumsky::Parser as _,
+    compiler::{Compiler, Instruction},
+    config::Config,
+};
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+// Returns the number of calls left in the compiled program for `src`, and whether any function
+// was inlined
+fn calls_and_inlined(src: &str) -> (usize, bool) {
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+    let program = Compiler::default()
+        .with_opt_level(1)
+        .compile_ir(&ast)
+        .unwrap();
+
+    let calls = program
+        .instructions
+        .iter()
+        .filter(|instruction| matches!(instruction, Instruction::Call(_)))
+        .count();
+    let inlined = program
+        .instructions
+        .iter()
+        .any(|instruction| matches!(instruction, Instruction::EnterInlined(_)));
+    (calls, inlined)
+}
+
+#[test]
+fn small_functions_are_inlined() {
+    let src = indoc! {"
+        fn sq(x) x * x;
+        fn dist(a, b) sq(a - b);
+        total = 0;
+        for i in 0..10 { total += dist(i, 3) + sq(i) };
+    "};
+
+    // Bodies are copied as they were before inlining, so the copy of `dist` still calls `sq`
+    assert_eq!(calls_and_inlined(src), (1, true));
+}
+
+#[test]
+fn recursive_memoized_and_reassigned_functions_are_called() {
+    let recursive = "fn f(n) if n == 0 { 0 } else { f(n - 1) }; f(3)";
+    let memoized = "memoized fn f(n) n + 1; f(3)";
+    let reassigned = "fn f(n) n + 1; f(3); f = fn (n) n - 1; f(3)";
+    let defaults = "fn f(n, m = 1) n + m; f(3)";
+
+    assert_eq!(calls_and_inlined(recursive), (2, false));
+    assert_eq!(calls_and_inlined(memoized), (1, false));
+    assert_eq!(calls_and_inlined(reassigned), (2, false));
+    assert_eq!(calls_and_inlined(defaults), (1, false));
+}
+
+eval_and_assert!(
+    inlined_functions_behave_like_calls,
+    indoc! {r#"
+        fn sign(x) {
+            if x < 0 { return -1 };
+            if x == 0 { return 0 };
+            1
+        };
+        fn sum_to(n) {
+            total = 0;
+            for i in 1..(n + 1) {
+                if i > 100 { break };
+                total += i;
+            };
+            total
+        };
+        fn scale(x, by) x * by;
+
+        out = [];
+        for x in [-5, 0, 7] {
+            y = 10;
+            out.append((sign(x), scale(sum_to(sign(x) + 3), y), y));
+        };
+        print(out);
+    "#},
+    equals(indoc! {r#"
+        [(-1, 30, 10), (0, 60, 10), (1, 100, 10)]
+    "#}),
+    empty()
+);
+
+#[test]
+fn functions_called_before_they_are_declared_are_not_inlined() {
+    let src = "fn g() { f(1) }; g(); fn f(x) { x + 1 }";
+    assert_eq!(calls_and_inlined(src), (2, false));
+
+    for opt_level in [0, 1] {
+        let config = Config {
+            opt_level,
+            ..Config::default()
+        };
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let status =
+            linefeed::run_with_config(src, &config, std::io::empty(), &mut stdout, &mut stderr);
+
+        assert_eq!(status, linefeed::EXIT_FAILURE, "opt_level {opt_level}");
+        contains("Cannot call type uninitialized as a function")
+            .check("stderr", &String::from_utf8(stderr).unwrap())
+            .unwrap_or_else(|err| panic!("opt_level {opt_level}: {err}"));
+    }
+}
@@ -0,0 +1,94 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    nth_indexes_lists_ranges_and_strings,
+    indoc! {r#"
+        xs = [10, 20, 30, 40];
+        print(xs.nth(1), xs.nth(9));
+        print((0..100).nth(42), (10..0).nth(3), (5..).nth(1000), (0..3).nth(3));
+        print("abc".nth(2));
+    "#},
+    equals(indoc! {r#"
+        20 null
+        42 7 1005 null
+        c
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    count_without_argument_counts_items,
+    indoc! {r#"
+        print([1, 2, 1].count(), [1, 2, 1].count(1));
+        print((0..1000000000).count(), (5..0).count(), "abc".count());
+        print(set([1, 2]).count(), (1, 2, 3).count());
+    "#},
+    equals(indoc! {r#"
+        3 2
+        1000000000 5 3
+        2 3
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    terminal_operations_consume_iterators,
+    indoc! {r#"
+        it = [10, 20, 30, 40].enumerate();
+        print(it.nth(1));
+        print(it.nth(0));
+        print(it.count(), it.count());
+        print(it.nth(0));
+        it = "abcd".enumerate();
+        print(it.last());
+        print(it.is_empty());
+    "#},
+    equals(indoc! {r#"
+        (1, 20)
+        (2, 30)
+        1 0
+        null
+        (3, "d")
+        true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    is_empty_does_not_advance_iterators,
+    indoc! {r#"
+        it = [1, 2].enumerate();
+        print(it.is_empty(), it.is_empty(), it.count());
+        print([].is_empty(), "".is_empty(), (3..3).is_empty(), (0..).is_empty());
+    "#},
+    equals(indoc! {r#"
+        false false 2
+        true true true false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    last_of_exhausted_iterator_fails,
+    indoc! {r#"
+        it = [1].enumerate();
+        it.count();
+        it.last();
+    "#},
+    empty(),
+    contains("Cannot get last of empty iterator")
+);
+
+eval_and_assert!(
+    count_of_unbounded_range_fails,
+    indoc! {r#"
+        (0..).count();
+    "#},
+    empty(),
+    contains("Cannot count the items of an unbounded range")
+);
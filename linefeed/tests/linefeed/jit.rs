@@ -0,0 +1,80 @@
+use std::io;
+
+use indoc::indoc;
+use linefeed::{chumsky::Parser as _, compiler::Compiler, vm::BytecodeInterpreter};
+
+// Runs `src` without optimisations, so that no function is inlined away before it gets hot, and
+// returns its output along with the number of functions compiled to native code
+fn run_unoptimized(src: &str) -> (String, usize) {
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+    let program = Compiler::default().with_opt_level(0).compile(&ast).unwrap();
+
+    let mut stdout = Vec::new();
+    let compiled = {
+        let mut vm =
+            BytecodeInterpreter::new(program).with_handles(io::empty(), &mut stdout, io::sink());
+        assert!(vm.run().is_ok());
+        vm.jit_compiled_functions()
+    };
+    (String::from_utf8(stdout).unwrap(), compiled)
+}
+
+#[test]
+fn hot_functions_with_loops_give_the_same_results() {
+    let (stdout, compiled) = run_unoptimized(indoc! {r#"
+        fn sum_to(n) {
+          acc = 0;
+          for i in 0..n {
+            continue if i % 3 == 0;
+            break if i > 50;
+            acc += i;
+          };
+          acc
+        };
+        fn halvings(n, lo = 0) {
+          steps = 0;
+          while n > lo {
+            if n % 2 == 0 { n = n // 2 } else { n -= 1 };
+            steps += 1;
+          };
+          steps
+        };
+        total = 0;
+        for n in 0..2000 { total += sum_to(n % 100) };
+        steps = 0;
+        for n in 1..1500 { steps += halvings(n) };
+        print(total, steps, halvings(1000, 100));
+    "#});
+
+    assert_eq!(stdout.trim_end(), "1127440 20618 5");
+    assert_eq!(compiled, 2);
+}
+
+#[test]
+fn hot_functions_fall_back_on_overflow() {
+    let (stdout, compiled) = run_unoptimized(indoc! {r#"
+        fn sq(x) x * x;
+        total = 0;
+        for i in 0..1500 { total += sq(i) };
+        print(total, sq(3037000500), sq(10000000000));
+    "#});
+
+    assert_eq!(
+        stdout.trim_end(),
+        "1123875250 9223372037000250000 100000000000000000000"
+    );
+    assert_eq!(compiled, 1);
+}
+
+#[test]
+fn hot_functions_fall_back_on_other_values() {
+    let (stdout, compiled) = run_unoptimized(indoc! {r#"
+        fn add(a, b) a + b;
+        for i in 0..1500 { add(i, 1) };
+        print(add("a", "b"), add(1, 2), add([1], [2]), add(0.5, 1));
+    "#});
+
+    assert_eq!(stdout.trim_end(), "ab 3 [1, 2] 1.5");
+    assert_eq!(compiled, 1);
+}
@@ -0,0 +1,132 @@
+use indoc::indoc;
+use linefeed::{
+    chumsky::Parser as _,
+    compiler::{Compiler, Instruction, Label, Program},
+    grammar::ast::Span,
+    vm::{bytecode::Bytecode, BytecodeInterpreter},
+};
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{empty, equals},
+};
+
+fn compile_ir(src: &str) -> Program<Instruction> {
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+    Compiler::default()
+        .with_opt_level(1)
+        .compile_ir(&ast)
+        .unwrap()
+}
+
+fn jump_target(instruction: &Instruction) -> Option<Label> {
+    match instruction {
+        Instruction::Goto(target)
+        | Instruction::IfTrue(target)
+        | Instruction::IfFalse(target)
+        | Instruction::NextIterOrJump(target) => Some(*target),
+        _ => None,
+    }
+}
+
+#[test]
+fn jumps_go_straight_to_their_destination() {
+    let program = compile_ir(indoc! {"
+        for x in 0..10 {
+            if x % 2 == 0 {
+                if x > 4 { print(x) } else { continue }
+            } else {
+                while x > 0 { if x == 3 { break } else { x -= 1 } }
+            }
+        }
+    "});
+    let instructions = &program.instructions;
+
+    // Where execution continues after jumping to `target`
+    let destination = |target: Label| {
+        let at = instructions
+            .iter()
+            .position(
+                |instruction| matches!(instruction, Instruction::Label(label) if *label == target),
+            )
+            .unwrap();
+        instructions[at..]
+            .iter()
+            .find(|instruction| !matches!(instruction, Instruction::Label(_)))
+    };
+
+    for (i, instruction) in instructions.iter().enumerate() {
+        let Some(target) = jump_target(instruction) else {
+            continue;
+        };
+        assert!(!matches!(destination(target), Some(Instruction::Goto(_))));
+
+        let next_labels = instructions[i + 1..]
+            .iter()
+            .take_while(|instruction| matches!(instruction, Instruction::Label(_)));
+        assert!(!next_labels
+            .into_iter()
+            .any(|label| matches!(label, Instruction::Label(label) if *label == target)));
+    }
+}
+
+#[test]
+fn unreachable_code_and_unused_labels_are_removed() {
+    let program = compile_ir("for i in 0..3 { break; print(i) }; if true { 1 } else { 2 }");
+
+    let prints = program
+        .instructions
+        .iter()
+        .filter(|instruction| matches!(instruction, Instruction::StdlibCall(..)))
+        .count();
+    assert_eq!(prints, 0);
+
+    let targets = program
+        .instructions
+        .iter()
+        .filter_map(jump_target)
+        .collect::<Vec<_>>();
+    for instruction in &program.instructions {
+        if let Instruction::Label(label) = instruction {
+            assert!(targets.contains(label), "{label:?} is never jumped to");
+        }
+    }
+}
+
+eval_and_assert!(
+    threaded_jumps_keep_control_flow,
+    indoc! {r#"
+        fn first_over(xs, limit) {
+            for x in xs {
+                if x > limit { return x } else if x < 0 { continue }
+            };
+            null
+        };
+
+        out = [];
+        i = 0;
+        while true {
+            i += 1;
+            if i % 2 == 0 { continue };
+            if i > 7 { break };
+            out.append(first_over([-1, i, i * 2], 3));
+        };
+        print(out);
+    "#},
+    equals(indoc! {r#"
+        [null, 6, 5, 7]
+    "#}),
+    empty()
+);
+
+#[test]
+#[should_panic(expected = "Jump to 5 is outside of the program")]
+fn programs_jumping_out_of_themselves_are_rejected() {
+    // The interpreter fetches instructions without bounds checks, relying on jumps staying inside
+    // the program
+    let mut program = Program::new();
+    program.add_instruction(Bytecode::Goto(5), Span::new(0, 0));
+    program.add_instruction(Bytecode::Stop, Span::new(0, 0));
+    BytecodeInterpreter::new(program);
+}
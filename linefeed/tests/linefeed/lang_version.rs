@@ -0,0 +1,67 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    programs_without_pragma_are_version_one,
+    indoc! {r#"
+        collect = [1, 2];
+        print(collect);
+    "#},
+    equals("[1, 2]"),
+    empty()
+);
+
+eval_and_assert!(
+    version_pragma_is_a_comment,
+    indoc! {r#"
+        #!lang v2
+        print(1);
+    "#},
+    equals("1"),
+    empty()
+);
+
+eval_and_assert!(
+    keywords_of_version_cannot_be_names,
+    indoc! {r#"
+        #!lang v2
+        enum = 1;
+    "#},
+    empty(),
+    contains("'enum' is a keyword in lang v2 and can't be used as a name")
+);
+
+eval_and_assert!(
+    keyword_arguments_are_reported_at_the_argument,
+    indoc! {r#"
+        #!lang v2
+        fn f(a,
+          defer) a;
+    "#},
+    empty(),
+    contains("<unknown>:3:3")
+);
+
+eval_and_assert!(
+    unknown_versions_are_rejected,
+    indoc! {r#"
+        #!lang v9
+        print(1);
+    "#},
+    empty(),
+    contains("Unknown language version 'v9', expected 'v1' or 'v2'")
+);
+
+eval_and_assert!(
+    collect_for_works_in_version_two,
+    indoc! {r#"
+        #!lang v2
+        print(collect for x in 0..3 { x * 3 });
+    "#},
+    equals("[0, 3, 6]"),
+    empty()
+);
@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use linefeed::{
+    config::Config,
+    vm::limits::{parse_byte_size, parse_duration, RunLimits},
+};
+
+fn run_with_limits(src: &str, limits: RunLimits) -> (i32, String) {
+    let config = Config {
+        deterministic: true,
+        limits,
+        ..Config::default()
+    };
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let status = linefeed::run_with_config(src, &config, &b""[..], &mut stdout, &mut stderr);
+    (status, String::from_utf8(stderr).unwrap())
+}
+
+#[test]
+fn time_limit_stops_infinite_loop() {
+    let limits = RunLimits {
+        time: Some(Duration::from_millis(100)),
+        ..RunLimits::default()
+    };
+
+    let start = Instant::now();
+    let (status, stderr) = run_with_limits("i = 0; while true { i += 1; };", limits);
+
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(stderr.contains("Time limit of 100ms exceeded"), "{stderr}");
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn programs_within_limits_run_normally() {
+    let limits = RunLimits {
+        instructions: Some(100_000),
+        time: Some(Duration::from_secs(60)),
+        memory: Some(1 << 30),
+    };
+
+    let (status, stderr) = run_with_limits("print(sum(0..100));", limits);
+    assert_eq!(status, linefeed::EXIT_SUCCESS, "{stderr}");
+}
+
+#[test]
+fn instruction_limit_applies_to_runs() {
+    let limits = RunLimits {
+        instructions: Some(500),
+        ..RunLimits::default()
+    };
+
+    let (status, stderr) = run_with_limits("while true {};", limits);
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(
+        stderr.contains("Instruction limit of 500 exceeded"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn limits_apply_to_callbacks_of_builtins() {
+    let endless = "map([1], fn(x) { while true {}; x });";
+    let growing = "map([1], fn(x) { xs = []; while true { xs.append(x); }; x });";
+
+    let cases = [
+        (
+            endless,
+            RunLimits {
+                instructions: Some(500),
+                ..RunLimits::default()
+            },
+            "Instruction limit of 500 exceeded",
+        ),
+        (
+            endless,
+            RunLimits {
+                time: Some(Duration::from_millis(100)),
+                ..RunLimits::default()
+            },
+            "Time limit of 100ms exceeded",
+        ),
+        (
+            growing,
+            RunLimits {
+                memory: Some(1 << 20),
+                ..RunLimits::default()
+            },
+            "Memory limit of 1 MB exceeded",
+        ),
+    ];
+
+    for (src, limits, expected) in cases {
+        let (status, stderr) = run_with_limits(src, limits);
+        assert_eq!(status, linefeed::EXIT_FAILURE);
+        assert!(stderr.contains(expected), "{stderr}");
+    }
+}
+
+#[test]
+fn durations_are_parsed_with_units() {
+    assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+    assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+    assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+    assert_eq!(
+        parse_duration("5 days"),
+        Err("Invalid duration '5 days', expected e.g. '500ms', '5s' or '2m'".to_string())
+    );
+}
+
+#[test]
+fn durations_too_long_to_represent_are_rejected() {
+    assert_eq!(
+        parse_duration("99999999999999999999"),
+        Err("Duration '99999999999999999999' is too long".to_string())
+    );
+
+    // Deadlines beyond what the clock can represent are never reached
+    let limits = RunLimits {
+        time: Some(Duration::MAX),
+        ..RunLimits::default()
+    };
+    let (status, stderr) = run_with_limits("print(1);", limits);
+    assert_eq!(status, linefeed::EXIT_SUCCESS, "{stderr}");
+}
+
+#[test]
+fn byte_sizes_are_parsed_with_units() {
+    assert_eq!(parse_byte_size("100"), Ok(100));
+    assert_eq!(parse_byte_size("64k"), Ok(64 << 10));
+    assert_eq!(parse_byte_size("512MB"), Ok(512 << 20));
+    assert_eq!(parse_byte_size("2G"), Ok(2 << 30));
+    assert_eq!(
+        parse_byte_size("lots"),
+        Err("Invalid size 'lots', expected e.g. '64MB' or '2GB'".to_string())
+    );
+}
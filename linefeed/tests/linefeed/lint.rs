@@ -0,0 +1,261 @@
+use indoc::indoc;
+use linefeed::{
+    chumsky::Parser as _,
+    config::Config,
+    lint::{lint, Lint, LintLevel},
+    CheckOptions,
+};
+
+use crate::helpers::output::contains;
+
+// Returns the name and 1-indexed line of every lint reported for `src`
+fn lints(src: &str, config: &Config) -> Vec<(&'static str, usize)> {
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+
+    lint(src, &ast, config)
+        .into_iter()
+        .map(|d| (d.lint.name(), src[..d.span.start].matches('\n').count() + 1))
+        .collect()
+}
+
+#[test]
+fn reports_each_rule() {
+    let src = indoc! {"
+        x = 5;
+        x + 1;
+        if x = 3 { print(x) };
+        while false { print(1) };
+        for i in 0..3 {
+          for i in 0..2 { print(i) };
+        };
+    "};
+
+    assert_eq!(
+        lints(src, &Config::default()),
+        vec![
+            ("unused_pure_expression", 2),
+            ("assignment_in_condition", 3),
+            ("constant_condition", 4),
+            ("shadowed_loop_variable", 6),
+        ]
+    );
+}
+
+#[test]
+fn idiomatic_code_is_not_reported() {
+    let src = indoc! {"
+        fn last(xs) { n = xs.len(); xs[n - 1] };
+        while true { break };
+        for i in 0..3 {
+          fn inner() { for i in 0..2 { print(i) } };
+          inner();
+        };
+        print([x for x in 0..3 if x > 0]);
+    "};
+
+    assert_eq!(lints(src, &Config::default()), vec![]);
+}
+
+#[test]
+fn inline_comments_suppress_lints() {
+    let src = indoc! {"
+        # lint: allow(constant_condition)
+        if true { print(1) };
+        if false { print(2) }; # lint: allow(constant_condition, unused_pure_expression)
+        if null { print(3) };
+    "};
+
+    assert_eq!(
+        lints(src, &Config::default()),
+        vec![("constant_condition", 4)]
+    );
+}
+
+#[test]
+fn levels_come_from_config() {
+    let mut config = Config::default();
+    config
+        .apply_toml(
+            indoc! {r#"
+                [lints]
+                unused_pure_expression = "allow"
+                constant_condition = "deny"
+            "#},
+            std::path::Path::new("."),
+        )
+        .unwrap();
+
+    assert_eq!(config.lint_level(Lint::ConstantCondition), LintLevel::Deny);
+    assert_eq!(
+        lints("1; if true { 2 }", &config),
+        vec![("constant_condition", 1)]
+    );
+
+    let mut stderr = Vec::new();
+    let options = CheckOptions {
+        lints: true,
+        ..Default::default()
+    };
+    let status = linefeed::check("1; if true { 2 }", &config, options, &mut stderr);
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    contains("The condition of this `if` is always the same")
+        .check("stderr", &String::from_utf8(stderr).unwrap())
+        .unwrap_or_else(|err| panic!("{err}"));
+}
+
+#[test]
+fn unknown_lints_in_config_are_rejected() {
+    let err = Config::default()
+        .apply_toml(
+            "[lints]\nno_such_lint = \"warn\"\n",
+            std::path::Path::new("."),
+        )
+        .unwrap_err();
+    assert_eq!(err, "unknown lint `no_such_lint`");
+}
+
+#[test]
+fn check_passes_with_warnings() {
+    let mut stderr = Vec::new();
+    let options = CheckOptions {
+        lints: true,
+        ..Default::default()
+    };
+    let status = linefeed::check(
+        "x = 1; x; print(x)",
+        &Config::default(),
+        options,
+        &mut stderr,
+    );
+
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+    contains("The result of this expression is never used")
+        .check("stderr", &String::from_utf8(stderr).unwrap())
+        .unwrap_or_else(|err| panic!("{err}"));
+}
+
+#[test]
+fn names_that_are_keywords_in_later_versions_are_reported() {
+    let src = indoc! {"
+        enum = 1;
+        print(enum);
+        defer2 = 2;
+    "};
+
+    assert_eq!(
+        lints(src, &Config::default()),
+        vec![("future_keyword", 1), ("future_keyword", 2)]
+    );
+    assert_eq!(
+        lints(&format!("#!lang v1\n{src}"), &Config::default()).len(),
+        2
+    );
+}
+
+#[test]
+fn future_keywords_are_reported_at_the_argument() {
+    let src = "fn f(a, enum = 1) a;";
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+
+    let reported = lint(src, &ast, &Config::default())
+        .into_iter()
+        .map(|d| (d.lint.name(), &src[d.span.start..d.span.end]))
+        .collect::<Vec<_>>();
+    assert_eq!(reported, [("future_keyword", "enum")]);
+}
+
+#[test]
+fn unused_variables_and_unreachable_code_are_reported() {
+    let src = indoc! {"
+        fn f(xs) {
+          total = 0;
+          _ignored = 1;
+          for x in xs {
+            continue;
+            print(x);
+          };
+          return xs;
+          print(total);
+          total
+        };
+        fn g() { unused = 1; unused = 2; 3 };
+        print(f([1]), g());
+    "};
+
+    assert_eq!(
+        lints(src, &Config::default()),
+        vec![
+            ("unreachable_code", 6),
+            ("unreachable_code", 9),
+            ("unused_variable", 12),
+        ]
+    );
+}
+
+#[test]
+fn unused_variables_are_resolved_per_function() {
+    let src = indoc! {"
+        fn f() { x = 1; 2 };
+        fn g(x) { x * 2 };
+        fn counter() {
+          count = 0;
+          fn () { count += 1; count }
+        };
+        print(f(), g(1), counter()());
+    "};
+
+    assert_eq!(lints(src, &Config::default()), vec![("unused_variable", 1)]);
+}
+
+#[test]
+fn running_a_program_warns() {
+    let src = indoc! {"
+        fn f() { x = 1; 2 };
+        print(f());
+    "};
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let status = linefeed::run_with_config(
+        src,
+        &Config::default(),
+        std::io::empty(),
+        &mut stdout,
+        &mut stderr,
+    );
+
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+    assert_eq!(String::from_utf8(stdout).unwrap(), "2\n");
+    contains("Variable 'x' is assigned but never read")
+        .check("stderr", &String::from_utf8(stderr).unwrap())
+        .unwrap_or_else(|err| panic!("{err}"));
+}
+
+#[test]
+fn running_a_program_fails_on_denied_warnings() {
+    let mut config = Config::default();
+    config
+        .apply_toml(
+            "[lints]\nunreachable_code = \"deny\"\n",
+            std::path::Path::new("."),
+        )
+        .unwrap();
+
+    let src = indoc! {"
+        fn f() { return 1; print(2) };
+        print(f());
+    "};
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let status =
+        linefeed::run_with_config(src, &config, std::io::empty(), &mut stdout, &mut stderr);
+
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(stdout.is_empty());
+    contains("This code is never run")
+        .check("stderr", &String::from_utf8(stderr).unwrap())
+        .unwrap_or_else(|err| panic!("{err}"));
+}
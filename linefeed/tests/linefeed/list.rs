@@ -200,3 +200,10 @@ eval_and_assert!(
     equals("[]"),
     empty()
 );
+
+eval_and_assert!(
+    list_from_ranges_not_starting_at_zero,
+    r#"print(list(10..12), list(5..2), list(-3..0));"#,
+    equals("[10, 11] [5, 4, 3] [-3, -2, -1]"),
+    empty()
+);
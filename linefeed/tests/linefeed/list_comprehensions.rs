@@ -46,3 +46,44 @@ eval_and_assert!(
     equals("6"),
     empty()
 );
+
+eval_and_assert!(
+    collect_for_evaluates_to_body_values,
+    indoc! {r#"
+        squares = collect for x in 0..5 {
+            y = x * x;
+            y + 1
+        };
+        print(squares);
+        print(collect for (a, b) in [(1, 2), (3, 4)] { a * b });
+    "#},
+    equals(indoc! {r#"
+        [1, 2, 5, 10, 17]
+        [2, 12]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    collect_for_skips_continued_iterations,
+    indoc! {r#"
+        picked = collect for (i, x) in [5, 6, 7, 8, 10].enumerate() {
+            continue if x % 2 == 1;
+            break if i > 3;
+            x
+        };
+        print(picked);
+    "#},
+    equals("[6, 8]"),
+    empty()
+);
+
+eval_and_assert!(
+    collect_is_still_a_name_without_for,
+    indoc! {r#"
+        collect = [1, 2];
+        print(collect);
+    "#},
+    equals("[1, 2]"),
+    empty()
+);
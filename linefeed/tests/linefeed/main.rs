@@ -2,33 +2,96 @@ pub mod helpers;
 
 mod advent_of_code_2020;
 mod all_any;
+mod batch;
 mod big_ints;
 mod bitwise;
+mod capacity;
+mod chunk_by;
+mod closures;
+mod combinatorics;
 mod comparison;
+mod config;
+mod consecutive;
+mod constants;
+mod conversions;
+mod copy;
 mod count;
 mod counter;
+mod csv;
+mod cycles;
+mod datetime;
+mod defer;
+mod deque;
 mod destructure;
+mod diff_run;
+mod docs;
+mod emit_dot;
+mod enum_;
 mod enumerate;
+mod error;
+mod eval;
+mod exit;
 mod for_loops;
+mod format;
+mod formatter;
 mod functions;
+mod graph_search;
+mod grid;
+mod hash;
+mod histogram;
+mod host;
+mod import;
 mod in_;
+mod inlining;
+mod iterators;
+#[cfg(feature = "jit")]
+mod jit;
+mod jump_threading;
+mod lang_version;
+mod limits;
+mod lint;
 mod list;
 mod list_comprehensions;
 mod logical;
 mod map;
+mod map_filter_reduce;
 mod map_with_default;
 mod match_;
 mod math;
 mod memoized;
 mod method;
+mod null_coalescing;
 mod postfix_control_flow;
 mod print;
+mod python;
+mod random;
+mod rational;
+mod read_line;
 mod regex;
+mod repl;
 mod return_;
 mod scope;
 mod set;
+mod snippets;
 mod sort;
+mod spec;
+mod spread;
 mod string;
+mod symbol;
+mod test_runner;
+mod try_;
 mod tuple;
 mod tuple2d;
+mod type_check;
+mod types;
+mod vector;
+mod vm_state;
+#[cfg(feature = "wasm")]
+mod wasm;
 mod while_loops;
+
+use linefeed::alloc_stats::CountingAllocator;
+
+// Memory limits and allocation counts are only measured with the counting allocator
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
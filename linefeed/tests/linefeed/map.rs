@@ -325,3 +325,18 @@ eval_and_assert!(
     equals("3"),
     empty()
 );
+
+eval_and_assert!(
+    map_get_or_falls_back_to_default,
+    indoc! {r#"
+        map = {"a": 1};
+        print(map.get_or("a", 0), map.get_or("b", 0));
+        counts = defaultmap(5);
+        print(counts.get_or("x", 0), counts.len());
+    "#},
+    equals(indoc! {r#"
+        1 0
+        0 0
+    "#}),
+    empty()
+);
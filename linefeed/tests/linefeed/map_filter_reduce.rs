@@ -0,0 +1,85 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    map_filter_reduce_call_functions,
+    indoc! {r#"
+        xs = [1, 2, 3, 4];
+        print(map(fn (x) x * x, xs));
+        print(filter(fn (x) x % 2 == 0, 1..10));
+        print(reduce(fn (acc, x) acc + x, xs, 0));
+        print(reduce(fn (acc, c) c + acc, "abc", ""));
+        print(map(fn (x) x, []), filter(fn (x) true, []), reduce(fn (acc, x) x, [], null));
+    "#},
+    equals(indoc! {r#"
+        [1, 4, 9, 16]
+        [2, 4, 6, 8]
+        10
+        cba
+        [] [] null
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    map_filter_reduce_nest_and_capture,
+    indoc! {r#"
+        offset = 10;
+        grid = [[1, 2], [3, 4]];
+        print(map(fn (row) map(fn (x) x + offset, row), grid));
+        print(reduce(fn (acc, row) acc + reduce(fn (a, x) a * x, row, 1), grid, 0));
+        print(map(fn (x) -x, filter(fn (x) x > offset / 5, [1, 2, 3, 4])));
+    "#},
+    equals(indoc! {r#"
+        [[11, 12], [13, 14]]
+        14
+        [-3, -4]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    map_with_one_argument_still_creates_map,
+    indoc! {r#"
+        print(map([("a", 1)]));
+    "#},
+    equals(indoc! {r#"
+        {"a": 1}
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    map_filter_reduce_require_functions,
+    indoc! {r#"
+        filter([1, 2], fn (x) x);
+    "#},
+    empty(),
+    contains("Expected function as filter predicate, got list")
+);
+
+eval_and_assert!(
+    functions_defined_by_the_program_shadow_builtins,
+    indoc! {r#"
+        fn filter(xs, f) {
+            [x for x in xs if f(x)]
+        };
+        fn uses_filter() {
+            filter([1, 2, 3, 4], fn (x) x > 2)
+        };
+        print(filter([1, 2, 3], fn (x) x != 2));
+        print(uses_filter());
+        sum = sum([1, 2, 3]);
+        print(sum);
+    "#},
+    equals(indoc! {r#"
+        [1, 3]
+        [3, 4]
+        6
+    "#}),
+    empty()
+);
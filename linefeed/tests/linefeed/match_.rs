@@ -117,3 +117,167 @@ eval_and_assert!(
     equals("5"),
     empty()
 );
+
+eval_and_assert!(
+    match_as_argument_leaves_only_its_value,
+    indoc! {r#"
+        print("value:", match 2 { 1 => "one", 2 => "two" }, match 3 { n => n * 2 });
+    "#},
+    equals("value: two 6"),
+    empty()
+);
+
+eval_and_assert!(
+    match_regex_arms_bind_named_groups,
+    indoc! {r#"
+        fn step(line) {
+            match line {
+                r/^move (?<n>\d+) from (?<from>\d+)/n => n + from,
+                r/^noop/ => "nothing",
+                other => "unknown: " + other,
+            }
+        };
+
+        print(step("move 3 from 4"));
+        print(step("noop"));
+        print(step("jump"));
+    "#},
+    equals(indoc! {r#"
+        7
+        nothing
+        unknown: jump
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    match_regex_arms_can_be_mixed_with_constants,
+    indoc! {r#"
+        fn describe(s) {
+            match s {
+                "" => "empty",
+                r/^(?<word>[a-z]+)$/i => "word " + word,
+                _ => "other",
+            }
+        };
+
+        print(describe(""), describe("Hello"), describe("a b"));
+    "#},
+    equals("empty word Hello other"),
+    empty()
+);
+
+eval_and_assert!(
+    match_string_prefix_binds_the_rest,
+    indoc! {r#"
+        fn command(line) {
+            match line {
+                "say " + msg => "said '" + msg + "'",
+                "go" + dir => "went" + dir,
+            }
+        };
+
+        print(command("say hi there"));
+        print(command("say "));
+        print(command("go north"));
+    "#},
+    equals(indoc! {r#"
+        said 'hi there'
+        said ''
+        went north
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    match_string_prefix_is_not_a_regex,
+    indoc! {r#"
+        print(match "a.b" { "a.b" + rest => "literal", _ => "no" });
+        print(match "axb" { "a.b" + rest => "literal", _ => "no" });
+    "#},
+    equals(indoc! {r#"
+        literal
+        no
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    match_tuple_patterns_bind_items,
+    indoc! {r#"
+        fn classify(p) {
+            match p {
+                (0, 0) => "origin",
+                (x, 0) => "on the x axis at " + str(x),
+                (x, y) => x + y,
+                _ => "not a pair",
+            }
+        };
+
+        print(classify((0, 0)));
+        print(classify((5, 0)));
+        print(classify((2, 3)));
+        print(classify(("a", "b")));
+        print(classify((1, 2, 3)));
+    "#},
+    equals(indoc! {r#"
+        origin
+        on the x axis at 5
+        5
+        ab
+        not a pair
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    match_list_patterns_check_length_and_nest,
+    indoc! {r#"
+        fn f(v) {
+            match v {
+                [] => "empty",
+                [a, [b, c]] => a * b * c,
+                ["go", r/^(?<n>\d+)$/n] => n,
+                [first, "say " + rest] => first + rest,
+                (x, y) => "tuple",
+                other => "other",
+            }
+        };
+
+        print(f([]));
+        print(f([2, [3, 4]]));
+        print(f(["go", "12"]));
+        print(f(["x", "say yo"]));
+        print(f([1, 2]));
+        print(f((1, 2)));
+        print(f([1, 2, 3]));
+    "#},
+    equals(indoc! {r#"
+        empty
+        24
+        12
+        xyo
+        other
+        tuple
+        other
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    match_string_patterns_skip_other_types,
+    indoc! {r#"
+        print(match 5 { r/\d/ => "regex", "x" + rest => "prefix", n => n });
+    "#},
+    equals("5"),
+    empty()
+);
+
+eval_and_assert!(
+    match_unsupported_pattern_is_an_error,
+    indoc! {r#"
+        print(match 5 { 1 + 2 => "three" });
+    "#},
+    empty(),
+    contains("Unsupported pattern in match arm")
+);
@@ -59,6 +59,35 @@ eval_and_assert!(
     empty()
 );
 
+eval_and_assert!(
+    negation_of_each_number_kind,
+    indoc::indoc! {r#"
+        x = 5;
+        f = 2.5;
+        big = 2 ** 70;
+        smallest = -9223372036854775807 - 1;
+        print(-x, -f, -big, -(-x));
+        print(-smallest);
+        print(-(1, -2), -(1.5, 2));
+    "#},
+    equals(indoc::indoc! {r#"
+        -5 -2.5 -1180591620717411303424 5
+        9223372036854775808
+        (-1, 2) (-1.5, -2)
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    negation_of_non_number_error,
+    indoc::indoc! {r#"
+        s = "abc";
+        print(-s);
+    "#},
+    empty(),
+    contains("Cannot negate type 'str'")
+);
+
 eval_and_assert!(
     manhattan_distance_single_tuple,
     indoc::indoc! {r#"
@@ -212,3 +241,142 @@ eval_and_assert!(
     empty(),
     contains("Cannot compute square root of type str")
 );
+
+eval_and_assert!(
+    isqrt_rounds_down,
+    indoc::indoc! {r#"
+        print(isqrt(15), isqrt(16), isqrt(0));
+        print(isqrt(10 ** 40 + 1));
+    "#},
+    equals(indoc::indoc! {r#"
+        3 4 0
+        100000000000000000000
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    isqrt_rejects_floats,
+    "isqrt(2.5);",
+    empty(),
+    contains("Cannot compute integer square root of 2.5, as it must be an integer")
+);
+
+eval_and_assert!(
+    gcd_and_lcm,
+    indoc::indoc! {r#"
+        print(gcd(12, 18), gcd(-12, 18), gcd(7, 0));
+        print(lcm(4, 6), lcm(4, -6), lcm(0, 5));
+        print(gcd([12, 18, 27]), lcm([2, 3, 4, 5]));
+        cycles = [13, 17, 19];
+        print(lcm(*cycles, 2));
+        print(gcd(2 ** 100, 6 ** 50));
+    "#},
+    equals(indoc::indoc! {r#"
+        6 6 7
+        12 12 0
+        3 60
+        8398
+        1125899906842624
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    gcd_rejects_floats,
+    "gcd(1.5, 3);",
+    empty(),
+    contains("gcd expects integers, got 1.5")
+);
+
+eval_and_assert!(
+    lcm_rejects_non_numbers,
+    "lcm([4, \"6\"]);",
+    empty(),
+    contains("lcm expects integers, got \"6\"")
+);
+
+eval_and_assert!(
+    floor_ceil_and_round,
+    indoc::indoc! {r#"
+        print(floor(2.5), ceil(2.5), round(2.5));
+        print(floor(-2.5), ceil(-2.5), round(-2.5));
+        print(floor(7), round(2.4));
+    "#},
+    equals(indoc::indoc! {r#"
+        2 3 3
+        -3 -2 -3
+        7 2
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    trigonometry,
+    indoc::indoc! {r#"
+        print(sin(0), cos(0), tan(0));
+        print(approx_eq(sin(3.141592653589793 / 2), 1));
+    "#},
+    equals(indoc::indoc! {r#"
+        0 1 0
+        true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    logarithms,
+    indoc::indoc! {r#"
+        print(log(1), log2(1024), log(1000, 10));
+        print(log2(2 ** 100));
+    "#},
+    equals(indoc::indoc! {r#"
+        0 10 2.9999999999999996
+        100
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    log_type_error,
+    "log(\"e\");",
+    empty(),
+    contains("log expects a number, got 'str'")
+);
+
+eval_and_assert!(
+    wrapping_arithmetic,
+    indoc::indoc! {r#"
+        print(wrap_add(4294967295, 1, 32));
+        print(wrap_sub(0, 1, 32));
+        print(wrap_mul(16807, 2147483646, 31));
+        print(wrap_mul(9223372036854775807, 3, 64));
+        print(wrap_add(250, 10, 8));
+    "#},
+    equals(indoc::indoc! {r#"
+        0
+        4294967295
+        2147450034
+        9223372036854775805
+        4
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    wrapping_arithmetic_rejects_floats,
+    indoc::indoc! {r#"
+        wrap_add(1.5, 1, 32);
+    "#},
+    empty(),
+    contains("Cannot use wrap_add on floating point numbers")
+);
+
+eval_and_assert!(
+    wrapping_arithmetic_rejects_invalid_bit_width,
+    indoc::indoc! {r#"
+        wrap_mul(1, 1, 0);
+    "#},
+    empty(),
+    contains("wrap_mul bit width must be an integer between 1 and 128")
+);
@@ -247,7 +247,7 @@ eval_and_assert!(
     indoc! {r#"
         call_count = 0;
 
-        if true {
+        if true { # lint: allow(constant_condition)
             memoized fn foo(x) {
                 call_count = call_count + 1;
                 x * 2
@@ -1,6 +1,6 @@
 use crate::helpers::{
     eval_and_assert,
-    output::{contains, empty},
+    output::{contains, empty, equals},
 };
 
 use indoc::indoc;
@@ -13,3 +13,122 @@ eval_and_assert!(
     empty(),
     contains("Type mismatch: Cannot call method 'upper' on type 'number'")
 );
+
+eval_and_assert!(
+    methods_on_literals,
+    indoc! {r#"
+        print([3, 1, 2].reverse());
+        print("abc".upper());
+        print(907.digits());
+        print((-12).digits());
+        print((0..4).len());
+        print({"a": 1}.keys());
+        print([x * 2 for x in 0..3].reverse());
+        print(match 1 { 1 => "ab" }.upper());
+        print((1 + 2).binary());
+    "#},
+    equals(indoc! {r#"
+        [2, 1, 3]
+        ABC
+        [9, 0, 7]
+        [1, 2]
+        4
+        ["a"]
+        [4, 2, 0]
+        AB
+        11
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    calls_indexes_and_methods_chain_in_any_order,
+    indoc! {r#"
+        fn pair(x) { [x, x + 1] };
+        fn adder(n) { fn(x) x + n };
+        fns = [pair];
+        words = ["ab", "c"];
+
+        print(pair(1)[1]);
+        print(pair(1).reverse());
+        print(words.reverse()[0]);
+        print(words.reverse()[0].upper());
+        print(fns[0](5));
+        print(fns[0](5)[1].digits());
+        print(adder(1)(2));
+        print("a b".split(" ")[1].upper().len());
+        print([[1, 2]][0][1]);
+    "#},
+    equals(indoc! {r#"
+        2
+        [2, 1]
+        c
+        AB
+        [5, 6]
+        [6]
+        3
+        1
+        2
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    method_calls_bind_tighter_than_operators,
+    indoc! {r#"
+        xs = [1, 2];
+        print(-xs.len());
+        print(not xs.is_empty());
+        print(1 + xs.len() * 2);
+        print(2 ** xs.len());
+        print(1..xs.len());
+        print(xs.len() in xs);
+    "#},
+    equals(indoc! {r#"
+        -2
+        true
+        5
+        4
+        1..2
+        true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    map_literals_in_arguments_lists_and_parentheses,
+    indoc! {r#"
+        print({"a": 1});
+        print([{"a": 1}, {"b": 2}].len());
+        print(({"a": 1}).len());
+        print({"a": 1}.keys()[0]);
+    "#},
+    equals(indoc! {r#"
+        {"a": 1}
+        2
+        1
+        a
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    assign_to_index_of_call_result,
+    indoc! {r#"
+        grid = [[1, 2]];
+        fn rows() { grid };
+        rows()[0][1] = 5;
+        print(grid);
+    "#},
+    equals("[[1, 5]]"),
+    empty()
+);
+
+eval_and_assert!(
+    unclosed_index_at_end_of_input_is_reported,
+    indoc! {r#"
+        xs = [[1]];
+        xs[0][0"#},
+    empty(),
+    contains("Unexpected end of input")
+);
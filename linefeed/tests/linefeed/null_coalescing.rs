@@ -0,0 +1,136 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    coalesce_uses_right_side_only_for_null,
+    indoc! {r#"
+        print(null ?? 1, 2 ?? 1, 0 ?? 1, false ?? 1, null ?? null ?? 3);
+    "#},
+    equals("1 2 0 false 3"),
+    empty()
+);
+
+eval_and_assert!(
+    coalesce_with_missing_keys_and_indices,
+    indoc! {r#"
+        m = {"a": 1};
+        xs = [10, 20];
+        print(m["a"] ?? 0, m["b"] ?? 0, m.len());
+        print(xs[1] ?? -1, xs[2] ?? -1, xs[-3] ?? -1);
+    "#},
+    equals(indoc! {r#"
+        1 0 1
+        20 -1 -1
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    coalesce_only_evaluates_right_side_when_needed,
+    indoc! {r#"
+        fn fallback() {
+            print("fallback");
+            0
+        };
+        print(1 ?? fallback());
+        print(null ?? fallback());
+    "#},
+    equals(indoc! {r#"
+        1
+        fallback
+        0
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    coalesce_binds_looser_than_arithmetic,
+    indoc! {r#"
+        print(2 ?? 1 + 10, (2 ?? 1) + 10);
+    "#},
+    equals("2 12"),
+    empty()
+);
+
+eval_and_assert!(
+    coalesce_still_reports_invalid_indexing,
+    indoc! {r#"
+        xs = [1];
+        print(xs["a"] ?? 0);
+    "#},
+    empty(),
+    contains("Cannot index into 'list' with type 'str'")
+);
+
+eval_and_assert!(
+    optional_chaining_gives_null_on_null_receiver,
+    indoc! {r#"
+        m = null;
+        print(m?.get_or("a", 0), m?[0], m?.len());
+    "#},
+    equals("null null null"),
+    empty()
+);
+
+eval_and_assert!(
+    optional_chaining_calls_through_on_values,
+    indoc! {r#"
+        xs = [1, 2];
+        m = {"a": 1};
+        print(xs?[1], xs?.len(), m?.get_or("a", 0), m?["a"]);
+    "#},
+    equals("2 2 1 1"),
+    empty()
+);
+
+eval_and_assert!(
+    optional_index_is_null_when_out_of_bounds,
+    indoc! {r#"
+        xs = [1, 2];
+        print(xs?[5], xs?[5] ?? "none");
+    "#},
+    equals("null none"),
+    empty()
+);
+
+eval_and_assert!(
+    optional_chaining_skips_arguments_on_null_receiver,
+    indoc! {r#"
+        fn key() {
+            print("key");
+            "a"
+        };
+        m = null;
+        print(m?.get_or(key(), 0));
+        print(m?[key()]);
+    "#},
+    equals(indoc! {r#"
+        null
+        null
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    optional_chaining_only_guards_its_own_step,
+    indoc! {r#"
+        m = null;
+        print(m?.get_or("a", 0).len());
+    "#},
+    empty(),
+    contains("Cannot get length of 'null'")
+);
+
+eval_and_assert!(
+    optional_index_cannot_be_assigned_to,
+    indoc! {r#"
+        xs = null;
+        xs?[0] = 1;
+    "#},
+    empty(),
+    contains("Cannot assign to a null-safe index `?[...]`")
+);
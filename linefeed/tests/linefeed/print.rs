@@ -68,3 +68,21 @@ eval_and_assert!(
     "#}),
     empty()
 );
+
+eval_and_assert!(
+    eprint_writes_to_stderr,
+    indoc! {r#"
+        eprint("progress:", 1, [2, 3]);
+        print("output");
+        x = eprint("done");
+        print(x);
+    "#},
+    equals(indoc! {r#"
+        output
+        done
+    "#}),
+    equals(indoc! {r#"
+        progress: 1 [2, 3]
+        done
+    "#})
+);
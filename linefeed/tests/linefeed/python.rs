@@ -0,0 +1,96 @@
+use indoc::indoc;
+
+fn python(src: &str) -> String {
+    let mut out = Vec::new();
+    let status = linefeed::emit_python(src, &mut out, std::io::sink());
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn functions_and_loops_become_python_statements() {
+    let out = python(indoc! {r#"
+        fn fib(n) if n < 2 { n } else { fib(n - 1) + fib(n - 2) };
+        total = 0;
+        for i in 0..10 { total += fib(i) };
+        print("total:", total);
+    "#});
+
+    assert!(out.contains(indoc! {"
+        def fib(n):
+            return n if n < 2 else fib(n - 1) + fib(n - 2)
+
+
+        total = 0
+        for i in range(0, 10):
+            total += fib(i)
+        lf_print(\"total:\", total)
+    "}));
+    // Printing needs the helpers formatting values the way Linefeed does
+    assert!(out.starts_with("import json\nimport sys\n"));
+    assert!(out.contains("def lf_print("));
+    assert!(out.contains("def lf_str("));
+}
+
+#[test]
+fn assigning_outer_variables_declares_them() {
+    let out = python(indoc! {r#"
+        calls = 0;
+        fn make_counter() {
+            calls += 1;
+            count = 0;
+            fn () {
+                count += 1;
+                count
+            }
+        };
+        fn double(x) x * 2;
+        print([3, 1, 2].sort(fn (x) -x));
+    "#});
+
+    assert!(out.contains(indoc! {"
+        def make_counter():
+            global calls
+            calls += 1
+            count = 0
+            def lf_fn1():
+                nonlocal count
+                count += 1
+                return count
+            return lf_fn1
+    "}));
+    assert!(out.contains(indoc! {"
+        def double(x):
+            return x * 2
+
+
+        lf_print(sorted([3, 1, 2], key=lambda x: -x))
+    "}));
+}
+
+#[test]
+fn expressions_needing_statements_are_computed_first() {
+    let out = python(indoc! {r#"
+        x = 3;
+        print(if x > 1 { y = x * 2; y // 4 } else { 0 });
+    "#});
+
+    assert!(out.contains(indoc! {"
+        x = 3
+        if x > 1:
+            y = x * 2
+            lf_tmp1 = lf_div_floor(y, 4)
+        else:
+            lf_tmp1 = 0
+        lf_print(lf_tmp1)
+    "}));
+}
+
+#[test]
+fn unsupported_code_is_an_error() {
+    let mut stderr = Vec::new();
+    let status = linefeed::emit_python("x = 1; match x { 1 => 2 };", std::io::sink(), &mut stderr);
+
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(String::from_utf8_lossy(&stderr).contains("Match can't be transpiled to Python"));
+}
@@ -0,0 +1,78 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    seeded_random_repeats,
+    indoc::indoc! {r#"
+        seed(42);
+        first = [rand(), rand_int(1, 100), shuffle(0..10), choice("abc")];
+        seed(42);
+        second = [rand(), rand_int(1, 100), shuffle(0..10), choice("abc")];
+        print(first == second);
+        seed(43);
+        print(first == [rand(), rand_int(1, 100), shuffle(0..10), choice("abc")]);
+    "#},
+    equals(indoc::indoc! {r#"
+        true
+        false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    random_numbers_are_in_range,
+    indoc::indoc! {r#"
+        floats = [rand() for _ in 0..1000];
+        print(all([0 <= f and f < 1 for f in floats]));
+        ints = set([rand_int(-2, 2) for _ in 0..1000]);
+        print(sorted(ints));
+        print(rand_int(7, 7));
+    "#},
+    equals(indoc::indoc! {r#"
+        true
+        [-2, -1, 0, 1, 2]
+        7
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    shuffle_and_choice_use_the_items,
+    indoc::indoc! {r#"
+        xs = [3, 1, 4, 1, 5, 9];
+        print(sorted(shuffle(xs)) == sorted(xs));
+        print(xs);
+        print(choice(xs) in xs, choice([7]));
+        print(shuffle([]));
+    "#},
+    equals(indoc::indoc! {r#"
+        true
+        [3, 1, 4, 1, 5, 9]
+        true 7
+        []
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    choice_from_empty_error,
+    "choice([]);",
+    empty(),
+    contains("Cannot choose from an empty iterable")
+);
+
+eval_and_assert!(
+    rand_int_bounds_error,
+    "rand_int(5, 1);",
+    empty(),
+    contains("Cannot pick a random integer between 5 and 1, as 5 is larger")
+);
+
+eval_and_assert!(
+    seed_type_error,
+    "seed(1.5);",
+    empty(),
+    contains("seed expects an integer, got 1.5")
+);
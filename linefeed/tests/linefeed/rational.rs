@@ -0,0 +1,101 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    rational_is_printed_as_a_fraction,
+    indoc::indoc! {r#"
+        print(rational(3, 4));
+        print(rational(6, 8));
+        print(rational(1, -2));
+        print(rational(4, 2));
+    "#},
+    equals(indoc::indoc! {r#"
+        3/4
+        3/4
+        -1/2
+        2
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    rational_arithmetic_is_exact,
+    indoc::indoc! {r#"
+        a = rational(1, 3);
+        print(a + a + a);
+        print(a - 1);
+        print(2 * a);
+        print(a / 2);
+        print(1 / a);
+        print(-a);
+        print(a ** 2);
+        print(rational(7, 2) % 2);
+        print(rational(7, 2) // 1);
+    "#},
+    equals(indoc::indoc! {r#"
+        1
+        -2/3
+        2/3
+        1/6
+        3
+        -1/3
+        1/9
+        3/2
+        3
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    rational_with_floats_gives_floats,
+    indoc::indoc! {r#"
+        print(rational(1, 2) + 0.25);
+        print(rational(1, 2) / 0);
+        print(float(rational(3, 4)));
+    "#},
+    equals(indoc::indoc! {r#"
+        0.75
+        inf
+        0.75
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    rational_comparison_and_conversion,
+    indoc::indoc! {r#"
+        print(rational(1, 3) < rational(1, 2));
+        print(rational(1, 2) == 0.5);
+        print(rational(2, 4) == rational(1, 2));
+        print(rational(7, 2) > 3);
+        print(int(rational(7, 2)));
+        print(int(rational(-7, 2)));
+        print(abs(rational(-1, 5)));
+    "#},
+    equals(indoc::indoc! {r#"
+        true
+        true
+        true
+        true
+        3
+        -3
+        1/5
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    rational_rejects_zero_denominator,
+    "rational(1, 0);",
+    empty(),
+    contains("Cannot create a rational with a zero denominator")
+);
+
+eval_and_assert!(
+    rational_rejects_floats,
+    "rational(0.5, 2);",
+    empty(),
+    contains("Cannot create a rational from floating point numbers")
+);
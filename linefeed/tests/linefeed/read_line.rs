@@ -0,0 +1,49 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{empty, equals},
+};
+
+eval_and_assert!(
+    read_line_reads_one_line_at_a_time,
+    indoc::indoc! {r#"
+        first = read_line();
+        second = read_line();
+        print(first, "|", second);
+        print(input());
+    "#},
+    "one\r\ntwo\nthree\nfour\n",
+    equals(indoc::indoc! {r#"
+        one | two
+        three
+        four
+
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    read_line_returns_null_at_the_end,
+    indoc::indoc! {r#"
+        total = 0;
+        line = read_line();
+        while line != null {
+          total = total + int(line);
+          line = read_line();
+        };
+        print(total);
+    "#},
+    "1\n2\n3",
+    equals("6\n"),
+    empty()
+);
+
+eval_and_assert!(
+    read_line_prints_the_prompt,
+    indoc::indoc! {r#"
+        name = read_line("Name: ");
+        print("Hello,", name);
+    "#},
+    "Ada\n",
+    equals("Name: Hello, Ada\n"),
+    empty()
+);
@@ -1,9 +1,31 @@
 use crate::helpers::{
     eval_and_assert,
-    output::{empty, equals},
+    output::{contains, empty, equals},
 };
 
 use indoc::indoc;
+use linefeed::{chumsky::Parser as _, compiler::Compiler};
+
+// Returns the number of distinct regexes compiled for the literals in `src`
+fn pooled_regexes(src: &str) -> usize {
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+    let program = Compiler::default().compile(&ast).unwrap();
+
+    program.regex_pool.len()
+}
+
+#[test]
+fn identical_regex_literals_share_a_compiled_regex() {
+    let src = indoc! {r#"
+        a = r/\d+/n;
+        b = r/\d+/n;
+        c = r/\d+/;
+        d = [r/x/i, r/x/i];
+    "#};
+
+    assert_eq!(pooled_regexes(src), 3);
+}
 
 eval_and_assert!(
     regex_basic_can_be_created,
@@ -109,3 +131,104 @@ eval_and_assert!(
     equals("true \n false"),
     empty()
 );
+
+eval_and_assert!(
+    regex_can_be_built_from_a_string,
+    indoc! {r#"
+        word = "b+";
+        reg = regex(word + "c");
+        print(reg);
+        print("abbbc".find(reg));
+        print("1 22 333".find_all(regex("\d+", "n")));
+        print("xabcx".is_match(regex("ABC", "i")));
+    "#},
+    equals(indoc! {r#"
+        /b+c/
+        ("bbbc")
+        [(1), (22), (333)]
+        true
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    regex_built_from_a_string_equals_literal,
+    indoc! {r#"
+        print(regex("\d") == r/\d/);
+    "#},
+    equals("true\n"),
+    empty()
+);
+
+eval_and_assert!(
+    regex_with_unknown_flag_is_an_error,
+    indoc! {r#"
+        regex("a", "x");
+    "#},
+    empty(),
+    contains("Unknown regex flag 'x'")
+);
+
+eval_and_assert!(
+    regex_with_invalid_pattern_is_an_error,
+    indoc! {r#"
+        regex("(");
+    "#},
+    empty(),
+    contains("Invalid regex")
+);
+
+eval_and_assert!(
+    regex_replace_substitutes_matches,
+    indoc! {r#"
+        print("a1b22c333".replace(r/\d+/, "_"));
+        print("John Smith".replace(r/(\w+) (\w+)/, "$2, $1"));
+        print("x=1, y=2".replace(r/(?P<key>\w)=(?P<val>\d)/, "${val}=${key}"));
+        print("cost: 5".replace(r/\d/, "$$${0}0"));
+        print("ABC".replace(r/b/i, "-"));
+        print("abc".replace(r/x/, "y"));
+    "#},
+    equals(indoc! {r#"
+        a_b_c_
+        Smith, John
+        1=x, 2=y
+        cost: $50
+        A-C
+        abc
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    regex_replace_expects_a_replacement_string,
+    r#""abc".replace(r/b/, 1);"#,
+    empty(),
+    contains(
+        "replace expects a string or regex and a replacement string, got 'regex' and 'number'"
+    )
+);
+
+eval_and_assert!(
+    regex_named_groups_give_maps,
+    indoc! {r#"
+        line = "pos=<3,-4> vel=<-1,2>";
+        m = line.find(r/pos=<(?P<x>-?\d+),(?P<y>-?\d+)> vel=<(?P<dx>-?\d+),(?P<dy>-?\d+)>/n);
+        print(m["x"] + m["dx"], m["y"] + m["dy"]);
+        print(m.len());
+
+        words = "a=1 b=two".find_all(r/(?P<key>\w)=(?P<value>\w+)/);
+        print(words.len(), words[0]["key"], words[1]["value"]);
+
+        opt = "id:".find(r/id:(?P<n>\d+)?/);
+        print(opt["n"]);
+        print("nothing".find(r/(?P<n>\d)/));
+    "#},
+    equals(indoc! {r#"
+        2 -2
+        4
+        2 a two
+        null
+        null
+    "#}),
+    empty()
+);
@@ -0,0 +1,217 @@
+use std::fs;
+
+use indoc::indoc;
+use linefeed::{
+    config::Config,
+    repl::{run_repl, run_repl_after_file},
+    EXIT_FAILURE, EXIT_SUCCESS,
+};
+
+// Runs the REPL on `input`, returning its exit status, stdout without prompts, and stderr
+fn repl(config: &Config, input: &str) -> (i32, String, String) {
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let status = run_repl(config, input.as_bytes(), &mut stdout, &mut stderr);
+
+    let stdout = String::from_utf8(stdout).unwrap();
+    let stdout = stdout
+        .replace("... ", "")
+        .replace("> ", "")
+        .trim_end()
+        .to_string();
+    let stderr = String::from_utf8(strip_ansi_escapes::strip(stderr)).unwrap();
+
+    (status, stdout, stderr)
+}
+
+// Runs the program in `file` with `stdin`, then the REPL on `input`, like `repl`
+fn repl_after_file(file: &str, src: &str, stdin: &str, input: &str) -> (i32, String, String) {
+    let dir = std::env::temp_dir().join(format!("linefeed-repl-after-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(file);
+    fs::write(&path, src).unwrap();
+
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let status = run_repl_after_file(
+        &path,
+        &Config::default(),
+        stdin.as_bytes(),
+        input.as_bytes(),
+        &mut stdout,
+        &mut stderr,
+    );
+
+    let stdout = String::from_utf8(stdout).unwrap();
+    let stdout = stdout.replace("> ", "").trim_end().to_string();
+    let stderr = String::from_utf8(strip_ansi_escapes::strip(stderr)).unwrap();
+
+    (status, stdout, stderr)
+}
+
+#[test]
+fn definitions_are_kept_between_inputs() {
+    let input = indoc! {"
+        x = 5;
+        fn add_x(n) n + x;
+        add_x(2)
+        x = 10;
+        add_x(2)
+    "};
+
+    let (status, stdout, stderr) = repl(&Config::default(), input);
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "7\n12");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn literals_of_each_input_keep_their_values() {
+    let input = indoc! {r#"
+        a = "first";
+        b = "second"
+        [a, "third"]
+    "#};
+
+    let (_, stdout, stderr) = repl(&Config::default(), input);
+    assert_eq!(stdout, "\"second\"\n[\"first\", \"third\"]");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn values_are_printed_unless_null_or_followed_by_semicolon() {
+    let input = indoc! {r#"
+        "a" + "b"
+        [1, 2];
+        print("printed")
+        if false { 1 }
+    "#};
+
+    let (_, stdout, _) = repl(&Config::default(), input);
+    assert_eq!(stdout, "\"ab\"\nprinted\n\"printed\"");
+}
+
+#[test]
+fn unfinished_inputs_continue_on_the_next_line() {
+    let input = indoc! {"
+        fn double(n) {
+          n * 2
+        };
+        double(4)
+    "};
+
+    let (_, stdout, stderr) = repl(&Config::default(), input);
+    assert_eq!(stdout, "8");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn errors_are_reported_without_exiting() {
+    let input = indoc! {"
+        x = 1;
+        missing
+        y = x + [];
+        fn fail() [] - 1;
+        fail()
+        x + 1
+    "};
+
+    let (status, stdout, stderr) = repl(&Config::default(), input);
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "2");
+    assert!(stderr.contains("No such variable 'missing' in scope"));
+    assert!(stderr.contains("Cannot add types 'number' and 'list'"));
+    assert!(stderr.contains("fn fail() [] - 1"));
+}
+
+#[test]
+fn variables_of_failed_inputs_can_be_assigned_later() {
+    let input = indoc! {"
+        a = missing;
+        a = 3;
+        b = a + [];
+        b = a * 2;
+        (a, b)
+    "};
+
+    let (_, stdout, _) = repl(&Config::default(), input);
+    assert_eq!(stdout, "(3, 6)");
+}
+
+#[test]
+fn prelude_is_loaded_first() {
+    let dir = std::env::temp_dir().join(format!("linefeed-repl-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let prelude = dir.join("prelude.lf");
+    fs::write(&prelude, "fn triple(n) n * 3;\n").unwrap();
+
+    let config = Config {
+        repl_prelude: vec![prelude],
+        ..Config::default()
+    };
+    let (status, stdout, _) = repl(&config, "triple(3)\n");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "9");
+
+    let config = Config {
+        repl_prelude: vec![dir.join("missing.lf")],
+        ..Config::default()
+    };
+    let (status, _, stderr) = repl(&config, "1\n");
+    assert_eq!(status, EXIT_FAILURE);
+    assert!(stderr.contains("failed to read"));
+}
+
+#[test]
+fn repl_after_run_continues_from_final_state() {
+    let src = indoc! {"
+        fn parse(lines) [int(n) for n in lines.lines()];
+        nums = parse(input());
+        print(\"first\", nums[0]);
+    "};
+
+    let input = "nums\nparse(\"4\\n5\")\n";
+    let (status, stdout, stderr) = repl_after_file("final_state.lf", src, "1\n2\n3\n", input);
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "first 1\n[1, 2, 3]\n[4, 5]");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn repl_after_run_starts_after_runtime_error() {
+    let src = indoc! {"
+        xs = [1, 2, 3];
+        x = xs[10];
+        y = 2;
+    "};
+
+    let (status, stdout, stderr) = repl_after_file("failing.lf", src, "", "xs.len()\n");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "3");
+    assert!(stderr.contains("Index 10 out of bounds"));
+}
+
+#[test]
+fn exit_stops_the_repl_with_its_code() {
+    let input = indoc! {"
+        print(1);
+        exit(4)
+        print(2);
+    "};
+
+    let (status, stdout, stderr) = repl(&Config::default(), input);
+    assert_eq!(status, 4);
+    assert_eq!(stdout, "1");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn repl_after_run_does_not_start_after_exit() {
+    let src = indoc! {"
+        print(\"exiting\");
+        exit(0);
+    "};
+
+    let (status, stdout, stderr) = repl_after_file("exiting.lf", src, "", "1 + 1\n");
+    assert_eq!(status, EXIT_SUCCESS);
+    assert_eq!(stdout, "exiting");
+    assert_eq!(stderr, "");
+}
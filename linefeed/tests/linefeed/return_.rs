@@ -20,6 +20,7 @@ eval_and_assert!(
     indoc! {r#"
         fn foo() {
             return print("this is printed");
+            # lint: allow(unreachable_code)
             print("this is not printed");
         };
 
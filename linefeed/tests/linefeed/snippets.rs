@@ -0,0 +1,177 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    part_prints_answer,
+    indoc! {r#"
+        part(1, 42);
+        part(2, "abc");
+    "#},
+    equals(indoc! {r#"
+        Part 1: 42
+        Part 2: abc
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    aoc_parts_prints_both_answers,
+    indoc! {r#"
+        nums = [3, 1, 2];
+        aoc_parts(sum(nums), max(nums));
+    "#},
+    equals(indoc! {r#"
+        Part 1: 6
+        Part 2: 3
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    aoc_parts_prints_like_printing_each_part,
+    indoc! {r#"
+        part1 = 2 ** 70;
+        part2 = [(1, "a")];
+        aoc_parts(part1, part2);
+        print("Part 1:", part1);
+        print("Part 2:", part2);
+    "#},
+    equals(indoc! {r#"
+        Part 1: 1180591620717411303424
+        Part 2: [(1, "a")]
+        Part 1: 1180591620717411303424
+        Part 2: [(1, "a")]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    snippet_arguments_are_evaluated_once_in_order,
+    indoc! {r#"
+        fn answer(n) {
+            print("computing", n);
+            n * 10
+        };
+        aoc_parts(answer(1), answer(2));
+    "#},
+    equals(indoc! {r#"
+        computing 1
+        Part 1: 10
+        computing 2
+        Part 2: 20
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    snippets_expand_inside_functions,
+    indoc! {r#"
+        fn report(xs) part(xs.len(), xs);
+        report([1, 2]);
+    "#},
+    equals(indoc! {r#"
+        Part 2: [1, 2]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    user_definitions_take_precedence_over_snippets,
+    indoc! {r#"
+        fn part(a, b) a * b;
+        aoc_parts = fn (a, b) a - b;
+        print(part(2, 3), aoc_parts(5, 1));
+    "#},
+    equals(indoc! {r#"
+        6 4
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    snippet_with_wrong_number_of_arguments_is_an_error,
+    indoc! {r#"
+        aoc_parts(1);
+    "#},
+    empty(),
+    contains("Snippet aoc_parts expects 2 arguments, got 1")
+);
+
+eval_and_assert!(
+    dbg_prints_source_and_repr_to_stderr,
+    indoc! {r#"
+        xs = [1, 2];
+        total = dbg(xs.len()) + 1;
+        print(total, dbg("a" +
+          "b"));
+    "#},
+    equals("3 ab"),
+    equals(indoc! {r#"
+        [2:9] xs.len() = 2
+        [3:14] "a" +
+          "b" = "ab"
+    "#})
+);
+
+eval_and_assert!(
+    dbg_expects_one_argument,
+    indoc! {r#"
+        dbg(1, 2);
+    "#},
+    empty(),
+    contains("Snippet dbg expects 1 arguments, got 2")
+);
+
+eval_and_assert!(
+    dbg_is_an_error_in_programs_defining_inspect,
+    indoc! {r#"
+        fn inspect(value, label) print("captured", value);
+        dbg(1);
+    "#},
+    empty(),
+    contains(
+        "Snippet dbg calls the builtin inspect, so it can't be used in a program that defines \
+         its own function inspect"
+    )
+);
+
+eval_and_assert!(
+    aoc_parts_is_an_error_in_programs_defining_str,
+    indoc! {r#"
+        fn f() {
+          fn str(x) "captured";
+        };
+        aoc_parts(1, 2);
+    "#},
+    empty(),
+    contains("Snippet aoc_parts calls the builtin str")
+);
+
+eval_and_assert!(
+    variables_named_after_builtins_do_not_affect_snippets,
+    indoc! {r#"
+        str = 5;
+        inspect = "label";
+        part(str, inspect);
+        dbg(str);
+    "#},
+    equals("Part 5: label"),
+    contains("[4:1] str = 5")
+);
+
+eval_and_assert!(
+    inspect_prints_repr_with_optional_label,
+    indoc! {r#"
+        x = inspect({"k": [1, "v"]});
+        inspect(x["k"], "value:");
+    "#},
+    empty(),
+    equals(indoc! {r#"
+        {"k": [1, "v"]}
+        value: [1, "v"]
+    "#})
+);
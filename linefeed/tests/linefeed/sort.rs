@@ -1,6 +1,6 @@
 use crate::helpers::{
     eval_and_assert,
-    output::{empty, equals},
+    output::{contains, empty, equals},
 };
 
 use indoc::indoc;
@@ -77,3 +77,46 @@ eval_and_assert!(
     equals("[5, 4, 3, 2, 1]"),
     empty()
 );
+
+eval_and_assert!(
+    sorted_returns_new_list_of_any_iterable,
+    indoc! {r#"
+        xs = [3, 1, 2];
+        print(sorted(xs), xs);
+        print(sorted({"b": 1, "a": 2}.keys()));
+        print(sorted("bca"));
+        print(sorted([x * x for x in -2..2]));
+    "#},
+    equals(indoc! {r#"
+        [1, 2, 3] [3, 1, 2]
+        ["a", "b"]
+        ["a", "b", "c"]
+        [0, 1, 1, 4]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    sorted_with_key_and_reverse,
+    indoc! {r#"
+        words = ["bb", "a", "ccc", "dd"];
+        print(sorted(words, fn (s) s.len()));
+        print(sorted(words, {"key": fn (s) s.len(), "reverse": true}));
+        print(sorted(0..4, {"reverse": true}));
+    "#},
+    equals(indoc! {r#"
+        ["a", "bb", "dd", "ccc"]
+        ["ccc", "bb", "dd", "a"]
+        [3, 2, 1, 0]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    sorted_rejects_unknown_options,
+    indoc! {r#"
+        sorted([2, 1], {"rev": true});
+    "#},
+    empty(),
+    contains("Unknown sorted option 'rev', expected 'key' or 'reverse'")
+);
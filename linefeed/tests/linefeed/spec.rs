@@ -0,0 +1,143 @@
+//! Runner for the conformance suite in `linefeed/spec`.
+//!
+//! Every `<name>.lf` program in the spec directory has a sibling `<name>.expected` manifest
+//! describing what running it must produce. A manifest consists of sections, each introduced by
+//! a `--- <section>` header line:
+//!
+//! - `--- stdin`: input fed to the program (default: nothing)
+//! - `--- stdout` / `--- stdout contains`: expected standard output (default: empty)
+//! - `--- stderr` / `--- stderr contains`: expected standard error (default: empty)
+//! - `--- status`: expected exit status (default: 0)
+//!
+//! Output is compared the same way as in the rest of the test suite, i.e. ignoring ANSI escapes
+//! and leading/trailing whitespace on each line.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::helpers::{
+    output::{contains, empty, equals, OutputAssertion},
+    run_program_with_status,
+};
+
+const SPEC_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/spec");
+
+struct Manifest {
+    stdin: String,
+    stdout: OutputAssertion,
+    stderr: OutputAssertion,
+    status: i32,
+}
+
+impl Manifest {
+    fn parse(src: &str) -> Result<Self, String> {
+        let mut manifest = Manifest {
+            stdin: String::new(),
+            stdout: empty(),
+            stderr: empty(),
+            status: linefeed::EXIT_SUCCESS,
+        };
+
+        for (header, body) in sections(src)? {
+            match header.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["stdin"] => manifest.stdin = body,
+                ["stdout"] => manifest.stdout = equals(body),
+                ["stdout", "contains"] => manifest.stdout = contains(body),
+                ["stderr"] => manifest.stderr = equals(body),
+                ["stderr", "contains"] => manifest.stderr = contains(body),
+                ["status"] => {
+                    manifest.status = body
+                        .trim()
+                        .parse()
+                        .map_err(|err| format!("invalid status {:?}: {err}", body.trim()))?
+                }
+                _ => return Err(format!("unknown section '--- {header}'")),
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+fn sections(src: &str) -> Result<Vec<(String, String)>, String> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+
+    for line in src.lines() {
+        if let Some(header) = line.strip_prefix("--- ") {
+            sections.push((header.trim().to_string(), String::new()));
+        } else if let Some((_, body)) = sections.last_mut() {
+            body.push_str(line);
+            body.push('\n');
+        } else if !line.trim().is_empty() && !line.starts_with('#') {
+            return Err(format!("expected a section header, found {line:?}"));
+        }
+    }
+
+    Ok(sections)
+}
+
+fn collect_programs(dir: &Path, programs: &mut Vec<PathBuf>) {
+    let mut entries = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("failed to read {}: {err}", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_programs(&path, programs);
+        } else if path.extension().is_some_and(|ext| ext == "lf") {
+            programs.push(path);
+        }
+    }
+}
+
+fn check_program(program: &Path) -> Result<(), String> {
+    let manifest_path = program.with_extension("expected");
+    let manifest_src = fs::read_to_string(&manifest_path)
+        .map_err(|err| format!("missing manifest {}: {err}", manifest_path.display()))?;
+    let manifest = Manifest::parse(&manifest_src)?;
+
+    let src = fs::read_to_string(program).map_err(|err| err.to_string())?;
+    let (stdout, stderr, status) =
+        run_program_with_status(&src, std::io::Cursor::new(manifest.stdin));
+
+    manifest.stdout.check("stdout", &stdout)?;
+    manifest.stderr.check("stderr", &stderr)?;
+
+    if status != manifest.status {
+        return Err(format!(
+            "expected exit status {}, got {status}",
+            manifest.status
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn conformance() {
+    let mut programs = Vec::new();
+    collect_programs(Path::new(SPEC_DIR), &mut programs);
+    assert!(!programs.is_empty(), "no spec programs found in {SPEC_DIR}");
+
+    let failures = programs
+        .iter()
+        .filter_map(|program| {
+            check_program(program).err().map(|err| {
+                let name = program.strip_prefix(SPEC_DIR).unwrap_or(program);
+                format!("spec {} failed:\n{err}", name.display())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} spec programs failed\n\n{}",
+        failures.len(),
+        programs.len(),
+        failures.join("\n\n")
+    );
+}
@@ -0,0 +1,72 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    spread_mixes_scalars_and_iterables,
+    indoc! {r#"
+        xs = [3, 9, 2];
+        print(max(*xs, 0));
+        print(min(0, *xs));
+        print(max(*xs, *(10..12), 4));
+        print(min(*"bca"));
+        print(all(true, *[1, "a"]), any(false, *[]));
+    "#},
+    equals(indoc! {r#"
+        9
+        0
+        11
+        a
+        true false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    spread_does_not_modify_the_spread_list,
+    indoc! {r#"
+        xs = [1, 2];
+        print(max(*xs, 5), xs);
+    "#},
+    equals("5 [1, 2]"),
+    empty()
+);
+
+eval_and_assert!(
+    variadic_forms_without_spread_are_unchanged,
+    indoc! {r#"
+        print(max([4, 8, 1]), max(4, 8, 1), max([1, 2], [3]));
+    "#},
+    equals("8 8 [3]"),
+    empty()
+);
+
+eval_and_assert!(
+    mixing_iterable_literal_with_scalars_is_rejected,
+    "print(max(0, [1, 2]))",
+    empty(),
+    contains("Ambiguous call to max: this iterable is treated as a single value alongside the other arguments")
+);
+
+eval_and_assert!(
+    comparing_scalar_with_iterable_at_runtime_fails,
+    indoc! {r#"
+        xs = [1, 2];
+        print(min(0, xs));
+    "#},
+    empty(),
+    contains("Cannot compare types 'number' and 'list' in min. To include the items of an iterable, spread it with `*`, e.g. min(x, *xs)")
+);
+
+eval_and_assert!(
+    spread_outside_variadic_call_is_rejected,
+    indoc! {r#"
+        fn f(a, b) a + b;
+        print(f(*[1, 2]));
+    "#},
+    empty(),
+    contains("Spread arguments can only be passed to all, any, max and min")
+);
@@ -4,6 +4,22 @@ use crate::helpers::{
 };
 
 use indoc::indoc;
+use linefeed::{
+    chumsky::Parser as _,
+    grammar::lexer::{lexer, Token},
+};
+
+// Returns the text of the single string literal in `src`
+fn lex_str(src: &str) -> String {
+    let tokens = lexer().parse(src).unwrap();
+    match tokens.as_slice() {
+        [token] => match &token.0 {
+            Token::Str(s) => s.clone(),
+            token => panic!("expected a string, got {token:?}"),
+        },
+        tokens => panic!("expected a single token, got {tokens:?}"),
+    }
+}
 
 eval_and_assert!(
     string_upper_and_lower,
@@ -104,6 +120,86 @@ eval_and_assert!(
     empty()
 );
 
+eval_and_assert!(
+    triple_quoted_string_spans_lines,
+    indoc! {r#"
+        text = """
+        first "quoted" line
+          indented\nsplit
+        """;
+        print(text);
+        print("""a""" + """b "c" d""");
+    "#},
+    equals(indoc! {r#"
+        first "quoted" line
+          indented
+        split
+
+        ab "c" d
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    empty_strings_are_not_triple_quoted,
+    indoc! {r#"
+        print("" + "x" + "");
+        print(["", ""]);
+    "#},
+    equals(indoc! {r#"
+        x
+        ["", ""]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    errors_after_multiline_strings_point_at_the_right_line,
+    indoc! {r#"
+        text = """
+        one
+        two
+        """;
+        print(text * 2);
+    "#},
+    empty(),
+    contains("<unknown>:5:7")
+);
+
+#[test]
+fn strings_unescape_tabs_backslashes_and_quotes() {
+    assert_eq!(lex_str(r#""a\tb\\c\"d\ne""#), "a\tb\\c\"d\ne");
+    assert_eq!(lex_str(r#""\d+\\""#), "\\d+\\");
+}
+
+#[test]
+fn triple_quoted_strings_unescape_like_other_strings() {
+    assert_eq!(lex_str(r#""""a\tb\\c\"d\ne""""#), "a\tb\\c\"d\ne");
+    assert_eq!(lex_str(r#""""\"""""#), "\"");
+}
+
+#[test]
+fn triple_quoted_strings_drop_a_leading_crlf() {
+    assert_eq!(lex_str("\"\"\"\r\none\r\ntwo\r\n\"\"\""), "one\r\ntwo\r\n");
+    assert_eq!(lex_str("\"\"\"\n\r\none\"\"\""), "\r\none");
+}
+
+#[test]
+fn triple_quoted_strings_can_end_with_quotes() {
+    assert_eq!(lex_str(r#""""say "hi"""""#), "say \"hi\"");
+    assert_eq!(lex_str(&"\"".repeat(8)), "\"\"");
+    assert_eq!(lex_str(&"\"".repeat(6)), "");
+}
+
+eval_and_assert!(
+    escapes_work_in_all_strings,
+    indoc! {r#"
+        print(len("a\tb"), "\\" + """"quoted\"""", len("\"\\"));
+    "#},
+    equals(r#"3 \"quoted" 2"#),
+    empty()
+);
+
 eval_and_assert!(
     string_join_works,
     indoc! {r#"
@@ -233,3 +329,172 @@ eval_and_assert!(
     equals("[]"),
     empty()
 );
+
+eval_and_assert!(
+    nums_leaves_out_sentence_ending_periods,
+    indoc! {r#"
+        print("There are 5. Or -.5?".nums());
+    "#},
+    equals("[5, -0.5]"),
+    empty()
+);
+
+eval_and_assert!(
+    nums_keeps_large_integers_exact,
+    indoc! {r#"
+        print("id: 123456789012345678901234567890".nums());
+    "#},
+    equals("[123456789012345678901234567890]"),
+    empty()
+);
+
+eval_and_assert!(
+    digits_of_strings_and_integers,
+    indoc! {r#"
+        print("12345\n".digits());
+        print(907.digits(), (-42).digits());
+    "#},
+    equals(indoc! {r#"
+        [1, 2, 3, 4, 5]
+        [9, 0, 7] [4, 2]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    digits_of_non_digit_string_error,
+    indoc! {r#"
+        print("12a".digits());
+    "#},
+    empty(),
+    contains("Cannot get digits of \"12a\", since 'a' is not a digit")
+);
+
+eval_and_assert!(
+    paragraphs_splits_on_blank_lines,
+    indoc! {r#"
+        print("a\nb\n\nc\n\n\nd e\n".paragraphs());
+    "#},
+    equals(r#"["a\nb", "c", "d e"]"#),
+    empty()
+);
+
+eval_and_assert!(
+    paragraphs_handles_crlf_and_trailing_whitespace,
+    indoc! {r#"
+        print(input().paragraphs());
+    "#},
+    "a  \r\nb\r\n \r\nc\r\n\r\n",
+    equals(r#"["a\nb", "c"]"#),
+    empty()
+);
+
+eval_and_assert!(
+    paragraphs_of_input_split_into_groups_of_lines,
+    indoc! {r#"
+        for group in [p.lines() for p in input().paragraphs()] {
+            print(group.len(), group);
+        };
+    "#},
+    "abc\n\na\nb\nc\n\nab\nac\n",
+    equals(indoc! {r#"
+        1 ["abc"]
+        3 ["a", "b", "c"]
+        2 ["ab", "ac"]
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    paragraphs_of_empty_string,
+    indoc! {r#"
+        print("".paragraphs());
+    "#},
+    equals("[]"),
+    empty()
+);
+
+eval_and_assert!(
+    ends_with_checks_suffix,
+    indoc! {r#"
+        text = "hello world";
+        print(text.ends_with("world"), text.ends_with(""), text.ends_with("hello"));
+    "#},
+    equals("true true false"),
+    empty()
+);
+
+eval_and_assert!(
+    index_of_finds_first_occurrence,
+    indoc! {r#"
+        text = "abcabc";
+        print(text.index_of("c"), text.index_of("bc"), text.index_of(""));
+        print(text.index_of("x"), text.index_of("x") ?? -1);
+    "#},
+    equals(indoc! {r#"
+        2 1 0
+        null -1
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    trim_removes_whitespace,
+    indoc! {r#"
+        text = "   padded \n";
+        print("[" + text.trim() + "]");
+        print("[" + text.trim_start() + "]");
+        print("[" + text.trim_end() + "]");
+    "#},
+    equals("[padded]\n[padded \n]\n[   padded]"),
+    empty()
+);
+
+eval_and_assert!(
+    replace_substitutes_every_occurrence,
+    indoc! {r#"
+        print("a-b-c".replace("-", ", "));
+        print("aaa".replace("aa", "b"));
+        print("abc".replace("x", "y"));
+    "#},
+    equals(indoc! {r#"
+        a, b, c
+        ba
+        abc
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    replace_expects_strings,
+    r#""abc".replace("a", 1);"#,
+    empty(),
+    contains("replace expects a string or regex and a replacement string, got 'str' and 'number'")
+);
+
+eval_and_assert!(
+    repeat_concatenates_copies,
+    indoc! {r#"
+        print("ab".repeat(3));
+        print("[" + "ab".repeat(0) + "]");
+    "#},
+    equals(indoc! {r#"
+        ababab
+        []
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    repeat_rejects_negative_counts,
+    r#""ab".repeat(-1);"#,
+    empty(),
+    contains("repeat expects a non-negative integer, got -1")
+);
+
+eval_and_assert!(
+    string_methods_reject_other_types,
+    "[1].trim();",
+    empty(),
+    contains("Cannot call method 'trim' on type 'list'")
+);
@@ -0,0 +1,89 @@
+use crate::helpers::{
+    eval_and_assert,
+    output::{empty, equals},
+};
+
+use indoc::indoc;
+
+eval_and_assert!(
+    symbols_compare_by_name,
+    indoc! {r#"
+        dir = :north;
+        print(dir == :north);
+        print(dir == :south);
+        print(dir != :south);
+        print(dir == "north");
+    "#},
+    equals(indoc! {r#"
+        true
+        false
+        true
+        false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    symbols_can_be_map_keys,
+    indoc! {r#"
+        steps = {:north: (0, -1), :south: (0, 1)};
+        print(steps[:south]);
+        print(:north in steps);
+        print(:east in steps);
+    "#},
+    equals(indoc! {r#"
+        (0, 1)
+        true
+        false
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    symbols_can_be_matched,
+    indoc! {r#"
+        fn turn(dir) match dir {
+            :north => :east,
+            :east => :south,
+            :south => :west,
+            :west => :north,
+        };
+
+        print(turn(turn(:north)));
+    "#},
+    equals(indoc! {r#"
+        south
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    symbols_are_printed_without_colon_unless_nested,
+    indoc! {r#"
+        print(:north);
+        print(str(:north) + "!");
+        print([:north, (:south, 1)]);
+        turns = {:north: :east};
+        print(turns);
+    "#},
+    equals(indoc! {r#"
+        north
+        north!
+        [:north, (:south, 1)]
+        {:north: :east}
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    symbols_are_sorted_by_name,
+    indoc! {r#"
+        print([:c, :a, :b].sort());
+        print(:apple < :banana);
+    "#},
+    equals(indoc! {r#"
+        [:a, :b, :c]
+        true
+    "#}),
+    empty()
+);
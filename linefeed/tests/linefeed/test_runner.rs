@@ -0,0 +1,90 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/linefeed/test_runner");
+
+fn run_fixtures(dir: &str) -> (String, i32) {
+    let mut out = Vec::new();
+    let status = linefeed::test_runner::run_tests(format!("{FIXTURES_DIR}/{dir}"), &mut out);
+    (String::from_utf8(out).unwrap(), status)
+}
+
+#[test]
+fn runs_tests_in_discovered_files() {
+    let (report, status) = run_fixtures("passing");
+
+    assert_eq!(status, linefeed::EXIT_SUCCESS, "{report}");
+    equals(indoc! {"
+        test math_test.lf: double ... ok
+        test math_test.lf: sum ... ok
+        test nested/strings_test.lf: upper ... ok
+
+        test result: ok. 3 passed; 0 failed
+    "})
+    .check("report", &report)
+    .unwrap_or_else(|err| panic!("{err}"));
+}
+
+#[test]
+fn reports_failing_assertions() {
+    let (report, status) = run_fixtures("failing");
+
+    assert_eq!(status, linefeed::EXIT_FAILURE, "{report}");
+    for expected in [
+        "test mixed_test.lf: passes ... ok",
+        "test mixed_test.lf: sees fresh state ... FAILED",
+        "---- mixed_test.lf: sees fresh state ----",
+        "before failure",
+        "Assertion failed: expected 11, got 10",
+        "assert_eq(counter * 10, 11)",
+        "test result: FAILED. 1 passed; 1 failed",
+    ] {
+        contains(expected)
+            .check("report", &report)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+}
+
+eval_and_assert!(
+    assert_eq_passes_on_equal_values,
+    indoc! {r#"
+        assert_eq(1 + 1, 2);
+        assert_eq([1, (2, "a")], [1, (2, "a")]);
+        print("done");
+    "#},
+    equals("done"),
+    empty()
+);
+
+eval_and_assert!(
+    assert_eq_fails_with_both_values,
+    r#"assert_eq("elf", "elves")"#,
+    empty(),
+    contains(r#"Assertion failed: expected "elves", got "elf""#)
+);
+
+eval_and_assert!(
+    test_blocks_are_skipped_in_regular_runs,
+    indoc! {r#"
+        test "not run" {
+          print("inside test");
+        };
+        print("outside test");
+    "#},
+    equals("outside test"),
+    empty()
+);
+
+eval_and_assert!(
+    test_is_still_a_valid_identifier,
+    indoc! {r#"
+        test = 5;
+        print(test);
+    "#},
+    equals("5"),
+    empty()
+);
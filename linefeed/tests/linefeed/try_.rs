@@ -0,0 +1,76 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    try_num_parses_integers_and_floats,
+    indoc! {r#"
+        print(try_num("12"), try_num(" 1.5 "), try_num("-3"), try_num(7));
+        print(try_num("x"), try_num(""), try_num([1]), try_num(null));
+    "#},
+    equals(indoc! {r#"
+        12 1.5 -3 7
+        null null null null
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    try_int_and_try_float_return_null_on_failure,
+    indoc! {r#"
+        print(try_int("7"), try_int(2.5, "round"), try_int("7.5"), try_int([]));
+        print(try_float("2.5"), try_float(1), try_float("abc"));
+    "#},
+    equals(indoc! {r#"
+        7 3 null null
+        2.5 1 null
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    try_int_still_fails_on_unknown_rounding_mode,
+    r#"print(try_int(1.5, "sideways"))"#,
+    empty(),
+    contains("Unknown rounding mode")
+);
+
+eval_and_assert!(
+    try_index_returns_null_when_out_of_bounds,
+    indoc! {r#"
+        xs = [1, 2, 3];
+        print(try_index(xs, 1), try_index(xs, 3), try_index("abc", 0), try_index(5, 0));
+    "#},
+    equals("2 null a null"),
+    empty()
+);
+
+eval_and_assert!(
+    try_get_looks_up_nested_keys,
+    indoc! {r#"
+        grid = [[1, 2], [3, 4]];
+        print(try_get(grid, 1, 0), try_get(grid, 2, 0), try_get(grid, 0, 2), try_get(grid, 0, 0, 0));
+
+        m = {"a": {"b": 1}};
+        print(try_get(m, "a", "b"), try_get(m, "x", "b"));
+    "#},
+    equals(indoc! {r#"
+        3 null null null
+        1 null
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    try_builtins_filter_out_unparsable_lines,
+    indoc! {r#"
+        nums = [try_num(line) for line in input().lines()];
+        print(sum([n for n in nums if n != null]));
+    "#},
+    "1\nfoo\n2.5\n\n3\n",
+    equals("6.5"),
+    empty()
+);
@@ -0,0 +1,104 @@
+use indoc::indoc;
+use linefeed::{chumsky::Parser as _, config::Config, type_check::check_types, CheckOptions};
+
+use crate::helpers::output::contains;
+
+// Returns the message and 1-indexed line of every type error reported for `src`
+fn type_errors(src: &str) -> Vec<(String, usize)> {
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+
+    check_types(&ast)
+        .into_iter()
+        .map(|e| (e.msg, src[..e.span.start].matches('\n').count() + 1))
+        .collect()
+}
+
+#[test]
+fn reports_operations_that_always_fail() {
+    let src = indoc! {r#"
+        print("a" - 1);
+        n = 5;
+        n();
+        s = "x" + 1;
+        print(-s);
+        print(~[1, 2]);
+        t = (1, 2) * 3;
+        print(t / 2);
+    "#};
+
+    assert_eq!(
+        type_errors(src),
+        vec![
+            ("Cannot subtract types 'str' and 'number'".to_string(), 1),
+            ("Cannot call type number as a function".to_string(), 3),
+            ("Cannot negate type 'str'".to_string(), 5),
+            ("Cannot use ~ on type 'list'".to_string(), 6),
+            ("Cannot divide types 'tuple' and 'number'".to_string(), 8),
+        ]
+    );
+}
+
+#[test]
+fn values_of_unknown_type_are_not_reported() {
+    let src = indoc! {r#"
+        x = 1;
+        x = "a";
+        print(x - 1);
+        f = fn(a) a - 1;
+        print(f(2) - "b");
+        for s in ["a"] { print(s - 1) };
+        len = 3;
+        print(len([1, 2]));
+        y = if input() { 1 } else { "b" };
+        print(y * 2);
+        z = 3;
+        z += 1;
+        print(z - 1, "a" + 1, (1, 2) + (3, 4));
+    "#};
+
+    assert_eq!(type_errors(src), vec![]);
+}
+
+#[test]
+fn types_flow_through_variables() {
+    let src = indoc! {r#"
+        a = b;
+        b = c + "!";
+        c = "hi";
+        print(a - 1);
+    "#};
+
+    assert_eq!(
+        type_errors(src),
+        vec![("Cannot subtract types 'str' and 'number'".to_string(), 4)]
+    );
+}
+
+#[test]
+fn check_fails_on_type_errors() {
+    let mut stderr = Vec::new();
+    let options = CheckOptions {
+        types: true,
+        ..Default::default()
+    };
+    let status = linefeed::check(
+        "x = true; print(x ^ 1)",
+        &Config::default(),
+        options,
+        &mut stderr,
+    );
+
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    contains("Cannot use ^ on types 'boolean' and 'number'")
+        .check("stderr", &String::from_utf8(stderr).unwrap())
+        .unwrap_or_else(|err| panic!("{err}"));
+
+    let status = linefeed::check(
+        "x = 1; print(x ^ 1)",
+        &Config::default(),
+        options,
+        &mut Vec::new(),
+    );
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+}
@@ -0,0 +1,56 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{empty, equals},
+};
+
+eval_and_assert!(
+    type_names_the_type_of_values,
+    indoc! {r#"
+        for x in [null, true, 1, 2.5, "a", [1], (1, 2), ("a", 1), {"k": 1}, set(), fn() 1, 0..2] {
+          print(type(x));
+        };
+    "#},
+    equals(indoc! {r#"
+        null
+        boolean
+        number
+        number
+        str
+        list
+        tuple
+        tuple
+        map
+        set
+        function
+        range
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    type_predicates_branch_on_shape,
+    indoc! {r#"
+        fn flatten(x) {
+          if is_list(x) or is_tuple(x) {
+            result = [];
+            for item in x {
+              result = result + flatten(item);
+            };
+            result
+          } else {
+            [x]
+          }
+        };
+        print(flatten([1, (2, [3, "four"]), [[5]]]));
+        print(is_num(3), is_num("3"), is_str("3"), is_bool(false), is_map({"k": 1}), is_set(set()));
+        print(is_function(flatten), is_function(1), is_list("ab"), is_tuple((1, 2, 3)));
+    "#},
+    equals(indoc! {r#"
+        [1, 2, 3, "four", 5]
+        true false true true true true
+        true false false true
+    "#}),
+    empty()
+);
@@ -0,0 +1,76 @@
+use indoc::indoc;
+
+use crate::helpers::{
+    eval_and_assert,
+    output::{contains, empty, equals},
+};
+
+eval_and_assert!(
+    vector_constructors_and_components,
+    indoc! {r#"
+        a = vec2(3, 4);
+        b = vec3(1, 2, 3);
+        print(a, b, a == (3, 4), b == (1, 2, 3));
+        print(a.x(), a.y(), b.x(), b.y(), b.z());
+        print(vec2(1.5, 2).x(), vec3(100000, 0, 0));
+    "#},
+    equals(indoc! {r#"
+        (3, 4) (1, 2, 3) true true
+        3 4 1 2 3
+        1.5 (100000, 0, 0)
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    vec3_arithmetic,
+    indoc! {r#"
+        a = (1, 2, 3);
+        b = (4, 5, 6);
+        print(a + b, b - a, a * 2, 3 * a, -a);
+        print(a[2], a[-3], 2 in a, list(a));
+        (x, y, z) = b;
+        print(x + y + z, manhattan(a, b));
+        print(a + (0.5, 0, 0), (30000, 0, 0) + (30000, 0, 0));
+    "#},
+    equals(indoc! {r#"
+        (5, 7, 9) (3, 3, 3) (2, 4, 6) (3, 6, 9) (-1, -2, -3)
+        3 1 true [1, 2, 3]
+        15 9
+        (1.5, 2, 3) (60000, 0, 0)
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    dot_and_cross_products,
+    indoc! {r#"
+        print((1, 2).dot((3, 4)), (1, 2, 3).dot((4, 5, 6)));
+        print((1, 0).cross((0, 1)), (1, 0, 0).cross((0, 1, 0)));
+        print((1, 2, 3).cross((4, 5, 6)));
+    "#},
+    equals(indoc! {r#"
+        11 32
+        1 (0, 0, 1)
+        (-3, 6, -3)
+    "#}),
+    empty()
+);
+
+eval_and_assert!(
+    dot_needs_same_length,
+    indoc! {r#"
+        (1, 2).dot((1, 2, 3));
+    "#},
+    empty(),
+    contains("Cannot take the dot product of vectors of different lengths: 2 and 3")
+);
+
+eval_and_assert!(
+    vec2_has_no_z,
+    indoc! {r#"
+        vec2(1, 2).z();
+    "#},
+    empty(),
+    contains("Index 2 out of bounds, length is 2")
+);
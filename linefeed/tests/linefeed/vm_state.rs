@@ -0,0 +1,53 @@
+#![cfg(feature = "vm-state")]
+
+use indoc::indoc;
+
+fn run_seeded(src: &str, seed: &str) -> (String, linefeed::vm::state::VmState) {
+    let seed_stack = linefeed::eval_seed_stack(seed).unwrap();
+    let mut stdout = Vec::new();
+    let (status, state) = linefeed::run_with_state(
+        src,
+        seed_stack,
+        std::io::empty(),
+        &mut stdout,
+        std::io::sink(),
+    );
+
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+    (String::from_utf8(stdout).unwrap(), state.unwrap())
+}
+
+#[test]
+fn seed_stack_initializes_globals_in_assignment_order() {
+    let (stdout, _) = run_seeded(
+        indoc! {"
+            a = a + 1;
+            b = [b, a];
+            print(a, b);
+        "},
+        r#"[41, "x"]"#,
+    );
+
+    assert_eq!(stdout.trim(), r#"42 ["x", 42]"#);
+}
+
+#[test]
+fn state_dump_is_reproducible() {
+    let src = indoc! {"
+        s = set([3, 1, 2]);
+        m = {(1, 2): 3, 4: [5]};
+        for i in 0..3 { s.add(i * 10) };
+    "};
+
+    let (_, first) = run_seeded(src, "[]");
+    let (_, second) = run_seeded(src, "[]");
+
+    assert_eq!(first.to_string(), second.to_string());
+    assert!(first.to_string().contains("[0] set: {0, 1, 2, 3, 10, 20}"));
+}
+
+#[test]
+fn seed_stack_must_be_a_list() {
+    let err = linefeed::eval_seed_stack("5").unwrap_err();
+    assert_eq!(err, "Seed stack must be a list, got number");
+}
@@ -0,0 +1,149 @@
+use std::io::{self, Write};
+
+use indoc::indoc;
+use linefeed::{
+    chumsky::Parser as _, compiler::Compiler, vm::runtime_value::number::RuntimeNumber,
+    wasm::compile_to_wasm,
+};
+use wasmi::{Caller, Engine, Extern, Linker, Module, Store};
+
+use crate::helpers::run_program;
+
+fn wasm(src: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    let status = linefeed::emit_wasm(src, &mut out, std::io::sink());
+    assert_eq!(status, linefeed::EXIT_SUCCESS);
+    out
+}
+
+fn validate(module: &[u8]) {
+    wasmparser::Validator::new()
+        .validate_all(module)
+        .unwrap_or_else(|err| panic!("invalid module: {err}"));
+}
+
+// What the host's imports wrote to stdout and stderr
+#[derive(Default)]
+struct Host {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl Host {
+    fn output(&mut self, fd: i32) -> &mut Vec<u8> {
+        if fd == 2 {
+            &mut self.stderr
+        } else {
+            &mut self.stdout
+        }
+    }
+}
+
+// Runs the `main` of a module, providing the imports the way the module expects, and returns
+// what it wrote to stdout and stderr
+fn run_wasm(module: &[u8]) -> (String, String) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, module).unwrap();
+    let mut store = Store::new(&engine, Host::default());
+    let mut linker = Linker::<Host>::new(&engine);
+
+    linker
+        .func_wrap(
+            "env",
+            "write",
+            |mut caller: Caller<'_, Host>, fd: i32, ptr: i32, len: i32| {
+                let memory = caller
+                    .get_export("memory")
+                    .and_then(Extern::into_memory)
+                    .unwrap();
+                let mut bytes = vec![0; len as usize];
+                memory.read(&caller, ptr as usize, &mut bytes).unwrap();
+                caller.data_mut().output(fd).extend(bytes);
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "env",
+            "write_int",
+            |mut caller: Caller<'_, Host>, fd: i32, n: i64| {
+                write!(caller.data_mut().output(fd), "{n}").unwrap();
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "env",
+            "write_float",
+            |mut caller: Caller<'_, Host>, fd: i32, x: f64| {
+                let x = RuntimeNumber::Float(x);
+                write!(caller.data_mut().output(fd), "{x}").unwrap();
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap("env", "pow", |x: f64, y: f64| x.powf(y))
+        .unwrap();
+
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    main.call(&mut store, ()).unwrap();
+
+    let host = store.into_data();
+    (
+        String::from_utf8(host.stdout).unwrap(),
+        String::from_utf8(host.stderr).unwrap(),
+    )
+}
+
+// Checks that the module compiled from `src` prints the same as the VM running it
+fn assert_same_output(src: &str, module: &[u8]) {
+    validate(module);
+    assert_eq!(run_wasm(module), run_program(src, io::empty()));
+}
+
+#[test]
+fn numeric_programs_run_like_in_the_vm() {
+    let src = indoc! {r#"
+        fn fib(n) if n < 2 { n } else { fib(n - 1) + fib(n - 2) };
+        total = 0;
+        for i in 0..20 {
+          if i % 3 == 0 or i ** 2 > 50 { total += fib(i) } else { total -= i / 2 }
+        };
+        eprint("total:", total, -1.5, null);
+    "#};
+    let module = wasm(src);
+
+    assert_eq!(&module[..8], b"\0asm\x01\0\0\0");
+    assert_same_output(src, &module);
+}
+
+#[test]
+fn functions_with_default_arguments_and_registers_run_like_in_the_vm() {
+    let src = "fn f(a, b = 2) a * b; while true { print(f(3), f(3, 4)); break }";
+    let tokens = linefeed::grammar::lexer::lexer().parse(src).unwrap();
+    let ast = linefeed::parse_tokens(src, &tokens).unwrap();
+    let program = Compiler::default().compile_ir(&ast).unwrap();
+
+    assert_same_output(src, &compile_to_wasm(&program).unwrap());
+}
+
+#[test]
+fn unsupported_values_are_reported() {
+    let mut stderr = Vec::new();
+    let status = linefeed::emit_wasm("x = {1: 2}; print(x)", std::io::sink(), &mut stderr);
+
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(String::from_utf8_lossy(&stderr).contains("can't be compiled to WebAssembly"));
+
+    let mut stderr = Vec::new();
+    let status = linefeed::emit_wasm("print(len(\"abc\"))", std::io::sink(), &mut stderr);
+
+    assert_eq!(status, linefeed::EXIT_FAILURE);
+    assert!(String::from_utf8_lossy(&stderr)
+        .contains("The function len can't be compiled to WebAssembly"));
+}